@@ -0,0 +1,33 @@
+-- -*- mode: sql; sql-product: postgres; -*-
+-- Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+--
+-- This program is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU Affero General Public License as
+-- published by the Free Software Foundation, either version 3 of the
+-- License, or (at your option) any later version.
+--
+-- This program is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY; without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU Affero General Public License for more details.
+--
+-- You should have received a copy of the GNU Affero General Public License
+-- along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+-- brokers registered with the cluster
+begin;
+
+create table if not exists broker (
+    id int generated always as identity primary key,
+    cluster int references cluster (id) not null,
+    node int not null,
+    unique (cluster, node),
+    host text not null,
+    port int not null,
+    rack text,
+    incarnation uuid not null,
+    last_updated timestamp default current_timestamp not null,
+    created_at timestamp default current_timestamp not null
+);
+
+commit;
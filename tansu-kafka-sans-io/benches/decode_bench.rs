@@ -13,8 +13,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use criterion::{Criterion, black_box, criterion_group, criterion_main};
-use tansu_kafka_sans_io::Frame;
+use bytes::Bytes;
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
+use tansu_kafka_sans_io::{
+    Body, Frame, Header,
+    produce_request::{PartitionProduceData, TopicProduceData},
+    record::{Record, deflated, inflated},
+};
 
 fn api_versions_request_v3_000(c: &mut Criterion) {
     _ = c.bench_function("api_versions_request_v3_000", |b| {
@@ -28,5 +33,89 @@ fn api_versions_request_v3_000(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, api_versions_request_v3_000);
+const RECORDS: usize = 10_000;
+
+fn batch_of_small_records() -> deflated::Batch {
+    (0..RECORDS)
+        .try_fold(inflated::Batch::builder(), |builder, offset_delta| {
+            i32::try_from(offset_delta).map(|offset_delta| {
+                builder.last_offset_delta(offset_delta).record(
+                    Record::builder()
+                        .offset_delta(offset_delta)
+                        .key(Bytes::from(format!("k{offset_delta}")).into())
+                        .value(Bytes::from(format!("value-{offset_delta:0>10}")).into()),
+                )
+            })
+        })
+        .map_err(Into::into)
+        .and_then(|builder| builder.build())
+        .and_then(deflated::Batch::try_from)
+        .expect("batch")
+}
+
+fn inflate_small_records(c: &mut Criterion) {
+    let deflated = batch_of_small_records();
+
+    let mut group = c.benchmark_group("inflate");
+    _ = group.throughput(Throughput::Elements(RECORDS as u64));
+    _ = group.bench_function("small_records_10k", |b| {
+        b.iter(|| Vec::<Record>::try_from(black_box(&deflated)))
+    });
+    group.finish();
+}
+
+fn produce_request_small_records(c: &mut Criterion) {
+    let encoded = Frame::request(
+        Header::Request {
+            api_key: 0,
+            api_version: 9,
+            correlation_id: 1,
+            client_id: Some("bench".into()),
+        },
+        Body::ProduceRequest {
+            transactional_id: None,
+            acks: -1,
+            timeout_ms: 1_500,
+            topic_data: Some(vec![TopicProduceData {
+                name: "bench".into(),
+                partition_data: Some(vec![PartitionProduceData {
+                    index: 0,
+                    records: Some(deflated::Frame {
+                        batches: vec![batch_of_small_records()],
+                    }),
+                }]),
+            }]),
+        },
+    )
+    .expect("produce request");
+
+    let mut group = c.benchmark_group("produce");
+    _ = group.throughput(Throughput::Elements(RECORDS as u64));
+    _ = group.bench_function("small_records_10k", |b| {
+        b.iter(|| {
+            Frame::request_from_bytes(black_box(&encoded[..])).and_then(|frame| match frame.body {
+                Body::ProduceRequest {
+                    topic_data: Some(topic_data),
+                    ..
+                } => topic_data
+                    .into_iter()
+                    .flat_map(|topic| topic.partition_data.unwrap_or_default())
+                    .flat_map(|partition| partition.records)
+                    .flat_map(|records| records.batches)
+                    .try_fold(0, |count, batch| {
+                        Vec::<Record>::try_from(batch).map(|records| count + records.len())
+                    }),
+                _ => Ok(0),
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    api_versions_request_v3_000,
+    inflate_small_records,
+    produce_request_small_records
+);
 criterion_main!(benches);
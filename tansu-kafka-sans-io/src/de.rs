@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{Error, Result, RootMessageMeta};
+use crate::{API_VERSIONS_API_KEY, Error, Result, RootMessageMeta};
 use serde::{
     Deserializer,
     de::{DeserializeSeed, EnumAccess, SeqAccess, VariantAccess, Visitor},
@@ -209,7 +209,9 @@ impl<'de> Decoder<'de> {
             && ((self.kind.is_some_and(|kind| kind == Kind::Request)
                 && self.field.is_some_and(|field| field == "client_id"))
                 || (self.kind.is_some_and(|kind| kind == Kind::Response)
-                    && self.api_key.is_some_and(|api_key| api_key == 18)))
+                    && self
+                        .api_key
+                        .is_some_and(|api_key| api_key == API_VERSIONS_API_KEY)))
         {
             false
         } else {
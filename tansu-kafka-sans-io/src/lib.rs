@@ -14,6 +14,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 pub mod de;
+pub mod partitioner;
 pub mod primitive;
 pub mod record;
 pub mod ser;
@@ -34,7 +35,7 @@ use std::{
     sync::OnceLock,
     time::{Duration, SystemTime, SystemTimeError},
 };
-use tansu_kafka_model::{MessageKind, MessageMeta};
+use tansu_kafka_model::{FieldMeta, MessageKind, MessageMeta};
 use tracing::{debug, error, warn};
 use tracing_subscriber::filter::ParseError;
 
@@ -90,16 +91,20 @@ pub enum Error {
     ApiError(ErrorCode),
     EnvVar(VarError),
     FromUtf8(string::FromUtf8Error),
+    InflatedSizeExceeded(u64),
     InvalidAckValue(i16),
+    InvalidCompressionLevel(Compression, i32),
     InvalidCoordinatorType(i8),
     InvalidIsolationLevel(i8),
     InvalidOpType(i8),
+    InvalidPartitionCount(i32),
     Io(io::Error),
     Message(String),
     NoSuchField(&'static str),
     NoSuchMessage(&'static str),
     NoSuchRequest(i16),
     ParseFilter(#[from] ParseError),
+    RecordCountExceeded(u32),
     Snap(#[from] snap::Error),
     StringWithoutApiVersion,
     StringWithoutLength,
@@ -114,6 +119,15 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// ApiVersions responses always have a version 0 header, so that a client
+/// can parse the response to a request in a version that is unsupported
+pub const API_VERSIONS_API_KEY: i16 = 18;
+
+pub const PRODUCE_API_KEY: i16 = 0;
+pub const LIST_OFFSETS_API_KEY: i16 = 2;
+pub const METADATA_API_KEY: i16 = 3;
+pub const OFFSET_COMMIT_API_KEY: i16 = 8;
+
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
@@ -239,6 +253,101 @@ impl Frame {
     }
 }
 
+impl Body {
+    pub fn error_response(api_key: i16, api_version: i16, error_code: ErrorCode) -> Result<Self> {
+        fn encode(
+            fields: &[(&str, &FieldMeta)],
+            api_version: i16,
+            flexible: bool,
+            error_code: i16,
+            encoded: &mut BytesMut,
+        ) {
+            for (name, field) in fields {
+                if !field.version.within(api_version)
+                    || field
+                        .tagged
+                        .is_some_and(|tagged| tagged.within(api_version))
+                {
+                    continue;
+                }
+
+                match field.kind.name() {
+                    "bool" | "int8" => encoded.put_i8(0),
+                    "int16" if *name == "error_code" => encoded.put_i16(error_code),
+                    "int16" | "uint16" => encoded.put_i16(0),
+                    "int32" | "uint32" => encoded.put_i32(0),
+                    "int64" | "float64" => encoded.put_i64(0),
+                    "uuid" => encoded.put_bytes(0, 16),
+
+                    "string" if flexible => encoded.put_u8(1),
+                    "string" => encoded.put_i16(0),
+
+                    "bytes" | "records" if field.is_nullable(api_version) => {
+                        if flexible {
+                            encoded.put_u8(0)
+                        } else {
+                            encoded.put_i32(-1)
+                        }
+                    }
+
+                    "bytes" | "records" if flexible => encoded.put_u8(1),
+                    "bytes" | "records" => encoded.put_i32(0),
+
+                    sequence if sequence.starts_with("[]") => {
+                        if flexible {
+                            encoded.put_u8(1)
+                        } else {
+                            encoded.put_i32(0)
+                        }
+                    }
+
+                    _structure if field.is_nullable(api_version) => encoded.put_i8(-1),
+
+                    _structure => {
+                        encode(field.fields, api_version, flexible, error_code, encoded);
+
+                        if flexible {
+                            encoded.put_u8(0);
+                        }
+                    }
+                }
+            }
+        }
+
+        let meta = RootMessageMeta::messages()
+            .responses()
+            .get(&api_key)
+            .ok_or(Error::NoSuchRequest(api_key))?;
+
+        let flexible = meta.is_flexible(api_version);
+
+        let mut encoded = BytesMut::new();
+        encoded.put_i32(0);
+        encoded.put_i32(0);
+
+        if flexible && api_key != API_VERSIONS_API_KEY {
+            encoded.put_u8(0);
+        }
+
+        encode(
+            meta.fields,
+            api_version,
+            flexible,
+            error_code.into(),
+            &mut encoded,
+        );
+
+        if flexible {
+            encoded.put_u8(0);
+        }
+
+        let size = i32::try_from(encoded.len() - 4)?;
+        encoded[0..4].copy_from_slice(&size.to_be_bytes());
+
+        Frame::response_from_bytes(&encoded, api_key, api_version).map(|frame| frame.body)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, PartialOrd, Serialize)]
 #[serde(try_from = "HeaderMezzanine")]
 #[serde(into = "HeaderMezzanine")]
@@ -1106,7 +1215,9 @@ pub enum ErrorCode {
     InvalidRegistration,
 }
 
-#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
 pub enum IsolationLevel {
     #[default]
     ReadUncommitted,
@@ -1711,6 +1822,56 @@ mod tests {
             i16::from(BatchAttribute::default().delete_horizon(true))
         );
     }
+
+    #[test]
+    fn named_api_keys() {
+        let requests = RootMessageMeta::messages().requests();
+
+        for (name, api_key) in [
+            ("ProduceRequest", PRODUCE_API_KEY),
+            ("ListOffsetsRequest", LIST_OFFSETS_API_KEY),
+            ("MetadataRequest", METADATA_API_KEY),
+            ("OffsetCommitRequest", OFFSET_COMMIT_API_KEY),
+            ("ApiVersionsRequest", API_VERSIONS_API_KEY),
+        ] {
+            assert_eq!(Some(name), requests.get(&api_key).map(|meta| meta.name));
+        }
+    }
+
+    #[test]
+    fn error_response() -> Result<()> {
+        for (api_key, meta) in RootMessageMeta::messages().responses() {
+            for api_version in meta.version.valid.start..=meta.version.valid.end {
+                let body =
+                    Body::error_response(*api_key, api_version, ErrorCode::UnsupportedVersion)
+                        .inspect_err(|err| error!(?err, name = meta.name, api_version))?;
+
+                let encoded = Frame::response(
+                    Header::Response { correlation_id: 0 },
+                    body.clone(),
+                    *api_key,
+                    api_version,
+                )?;
+
+                assert_eq!(
+                    body,
+                    Frame::response_from_bytes(&encoded, *api_key, api_version)?.body,
+                    "{} v{api_version}",
+                    meta.name
+                );
+            }
+        }
+
+        let Body::SaslHandshakeResponse { error_code, .. } =
+            Body::error_response(17, 1, ErrorCode::UnsupportedVersion)?
+        else {
+            panic!("sasl handshake response");
+        };
+
+        assert_eq!(i16::from(ErrorCode::UnsupportedVersion), error_code);
+
+        Ok(())
+    }
 }
 
 include!(concat!(env!("OUT_DIR"), "/generate.rs"));
@@ -0,0 +1,197 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{Error, Result};
+use std::hash::{BuildHasher, RandomState};
+
+const SEED: u32 = 0x9747_b28c;
+const M: u32 = 0x5bd1_e995;
+const R: u32 = 24;
+
+// the murmur2 hash used by the Java producer, over the key of a record
+pub fn murmur2(data: &[u8]) -> i32 {
+    let mut h = SEED ^ data.len() as u32;
+
+    let mut chunks = data.chunks_exact(4);
+
+    for chunk in chunks.by_ref() {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+
+    let remainder = chunks.remainder();
+
+    if !remainder.is_empty() {
+        for (i, byte) in remainder.iter().enumerate() {
+            h ^= u32::from(*byte) << (8 * i);
+        }
+
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+
+    h as i32
+}
+
+fn to_positive(n: i32) -> i32 {
+    n & 0x7fff_ffff
+}
+
+fn validate(partitions: i32) -> Result<i32> {
+    if partitions > 0 {
+        Ok(partitions)
+    } else {
+        Err(Error::InvalidPartitionCount(partitions))
+    }
+}
+
+// the partition of a keyed record, as chosen by the Java default partitioner
+pub fn partition(key: &[u8], partitions: i32) -> Result<i32> {
+    validate(partitions).map(|partitions| to_positive(murmur2(key)) % partitions)
+}
+
+// keyed records are hashed with murmur2, while records without a key
+// stick to a partition until a batch of bytes has been produced, then
+// move to another partition
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Sticky {
+    batch_size: usize,
+    current: Option<i32>,
+    produced: usize,
+}
+
+impl Sticky {
+    pub fn new(batch_size: usize) -> Self {
+        Self {
+            batch_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn partition(&mut self, key: Option<&[u8]>, size: usize, partitions: i32) -> Result<i32> {
+        if let Some(key) = key {
+            return partition(key, partitions);
+        }
+
+        let partitions = validate(partitions)?;
+
+        let current = match self.current {
+            Some(current) if current < partitions && self.produced < self.batch_size => current,
+
+            previous => {
+                self.produced = 0;
+                Self::next(previous, partitions)
+            }
+        };
+
+        self.current = Some(current);
+        self.produced += size;
+
+        Ok(current)
+    }
+
+    // a random partition that differs from the previous, when there is a choice
+    fn next(previous: Option<i32>, partitions: i32) -> i32 {
+        let random = RandomState::new().hash_one(previous);
+
+        match previous {
+            Some(previous) if partitions > 1 && previous < partitions => {
+                let offset = (random % (partitions as u64 - 1)) as i32;
+                (previous + 1 + offset) % partitions
+            }
+
+            _ => (random % partitions as u64) as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // expected hashes from the Java client's Utils.murmur2
+    #[test]
+    fn murmur2_reference() {
+        for (key, expected) in [
+            (&b"21"[..], -973932308),
+            (b"foobar", -790332482),
+            (b"a-little-bit-long-string", -985981536),
+            (b"a-little-bit-longer-string", -1486304829),
+            (
+                b"lkjh234lh9fiuh90y23oiuhsafujhadof229phr9h19h89h8",
+                -58897971,
+            ),
+            (b"abc", 479470107),
+        ] {
+            assert_eq!(expected, murmur2(key), "{}", String::from_utf8_lossy(key));
+        }
+    }
+
+    #[test]
+    fn partition_reference() -> Result<()> {
+        for (key, partitions, expected) in [
+            (&b"21"[..], 6, 0),
+            (b"foobar", 12, 6),
+            (b"a-little-bit-long-string", 6, 2),
+            (b"a-little-bit-longer-string", 12, 11),
+            (b"lkjh234lh9fiuh90y23oiuhsafujhadof229phr9h19h89h8", 6, 5),
+            (b"abc", 6, 3),
+            (b"", 12, 9),
+        ] {
+            assert_eq!(expected, partition(key, partitions)?);
+        }
+
+        assert!(matches!(
+            partition(b"abc", 0),
+            Err(Error::InvalidPartitionCount(0))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn sticky() -> Result<()> {
+        let partitions = 6;
+        let mut sticky = Sticky::new(100);
+
+        let first = sticky.partition(None, 60, partitions)?;
+        assert_eq!(first, sticky.partition(None, 60, partitions)?);
+
+        // keyed records are unaffected by the sticky partition
+        assert_eq!(3, sticky.partition(Some(b"abc"), 60, partitions)?);
+
+        // the batch is full, so the next record moves to another partition
+        let second = sticky.partition(None, 60, partitions)?;
+        assert_ne!(first, second);
+        assert!((0..partitions).contains(&second));
+        assert_eq!(second, sticky.partition(None, 60, partitions)?);
+
+        // a single partition is always chosen
+        let mut sticky = Sticky::new(1);
+        for _ in 0..10 {
+            assert_eq!(0, sticky.partition(None, 10, 1)?);
+        }
+
+        Ok(())
+    }
+}
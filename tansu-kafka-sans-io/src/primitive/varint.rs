@@ -15,17 +15,47 @@
 
 use super::ByteSize;
 use crate::{Error, Result};
+use bytes::Buf;
 use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
     de::{self, SeqAccess, Visitor},
     ser::SerializeSeq,
 };
-use std::{any::type_name_of_val, fmt::Formatter, ops::Deref};
+use std::{
+    any::type_name_of_val,
+    fmt::Formatter,
+    io::{self, ErrorKind},
+    ops::Deref,
+};
 use tracing::debug;
 
 const CONTINUATION: u8 = 0b1000_0000;
 const MASK: u8 = 0b0111_1111;
 
+fn decode_unsigned(encoded: &mut impl Buf, max_shift: u8) -> Result<u64> {
+    let mut shift = 0u8;
+    let mut accumulator = 0u64;
+
+    loop {
+        if !encoded.has_remaining() {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+
+        let byte = encoded.get_u8();
+        accumulator |= u64::from(byte & MASK) << shift;
+
+        if byte & CONTINUATION != CONTINUATION {
+            return Ok(accumulator);
+        }
+
+        shift += 7;
+
+        if shift > max_shift {
+            return Err(Error::Message(String::from("varint too long")));
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct VarInt(pub i32);
 
@@ -68,6 +98,12 @@ impl VarInt {
         s.end()
     }
 
+    pub(crate) fn decode(encoded: &mut impl Buf) -> Result<i32> {
+        decode_unsigned(encoded, 28)
+            .and_then(|decoded| u32::try_from(decoded).map_err(Into::into))
+            .map(Self::de_zigzag)
+    }
+
     pub fn deserialize<'de, D>(deserializer: D) -> Result<i32, D::Error>
     where
         D: Deserializer<'de>,
@@ -208,6 +244,10 @@ impl LongVarInt {
         s.end()
     }
 
+    pub(crate) fn decode(encoded: &mut impl Buf) -> Result<i64> {
+        decode_unsigned(encoded, 63).map(Self::de_zigzag)
+    }
+
     pub fn deserialize<'de, D>(deserializer: D) -> Result<i64, D::Error>
     where
         D: Deserializer<'de>,
@@ -19,13 +19,13 @@ pub mod header;
 pub mod inflated;
 
 use crate::{
-    Result,
+    Error, Result,
     primitive::{
         ByteSize,
         varint::{LongVarInt, VarInt},
     },
 };
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 use codec::{Octets, VarIntSequence};
 pub use header::Header;
 use serde::{
@@ -68,6 +68,41 @@ impl Record {
         Builder::default()
     }
 
+    pub(crate) fn decode(encoded: &mut Bytes) -> Result<Self> {
+        let length = VarInt::decode(encoded)?;
+        let size = usize::try_from(length)?;
+
+        if size == 0 || encoded.remaining() < size {
+            return Err(Error::Message(format!("record length: {length}")));
+        }
+
+        let mut encoded = encoded.split_to(size);
+
+        let attributes = encoded.get_u8();
+        let timestamp_delta = LongVarInt::decode(&mut encoded)?;
+        let offset_delta = VarInt::decode(&mut encoded)?;
+        let key = Octets::decode(&mut encoded)?;
+        let value = Octets::decode(&mut encoded)?;
+
+        let headers = VarInt::decode(&mut encoded)
+            .and_then(|count| usize::try_from(count).map_err(Into::into))
+            .and_then(|count| {
+                (0..count)
+                    .map(|_| Header::decode(&mut encoded))
+                    .collect::<Result<Vec<_>>>()
+            })?;
+
+        Ok(Self {
+            length,
+            attributes,
+            timestamp_delta,
+            offset_delta,
+            key,
+            value,
+            headers,
+        })
+    }
+
     pub fn key(&self) -> Option<Bytes> {
         self.key.clone()
     }
@@ -79,6 +114,27 @@ impl Record {
     pub fn is_tombstone(&self) -> bool {
         self.key.is_some() && self.value.is_none()
     }
+
+    pub fn offset(&self, base_offset: i64) -> i64 {
+        base_offset + i64::from(self.offset_delta)
+    }
+
+    pub fn timestamp(&self, base_timestamp: i64) -> i64 {
+        base_timestamp + self.timestamp_delta
+    }
+
+    pub fn absolute(&self, base_offset: i64, base_timestamp: i64) -> Absolute {
+        Absolute {
+            offset: self.offset(base_offset),
+            timestamp: self.timestamp(base_timestamp),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Absolute {
+    pub offset: i64,
+    pub timestamp: i64,
 }
 
 impl TryFrom<Builder> for Record {
@@ -260,6 +316,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn absolute_offset_and_timestamp() -> Result<()> {
+        let base_offset = 1_000;
+        let base_timestamp = 1_707_058_170_165;
+
+        let records = [(0, 0), (1, 5), (2, 12)]
+            .into_iter()
+            .map(|(offset_delta, timestamp_delta)| {
+                Record::builder()
+                    .offset_delta(offset_delta)
+                    .timestamp_delta(timestamp_delta)
+                    .value(vec![100, 101, 102].into())
+                    .build()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            vec![
+                Absolute {
+                    offset: 1_000,
+                    timestamp: 1_707_058_170_165
+                },
+                Absolute {
+                    offset: 1_001,
+                    timestamp: 1_707_058_170_170
+                },
+                Absolute {
+                    offset: 1_002,
+                    timestamp: 1_707_058_170_177
+                },
+            ],
+            records
+                .iter()
+                .map(|record| record.absolute(base_offset, base_timestamp))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(1_002, records[2].offset(base_offset));
+        assert_eq!(1_707_058_170_177, records[2].timestamp(base_timestamp));
+
+        Ok(())
+    }
+
     #[test]
     fn crc_check() {
         use crc::CRC_32_ISCSI;
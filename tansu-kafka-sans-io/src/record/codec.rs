@@ -20,7 +20,7 @@ use crate::{
         varint::{UnsignedVarInt, VarInt},
     },
 };
-use bytes::{BufMut, Bytes, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
     de::{self, SeqAccess, Visitor},
@@ -28,6 +28,7 @@ use serde::{
 };
 use std::{
     fmt::{self, Formatter},
+    io::{self, ErrorKind},
     marker::PhantomData,
 };
 use tracing::debug;
@@ -78,6 +79,22 @@ impl Octets {
         }
     }
 
+    pub(crate) fn decode(encoded: &mut Bytes) -> Result<Option<Bytes>> {
+        let length = VarInt::decode(encoded)?;
+
+        if length == -1 {
+            Ok(None)
+        } else {
+            let length = usize::try_from(length)?;
+
+            if encoded.remaining() < length {
+                Err(io::Error::from(ErrorKind::UnexpectedEof).into())
+            } else {
+                Ok(Some(encoded.split_to(length)))
+            }
+        }
+    }
+
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Bytes>, D::Error>
     where
         D: Deserializer<'de>,
@@ -13,10 +13,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::fmt::Formatter;
+use std::{
+    fmt::Formatter,
+    io::{Cursor, Read},
+    num::NonZeroU32,
+    ops::RangeInclusive,
+};
 
 use bytes::{Buf, BufMut, Bytes, BytesMut};
-use crc::{CRC_32_ISCSI, Crc, Digest};
+use crc::{CRC_32_ISCSI, CRC_32_ISO_HDLC, Crc, Digest};
 use flate2::write::GzEncoder;
 use serde::{
     Deserialize, Deserializer, Serialize,
@@ -24,13 +29,54 @@ use serde::{
 };
 use tracing::debug;
 
-use crate::{Compression, Decoder, Encoder, Error, Result, record::Record};
+use crate::{
+    BatchAttribute, Compression, Decoder, Encoder, Error, ErrorCode, Result, TimestampType,
+    record::Record,
+};
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "MessageSet")]
 pub struct Frame {
     pub batches: Vec<Batch>,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(rename = "Frame")]
+struct MessageSet {
+    batches: Vec<Entry>,
+}
+
+impl TryFrom<MessageSet> for Frame {
+    type Error = Error;
+
+    fn try_from(message_set: MessageSet) -> Result<Self, Self::Error> {
+        let mut batches = vec![];
+        let mut legacy: Option<Vec<LegacyMessage>> = None;
+
+        for entry in message_set.batches {
+            match entry {
+                Entry::Batch(batch) => {
+                    if let Some(messages) = legacy.take() {
+                        batches.push(up_convert(&messages)?);
+                    }
+
+                    batches.push(batch);
+                }
+
+                Entry::Legacy(messages) => {
+                    legacy.get_or_insert_with(Vec::new).extend(messages);
+                }
+            }
+        }
+
+        if let Some(messages) = legacy {
+            batches.push(up_convert(&messages)?);
+        }
+
+        Ok(Self { batches })
+    }
+}
+
 impl TryFrom<crate::record::inflated::Frame> for Frame {
     type Error = Error;
 
@@ -81,6 +127,104 @@ impl Batch {
     pub fn is_idempotent(&self) -> bool {
         self.producer_id != -1 && self.base_sequence != -1
     }
+
+    pub fn recompute_crc(&self) -> Result<u32> {
+        CrcData::from(self).crc()
+    }
+
+    pub fn verify_crc(&self) -> bool {
+        self.recompute_crc().is_ok_and(|crc| crc == self.crc)
+    }
+}
+
+/// Kafka's default fetch.max.bytes, a batch inflating beyond which could
+/// not be returned to a consumer in a single fetch
+pub const DEFAULT_MAX_INFLATED_BYTES: u64 = 52_428_800;
+
+/// The most records that fit within the default inflated size, with each
+/// record occupying at least 7 bytes
+pub const DEFAULT_MAX_RECORD_COUNT: u32 = (DEFAULT_MAX_INFLATED_BYTES / 7) as u32;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Limit {
+    max_inflated_bytes: Option<u64>,
+    max_record_count: Option<u32>,
+}
+
+impl Limit {
+    /// The limits applied to produced batches unless configured otherwise
+    pub fn produce_defaults() -> Self {
+        Self {
+            max_inflated_bytes: Some(DEFAULT_MAX_INFLATED_BYTES),
+            max_record_count: Some(DEFAULT_MAX_RECORD_COUNT),
+        }
+    }
+
+    pub fn max_inflated_bytes(self, max_inflated_bytes: Option<u64>) -> Self {
+        Self {
+            max_inflated_bytes,
+            ..self
+        }
+    }
+
+    pub fn max_record_count(self, max_record_count: Option<u32>) -> Self {
+        Self {
+            max_record_count,
+            ..self
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_inflated_bytes.is_none() && self.max_record_count.is_none()
+    }
+}
+
+// the level that each codec compresses with, using the default level of
+// the codec when absent
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CompressionLevel {
+    gzip: Option<u32>,
+    lz4: Option<u32>,
+    zstd: Option<i32>,
+}
+
+impl CompressionLevel {
+    const GZIP: RangeInclusive<i32> = 1..=9;
+    const LZ4: RangeInclusive<i32> = 1..=17;
+    const ZSTD: RangeInclusive<i32> = -131_072..=22;
+
+    // the level for a codec, where -1 is the default gzip level
+    pub fn level(self, compression: Compression, level: i32) -> Result<Self> {
+        match compression {
+            Compression::Gzip if level == -1 => Ok(Self { gzip: None, ..self }),
+
+            Compression::Gzip if Self::GZIP.contains(&level) => Ok(Self {
+                gzip: Some(u32::try_from(level)?),
+                ..self
+            }),
+
+            Compression::Lz4 if Self::LZ4.contains(&level) => Ok(Self {
+                lz4: Some(u32::try_from(level)?),
+                ..self
+            }),
+
+            Compression::Zstd if Self::ZSTD.contains(&level) => Ok(Self {
+                zstd: Some(level),
+                ..self
+            }),
+
+            compression => Err(Error::InvalidCompressionLevel(compression, level)),
+        }
+    }
+
+    // levels that are absent are taken from the fallback
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            gzip: self.gzip.or(fallback.gzip),
+            lz4: self.lz4.or(fallback.lz4),
+            zstd: self.zstd.or(fallback.zstd),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
@@ -96,6 +240,22 @@ pub struct CrcData {
     pub record_data: Bytes,
 }
 
+impl From<&Batch> for CrcData {
+    fn from(batch: &Batch) -> Self {
+        Self {
+            attributes: batch.attributes,
+            last_offset_delta: batch.last_offset_delta,
+            base_timestamp: batch.base_timestamp,
+            max_timestamp: batch.max_timestamp,
+            producer_id: batch.producer_id,
+            producer_epoch: batch.producer_epoch,
+            base_sequence: batch.base_sequence,
+            record_count: batch.record_count,
+            record_data: batch.record_data.clone(),
+        }
+    }
+}
+
 impl CrcData {
     fn into_batch(self, base_offset: i64, partition_leader_epoch: i32, magic: i8) -> Result<Batch> {
         let crc = self.crc()?;
@@ -146,7 +306,11 @@ impl CrcData {
     }
 }
 
-fn into_record_data(records: &[Record], compression: Compression) -> Result<Bytes> {
+fn into_record_data(
+    records: &[Record],
+    compression: Compression,
+    level: CompressionLevel,
+) -> Result<Bytes> {
     match compression {
         Compression::None => {
             let mut record_data = BytesMut::new().writer();
@@ -160,7 +324,12 @@ fn into_record_data(records: &[Record], compression: Compression) -> Result<Byte
         }
 
         Compression::Gzip => {
-            let mut gz = GzEncoder::new(BytesMut::new().writer(), flate2::Compression::default());
+            let mut gz = GzEncoder::new(
+                BytesMut::new().writer(),
+                level
+                    .gzip
+                    .map_or(flate2::Compression::default(), flate2::Compression::new),
+            );
             let mut encoder = Encoder::new(&mut gz);
 
             for record in records {
@@ -173,8 +342,24 @@ fn into_record_data(records: &[Record], compression: Compression) -> Result<Byte
                 .map_err(Into::into)
         }
 
+        Compression::Snappy => {
+            let mut record_data = BytesMut::new().writer();
+            let mut encoder = Encoder::new(&mut record_data);
+
+            for record in records {
+                record.serialize(&mut encoder)?;
+            }
+
+            snap::raw::Encoder::new()
+                .compress_vec(&record_data.into_inner()[..])
+                .map(Bytes::from)
+                .map_err(Into::into)
+        }
+
         Compression::Lz4 => {
-            let mut lz4 = lz4::EncoderBuilder::new().build(BytesMut::new().writer())?;
+            let mut lz4 = lz4::EncoderBuilder::new()
+                .level(level.lz4.unwrap_or_default())
+                .build(BytesMut::new().writer())?;
             let mut encoder = Encoder::new(&mut lz4);
 
             for record in records {
@@ -186,7 +371,10 @@ fn into_record_data(records: &[Record], compression: Compression) -> Result<Byte
         }
 
         Compression::Zstd => {
-            let mut zstd = zstd::stream::write::Encoder::new(BytesMut::new().writer(), 0)?;
+            let mut zstd = zstd::stream::write::Encoder::new(
+                BytesMut::new().writer(),
+                level.zstd.unwrap_or_default(),
+            )?;
             let mut encoder = Encoder::new(&mut zstd);
 
             for record in records {
@@ -198,8 +386,6 @@ fn into_record_data(records: &[Record], compression: Compression) -> Result<Byte
                 .map(Bytes::from)
                 .map_err(Into::into)
         }
-
-        _ => todo!(),
     }
 }
 
@@ -216,7 +402,11 @@ impl TryFrom<crate::record::inflated::Batch> for Batch {
             producer_epoch: batch.producer_epoch,
             base_sequence: batch.base_sequence,
             record_count: u32::try_from(batch.records.len())?,
-            record_data: into_record_data(&batch.records[..], batch.compression()?)?,
+            record_data: into_record_data(
+                &batch.records[..],
+                batch.compression()?,
+                CompressionLevel::default(),
+            )?,
         }
         .into_batch(batch.base_offset, batch.partition_leader_epoch, batch.magic)
     }
@@ -230,55 +420,155 @@ impl Batch {
     fn compression(&self) -> Result<Compression> {
         Compression::try_from(self.attributes)
     }
-}
 
-impl TryFrom<Batch> for Vec<Record> {
-    type Error = Error;
+    pub fn recompress(self, compression: Compression) -> Result<Self> {
+        self.recompress_with_level(compression, CompressionLevel::default())
+    }
 
-    fn try_from(batch: Batch) -> Result<Self, Self::Error> {
-        let record_count = usize::try_from(batch.record_count)?;
+    pub fn recompress_with_level(
+        self,
+        compression: Compression,
+        level: CompressionLevel,
+    ) -> Result<Self> {
+        if self.compression()? == compression {
+            return Ok(self);
+        }
 
-        debug!(?record_count);
-        debug!(?batch.record_data);
+        let records = self.records(Limit::default())?;
 
-        let mut reader = batch
-            .compression()
-            .and_then(|compression| compression.inflator(batch.record_data.reader()))?;
+        CrcData {
+            attributes: BatchAttribute::try_from(self.attributes)?
+                .compression(compression.clone())
+                .into(),
+            record_data: into_record_data(&records[..], compression, level)?,
+            ..CrcData::from(&self)
+        }
+        .into_batch(self.base_offset, self.partition_leader_epoch, self.magic)
+    }
 
-        let mut decoder = Decoder::new(&mut reader);
-        let mut records = Vec::with_capacity(record_count);
+    pub fn records(&self, limit: Limit) -> Result<Vec<Record>> {
+        if limit
+            .max_record_count
+            .is_some_and(|max_record_count| self.record_count > max_record_count)
+        {
+            return Err(Error::RecordCountExceeded(self.record_count));
+        }
+
+        debug!(?self.record_data);
+
+        self.inflated_record_data(limit)
+            .and_then(|encoded| self.decode_records(encoded))
+    }
+
+    fn decode_records(&self, mut encoded: Bytes) -> Result<Vec<Record>> {
+        let record_count = usize::try_from(self.record_count)?;
+        debug!(?record_count);
+
+        // every record occupies at least one byte
+        let mut records = Vec::with_capacity(record_count.min(encoded.len()));
 
         for _ in 0..record_count {
-            let record = Record::deserialize(&mut decoder)?;
+            let record = Record::decode(&mut encoded)?;
             records.push(record);
         }
 
         Ok(records)
     }
-}
 
-impl TryFrom<&Batch> for Vec<Record> {
-    type Error = Error;
+    fn inflated_record_data(&self, limit: Limit) -> Result<Bytes> {
+        match self.compression()? {
+            Compression::None => Ok(self.record_data.clone()),
 
-    fn try_from(batch: &Batch) -> Result<Self, Self::Error> {
-        let record_count = usize::try_from(batch.record_count)?;
+            compression => {
+                let mut inflator = compression.inflator(self.record_data.clone().reader())?;
+                let mut inflated = vec![];
 
-        debug!(?record_count);
-        debug!(?batch.record_data);
+                if let Some(max_inflated_bytes) = limit.max_inflated_bytes {
+                    let size = inflator
+                        .take(max_inflated_bytes.saturating_add(1))
+                        .read_to_end(&mut inflated)?;
+
+                    if u64::try_from(size)? > max_inflated_bytes {
+                        return Err(Error::InflatedSizeExceeded(max_inflated_bytes));
+                    }
+                } else {
+                    _ = inflator.read_to_end(&mut inflated)?;
+                }
+
+                Ok(Bytes::from(inflated))
+            }
+        }
+    }
 
-        let mut reader = batch
-            .compression()
-            .and_then(|compression| compression.inflator(batch.record_data.clone().reader()))?;
+    // the id of the zstd dictionary used to compress the record data
+    pub fn dictionary_id(&self) -> Option<u32> {
+        self.compression()
+            .is_ok_and(|compression| compression == Compression::Zstd)
+            .then(|| zstd::zstd_safe::get_dict_id_from_frame(&self.record_data[..]))
+            .flatten()
+            .map(NonZeroU32::get)
+    }
 
-        let mut decoder = Decoder::new(&mut reader);
-        let mut records = Vec::with_capacity(record_count);
+    pub fn compress_with_dictionary(self, dictionary: &[u8]) -> Result<Self> {
+        let records = self.records(Limit::default())?;
 
-        for _ in 0..record_count {
-            let record = Record::deserialize(&mut decoder)?;
-            records.push(record);
+        let mut zstd =
+            zstd::stream::write::Encoder::with_dictionary(BytesMut::new().writer(), 0, dictionary)?;
+        let mut encoder = Encoder::new(&mut zstd);
+
+        for record in records {
+            record.serialize(&mut encoder)?;
         }
 
-        Ok(records)
+        let record_data = zstd.finish().map(|w| Bytes::from(w.into_inner()))?;
+
+        CrcData {
+            attributes: BatchAttribute::try_from(self.attributes)?
+                .compression(Compression::Zstd)
+                .into(),
+            record_data,
+            ..CrcData::from(&self)
+        }
+        .into_batch(self.base_offset, self.partition_leader_epoch, self.magic)
+    }
+
+    // recompress without a dictionary for clients that only know the codec
+    pub fn decompress_with_dictionary(self, dictionary: &[u8]) -> Result<Self> {
+        let mut inflated = vec![];
+
+        _ = zstd::stream::read::Decoder::with_dictionary(
+            self.record_data.clone().reader(),
+            dictionary,
+        )?
+        .read_to_end(&mut inflated)?;
+
+        let records = self.decode_records(Bytes::from(inflated))?;
+
+        CrcData {
+            record_data: into_record_data(
+                &records[..],
+                Compression::Zstd,
+                CompressionLevel::default(),
+            )?,
+            ..CrcData::from(&self)
+        }
+        .into_batch(self.base_offset, self.partition_leader_epoch, self.magic)
+    }
+}
+
+impl TryFrom<Batch> for Vec<Record> {
+    type Error = Error;
+
+    fn try_from(batch: Batch) -> Result<Self, Self::Error> {
+        Self::try_from(&batch)
+    }
+}
+
+impl TryFrom<&Batch> for Vec<Record> {
+    type Error = Error;
+
+    fn try_from(batch: &Batch) -> Result<Self, Self::Error> {
+        batch.records(Limit::default())
     }
 }
 
@@ -306,7 +596,317 @@ const FIXED_BATCH_LENGTH: usize =
     // record count
     + size_of::<u32>();
 
+const NO_TIMESTAMP: i64 = -1;
+
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct LegacyMessage {
+    offset: i64,
+    magic: i8,
+    attributes: i8,
+    timestamp: Option<i64>,
+    key: Option<Bytes>,
+    value: Option<Bytes>,
+}
+
+// the bytes of a legacy message following its CRC, excluding the key and value
+fn legacy_header_size(magic: i8) -> usize {
+    // magic
+    size_of::<i8>()
+    // attributes
+    + size_of::<i8>()
+    // timestamp
+    + if magic > 0 { size_of::<i64>() } else { 0 }
+}
+
+// the length of a legacy key or value, which must fit within the
+// remaining bytes of the message before anything is allocated
+fn legacy_length(length: i32, remaining: &mut usize) -> Result<Option<usize>> {
+    let Ok(length) = usize::try_from(length) else {
+        return Ok(None);
+    };
+
+    *remaining = remaining
+        .checked_sub(length)
+        .ok_or(Error::ApiError(ErrorCode::CorruptMessage))?;
+
+    Ok(Some(length))
+}
+
+impl LegacyMessage {
+    const TIMESTAMP_TYPE_BITMASK: i8 = 0b1000;
+
+    // the CRC-32 (rather than CRC-32C) of everything following the CRC
+    fn crc(&self) -> u32 {
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+
+        digest.update(&self.magic.to_be_bytes());
+        digest.update(&self.attributes.to_be_bytes());
+
+        if let Some(timestamp) = self.timestamp {
+            digest.update(&timestamp.to_be_bytes());
+        }
+
+        for octets in [&self.key, &self.value] {
+            match octets {
+                Some(octets) => {
+                    digest.update(&i32::try_from(octets.len()).unwrap_or(-1).to_be_bytes());
+                    digest.update(octets);
+                }
+
+                None => digest.update(&(-1i32).to_be_bytes()),
+            }
+        }
+
+        digest.finalize()
+    }
+
+    fn verify(self, crc: u32) -> Result<Self> {
+        if self.crc() == crc {
+            Ok(self)
+        } else {
+            debug!(offset = self.offset, crc, computed = self.crc());
+            Err(Error::ApiError(ErrorCode::CorruptMessage))
+        }
+    }
+
+    fn decode(reader: &mut Cursor<Vec<u8>>) -> Result<Self> {
+        let available = reader.get_ref().len() - usize::try_from(reader.position())?;
+        let mut decoder = Decoder::new(reader);
+
+        let offset = i64::deserialize(&mut decoder)?;
+        let message_size = i32::deserialize(&mut decoder)?;
+        let crc = u32::deserialize(&mut decoder)?;
+        let magic = i8::deserialize(&mut decoder)?;
+        let attributes = i8::deserialize(&mut decoder)?;
+        let timestamp = if magic > 0 {
+            i64::deserialize(&mut decoder).map(Some)?
+        } else {
+            None
+        };
+
+        let mut remaining = usize::try_from(message_size)
+            .ok()
+            .filter(|message_size| {
+                *message_size <= available.saturating_sub(size_of::<i64>() + size_of::<i32>())
+            })
+            .and_then(|message_size| {
+                message_size.checked_sub(size_of::<u32>() + legacy_header_size(magic))
+            })
+            .ok_or(Error::ApiError(ErrorCode::CorruptMessage))?;
+
+        let mut octets = || {
+            remaining = remaining
+                .checked_sub(size_of::<i32>())
+                .ok_or(Error::ApiError(ErrorCode::CorruptMessage))?;
+
+            i32::deserialize(&mut decoder)
+                .and_then(|length| legacy_length(length, &mut remaining))
+                .and_then(|length| {
+                    length
+                        .map(|length| {
+                            let mut bytes = BytesMut::with_capacity(length);
+
+                            for _ in 0..length {
+                                bytes.put_u8(u8::deserialize(&mut decoder)?);
+                            }
+
+                            Ok(Bytes::from(bytes))
+                        })
+                        .transpose()
+                })
+        };
+
+        let key = octets()?;
+        let value = octets()?;
+
+        Self {
+            offset,
+            magic,
+            attributes,
+            timestamp,
+            key,
+            value,
+        }
+        .verify(crc)
+    }
+
+    fn timestamp_type(&self) -> TimestampType {
+        if self.attributes & Self::TIMESTAMP_TYPE_BITMASK == Self::TIMESTAMP_TYPE_BITMASK {
+            TimestampType::LogAppendTime
+        } else {
+            TimestampType::CreateTime
+        }
+    }
+
+    fn messages(self) -> Result<Vec<Self>> {
+        match Compression::try_from(i16::from(self.attributes))? {
+            Compression::None => Ok(vec![self]),
+
+            compression => {
+                // the value of a compressed wrapper message is itself a message set
+                let mut inflated = vec![];
+                let size = compression
+                    .inflator(self.value.unwrap_or_default().reader())?
+                    .take(DEFAULT_MAX_INFLATED_BYTES.saturating_add(1))
+                    .read_to_end(&mut inflated)?;
+
+                if u64::try_from(size)? > DEFAULT_MAX_INFLATED_BYTES {
+                    return Err(Error::InflatedSizeExceeded(DEFAULT_MAX_INFLATED_BYTES));
+                }
+
+                let length = u64::try_from(inflated.len())?;
+                let mut reader = Cursor::new(inflated);
+                let mut messages = vec![];
+
+                while reader.position() < length {
+                    messages.push(Self::decode(&mut reader)?);
+                }
+
+                // v1 inner offsets are relative, with the wrapper holding the
+                // absolute offset of the last inner message
+                if self.magic > 0 {
+                    let last = messages.last().map_or(0, |message| message.offset);
+
+                    for message in messages.iter_mut() {
+                        message.offset += self.offset - last;
+                    }
+                }
+
+                Ok(messages)
+            }
+        }
+    }
+}
+
+fn legacy_octets<'de, A>(
+    seq: &mut A,
+    field: &'static str,
+    remaining: &mut usize,
+) -> Result<Option<Bytes>, A::Error>
+where
+    A: SeqAccess<'de>,
+{
+    *remaining = remaining
+        .checked_sub(size_of::<i32>())
+        .ok_or(<A::Error as de::Error>::custom(field))?;
+
+    let length = seq
+        .next_element::<i32>()?
+        .ok_or(<A::Error as de::Error>::custom(field))?;
+
+    let Some(length) = legacy_length(length, remaining).map_err(de::Error::custom)? else {
+        return Ok(None);
+    };
+
+    let mut bytes = BytesMut::with_capacity(length);
+
+    for _ in 0..length {
+        let byte = seq
+            .next_element::<u8>()?
+            .ok_or(<A::Error as de::Error>::custom(field))?;
+
+        bytes.put_u8(byte);
+    }
+
+    Ok(Some(Bytes::from(bytes)))
+}
+
+fn up_convert(messages: &[LegacyMessage]) -> Result<Batch> {
+    let base_offset = messages.first().map_or(0, |message| message.offset);
+
+    // offsets that do not increase, such as a producer leaving them all
+    // zero, are taken from the position of each message instead
+    let increasing = messages
+        .windows(2)
+        .all(|pair| pair[0].offset < pair[1].offset);
+
+    let base_timestamp = messages
+        .first()
+        .and_then(|message| message.timestamp)
+        .unwrap_or(NO_TIMESTAMP);
+
+    let max_timestamp = messages
+        .iter()
+        .filter_map(|message| message.timestamp)
+        .max()
+        .unwrap_or(NO_TIMESTAMP);
+
+    let timestamp_type = messages
+        .first()
+        .map(LegacyMessage::timestamp_type)
+        .unwrap_or_default();
+
+    debug!(base_offset, base_timestamp, max_timestamp, ?timestamp_type);
+
+    messages
+        .iter()
+        .enumerate()
+        .try_fold(
+            crate::record::inflated::Batch::builder()
+                .base_offset(base_offset)
+                .attributes(BatchAttribute::default().timestamp(timestamp_type).into())
+                .last_offset_delta(i32::try_from(if increasing {
+                    messages
+                        .last()
+                        .map_or(0, |message| message.offset - base_offset)
+                } else {
+                    i64::try_from(messages.len().saturating_sub(1))?
+                })?)
+                .base_timestamp(base_timestamp)
+                .max_timestamp(max_timestamp)
+                .producer_id(-1)
+                .producer_epoch(-1)
+                .base_sequence(-1),
+            |builder, (position, message)| {
+                let offset_delta = if increasing {
+                    message.offset - base_offset
+                } else {
+                    i64::try_from(position)?
+                };
+
+                debug!(offset_delta, magic = message.magic);
+
+                i32::try_from(offset_delta)
+                    .map(|offset_delta| {
+                        builder.record(
+                            Record::builder()
+                                .offset_delta(offset_delta)
+                                .timestamp_delta(
+                                    message
+                                        .timestamp
+                                        .map_or(0, |timestamp| timestamp - base_timestamp),
+                                )
+                                .key(message.key.clone().into())
+                                .value(message.value.clone().into()),
+                        )
+                    })
+                    .map_err(Into::into)
+            },
+        )
+        .and_then(|builder| builder.build())
+        .and_then(Batch::try_from)
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum Entry {
+    Batch(Batch),
+    Legacy(Vec<LegacyMessage>),
+}
+
 impl<'de> Deserialize<'de> for Batch {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Entry::deserialize(deserializer).and_then(|entry| match entry {
+            Entry::Batch(batch) => Ok(batch),
+            Entry::Legacy(messages) => up_convert(&messages).map_err(de::Error::custom),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Entry {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
@@ -314,7 +914,7 @@ impl<'de> Deserialize<'de> for Batch {
         struct V;
 
         impl<'de> Visitor<'de> for V {
-            type Value = Batch;
+            type Value = Entry;
 
             fn expecting(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
                 formatter.write_str(stringify!(Batch))
@@ -336,6 +936,50 @@ impl<'de> Deserialize<'de> for Batch {
                 let magic = seq
                     .next_element::<i8>()?
                     .ok_or(<A::Error as de::Error>::custom("magic"))?;
+
+                if magic < 2 {
+                    // legacy message: the batch length position holds the
+                    // message size, and the partition leader epoch the CRC
+                    let crc = partition_leader_epoch as u32;
+
+                    let mut remaining = usize::try_from(batch_length)
+                        .ok()
+                        .and_then(|message_size| {
+                            message_size.checked_sub(size_of::<u32>() + legacy_header_size(magic))
+                        })
+                        .ok_or(<A::Error as de::Error>::custom("message_size"))?;
+
+                    let attributes = seq
+                        .next_element::<i8>()?
+                        .ok_or(<A::Error as de::Error>::custom("attributes"))?;
+                    let timestamp = if magic > 0 {
+                        seq.next_element::<i64>()?
+                            .map(Some)
+                            .ok_or(<A::Error as de::Error>::custom("timestamp"))?
+                    } else {
+                        None
+                    };
+                    let key = legacy_octets(&mut seq, "key", &mut remaining)?;
+                    let value = legacy_octets(&mut seq, "value", &mut remaining)?;
+
+                    return LegacyMessage {
+                        offset: base_offset,
+                        magic,
+                        attributes,
+                        timestamp,
+                        key,
+                        value,
+                    }
+                    .verify(crc)
+                    .and_then(LegacyMessage::messages)
+                    .map(Entry::Legacy)
+                    .map_err(|e| {
+                        <A::Error as de::Error>::custom(format!(
+                            "base_offset: {base_offset}, caused: {e:?}"
+                        ))
+                    });
+                }
+
                 let crc = seq
                     .next_element::<u32>()?
                     .ok_or(<A::Error as de::Error>::custom("crc"))?;
@@ -403,7 +1047,7 @@ impl<'de> Deserialize<'de> for Batch {
                     record_data,
                 };
 
-                Ok(batch)
+                Ok(Entry::Batch(batch))
             }
         }
 
@@ -415,7 +1059,10 @@ impl<'de> Deserialize<'de> for Batch {
 mod tests {
     use std::io::Cursor;
 
-    use crate::{BatchAttribute, ControlBatch, EndTransactionMarker, record::inflated};
+    use crate::{
+        BatchAttribute, ControlBatch, EndTransactionMarker,
+        record::{Header, inflated},
+    };
 
     use super::*;
 
@@ -794,6 +1441,287 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn verify_crc() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let batch = Batch {
+            base_offset: 0,
+            batch_length: 68,
+            partition_leader_epoch: 0,
+            magic: 2,
+            crc: 3650210183,
+            attributes: 16,
+            last_offset_delta: 0,
+            base_timestamp: 1729509915759,
+            max_timestamp: 1729509915759,
+            producer_id: 5,
+            producer_epoch: 0,
+            base_sequence: 0,
+            record_count: 1,
+            record_data: Bytes::from_static(b"$\0\0\0\x08\0\0\0\0\x10test0-ok\0"),
+        };
+
+        assert_eq!(3650210183, batch.recompute_crc()?);
+        assert!(batch.verify_crc());
+
+        let mutated = Batch {
+            record_data: Bytes::from_static(b"$\0\0\0\x08\0\0\0\0\x10test0-KO\0"),
+            ..batch.clone()
+        };
+
+        assert!(!mutated.verify_crc());
+
+        let mutated = Batch {
+            max_timestamp: batch.max_timestamp + 1,
+            ..batch
+        };
+
+        assert!(!mutated.verify_crc());
+
+        Ok(())
+    }
+
+    #[test]
+    fn built_batch_verifies_crc() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let batch: Batch = inflated::Batch::builder()
+            .record(Record::builder().value(vec![100, 101, 102].into()))
+            .base_timestamp(1_707_058_170_165)
+            .max_timestamp(1_707_058_170_165)
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        assert!(batch.verify_crc());
+
+        let mutated = Batch {
+            attributes: BatchAttribute::default().transaction(true).into(),
+            ..batch
+        };
+
+        assert!(!mutated.verify_crc());
+
+        Ok(())
+    }
+
+    #[test]
+    fn partition_leader_epoch_round_trip() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let batch: Batch = inflated::Batch::builder()
+            .partition_leader_epoch(32_123)
+            .record(Record::builder().value(vec![100, 101, 102].into()))
+            .base_timestamp(1_707_058_170_165)
+            .max_timestamp(1_707_058_170_165)
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        assert_eq!(32_123, batch.partition_leader_epoch);
+
+        let mut encoded = vec![];
+        let mut encoder = Encoder::new(&mut encoded);
+        batch.serialize(&mut encoder)?;
+
+        let mut c = Cursor::new(encoded);
+        let mut decoder = Decoder::new(&mut c);
+        let decoded = Batch::deserialize(&mut decoder)?;
+
+        assert_eq!(32_123, decoded.partition_leader_epoch);
+        assert!(decoded.verify_crc());
+        assert_eq!(batch, decoded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn headers_round_trip() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let headers = [
+            Header::builder().key(b"trace".into()).value(b"abc".into()),
+            Header::builder().key(b"trace".into()).value(b"pqr".into()),
+            Header::builder().key(b"empty".into()).value(vec![]),
+            Header::builder().key(b"null".into()),
+        ];
+
+        let batch: Batch = inflated::Batch::builder()
+            .record(
+                headers
+                    .iter()
+                    .cloned()
+                    .fold(Record::builder(), |record, header| record.header(header))
+                    .value(vec![100, 101, 102].into()),
+            )
+            .base_timestamp(1_707_058_170_165)
+            .max_timestamp(1_707_058_170_165)
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        let mut encoded = vec![];
+        let mut encoder = Encoder::new(&mut encoded);
+        batch.serialize(&mut encoder)?;
+
+        let mut c = Cursor::new(encoded);
+        let mut decoder = Decoder::new(&mut c);
+        let decoded = Batch::deserialize(&mut decoder)?;
+        assert!(decoded.verify_crc());
+
+        let inflated = inflated::Batch::try_from(decoded)?;
+        assert_eq!(1, inflated.records.len());
+        assert_eq!(
+            headers.into_iter().map(Header::from).collect::<Vec<_>>(),
+            inflated.records[0].headers
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn recompress() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let batch: Batch = inflated::Batch::builder()
+            .record(Record::builder().value(vec![100, 101, 102].into()))
+            .record(Record::builder().value(vec![103, 104, 105].into()))
+            .base_timestamp(1_707_058_170_165)
+            .max_timestamp(1_707_058_170_165)
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        let records = batch.records(Limit::default())?;
+
+        for compression in [
+            Compression::Gzip,
+            Compression::Snappy,
+            Compression::Lz4,
+            Compression::Zstd,
+            Compression::None,
+        ] {
+            let recompressed = batch.clone().recompress(compression.clone())?;
+
+            assert_eq!(compression, recompressed.compression()?);
+            assert!(recompressed.verify_crc());
+            assert_eq!(batch.record_count, recompressed.record_count);
+            assert_eq!(records, recompressed.records(Limit::default())?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn compression_level() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let value = |n: i32| {
+            Bytes::from(format!(
+                r#"{{"sensor":"temperature","unit":"celsius","site":"north","reading":{n}}}"#
+            ))
+        };
+
+        let batch: Batch = (0..250)
+            .fold(inflated::Batch::builder(), |builder, n| {
+                builder.record(Record::builder().offset_delta(n).value(value(n).into()))
+            })
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        let fastest = batch.clone().recompress_with_level(
+            Compression::Zstd,
+            CompressionLevel::default().level(Compression::Zstd, 1)?,
+        )?;
+
+        let smallest = batch.clone().recompress_with_level(
+            Compression::Zstd,
+            CompressionLevel::default().level(Compression::Zstd, 19)?,
+        )?;
+
+        assert!(
+            smallest.record_data.len() <= fastest.record_data.len(),
+            "smallest: {}, fastest: {}",
+            smallest.record_data.len(),
+            fastest.record_data.len()
+        );
+
+        assert!(fastest.verify_crc());
+        assert!(smallest.verify_crc());
+
+        let records = batch.records(Limit::default())?;
+        assert_eq!(records, fastest.records(Limit::default())?);
+        assert_eq!(records, smallest.records(Limit::default())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_compression_level() {
+        for (compression, level) in [
+            (Compression::Gzip, 0),
+            (Compression::Gzip, 10),
+            (Compression::Lz4, 18),
+            (Compression::Zstd, 23),
+            (Compression::Snappy, 1),
+            (Compression::None, 1),
+        ] {
+            assert!(matches!(
+                CompressionLevel::default().level(compression.clone(), level),
+                Err(Error::InvalidCompressionLevel(invalid, invalid_level))
+                    if invalid == compression && invalid_level == level
+            ));
+        }
+    }
+
+    #[test]
+    fn compress_with_dictionary() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let value = |n: usize| {
+            Bytes::from(format!(
+                r#"{{"sensor":"temperature","unit":"celsius","site":"north","reading":{n}}}"#
+            ))
+        };
+
+        let dictionary =
+            zstd::dict::from_samples(&(0..1_000).map(value).collect::<Vec<_>>()[..], 4_096)?;
+
+        let dictionary_id = zstd::zstd_safe::get_dict_id_from_dict(&dictionary[..])
+            .map(NonZeroU32::get)
+            .expect("dictionary id");
+
+        let mut plain = 0;
+        let mut dictionaried = 0;
+
+        for n in 1_000..1_100 {
+            let batch: Batch = inflated::Batch::builder()
+                .record(Record::builder().value(value(n).into()))
+                .build()
+                .and_then(TryInto::try_into)?;
+
+            let records = batch.records(Limit::default())?;
+
+            let zstd = batch.clone().recompress(Compression::Zstd)?;
+            assert_eq!(None, zstd.dictionary_id());
+            plain += zstd.record_data.len();
+
+            let compressed = batch.compress_with_dictionary(&dictionary[..])?;
+            assert_eq!(Compression::Zstd, compressed.compression()?);
+            assert_eq!(Some(dictionary_id), compressed.dictionary_id());
+            assert!(compressed.verify_crc());
+            dictionaried += compressed.record_data.len();
+
+            let decompressed = compressed.decompress_with_dictionary(&dictionary[..])?;
+            assert_eq!(Compression::Zstd, decompressed.compression()?);
+            assert_eq!(None, decompressed.dictionary_id());
+            assert!(decompressed.verify_crc());
+            assert_eq!(records, decompressed.records(Limit::default())?);
+        }
+
+        debug!(plain, dictionaried);
+        assert!(dictionaried < plain);
+
+        Ok(())
+    }
+
     #[test]
     pub fn is_transactional_control() -> Result<()> {
         use crate::record::inflated;
@@ -846,6 +1774,102 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn uncompressed_records_share_record_data() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let batch: Batch = inflated::Batch::builder()
+            .record(
+                Record::builder()
+                    .key(Bytes::from_static(b"Lorem ipsum").into())
+                    .value(Bytes::from_static(b"dolor sit amet").into())
+                    .header(
+                        crate::record::Header::builder()
+                            .key(b"consectetur".to_vec())
+                            .value(b"adipiscing".to_vec()),
+                    ),
+            )
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        let within = |bytes: &Bytes| {
+            let start = batch.record_data.as_ptr() as usize;
+            let end = start + batch.record_data.len();
+            let ptr = bytes.as_ptr() as usize;
+
+            ptr >= start && ptr + bytes.len() <= end
+        };
+
+        let records = Vec::<Record>::try_from(&batch)?;
+        assert_eq!(1, records.len());
+
+        let key = records[0].key.as_ref().unwrap();
+        assert_eq!(b"Lorem ipsum", &key[..]);
+        assert!(within(key));
+
+        let value = records[0].value.as_ref().unwrap();
+        assert_eq!(b"dolor sit amet", &value[..]);
+        assert!(within(value));
+
+        let header = records[0].headers[0].value.as_ref().unwrap();
+        assert_eq!(b"adipiscing", &header[..]);
+        assert!(within(header));
+
+        Ok(())
+    }
+
+    #[test]
+    fn inflated_size_limit() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let batch: Batch = inflated::Batch::builder()
+            .attributes(BatchAttribute::default().compression(Compression::Gzip).into())
+            .record(Record::builder().value(vec![0u8; 1_048_576].into()))
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        assert!(batch.record_data.len() < 4_096);
+
+        assert!(matches!(
+            batch.records(Limit::default().max_inflated_bytes(Some(65_536))),
+            Err(Error::InflatedSizeExceeded(65_536))
+        ));
+
+        let records = batch.records(Limit::default().max_inflated_bytes(Some(2_097_152)))?;
+        assert_eq!(1, records.len());
+        assert_eq!(Some(1_048_576), records[0].value.as_ref().map(Bytes::len));
+
+        Ok(())
+    }
+
+    #[test]
+    fn record_count_limit() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let batch: Batch = (0..5)
+            .fold(inflated::Batch::builder(), |builder, offset_delta| {
+                builder
+                    .last_offset_delta(offset_delta)
+                    .record(Record::builder().offset_delta(offset_delta))
+            })
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        assert!(matches!(
+            batch.records(Limit::default().max_record_count(Some(4))),
+            Err(Error::RecordCountExceeded(5))
+        ));
+
+        assert_eq!(
+            5,
+            batch
+                .records(Limit::default().max_record_count(Some(5)))?
+                .len()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn deflate() -> Result<()> {
         let key = Bytes::copy_from_slice("Lorem ipsum dolor sit amet".as_bytes());
@@ -881,4 +1905,121 @@ mod tests {
 
         Ok(())
     }
+
+    fn legacy(message: &LegacyMessage) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&message.crc().to_be_bytes());
+        body.push(message.magic as u8);
+        body.push(message.attributes as u8);
+
+        if let Some(timestamp) = message.timestamp {
+            body.extend_from_slice(&timestamp.to_be_bytes());
+        }
+
+        for octets in [&message.key, &message.value] {
+            match octets {
+                Some(octets) => {
+                    body.extend_from_slice(&(octets.len() as i32).to_be_bytes());
+                    body.extend_from_slice(octets);
+                }
+                None => body.extend_from_slice(&(-1i32).to_be_bytes()),
+            }
+        }
+
+        let mut encoded = vec![];
+        encoded.extend_from_slice(&message.offset.to_be_bytes());
+        encoded.extend_from_slice(&(body.len() as i32).to_be_bytes());
+        encoded.extend_from_slice(&body);
+        encoded
+    }
+
+    fn legacy_value(offset: i64, value: &'static [u8]) -> LegacyMessage {
+        LegacyMessage {
+            offset,
+            magic: 1,
+            attributes: 0,
+            timestamp: Some(1_707_058_170_165),
+            key: None,
+            value: Some(Bytes::from_static(value)),
+        }
+    }
+
+    #[test]
+    fn legacy_crc_mismatch() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let mut encoded = legacy(&legacy_value(0, b"abc"));
+        *encoded.last_mut().unwrap() ^= 0xff;
+
+        let mut c = Cursor::new(encoded);
+        let mut decoder = Decoder::new(&mut c);
+
+        assert!(Batch::deserialize(&mut decoder).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_length_beyond_message() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let mut encoded = legacy(&legacy_value(0, b"abc"));
+
+        // the value length of a magic 1 message follows the offset, size,
+        // crc, magic, attributes, timestamp and null key
+        let value_length = 8 + 4 + 4 + 1 + 1 + 8 + 4;
+        encoded[value_length..value_length + 4].copy_from_slice(&i32::MAX.to_be_bytes());
+
+        let mut c = Cursor::new(encoded);
+        let mut decoder = Decoder::new(&mut c);
+
+        assert!(Batch::deserialize(&mut decoder).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_compressed_relative_offsets() -> Result<()> {
+        use std::io::Write;
+
+        let _guard = init_tracing()?;
+
+        let mut inner = GzEncoder::new(vec![], flate2::Compression::default());
+
+        for (offset, value) in [(0, b"v0"), (1, b"v1"), (2, b"v2")] {
+            inner.write_all(&legacy(&legacy_value(offset, value)))?;
+        }
+
+        let wrapper = LegacyMessage {
+            offset: 42,
+            attributes: i8::try_from(i16::from(Compression::Gzip))?,
+            value: Some(Bytes::from(inner.finish()?)),
+            ..legacy_value(42, b"")
+        };
+
+        let mut c = Cursor::new(legacy(&wrapper));
+        let mut decoder = Decoder::new(&mut c);
+
+        let batch = Batch::deserialize(&mut decoder)?;
+        assert_eq!(40, batch.base_offset);
+        assert_eq!(2, batch.last_offset_delta);
+
+        let records = batch.records(Limit::default())?;
+        assert_eq!(
+            vec![0, 1, 2],
+            records
+                .iter()
+                .map(|record| record.offset_delta)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Some(Bytes::from_static(b"v2"))],
+            records[2..]
+                .iter()
+                .map(|record| record.value.clone())
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
 }
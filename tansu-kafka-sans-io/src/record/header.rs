@@ -32,6 +32,12 @@ impl Header {
     pub fn builder() -> Builder {
         Builder::default()
     }
+
+    pub(crate) fn decode(encoded: &mut Bytes) -> Result<Self> {
+        let key = Octets::decode(encoded)?;
+        let value = Octets::decode(encoded)?;
+        Ok(Self { key, value })
+    }
 }
 
 impl From<Builder> for Header {
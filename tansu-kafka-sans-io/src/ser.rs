@@ -30,7 +30,7 @@ use serde::{
 use tansu_kafka_model::{FieldMeta, MessageMeta};
 use tracing::debug;
 
-use crate::{Error, Result, RootMessageMeta};
+use crate::{API_VERSIONS_API_KEY, Error, Result, RootMessageMeta};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 enum Kind {
@@ -214,7 +214,9 @@ impl<'a> Encoder<'a> {
             && ((self.kind.is_some_and(|kind| kind == Kind::Request)
                 && self.field.is_some_and(|field| field == "client_id"))
                 || (self.kind.is_some_and(|kind| kind == Kind::Response)
-                    && self.api_key.is_some_and(|api_key| api_key == 18)))
+                    && self
+                        .api_key
+                        .is_some_and(|api_key| api_key == API_VERSIONS_API_KEY)))
         {
             false
         } else {
@@ -5268,6 +5268,77 @@ fn produce_request_v0_000() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn produce_request_v2_legacy_message_set() -> Result<()> {
+    let _guard = init_tracing()?;
+
+    let v = vec![
+        0, 0, 0, 118, 0, 0, 0, 2, 0, 0, 0, 7, 0, 6, 108, 101, 103, 97, 99, 121, 0, 1, 0, 0, 5, 220,
+        0, 0, 0, 1, 0, 4, 116, 101, 115, 116, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 74, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 26, 135, 117, 41, 225, 1, 0, 0, 0, 1, 141, 116, 152, 137, 53, 0, 0, 0, 2,
+        107, 49, 0, 0, 0, 2, 118, 49, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 24, 82, 22, 85, 237, 1, 0,
+        0, 0, 1, 141, 116, 152, 137, 58, 255, 255, 255, 255, 0, 0, 0, 2, 118, 50,
+    ];
+
+    let Frame {
+        body:
+            Body::ProduceRequest {
+                topic_data: Some(topic_data),
+                ..
+            },
+        ..
+    } = Frame::request_from_bytes(&v)?
+    else {
+        panic!("expecting produce request")
+    };
+
+    assert_eq!(1, topic_data.len());
+    assert_eq!("test", topic_data[0].name);
+
+    let partition_data = topic_data[0].partition_data.as_deref().unwrap_or_default();
+    assert_eq!(1, partition_data.len());
+
+    let Some(ref records) = partition_data[0].records else {
+        panic!("expecting records")
+    };
+
+    assert_eq!(1, records.batches.len());
+
+    let batch = inflated::Batch::try_from(&records.batches[0])?;
+    assert_eq!(2, batch.magic);
+    assert_eq!(1, batch.last_offset_delta);
+    assert_eq!(1707058170165, batch.base_timestamp);
+    assert_eq!(1707058170170, batch.max_timestamp);
+    assert_eq!(-1, batch.producer_id);
+    assert!(records.batches[0].verify_crc());
+
+    assert_eq!(
+        vec![
+            Record {
+                length: 10,
+                attributes: 0,
+                timestamp_delta: 0,
+                offset_delta: 0,
+                key: Some(Bytes::from_static(b"k1")),
+                value: Some(Bytes::from_static(b"v1")),
+                headers: [].into()
+            },
+            Record {
+                length: 8,
+                attributes: 0,
+                timestamp_delta: 5,
+                offset_delta: 1,
+                key: None,
+                value: Some(Bytes::from_static(b"v2")),
+                headers: [].into()
+            },
+        ],
+        batch.records
+    );
+
+    Ok(())
+}
+
 #[test]
 fn produce_request_v3_000() -> Result<()> {
     use tansu_kafka_sans_io::produce_request::{PartitionProduceData, TopicProduceData};
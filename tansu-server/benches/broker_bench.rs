@@ -0,0 +1,343 @@
+// Copyright ⓒ 2024-2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use bytes::Bytes;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use object_store::memory::InMemory;
+use std::net::SocketAddr;
+use tansu_kafka_sans_io::{
+    Body, Frame, Header,
+    create_topics_request::CreatableTopic,
+    fetch_request::{FetchPartition, FetchTopic},
+    metadata_request::MetadataRequestTopic,
+    produce_request::{PartitionProduceData, TopicProduceData},
+    record::{Record, deflated, inflated},
+};
+use tansu_server::{
+    broker::{Broker, SocketOptions},
+    config::Config,
+    coordinator::group::administrator::Controller,
+};
+use tansu_storage::{Storage, StorageContainer, dynostore::DynoStore};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+};
+use url::Url;
+use uuid::Uuid;
+
+const CLUSTER_ID: &str = "tansu";
+const NODE_ID: i32 = 111;
+const TOPIC: &str = "bench";
+
+fn broker(rt: &Runtime) -> Broker<Controller<StorageContainer>, StorageContainer> {
+    broker_with_topics(rt, &[TOPIC.into()])
+}
+
+fn broker_with_topics(
+    rt: &Runtime,
+    topics: &[String],
+) -> Broker<Controller<StorageContainer>, StorageContainer> {
+    let mut storage =
+        StorageContainer::DynoStore(DynoStore::new(CLUSTER_ID, NODE_ID, InMemory::new()));
+
+    for topic in topics {
+        _ = rt
+            .block_on(storage.create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            ))
+            .unwrap();
+    }
+
+    let listener = Url::parse("tcp://localhost:9092/").unwrap();
+
+    let config = Config::builder()
+        .cluster_id(CLUSTER_ID)
+        .node_id(NODE_ID)
+        .listener(listener.clone())
+        .advertised_listener(listener)
+        .storage(Url::parse("memory://tansu/").unwrap())
+        .build()
+        .unwrap();
+
+    Broker::new(
+        &config,
+        storage.clone(),
+        Controller::with_storage(storage).unwrap(),
+        Uuid::nil(),
+    )
+}
+
+fn produce_request(value: &'static [u8]) -> Bytes {
+    let batch = inflated::Batch::builder()
+        .record(Record::builder().value(Bytes::from_static(value).into()))
+        .build()
+        .and_then(deflated::Batch::try_from)
+        .unwrap();
+
+    Frame::request(
+        Header::Request {
+            api_key: 0,
+            api_version: 9,
+            correlation_id: 1,
+            client_id: Some("bench".into()),
+        },
+        Body::ProduceRequest {
+            transactional_id: None,
+            acks: 1,
+            timeout_ms: 5_000,
+            topic_data: Some(
+                [TopicProduceData {
+                    name: TOPIC.into(),
+                    partition_data: Some(
+                        [PartitionProduceData {
+                            index: 0,
+                            records: Some(deflated::Frame {
+                                batches: vec![batch],
+                            }),
+                        }]
+                        .into(),
+                    ),
+                }]
+                .into(),
+            ),
+        },
+    )
+    .map(Bytes::from)
+    .unwrap()
+}
+
+fn fetch_request() -> Bytes {
+    Frame::request(
+        Header::Request {
+            api_key: 1,
+            api_version: 12,
+            correlation_id: 1,
+            client_id: Some("bench".into()),
+        },
+        Body::FetchRequest {
+            cluster_id: None,
+            replica_id: Some(-1),
+            replica_state: None,
+            max_wait_ms: 0,
+            min_bytes: 1,
+            max_bytes: Some(50 * 1024),
+            isolation_level: Some(0),
+            session_id: Some(0),
+            session_epoch: Some(-1),
+            topics: Some(
+                [FetchTopic {
+                    topic: Some(TOPIC.into()),
+                    topic_id: None,
+                    partitions: Some(
+                        [FetchPartition {
+                            partition: 0,
+                            current_leader_epoch: Some(-1),
+                            fetch_offset: 0,
+                            last_fetched_epoch: Some(-1),
+                            log_start_offset: Some(-1),
+                            partition_max_bytes: 50 * 1024,
+                            replica_directory_id: None,
+                        }]
+                        .into(),
+                    ),
+                }]
+                .into(),
+            ),
+            forgotten_topics_data: Some([].into()),
+            rack_id: Some("".into()),
+        },
+    )
+    .map(Bytes::from)
+    .unwrap()
+}
+
+fn produce(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut broker = broker(&rt);
+    let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+    let request = produce_request(b"Lorem ipsum dolor sit amet");
+
+    let mut group = c.benchmark_group("produce");
+    _ = group.throughput(Throughput::Elements(1));
+
+    _ = group.bench_function("in_memory", |b| {
+        b.iter(|| {
+            rt.block_on(broker.process_request(&peer, &request))
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+fn fetch(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut broker = broker(&rt);
+    let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+    let request = produce_request(b"Lorem ipsum dolor sit amet");
+    _ = rt
+        .block_on(broker.process_request(&peer, &request))
+        .unwrap();
+
+    let request = fetch_request();
+
+    let mut group = c.benchmark_group("fetch");
+    _ = group.throughput(Throughput::Elements(1));
+
+    _ = group.bench_function("in_memory", |b| {
+        b.iter(|| {
+            rt.block_on(broker.process_request(&peer, &request))
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+fn metadata_request(topics: &[String]) -> Bytes {
+    Frame::request(
+        Header::Request {
+            api_key: 3,
+            api_version: 12,
+            correlation_id: 1,
+            client_id: Some("bench".into()),
+        },
+        Body::MetadataRequest {
+            topics: Some(
+                topics
+                    .iter()
+                    .map(|name| MetadataRequestTopic {
+                        topic_id: None,
+                        name: Some(name.into()),
+                    })
+                    .collect(),
+            ),
+            allow_auto_topic_creation: Some(false),
+            include_cluster_authorized_operations: None,
+            include_topic_authorized_operations: Some(false),
+        },
+    )
+    .map(Bytes::from)
+    .unwrap()
+}
+
+fn metadata(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+    let mut group = c.benchmark_group("metadata");
+
+    for count in [1, 100, 1_000] {
+        let topics = (0..count)
+            .map(|i| format!("{TOPIC}-{i:04}"))
+            .collect::<Vec<_>>();
+
+        let mut broker = broker_with_topics(&rt, &topics);
+        let request = metadata_request(&topics);
+
+        _ = group.throughput(Throughput::Elements(count));
+
+        _ = group.bench_function(format!("in_memory/{count}"), |b| {
+            b.iter(|| {
+                rt.block_on(broker.process_request(&peer, &request))
+                    .unwrap()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn api_versions_request() -> Bytes {
+    Frame::request(
+        Header::Request {
+            api_key: 18,
+            api_version: 3,
+            correlation_id: 1,
+            client_id: Some("bench".into()),
+        },
+        Body::ApiVersionsRequest {
+            client_software_name: Some("bench".into()),
+            client_software_version: Some("0.1.0".into()),
+        },
+    )
+    .map(Bytes::from)
+    .unwrap()
+}
+
+fn connection(rt: &Runtime, socket_options: SocketOptions) -> TcpStream {
+    let mut broker = broker(rt).socket_options(socket_options);
+
+    rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        _ = tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            stream.set_nodelay(true).unwrap();
+            broker.stream_handler(&peer, stream).await
+        });
+
+        let client = TcpStream::connect(addr).await.unwrap();
+        client.set_nodelay(true).unwrap();
+        client
+    })
+}
+
+fn small_requests(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let request = api_versions_request();
+
+    let mut group = c.benchmark_group("small_requests");
+    _ = group.throughput(Throughput::Elements(1));
+
+    for (name, size) in [("unbuffered", 0), ("buffered", 8 * 1024)] {
+        let mut client = connection(
+            &rt,
+            SocketOptions::default()
+                .read_buffer_size(size)
+                .write_buffer_size(size),
+        );
+
+        _ = group.bench_function(name, |b| {
+            b.iter(|| {
+                rt.block_on(async {
+                    client.write_all(&request).await.unwrap();
+
+                    let mut size = [0u8; 4];
+                    _ = client.read_exact(&mut size).await.unwrap();
+
+                    let mut response = vec![0u8; i32::from_be_bytes(size) as usize];
+                    _ = client.read_exact(&mut response).await.unwrap();
+                })
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, produce, fetch, metadata, small_requests);
+criterion_main!(benches);
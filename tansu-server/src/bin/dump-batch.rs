@@ -0,0 +1,47 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use bytes::Bytes;
+use clap::Parser;
+use std::{fs, io::Write, path::PathBuf};
+use tansu_server::{Result, dump::batches};
+
+// decodes a file of raw batch bytes, writing the header fields and
+// records of each batch as text, or as a line of JSON per batch
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    #[arg(long)]
+    json: bool,
+
+    file: PathBuf,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+
+    let mut stdout = std::io::stdout().lock();
+
+    for batch in batches(Bytes::from(fs::read(args.file)?))? {
+        if args.json {
+            serde_json::to_writer(&mut stdout, &batch)?;
+            writeln!(stdout)?;
+        } else {
+            write!(stdout, "{batch}")?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use clap::Parser;
+use object_store::memory::InMemory;
+use std::{io::Write, path::PathBuf};
+use tansu_server::{
+    NODE_ID, Result,
+    broker::{Broker, replay::Replay},
+    config::Config,
+    coordinator::group::administrator::Controller,
+};
+use tansu_storage::{StorageContainer, dynostore::DynoStore};
+use url::Url;
+use uuid::Uuid;
+
+// replays a capture of length prefixed requests against a memory broker,
+// writing each decoded request and response as a line of JSON
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    #[arg(long, default_value = "tansu")]
+    cluster_id: String,
+
+    capture: PathBuf,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Cli::parse();
+
+    let listener = Url::parse("tcp://localhost:9092/")?;
+
+    let config = Config::builder()
+        .cluster_id(args.cluster_id.as_str())
+        .node_id(NODE_ID)
+        .listener(listener.clone())
+        .advertised_listener(listener)
+        .storage(Url::parse("memory://tansu/")?)
+        .build()?;
+
+    let storage = StorageContainer::DynoStore(DynoStore::new(
+        config.cluster_id(),
+        config.node_id(),
+        InMemory::new(),
+    ));
+
+    let mut broker = Broker::new(
+        &config,
+        storage.clone(),
+        Controller::with_storage(storage)?,
+        Uuid::now_v7(),
+    );
+    broker.register().await?;
+
+    let mut stdout = std::io::stdout().lock();
+
+    for replayed in Replay::with_broker(broker).file(args.capture).await? {
+        serde_json::to_writer(&mut stdout, &replayed)?;
+        writeln!(stdout)?;
+    }
+
+    Ok(())
+}
@@ -14,11 +14,18 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 pub mod api_versions;
+pub mod chaos;
+pub mod connection_limit;
 pub mod create_topic;
 pub mod delete_records;
 pub mod delete_topics;
 pub mod describe_cluster;
 pub mod describe_configs;
+pub mod drain;
+pub mod elect_leaders;
+pub mod events;
+#[cfg(test)]
+pub(crate) mod faulty;
 pub mod fetch;
 pub mod find_coordinator;
 pub mod group;
@@ -26,59 +33,125 @@ pub mod init_producer_id;
 pub mod list_offsets;
 pub mod list_partition_reassignments;
 pub mod metadata;
+pub mod policy;
 pub mod produce;
+mod refusal;
+pub mod replay;
+pub mod sampler;
+pub mod security;
 pub mod telemetry;
 pub mod txn;
 
-use crate::{Error, METER, Result, coordinator::group::Coordinator};
+use crate::{
+    Error, METER, Result,
+    config::Config,
+    coordinator::group::{ConsumerGroupHeartbeat, Coordinator},
+};
 use api_versions::ApiVersionsRequest;
-use create_topic::CreateTopic;
+use bytes::{Bytes, BytesMut};
+use chaos::{Chaos, Fault};
+use connection_limit::ConnectionLimit;
+use create_topic::{CreateTopic, invalid_config};
 use delete_records::DeleteRecordsRequest;
 use delete_topics::DeleteTopicsRequest;
 use describe_cluster::DescribeClusterRequest;
 use describe_configs::DescribeConfigsRequest;
-use fetch::FetchRequest;
+use drain::Drain;
+use elect_leaders::ElectLeadersRequest;
+use events::{Event, Events};
+use fetch::{FetchRequest, Rotation, notifier::Notifier};
 use find_coordinator::FindCoordinatorRequest;
-use init_producer_id::InitProducerIdRequest;
+use init_producer_id::{InitProducerIdRequest, ProducerIdBlock};
 use list_offsets::ListOffsetsRequest;
 use list_partition_reassignments::ListPartitionReassignmentsRequest;
-use metadata::MetadataRequest;
+use metadata::{MetadataCache, MetadataRequest};
 use opentelemetry::{
     KeyValue,
-    metrics::{Counter, Histogram},
+    metrics::{Counter, Histogram, Meter, UpDownCounter},
 };
-use produce::ProduceRequest;
+use policy::{NoPolicy, Policy};
+use produce::{ProduceRequest, config::TopicConfigCache, tee::Tee, transform::Transforms};
+use refusal::Refusal;
+use sampler::Sampler;
+use security::{Listener, SecurityProtocol};
+use socket2::{SockRef, TcpKeepalive};
 use std::{
-    io::ErrorKind,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    io::{self, ErrorKind},
     net::{IpAddr, Ipv6Addr, SocketAddr},
     str::FromStr,
-    time::SystemTime,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
 };
 use tansu_kafka_sans_io::{
-    Body, ErrorCode, Frame, Header, IsolationLevel, consumer_group_describe_response,
-    describe_groups_response,
+    Body, ConfigResource, ErrorCode, Frame, Header, IsolationLevel, OpType,
+    add_partitions_to_txn_response::AddPartitionsToTxnTopicResult,
+    consumer_group_describe_response, describe_groups_response,
+    fetch_response::FetchableTopicResponse,
+    incremental_alter_configs_response::AlterConfigsResourceResponse,
+    produce_request::TopicProduceData,
+    produce_response::TopicProduceResponse,
+    record::deflated::{CompressionLevel, Limit},
+};
+use tansu_storage::{
+    BrokerRegistrationRequest, Storage, TopicId, TopicLimit, TxnAddPartitionsRequest,
+    clock::{Clock, SystemClock},
 };
-use tansu_storage::{BrokerRegistrationRequest, Storage, TopicId};
-use telemetry::GetTelemetrySubscriptionsRequest;
+use telemetry::{GetTelemetrySubscriptionsRequest, PushTelemetryRequest, Telemetry};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream},
     net::{TcpListener, TcpStream},
+    time::{error::Elapsed, interval, sleep, timeout},
 };
-use tracing::{Instrument, Level, Span, debug, debug_span, error, info, span};
+use tracing::{Instrument, Level, Span, debug, debug_span, error, span, warn};
 use txn::{add_offsets::AddOffsets, add_partitions::AddPartitions};
 use url::Url;
 use uuid::Uuid;
 
+// well within the liveness of a broker registration in storage
+const REGISTRATION_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Clone, Debug)]
 pub struct Broker<G, S> {
     node_id: i32,
     cluster_id: String,
     incarnation_id: Uuid,
     listener: Url,
+    listener_name: Option<String>,
+    inter_broker_listener_name: Option<String>,
     advertised_listener: Url,
+    security_protocol: SecurityProtocol,
     storage: S,
     groups: G,
+    record_limit: Limit,
+    compression_level: CompressionLevel,
+    default_isolation_level: IsolationLevel,
+    transactional_acks_all: bool,
+    max_partitions_per_fetch: Option<usize>,
+    fetch_rotation: Option<Rotation>,
+    tee: Option<Tee>,
+    transforms: Transforms,
+    notifier: Notifier,
+    events: Events,
+    metadata_cache: MetadataCache,
+    topic_config_cache: TopicConfigCache,
     metron: Metron,
+    telemetry: Telemetry,
+    socket_options: SocketOptions,
+    connection_limit: ConnectionLimit,
+    request_timeout: Option<Duration>,
+    api_request_timeouts: BTreeMap<i16, Duration>,
+    producer_ids: Option<ProducerIdBlock>,
+    topic_limit: TopicLimit,
+    policy: Arc<dyn Policy>,
+    chaos: Chaos,
+    sampler: Sampler,
+    drain: Drain,
+    clock: Arc<dyn Clock>,
+    connection_attributes: Vec<KeyValue>,
+    request_attributes: BTreeMap<(i16, i16), Vec<KeyValue>>,
+    conn_req_seq: u64,
 }
 
 impl<G, S> Broker<G, S>
@@ -86,31 +159,242 @@ where
     G: Coordinator,
     S: Storage + Clone + 'static,
 {
-    #[allow(clippy::too_many_arguments)]
-    pub fn new(
-        node_id: i32,
-        cluster_id: &str,
-        listener: Url,
-        advertised_listener: Url,
-        storage: S,
-        groups: G,
-        incarnation_id: Uuid,
-    ) -> Self {
+    pub fn new(config: &Config, storage: S, groups: G, incarnation_id: Uuid) -> Self {
+        let metron = Metron::new(config.cluster_id(), incarnation_id);
+        let connection_attributes = vec![metron.cluster_id.clone()];
+
         Self {
-            node_id,
-            cluster_id: cluster_id.to_owned(),
+            node_id: config.node_id(),
+            cluster_id: config.cluster_id().to_owned(),
             incarnation_id,
-            listener,
-            advertised_listener,
+            listener: config.listener().clone(),
+            listener_name: None,
+            inter_broker_listener_name: None,
+            advertised_listener: config.advertised_listener().clone(),
+            security_protocol: SecurityProtocol::default(),
             storage,
             groups,
-            metron: Metron::new(cluster_id, incarnation_id),
+            record_limit: Limit::produce_defaults(),
+            compression_level: CompressionLevel::default(),
+            default_isolation_level: IsolationLevel::default(),
+            transactional_acks_all: true,
+            max_partitions_per_fetch: None,
+            fetch_rotation: None,
+            tee: None,
+            transforms: Transforms::default(),
+            notifier: Notifier::default(),
+            events: Events::default(),
+            metadata_cache: MetadataCache::default(),
+            topic_config_cache: TopicConfigCache::default(),
+            metron,
+            telemetry: Telemetry::default(),
+            socket_options: SocketOptions::default(),
+            connection_limit: ConnectionLimit::default(),
+            request_timeout: None,
+            api_request_timeouts: BTreeMap::new(),
+            producer_ids: None,
+            topic_limit: TopicLimit::default(),
+            policy: Arc::new(NoPolicy),
+            chaos: Chaos::default(),
+            sampler: Sampler::default(),
+            drain: Drain::default(),
+            clock: Arc::new(SystemClock),
+            connection_attributes,
+            request_attributes: BTreeMap::new(),
+            conn_req_seq: 0,
+        }
+    }
+
+    pub fn record_limit(self, record_limit: Limit) -> Self {
+        Self {
+            record_limit,
+            ..self
+        }
+    }
+
+    pub fn compression_level(self, compression_level: CompressionLevel) -> Self {
+        Self {
+            compression_level,
+            ..self
+        }
+    }
+
+    pub fn default_isolation_level(self, default_isolation_level: IsolationLevel) -> Self {
+        Self {
+            default_isolation_level,
+            ..self
+        }
+    }
+
+    pub fn transactional_acks_all(self, transactional_acks_all: bool) -> Self {
+        Self {
+            transactional_acks_all,
+            ..self
+        }
+    }
+
+    pub fn max_partitions_per_fetch(self, max_partitions_per_fetch: Option<usize>) -> Self {
+        Self {
+            max_partitions_per_fetch,
+            ..self
+        }
+    }
+
+    pub fn listener(self, listener: Listener) -> Self {
+        Self {
+            security_protocol: listener.security_protocol(),
+            listener_name: listener.name().map(ToOwned::to_owned),
+            listener: listener.url().clone(),
+            ..self
+        }
+    }
+
+    // the listener dedicated to inter-broker requests, which are refused
+    // on any other listener, or permitted on every listener when unset
+    pub fn inter_broker_listener_name(self, inter_broker_listener_name: Option<String>) -> Self {
+        Self {
+            inter_broker_listener_name,
+            ..self
+        }
+    }
+
+    fn refuses(&self, body: &Body) -> bool {
+        self.inter_broker_listener_name
+            .as_ref()
+            .is_some_and(|inter_broker| self.listener_name.as_ref() != Some(inter_broker))
+            && security::inter_broker(body)
+    }
+
+    pub fn tee(self, tee: Option<Tee>) -> Self {
+        Self { tee, ..self }
+    }
+
+    pub fn transforms(self, transforms: Transforms) -> Self {
+        Self { transforms, ..self }
+    }
+
+    pub fn notifier(self, notifier: Notifier) -> Self {
+        Self { notifier, ..self }
+    }
+
+    pub fn events(self, events: Events) -> Self {
+        Self { events, ..self }
+    }
+
+    pub fn metadata_cache(self, metadata_cache: MetadataCache) -> Self {
+        Self {
+            metadata_cache,
+            ..self
+        }
+    }
+
+    pub fn topic_config_cache(self, topic_config_cache: TopicConfigCache) -> Self {
+        Self {
+            topic_config_cache,
+            ..self
+        }
+    }
+
+    pub fn metric_topics(self, metric_topics: Option<BTreeSet<String>>) -> Self {
+        Self {
+            metron: self.metron.topics(metric_topics),
+            ..self
+        }
+    }
+
+    pub fn telemetry(self, telemetry: Telemetry) -> Self {
+        Self { telemetry, ..self }
+    }
+
+    pub fn socket_options(self, socket_options: SocketOptions) -> Self {
+        Self {
+            socket_options,
+            ..self
+        }
+    }
+
+    pub fn connection_limit(self, connection_limit: ConnectionLimit) -> Self {
+        Self {
+            connection_limit,
+            ..self
+        }
+    }
+
+    pub fn request_timeout(self, request_timeout: Option<Duration>) -> Self {
+        Self {
+            request_timeout,
+            ..self
+        }
+    }
+
+    pub fn api_request_timeouts(self, api_request_timeouts: BTreeMap<i16, Duration>) -> Self {
+        Self {
+            api_request_timeouts,
+            ..self
+        }
+    }
+
+    pub fn producer_ids(self, producer_ids: Option<ProducerIdBlock>) -> Self {
+        Self {
+            producer_ids,
+            ..self
+        }
+    }
+
+    pub fn topic_limit(self, topic_limit: TopicLimit) -> Self {
+        Self {
+            topic_limit,
+            ..self
+        }
+    }
+
+    pub fn policy(self, policy: Arc<dyn Policy>) -> Self {
+        Self { policy, ..self }
+    }
+
+    pub fn chaos(self, chaos: Chaos) -> Self {
+        Self { chaos, ..self }
+    }
+
+    pub fn sampler(self, sampler: Sampler) -> Self {
+        Self { sampler, ..self }
+    }
+
+    pub fn drain(self, drain: Drain) -> Self {
+        Self { drain, ..self }
+    }
+
+    pub fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            metron: self.metron.clock(clock.clone()),
+            storage: self.storage.clock(clock.clone()),
+            groups: self.groups.clock(clock.clone()),
+            clock,
+            ..self
         }
     }
 
     pub async fn serve(&mut self) -> Result<()> {
         self.register().await?;
-        self.listen().await
+
+        // the registration is refreshed, so that this broker remains live
+        let mut registrar = self.clone();
+        let refresh = tokio::spawn(async move {
+            let mut interval = interval(REGISTRATION_INTERVAL);
+            _ = interval.tick().await;
+
+            loop {
+                _ = interval.tick().await;
+
+                if let Err(error) = registrar.register().await {
+                    warn!(?error);
+                }
+            }
+        });
+
+        let listening = self.listen().await;
+        refresh.abort();
+        listening
     }
 
     pub async fn register(&mut self) -> Result<()> {
@@ -122,11 +406,23 @@ where
                 rack: None,
             })
             .await
+            .inspect(|()| self.metadata_cache.invalidate())
             .map_err(Into::into)
     }
 
     pub async fn listen(&self) -> Result<()> {
-        debug!(listener = %self.listener, advertised_listener = %self.advertised_listener);
+        debug!(
+            listener = %self.listener,
+            advertised_listener = %self.advertised_listener,
+            security_protocol = %self.security_protocol
+        );
+
+        // there is no support for encrypted or authenticated connections
+        if self.security_protocol.requires_encryption()
+            || self.security_protocol.requires_authentication()
+        {
+            return Err(Error::UnsupportedSecurityProtocol(self.security_protocol));
+        }
 
         let listener = TcpListener::bind(self.listener.host().map_or_else(
             || {
@@ -148,16 +444,31 @@ where
         ))
         .await?;
 
+        self.accept(listener).await
+    }
+
+    async fn accept(&self, listener: TcpListener) -> Result<()> {
         loop {
             let (stream, addr) = listener.accept().await?;
             debug!(?addr);
 
+            let Some(permit) = self.connection_limit.acquire(addr.ip()) else {
+                drop(stream);
+                continue;
+            };
+
+            if let Err(error) = self.socket_options.apply(&stream) {
+                warn!(?addr, ?error);
+            }
+
             let mut broker = self.clone();
 
             _ = tokio::spawn(async move {
                 let span = span!(Level::DEBUG, "peer", addr = %addr);
 
-                async move {
+                let _connected = broker.metron.connected();
+
+                async {
                     match broker.stream_handler(&addr, stream).await {
                         Err(Error::Io(ref io))
                             if io.kind() == ErrorKind::UnexpectedEof
@@ -172,73 +483,94 @@ where
                     }
                 }
                 .instrument(span)
-                .await
+                .await;
+
+                drop(permit);
             });
         }
     }
 
-    async fn stream_handler(&mut self, peer: &SocketAddr, mut stream: TcpStream) -> Result<()> {
-        debug!(?stream);
+    pub async fn stream_handler<T>(&mut self, peer: &SocketAddr, stream: T) -> Result<()>
+    where
+        T: AsyncRead + AsyncWrite + Debug + Unpin,
+    {
+        debug!(?stream, security_protocol = %self.security_protocol);
 
-        let mut size = [0u8; 4];
+        let mut stream = BufStream::with_capacity(
+            self.socket_options.read_buffer_size,
+            self.socket_options.write_buffer_size,
+            stream,
+        );
 
         loop {
-            _ = stream
-                .read_exact(&mut size)
-                .await
-                .inspect_err(|error| match error.kind() {
-                    ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset => (),
-
-                    _ => error!(?error),
-                })?;
-
-            if i32::from_be_bytes(size) == 0 {
-                info!("empty read!");
-                continue;
-            }
-
-            let mut request: Vec<u8> = vec![0u8; i32::from_be_bytes(size) as usize + size.len()];
-            request[0..4].copy_from_slice(&size[..]);
-
-            _ = stream
-                .read_exact(&mut request[4..])
-                .await
-                .inspect_err(|error| error!(?size, ?request, ?error))?;
+            let Some(request) =
+                read_frame(&mut stream, self.socket_options.max_request_size).await?
+            else {
+                return Ok(());
+            };
             debug!(?request);
 
-            let request_start = SystemTime::now();
-
-            let attributes = [KeyValue::new("cluster_id", self.cluster_id.clone())];
+            let request_start = self.clock.now();
 
             self.metron
                 .request_size
-                .record(request.len() as u64, &attributes);
+                .record(request.len() as u64, &self.connection_attributes);
 
-            let response = self
-                .process_request(peer, &request)
-                .await
-                .inspect_err(|error| error!(?request, ?error))?;
+            let in_flight = self.metron.in_flight();
+            let response = self.process_request(peer, &request).await;
+            drop(in_flight);
+
+            let response = response.inspect_err(|error| error!(?request, ?error))?;
             debug!(?response);
 
             self.metron
                 .response_size
-                .record(response.len() as u64, &attributes);
+                .record(response.len() as u64, &self.connection_attributes);
             self.metron.request_duration.record(
-                request_start
-                    .elapsed()
-                    .map_or(0, |duration| duration.as_millis() as u64),
-                &attributes,
+                self.clock.elapsed(request_start).as_millis() as u64,
+                &self.connection_attributes,
             );
 
             stream
                 .write_all(&response)
                 .await
                 .inspect_err(|error| error!(?request, ?response, ?error))?;
+
+            stream
+                .flush()
+                .await
+                .inspect_err(|error| error!(?request, ?error))?;
         }
     }
 
-    async fn process_request(&mut self, _peer: &SocketAddr, input: &[u8]) -> Result<Vec<u8>> {
-        match Frame::request_from_bytes(input)? {
+    fn client_software(&mut self, name: Option<&str>, version: Option<&str>) {
+        debug!(?name, ?version);
+
+        self.connection_attributes = [
+            Some(self.metron.cluster_id.clone()),
+            name.map(|name| KeyValue::new("client_software_name", client_software_label(name))),
+            version.map(|version| {
+                KeyValue::new("client_software_version", client_software_label(version))
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        self.request_attributes.clear();
+    }
+
+    pub async fn process_request(&mut self, peer: &SocketAddr, input: &Bytes) -> Result<Vec<u8>> {
+        if let Some(response) = ApiVersionsRequest::unsupported_version(input)? {
+            return Ok(response);
+        }
+
+        let frame = match Frame::request_from_bytes(input) {
+            Ok(frame) => frame,
+            Err(error) => return corrupt_request(input, error),
+        };
+
+        match frame {
             Frame {
                 header:
                     Header::Request {
@@ -251,25 +583,146 @@ where
                 body,
                 ..
             } => {
-                let span = request_span(api_key, api_version, correlation_id, &body);
+                // requests on this connection are numbered, so that they
+                // can be grouped when debugging client retries
+                self.conn_req_seq += 1;
+
+                let span = request_span(
+                    peer,
+                    self.conn_req_seq,
+                    api_key,
+                    api_version,
+                    correlation_id,
+                    &body,
+                );
+
+                if let Body::ApiVersionsRequest {
+                    client_software_name,
+                    client_software_version,
+                } = &body
+                {
+                    self.client_software(
+                        client_software_name.as_deref(),
+                        client_software_version.as_deref(),
+                    );
+                }
 
                 {
-                    let mut attributes = attributes(api_key, api_version, correlation_id, &body);
-                    attributes.push(KeyValue::new("cluster_id", self.cluster_id.clone()));
-                    self.metron.api_requests.add(1, &attributes);
+                    let attributes = self
+                        .request_attributes
+                        .entry((api_key, api_version))
+                        .or_insert_with(|| {
+                            [
+                                KeyValue::new("api_key", api_key as i64),
+                                KeyValue::new("api_version", api_version as i64),
+                            ]
+                            .into_iter()
+                            .chain(self.connection_attributes.iter().cloned())
+                            .chain(
+                                api_name(&body).map(|api_name| KeyValue::new("api_name", api_name)),
+                            )
+                            .collect()
+                        });
+
+                    self.metron.api_requests.add(1, attributes);
                 }
 
+                let deadline = self
+                    .api_request_timeouts
+                    .get(&api_key)
+                    .copied()
+                    .or(self.request_timeout);
+
+                let delay = self.chaos.delay(api_key);
+                let fault = self.chaos.fault(api_key);
+
+                // full bodies are only logged for sampled or failed requests,
+                // the sampler is not consulted unless debug is enabled
+                let sampled = tracing::enabled!(Level::DEBUG) && self.sampler.sample();
+                let mut failed = false;
+
                 async move {
+                    if let Some(delay) = delay {
+                        sleep(delay).await;
+                    }
+
+                    if sampled {
+                        debug!(request = ?body);
+                    }
+
+                    let refusal = Refusal::from(&body);
+
+                    let body = match fault {
+                        Some(Fault::Disconnect) => {
+                            warn!(api_key, api_version, "injected disconnect");
+                            return Err(io::Error::from(ErrorKind::ConnectionAborted).into());
+                        }
+
+                        Some(Fault::Error(error_code)) => {
+                            warn!(api_key, api_version, %error_code, "injected error");
+                            failed = true;
+                            refusal.error_response(api_key, api_version, error_code)?
+                        }
+
+                        None => {
+                            let response = if let Some(deadline) = deadline {
+                                self.response_within(
+                                    deadline,
+                                    *peer,
+                                    client_id,
+                                    body,
+                                    correlation_id,
+                                )
+                                .await
+                                .map_err(|_| deadline)
+                            } else {
+                                Ok(self
+                                    .response_for(peer, client_id.as_deref(), body, correlation_id)
+                                    .await)
+                            };
+
+                            match response {
+                                Err(deadline) => {
+                                    warn!(api_key, api_version, ?deadline);
+                                    failed = true;
+                                    refusal.error_response(
+                                        api_key,
+                                        api_version,
+                                        ErrorCode::RequestTimedOut,
+                                    )?
+                                }
+
+                                Ok(Ok(body)) => body,
+
+                                Ok(Err(error)) => {
+                                    let Some(error_code) = error.error_code() else {
+                                        error!(?error);
+                                        return Err(error);
+                                    };
+
+                                    warn!(api_key, api_version, ?error, ?error_code);
+                                    failed = true;
+                                    refusal.error_response(api_key, api_version, error_code)?
+                                }
+                            }
+                        }
+                    };
+
+                    if sampled || failed {
+                        debug!(?body);
+                    }
+
                     Frame::response(
                         Header::Response { correlation_id },
-                        self.response_for(client_id.as_deref(), body, correlation_id)
-                            .await
-                            .inspect(|body| debug!(?body))
-                            .inspect_err(|err| error!(?err))?,
+                        body,
                         api_key,
                         api_version,
                     )
-                    .inspect(|response| debug!(?response))
+                    .inspect(|response| {
+                        if sampled || failed {
+                            debug!(?response)
+                        }
+                    })
                     .inspect_err(|err| error!(?err))
                     .map_err(Into::into)
                 }
@@ -281,13 +734,75 @@ where
         }
     }
 
+    // a request exceeding its deadline is answered with an error, while
+    // the request itself runs to completion in the background rather than
+    // being cancelled part way through storage. Any connection state that
+    // it changes is kept only when it completes within the deadline
+    async fn response_within(
+        &mut self,
+        deadline: Duration,
+        peer: SocketAddr,
+        client_id: Option<String>,
+        body: Body,
+        correlation_id: i32,
+    ) -> Result<Result<Body>, Elapsed> {
+        let mut broker = self.clone();
+
+        let request = tokio::spawn(
+            async move {
+                let response = broker
+                    .response_for(&peer, client_id.as_deref(), body, correlation_id)
+                    .await;
+                (broker, response)
+            }
+            .in_current_span(),
+        );
+
+        match timeout(deadline, request).await? {
+            Ok((broker, response)) => {
+                *self = broker;
+                Ok(response)
+            }
+
+            Err(join_error) => Ok(Err(Error::Message(join_error.to_string()))),
+        }
+    }
+
     pub async fn response_for(
         &mut self,
+        peer: &SocketAddr,
         client_id: Option<&str>,
         body: Body,
         correlation_id: i32,
     ) -> Result<Body> {
-        debug!(?body, ?correlation_id);
+        debug!(?correlation_id);
+
+        if !self.security_protocol.permits(&body) {
+            warn!(
+                %peer,
+                security_protocol = %self.security_protocol,
+                api_name = api_name(&body),
+                correlation_id,
+                "unauthenticated"
+            );
+            return Err(Error::Api(ErrorCode::SaslAuthenticationFailed));
+        }
+
+        if self.refuses(&body) {
+            warn!(
+                %peer,
+                listener_name = ?self.listener_name,
+                api_name = api_name(&body),
+                correlation_id,
+                "inter-broker only"
+            );
+            return Err(Error::Api(ErrorCode::ClusterAuthorizationFailed));
+        }
+
+        if let Some(refused) = self.drain.refused(&body) {
+            warn!(%peer, api_name = api_name(&body), correlation_id, "draining");
+            return Ok(refused);
+        }
 
         match body {
             Body::AddOffsetsToTxnRequest {
@@ -309,9 +824,29 @@ where
             }
 
             add_partitions @ Body::AddPartitionsToTxnRequest { .. } => {
-                AddPartitions::with_storage(self.storage.clone())
-                    .response(add_partitions.try_into()?)
-                    .await
+                let request = TxnAddPartitionsRequest::try_from(add_partitions)?;
+
+                let transaction_ids = match request {
+                    TxnAddPartitionsRequest::VersionZeroToThree {
+                        ref transaction_id, ..
+                    } => vec![transaction_id.to_owned()],
+
+                    TxnAddPartitionsRequest::VersionFourPlus { ref transactions } => transactions
+                        .iter()
+                        .filter(|transaction| !transaction.verify_only)
+                        .map(|transaction| transaction.transactional_id.to_owned())
+                        .collect(),
+                };
+
+                let body = AddPartitions::with_storage(self.storage.clone())
+                    .response(request)
+                    .await?;
+
+                for transaction_id in added_to_txn(&transaction_ids, &body) {
+                    self.metron.txn_added(transaction_id)?;
+                }
+
+                Ok(body)
             }
 
             Body::ApiVersionsRequest {
@@ -347,6 +882,30 @@ where
                 })
                 .map_err(Into::into),
 
+            Body::ConsumerGroupHeartbeatRequest {
+                group_id,
+                member_id,
+                member_epoch,
+                subscribed_topic_names,
+                ..
+            } => {
+                debug!(
+                    ?group_id,
+                    ?member_id,
+                    ?member_epoch,
+                    ?subscribed_topic_names
+                );
+
+                self.groups
+                    .consumer_group_heartbeat(ConsumerGroupHeartbeat {
+                        group_id: group_id.as_str(),
+                        member_id: member_id.as_str(),
+                        member_epoch,
+                        subscribed_topic_names: subscribed_topic_names.as_deref(),
+                    })
+                    .await
+            }
+
             Body::CreateTopicsRequest {
                 validate_only,
                 topics,
@@ -354,8 +913,21 @@ where
             } => {
                 debug!(?validate_only, ?topics);
                 CreateTopic::with_storage(self.storage.clone())
+                    .topic_limit(self.topic_limit)
+                    .policy(self.policy.clone())
                     .response(topics, validate_only.unwrap_or(false))
                     .await
+                    .inspect(|topics| {
+                        if !validate_only.unwrap_or(false) {
+                            self.metadata_cache.invalidate();
+
+                            for topic in topics {
+                                self.topic_config_cache.invalidate(&topic.name);
+                            }
+
+                            self.events.topics_created(topics)
+                        }
+                    })
                     .map(Some)
                     .map(|topics| Body::CreateTopicsResponse {
                         throttle_time_ms: Some(0),
@@ -394,6 +966,15 @@ where
                     responses: DeleteTopicsRequest::with_storage(self.storage.clone())
                         .response(topics, topic_names)
                         .await
+                        .inspect(|topics| {
+                            self.metadata_cache.invalidate();
+
+                            for name in topics.iter().filter_map(|topic| topic.name.as_deref()) {
+                                self.topic_config_cache.invalidate(name);
+                            }
+
+                            self.events.topics_deleted(topics)
+                        })
                         .map(Some)?,
                 })
             }
@@ -420,6 +1001,9 @@ where
                 debug!(?resources, ?include_synonyms, ?include_documentation,);
 
                 DescribeConfigsRequest::with_storage(self.storage.clone())
+                    .node_id(self.node_id)
+                    .listener(self.listener.clone())
+                    .advertised_listener(self.advertised_listener.clone())
                     .response(
                         resources.as_deref(),
                         include_synonyms,
@@ -478,12 +1062,26 @@ where
                 })
                 .map_err(Into::into),
 
+            Body::ElectLeadersRequest {
+                election_type,
+                topic_partitions,
+                timeout_ms,
+            } => {
+                debug!(?election_type, ?topic_partitions, timeout_ms);
+
+                ElectLeadersRequest::with_storage(self.storage.clone())
+                    .response(election_type, topic_partitions.as_deref(), timeout_ms)
+                    .await
+                    .inspect(|_| self.metadata_cache.invalidate())
+            }
+
             Body::FetchRequest {
                 max_wait_ms,
                 min_bytes,
                 max_bytes,
                 isolation_level,
                 topics,
+                rack_id,
                 ..
             } => {
                 debug!(
@@ -492,9 +1090,19 @@ where
                     ?max_bytes,
                     ?isolation_level,
                     ?topics,
+                    ?rack_id,
                 );
 
-                FetchRequest::with_storage(self.storage.clone())
+                // each connection is cloned from the listening broker, with
+                // the rotation of capped fetches carried between its requests
+                let mut fetch = FetchRequest::with_storage(self.storage.clone())
+                    .default_isolation_level(self.default_isolation_level)
+                    .rack_id(rack_id)
+                    .notifier(Some(self.notifier.clone()))
+                    .max_partitions(self.max_partitions_per_fetch)
+                    .rotation(self.fetch_rotation.take());
+
+                let response = fetch
                     .response(
                         max_wait_ms,
                         min_bytes,
@@ -502,8 +1110,18 @@ where
                         isolation_level,
                         topics.as_deref(),
                     )
-                    .await
+                    .await;
+
+                self.fetch_rotation = fetch.next_rotation();
+
+                response
                     .inspect(|r| debug!(?r))
+                    .inspect(|body| {
+                        if let Body::FetchResponse { responses, .. } = body {
+                            self.metron
+                                .fetched(self.cluster_id.as_str(), responses.as_deref())
+                        }
+                    })
                     .inspect_err(|error| error!(?error))
             }
 
@@ -527,7 +1145,8 @@ where
 
             Body::GetTelemetrySubscriptionsRequest { client_instance_id } => {
                 debug!(?client_instance_id);
-                let get_telemetry_subscriptions = GetTelemetrySubscriptionsRequest;
+                let get_telemetry_subscriptions =
+                    GetTelemetrySubscriptionsRequest::with_telemetry(self.telemetry.clone());
                 Ok(get_telemetry_subscriptions.response(client_instance_id))
             }
 
@@ -557,7 +1176,56 @@ where
                 let mut responses = vec![];
 
                 for resource in resources.unwrap_or_default() {
+                    if let Err(error_message) = self.policy.validate_alter_config(&resource) {
+                        debug!(error_message);
+
+                        responses.push(AlterConfigsResourceResponse {
+                            error_code: ErrorCode::PolicyViolation.into(),
+                            error_message: Some(error_message),
+                            resource_type: resource.resource_type,
+                            resource_name: resource.resource_name,
+                        });
+
+                        continue;
+                    }
+
+                    let topic = (ConfigResource::from(resource.resource_type)
+                        == ConfigResource::Topic)
+                        .then(|| resource.resource_name.clone());
+
+                    if let Some(error) = topic.as_deref().and_then(|topic| {
+                        resource
+                            .configs
+                            .as_deref()
+                            .unwrap_or_default()
+                            .iter()
+                            .filter(|config| {
+                                matches!(OpType::try_from(config.config_operation), Ok(OpType::Set))
+                            })
+                            .find_map(|config| {
+                                config
+                                    .value
+                                    .as_deref()
+                                    .and_then(|value| invalid_config(topic, &config.name, value))
+                            })
+                    }) {
+                        debug!(?error);
+
+                        responses.push(AlterConfigsResourceResponse {
+                            error_code: ErrorCode::InvalidConfig.into(),
+                            error_message: Some(error.to_string()),
+                            resource_type: resource.resource_type,
+                            resource_name: resource.resource_name,
+                        });
+
+                        continue;
+                    }
+
                     responses.push(self.storage.incremental_alter_resource(resource).await?);
+
+                    if let Some(topic) = topic {
+                        self.topic_config_cache.invalidate(&topic);
+                    }
                 }
 
                 Ok(Body::IncrementalAlterConfigsResponse {
@@ -579,20 +1247,30 @@ where
                     ?producer_epoch,
                 );
 
-                InitProducerIdRequest::with_storage(self.storage.clone())
+                let response = InitProducerIdRequest::with_storage(self.storage.clone())
+                    .producer_ids(self.producer_ids.clone())
                     .response(
                         transactional_id.as_deref(),
                         transaction_timeout_ms,
                         producer_id,
                         producer_epoch,
                     )
-                    .await
-                    .map(|response| Body::InitProducerIdResponse {
-                        throttle_time_ms: 0,
-                        error_code: response.error.into(),
-                        producer_id: response.id,
-                        producer_epoch: response.epoch,
-                    })
+                    .await?;
+
+                if let Some(transaction_id) = transactional_id
+                    .as_deref()
+                    .filter(|_| response.error == ErrorCode::None)
+                {
+                    self.metron
+                        .txn_initialised(transaction_id, transaction_timeout_ms)?;
+                }
+
+                Ok(Body::InitProducerIdResponse {
+                    throttle_time_ms: 0,
+                    error_code: response.error.into(),
+                    producer_id: response.id,
+                    producer_epoch: response.epoch,
+                })
             }
 
             Body::JoinGroupRequest {
@@ -616,9 +1294,13 @@ where
                     ?reason,
                 );
 
+                // formatted as a java InetAddress, as reported by kafka brokers
+                let client_host = format!("/{}", peer.ip());
+
                 self.groups
                     .join(
                         client_id,
+                        Some(client_host.as_str()),
                         &group_id,
                         session_timeout_ms,
                         rebalance_timeout_ms,
@@ -629,6 +1311,7 @@ where
                         reason.as_deref(),
                     )
                     .await
+                    .inspect(|body| self.events.joined(&group_id, body))
             }
 
             Body::LeaveGroupRequest {
@@ -649,7 +1332,7 @@ where
             } => {
                 debug!(?states_filter, ?types_filter);
                 self.storage
-                    .list_groups(states_filter.as_deref())
+                    .list_groups(states_filter.as_deref(), types_filter.as_deref())
                     .await
                     .map(Some)
                     .map(|groups| Body::ListGroupsResponse {
@@ -667,12 +1350,8 @@ where
             } => {
                 debug!(?replica_id, ?isolation_level, ?topics);
 
-                let isolation_level = isolation_level
-                    .map_or(Ok(IsolationLevel::ReadUncommitted), |isolation_level| {
-                        IsolationLevel::try_from(isolation_level)
-                    })?;
-
                 ListOffsetsRequest::with_storage(self.storage.clone())
+                    .default_isolation_level(self.default_isolation_level)
                     .response(replica_id, isolation_level, topics.as_deref())
                     .await
             }
@@ -688,6 +1367,8 @@ where
             Body::MetadataRequest { topics, .. } => {
                 debug!(?topics);
                 MetadataRequest::with_storage(self.storage.clone())
+                    .cache(self.metadata_cache.clone())
+                    .clock(self.clock.clone())
                     .response(topics)
                     .await
             }
@@ -738,16 +1419,56 @@ where
                     .await
             }
 
+            Body::PushTelemetryRequest {
+                client_instance_id,
+                subscription_id,
+                terminating,
+                compression_type,
+                metrics,
+            } => {
+                debug!(
+                    ?client_instance_id,
+                    subscription_id, terminating, compression_type
+                );
+
+                let push_telemetry = PushTelemetryRequest::with_telemetry(self.telemetry.clone());
+                Ok(push_telemetry.response(
+                    client_instance_id,
+                    subscription_id,
+                    terminating,
+                    compression_type,
+                    &metrics,
+                ))
+            }
+
             Body::ProduceRequest {
                 transactional_id,
                 acks,
                 timeout_ms,
                 topic_data,
             } => {
-                debug!(?transactional_id, ?acks, ?timeout_ms, ?topic_data);
+                debug!(?transactional_id, ?acks, ?timeout_ms);
+
+                let produced = Metron::produce_sizes(topic_data.as_deref());
+
                 ProduceRequest::with_storage(self.storage.clone())
+                    .record_limit(self.record_limit)
+                    .compression_level(self.compression_level)
+                    .tee(self.tee.clone())
+                    .transforms(self.transforms.clone())
+                    .clock(self.clock.clone())
+                    .notifier(Some(self.notifier.clone()))
+                    .transactional_acks_all(self.transactional_acks_all)
+                    .topic_config_cache(self.topic_config_cache.clone())
                     .response(transactional_id, acks, timeout_ms, topic_data)
                     .await
+                    .inspect(|response| {
+                        self.metron.produced(
+                            self.cluster_id.as_str(),
+                            &produced,
+                            response.responses.as_deref(),
+                        )
+                    })
                     .map(|response| Body::ProduceResponse {
                         responses: response.responses,
                         throttle_time_ms: response.throttle_time_ms,
@@ -817,120 +1538,245 @@ where
                 producer_id,
                 producer_epoch,
                 committed,
-            } => self
-                .storage
-                .txn_end(
-                    transactional_id.as_str(),
-                    producer_id,
-                    producer_epoch,
-                    committed,
-                )
-                .await
-                .map(|error_code| Body::EndTxnResponse {
+            } => {
+                let error_code = self
+                    .storage
+                    .txn_end(
+                        transactional_id.as_str(),
+                        producer_id,
+                        producer_epoch,
+                        committed,
+                    )
+                    .await?;
+
+                if error_code == ErrorCode::None {
+                    self.metron
+                        .txn_ended(transactional_id.as_str(), committed)?;
+
+                    self.events.publish(Event::TransactionEnded {
+                        transaction_id: transactional_id,
+                        producer_id,
+                        committed,
+                    });
+                }
+
+                Ok(Body::EndTxnResponse {
                     throttle_time_ms: 0,
                     error_code: i16::from(error_code),
                 })
-                .map_err(Into::into),
-
-            request => {
-                error!(?request);
-                unimplemented!("{request:?}")
             }
+
+            request => Err(Error::UnsupportedRequest(Box::new(request))),
         }
     }
 }
 
-fn attributes(api_key: i16, api_version: i16, _correlation_id: i32, body: &Body) -> Vec<KeyValue> {
-    let mut attributes = vec![
-        KeyValue::new("api_key", api_key as i64),
-        KeyValue::new("api_version", api_version as i64),
-    ];
+// the transactions with at least one partition successfully added
+fn added_to_txn<'a>(transaction_ids: &'a [String], body: &'a Body) -> Vec<&'a str> {
+    let Body::AddPartitionsToTxnResponse {
+        results_by_transaction,
+        results_by_topic_v_3_and_below,
+        ..
+    } = body
+    else {
+        return vec![];
+    };
 
-    match body {
-        Body::AddOffsetsToTxnRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "add_offsets_to_txn")])
-        }
+    let added = |topic_results: &[AddPartitionsToTxnTopicResult]| {
+        topic_results.iter().any(|topic| {
+            topic
+                .results_by_partition
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .any(|partition| partition.partition_error_code == i16::from(ErrorCode::None))
+        })
+    };
 
-        Body::AddPartitionsToTxnRequest { .. } => attributes.append(&mut vec![KeyValue::new(
-            "api_name",
-            "add_partitions_to_txn",
-        )]),
+    match results_by_transaction.as_deref() {
+        Some(results) if !results.is_empty() => results
+            .iter()
+            .filter(|result| added(result.topic_results.as_deref().unwrap_or_default()))
+            .map(|result| result.transactional_id.as_str())
+            .filter(|transaction_id| transaction_ids.iter().any(|id| id == transaction_id))
+            .collect(),
 
-        Body::ApiVersionsRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "api_versions")])
-        }
+        _ => transaction_ids
+            .first()
+            .filter(|_| {
+                added(
+                    results_by_topic_v_3_and_below
+                        .as_deref()
+                        .unwrap_or_default(),
+                )
+            })
+            .map(String::as_str)
+            .into_iter()
+            .collect(),
+    }
+}
 
-        Body::CreateTopicsRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "create_topics")])
-        }
+async fn read_fully<R>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut received = 0;
 
-        Body::DeleteTopicsRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "delete_topics")])
+    while received < buf.len() {
+        match reader.read(&mut buf[received..]).await {
+            Ok(0) => break,
+            Ok(n) => received += n,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
         }
+    }
 
-        Body::EndTxnRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "end_txn")])
-        }
+    Ok(received)
+}
 
-        Body::FetchRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "fetch")]);
-        }
+async fn read_frame<R>(reader: &mut R, max_size: usize) -> Result<Option<Bytes>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut size = [0u8; 4];
 
-        Body::FindCoordinatorRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "find_coordinator")])
-        }
+    loop {
+        let received = match read_fully(reader, &mut size).await {
+            Ok(received) => received,
 
-        Body::HeartbeatRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "heartbeat")]);
-        }
+            Err(error) if error.kind() == ErrorKind::ConnectionReset => {
+                debug!(?error);
+                return Ok(None);
+            }
 
-        Body::InitProducerIdRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "init_producer_id")]);
-        }
+            Err(error) => return Err(error.into()),
+        };
 
-        Body::JoinGroupRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "join_group")]);
+        if received == 0 {
+            debug!("disconnected");
+            return Ok(None);
         }
 
-        Body::LeaveGroupRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "leave_group")]);
+        if received < size.len() {
+            warn!(expected = size.len(), received, "truncated frame size");
+            return Ok(None);
         }
 
-        Body::ListOffsetsRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "list_offsets")]);
-        }
+        match i32::from_be_bytes(size) {
+            0 => {
+                debug!("empty read!");
+                continue;
+            }
 
-        Body::MetadataRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "metadata")]);
-        }
+            length if length < 0 => {
+                warn!(length, "invalid frame size");
+                return Ok(None);
+            }
 
-        Body::OffsetCommitRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "offset_commit")]);
-        }
+            // rejected before any allocation is made for the frame
+            length if length as usize > max_size => {
+                warn!(length, max_size, "frame too large");
+                return Ok(None);
+            }
 
-        Body::OffsetFetchRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "offset_fetch")]);
-        }
+            length => {
+                let mut request = BytesMut::zeroed(length as usize + size.len());
+                request[0..4].copy_from_slice(&size[..]);
 
-        Body::ProduceRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "produce")]);
-        }
+                let received = match read_fully(reader, &mut request[4..]).await {
+                    Ok(received) => received,
 
-        Body::SyncGroupRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "sync_group")]);
-        }
+                    Err(error) if error.kind() == ErrorKind::ConnectionReset => {
+                        debug!(?error);
+                        return Ok(None);
+                    }
 
-        Body::TxnOffsetCommitRequest { .. } => {
-            attributes.append(&mut vec![KeyValue::new("api_name", "txn_offset_commit")]);
+                    Err(error) => return Err(error.into()),
+                };
+
+                if received < length as usize {
+                    warn!(expected = length, received, "truncated frame");
+                    return Ok(None);
+                }
+
+                return Ok(Some(request.freeze()));
+            }
         }
+    }
+}
+
+// a request that could not be decoded is answered with a corrupt message
+// error when its header can be read, otherwise the connection is closed
+fn corrupt_request(input: &[u8], error: tansu_kafka_sans_io::Error) -> Result<Vec<u8>> {
+    let error_code = ErrorCode::CorruptMessage;
 
-        _ => (),
+    // size, api key, api version and correlation id
+    let Some(header) = input.get(4..12) else {
+        error!(?error);
+        return Err(error.into());
     };
 
-    attributes
+    let api_key = i16::from_be_bytes([header[0], header[1]]);
+    let api_version = i16::from_be_bytes([header[2], header[3]]);
+    let correlation_id = i32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+
+    // an empty response would read as success for every topic requested
+    if Refusal::is_partitioned(api_key) {
+        error!(api_key, api_version, correlation_id, ?error);
+        return Err(error.into());
+    }
+
+    warn!(api_key, api_version, correlation_id, ?error, ?error_code);
+
+    Body::error_response(api_key, api_version, error_code)
+        .and_then(|body| {
+            Frame::response(
+                Header::Response { correlation_id },
+                body,
+                api_key,
+                api_version,
+            )
+        })
+        .map_err(|unanswerable| {
+            error!(?error, ?unanswerable);
+            error.into()
+        })
+}
+
+fn api_name(body: &Body) -> Option<&'static str> {
+    match body {
+        Body::AddOffsetsToTxnRequest { .. } => Some("add_offsets_to_txn"),
+        Body::AddPartitionsToTxnRequest { .. } => Some("add_partitions_to_txn"),
+        Body::ApiVersionsRequest { .. } => Some("api_versions"),
+        Body::ConsumerGroupHeartbeatRequest { .. } => Some("consumer_group_heartbeat"),
+        Body::CreateTopicsRequest { .. } => Some("create_topics"),
+        Body::DeleteTopicsRequest { .. } => Some("delete_topics"),
+        Body::EndTxnRequest { .. } => Some("end_txn"),
+        Body::FetchRequest { .. } => Some("fetch"),
+        Body::FindCoordinatorRequest { .. } => Some("find_coordinator"),
+        Body::HeartbeatRequest { .. } => Some("heartbeat"),
+        Body::InitProducerIdRequest { .. } => Some("init_producer_id"),
+        Body::JoinGroupRequest { .. } => Some("join_group"),
+        Body::LeaveGroupRequest { .. } => Some("leave_group"),
+        Body::ListOffsetsRequest { .. } => Some("list_offsets"),
+        Body::MetadataRequest { .. } => Some("metadata"),
+        Body::OffsetCommitRequest { .. } => Some("offset_commit"),
+        Body::OffsetFetchRequest { .. } => Some("offset_fetch"),
+        Body::ProduceRequest { .. } => Some("produce"),
+        Body::SyncGroupRequest { .. } => Some("sync_group"),
+        Body::TxnOffsetCommitRequest { .. } => Some("txn_offset_commit"),
+        _ => None,
+    }
 }
 
-fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body) -> Span {
+fn request_span(
+    peer: &SocketAddr,
+    conn_req_seq: u64,
+    api_key: i16,
+    api_version: i16,
+    correlation_id: i32,
+    body: &Body,
+) -> Span {
     match body {
         Body::AddOffsetsToTxnRequest {
             transactional_id,
@@ -944,6 +1790,8 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
                 api_key,
                 api_version,
                 correlation_id,
+                peer = %peer,
+                conn_req_seq,
                 transactional_id,
                 producer_id,
                 producer_epoch,
@@ -957,19 +1805,42 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
                 api_key,
                 api_version,
                 correlation_id,
+                peer = %peer,
+                conn_req_seq,
             )
         }
 
         Body::ApiVersionsRequest { .. } => {
-            debug_span!("api_versions", api_key, api_version, correlation_id,)
+            debug_span!(
+                "api_versions",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::CreateTopicsRequest { .. } => {
-            debug_span!("create_topics", api_key, api_version, correlation_id)
+            debug_span!(
+                "create_topics",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::DeleteTopicsRequest { .. } => {
-            debug_span!("delete_topics", api_key, api_version, correlation_id)
+            debug_span!(
+                "delete_topics",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::EndTxnRequest {
@@ -983,6 +1854,8 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
                 api_key,
                 api_version,
                 correlation_id,
+                peer = %peer,
+                conn_req_seq,
                 transactional_id,
                 producer_id,
                 producer_epoch,
@@ -991,11 +1864,25 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
         }
 
         Body::FetchRequest { .. } => {
-            debug_span!("fetch", api_key, api_version, correlation_id)
+            debug_span!(
+                "fetch",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::FindCoordinatorRequest { .. } => {
-            debug_span!("find_coordinator", api_key, api_version, correlation_id)
+            debug_span!(
+                "find_coordinator",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::InitProducerIdRequest {
@@ -1009,6 +1896,8 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
                 api_key,
                 api_version,
                 correlation_id,
+                peer = %peer,
+                conn_req_seq,
                 transactional_id,
                 producer_id,
                 producer_epoch,
@@ -1023,29 +1912,66 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
         } => debug_span!(
             "join_group",
             correlation_id,
+            peer = %peer,
+            conn_req_seq,
             group_id,
             member_id,
             group_instance_id,
         ),
 
         Body::LeaveGroupRequest { .. } => {
-            debug_span!("leave_group", api_key, api_version, correlation_id)
+            debug_span!(
+                "leave_group",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::ListOffsetsRequest { .. } => {
-            debug_span!("list_offsets", api_key, api_version, correlation_id)
+            debug_span!(
+                "list_offsets",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::MetadataRequest { .. } => {
-            debug_span!("metadata", api_key, api_version, correlation_id,)
+            debug_span!(
+                "metadata",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::OffsetFetchRequest { .. } => {
-            debug_span!("offset_fetch", api_key, api_version, correlation_id,)
+            debug_span!(
+                "offset_fetch",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::ProduceRequest { .. } => {
-            debug_span!("produce", api_key, api_version, correlation_id)
+            debug_span!(
+                "produce",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
         }
 
         Body::SyncGroupRequest {
@@ -1057,6 +1983,8 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
         } => debug_span!(
             "sync_group",
             correlation_id,
+            peer = %peer,
+            conn_req_seq,
             group_id,
             generation_id,
             member_id,
@@ -1078,6 +2006,8 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
                 api_key,
                 api_version,
                 correlation_id,
+                peer = %peer,
+                conn_req_seq,
                 transactional_id,
                 group_id,
                 producer_id,
@@ -1088,40 +2018,3414 @@ fn request_span(api_key: i16, api_version: i16, correlation_id: i32, body: &Body
             )
         }
 
-        _ => debug_span!("request", api_key, api_version, correlation_id,),
+        _ => {
+            debug_span!(
+                "request",
+                api_key,
+                api_version,
+                correlation_id,
+                peer = %peer,
+                conn_req_seq,
+            )
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SocketOptions {
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+    read_buffer_size: usize,
+    write_buffer_size: usize,
+    max_request_size: usize,
+}
+
+// user space buffering of each connection, a size of zero is unbuffered
+pub const DEFAULT_STREAM_BUFFER_SIZE: usize = 8 * 1024;
+
+// the largest request frame accepted, as Kafka's socket.request.max.bytes
+pub const DEFAULT_MAX_REQUEST_SIZE: usize = 100 * 1024 * 1024;
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            read_buffer_size: DEFAULT_STREAM_BUFFER_SIZE,
+            write_buffer_size: DEFAULT_STREAM_BUFFER_SIZE,
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+        }
+    }
+}
+
+impl SocketOptions {
+    pub fn nodelay(self, nodelay: bool) -> Self {
+        Self { nodelay, ..self }
+    }
+
+    pub fn keepalive(self, keepalive: Option<Duration>) -> Self {
+        Self { keepalive, ..self }
+    }
+
+    pub fn send_buffer_size(self, send_buffer_size: Option<usize>) -> Self {
+        Self {
+            send_buffer_size,
+            ..self
+        }
+    }
+
+    pub fn recv_buffer_size(self, recv_buffer_size: Option<usize>) -> Self {
+        Self {
+            recv_buffer_size,
+            ..self
+        }
+    }
+
+    pub fn read_buffer_size(self, read_buffer_size: usize) -> Self {
+        Self {
+            read_buffer_size,
+            ..self
+        }
+    }
+
+    pub fn write_buffer_size(self, write_buffer_size: usize) -> Self {
+        Self {
+            write_buffer_size,
+            ..self
+        }
+    }
+
+    pub fn max_request_size(self, max_request_size: usize) -> Self {
+        Self {
+            max_request_size,
+            ..self
+        }
+    }
+
+    fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+
+        let socket = SockRef::from(stream);
+
+        if let Some(keepalive) = self.keepalive {
+            socket.set_tcp_keepalive(
+                &TcpKeepalive::new()
+                    .with_time(keepalive)
+                    .with_interval(keepalive),
+            )?;
+        }
+
+        if let Some(send_buffer_size) = self.send_buffer_size {
+            socket.set_send_buffer_size(send_buffer_size)?;
+        }
+
+        if let Some(recv_buffer_size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(recv_buffer_size)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub const OTHER_TOPIC: &str = "__other__";
+
+// client software names and versions are supplied by the client, so only
+// short values made of the characters that Kafka allows are used as labels
+const MAX_CLIENT_SOFTWARE_LABEL_LENGTH: usize = 32;
+const OTHER_CLIENT_SOFTWARE: &str = "__other__";
+
+fn client_software_label(value: &str) -> String {
+    if value.is_empty()
+        || value.len() > MAX_CLIENT_SOFTWARE_LABEL_LENGTH
+        || !value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'))
+    {
+        OTHER_CLIENT_SOFTWARE.into()
+    } else {
+        value.into()
+    }
+}
+
+// an up down counter incremented while held, decremented with the same
+// attributes when dropped, including when the holding future is cancelled
+#[derive(Debug)]
+struct Gauged {
+    counter: UpDownCounter<i64>,
+    attributes: [KeyValue; 1],
+}
+
+impl Gauged {
+    fn new(counter: &UpDownCounter<i64>, attribute: &KeyValue) -> Self {
+        let attributes = [attribute.clone()];
+        counter.add(1, &attributes);
+
+        Self {
+            counter: counter.clone(),
+            attributes,
+        }
+    }
+}
+
+impl Drop for Gauged {
+    fn drop(&mut self) {
+        self.counter.add(-1, &self.attributes);
     }
 }
 
 #[derive(Debug, Clone)]
 struct Metron {
+    cluster_id: KeyValue,
     api_requests: Counter<u64>,
     request_size: Histogram<u64>,
     response_size: Histogram<u64>,
     request_duration: Histogram<u64>,
+    produce_records: Counter<u64>,
+    produce_bytes: Counter<u64>,
+    fetch_records: Counter<u64>,
+    fetch_bytes: Counter<u64>,
+    active_connections: UpDownCounter<i64>,
+    in_flight_requests: UpDownCounter<i64>,
+    txn_begun: Counter<u64>,
+    txn_committed: Counter<u64>,
+    txn_aborted: Counter<u64>,
+    txn_timed_out: Counter<u64>,
+    txn_duration: Histogram<u64>,
+    transactions: Arc<Mutex<TxnTimings>>,
+    topics: Option<BTreeSet<String>>,
+    clock: Arc<dyn Clock>,
+}
+
+// the timeout of a transactional id, with the start of any open transaction
+#[derive(Clone, Copy, Debug)]
+struct TxnTiming {
+    timeout: Option<Duration>,
+    begun: Option<SystemTime>,
+    touched: SystemTime,
+}
+
+// transactional ids idle for longer than their timeout and the longest
+// transaction timeout allowed by Kafka are forgotten
+const TXN_TIMING_EXPIRY: Duration = Duration::from_secs(15 * 60);
+const TXN_TIMING_SWEEP: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Default)]
+struct TxnTimings {
+    timings: BTreeMap<String, TxnTiming>,
+    swept: Option<SystemTime>,
+}
+
+impl TxnTimings {
+    // a producer that is fenced, or whose connection drops, never ends its
+    // transaction: stale timings are swept at most once per period
+    fn sweep(&mut self, now: SystemTime) {
+        if self
+            .swept
+            .is_some_and(|swept| now.duration_since(swept).unwrap_or_default() < TXN_TIMING_SWEEP)
+        {
+            return;
+        }
+
+        self.swept = Some(now);
+
+        self.timings.retain(|_, timing| {
+            now.duration_since(timing.touched).unwrap_or_default()
+                < timing.timeout.unwrap_or_default() + TXN_TIMING_EXPIRY
+        });
+    }
 }
 
 impl Metron {
-    fn new(_namespace: &str, _instance_id: Uuid) -> Self {
+    fn new(cluster_id: &str, _instance_id: Uuid) -> Self {
+        Self::with_meter(cluster_id, &METER)
+    }
+
+    fn connected(&self) -> Gauged {
+        Gauged::new(&self.active_connections, &self.cluster_id)
+    }
+
+    fn in_flight(&self) -> Gauged {
+        Gauged::new(&self.in_flight_requests, &self.cluster_id)
+    }
+
+    fn with_meter(cluster_id: &str, meter: &Meter) -> Self {
         Self {
-            api_requests: METER
+            cluster_id: KeyValue::new("cluster_id", Arc::<str>::from(cluster_id)),
+            api_requests: meter
                 .u64_counter("tansu_api_requests")
                 .with_description("The number of API requests made")
                 .build(),
-            request_size: METER
+            request_size: meter
                 .u64_histogram("tansu_request_size")
                 .with_unit("By")
                 .with_description("The API request size in bytes")
                 .build(),
-            response_size: METER
+            response_size: meter
                 .u64_histogram("tansu_response_size")
                 .with_unit("By")
                 .with_description("The API response size in bytes")
                 .build(),
-            request_duration: METER
+            request_duration: meter
                 .u64_histogram("tansu_request_duration")
                 .with_unit("ms")
                 .with_description("The API request latencies in milliseconds")
                 .build(),
+            produce_records: meter
+                .u64_counter("tansu_produce_records")
+                .with_description("The number of records produced")
+                .build(),
+            produce_bytes: meter
+                .u64_counter("tansu_produce_bytes")
+                .with_unit("By")
+                .with_description("The number of record bytes produced")
+                .build(),
+            fetch_records: meter
+                .u64_counter("tansu_fetch_records")
+                .with_description("The number of records fetched")
+                .build(),
+            fetch_bytes: meter
+                .u64_counter("tansu_fetch_bytes")
+                .with_unit("By")
+                .with_description("The number of record bytes fetched")
+                .build(),
+            active_connections: meter
+                .i64_up_down_counter("tansu_active_connections")
+                .with_description("The number of active connections")
+                .build(),
+            in_flight_requests: meter
+                .i64_up_down_counter("tansu_in_flight_requests")
+                .with_description("The number of requests being processed")
+                .build(),
+            txn_begun: meter
+                .u64_counter("tansu_txn_begun")
+                .with_description("The number of transactions begun")
+                .build(),
+            txn_committed: meter
+                .u64_counter("tansu_txn_committed")
+                .with_description("The number of transactions committed")
+                .build(),
+            txn_aborted: meter
+                .u64_counter("tansu_txn_aborted")
+                .with_description("The number of transactions aborted")
+                .build(),
+            txn_timed_out: meter
+                .u64_counter("tansu_txn_timed_out")
+                .with_description("The number of transactions aborted after timing out")
+                .build(),
+            txn_duration: meter
+                .u64_histogram("tansu_txn_duration")
+                .with_unit("ms")
+                .with_description("The transaction durations in milliseconds")
+                .build(),
+            transactions: Arc::new(Mutex::new(TxnTimings::default())),
+            clock: Arc::new(SystemClock),
+            topics: None,
+        }
+    }
+
+    fn topics(self, topics: Option<BTreeSet<String>>) -> Self {
+        Self { topics, ..self }
+    }
+
+    fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    fn topic_attributes(&self, cluster_id: &str, topic: &str, partition: i32) -> Vec<KeyValue> {
+        let mut attributes = vec![KeyValue::new("cluster_id", cluster_id.to_owned())];
+
+        match self.topics {
+            None => (),
+
+            Some(ref topics) if topics.contains(topic) => {
+                attributes.push(KeyValue::new("topic", topic.to_owned()));
+                attributes.push(KeyValue::new("partition", i64::from(partition)));
+            }
+
+            Some(_) => attributes.push(KeyValue::new("topic", OTHER_TOPIC)),
+        }
+
+        attributes
+    }
+
+    // the records and bytes in each partition of a produce request, taken
+    // before the request is consumed
+    fn produce_sizes(
+        topic_data: Option<&[TopicProduceData]>,
+    ) -> BTreeMap<(String, i32), (u64, u64)> {
+        let mut sizes = BTreeMap::new();
+
+        for topic in topic_data.unwrap_or_default() {
+            for partition in topic.partition_data.as_deref().unwrap_or_default() {
+                let (records, bytes) = sizes
+                    .entry((topic.name.clone(), partition.index))
+                    .or_insert((0, 0));
+
+                for batch in partition
+                    .records
+                    .as_ref()
+                    .map_or(&[][..], |frame| &frame.batches[..])
+                {
+                    *records += u64::from(batch.record_count);
+                    *bytes += batch.record_data.len() as u64;
+                }
+            }
         }
+
+        sizes
+    }
+
+    // only partitions that were written are counted as produced
+    fn produced(
+        &self,
+        cluster_id: &str,
+        sizes: &BTreeMap<(String, i32), (u64, u64)>,
+        responses: Option<&[TopicProduceResponse]>,
+    ) {
+        for topic in responses.unwrap_or_default() {
+            for partition in topic.partition_responses.as_deref().unwrap_or_default() {
+                if partition.error_code != i16::from(ErrorCode::None) {
+                    continue;
+                }
+
+                let Some((records, bytes)) = sizes.get(&(topic.name.clone(), partition.index))
+                else {
+                    continue;
+                };
+
+                let attributes =
+                    self.topic_attributes(cluster_id, topic.name.as_str(), partition.index);
+
+                self.produce_records.add(*records, &attributes);
+                self.produce_bytes.add(*bytes, &attributes);
+            }
+        }
+    }
+
+    // an open transaction is rolled back by init producer, having timed out
+    // when it has outlived the transaction timeout
+    fn txn_initialised(&self, transaction_id: &str, transaction_timeout_ms: i32) -> Result<()> {
+        let now = self.clock.now();
+
+        let timing = TxnTiming {
+            timeout: Some(Duration::from_millis(
+                u64::try_from(transaction_timeout_ms).unwrap_or_default(),
+            )),
+            begun: None,
+            touched: now,
+        };
+
+        let previous = {
+            let mut transactions = self.transactions.lock()?;
+            transactions.sweep(now);
+            transactions
+                .timings
+                .insert(transaction_id.to_owned(), timing)
+        };
+
+        if let Some(TxnTiming {
+            timeout,
+            begun: Some(begun),
+            ..
+        }) = previous
+        {
+            let elapsed = self.clock.elapsed(begun);
+            let initiator = if timeout.is_some_and(|timeout| elapsed >= timeout) {
+                "timeout"
+            } else {
+                "client"
+            };
+
+            self.txn_completed(false, initiator, Some(elapsed));
+        }
+
+        Ok(())
+    }
+
+    // a transactional id initialised on another broker has no known timeout
+    fn txn_added(&self, transaction_id: &str) -> Result<()> {
+        let now = self.clock.now();
+
+        let mut transactions = self.transactions.lock()?;
+        transactions.sweep(now);
+
+        let timing = transactions
+            .timings
+            .entry(transaction_id.to_owned())
+            .or_insert(TxnTiming {
+                timeout: None,
+                begun: None,
+                touched: now,
+            });
+
+        timing.touched = now;
+
+        if timing.begun.is_none() {
+            timing.begun = Some(now);
+            self.txn_begun.add(1, &[self.cluster_id.clone()]);
+        }
+
+        Ok(())
+    }
+
+    fn txn_ended(&self, transaction_id: &str, committed: bool) -> Result<()> {
+        let begun = self
+            .transactions
+            .lock()?
+            .timings
+            .remove(transaction_id)
+            .and_then(|timing| timing.begun);
+
+        self.txn_completed(
+            committed,
+            "client",
+            begun.map(|begun| self.clock.elapsed(begun)),
+        );
+
+        Ok(())
+    }
+
+    // a transaction begun on another broker has no duration
+    fn txn_completed(&self, committed: bool, initiator: &'static str, duration: Option<Duration>) {
+        let mut attributes = vec![self.cluster_id.clone()];
+
+        let outcome = if committed {
+            self.txn_committed.add(1, &attributes);
+            "commit"
+        } else {
+            attributes.push(KeyValue::new("initiator", initiator));
+            self.txn_aborted.add(1, &attributes);
+
+            if initiator == "timeout" {
+                self.txn_timed_out.add(1, &[self.cluster_id.clone()]);
+            }
+
+            "abort"
+        };
+
+        if let Some(duration) = duration {
+            attributes.push(KeyValue::new("outcome", outcome));
+            self.txn_duration
+                .record(duration.as_millis() as u64, &attributes);
+        }
+    }
+
+    fn fetched(&self, cluster_id: &str, responses: Option<&[FetchableTopicResponse]>) {
+        for topic in responses.unwrap_or_default() {
+            for partition in topic.partitions.as_deref().unwrap_or_default() {
+                let attributes = self.topic_attributes(
+                    cluster_id,
+                    topic.topic.as_deref().unwrap_or_default(),
+                    partition.partition_index,
+                );
+
+                for batch in partition
+                    .records
+                    .as_ref()
+                    .map_or(&[][..], |frame| &frame.batches[..])
+                {
+                    self.fetch_records
+                        .add(u64::from(batch.record_count), &attributes);
+                    self.fetch_bytes
+                        .add(batch.record_data.len() as u64, &attributes);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::group::administrator::Controller;
+    use faulty::Faulty;
+    use futures::future::{self, BoxFuture};
+    use object_store::memory::InMemory;
+    use opentelemetry::{Value, metrics::MeterProvider, trace::TracerProvider};
+    use opentelemetry_sdk::{
+        Resource,
+        error::{OTelSdkError, OTelSdkResult},
+        metrics::{
+            InstrumentKind, ManualReader, MetricResult, Pipeline, SdkMeterProvider, Temporality,
+            data::{ResourceMetrics, Sum},
+            reader::MetricReader,
+        },
+        trace::{SdkTracerProvider, SpanData, SpanExporter},
+    };
+    use produce::transform::Transform;
+    use std::{
+        sync::{Arc, Mutex, Weak},
+        time::Instant,
+    };
+    use tansu_kafka_sans_io::{
+        ConfigResource,
+        add_partitions_to_txn_request::AddPartitionsToTxnTopic,
+        create_topics_request::CreatableTopic,
+        fetch_request::{FetchPartition, FetchTopic},
+        incremental_alter_configs_request::{AlterConfigsResource, AlterableConfig},
+        list_offsets_request::{ListOffsetsPartition, ListOffsetsTopic},
+        produce_request::PartitionProduceData,
+        record::{Record, deflated, inflated},
+    };
+    use tansu_storage::{
+        ListOffsetRequest, StorageContainer, Topition, clock::ManualClock, dynostore::DynoStore,
+    };
+    use tokio::{io::duplex, time::sleep};
+    use tracing::subscriber::DefaultGuard;
+
+    #[derive(Clone, Debug, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .map(|mut captured| captured.extend_from_slice(buf))
+                .map(|()| buf.len())
+                .map_err(|_| io::Error::other("poison"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn init_tracing(&self) -> DefaultGuard {
+            let captured = self.clone();
+
+            tracing::subscriber::set_default(
+                tracing_subscriber::fmt()
+                    .with_ansi(false)
+                    .with_max_level(Level::DEBUG)
+                    .with_writer(move || captured.clone())
+                    .finish(),
+            )
+        }
+
+        fn logs(&self) -> String {
+            self.0
+                .lock()
+                .map(|captured| String::from_utf8_lossy(&captured[..]).into_owned())
+                .unwrap_or_default()
+        }
+    }
+
+    #[derive(Clone, Debug, Default)]
+    struct SharedReader(Arc<ManualReader>);
+
+    impl MetricReader for SharedReader {
+        fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+            self.0.register_pipeline(pipeline)
+        }
+
+        fn collect(&self, rm: &mut ResourceMetrics) -> MetricResult<()> {
+            self.0.collect(rm)
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            self.0.force_flush()
+        }
+
+        fn shutdown(&self) -> OTelSdkResult {
+            self.0.shutdown()
+        }
+
+        fn temporality(&self, kind: InstrumentKind) -> Temporality {
+            self.0.temporality(kind)
+        }
+    }
+
+    // spans exported to memory
+    #[derive(Clone, Debug, Default)]
+    struct SpanCollector(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for SpanCollector {
+        fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, OTelSdkResult> {
+            let exported = self
+                .0
+                .lock()
+                .map(|mut spans| spans.extend(batch))
+                .map_err(|_| OTelSdkError::InternalFailure("poison".into()));
+
+            Box::pin(async move { exported })
+        }
+    }
+
+    impl SpanCollector {
+        fn attribute(&self, span: &str, key: &str) -> Vec<Value> {
+            self.0
+                .lock()
+                .map(|spans| {
+                    spans
+                        .iter()
+                        .filter(|data| data.name == span)
+                        .flat_map(|data| data.attributes.iter())
+                        .filter(|attribute| attribute.key.as_str() == key)
+                        .map(|attribute| attribute.value.clone())
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    }
+
+    impl SharedReader {
+        fn up_down_counter(&self, name: &str) -> Result<i64> {
+            let mut rm = ResourceMetrics {
+                resource: Resource::builder_empty().build(),
+                scope_metrics: vec![],
+            };
+
+            self.collect(&mut rm)?;
+
+            Ok(rm
+                .scope_metrics
+                .iter()
+                .flat_map(|scope| scope.metrics.iter())
+                .filter(|metric| metric.name == name)
+                .filter_map(|metric| metric.data.as_any().downcast_ref::<Sum<i64>>())
+                .flat_map(|sum| sum.data_points.iter())
+                .map(|data_point| data_point.value)
+                .sum())
+        }
+
+        fn counter(&self, name: &str) -> Result<u64> {
+            let mut rm = ResourceMetrics {
+                resource: Resource::builder_empty().build(),
+                scope_metrics: vec![],
+            };
+
+            self.collect(&mut rm)?;
+
+            Ok(rm
+                .scope_metrics
+                .iter()
+                .flat_map(|scope| scope.metrics.iter())
+                .filter(|metric| metric.name == name)
+                .filter_map(|metric| metric.data.as_any().downcast_ref::<Sum<u64>>())
+                .flat_map(|sum| sum.data_points.iter())
+                .map(|data_point| data_point.value)
+                .sum())
+        }
+
+        fn counter_attributes(&self, name: &str) -> Result<Vec<Vec<KeyValue>>> {
+            let mut rm = ResourceMetrics {
+                resource: Resource::builder_empty().build(),
+                scope_metrics: vec![],
+            };
+
+            self.collect(&mut rm)?;
+
+            Ok(rm
+                .scope_metrics
+                .iter()
+                .flat_map(|scope| scope.metrics.iter())
+                .filter(|metric| metric.name == name)
+                .filter_map(|metric| metric.data.as_any().downcast_ref::<Sum<u64>>())
+                .flat_map(|sum| sum.data_points.iter())
+                .map(|data_point| {
+                    let mut attributes = data_point.attributes.clone();
+                    attributes.sort_by(|a, b| a.key.cmp(&b.key));
+                    attributes
+                })
+                .collect())
+        }
+
+        async fn eventually(&self, name: &str, expected: i64) -> Result<i64> {
+            for _ in 0..100 {
+                let value = self.up_down_counter(name)?;
+
+                if value == expected {
+                    return Ok(value);
+                }
+
+                sleep(Duration::from_millis(10)).await;
+            }
+
+            self.up_down_counter(name)
+        }
+    }
+
+    fn config(cluster_id: &str, node_id: i32) -> Result<Config> {
+        Config::builder()
+            .cluster_id(cluster_id)
+            .node_id(node_id)
+            .listener(Url::parse("tcp://localhost:9092/")?)
+            .advertised_listener(Url::parse("tcp://localhost:9092/")?)
+            .storage(Url::parse("memory://tansu/")?)
+            .build()
+    }
+
+    fn broker() -> Result<Broker<Controller<StorageContainer>, StorageContainer>> {
+        let cluster_id = "abc";
+        let node_id = 111;
+
+        let storage =
+            StorageContainer::DynoStore(DynoStore::new(cluster_id, node_id, InMemory::new()));
+
+        Ok(Broker::new(
+            &config(cluster_id, node_id)?,
+            storage.clone(),
+            Controller::with_storage(storage)?,
+            Uuid::nil(),
+        ))
+    }
+
+    // a zero filled request decodes with default values, only requests
+    // without a handler are unsupported
+    #[tokio::test]
+    async fn advertised_apis_are_handled() -> Result<()> {
+        use api_versions::SUPPORTED;
+        use tansu_kafka_sans_io::RootMessageMeta;
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        for (api_key, meta) in RootMessageMeta::messages().requests() {
+            let api_version = meta.version.valid.start;
+
+            let mut request = vec![];
+            request.extend_from_slice(&api_key.to_be_bytes());
+            request.extend_from_slice(&api_version.to_be_bytes());
+            request.extend_from_slice(&6i32.to_be_bytes());
+
+            if meta.is_flexible(api_version) {
+                // an empty client id and no tagged fields in the header,
+                // a compact length of 1 is empty in the body
+                request.extend_from_slice(&[0, 0, 0]);
+                request.extend_from_slice(&[1; 256]);
+            } else {
+                request.extend_from_slice(&[0; 256]);
+            }
+
+            let size = i32::try_from(request.len())?;
+            let request = [&size.to_be_bytes()[..], &request[..]].concat();
+
+            let Frame { body, .. } = Frame::request_from_bytes(&request)?;
+
+            let unsupported = match tokio::time::timeout(
+                Duration::from_millis(100),
+                broker()?.response_for(&peer, Some("test"), body, 6),
+            )
+            .await
+            {
+                Ok(Err(Error::UnsupportedRequest(_))) => true,
+                Ok(_) | Err(_) => false,
+            };
+
+            assert_eq!(
+                SUPPORTED.contains(&meta.name),
+                !unsupported,
+                "{} is advertised: {}, handled: {}",
+                meta.name,
+                SUPPORTED.contains(&meta.name),
+                !unsupported
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clean_disconnect() -> Result<()> {
+        let captured = Captured::default();
+        let _guard = captured.init_tracing();
+
+        let (client, server) = duplex(64);
+        drop(client);
+
+        broker()?
+            .stream_handler(&SocketAddr::from(([127, 0, 0, 1], 9092)), server)
+            .await?;
+
+        let logs = captured.logs();
+        assert!(logs.contains("DEBUG"), "{logs}");
+        assert!(logs.contains("disconnected"), "{logs}");
+        assert!(!logs.contains("WARN"), "{logs}");
+        assert!(!logs.contains("ERROR"), "{logs}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn truncated_size() -> Result<()> {
+        let captured = Captured::default();
+        let _guard = captured.init_tracing();
+
+        let (mut client, mut server) = duplex(64);
+        client.write_all(&[0, 0]).await?;
+        drop(client);
+
+        assert_eq!(
+            None,
+            read_frame(&mut server, DEFAULT_MAX_REQUEST_SIZE).await?
+        );
+
+        let logs = captured.logs();
+        assert!(logs.contains("WARN"), "{logs}");
+        assert!(logs.contains("expected=4 received=2"), "{logs}");
+        assert!(!logs.contains("ERROR"), "{logs}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mid_frame_truncation() -> Result<()> {
+        let captured = Captured::default();
+        let _guard = captured.init_tracing();
+
+        let (mut client, server) = duplex(64);
+        client.write_all(&[0, 0, 0, 10, 1, 2, 3]).await?;
+        drop(client);
+
+        broker()?
+            .stream_handler(&SocketAddr::from(([127, 0, 0, 1], 9092)), server)
+            .await?;
+
+        let logs = captured.logs();
+        assert!(logs.contains("WARN"), "{logs}");
+        assert!(logs.contains("expected=10 received=3"), "{logs}");
+        assert!(!logs.contains("ERROR"), "{logs}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn frame_too_large() -> Result<()> {
+        let captured = Captured::default();
+        let _guard = captured.init_tracing();
+
+        // the client remains connected, a read of the frame would not return
+        let (mut client, mut server) = duplex(64);
+        client.write_all(&[0, 0, 0, 11]).await?;
+
+        assert_eq!(
+            None,
+            tokio::time::timeout(Duration::from_secs(5), read_frame(&mut server, 10))
+                .await
+                .map_err(|elapsed| Error::Message(elapsed.to_string()))??
+        );
+
+        let logs = captured.logs();
+        assert!(logs.contains("frame too large"), "{logs}");
+        assert!(logs.contains("length=11 max_size=10"), "{logs}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn frames_after_empty_read() -> Result<()> {
+        let (mut client, mut server) = duplex(64);
+        client.write_all(&[0, 0, 0, 0, 0, 0, 0, 2, 6, 7]).await?;
+        drop(client);
+
+        assert_eq!(
+            Some(Bytes::from_static(&[0, 0, 0, 2, 6, 7])),
+            read_frame(&mut server, DEFAULT_MAX_REQUEST_SIZE).await?
+        );
+        assert_eq!(
+            None,
+            read_frame(&mut server, DEFAULT_MAX_REQUEST_SIZE).await?
+        );
+
+        Ok(())
+    }
+
+    async fn pipelined(read_buffer_size: usize, write_buffer_size: usize) -> Result<()> {
+        let api_key = 18;
+        let api_version = 3;
+        let requests = 10;
+
+        let mut pipelined = BytesMut::new();
+
+        for correlation_id in 0..requests {
+            pipelined.extend_from_slice(&Frame::request(
+                Header::Request {
+                    api_key,
+                    api_version,
+                    correlation_id,
+                    client_id: Some("test".into()),
+                },
+                Body::ApiVersionsRequest {
+                    client_software_name: Some("tansu".into()),
+                    client_software_version: Some("0.1.0".into()),
+                },
+            )?);
+        }
+
+        let mut broker = broker()?.socket_options(
+            SocketOptions::default()
+                .read_buffer_size(read_buffer_size)
+                .write_buffer_size(write_buffer_size),
+        );
+
+        let (mut client, server) = duplex(64);
+
+        let handler = tokio::spawn(async move {
+            broker
+                .stream_handler(&SocketAddr::from(([127, 0, 0, 1], 9092)), server)
+                .await
+        });
+
+        let (mut reader, mut writer) = tokio::io::split(&mut client);
+
+        // responses left unflushed in the write buffer would never arrive
+        let (written, responses) = tokio::join!(
+            writer.write_all(&pipelined),
+            timeout(Duration::from_secs(5), async {
+                let mut responses = vec![];
+
+                for _ in 0..requests {
+                    responses.push(read_frame(&mut reader, DEFAULT_MAX_REQUEST_SIZE).await?);
+                }
+
+                Ok::<_, Error>(responses)
+            })
+        );
+
+        written?;
+
+        for (correlation_id, response) in (0..requests).zip(responses.expect("responses")?) {
+            let response = response.expect("response frame");
+
+            let Frame {
+                header:
+                    Header::Response {
+                        correlation_id: response_correlation_id,
+                    },
+                body: Body::ApiVersionsResponse { error_code, .. },
+                ..
+            } = Frame::response_from_bytes(&response, api_key, api_version)?
+            else {
+                panic!("api versions response")
+            };
+
+            assert_eq!(correlation_id, response_correlation_id);
+            assert_eq!(i16::from(ErrorCode::None), error_code);
+        }
+
+        drop(client);
+        handler.await.expect("handler")?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn buffered_frames() -> Result<()> {
+        // buffers smaller than a frame, so that every frame straddles a buffer boundary
+        pipelined(7, 5).await?;
+
+        // buffers larger than a frame, holding several frames at once
+        pipelined(4096, 4096).await?;
+
+        pipelined(0, 0).await
+    }
+
+    #[tokio::test]
+    async fn sampled_request_logging() -> Result<()> {
+        let captured = Captured::default();
+        let _guard = captured.init_tracing();
+
+        let mut broker = broker()?.sampler(Sampler::default().rate(0.25));
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        for correlation_id in 0..8 {
+            let request = Frame::request(
+                Header::Request {
+                    api_key: 18,
+                    api_version: 3,
+                    correlation_id,
+                    client_id: Some("test".into()),
+                },
+                Body::ApiVersionsRequest {
+                    client_software_name: Some("abc".into()),
+                    client_software_version: Some("1.2.3".into()),
+                },
+            )
+            .map(Bytes::from)?;
+
+            _ = broker.process_request(&peer, &request).await?;
+        }
+
+        // failed requests are always logged in full
+        for correlation_id in 8..12 {
+            let request = Frame::request(
+                Header::Request {
+                    api_key: 17,
+                    api_version: 1,
+                    correlation_id,
+                    client_id: Some("test".into()),
+                },
+                Body::SaslHandshakeRequest {
+                    mechanism: "PLAIN".into(),
+                },
+            )
+            .map(Bytes::from)?;
+
+            _ = broker.process_request(&peer, &request).await?;
+        }
+
+        let logs = captured.logs();
+        assert_eq!(
+            2,
+            logs.matches("request=ApiVersionsRequest").count(),
+            "{logs}"
+        );
+        assert_eq!(
+            2,
+            logs.matches("body=ApiVersionsResponse").count(),
+            "{logs}"
+        );
+        assert_eq!(
+            4,
+            logs.matches("body=SaslHandshakeResponse").count(),
+            "{logs}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unsampled_produce_is_not_logged() -> Result<()> {
+        let captured = Captured::default();
+        let _guard = captured.init_tracing();
+
+        let mut broker = broker()?.sampler(Sampler::default().rate(0.0));
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        let request = Frame::request(
+            Header::Request {
+                api_key: 0,
+                api_version: 9,
+                correlation_id: 0,
+                client_id: Some("test".into()),
+            },
+            Body::ProduceRequest {
+                transactional_id: None,
+                acks: -1,
+                timeout_ms: 1_500,
+                topic_data: Some([].into()),
+            },
+        )
+        .map(Bytes::from)?;
+
+        _ = broker.process_request(&peer, &request).await?;
+
+        let logs = captured.logs();
+        assert!(
+            !logs
+                .lines()
+                .filter(|line| line.contains("tansu_server"))
+                .any(|line| line.contains("topic_data")),
+            "{logs}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unsupported_request() -> Result<()> {
+        let mut broker = broker()?;
+
+        let api_key = 17;
+        let api_version = 1;
+        let correlation_id = 12321;
+
+        let request = Frame::request(
+            Header::Request {
+                api_key,
+                api_version,
+                correlation_id,
+                client_id: Some("test".into()),
+            },
+            Body::SaslHandshakeRequest {
+                mechanism: "PLAIN".into(),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let response = broker
+            .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+            .await?;
+
+        let Frame {
+            header:
+                Header::Response {
+                    correlation_id: response_correlation_id,
+                },
+            body: Body::SaslHandshakeResponse { error_code, .. },
+            ..
+        } = Frame::response_from_bytes(&response, api_key, api_version)?
+        else {
+            panic!("sasl handshake response")
+        };
+
+        assert_eq!(correlation_id, response_correlation_id);
+        assert_eq!(i16::from(ErrorCode::UnsupportedVersion), error_code);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn corrupt_request() -> Result<()> {
+        use bytes::{BufMut, BytesMut};
+
+        let mut broker = broker()?;
+
+        let api_key = 60;
+        let api_version = 0;
+        let correlation_id = 23432;
+
+        // a describe cluster header, without the body that must follow
+        let request = {
+            let mut header = BytesMut::new();
+            header.put_i16(api_key);
+            header.put_i16(api_version);
+            header.put_i32(correlation_id);
+            header.put_i16(4);
+            header.put_slice(b"test");
+            header.put_u8(0);
+
+            let mut frame = BytesMut::new();
+            frame.put_i32(i32::try_from(header.len())?);
+            frame.put(header);
+            frame.freeze()
+        };
+
+        assert!(matches!(
+            Frame::request_from_bytes(&request),
+            Err(tansu_kafka_sans_io::Error::Io(_))
+        ));
+
+        let response = broker
+            .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+            .await?;
+
+        let Frame {
+            header:
+                Header::Response {
+                    correlation_id: response_correlation_id,
+                },
+            body: Body::DescribeClusterResponse { error_code, .. },
+            ..
+        } = Frame::response_from_bytes(&response, api_key, api_version)?
+        else {
+            panic!("describe cluster response")
+        };
+
+        assert_eq!(correlation_id, response_correlation_id);
+        assert_eq!(i16::from(ErrorCode::CorruptMessage), error_code);
+
+        // without a header there is nothing to answer
+        assert!(matches!(
+            broker
+                .process_request(
+                    &SocketAddr::from(([127, 0, 0, 1], 9092)),
+                    &Bytes::from_static(&[0, 0, 0, 2, 0, 60])
+                )
+                .await,
+            Err(Error::KafkaProtocol(_))
+        ));
+
+        // a metadata header, without the topics that must follow, has no
+        // topic to report the error against
+        let request = {
+            let mut header = BytesMut::new();
+            header.put_i16(3);
+            header.put_i16(1);
+            header.put_i32(correlation_id);
+            header.put_i16(4);
+            header.put_slice(b"test");
+
+            let mut frame = BytesMut::new();
+            frame.put_i32(i32::try_from(header.len())?);
+            frame.put(header);
+            frame.freeze()
+        };
+
+        assert!(matches!(
+            broker
+                .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+                .await,
+            Err(Error::KafkaProtocol(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unsupported_api_versions_version() -> Result<()> {
+        use bytes::{BufMut, BytesMut};
+
+        let mut broker = broker()?;
+
+        let api_key = 18;
+        let api_version = i16::MAX;
+        let correlation_id = 32123;
+
+        // a version that is not understood, with a body that cannot be decoded
+        let request = {
+            let mut header = BytesMut::new();
+            header.put_i16(api_key);
+            header.put_i16(api_version);
+            header.put_i32(correlation_id);
+            header.put_i16(4);
+            header.put_slice(b"test");
+            header.put_slice(&[0xff; 7]);
+
+            let mut frame = BytesMut::new();
+            frame.put_i32(i32::try_from(header.len())?);
+            frame.put(header);
+            frame.freeze()
+        };
+
+        let response = broker
+            .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+            .await?;
+
+        // the fallback response is always in version 0
+        let Frame {
+            header:
+                Header::Response {
+                    correlation_id: response_correlation_id,
+                },
+            body:
+                Body::ApiVersionsResponse {
+                    error_code,
+                    api_keys: Some(api_keys),
+                    ..
+                },
+            ..
+        } = Frame::response_from_bytes(&response, api_key, 0)?
+        else {
+            panic!("api versions response")
+        };
+
+        assert_eq!(correlation_id, response_correlation_id);
+        assert_eq!(i16::from(ErrorCode::UnsupportedVersion), error_code);
+        assert_eq!(1, api_keys.len());
+        assert_eq!(api_key, api_keys[0].api_key);
+        assert_eq!(0, api_keys[0].min_version);
+        assert!(api_keys[0].max_version >= 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn listener_security() -> Result<()> {
+        use tansu_kafka_sans_io::{
+            produce_request::PartitionProduceData,
+            record::{Record, deflated, inflated},
+        };
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        let api_key = 0;
+        let api_version = 9;
+
+        let produce = || {
+            inflated::Batch::builder()
+                .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+                .build()
+                .and_then(deflated::Batch::try_from)
+                .and_then(|batch| {
+                    Frame::request(
+                        Header::Request {
+                            api_key,
+                            api_version,
+                            correlation_id: 6,
+                            client_id: Some("test".into()),
+                        },
+                        Body::ProduceRequest {
+                            transactional_id: None,
+                            acks: -1,
+                            timeout_ms: 1_000,
+                            topic_data: Some(vec![TopicProduceData {
+                                name: "pqr".into(),
+                                partition_data: Some(vec![PartitionProduceData {
+                                    index: 0,
+                                    records: Some(deflated::Frame {
+                                        batches: vec![batch],
+                                    }),
+                                }]),
+                            }]),
+                        },
+                    )
+                })
+                .map(Bytes::from)
+        };
+
+        let mut plaintext = broker()?.listener(Listener::new(
+            Url::parse("tcp://localhost:9092")?,
+            SecurityProtocol::Plaintext,
+        ));
+
+        _ = plaintext
+            .storage
+            .create_topic(
+                CreatableTopic {
+                    name: "pqr".into(),
+                    num_partitions: 1,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let Frame {
+            body:
+                Body::ProduceResponse {
+                    responses: Some(responses),
+                    ..
+                },
+            ..
+        } = Frame::response_from_bytes(
+            &plaintext.process_request(&peer, &produce()?).await?,
+            api_key,
+            api_version,
+        )?
+        else {
+            panic!("produce response")
+        };
+
+        assert_eq!(
+            vec![i16::from(ErrorCode::None)],
+            responses
+                .iter()
+                .flat_map(|response| response.partition_responses.iter().flatten())
+                .map(|partition| partition.error_code)
+                .collect::<Vec<_>>()
+        );
+
+        let mut sasl = broker()?.listener(Listener::new(
+            Url::parse("tcp://localhost:9093")?,
+            SecurityProtocol::SaslPlaintext,
+        ));
+
+        assert!(matches!(
+            sasl.process_request(&peer, &produce()?).await,
+            Err(Error::Api(ErrorCode::SaslAuthenticationFailed))
+        ));
+
+        let api_versions = Frame::request(
+            Header::Request {
+                api_key: 18,
+                api_version: 3,
+                correlation_id: 7,
+                client_id: Some("test".into()),
+            },
+            Body::ApiVersionsRequest {
+                client_software_name: Some("test".into()),
+                client_software_version: Some("1.0".into()),
+            },
+        )
+        .map(Bytes::from)?;
+
+        assert!(sasl.process_request(&peer, &api_versions).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn inter_broker_listener() -> Result<()> {
+        use tansu_kafka_sans_io::write_txn_markers_request::{
+            WritableTxnMarker, WritableTxnMarkerTopic,
+        };
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        let api_key = 27;
+        let api_version = 1;
+
+        let write_txn_markers = Frame::request(
+            Header::Request {
+                api_key,
+                api_version,
+                correlation_id: 6,
+                client_id: Some("broker".into()),
+            },
+            Body::WriteTxnMarkersRequest {
+                markers: Some(vec![WritableTxnMarker {
+                    producer_id: 4321,
+                    producer_epoch: 0,
+                    transaction_result: true,
+                    topics: Some(vec![WritableTxnMarkerTopic {
+                        name: "pqr".into(),
+                        partition_indexes: Some(vec![0]),
+                    }]),
+                    coordinator_epoch: 0,
+                }]),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let listener = |name: &str, port: u16| {
+            Url::parse(&format!("tcp://localhost:{port}"))
+                .map(|url| Listener::new(url, SecurityProtocol::Plaintext).named(name))
+        };
+
+        let inter_broker_listener_name = Some(String::from("INTERNAL"));
+
+        let mut client = broker()?
+            .listener(listener("CLIENT", 9092)?)
+            .inter_broker_listener_name(inter_broker_listener_name.clone());
+
+        assert!(matches!(
+            client.process_request(&peer, &write_txn_markers).await,
+            Err(Error::Api(ErrorCode::ClusterAuthorizationFailed))
+        ));
+
+        // client requests are unaffected on the client listener
+        let api_versions = Frame::request(
+            Header::Request {
+                api_key: 18,
+                api_version: 3,
+                correlation_id: 7,
+                client_id: Some("test".into()),
+            },
+            Body::ApiVersionsRequest {
+                client_software_name: Some("test".into()),
+                client_software_version: Some("1.0".into()),
+            },
+        )
+        .map(Bytes::from)?;
+
+        assert!(client.process_request(&peer, &api_versions).await.is_ok());
+
+        let mut inter_broker = broker()?
+            .listener(listener("INTERNAL", 9094)?)
+            .inter_broker_listener_name(inter_broker_listener_name);
+
+        assert!(
+            inter_broker
+                .process_request(&peer, &write_txn_markers)
+                .await
+                .is_ok()
+        );
+
+        // without an inter-broker listener, every listener is permitted
+        let mut unnamed = broker()?.listener(listener("CLIENT", 9092)?);
+
+        assert!(
+            unnamed
+                .process_request(&peer, &write_txn_markers)
+                .await
+                .is_ok()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn secured_listener_unsupported() -> Result<()> {
+        for security_protocol in [
+            SecurityProtocol::Ssl,
+            SecurityProtocol::SaslPlaintext,
+            SecurityProtocol::SaslSsl,
+        ] {
+            assert!(matches!(
+                broker()?
+                    .listener(Listener::new(
+                        Url::parse("tcp://localhost:0")?,
+                        security_protocol,
+                    ))
+                    .listen()
+                    .await,
+                Err(Error::UnsupportedSecurityProtocol(unsupported)) if unsupported == security_protocol
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn api_request_attributes() -> Result<()> {
+        let reader = SharedReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+
+        let mut broker = Broker {
+            metron: Metron::with_meter("abc", &provider.meter("api_request_attributes")),
+            ..broker()?
+        };
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        for (api_key, api_version, body) in [
+            (
+                18,
+                3,
+                Body::ApiVersionsRequest {
+                    client_software_name: Some("test".into()),
+                    client_software_version: Some("1.0".into()),
+                },
+            ),
+            (
+                17,
+                1,
+                Body::SaslHandshakeRequest {
+                    mechanism: "PLAIN".into(),
+                },
+            ),
+        ] {
+            let request = Frame::request(
+                Header::Request {
+                    api_key,
+                    api_version,
+                    correlation_id: 6,
+                    client_id: Some("test".into()),
+                },
+                body,
+            )
+            .map(Bytes::from)?;
+
+            _ = broker.process_request(&peer, &request).await?;
+        }
+
+        let mut attributes = reader.counter_attributes("tansu_api_requests")?;
+        attributes.sort_by_key(|attributes| attributes.len());
+
+        assert_eq!(
+            vec![
+                vec![
+                    KeyValue::new("api_key", 17),
+                    KeyValue::new("api_version", 1),
+                    KeyValue::new("client_software_name", "test"),
+                    KeyValue::new("client_software_version", "1.0"),
+                    KeyValue::new("cluster_id", "abc"),
+                ],
+                vec![
+                    KeyValue::new("api_key", 18),
+                    KeyValue::new("api_name", "api_versions"),
+                    KeyValue::new("api_version", 3),
+                    KeyValue::new("client_software_name", "test"),
+                    KeyValue::new("client_software_version", "1.0"),
+                    KeyValue::new("cluster_id", "abc"),
+                ],
+            ],
+            attributes
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_software_attributes() -> Result<()> {
+        let reader = SharedReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+
+        let mut broker = Broker {
+            metron: Metron::with_meter("abc", &provider.meter("client_software_attributes")),
+            ..broker()?
+        };
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        for (api_key, api_version, body) in [
+            (
+                17,
+                1,
+                Body::SaslHandshakeRequest {
+                    mechanism: "PLAIN".into(),
+                },
+            ),
+            (
+                18,
+                3,
+                Body::ApiVersionsRequest {
+                    client_software_name: Some("librdkafka".into()),
+                    client_software_version: Some("2.8.0".into()),
+                },
+            ),
+            (
+                17,
+                1,
+                Body::SaslHandshakeRequest {
+                    mechanism: "PLAIN".into(),
+                },
+            ),
+        ] {
+            let request = Frame::request(
+                Header::Request {
+                    api_key,
+                    api_version,
+                    correlation_id: 6,
+                    client_id: Some("test".into()),
+                },
+                body,
+            )
+            .map(Bytes::from)?;
+
+            _ = broker.process_request(&peer, &request).await?;
+        }
+
+        let mut handshakes = reader
+            .counter_attributes("tansu_api_requests")?
+            .into_iter()
+            .filter(|attributes| attributes.contains(&KeyValue::new("api_key", 17)))
+            .map(|attributes| {
+                attributes
+                    .into_iter()
+                    .filter(|attribute| attribute.key.as_str().starts_with("client_software"))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        handshakes.sort_by_key(|attributes| attributes.len());
+
+        assert_eq!(
+            vec![
+                vec![],
+                vec![
+                    KeyValue::new("client_software_name", "librdkafka"),
+                    KeyValue::new("client_software_version", "2.8.0"),
+                ],
+            ],
+            handshakes
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn client_software_labels() {
+        assert_eq!("librdkafka", client_software_label("librdkafka"));
+        assert_eq!("2.8.0", client_software_label("2.8.0"));
+        assert_eq!(OTHER_CLIENT_SOFTWARE, client_software_label(""));
+        assert_eq!(OTHER_CLIENT_SOFTWARE, client_software_label("a b"));
+        assert_eq!(
+            OTHER_CLIENT_SOFTWARE,
+            client_software_label(&"a".repeat(MAX_CLIENT_SOFTWARE_LABEL_LENGTH + 1))
+        );
+    }
+
+    #[tokio::test]
+    async fn produced_metrics_from_outcome() -> Result<()> {
+        let reader = SharedReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+
+        let mut broker = Broker {
+            metron: Metron::with_meter("abc", &provider.meter("produced_metrics_from_outcome")),
+            ..broker()?
+        };
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+        let topic = "pqr";
+
+        _ = broker
+            .storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let batch = || {
+            inflated::Batch::builder()
+                .last_offset_delta(1)
+                .record(Record::builder().value(Bytes::from_static(b"abc").into()))
+                .record(
+                    Record::builder()
+                        .offset_delta(1)
+                        .value(Bytes::from_static(b"def").into()),
+                )
+                .build()
+                .and_then(deflated::Batch::try_from)
+        };
+
+        // the second partition does not exist and is not counted
+        let Body::ProduceResponse { responses, .. } = broker
+            .response_for(
+                &peer,
+                Some("test"),
+                Body::ProduceRequest {
+                    transactional_id: None,
+                    acks: -1,
+                    timeout_ms: 1_500,
+                    topic_data: Some(
+                        [TopicProduceData {
+                            name: topic.into(),
+                            partition_data: Some(
+                                [0, 1]
+                                    .into_iter()
+                                    .map(|index| {
+                                        batch().map(|batch| PartitionProduceData {
+                                            index,
+                                            records: Some(deflated::Frame {
+                                                batches: vec![batch],
+                                            }),
+                                        })
+                                    })
+                                    .collect::<Result<Vec<_>, _>>()?,
+                            ),
+                        }]
+                        .into(),
+                    ),
+                },
+                3,
+            )
+            .await?
+        else {
+            panic!("produce response")
+        };
+
+        assert_eq!(
+            vec![
+                i16::from(ErrorCode::None),
+                i16::from(ErrorCode::UnknownTopicOrPartition)
+            ],
+            responses
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|response| response.partition_responses.iter().flatten())
+                .map(|partition| partition.error_code)
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(2, reader.counter("tansu_produce_records")?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn txn_lifecycle() -> Result<()> {
+        let reader = SharedReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+
+        let mut broker = Broker {
+            metron: Metron::with_meter("abc", &provider.meter("txn_lifecycle")),
+            ..broker()?
+        };
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+        let topic = "pqr";
+
+        _ = broker
+            .storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        for (transaction_id, transaction_timeout_ms, committed) in [
+            ("committed", 10_000, Some(true)),
+            ("aborted", 10_000, Some(false)),
+            ("timed_out", 0, None),
+        ] {
+            let Body::InitProducerIdResponse {
+                error_code,
+                producer_id,
+                producer_epoch,
+                ..
+            } = broker
+                .response_for(
+                    &peer,
+                    Some("test"),
+                    Body::InitProducerIdRequest {
+                        transactional_id: Some(transaction_id.into()),
+                        transaction_timeout_ms,
+                        producer_id: Some(-1),
+                        producer_epoch: Some(-1),
+                    },
+                    6,
+                )
+                .await?
+            else {
+                panic!("init producer id response")
+            };
+            assert_eq!(i16::from(ErrorCode::None), error_code);
+
+            _ = broker
+                .response_for(
+                    &peer,
+                    Some("test"),
+                    Body::AddPartitionsToTxnRequest {
+                        transactions: None,
+                        v_3_and_below_transactional_id: Some(transaction_id.into()),
+                        v_3_and_below_producer_id: Some(producer_id),
+                        v_3_and_below_producer_epoch: Some(producer_epoch),
+                        v_3_and_below_topics: Some(
+                            [AddPartitionsToTxnTopic {
+                                name: topic.into(),
+                                partitions: Some([0].into()),
+                            }]
+                            .into(),
+                        ),
+                    },
+                    7,
+                )
+                .await?;
+
+            let body = if let Some(committed) = committed {
+                Body::EndTxnRequest {
+                    transactional_id: transaction_id.into(),
+                    producer_id,
+                    producer_epoch,
+                    committed,
+                }
+            } else {
+                // the open transaction is rolled back by init producer
+                Body::InitProducerIdRequest {
+                    transactional_id: Some(transaction_id.into()),
+                    transaction_timeout_ms,
+                    producer_id: Some(-1),
+                    producer_epoch: Some(-1),
+                }
+            };
+
+            _ = broker.response_for(&peer, Some("test"), body, 8).await?;
+        }
+
+        assert_eq!(3, reader.counter("tansu_txn_begun")?);
+        assert_eq!(1, reader.counter("tansu_txn_committed")?);
+        assert_eq!(2, reader.counter("tansu_txn_aborted")?);
+        assert_eq!(1, reader.counter("tansu_txn_timed_out")?);
+
+        let mut aborted = reader.counter_attributes("tansu_txn_aborted")?;
+        aborted.sort_by_key(|attributes| format!("{attributes:?}"));
+
+        assert_eq!(
+            vec![
+                vec![
+                    KeyValue::new("cluster_id", "abc"),
+                    KeyValue::new("initiator", "client"),
+                ],
+                vec![
+                    KeyValue::new("cluster_id", "abc"),
+                    KeyValue::new("initiator", "timeout"),
+                ],
+            ],
+            aborted
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn txn_timings_swept() -> Result<()> {
+        let clock = ManualClock::default();
+        let metron = Metron::new("abc", Uuid::nil()).clock(Arc::new(clock.clone()));
+
+        let transaction_ids = |metron: &Metron| -> Result<Vec<String>> {
+            Ok(metron
+                .transactions
+                .lock()?
+                .timings
+                .keys()
+                .cloned()
+                .collect())
+        };
+
+        metron.txn_initialised("ended", 10_000)?;
+        metron.txn_added("ended")?;
+        metron.txn_initialised("fenced", 10_000)?;
+        metron.txn_added("fenced")?;
+
+        // an ended transaction is forgotten straight away
+        metron.txn_ended("ended", true)?;
+        assert_eq!(vec!["fenced".to_owned()], transaction_ids(&metron)?);
+
+        // a transaction that never ends is forgotten once stale
+        clock.advance(TXN_TIMING_EXPIRY);
+        metron.txn_initialised("current", 10_000)?;
+        assert_eq!(
+            vec!["current".to_owned(), "fenced".to_owned()],
+            transaction_ids(&metron)?
+        );
+
+        clock.advance(TXN_TIMING_SWEEP);
+        metron.txn_added("current")?;
+        assert_eq!(vec!["current".to_owned()], transaction_ids(&metron)?);
+
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct ImmutableRetention;
+
+    impl Policy for ImmutableRetention {
+        fn validate_alter_config(&self, resource: &AlterConfigsResource) -> Result<(), String> {
+            if resource
+                .configs
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .any(|config| config.name == "retention.ms")
+            {
+                Err(format!(
+                    "retention.ms of {} is immutable",
+                    resource.resource_name
+                ))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn topic_created_event() -> Result<()> {
+        use tansu_kafka_sans_io::create_topics_request::CreatableTopic;
+
+        let events = Events::default();
+        let mut subscriber = events.subscribe();
+
+        let mut broker = broker()?.events(events);
+
+        let Body::CreateTopicsResponse {
+            topics: Some(topics),
+            ..
+        } = broker
+            .response_for(
+                &SocketAddr::from(([127, 0, 0, 1], 9092)),
+                Some("test"),
+                Body::CreateTopicsRequest {
+                    topics: Some(vec![CreatableTopic {
+                        name: "pqr".into(),
+                        num_partitions: 3,
+                        replication_factor: 1,
+                        assignments: Some([].into()),
+                        configs: Some([].into()),
+                    }]),
+                    timeout_ms: 30_000,
+                    validate_only: Some(false),
+                },
+                32123,
+            )
+            .await?
+        else {
+            panic!("create topics response")
+        };
+
+        assert_eq!(1, topics.len());
+        assert_eq!(ErrorCode::None, ErrorCode::try_from(topics[0].error_code)?);
+
+        assert_eq!(
+            Event::TopicCreated {
+                name: "pqr".into(),
+                topic_id: topics[0].topic_id.map(Uuid::from_bytes).unwrap(),
+            },
+            subscriber.try_recv().expect("topic created")
+        );
+
+        assert!(subscriber.try_recv().is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn alter_config_policy_violation() -> Result<()> {
+        let mut broker = broker()?.policy(Arc::new(ImmutableRetention));
+
+        let Body::IncrementalAlterConfigsResponse {
+            responses: Some(responses),
+            ..
+        } = broker
+            .response_for(
+                &SocketAddr::from(([127, 0, 0, 1], 9092)),
+                Some("test"),
+                Body::IncrementalAlterConfigsRequest {
+                    resources: Some(vec![AlterConfigsResource {
+                        resource_type: i8::from(ConfigResource::Topic),
+                        resource_name: "pqr".into(),
+                        configs: Some(vec![AlterableConfig {
+                            name: "retention.ms".into(),
+                            config_operation: 0,
+                            value: Some("3600000".into()),
+                        }]),
+                    }]),
+                    validate_only: false,
+                },
+                32123,
+            )
+            .await?
+        else {
+            panic!("incremental alter configs response")
+        };
+
+        assert_eq!(1, responses.len());
+        assert_eq!(
+            ErrorCode::PolicyViolation,
+            ErrorCode::try_from(responses[0].error_code)?
+        );
+        assert_eq!(
+            Some("retention.ms of pqr is immutable"),
+            responses[0].error_message.as_deref()
+        );
+        assert_eq!("pqr", responses[0].resource_name);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn alter_config_invalid_cleanup_policy() -> Result<()> {
+        let mut broker = broker()?;
+
+        let Body::IncrementalAlterConfigsResponse {
+            responses: Some(responses),
+            ..
+        } = broker
+            .response_for(
+                &SocketAddr::from(([127, 0, 0, 1], 9092)),
+                Some("test"),
+                Body::IncrementalAlterConfigsRequest {
+                    resources: Some(vec![AlterConfigsResource {
+                        resource_type: i8::from(ConfigResource::Topic),
+                        resource_name: "pqr".into(),
+                        configs: Some(vec![AlterableConfig {
+                            name: "cleanup.policy".into(),
+                            config_operation: 0,
+                            value: Some("compact,remove".into()),
+                        }]),
+                    }]),
+                    validate_only: false,
+                },
+                32123,
+            )
+            .await?
+        else {
+            panic!("incremental alter configs response")
+        };
+
+        assert_eq!(1, responses.len());
+        assert_eq!(
+            ErrorCode::InvalidConfig,
+            ErrorCode::try_from(responses[0].error_code)?
+        );
+        assert_eq!("pqr", responses[0].resource_name);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn storage_failure_reported_per_topic() -> Result<()> {
+        use tansu_kafka_sans_io::metadata_request::MetadataRequestTopic;
+
+        let cluster_id = "abc";
+        let node_id = 111;
+
+        let storage = StorageContainer::DynoStore(DynoStore::new(
+            cluster_id,
+            node_id,
+            Faulty::default().unavailable(),
+        ));
+
+        let api_key = 3;
+        let api_version = 12;
+        let correlation_id = 32123;
+
+        let mut broker = Broker::new(
+            &config(cluster_id, node_id)?,
+            storage.clone(),
+            Controller::with_storage(storage)?,
+            Uuid::nil(),
+        );
+
+        let request = Frame::request(
+            Header::Request {
+                api_key,
+                api_version,
+                correlation_id,
+                client_id: Some("test".into()),
+            },
+            Body::MetadataRequest {
+                topics: Some(
+                    [MetadataRequestTopic {
+                        topic_id: Some([0; 16]),
+                        name: Some("pqr".into()),
+                    }]
+                    .into(),
+                ),
+                allow_auto_topic_creation: Some(false),
+                include_cluster_authorized_operations: None,
+                include_topic_authorized_operations: Some(false),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let response = broker
+            .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+            .await?;
+
+        let Frame {
+            body:
+                Body::MetadataResponse {
+                    topics: Some(topics),
+                    ..
+                },
+            ..
+        } = Frame::response_from_bytes(&response, api_key, api_version)?
+        else {
+            panic!("metadata response")
+        };
+
+        assert_eq!(1, topics.len());
+        assert_eq!(Some("pqr".into()), topics[0].name);
+        assert_eq!(
+            i16::from(ErrorCode::KafkaStorageError),
+            topics[0].error_code
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_timed_out() -> Result<()> {
+        let cluster_id = "abc";
+        let node_id = 111;
+
+        let storage = StorageContainer::DynoStore(DynoStore::new(
+            cluster_id,
+            node_id,
+            Faulty::default().get_delay(Duration::from_secs(5)),
+        ));
+
+        let api_key = 1;
+        let api_version = 12;
+        let correlation_id = 32123;
+
+        let mut broker = Broker::new(
+            &config(cluster_id, node_id)?,
+            storage.clone(),
+            Controller::with_storage(storage)?,
+            Uuid::nil(),
+        )
+        .request_timeout(Some(Duration::from_secs(30)))
+        .api_request_timeouts([(api_key, Duration::from_millis(250))].into());
+
+        let request = Frame::request(
+            Header::Request {
+                api_key,
+                api_version,
+                correlation_id,
+                client_id: Some("test".into()),
+            },
+            Body::FetchRequest {
+                cluster_id: None,
+                replica_id: Some(-1),
+                replica_state: None,
+                max_wait_ms: 500,
+                min_bytes: 1,
+                max_bytes: Some(50 * 1024),
+                isolation_level: Some(0),
+                session_id: Some(0),
+                session_epoch: Some(-1),
+                topics: Some(
+                    [FetchTopic {
+                        topic: Some("pqr".into()),
+                        topic_id: None,
+                        partitions: Some(
+                            [FetchPartition {
+                                partition: 0,
+                                current_leader_epoch: Some(-1),
+                                fetch_offset: 0,
+                                last_fetched_epoch: Some(-1),
+                                log_start_offset: Some(-1),
+                                partition_max_bytes: 50 * 1024,
+                                replica_directory_id: None,
+                            }]
+                            .into(),
+                        ),
+                    }]
+                    .into(),
+                ),
+                forgotten_topics_data: Some([].into()),
+                rack_id: Some("".into()),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let start = Instant::now();
+
+        let response = broker
+            .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+            .await?;
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(250), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+
+        let Frame {
+            header:
+                Header::Response {
+                    correlation_id: response_correlation_id,
+                },
+            body: Body::FetchResponse { error_code, .. },
+            ..
+        } = Frame::response_from_bytes(&response, api_key, api_version)?
+        else {
+            panic!("fetch response")
+        };
+
+        assert_eq!(correlation_id, response_correlation_id);
+        assert_eq!(Some(i16::from(ErrorCode::RequestTimedOut)), error_code);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn timed_out_request_completes() -> Result<()> {
+        let cluster_id = "abc";
+        let node_id = 111;
+
+        let storage = StorageContainer::DynoStore(DynoStore::new(
+            cluster_id,
+            node_id,
+            Faulty::default().put_delay(Duration::from_millis(100)),
+        ));
+
+        let api_key = 0;
+        let api_version = 9;
+
+        let mut broker = Broker::new(
+            &config(cluster_id, node_id)?,
+            storage.clone(),
+            Controller::with_storage(storage.clone())?,
+            Uuid::nil(),
+        )
+        .api_request_timeouts([(api_key, Duration::from_millis(10))].into());
+
+        let topition = Topition::new("pqr", 0);
+
+        _ = broker
+            .storage
+            .create_topic(
+                CreatableTopic {
+                    name: topition.topic().into(),
+                    num_partitions: 1,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let request = inflated::Batch::builder()
+            .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+            .build()
+            .and_then(deflated::Batch::try_from)
+            .and_then(|batch| {
+                Frame::request(
+                    Header::Request {
+                        api_key,
+                        api_version,
+                        correlation_id: 6,
+                        client_id: Some("test".into()),
+                    },
+                    Body::ProduceRequest {
+                        transactional_id: None,
+                        acks: -1,
+                        timeout_ms: 30_000,
+                        topic_data: Some(vec![TopicProduceData {
+                            name: topition.topic().into(),
+                            partition_data: Some(vec![PartitionProduceData {
+                                index: topition.partition(),
+                                records: Some(deflated::Frame {
+                                    batches: vec![batch],
+                                }),
+                            }]),
+                        }]),
+                    },
+                )
+            })
+            .map(Bytes::from)?;
+
+        let response = broker
+            .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+            .await?;
+
+        let Frame {
+            body: Body::ProduceResponse { responses, .. },
+            ..
+        } = Frame::response_from_bytes(&response, api_key, api_version)?
+        else {
+            panic!("produce response")
+        };
+
+        assert_eq!(
+            vec![i16::from(ErrorCode::RequestTimedOut)],
+            responses
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|response| response.partition_responses.iter().flatten())
+                .map(|partition| partition.error_code)
+                .collect::<Vec<_>>()
+        );
+
+        // the produce was not cancelled part way, running to completion
+        let mut storage = storage;
+
+        for _ in 0..50 {
+            if storage.offset_stage(&topition).await?.high_watermark() == 1 {
+                break;
+            }
+
+            sleep(Duration::from_millis(100)).await;
+        }
+
+        assert_eq!(1, storage.offset_stage(&topition).await?.high_watermark());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_list_offsets_isolation_level() -> Result<()> {
+        let mut broker = broker()?;
+
+        let api_key = 2;
+        let api_version = 7;
+        let correlation_id = 32123;
+
+        let request = Frame::request(
+            Header::Request {
+                api_key,
+                api_version,
+                correlation_id,
+                client_id: Some("test".into()),
+            },
+            Body::ListOffsetsRequest {
+                replica_id: -1,
+                isolation_level: Some(7),
+                topics: Some(
+                    [ListOffsetsTopic {
+                        name: "pqr".into(),
+                        partitions: Some(
+                            [ListOffsetsPartition {
+                                partition_index: 0,
+                                current_leader_epoch: Some(-1),
+                                timestamp: ListOffsetRequest::Latest.try_into()?,
+                                max_num_offsets: None,
+                            }]
+                            .into(),
+                        ),
+                    }]
+                    .into(),
+                ),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let response = broker
+            .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+            .await?;
+
+        let Frame {
+            header:
+                Header::Response {
+                    correlation_id: response_correlation_id,
+                },
+            body:
+                Body::ListOffsetsResponse {
+                    topics: Some(topics),
+                    ..
+                },
+            ..
+        } = Frame::response_from_bytes(&response, api_key, api_version)?
+        else {
+            panic!("list offsets response")
+        };
+
+        assert_eq!(correlation_id, response_correlation_id);
+
+        assert_eq!(
+            vec![("pqr".into(), 0, i16::from(ErrorCode::InvalidRequest))],
+            topics
+                .iter()
+                .flat_map(|topic| {
+                    topic.partitions.iter().flatten().map(|partition| {
+                        (
+                            topic.name.clone(),
+                            partition.partition_index,
+                            partition.error_code,
+                        )
+                    })
+                })
+                .collect::<Vec<(String, i32, i16)>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_fetch_isolation_level() -> Result<()> {
+        let mut broker = broker()?;
+
+        let api_key = 1;
+        let api_version = 12;
+        let correlation_id = 32123;
+
+        let request = Frame::request(
+            Header::Request {
+                api_key,
+                api_version,
+                correlation_id,
+                client_id: Some("test".into()),
+            },
+            Body::FetchRequest {
+                cluster_id: None,
+                replica_id: Some(-1),
+                replica_state: None,
+                max_wait_ms: 500,
+                min_bytes: 1,
+                max_bytes: Some(50 * 1024),
+                isolation_level: Some(-3),
+                session_id: Some(0),
+                session_epoch: Some(-1),
+                topics: Some(
+                    [FetchTopic {
+                        topic: Some("pqr".into()),
+                        topic_id: None,
+                        partitions: Some(
+                            [FetchPartition {
+                                partition: 0,
+                                current_leader_epoch: Some(-1),
+                                fetch_offset: 0,
+                                last_fetched_epoch: Some(-1),
+                                log_start_offset: Some(-1),
+                                partition_max_bytes: 50 * 1024,
+                                replica_directory_id: None,
+                            }]
+                            .into(),
+                        ),
+                    }]
+                    .into(),
+                ),
+                forgotten_topics_data: Some([].into()),
+                rack_id: Some("".into()),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let response = broker
+            .process_request(&SocketAddr::from(([127, 0, 0, 1], 9092)), &request)
+            .await?;
+
+        let Frame {
+            header:
+                Header::Response {
+                    correlation_id: response_correlation_id,
+                },
+            body:
+                Body::FetchResponse {
+                    error_code,
+                    responses: Some(responses),
+                    ..
+                },
+            ..
+        } = Frame::response_from_bytes(&response, api_key, api_version)?
+        else {
+            panic!("fetch response")
+        };
+
+        assert_eq!(correlation_id, response_correlation_id);
+        assert_eq!(Some(i16::from(ErrorCode::InvalidRequest)), error_code);
+
+        assert_eq!(
+            vec![(0, i16::from(ErrorCode::InvalidRequest))],
+            responses
+                .iter()
+                .flat_map(|response| response.partitions.iter().flatten())
+                .map(|partition| (partition.partition_index, partition.error_code))
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chaos_errors() -> Result<()> {
+        let cluster_id = "abc";
+        let node_id = 111;
+
+        let storage =
+            StorageContainer::DynoStore(DynoStore::new(cluster_id, node_id, InMemory::new()));
+
+        let produce = 0;
+        let fetch = 1;
+        let list_offsets = 2;
+        let api_version = 9;
+        let correlation_id = 32123;
+
+        let mut broker = Broker::new(
+            &config(cluster_id, node_id)?,
+            storage.clone(),
+            Controller::with_storage(storage)?,
+            Uuid::nil(),
+        )
+        .chaos(
+            Chaos::default()
+                .apis([produce, list_offsets].into())
+                .error_rate(1.0)
+                .error_codes(vec![ErrorCode::NotCoordinator]),
+        );
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        let request = Frame::request(
+            Header::Request {
+                api_key: produce,
+                api_version,
+                correlation_id,
+                client_id: Some("test".into()),
+            },
+            Body::ProduceRequest {
+                transactional_id: None,
+                acks: -1,
+                timeout_ms: 1_500,
+                topic_data: Some(
+                    [TopicProduceData {
+                        name: "pqr".into(),
+                        partition_data: Some(
+                            [PartitionProduceData {
+                                index: 0,
+                                records: None,
+                            }]
+                            .into(),
+                        ),
+                    }]
+                    .into(),
+                ),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let Frame {
+            body: Body::ProduceResponse { responses, .. },
+            ..
+        } = Frame::response_from_bytes(
+            &broker.process_request(&peer, &request).await?,
+            produce,
+            api_version,
+        )?
+        else {
+            panic!("produce response")
+        };
+
+        let responses = responses.unwrap_or_default();
+        assert_eq!(1, responses.len());
+        assert_eq!("pqr", responses[0].name);
+
+        let partitions = responses[0]
+            .partition_responses
+            .as_deref()
+            .unwrap_or_default();
+        assert_eq!(1, partitions.len());
+        assert_eq!(0, partitions[0].index);
+        assert_eq!(
+            i16::from(ErrorCode::NotCoordinator),
+            partitions[0].error_code
+        );
+
+        let request = Frame::request(
+            Header::Request {
+                api_key: fetch,
+                api_version: 12,
+                correlation_id,
+                client_id: Some("test".into()),
+            },
+            Body::FetchRequest {
+                cluster_id: None,
+                replica_id: Some(-1),
+                replica_state: None,
+                max_wait_ms: 0,
+                min_bytes: 1,
+                max_bytes: Some(50 * 1024),
+                isolation_level: Some(0),
+                session_id: Some(0),
+                session_epoch: Some(-1),
+                topics: Some([].into()),
+                forgotten_topics_data: Some([].into()),
+                rack_id: Some("".into()),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let Frame {
+            body: Body::FetchResponse { error_code, .. },
+            ..
+        } = Frame::response_from_bytes(&broker.process_request(&peer, &request).await?, fetch, 12)?
+        else {
+            panic!("fetch response")
+        };
+
+        assert_eq!(Some(i16::from(ErrorCode::None)), error_code);
+
+        // list offsets has no top level error code
+        let request = Frame::request(
+            Header::Request {
+                api_key: list_offsets,
+                api_version: 7,
+                correlation_id,
+                client_id: Some("test".into()),
+            },
+            Body::ListOffsetsRequest {
+                replica_id: -1,
+                isolation_level: Some(0),
+                topics: Some(
+                    [ListOffsetsTopic {
+                        name: "pqr".into(),
+                        partitions: Some(
+                            [ListOffsetsPartition {
+                                partition_index: 0,
+                                current_leader_epoch: Some(-1),
+                                timestamp: -1,
+                                max_num_offsets: None,
+                            }]
+                            .into(),
+                        ),
+                    }]
+                    .into(),
+                ),
+            },
+        )
+        .map(Bytes::from)?;
+
+        let Frame {
+            body: Body::ListOffsetsResponse { topics, .. },
+            ..
+        } = Frame::response_from_bytes(
+            &broker.process_request(&peer, &request).await?,
+            list_offsets,
+            7,
+        )?
+        else {
+            panic!("list offsets response")
+        };
+
+        assert_eq!(
+            vec![("pqr".into(), 0, i16::from(ErrorCode::NotCoordinator))],
+            topics
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|topic| {
+                    topic
+                        .partitions
+                        .iter()
+                        .flatten()
+                        .map(|partition| {
+                            (
+                                topic.name.clone(),
+                                partition.partition_index,
+                                partition.error_code,
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<(String, i32, i16)>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drain() -> Result<()> {
+        let drain = Drain::default();
+        let mut broker = broker()?.drain(drain.clone());
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+        let topic = "pqr";
+
+        _ = broker
+            .storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let produce = Body::ProduceRequest {
+            transactional_id: None,
+            acks: -1,
+            timeout_ms: 1_500,
+            topic_data: Some(
+                [TopicProduceData {
+                    name: topic.into(),
+                    partition_data: Some(
+                        [PartitionProduceData {
+                            index: 0,
+                            records: None,
+                        }]
+                        .into(),
+                    ),
+                }]
+                .into(),
+            ),
+        };
+
+        let produce_error_codes = |body: Body| {
+            let Body::ProduceResponse { responses, .. } = body else {
+                panic!("produce response")
+            };
+
+            responses
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|response| response.partition_responses.iter().flatten())
+                .map(|partition| partition.error_code)
+                .collect::<Vec<_>>()
+        };
+
+        // a fetch waiting for data when draining begins
+        let mut fetcher = broker.clone();
+
+        let (fetched, produced) = tokio::join!(
+            fetcher.response_for(
+                &peer,
+                Some("test"),
+                Body::FetchRequest {
+                    cluster_id: None,
+                    replica_id: Some(-1),
+                    replica_state: None,
+                    max_wait_ms: 500,
+                    min_bytes: 1,
+                    max_bytes: Some(50 * 1024),
+                    isolation_level: Some(0),
+                    session_id: Some(0),
+                    session_epoch: Some(-1),
+                    topics: Some(
+                        [FetchTopic {
+                            topic: Some(topic.into()),
+                            topic_id: None,
+                            partitions: Some(
+                                [FetchPartition {
+                                    partition: 0,
+                                    current_leader_epoch: Some(-1),
+                                    fetch_offset: 0,
+                                    last_fetched_epoch: Some(-1),
+                                    log_start_offset: Some(-1),
+                                    partition_max_bytes: 50 * 1024,
+                                    replica_directory_id: None,
+                                }]
+                                .into(),
+                            ),
+                        }]
+                        .into(),
+                    ),
+                    forgotten_topics_data: Some([].into()),
+                    rack_id: Some("".into()),
+                },
+                6,
+            ),
+            async {
+                sleep(Duration::from_millis(50)).await;
+                drain.draining(true);
+
+                broker
+                    .response_for(&peer, Some("test"), produce.clone(), 7)
+                    .await
+            }
+        );
+
+        assert_eq!(
+            vec![i16::from(ErrorCode::NotLeaderOrFollower)],
+            produce_error_codes(produced?)
+        );
+
+        let Body::FetchResponse {
+            error_code,
+            responses,
+            ..
+        } = fetched?
+        else {
+            panic!("fetch response")
+        };
+
+        assert_eq!(Some(i16::from(ErrorCode::None)), error_code);
+        assert_eq!(
+            vec![i16::from(ErrorCode::None)],
+            responses
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|response| response.partitions.iter().flatten())
+                .map(|partition| partition.error_code)
+                .collect::<Vec<_>>()
+        );
+
+        drain.draining(false);
+
+        assert_ne!(
+            vec![i16::from(ErrorCode::NotLeaderOrFollower)],
+            produce_error_codes(broker.response_for(&peer, Some("test"), produce, 8).await?)
+        );
+
+        Ok(())
+    }
+
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Uppercase;
+
+    impl Transform for Uppercase {
+        fn transform(&self, value: Bytes) -> Result<Bytes, String> {
+            std::str::from_utf8(&value[..])
+                .map(|value| Bytes::from(value.to_uppercase()))
+                .map_err(|error| error.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn transform_values() -> Result<()> {
+        let topic = "pqr";
+        let mut broker =
+            broker()?.transforms(Transforms::default().topic(topic, Arc::new(Uppercase)));
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        _ = broker
+            .storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let produce = |values: &[&'static [u8]]| -> Result<Body> {
+            let batch = values
+                .iter()
+                .enumerate()
+                .try_fold(
+                    inflated::Batch::builder(),
+                    |builder, (offset_delta, value)| {
+                        i32::try_from(offset_delta).map(|offset_delta| {
+                            builder.last_offset_delta(offset_delta).record(
+                                Record::builder()
+                                    .offset_delta(offset_delta)
+                                    .value(Bytes::from_static(value).into()),
+                            )
+                        })
+                    },
+                )?
+                .build()
+                .and_then(deflated::Batch::try_from)?;
+
+            Ok(Body::ProduceRequest {
+                transactional_id: None,
+                acks: -1,
+                timeout_ms: 1_500,
+                topic_data: Some(
+                    [TopicProduceData {
+                        name: topic.into(),
+                        partition_data: Some(
+                            [PartitionProduceData {
+                                index: 0,
+                                records: Some(deflated::Frame {
+                                    batches: vec![batch],
+                                }),
+                            }]
+                            .into(),
+                        ),
+                    }]
+                    .into(),
+                ),
+            })
+        };
+
+        let produce_error_codes = |body: Body| {
+            let Body::ProduceResponse { responses, .. } = body else {
+                panic!("produce response")
+            };
+
+            responses
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|response| response.partition_responses.iter().flatten())
+                .map(|partition| partition.error_code)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![i16::from(ErrorCode::None)],
+            produce_error_codes(
+                broker
+                    .response_for(&peer, Some("test"), produce(&[b"abc", b"def"])?, 6)
+                    .await?
+            )
+        );
+
+        // a value that cannot be transformed rejects the record
+        assert_eq!(
+            vec![i16::from(ErrorCode::InvalidRecord)],
+            produce_error_codes(
+                broker
+                    .response_for(&peer, Some("test"), produce(&[b"ghi", b"\xff"])?, 7)
+                    .await?
+            )
+        );
+
+        let Body::FetchResponse { responses, .. } = broker
+            .response_for(
+                &peer,
+                Some("test"),
+                Body::FetchRequest {
+                    cluster_id: None,
+                    replica_id: Some(-1),
+                    replica_state: None,
+                    max_wait_ms: 500,
+                    min_bytes: 1,
+                    max_bytes: Some(50 * 1024),
+                    isolation_level: Some(0),
+                    session_id: Some(0),
+                    session_epoch: Some(-1),
+                    topics: Some(
+                        [FetchTopic {
+                            topic: Some(topic.into()),
+                            topic_id: None,
+                            partitions: Some(
+                                [FetchPartition {
+                                    partition: 0,
+                                    current_leader_epoch: Some(-1),
+                                    fetch_offset: 0,
+                                    last_fetched_epoch: Some(-1),
+                                    log_start_offset: Some(-1),
+                                    partition_max_bytes: 50 * 1024,
+                                    replica_directory_id: None,
+                                }]
+                                .into(),
+                            ),
+                        }]
+                        .into(),
+                    ),
+                    forgotten_topics_data: Some([].into()),
+                    rack_id: Some("".into()),
+                },
+                8,
+            )
+            .await?
+        else {
+            panic!("fetch response")
+        };
+
+        let values = responses
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|response| response.partitions.unwrap_or_default())
+            .flat_map(|partition| partition.records)
+            .flat_map(|frame| frame.batches)
+            .map(inflated::Batch::try_from)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flat_map(|batch| batch.records)
+            .flat_map(|record| record.value)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![Bytes::from_static(b"ABC"), Bytes::from_static(b"DEF")],
+            values
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn advertised_port() -> Result<()> {
+        let node_id = 111;
+        let cluster_id = Uuid::now_v7().to_string();
+
+        let tcp_listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let port = tcp_listener.local_addr()?.port();
+
+        let listener = Url::parse(&format!("tcp://127.0.0.1:{port}/"))?;
+        let advertised_listener = Url::parse("tcp://localhost:9092/")?;
+        let storage_url = Url::parse("memory://tansu/")?;
+
+        let storage = StorageContainer::builder()
+            .cluster_id(cluster_id.as_str())
+            .node(node_id)
+            .advertised_listener(advertised_listener.clone())
+            .storage(storage_url.clone())
+            .build()?;
+
+        let config = Config::builder()
+            .cluster_id(cluster_id.as_str())
+            .node_id(node_id)
+            .listener(listener)
+            .advertised_listener(advertised_listener)
+            .storage(storage_url)
+            .build()?;
+
+        let mut broker = Broker::new(
+            &config,
+            storage.clone(),
+            Controller::with_storage(storage)?,
+            Uuid::now_v7(),
+        );
+
+        broker.register().await?;
+
+        let server = tokio::spawn(async move { broker.accept(tcp_listener).await });
+
+        let mut stream = TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], port))).await?;
+
+        let mut exchange = async |api_key: i16, api_version: i16, body: Body| -> Result<Body> {
+            let request = Frame::request(
+                Header::Request {
+                    api_key,
+                    api_version,
+                    correlation_id: 6,
+                    client_id: Some("test".into()),
+                },
+                body,
+            )?;
+
+            stream.write_all(&request).await?;
+            stream.flush().await?;
+
+            let response = read_frame(&mut stream, DEFAULT_MAX_REQUEST_SIZE)
+                .await?
+                .ok_or(Error::Message("disconnected".into()))?;
+
+            Frame::response_from_bytes(&response, api_key, api_version)
+                .map(|frame| frame.body)
+                .map_err(Into::into)
+        };
+
+        let Body::MetadataResponse {
+            brokers: Some(brokers),
+            ..
+        } = exchange(
+            3,
+            12,
+            Body::MetadataRequest {
+                topics: None,
+                allow_auto_topic_creation: Some(false),
+                include_cluster_authorized_operations: None,
+                include_topic_authorized_operations: Some(false),
+            },
+        )
+        .await?
+        else {
+            panic!("metadata response")
+        };
+
+        assert_eq!(
+            vec![("localhost", 9092)],
+            brokers
+                .iter()
+                .map(|broker| (broker.host.as_str(), broker.port))
+                .collect::<Vec<_>>()
+        );
+
+        let Body::DescribeClusterResponse {
+            brokers: Some(brokers),
+            ..
+        } = exchange(
+            60,
+            1,
+            Body::DescribeClusterRequest {
+                include_cluster_authorized_operations: false,
+                endpoint_type: Some(1),
+            },
+        )
+        .await?
+        else {
+            panic!("describe cluster response")
+        };
+
+        assert_eq!(
+            vec![("localhost", 9092)],
+            brokers
+                .iter()
+                .map(|broker| (broker.host.as_str(), broker.port))
+                .collect::<Vec<_>>()
+        );
+
+        let Body::FindCoordinatorResponse { host, port, .. } = exchange(
+            10,
+            3,
+            Body::FindCoordinatorRequest {
+                key: Some("abc".into()),
+                key_type: Some(0),
+                coordinator_keys: None,
+            },
+        )
+        .await?
+        else {
+            panic!("find coordinator response")
+        };
+
+        assert_eq!(Some("localhost".into()), host);
+        assert_eq!(Some(9092), port);
+
+        let Body::FindCoordinatorResponse {
+            coordinators: Some(coordinators),
+            ..
+        } = exchange(
+            10,
+            4,
+            Body::FindCoordinatorRequest {
+                key: None,
+                key_type: Some(0),
+                coordinator_keys: Some(["abc".into(), "pqr".into()].into()),
+            },
+        )
+        .await?
+        else {
+            panic!("find coordinator response")
+        };
+
+        assert_eq!(
+            vec![("localhost", 9092); 2],
+            coordinators
+                .iter()
+                .map(|coordinator| (coordinator.host.as_str(), coordinator.port))
+                .collect::<Vec<_>>()
+        );
+
+        server.abort();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn socket_options() -> Result<()> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let client = TcpStream::connect(listener.local_addr()?).await?;
+        let (stream, _) = listener.accept().await?;
+
+        SocketOptions::default()
+            .keepalive(Some(Duration::from_secs(30)))
+            .send_buffer_size(Some(65_536))
+            .recv_buffer_size(Some(65_536))
+            .apply(&stream)?;
+
+        assert!(stream.nodelay()?);
+        assert!(SockRef::from(&stream).keepalive()?);
+
+        SocketOptions::default().nodelay(false).apply(&stream)?;
+        assert!(!stream.nodelay()?);
+
+        drop(client);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn active_connections() -> Result<()> {
+        let reader = SharedReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+
+        let broker = Broker {
+            metron: Metron::with_meter("abc", &provider.meter("active_connections")),
+            ..broker()?
+        };
+
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let addr = listener.local_addr()?;
+
+        let server = tokio::spawn(async move { broker.accept(listener).await });
+
+        let first = TcpStream::connect(addr).await?;
+        let second = TcpStream::connect(addr).await?;
+
+        assert_eq!(2, reader.eventually("tansu_active_connections", 2).await?);
+        assert_eq!(0, reader.up_down_counter("tansu_in_flight_requests")?);
+
+        drop(first);
+        drop(second);
+
+        assert_eq!(0, reader.eventually("tansu_active_connections", 0).await?);
+
+        server.abort();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn in_flight_when_cancelled() -> Result<()> {
+        let reader = SharedReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+
+        let metron = Metron::with_meter("abc", &provider.meter("in_flight_when_cancelled"));
+
+        let request = async {
+            let _in_flight = metron.in_flight();
+            future::pending::<()>().await
+        };
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), request)
+                .await
+                .is_err()
+        );
+
+        assert_eq!(0, reader.up_down_counter("tansu_in_flight_requests")?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn connection_limit() -> Result<()> {
+        let reader = SharedReader::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+
+        let broker = Broker {
+            metron: Metron::with_meter("abc", &provider.meter("connection_limit")),
+            ..broker()?
+        }
+        .connection_limit(ConnectionLimit::default().max_connections_per_ip(Some(2)));
+
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let port = listener.local_addr()?.port();
+
+        let server = tokio::spawn(async move { broker.accept(listener).await });
+
+        let connect = async || -> Result<TcpStream> {
+            TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], port)))
+                .await
+                .map_err(Into::into)
+        };
+
+        let first = connect().await?;
+        let second = connect().await?;
+        assert_eq!(2, reader.eventually("tansu_active_connections", 2).await?);
+
+        // the excess connection from the same address is closed on accept
+        let mut excess = connect().await?;
+        let mut buf = [0u8; 1];
+        assert_eq!(
+            0,
+            timeout(Duration::from_secs(5), excess.read(&mut buf))
+                .await
+                .expect("excess connection closed")?
+        );
+
+        // closing a connection makes room for another from the same address
+        drop(first);
+        assert_eq!(1, reader.eventually("tansu_active_connections", 1).await?);
+
+        let replacement = connect().await?;
+        assert_eq!(2, reader.eventually("tansu_active_connections", 2).await?);
+
+        drop((second, replacement));
+        server.abort();
+
+        Ok(())
+    }
+
+    #[test]
+    fn topic_attributes() {
+        let cluster_id = "abc";
+
+        let metron = Metron::new(cluster_id, Uuid::nil());
+
+        assert_eq!(
+            vec![KeyValue::new("cluster_id", cluster_id)],
+            metron.topic_attributes(cluster_id, "hot", 3)
+        );
+
+        let metron = metron.topics(Some(BTreeSet::from(["hot".into()])));
+
+        assert_eq!(
+            vec![
+                KeyValue::new("cluster_id", cluster_id),
+                KeyValue::new("topic", "hot"),
+                KeyValue::new("partition", 3),
+            ],
+            metron.topic_attributes(cluster_id, "hot", 3)
+        );
+
+        for (topic, partition) in [("cold", 0), ("tepid", 5)] {
+            assert_eq!(
+                vec![
+                    KeyValue::new("cluster_id", cluster_id),
+                    KeyValue::new("topic", OTHER_TOPIC),
+                ],
+                metron.topic_attributes(cluster_id, topic, partition)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn connection_request_sequence() -> Result<()> {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let collector = SpanCollector::default();
+
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(collector.clone())
+            .build();
+
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(
+                tracing_opentelemetry::layer()
+                    .with_tracer(provider.tracer("connection_request_sequence")),
+            ),
+        );
+
+        let mut broker = broker()?;
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        for correlation_id in [6, 6] {
+            let request = Frame::request(
+                Header::Request {
+                    api_key: 18,
+                    api_version: 3,
+                    correlation_id,
+                    client_id: Some("test".into()),
+                },
+                Body::ApiVersionsRequest {
+                    client_software_name: Some("test".into()),
+                    client_software_version: Some("1.0".into()),
+                },
+            )
+            .map(Bytes::from)?;
+
+            _ = broker.process_request(&peer, &request).await?;
+        }
+
+        assert_eq!(
+            vec![Value::from(peer.to_string()), Value::from(peer.to_string())],
+            collector.attribute("api_versions", "peer")
+        );
+
+        // a retry has the same correlation id, but a different sequence,
+        // with u64 span fields exported as strings
+        assert_eq!(
+            vec![Value::from("1"), Value::from("2")],
+            collector.attribute("api_versions", "conn_req_seq")
+        );
+
+        Ok(())
     }
 }
@@ -13,22 +13,51 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::sync::LazyLock;
-
-use tansu_kafka_sans_io::{Body, ErrorCode, RootMessageMeta, api_versions_response::ApiVersion};
+use tansu_kafka_sans_io::{
+    API_VERSIONS_API_KEY, Body, ErrorCode, Frame, Header, RootMessageMeta,
+    api_versions_response::ApiVersion,
+};
+use tracing::warn;
 
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct ApiVersionsRequest;
 
-const TELEMETRY: [i16; 3] = [71, 72, 74];
-const SASL: [i16; 1] = [17];
-
-static UNSUPPORTED: LazyLock<Vec<i16>> = LazyLock::new(|| {
-    let mut unsupported = vec![];
-    unsupported.extend_from_slice(&TELEMETRY);
-    unsupported.extend_from_slice(&SASL);
-    unsupported
-});
+// requests that have a handler in Broker::response_for
+pub(crate) const SUPPORTED: [&str; 33] = [
+    "AddOffsetsToTxnRequest",
+    "AddPartitionsToTxnRequest",
+    "ApiVersionsRequest",
+    "ConsumerGroupDescribeRequest",
+    "ConsumerGroupHeartbeatRequest",
+    "CreateTopicsRequest",
+    "DeleteGroupsRequest",
+    "DeleteRecordsRequest",
+    "DeleteTopicsRequest",
+    "DescribeClusterRequest",
+    "DescribeConfigsRequest",
+    "DescribeGroupsRequest",
+    "DescribeTopicPartitionsRequest",
+    "ElectLeadersRequest",
+    "EndTxnRequest",
+    "FetchRequest",
+    "FindCoordinatorRequest",
+    "GetTelemetrySubscriptionsRequest",
+    "HeartbeatRequest",
+    "IncrementalAlterConfigsRequest",
+    "InitProducerIdRequest",
+    "JoinGroupRequest",
+    "LeaveGroupRequest",
+    "ListGroupsRequest",
+    "ListOffsetsRequest",
+    "ListPartitionReassignmentsRequest",
+    "MetadataRequest",
+    "OffsetCommitRequest",
+    "OffsetFetchRequest",
+    "ProduceRequest",
+    "PushTelemetryRequest",
+    "SyncGroupRequest",
+    "TxnOffsetCommitRequest",
+];
 
 impl ApiVersionsRequest {
     pub fn response(
@@ -49,7 +78,7 @@ impl ApiVersionsRequest {
                 RootMessageMeta::messages()
                     .requests()
                     .iter()
-                    .filter(|(api_key, _)| !UNSUPPORTED.contains(api_key))
+                    .filter(|(_, meta)| SUPPORTED.contains(&meta.name))
                     .map(|(_, meta)| ApiVersion {
                         api_key: meta.api_key,
                         min_version: meta.version.valid.start,
@@ -60,4 +89,58 @@ impl ApiVersionsRequest {
             throttle_time_ms: Some(0),
         }
     }
+
+    /// A request for ApiVersions in a version that the broker does not
+    /// understand is answered with a version 0 response, that only
+    /// advertises the range supported for ApiVersions, so that the client
+    /// can retry in a supported version rather than being disconnected
+    pub(crate) fn unsupported_version(
+        frame: &[u8],
+    ) -> tansu_kafka_sans_io::Result<Option<Vec<u8>>> {
+        // size, api key, api version and correlation id
+        let Some(header) = frame.get(4..12) else {
+            return Ok(None);
+        };
+
+        let api_key = i16::from_be_bytes([header[0], header[1]]);
+        let api_version = i16::from_be_bytes([header[2], header[3]]);
+        let correlation_id = i32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+
+        if api_key != API_VERSIONS_API_KEY {
+            return Ok(None);
+        }
+
+        let Some(meta) = RootMessageMeta::messages()
+            .requests()
+            .get(&API_VERSIONS_API_KEY)
+        else {
+            return Ok(None);
+        };
+
+        if meta.version.valid.within(api_version) {
+            return Ok(None);
+        }
+
+        warn!(api_key, api_version, correlation_id, valid = ?meta.version.valid);
+
+        Frame::response(
+            Header::Response { correlation_id },
+            Body::ApiVersionsResponse {
+                finalized_features: None,
+                finalized_features_epoch: None,
+                supported_features: None,
+                zk_migration_ready: None,
+                error_code: ErrorCode::UnsupportedVersion.into(),
+                api_keys: Some(vec![ApiVersion {
+                    api_key: API_VERSIONS_API_KEY,
+                    min_version: meta.version.valid.start,
+                    max_version: meta.version.valid.end,
+                }]),
+                throttle_time_ms: None,
+            },
+            API_VERSIONS_API_KEY,
+            0,
+        )
+        .map(Some)
+    }
 }
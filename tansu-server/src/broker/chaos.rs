@@ -0,0 +1,143 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeSet, time::Duration};
+
+use rand::{prelude::*, rng};
+use tansu_kafka_sans_io::ErrorCode;
+
+// a fault injected into a request in place of its response
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Fault {
+    Error(ErrorCode),
+    Disconnect,
+}
+
+/// Latency, transient errors and dropped connections injected into
+/// requests to verify client resilience, nothing is injected by default
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chaos {
+    apis: BTreeSet<i16>,
+    latency: Option<Duration>,
+    error_rate: f64,
+    error_codes: Vec<ErrorCode>,
+    disconnect_rate: f64,
+}
+
+impl Default for Chaos {
+    fn default() -> Self {
+        Self {
+            apis: BTreeSet::new(),
+            latency: None,
+            error_rate: 0.0,
+            error_codes: vec![ErrorCode::RequestTimedOut],
+            disconnect_rate: 0.0,
+        }
+    }
+}
+
+impl Chaos {
+    // the api keys that chaos applies to, or every api when empty
+    pub fn apis(self, apis: BTreeSet<i16>) -> Self {
+        Self { apis, ..self }
+    }
+
+    pub fn latency(self, latency: Option<Duration>) -> Self {
+        Self { latency, ..self }
+    }
+
+    pub fn error_rate(self, error_rate: f64) -> Self {
+        Self { error_rate, ..self }
+    }
+
+    pub fn error_codes(self, error_codes: Vec<ErrorCode>) -> Self {
+        Self {
+            error_codes,
+            ..self
+        }
+    }
+
+    pub fn disconnect_rate(self, disconnect_rate: f64) -> Self {
+        Self {
+            disconnect_rate,
+            ..self
+        }
+    }
+
+    fn applies(&self, api_key: i16) -> bool {
+        self.apis.is_empty() || self.apis.contains(&api_key)
+    }
+
+    pub fn delay(&self, api_key: i16) -> Option<Duration> {
+        self.latency.filter(|_| self.applies(api_key))
+    }
+
+    pub fn fault(&self, api_key: i16) -> Option<Fault> {
+        if !self.applies(api_key) {
+            return None;
+        }
+
+        let mut rng = rng();
+
+        if rng.random::<f64>() < self.disconnect_rate {
+            Some(Fault::Disconnect)
+        } else if rng.random::<f64>() < self.error_rate {
+            self.error_codes.choose(&mut rng).copied().map(Fault::Error)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_by_default() {
+        let chaos = Chaos::default();
+
+        for api_key in 0..75 {
+            assert_eq!(None, chaos.delay(api_key));
+            assert_eq!(None, chaos.fault(api_key));
+        }
+    }
+
+    #[test]
+    fn scoped_to_apis() {
+        let produce = 0;
+        let fetch = 1;
+
+        let chaos = Chaos::default()
+            .apis([produce].into())
+            .latency(Some(Duration::from_millis(5)))
+            .error_rate(1.0)
+            .error_codes(vec![ErrorCode::NotCoordinator]);
+
+        assert_eq!(Some(Duration::from_millis(5)), chaos.delay(produce));
+        assert_eq!(
+            Some(Fault::Error(ErrorCode::NotCoordinator)),
+            chaos.fault(produce)
+        );
+
+        assert_eq!(None, chaos.delay(fetch));
+        assert_eq!(None, chaos.fault(fetch));
+
+        assert_eq!(
+            Some(Fault::Disconnect),
+            chaos.disconnect_rate(1.0).fault(produce)
+        );
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, warn};
+
+// refused connections are logged at most once in this interval
+const REFUSED_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default)]
+struct Connections {
+    total: usize,
+    by_ip: BTreeMap<IpAddr, usize>,
+    refused: u64,
+    logged: Option<Instant>,
+}
+
+/// Caps the connections accepted in total and from any one address,
+/// excess connections are closed as soon as they are accepted
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionLimit {
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    connections: Arc<Mutex<Connections>>,
+}
+
+/// An accepted connection, released when dropped
+#[derive(Debug)]
+pub(crate) struct Permit {
+    ip: IpAddr,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut connections = self
+            .connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        connections.total = connections.total.saturating_sub(1);
+
+        if let Some(count) = connections.by_ip.get_mut(&self.ip) {
+            *count = count.saturating_sub(1);
+
+            if *count == 0 {
+                _ = connections.by_ip.remove(&self.ip);
+            }
+        }
+    }
+}
+
+impl ConnectionLimit {
+    pub fn max_connections(self, max_connections: Option<usize>) -> Self {
+        Self {
+            max_connections,
+            ..self
+        }
+    }
+
+    pub fn max_connections_per_ip(self, max_connections_per_ip: Option<usize>) -> Self {
+        Self {
+            max_connections_per_ip,
+            ..self
+        }
+    }
+
+    // a permit for a connection from this address, or none when over a limit
+    pub(crate) fn acquire(&self, ip: IpAddr) -> Option<Permit> {
+        let mut connections = self
+            .connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let from_ip = connections.by_ip.get(&ip).copied().unwrap_or_default();
+
+        let exceeded = if self
+            .max_connections
+            .is_some_and(|max_connections| connections.total >= max_connections)
+        {
+            Some("max_connections")
+        } else if self
+            .max_connections_per_ip
+            .is_some_and(|max_connections_per_ip| from_ip >= max_connections_per_ip)
+        {
+            Some("max_connections_per_ip")
+        } else {
+            None
+        };
+
+        if let Some(limit) = exceeded {
+            connections.refused += 1;
+
+            let now = Instant::now();
+
+            if connections
+                .logged
+                .is_none_or(|logged| now.duration_since(logged) >= REFUSED_LOG_INTERVAL)
+            {
+                warn!(%ip, limit, refused = connections.refused);
+                connections.logged = Some(now);
+                connections.refused = 0;
+            } else {
+                debug!(%ip, limit);
+            }
+
+            return None;
+        }
+
+        connections.total += 1;
+        _ = connections.by_ip.insert(ip, from_ip + 1);
+
+        Some(Permit {
+            ip,
+            connections: self.connections.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const FIRST: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const SECOND: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    #[test]
+    fn unlimited_by_default() {
+        let limit = ConnectionLimit::default();
+        let permits = (0..1_000)
+            .map(|_| limit.acquire(FIRST))
+            .collect::<Option<Vec<_>>>();
+
+        assert_eq!(Some(1_000), permits.map(|permits| permits.len()));
+    }
+
+    #[test]
+    fn global() {
+        let limit = ConnectionLimit::default().max_connections(Some(2));
+
+        let first = limit.acquire(FIRST);
+        assert!(first.is_some());
+
+        let second = limit.acquire(SECOND);
+        assert!(second.is_some());
+        assert!(limit.acquire(SECOND).is_none());
+
+        // closing a connection releases its permit
+        drop(first);
+        assert!(limit.acquire(SECOND).is_some());
+    }
+
+    #[test]
+    fn per_ip() {
+        let limit = ConnectionLimit::default().max_connections_per_ip(Some(1));
+
+        let first = limit.acquire(FIRST);
+        assert!(first.is_some());
+        assert!(limit.acquire(FIRST).is_none());
+
+        // another address is unaffected
+        assert!(limit.acquire(SECOND).is_some());
+
+        drop(first);
+        assert!(limit.acquire(FIRST).is_some());
+    }
+}
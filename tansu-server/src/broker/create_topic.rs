@@ -13,16 +13,73 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::Result;
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    Result,
+    broker::{
+        policy::{NoPolicy, Policy},
+        produce::DEAD_LETTER_TOPIC,
+    },
+};
 use tansu_kafka_sans_io::{
     ErrorCode, create_topics_request::CreatableTopic, create_topics_response::CreatableTopicResult,
+    record::deflated::CompressionLevel,
+};
+use tansu_storage::{
+    CleanupPolicy, Error, Storage, TopicLimit, ZSTD_DICTIONARY, ZstdDictionaries, compaction,
+    compression_level,
 };
-use tansu_storage::Storage;
 use tracing::debug;
 
+// the error of a topic configuration that cannot be applied, checked when
+// the topic is created and when its configuration is altered
+pub(crate) fn invalid_config(topic: &str, name: &str, value: &str) -> Option<Error> {
+    match name {
+        "cleanup.policy" => CleanupPolicy::from_str(value).err(),
+        ZSTD_DICTIONARY => ZstdDictionaries::from_str(value).err(),
+        DEAD_LETTER_TOPIC => dead_letter_topic(topic, value).err(),
+        name => compression_level(CompressionLevel::default(), name, value)
+            .and_then(Result::err)
+            .or_else(|| compaction(name, value).and_then(Result::err)),
+    }
+}
+
+// the maximum length of a topic name
+const MAX_TOPIC_NAME_LENGTH: usize = 249;
+
+// the number of partitions for a topic without assignments that uses the
+// broker default (-1)
+pub(crate) const DEFAULT_NUM_PARTITIONS: i32 = 1;
+
+// the replication factor for a topic that uses the broker default (-1)
+pub(crate) const DEFAULT_REPLICATION_FACTOR: i16 = 3;
+
+// a dead letter topic must be a legal topic name other than the topic
+// itself, while empty leaves it unset
+fn dead_letter_topic(name: &str, dead_letter: &str) -> Result<(), Error> {
+    if dead_letter == name {
+        Err(Error::Message(format!(
+            "{DEAD_LETTER_TOPIC} cannot be the topic itself"
+        )))
+    } else if dead_letter.len() > MAX_TOPIC_NAME_LENGTH
+        || !dead_letter
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        Err(Error::Message(format!(
+            "{DEAD_LETTER_TOPIC}: {dead_letter} is not a legal topic name"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CreateTopic<S> {
     storage: S,
+    topic_limit: TopicLimit,
+    policy: Arc<dyn Policy>,
 }
 
 impl<S> CreateTopic<S>
@@ -30,7 +87,22 @@ where
     S: Storage,
 {
     pub fn with_storage(storage: S) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            topic_limit: TopicLimit::default(),
+            policy: Arc::new(NoPolicy),
+        }
+    }
+
+    pub fn topic_limit(self, topic_limit: TopicLimit) -> Self {
+        Self {
+            topic_limit,
+            ..self
+        }
+    }
+
+    pub fn policy(self, policy: Arc<dyn Policy>) -> Self {
+        Self { policy, ..self }
     }
 
     async fn create_topic(
@@ -40,19 +112,72 @@ where
     ) -> CreatableTopicResult {
         let _ = validate_only;
 
+        // resolve the defaults before the topic is counted against any limit,
+        // with explicit replica assignments giving the number of partitions
         if topic.num_partitions == -1 {
-            topic.num_partitions = 1;
+            topic.num_partitions = topic
+                .assignments
+                .as_ref()
+                .filter(|assignments| !assignments.is_empty())
+                .map_or(DEFAULT_NUM_PARTITIONS, |assignments| {
+                    assignments.len() as i32
+                });
         }
 
         if topic.replication_factor == -1 {
-            topic.replication_factor = 3
+            topic.replication_factor = DEFAULT_REPLICATION_FACTOR
         }
 
         let name = topic.name.clone();
         let num_partitions = Some(topic.num_partitions);
         let replication_factor = Some(topic.replication_factor);
 
-        match self.storage.create_topic(topic, validate_only).await {
+        if let Some(error) = topic
+            .configs
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .find_map(|config| {
+                config
+                    .value
+                    .as_deref()
+                    .and_then(|value| invalid_config(&topic.name, config.name.as_str(), value))
+            })
+        {
+            debug!(?error);
+
+            return CreatableTopicResult {
+                name,
+                topic_id: Some([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+                error_code: ErrorCode::InvalidConfig.into(),
+                error_message: Some(error.to_string()),
+                topic_config_error_code: None,
+                num_partitions,
+                replication_factor,
+                configs: Some([].into()),
+            };
+        }
+
+        if let Err(error_message) = self.policy.validate_create_topic(&topic) {
+            debug!(error_message);
+
+            return CreatableTopicResult {
+                name,
+                topic_id: Some([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+                error_code: ErrorCode::PolicyViolation.into(),
+                error_message: Some(error_message),
+                topic_config_error_code: None,
+                num_partitions,
+                replication_factor,
+                configs: Some([].into()),
+            };
+        }
+
+        match self
+            .storage
+            .create_topic_within(topic, validate_only, self.topic_limit)
+            .await
+        {
             Ok(topic_id) => {
                 debug!(?topic_id);
 
@@ -68,6 +193,21 @@ where
                 }
             }
 
+            Err(tansu_storage::Error::TopicLimit(error_message)) => {
+                debug!(error_message);
+
+                CreatableTopicResult {
+                    name,
+                    topic_id: Some([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+                    error_code: ErrorCode::PolicyViolation.into(),
+                    error_message: Some(error_message),
+                    topic_config_error_code: None,
+                    num_partitions,
+                    replication_factor,
+                    configs: Some([].into()),
+                }
+            }
+
             Err(tansu_storage::Error::Api(error_code)) => CreatableTopicResult {
                 name,
                 topic_id: Some([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
@@ -119,7 +259,12 @@ where
 #[cfg(test)]
 mod tests {
     use object_store::memory::InMemory;
-    use tansu_storage::{NULL_TOPIC_ID, dynostore::DynoStore};
+    use tansu_kafka_sans_io::create_topics_request::{
+        CreatableReplicaAssignment, CreatableTopicConfig,
+    };
+    use tansu_storage::{
+        COMPRESSION_GZIP_LEVEL, COMPRESSION_ZSTD_LEVEL, NULL_TOPIC_ID, dynostore::DynoStore,
+    };
 
     use super::*;
 
@@ -261,4 +406,384 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn topic_limit() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        let mut create_topic = CreateTopic::with_storage(storage)
+            .topic_limit(TopicLimit::default().max_topics(Some(2)));
+
+        for (name, error_code) in [
+            ("pqr", ErrorCode::None),
+            ("stu", ErrorCode::None),
+            ("vwx", ErrorCode::PolicyViolation),
+        ] {
+            let r = create_topic
+                .response(
+                    Some(vec![CreatableTopic {
+                        name: name.into(),
+                        num_partitions: 3,
+                        replication_factor: 1,
+                        assignments: Some([].into()),
+                        configs: Some([].into()),
+                    }]),
+                    false,
+                )
+                .await?;
+
+            assert_eq!(1, r.len());
+            assert_eq!(name, r[0].name.as_str());
+            assert_eq!(error_code, ErrorCode::try_from(r[0].error_code)?);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn partition_limit() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        let mut create_topic = CreateTopic::with_storage(storage)
+            .topic_limit(TopicLimit::default().max_partitions(Some(5)));
+
+        let r = create_topic
+            .response(
+                Some(
+                    [("pqr", 3), ("stu", 3), ("vwx", 2)]
+                        .into_iter()
+                        .map(|(name, num_partitions)| CreatableTopic {
+                            name: name.into(),
+                            num_partitions,
+                            replication_factor: 1,
+                            assignments: Some([].into()),
+                            configs: Some([].into()),
+                        })
+                        .collect(),
+                ),
+                false,
+            )
+            .await?;
+
+        assert_eq!(3, r.len());
+
+        assert_eq!("pqr", r[0].name.as_str());
+        assert_eq!(ErrorCode::None, ErrorCode::try_from(r[0].error_code)?);
+
+        assert_eq!("stu", r[1].name.as_str());
+        assert_eq!(Some(NULL_TOPIC_ID), r[1].topic_id);
+        assert_eq!(
+            ErrorCode::PolicyViolation,
+            ErrorCode::try_from(r[1].error_code)?
+        );
+        assert_eq!(
+            Some("partition limit of 5 exceeded, 3 in use"),
+            r[1].error_message.as_deref()
+        );
+
+        assert_eq!("vwx", r[2].name.as_str());
+        assert_eq!(ErrorCode::None, ErrorCode::try_from(r[2].error_code)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn partition_limit_with_default_partitions() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        let mut create_topic = CreateTopic::with_storage(storage)
+            .topic_limit(TopicLimit::default().max_partitions(Some(2)));
+
+        let r = create_topic
+            .response(
+                Some(vec![CreatableTopic {
+                    name: "pqr".into(),
+                    num_partitions: -1,
+                    replication_factor: 1,
+                    assignments: Some(
+                        (0..3)
+                            .map(|partition_index| CreatableReplicaAssignment {
+                                partition_index,
+                                broker_ids: Some(vec![node]),
+                            })
+                            .collect(),
+                    ),
+                    configs: Some([].into()),
+                }]),
+                false,
+            )
+            .await?;
+
+        assert_eq!(1, r.len());
+        assert_eq!(Some(3), r[0].num_partitions);
+        assert_eq!(
+            ErrorCode::PolicyViolation,
+            ErrorCode::try_from(r[0].error_code)?
+        );
+
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct MinReplicationFactor(i16);
+
+    impl Policy for MinReplicationFactor {
+        fn validate_create_topic(&self, topic: &CreatableTopic) -> Result<(), String> {
+            if topic.replication_factor < self.0 {
+                Err(format!("replication.factor must be at least {}", self.0))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn policy_violation() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        let mut create_topic =
+            CreateTopic::with_storage(storage).policy(Arc::new(MinReplicationFactor(3)));
+
+        let r = create_topic
+            .response(
+                Some(
+                    [("pqr", 1), ("stu", 3)]
+                        .into_iter()
+                        .map(|(name, replication_factor)| CreatableTopic {
+                            name: name.into(),
+                            num_partitions: 3,
+                            replication_factor,
+                            assignments: Some([].into()),
+                            configs: Some([].into()),
+                        })
+                        .collect(),
+                ),
+                false,
+            )
+            .await?;
+
+        assert_eq!(2, r.len());
+
+        assert_eq!("pqr", r[0].name.as_str());
+        assert_eq!(Some(NULL_TOPIC_ID), r[0].topic_id);
+        assert_eq!(
+            ErrorCode::PolicyViolation,
+            ErrorCode::try_from(r[0].error_code)?
+        );
+        assert_eq!(
+            Some("replication.factor must be at least 3"),
+            r[0].error_message.as_deref()
+        );
+
+        assert_eq!("stu", r[1].name.as_str());
+        assert_ne!(Some(NULL_TOPIC_ID), r[1].topic_id);
+        assert_eq!(ErrorCode::None, ErrorCode::try_from(r[1].error_code)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cleanup_policy() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        let mut create_topic = CreateTopic::with_storage(storage);
+
+        let r = create_topic
+            .response(
+                Some(
+                    [("pqr", "compact,delete"), ("stu", "compact,remove")]
+                        .into_iter()
+                        .map(|(name, cleanup_policy)| CreatableTopic {
+                            name: name.into(),
+                            num_partitions: 3,
+                            replication_factor: 1,
+                            assignments: Some([].into()),
+                            configs: Some(vec![CreatableTopicConfig {
+                                name: "cleanup.policy".into(),
+                                value: Some(cleanup_policy.into()),
+                            }]),
+                        })
+                        .collect(),
+                ),
+                false,
+            )
+            .await?;
+
+        assert_eq!(2, r.len());
+
+        assert_eq!("pqr", r[0].name.as_str());
+        assert_eq!(ErrorCode::None, ErrorCode::try_from(r[0].error_code)?);
+
+        assert_eq!("stu", r[1].name.as_str());
+        assert_eq!(Some(NULL_TOPIC_ID), r[1].topic_id);
+        assert_eq!(
+            ErrorCode::InvalidConfig,
+            ErrorCode::try_from(r[1].error_code)?
+        );
+        assert_eq!(
+            Some("cleanup policy: compact,remove"),
+            r[1].error_message.as_deref()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn zstd_dictionary() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        let mut create_topic = CreateTopic::with_storage(storage);
+
+        let r = create_topic
+            .response(
+                Some(vec![CreatableTopic {
+                    name: "pqr".into(),
+                    num_partitions: 3,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some(vec![CreatableTopicConfig {
+                        name: ZSTD_DICTIONARY.into(),
+                        value: Some("not base64!".into()),
+                    }]),
+                }]),
+                false,
+            )
+            .await?;
+
+        assert_eq!(1, r.len());
+        assert_eq!(Some(NULL_TOPIC_ID), r[0].topic_id);
+        assert_eq!(
+            ErrorCode::InvalidConfig,
+            ErrorCode::try_from(r[0].error_code)?
+        );
+        assert_eq!(
+            Some("zstd dictionary: 0: Invalid symbol 32, offset 3."),
+            r[0].error_message.as_deref()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_compression_level() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        let mut create_topic = CreateTopic::with_storage(storage);
+
+        let r = create_topic
+            .response(
+                Some(
+                    [
+                        ("pqr", COMPRESSION_ZSTD_LEVEL, "19"),
+                        ("stu", COMPRESSION_ZSTD_LEVEL, "23"),
+                        ("vwx", COMPRESSION_GZIP_LEVEL, "fast"),
+                    ]
+                    .into_iter()
+                    .map(|(name, config, level)| CreatableTopic {
+                        name: name.into(),
+                        num_partitions: 3,
+                        replication_factor: 1,
+                        assignments: Some([].into()),
+                        configs: Some(vec![CreatableTopicConfig {
+                            name: config.into(),
+                            value: Some(level.into()),
+                        }]),
+                    })
+                    .collect(),
+                ),
+                false,
+            )
+            .await?;
+
+        assert_eq!(3, r.len());
+
+        assert_eq!("pqr", r[0].name.as_str());
+        assert_eq!(ErrorCode::None, ErrorCode::try_from(r[0].error_code)?);
+
+        assert_eq!("stu", r[1].name.as_str());
+        assert_eq!(Some(NULL_TOPIC_ID), r[1].topic_id);
+        assert_eq!(
+            ErrorCode::InvalidConfig,
+            ErrorCode::try_from(r[1].error_code)?
+        );
+
+        assert_eq!("vwx", r[2].name.as_str());
+        assert_eq!(
+            ErrorCode::InvalidConfig,
+            ErrorCode::try_from(r[2].error_code)?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_dead_letter_topic() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        let mut create_topic = CreateTopic::with_storage(storage);
+
+        let r = create_topic
+            .response(
+                Some(
+                    [
+                        ("pqr", "pqr-dlq"),
+                        ("stu", ""),
+                        ("vwx", "vwx"),
+                        ("yza", "not a topic"),
+                    ]
+                    .into_iter()
+                    .map(|(name, dead_letter)| CreatableTopic {
+                        name: name.into(),
+                        num_partitions: 1,
+                        replication_factor: 1,
+                        assignments: Some([].into()),
+                        configs: Some(vec![CreatableTopicConfig {
+                            name: DEAD_LETTER_TOPIC.into(),
+                            value: Some(dead_letter.into()),
+                        }]),
+                    })
+                    .collect(),
+                ),
+                false,
+            )
+            .await?;
+
+        assert_eq!(
+            vec![
+                ErrorCode::None,
+                ErrorCode::None,
+                ErrorCode::InvalidConfig,
+                ErrorCode::InvalidConfig
+            ],
+            r.iter()
+                .map(|topic| ErrorCode::try_from(topic.error_code))
+                .collect::<Result<Vec<_>, _>>()?
+        );
+
+        Ok(())
+    }
 }
@@ -35,16 +35,23 @@ where
     ) -> Result<Body> {
         debug!(?include_cluster_authorized_operations, ?endpoint_type);
 
+        // only live brokers are described, the lowest id being controller
         let brokers = self.storage.brokers().await?;
         debug!(?brokers);
 
+        let controller_id = brokers
+            .iter()
+            .map(|broker| broker.broker_id)
+            .min()
+            .unwrap_or(-1);
+
         Ok(Body::DescribeClusterResponse {
             throttle_time_ms: 0,
             error_code: ErrorCode::None.into(),
             error_message: None,
             endpoint_type,
             cluster_id: self.cluster_id.clone(),
-            controller_id: -1,
+            controller_id,
             brokers: Some(brokers),
             cluster_authorized_operations: -2_147_483_648,
         })
@@ -13,17 +13,292 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::Result;
+use crate::{
+    Result,
+    broker::{
+        create_topic::{DEFAULT_NUM_PARTITIONS, DEFAULT_REPLICATION_FACTOR},
+        produce::DEAD_LETTER_TOPIC,
+    },
+};
 use tansu_kafka_sans_io::{
-    ConfigResource, describe_configs_request::DescribeConfigsResource,
-    describe_configs_response::DescribeConfigsResult,
+    ConfigResource, ConfigSource, ConfigType, ErrorCode,
+    describe_configs_request::DescribeConfigsResource,
+    describe_configs_response::{
+        DescribeConfigsResourceResult, DescribeConfigsResult, DescribeConfigsSynonym,
+    },
+};
+use tansu_storage::{
+    COMPRESSION_GZIP_LEVEL, COMPRESSION_LZ4_LEVEL, COMPRESSION_ZSTD_LEVEL, Storage, ZSTD_DICTIONARY,
 };
-use tansu_storage::Storage;
-use tracing::{debug, error};
+use tracing::{debug, error, level_filters::LevelFilter};
+use url::Url;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Importance {
+    High,
+    Medium,
+    Low,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TopicConfig {
+    name: &'static str,
+    broker: Option<&'static str>,
+    config_type: ConfigType,
+    default: Option<&'static str>,
+    importance: Importance,
+    documentation: &'static str,
+}
+
+impl TopicConfig {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn broker(&self) -> Option<&'static str> {
+        self.broker
+    }
+
+    pub fn config_type(&self) -> ConfigType {
+        self.config_type
+    }
+
+    pub fn default(&self) -> Option<&'static str> {
+        self.default
+    }
 
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub fn importance(&self) -> Importance {
+        self.importance
+    }
+
+    pub fn documentation(&self) -> &'static str {
+        self.documentation
+    }
+}
+
+// topic configurations, with the broker configuration that they override
+const TOPIC_CONFIGS: &[TopicConfig] = &[
+    TopicConfig {
+        name: "cleanup.policy",
+        broker: Some("log.cleanup.policy"),
+        config_type: ConfigType::List,
+        default: Some("delete"),
+        importance: Importance::Medium,
+        documentation: "A comma separated list of \"delete\" and \"compact\". \
+            The \"delete\" policy discards old segments when their retention time or size \
+            limit has been reached. The \"compact\" policy retains the latest value for each key.",
+    },
+    TopicConfig {
+        name: COMPRESSION_GZIP_LEVEL,
+        broker: Some(COMPRESSION_GZIP_LEVEL),
+        config_type: ConfigType::Int,
+        default: None,
+        importance: Importance::Medium,
+        documentation: "The level that gzip batches are recompressed with when \
+            compression.type is gzip. The default level of the codec is used when unset.",
+    },
+    TopicConfig {
+        name: COMPRESSION_LZ4_LEVEL,
+        broker: Some(COMPRESSION_LZ4_LEVEL),
+        config_type: ConfigType::Int,
+        default: None,
+        importance: Importance::Medium,
+        documentation: "The level that lz4 batches are recompressed with when \
+            compression.type is lz4. The default level of the codec is used when unset.",
+    },
+    TopicConfig {
+        name: "compression.type",
+        broker: Some("compression.type"),
+        config_type: ConfigType::String,
+        default: Some("producer"),
+        importance: Importance::High,
+        documentation: "The compression codec that batches are stored with: uncompressed, \
+            gzip, snappy, lz4 or zstd. The \"producer\" value retains the codec set by the \
+            producer.",
+    },
+    TopicConfig {
+        name: COMPRESSION_ZSTD_LEVEL,
+        broker: Some(COMPRESSION_ZSTD_LEVEL),
+        config_type: ConfigType::Int,
+        default: None,
+        importance: Importance::Medium,
+        documentation: "The level that zstd batches are recompressed with when \
+            compression.type is zstd. The default level of the codec is used when unset.",
+    },
+    TopicConfig {
+        name: ZSTD_DICTIONARY,
+        broker: None,
+        config_type: ConfigType::List,
+        default: None,
+        importance: Importance::Low,
+        documentation: "A comma separated list of base64 encoded zstd dictionaries. The first \
+            compresses stored zstd batches, with the remainder decompressing batches stored \
+            with an earlier dictionary.",
+    },
+    TopicConfig {
+        name: "delete.retention.ms",
+        broker: Some("log.cleaner.delete.retention.ms"),
+        config_type: ConfigType::Long,
+        default: Some("86400000"),
+        importance: Importance::Medium,
+        documentation: "The amount of time to retain delete tombstone markers for log \
+            compacted topics.",
+    },
+    TopicConfig {
+        name: DEAD_LETTER_TOPIC,
+        broker: None,
+        config_type: ConfigType::String,
+        default: None,
+        importance: Importance::Low,
+        documentation: "The topic keeping a copy of each batch rejected by validation, which \
+            is still refused with an error back to the producer.",
+    },
+    TopicConfig {
+        name: "max.compaction.lag.ms",
+        broker: Some("log.cleaner.max.compaction.lag.ms"),
+        config_type: ConfigType::Long,
+        default: Some("9223372036854775807"),
+        importance: Importance::Low,
+        documentation: "The maximum time a message will remain ineligible for compaction in \
+            the log, forcing compaction whatever the min.cleanable.dirty.ratio.",
+    },
+    TopicConfig {
+        name: "max.message.bytes",
+        broker: Some("message.max.bytes"),
+        config_type: ConfigType::Int,
+        default: Some("1048588"),
+        importance: Importance::Medium,
+        documentation: "The largest record batch size allowed by Kafka, after compression if \
+            compression is enabled.",
+    },
+    TopicConfig {
+        name: "message.timestamp.difference.max.ms",
+        broker: Some("log.message.timestamp.difference.max.ms"),
+        config_type: ConfigType::Long,
+        default: Some("9223372036854775807"),
+        importance: Importance::Medium,
+        documentation: "The maximum difference allowed between the timestamp when a broker \
+            receives a message and the timestamp specified in the message, for messages with \
+            a create time.",
+    },
+    TopicConfig {
+        name: "message.timestamp.type",
+        broker: Some("log.message.timestamp.type"),
+        config_type: ConfigType::String,
+        default: Some("CreateTime"),
+        importance: Importance::Medium,
+        documentation: "Define whether the timestamp in the message is message create time or \
+            log append time. The value should be either \"CreateTime\" or \"LogAppendTime\".",
+    },
+    TopicConfig {
+        name: "min.cleanable.dirty.ratio",
+        broker: Some("log.cleaner.min.cleanable.ratio"),
+        config_type: ConfigType::Double,
+        default: Some("0.5"),
+        importance: Importance::Medium,
+        documentation: "The minimum ratio of dirty log to total log for a log to be eligible \
+            for cleaning, unless a message has exceeded max.compaction.lag.ms.",
+    },
+    TopicConfig {
+        name: "min.compaction.lag.ms",
+        broker: Some("log.cleaner.min.compaction.lag.ms"),
+        config_type: ConfigType::Long,
+        default: Some("0"),
+        importance: Importance::Medium,
+        documentation: "The minimum time a message will remain uncompacted in the log.",
+    },
+    TopicConfig {
+        name: "min.insync.replicas",
+        broker: Some("min.insync.replicas"),
+        config_type: ConfigType::Int,
+        default: Some("1"),
+        importance: Importance::Medium,
+        documentation: "The minimum number of replicas that must acknowledge a write for the \
+            write to be considered successful when a producer sets acks to \"all\".",
+    },
+    TopicConfig {
+        name: "retention.bytes",
+        broker: Some("log.retention.bytes"),
+        config_type: ConfigType::Long,
+        default: Some("-1"),
+        importance: Importance::Medium,
+        documentation: "The maximum size a partition can grow to before old log segments are \
+            discarded to free up space, with -1 having no size limit.",
+    },
+    TopicConfig {
+        name: "retention.ms",
+        broker: Some("log.retention.ms"),
+        config_type: ConfigType::Long,
+        default: Some("604800000"),
+        importance: Importance::Medium,
+        documentation: "The maximum time a log is retained before old log segments are \
+            discarded to free up space, with -1 having no time limit.",
+    },
+    TopicConfig {
+        name: "segment.bytes",
+        broker: Some("log.segment.bytes"),
+        config_type: ConfigType::Int,
+        default: Some("1073741824"),
+        importance: Importance::Medium,
+        documentation: "The segment file size for the log.",
+    },
+    TopicConfig {
+        name: "segment.ms",
+        broker: Some("log.roll.ms"),
+        config_type: ConfigType::Long,
+        default: Some("604800000"),
+        importance: Importance::Medium,
+        documentation: "The period of time after which the log is rolled even if the segment \
+            file is not full.",
+    },
+    TopicConfig {
+        name: "unclean.leader.election.enable",
+        broker: Some("unclean.leader.election.enable"),
+        config_type: ConfigType::Boolean,
+        default: Some("false"),
+        importance: Importance::Medium,
+        documentation: "Indicates whether to enable replicas not in the ISR set to be elected \
+            as leader as a last resort, even though doing so may result in data loss.",
+    },
+];
+
+pub fn topic_config(name: &str) -> Option<&'static TopicConfig> {
+    TOPIC_CONFIGS.iter().find(|config| config.name == name)
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct BrokerConfig {
+    name: &'static str,
+    config_type: ConfigType,
+    value: String,
+    documentation: &'static str,
+}
+
+// broker configurations that are not overridden by a topic, with the
+// values that create topic resolves a broker default to
+fn broker_configs() -> [BrokerConfig; 2] {
+    [
+        BrokerConfig {
+            name: "num.partitions",
+            config_type: ConfigType::Int,
+            value: DEFAULT_NUM_PARTITIONS.to_string(),
+            documentation: "The default number of log partitions per topic.",
+        },
+        BrokerConfig {
+            name: "default.replication.factor",
+            config_type: ConfigType::Short,
+            value: DEFAULT_REPLICATION_FACTOR.to_string(),
+            documentation: "The default replication factor for automatically created topics.",
+        },
+    ]
+}
+
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct DescribeConfigsRequest<S> {
     storage: S,
+    node_id: i32,
+    listener: Option<Url>,
+    advertised_listener: Option<Url>,
 }
 
 impl<S> DescribeConfigsRequest<S>
@@ -31,7 +306,165 @@ where
     S: Storage,
 {
     pub fn with_storage(storage: S) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            node_id: 0,
+            listener: None,
+            advertised_listener: None,
+        }
+    }
+
+    pub fn node_id(self, node_id: i32) -> Self {
+        Self { node_id, ..self }
+    }
+
+    pub fn listener(self, listener: Url) -> Self {
+        Self {
+            listener: Some(listener),
+            ..self
+        }
+    }
+
+    pub fn advertised_listener(self, advertised_listener: Url) -> Self {
+        Self {
+            advertised_listener: Some(advertised_listener),
+            ..self
+        }
+    }
+
+    // the static configuration of this broker
+    fn static_configs(&self) -> Vec<(&'static str, String, &'static str)> {
+        let mut configs = vec![
+            (
+                "node.id",
+                self.node_id.to_string(),
+                "The node id for this broker.",
+            ),
+            (
+                "broker.id",
+                self.node_id.to_string(),
+                "The broker id for this server.",
+            ),
+        ];
+
+        if let Some(ref listener) = self.listener {
+            configs.push((
+                "listeners",
+                listener.to_string(),
+                "The URI that the broker listens on.",
+            ));
+        }
+
+        if let Some(ref advertised_listener) = self.advertised_listener {
+            configs.push((
+                "advertised.listeners",
+                advertised_listener.to_string(),
+                "The URI published to clients, when different to listeners.",
+            ));
+        }
+
+        configs
+    }
+
+    fn broker_result(
+        &self,
+        resource: &DescribeConfigsResource,
+        include_synonyms: bool,
+        include_documentation: bool,
+    ) -> DescribeConfigsResult {
+        let resource_type = ConfigResource::from(resource.resource_type);
+
+        // an empty resource name describes the cluster wide defaults
+        if !resource.resource_name.is_empty() && resource.resource_name != self.node_id.to_string()
+        {
+            let error_code = ErrorCode::InvalidRequest;
+
+            return DescribeConfigsResult {
+                error_code: error_code.into(),
+                error_message: Some(format!(
+                    "unexpected broker id: {}, expecting: {}",
+                    resource.resource_name, self.node_id
+                )),
+                resource_type: resource.resource_type,
+                resource_name: resource.resource_name.clone(),
+                configs: Some([].into()),
+            };
+        }
+
+        let mut configs = vec![];
+
+        if resource_type == ConfigResource::BrokerLogger {
+            configs.push(broker_config(
+                "root",
+                LevelFilter::current().to_string().to_uppercase(),
+                ConfigType::String,
+                ConfigSource::DynamicBrokerLoggerConfig,
+                "The level of the root logger.",
+                include_synonyms,
+                include_documentation,
+            ));
+        } else {
+            if !resource.resource_name.is_empty() {
+                for (name, value, documentation) in self.static_configs() {
+                    configs.push(broker_config(
+                        name,
+                        value,
+                        ConfigType::String,
+                        ConfigSource::StaticBrokerConfig,
+                        documentation,
+                        include_synonyms,
+                        include_documentation,
+                    ));
+                }
+            }
+
+            for config in broker_configs() {
+                configs.push(broker_config(
+                    config.name,
+                    config.value,
+                    config.config_type,
+                    ConfigSource::DefaultConfig,
+                    config.documentation,
+                    include_synonyms,
+                    include_documentation,
+                ));
+            }
+
+            for config in TOPIC_CONFIGS {
+                if let TopicConfig {
+                    broker: Some(name),
+                    config_type,
+                    default: Some(default),
+                    documentation,
+                    ..
+                } = config
+                {
+                    configs.push(broker_config(
+                        name,
+                        (*default).into(),
+                        *config_type,
+                        ConfigSource::DefaultConfig,
+                        documentation,
+                        include_synonyms,
+                        include_documentation,
+                    ));
+                }
+            }
+        }
+
+        if let Some(ref keys) = resource.configuration_keys {
+            configs.retain(|config| keys.contains(&config.name));
+        }
+
+        let error_code = ErrorCode::None;
+
+        DescribeConfigsResult {
+            error_code: error_code.into(),
+            error_message: Some(error_code.to_string()),
+            resource_type: resource.resource_type,
+            resource_name: resource.resource_name.clone(),
+            configs: Some(configs),
+        }
     }
 
     pub async fn response(
@@ -42,23 +475,249 @@ where
     ) -> Result<Vec<DescribeConfigsResult>> {
         debug!(?resources, ?include_synonyms, ?include_documentation);
 
+        let include_synonyms = include_synonyms.unwrap_or_default();
+        let include_documentation = include_documentation.unwrap_or_default();
+
         let mut results = vec![];
 
         if let Some(resources) = resources {
             for resource in resources {
-                results.push(
-                    self.storage
-                        .describe_config(
-                            resource.resource_name.as_str(),
-                            ConfigResource::from(resource.resource_type),
-                            resource.configuration_keys.as_deref(),
-                        )
-                        .await
-                        .inspect_err(|err| error!(?err))?,
-                );
+                let resource_type = ConfigResource::from(resource.resource_type);
+
+                if matches!(
+                    resource_type,
+                    ConfigResource::Broker | ConfigResource::BrokerLogger
+                ) {
+                    results.push(self.broker_result(
+                        resource,
+                        include_synonyms,
+                        include_documentation,
+                    ));
+                    continue;
+                }
+
+                let mut result = self
+                    .storage
+                    .describe_config(
+                        resource.resource_name.as_str(),
+                        resource_type,
+                        resource.configuration_keys.as_deref(),
+                    )
+                    .await
+                    .inspect_err(|err| error!(?err))?;
+
+                if resource_type == ConfigResource::Topic {
+                    result.configs = result.configs.map(|configs| {
+                        configs
+                            .into_iter()
+                            .map(|config| {
+                                topic_override(config, include_synonyms, include_documentation)
+                            })
+                            .collect()
+                    });
+                }
+
+                results.push(result);
             }
         }
 
         Ok(results)
     }
 }
+
+fn broker_config(
+    name: &str,
+    value: String,
+    config_type: ConfigType,
+    source: ConfigSource,
+    documentation: &str,
+    include_synonyms: bool,
+    include_documentation: bool,
+) -> DescribeConfigsResourceResult {
+    let synonyms = if include_synonyms {
+        vec![DescribeConfigsSynonym {
+            name: name.into(),
+            value: Some(value.clone()),
+            source: source.into(),
+        }]
+    } else {
+        vec![]
+    };
+
+    DescribeConfigsResourceResult {
+        name: name.into(),
+        value: Some(value),
+        read_only: true,
+        is_default: None,
+        config_source: Some(source.into()),
+        is_sensitive: false,
+        synonyms: Some(synonyms),
+        config_type: Some(config_type.into()),
+        documentation: Some(if include_documentation {
+            documentation.into()
+        } else {
+            "".into()
+        }),
+    }
+}
+
+// a topic configuration from storage overrides any broker or default value
+fn topic_override(
+    config: DescribeConfigsResourceResult,
+    include_synonyms: bool,
+    include_documentation: bool,
+) -> DescribeConfigsResourceResult {
+    let registered = topic_config(config.name.as_str());
+
+    let synonyms = if include_synonyms {
+        let mut synonyms = vec![DescribeConfigsSynonym {
+            name: config.name.clone(),
+            value: config.value.clone(),
+            source: ConfigSource::DynamicTopicConfig.into(),
+        }];
+
+        if let Some(TopicConfig {
+            broker: Some(broker),
+            default: Some(default),
+            ..
+        }) = registered
+        {
+            synonyms.push(DescribeConfigsSynonym {
+                name: (*broker).into(),
+                value: Some((*default).into()),
+                source: ConfigSource::DefaultConfig.into(),
+            });
+        }
+
+        Some(synonyms)
+    } else {
+        config.synonyms
+    };
+
+    let documentation = if include_documentation {
+        registered.map(|registered| registered.documentation.into())
+    } else {
+        config.documentation
+    };
+
+    let config_type = registered
+        .map(|registered| registered.config_type.into())
+        .or(config.config_type);
+
+    DescribeConfigsResourceResult {
+        synonyms,
+        config_type,
+        documentation,
+        ..config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use tansu_kafka_sans_io::create_topics_request::{CreatableTopic, CreatableTopicConfig};
+    use tansu_storage::dynostore::DynoStore;
+
+    use super::*;
+
+    async fn describe(
+        include_synonyms: Option<bool>,
+        include_documentation: Option<bool>,
+    ) -> Result<DescribeConfigsResourceResult> {
+        let mut storage = DynoStore::new("abc", 12321, InMemory::new());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: "pqr".into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some(vec![CreatableTopicConfig {
+                        name: "retention.ms".into(),
+                        value: Some("3600000".into()),
+                    }]),
+                },
+                false,
+            )
+            .await?;
+
+        let mut results = DescribeConfigsRequest::with_storage(storage)
+            .response(
+                Some(&[DescribeConfigsResource {
+                    resource_type: ConfigResource::Topic.into(),
+                    resource_name: "pqr".into(),
+                    configuration_keys: None,
+                }]),
+                include_synonyms,
+                include_documentation,
+            )
+            .await?;
+
+        assert_eq!(1, results.len());
+
+        let mut configs = results.remove(0).configs.unwrap_or_default();
+        assert_eq!(1, configs.len());
+
+        Ok(configs.remove(0))
+    }
+
+    #[tokio::test]
+    async fn synonyms_and_documentation() -> Result<()> {
+        let config = describe(Some(true), Some(true)).await?;
+
+        assert_eq!("retention.ms", config.name);
+        assert_eq!(Some("3600000"), config.value.as_deref());
+
+        assert_eq!(
+            Some(vec![
+                DescribeConfigsSynonym {
+                    name: "retention.ms".into(),
+                    value: Some("3600000".into()),
+                    source: ConfigSource::DynamicTopicConfig.into(),
+                },
+                DescribeConfigsSynonym {
+                    name: "log.retention.ms".into(),
+                    value: Some("604800000".into()),
+                    source: ConfigSource::DefaultConfig.into(),
+                },
+            ]),
+            config.synonyms
+        );
+
+        assert!(
+            config
+                .documentation
+                .is_some_and(|documentation| documentation.starts_with("The maximum time"))
+        );
+
+        assert_eq!(Some(ConfigType::Long.into()), config.config_type);
+
+        Ok(())
+    }
+
+    #[test]
+    fn retention_ms_registry() {
+        let config = topic_config("retention.ms").expect("retention.ms");
+
+        assert_eq!("retention.ms", config.name());
+        assert_eq!(Some("log.retention.ms"), config.broker());
+        assert_eq!(ConfigType::Long, config.config_type());
+        assert_eq!(Some("604800000"), config.default());
+        assert_eq!(Importance::Medium, config.importance());
+        assert!(config.documentation().contains("retained"));
+
+        assert_eq!(None, topic_config("retention.minutes"));
+    }
+
+    #[tokio::test]
+    async fn without_synonyms_or_documentation() -> Result<()> {
+        let config = describe(None, Some(false)).await?;
+
+        assert_eq!("retention.ms", config.name);
+        assert_eq!(Some([].into()), config.synonyms);
+        assert_eq!(Some(""), config.documentation.as_deref());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use bytes::Bytes;
+use tansu_kafka_sans_io::{Body, ErrorCode};
+use tracing::{debug, info};
+
+use crate::{Result, broker::produce};
+
+/// Draining moves clients off a broker ahead of a rolling restart. New
+/// produce and group coordination requests are refused with retriable
+/// errors, while connections and in flight requests are left alone
+#[derive(Clone, Debug, Default)]
+pub struct Drain(Arc<AtomicBool>);
+
+impl Drain {
+    pub fn is_draining(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn draining(&self, draining: bool) {
+        info!(draining);
+        self.0.store(draining, Ordering::Relaxed)
+    }
+
+    // each signal received toggles draining on or off
+    #[cfg(unix)]
+    pub async fn toggle_on(self, kind: tokio::signal::unix::SignalKind) -> Result<()> {
+        let mut signals = tokio::signal::unix::signal(kind)?;
+
+        while signals.recv().await.is_some() {
+            self.draining(!self.is_draining());
+        }
+
+        Ok(())
+    }
+
+    // the response to a request that is refused while draining
+    pub(crate) fn refused(&self, body: &Body) -> Option<Body> {
+        if !self.is_draining() {
+            return None;
+        }
+
+        let refused = match body {
+            Body::ProduceRequest { topic_data, .. } => Some(produce::error_response(
+                topic_data.as_deref(),
+                ErrorCode::NotLeaderOrFollower,
+            )),
+
+            Body::JoinGroupRequest {
+                protocol_type,
+                member_id,
+                ..
+            } => Some(Body::JoinGroupResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::CoordinatorLoadInProgress.into(),
+                generation_id: -1,
+                protocol_type: Some(protocol_type.to_owned()),
+                protocol_name: Some("".into()),
+                leader: "".into(),
+                skip_assignment: Some(false),
+                member_id: member_id.to_owned(),
+                members: Some([].into()),
+            }),
+
+            Body::SyncGroupRequest {
+                protocol_type,
+                protocol_name,
+                ..
+            } => Some(Body::SyncGroupResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::CoordinatorLoadInProgress.into(),
+                protocol_type: protocol_type.to_owned(),
+                protocol_name: protocol_name.to_owned(),
+                assignment: Bytes::from_static(b""),
+            }),
+
+            // members rejoin, being refused until draining has finished
+            Body::HeartbeatRequest { .. } => Some(Body::HeartbeatResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::RebalanceInProgress.into(),
+            }),
+
+            _otherwise => None,
+        };
+
+        debug!(?refused);
+        refused
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_draining_by_default() {
+        let drain = Drain::default();
+        assert!(!drain.is_draining());
+
+        assert_eq!(
+            None,
+            drain.refused(&Body::HeartbeatRequest {
+                group_id: "abc".into(),
+                generation_id: 1,
+                member_id: "pqr".into(),
+                group_instance_id: None,
+            })
+        );
+    }
+
+    #[test]
+    fn shared_between_clones() {
+        let drain = Drain::default();
+        drain.clone().draining(true);
+        assert!(drain.is_draining());
+
+        assert_eq!(
+            Some(Body::HeartbeatResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::RebalanceInProgress.into(),
+            }),
+            drain.refused(&Body::HeartbeatRequest {
+                group_id: "abc".into(),
+                generation_id: 1,
+                member_id: "pqr".into(),
+                group_instance_id: None,
+            })
+        );
+
+        drain.draining(false);
+        assert!(!drain.is_draining());
+    }
+}
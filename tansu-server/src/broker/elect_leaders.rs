@@ -0,0 +1,315 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use crate::Result;
+use tansu_kafka_sans_io::{
+    Body, ErrorCode,
+    elect_leaders_request::TopicPartitions,
+    elect_leaders_response::{PartitionResult, ReplicaElectionResult},
+    metadata_response::{MetadataResponsePartition, MetadataResponseTopic},
+};
+use tansu_storage::{Storage, TopicId};
+use tracing::{debug, info, warn};
+
+/// The preferred leader of a partition is the first of its replicas.
+/// Leadership is only ever held by the preferred leader until partitions
+/// are replicated between brokers
+pub(crate) fn preferred_leader(partition: &MetadataResponsePartition) -> Option<i32> {
+    partition
+        .replica_nodes
+        .as_deref()
+        .and_then(|replicas| replicas.first())
+        .copied()
+}
+
+// the outcome of moving leadership of a partition to its preferred leader
+fn election(partition: &MetadataResponsePartition) -> ErrorCode {
+    match preferred_leader(partition) {
+        // moving leadership waits on replication between brokers
+        Some(preferred) if preferred != partition.leader_id => {
+            ErrorCode::PreferredLeaderNotAvailable
+        }
+
+        // without replicas, the leader is the only copy of the partition
+        Some(_) | None => ErrorCode::ElectionNotNeeded,
+    }
+}
+
+// the partitions that are not led by their preferred leader
+fn imbalanced(topics: &[MetadataResponseTopic]) -> Vec<(String, i32)> {
+    topics
+        .iter()
+        .flat_map(|topic| {
+            topic
+                .partitions
+                .iter()
+                .flatten()
+                .filter(|partition| election(partition) != ErrorCode::ElectionNotNeeded)
+                .map(|partition| {
+                    (
+                        topic.name.clone().unwrap_or_default(),
+                        partition.partition_index,
+                    )
+                })
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ElectLeadersRequest<S> {
+    storage: S,
+}
+
+impl<S> ElectLeadersRequest<S>
+where
+    S: Storage,
+{
+    pub fn with_storage(storage: S) -> Self {
+        Self { storage }
+    }
+
+    pub async fn response(
+        &mut self,
+        election_type: Option<i8>,
+        topic_partitions: Option<&[TopicPartitions]>,
+        timeout_ms: i32,
+    ) -> Result<Body> {
+        debug!(?election_type, ?topic_partitions, timeout_ms);
+
+        let topics = topic_partitions.map(|topic_partitions| {
+            topic_partitions
+                .iter()
+                .map(|topic| TopicId::Name(topic.topic.as_str().into()))
+                .collect::<Vec<_>>()
+        });
+
+        let metadata = self.storage.metadata(topics.as_deref()).await?;
+
+        let results = if let Some(topic_partitions) = topic_partitions {
+            topic_partitions
+                .iter()
+                .map(|requested| {
+                    let partitions = metadata
+                        .topics()
+                        .iter()
+                        .find(|topic| topic.name.as_deref() == Some(requested.topic.as_str()))
+                        .and_then(|topic| topic.partitions.as_deref())
+                        .unwrap_or_default();
+
+                    ReplicaElectionResult {
+                        topic: requested.topic.clone(),
+                        partition_result: Some(
+                            requested
+                                .partitions
+                                .iter()
+                                .flatten()
+                                .map(|partition_id| {
+                                    partition_result(
+                                        *partition_id,
+                                        partitions
+                                            .iter()
+                                            .find(|partition| {
+                                                partition.partition_index == *partition_id
+                                            })
+                                            .map_or(ErrorCode::UnknownTopicOrPartition, election),
+                                    )
+                                })
+                                .collect(),
+                        ),
+                    }
+                })
+                .collect()
+        } else {
+            metadata
+                .topics()
+                .iter()
+                .map(|topic| ReplicaElectionResult {
+                    topic: topic.name.clone().unwrap_or_default(),
+                    partition_result: Some(
+                        topic
+                            .partitions
+                            .iter()
+                            .flatten()
+                            .map(|partition| {
+                                partition_result(partition.partition_index, election(partition))
+                            })
+                            .collect(),
+                    ),
+                })
+                .collect()
+        };
+
+        Ok(Body::ElectLeadersResponse {
+            throttle_time_ms: 0,
+            error_code: Some(ErrorCode::None.into()),
+            replica_election_results: Some(results),
+        })
+    }
+}
+
+fn partition_result(partition_id: i32, error_code: ErrorCode) -> PartitionResult {
+    PartitionResult {
+        partition_id,
+        error_code: error_code.into(),
+        error_message: if error_code == ErrorCode::ElectionNotNeeded {
+            None
+        } else {
+            Some(error_code.to_string())
+        },
+    }
+}
+
+/// Periodically checks that each partition is led by its preferred leader,
+/// as auto.leader.rebalance.enable. With a single broker the check only logs
+/// the partitions that would be moved, until replication plugs in here
+#[derive(Clone, Debug)]
+pub struct LeaderRebalance<S> {
+    storage: S,
+    interval: Duration,
+}
+
+impl<S> LeaderRebalance<S>
+where
+    S: Storage,
+{
+    pub fn new(storage: S, interval: Duration) -> Self {
+        Self { storage, interval }
+    }
+
+    pub async fn run(mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            _ = interval.tick().await;
+
+            // a failed check is retried on the next tick
+            let metadata = match self.storage.metadata(None).await {
+                Ok(metadata) => metadata,
+                Err(error) => {
+                    warn!(?error);
+                    continue;
+                }
+            };
+
+            let imbalanced = imbalanced(metadata.topics());
+
+            if imbalanced.is_empty() {
+                debug!(imbalanced = 0);
+            } else {
+                info!(imbalanced = imbalanced.len(), ?imbalanced);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use tansu_kafka_sans_io::create_topics_request::CreatableTopic;
+    use tansu_storage::dynostore::DynoStore;
+
+    #[tokio::test]
+    async fn election_not_needed() -> Result<()> {
+        let cluster = "abc";
+        let node = 12321;
+        let topic = "pqr";
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+
+        storage
+            .register_broker(tansu_storage::BrokerRegistrationRequest {
+                broker_id: node,
+                cluster_id: cluster.into(),
+                incarnation_id: uuid::Uuid::nil(),
+                rack: None,
+            })
+            .await?;
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 2,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        assert_eq!(
+            Body::ElectLeadersResponse {
+                throttle_time_ms: 0,
+                error_code: Some(ErrorCode::None.into()),
+                replica_election_results: Some(vec![
+                    ReplicaElectionResult {
+                        topic: topic.into(),
+                        partition_result: Some(vec![
+                            partition_result(1, ErrorCode::ElectionNotNeeded),
+                            partition_result(2, ErrorCode::UnknownTopicOrPartition),
+                        ]),
+                    },
+                    ReplicaElectionResult {
+                        topic: "xyz".into(),
+                        partition_result: Some(vec![partition_result(
+                            0,
+                            ErrorCode::UnknownTopicOrPartition
+                        )]),
+                    }
+                ]),
+            },
+            ElectLeadersRequest::with_storage(storage.clone())
+                .response(
+                    Some(0),
+                    Some(&[
+                        TopicPartitions {
+                            topic: topic.into(),
+                            partitions: Some(vec![1, 2]),
+                        },
+                        TopicPartitions {
+                            topic: "xyz".into(),
+                            partitions: Some(vec![0]),
+                        },
+                    ]),
+                    60_000,
+                )
+                .await?
+        );
+
+        // every partition when none are requested
+        assert_eq!(
+            Body::ElectLeadersResponse {
+                throttle_time_ms: 0,
+                error_code: Some(ErrorCode::None.into()),
+                replica_election_results: Some(vec![ReplicaElectionResult {
+                    topic: topic.into(),
+                    partition_result: Some(vec![
+                        partition_result(0, ErrorCode::ElectionNotNeeded),
+                        partition_result(1, ErrorCode::ElectionNotNeeded),
+                    ]),
+                }]),
+            },
+            ElectLeadersRequest::with_storage(storage)
+                .response(Some(0), None, 60_000)
+                .await?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use tansu_kafka_sans_io::{
+    Body, ErrorCode, create_topics_response::CreatableTopicResult,
+    delete_topics_response::DeletableTopicResult,
+};
+use tokio::sync::broadcast;
+use tracing::debug;
+use uuid::Uuid;
+
+const DEFAULT_CAPACITY: usize = 1_024;
+
+/// A significant change in the state of the cluster
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Event {
+    TopicCreated {
+        name: String,
+        topic_id: Uuid,
+    },
+
+    TopicDeleted {
+        name: Option<String>,
+        topic_id: Option<Uuid>,
+    },
+
+    GroupRebalanced {
+        group_id: String,
+        generation_id: i32,
+        leader: String,
+    },
+
+    TransactionEnded {
+        transaction_id: String,
+        producer_id: i64,
+        committed: bool,
+    },
+}
+
+/// Publishes events to any number of subscribers, such as an admin
+/// interface. A subscriber that falls more than the capacity behind
+/// loses the oldest events, and an event without subscribers is dropped
+#[derive(Clone, Debug)]
+pub struct Events(broadcast::Sender<Event>);
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Events {
+    pub fn new(capacity: usize) -> Self {
+        Self(broadcast::channel(capacity).0)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.0.subscribe()
+    }
+
+    pub fn publish(&self, event: Event) {
+        debug!(?event, subscribers = self.0.receiver_count());
+        _ = self.0.send(event);
+    }
+
+    pub(crate) fn topics_created(&self, topics: &[CreatableTopicResult]) {
+        for topic in topics
+            .iter()
+            .filter(|topic| topic.error_code == i16::from(ErrorCode::None))
+        {
+            self.publish(Event::TopicCreated {
+                name: topic.name.clone(),
+                topic_id: topic.topic_id.map(Uuid::from_bytes).unwrap_or_default(),
+            });
+        }
+    }
+
+    pub(crate) fn topics_deleted(&self, topics: &[DeletableTopicResult]) {
+        for topic in topics
+            .iter()
+            .filter(|topic| topic.error_code == i16::from(ErrorCode::None))
+        {
+            self.publish(Event::TopicDeleted {
+                name: topic.name.clone(),
+                topic_id: topic.topic_id.map(Uuid::from_bytes),
+            });
+        }
+    }
+
+    // each member of a new generation is answered, with the rebalance
+    // published once on the response to the leader
+    pub(crate) fn joined(&self, group_id: &str, body: &Body) {
+        if let Body::JoinGroupResponse {
+            error_code,
+            generation_id,
+            leader,
+            member_id,
+            ..
+        } = body
+        {
+            if *error_code == i16::from(ErrorCode::None) && leader == member_id {
+                self.publish(Event::GroupRebalanced {
+                    group_id: group_id.to_owned(),
+                    generation_id: *generation_id,
+                    leader: leader.to_owned(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
+use object_store::{
+    GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStore, PutMultipartOpts,
+    PutOptions, PutPayload, PutResult, memory::InMemory, path::Path,
+};
+use tokio::time::sleep;
+
+/// An in memory object store used by tests, that can be made unavailable,
+/// or slow to get or put, while counting the puts and reads made
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Faulty {
+    inner: Arc<InMemory>,
+    unavailable: bool,
+    get_delay: Option<Duration>,
+    put_delay: Option<Duration>,
+    puts: Arc<AtomicUsize>,
+    reads: Arc<AtomicUsize>,
+}
+
+impl Faulty {
+    // every operation fails
+    pub(crate) fn unavailable(self) -> Self {
+        Self {
+            unavailable: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn get_delay(self, get_delay: Duration) -> Self {
+        Self {
+            get_delay: Some(get_delay),
+            ..self
+        }
+    }
+
+    pub(crate) fn put_delay(self, put_delay: Duration) -> Self {
+        Self {
+            put_delay: Some(put_delay),
+            ..self
+        }
+    }
+
+    pub(crate) fn puts(&self) -> usize {
+        self.puts.load(Ordering::Relaxed)
+    }
+
+    // the gets and lists made
+    pub(crate) fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    fn available(&self) -> Result<(), object_store::Error> {
+        if self.unavailable {
+            Err(object_store::Error::Generic {
+                store: "unavailable",
+                source: "storage unavailable".into(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Faulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Faulty")
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectStore for Faulty {
+    async fn put_opts(
+        &self,
+        location: &Path,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> Result<PutResult, object_store::Error> {
+        self.available()?;
+        _ = self.puts.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(put_delay) = self.put_delay {
+            sleep(put_delay).await;
+        }
+
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> Result<Box<dyn MultipartUpload>, object_store::Error> {
+        self.available()?;
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> Result<GetResult, object_store::Error> {
+        self.available()?;
+        _ = self.reads.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(get_delay) = self.get_delay {
+            sleep(get_delay).await;
+        }
+
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn delete(&self, location: &Path) -> Result<(), object_store::Error> {
+        self.available()?;
+        self.inner.delete(location).await
+    }
+
+    fn list(
+        &self,
+        prefix: Option<&Path>,
+    ) -> BoxStream<'_, Result<ObjectMeta, object_store::Error>> {
+        if let Err(error) = self.available() {
+            stream::once(async { Err(error) }).boxed()
+        } else {
+            _ = self.reads.fetch_add(1, Ordering::Relaxed);
+            self.inner.list(prefix)
+        }
+    }
+
+    async fn list_with_delimiter(
+        &self,
+        prefix: Option<&Path>,
+    ) -> Result<ListResult, object_store::Error> {
+        self.available()?;
+        _ = self.reads.fetch_add(1, Ordering::Relaxed);
+        self.inner.list_with_delimiter(prefix).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<(), object_store::Error> {
+        self.available()?;
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> Result<(), object_store::Error> {
+        self.available()?;
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
@@ -13,7 +13,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::time::{Duration, Instant};
+pub mod notifier;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound,
+    time::{Duration, Instant},
+};
 
 use tansu_kafka_sans_io::{
     Body, ErrorCode, IsolationLevel,
@@ -21,18 +27,86 @@ use tansu_kafka_sans_io::{
     fetch_response::{
         EpochEndOffset, FetchableTopicResponse, LeaderIdAndEpoch, PartitionData, SnapshotId,
     },
-    metadata_response::MetadataResponseTopic,
+    metadata_response::{MetadataResponseBroker, MetadataResponsePartition, MetadataResponseTopic},
     record::{deflated::Batch, deflated::Frame},
 };
-use tansu_storage::{Storage, Topition};
+use tansu_storage::{LEADER_EPOCH, NULL_TOPIC_ID, Storage, Topition};
 use tokio::time::sleep;
 use tracing::{debug, error};
 
 use crate::Result;
+use notifier::{Notifier, Watch};
+
+// a follower in the same rack as the consumer, or -1 to fetch from the leader
+fn preferred_read_replica(
+    rack_id: Option<&str>,
+    brokers: &[MetadataResponseBroker],
+    partition: Option<&MetadataResponsePartition>,
+) -> i32 {
+    rack_id
+        .filter(|rack_id| !rack_id.is_empty())
+        .zip(partition)
+        .and_then(|(rack_id, partition)| {
+            partition
+                .replica_nodes
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .filter(|replica| **replica != partition.leader_id)
+                .find(|replica| {
+                    brokers.iter().any(|broker| {
+                        broker.node_id == **replica && broker.rack.as_deref() == Some(rack_id)
+                    })
+                })
+                .copied()
+        })
+        .unwrap_or(-1)
+}
+
+// the leader epoch known to a client is fenced when behind the leader epoch
+// of the partition, and unknown when ahead, either way the client refreshes
+// its metadata, with a negative epoch skipping the check
+fn current_leader_epoch(current_leader_epoch: Option<i32>, leader_epoch: i32) -> ErrorCode {
+    match current_leader_epoch.filter(|current_leader_epoch| *current_leader_epoch >= 0) {
+        Some(current_leader_epoch) if current_leader_epoch < leader_epoch => {
+            ErrorCode::FencedLeaderEpoch
+        }
+
+        Some(current_leader_epoch) if current_leader_epoch > leader_epoch => {
+            ErrorCode::UnknownLeaderEpoch
+        }
+
+        _otherwise => ErrorCode::None,
+    }
+}
 
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Default)]
 pub struct FetchRequest<S> {
     storage: S,
+    rack_id: Option<String>,
+    notifier: Option<Notifier>,
+    watching: BTreeMap<Topition, Watch>,
+    default_isolation_level: IsolationLevel,
+    max_partitions: Option<usize>,
+    rotation: Option<Rotation>,
+}
+
+/// The identity of the last partition served by a capped fetch
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Rotation {
+    topic: Option<String>,
+    topic_id: Option<[u8; 16]>,
+    partition: i32,
+}
+
+impl Rotation {
+    fn new(topic: &FetchTopic, partition: &FetchPartition) -> Self {
+        Self {
+            topic: topic.topic.clone(),
+            topic_id: topic.topic_id,
+            partition: partition.partition,
+        }
+    }
 }
 
 impl<S> FetchRequest<S>
@@ -40,9 +114,113 @@ where
     S: Storage,
 {
     pub fn with_storage(storage: S) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            rack_id: None,
+            notifier: None,
+            watching: BTreeMap::new(),
+            default_isolation_level: IsolationLevel::default(),
+            max_partitions: None,
+            rotation: None,
+        }
+    }
+
+    // the isolation level used when absent from the request
+    pub fn default_isolation_level(self, default_isolation_level: IsolationLevel) -> Self {
+        Self {
+            default_isolation_level,
+            ..self
+        }
+    }
+
+    pub fn rack_id(self, rack_id: Option<String>) -> Self {
+        Self { rack_id, ..self }
+    }
+
+    pub fn notifier(self, notifier: Option<Notifier>) -> Self {
+        Self { notifier, ..self }
+    }
+
+    // the most partitions fetched in a response, with the remainder
+    // deferred to later fetches
+    pub fn max_partitions(self, max_partitions: Option<usize>) -> Self {
+        Self {
+            max_partitions,
+            ..self
+        }
+    }
+
+    // where the next capped fetch starts, carried between the fetches
+    // of a connection, so that every partition is eventually served
+    pub fn rotation(self, rotation: Option<Rotation>) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub fn next_rotation(&self) -> Option<Rotation> {
+        self.rotation.clone()
+    }
+
+    // the partitions served by a capped fetch, starting after the last
+    // partition served by the previous fetch in partition order, wrapping
+    // around, regardless of the order that the partitions are requested
+    fn rotate(&mut self, topics: &[FetchTopic]) -> Option<Vec<FetchTopic>> {
+        let max_partitions = self.max_partitions?;
+
+        let requested = topics
+            .iter()
+            .flat_map(|topic| {
+                topic
+                    .partitions
+                    .iter()
+                    .flatten()
+                    .map(|partition| Rotation::new(topic, partition))
+            })
+            .collect::<BTreeSet<_>>();
+
+        if requested.len() <= max_partitions {
+            return None;
+        }
+
+        let served = self
+            .rotation
+            .as_ref()
+            .map_or(requested.range(..), |last| {
+                requested.range((Bound::Excluded(last), Bound::Unbounded))
+            })
+            .chain(requested.iter())
+            .take(max_partitions)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        debug!(requested = requested.len(), max_partitions, ?self.rotation);
+
+        // the next fetch continues after the last served, following the wrap
+        self.rotation = served.last().cloned();
+
+        let served = served.into_iter().collect::<BTreeSet<_>>();
+
+        Some(
+            topics
+                .iter()
+                .filter_map(|topic| {
+                    let partitions = topic
+                        .partitions
+                        .iter()
+                        .flatten()
+                        .filter(|partition| served.contains(&Rotation::new(topic, partition)))
+                        .cloned()
+                        .collect::<Vec<_>>();
+
+                    (!partitions.is_empty()).then(|| FetchTopic {
+                        partitions: Some(partitions),
+                        ..topic.clone()
+                    })
+                })
+                .collect(),
+        )
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn fetch_partition(
         &mut self,
         max_wait_ms: Duration,
@@ -51,6 +229,7 @@ where
         isolation: IsolationLevel,
         topic: &str,
         fetch_partition: &FetchPartition,
+        preferred_read_replica: i32,
     ) -> Result<PartitionData> {
         debug!(
             ?max_wait_ms,
@@ -63,12 +242,78 @@ where
         let partition_index = fetch_partition.partition;
         let tp = Topition::new(topic, partition_index);
 
+        // batches are stored with a leader epoch that is unchanged while
+        // there is a single broker
+        let error_code = current_leader_epoch(fetch_partition.current_leader_epoch, LEADER_EPOCH);
+
+        if error_code != ErrorCode::None {
+            debug!(?tp, ?fetch_partition.current_leader_epoch, ?error_code);
+            return Ok(self.error_partition(partition_index, error_code));
+        }
+
+        // watched before the offsets are read, so that a produce after
+        // the read wakes a waiting fetch
+        if let Some(ref notifier) = self.notifier {
+            _ = self.watching.insert(tp.clone(), notifier.watch(&tp)?);
+        }
+
+        // the log start, high watermark and last stable offset are
+        // reported on every partition, including those without records
+        let offset_stage = self
+            .storage
+            .offset_stage(&tp)
+            .await
+            .inspect_err(|error| error!(?error, ?tp))?;
+
+        let low = offset_stage.log_start();
+        let high = offset_stage.high_watermark();
+
+        if preferred_read_replica != -1 {
+            debug!(?tp, preferred_read_replica);
+
+            return Ok(PartitionData {
+                partition_index,
+                error_code: ErrorCode::None.into(),
+                high_watermark: high,
+                last_stable_offset: Some(offset_stage.last_stable()),
+                log_start_offset: Some(low),
+                diverging_epoch: None,
+                current_leader: None,
+                snapshot_id: None,
+                aborted_transactions: Some([].into()),
+                preferred_read_replica: Some(preferred_read_replica),
+                records: None,
+            });
+        }
+
+        if fetch_partition.fetch_offset < low || fetch_partition.fetch_offset > high {
+            debug!(?tp, low, high, fetch_offset = fetch_partition.fetch_offset);
+
+            return Ok(PartitionData {
+                partition_index,
+                error_code: ErrorCode::OffsetOutOfRange.into(),
+                high_watermark: high,
+                last_stable_offset: Some(offset_stage.last_stable()),
+                log_start_offset: Some(low),
+                diverging_epoch: None,
+                current_leader: None,
+                snapshot_id: None,
+                aborted_transactions: Some([].into()),
+                preferred_read_replica: Some(-1),
+                records: None,
+            });
+        }
+
+        // batches are served as they were stored: compressed batches are neither
+        // inflated nor recompressed, and uncompressed batches remain uncompressed,
+        // only a zstd dictionary is removed by storage, clients only knowing the codec
         let mut batches = Vec::new();
 
+        // batches are only fetched below the high watermark that is reported
         let mut offset = fetch_partition.fetch_offset;
 
         loop {
-            if *max_bytes == 0 {
+            if *max_bytes == 0 || offset >= high {
                 break;
             }
 
@@ -98,18 +343,12 @@ where
             }
         }
 
-        let offset_stage = self
-            .storage
-            .offset_stage(&tp)
-            .await
-            .inspect_err(|error| error!(?error, ?tp))?;
-
         Ok(PartitionData {
             partition_index,
             error_code: ErrorCode::None.into(),
-            high_watermark: offset_stage.high_watermark(),
+            high_watermark: high,
             last_stable_offset: Some(offset_stage.last_stable()),
-            log_start_offset: Some(offset_stage.log_start()),
+            log_start_offset: Some(low),
             diverging_epoch: None,
             current_leader: None,
             snapshot_id: None,
@@ -125,37 +364,56 @@ where
     }
 
     fn unknown_topic_response(&self, fetch: &FetchTopic) -> Result<FetchableTopicResponse> {
-        Ok(FetchableTopicResponse {
+        // newer clients only reference a topic by its id
+        let error_code = if fetch.topic.is_none() && fetch.topic_id.is_some() {
+            ErrorCode::UnknownTopicId
+        } else {
+            ErrorCode::UnknownTopicOrPartition
+        };
+
+        Ok(self.error_topic_response(fetch, error_code))
+    }
+
+    fn error_topic_response(
+        &self,
+        fetch: &FetchTopic,
+        error_code: ErrorCode,
+    ) -> FetchableTopicResponse {
+        FetchableTopicResponse {
             topic: fetch.topic.clone(),
-            topic_id: Some([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            topic_id: Some(fetch.topic_id.unwrap_or(NULL_TOPIC_ID)),
             partitions: fetch.partitions.as_ref().map(|partitions| {
                 partitions
                     .iter()
-                    .map(|partition| PartitionData {
-                        partition_index: partition.partition,
-                        error_code: ErrorCode::UnknownTopicOrPartition.into(),
-                        high_watermark: 0,
-                        last_stable_offset: Some(0),
-                        log_start_offset: Some(-1),
-                        diverging_epoch: Some(EpochEndOffset {
-                            epoch: -1,
-                            end_offset: -1,
-                        }),
-                        current_leader: Some(LeaderIdAndEpoch {
-                            leader_id: 0,
-                            leader_epoch: 0,
-                        }),
-                        snapshot_id: Some(SnapshotId {
-                            end_offset: -1,
-                            epoch: -1,
-                        }),
-                        aborted_transactions: Some([].into()),
-                        preferred_read_replica: Some(-1),
-                        records: None,
-                    })
+                    .map(|partition| self.error_partition(partition.partition, error_code))
                     .collect()
             }),
-        })
+        }
+    }
+
+    fn error_partition(&self, partition_index: i32, error_code: ErrorCode) -> PartitionData {
+        PartitionData {
+            partition_index,
+            error_code: error_code.into(),
+            high_watermark: 0,
+            last_stable_offset: Some(0),
+            log_start_offset: Some(-1),
+            diverging_epoch: Some(EpochEndOffset {
+                epoch: -1,
+                end_offset: -1,
+            }),
+            current_leader: Some(LeaderIdAndEpoch {
+                leader_id: 0,
+                leader_epoch: LEADER_EPOCH,
+            }),
+            snapshot_id: Some(SnapshotId {
+                end_offset: -1,
+                epoch: -1,
+            }),
+            aborted_transactions: Some([].into()),
+            preferred_read_replica: Some(-1),
+            records: None,
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -170,17 +428,43 @@ where
     ) -> Result<FetchableTopicResponse> {
         debug!(?max_wait_ms, ?min_bytes, ?isolation, ?fetch);
 
+        // the existence of the topic and its partitions is checked
+        // against its metadata
         let metadata = self.storage.metadata(Some(&[fetch.into()])).await?;
 
         if let Some(MetadataResponseTopic {
             topic_id,
             name: Some(name),
+            partitions: metadata_partitions,
             ..
-        }) = metadata.topics().first()
+        }) = metadata
+            .topics()
+            .first()
+            .filter(|topic| topic.error_code == i16::from(ErrorCode::None))
         {
             let mut partitions = Vec::new();
 
             for fetch_partition in fetch.partitions.as_ref().unwrap_or(&Vec::new()) {
+                let Some(metadata_partition) =
+                    metadata_partitions.as_deref().and_then(|partitions| {
+                        partitions.iter().find(|partition| {
+                            partition.partition_index == fetch_partition.partition
+                        })
+                    })
+                else {
+                    partitions.push(self.error_partition(
+                        fetch_partition.partition,
+                        ErrorCode::UnknownTopicOrPartition,
+                    ));
+                    continue;
+                };
+
+                let preferred_read_replica = preferred_read_replica(
+                    self.rack_id.as_deref(),
+                    metadata.brokers(),
+                    Some(metadata_partition),
+                );
+
                 let partition = self
                     .fetch_partition(
                         max_wait_ms,
@@ -189,6 +473,7 @@ where
                         isolation,
                         name,
                         fetch_partition,
+                        preferred_read_replica,
                     )
                     .await?;
 
@@ -253,12 +538,22 @@ where
                     ?min_bytes
                 );
 
-                sleep(if remaining.as_millis() >= 250 {
+                if bytes >= min_bytes {
+                    break;
+                }
+
+                let pause = if remaining.as_millis() >= 250 {
                     remaining / 2
                 } else {
                     remaining
-                })
-                .await;
+                };
+
+                if let Some(ref notifier) = self.notifier {
+                    let notified = notifier.wait(self.watching.values(), pause).await;
+                    debug!(notified);
+                } else {
+                    sleep(pause).await;
+                }
 
                 iteration += 1;
             }
@@ -277,12 +572,28 @@ where
     ) -> Result<Body> {
         debug!(?max_wait_ms, ?min_bytes, ?max_bytes, ?topics);
 
-        let responses = Some(if let Some(topics) = topics {
-            let isolation_level = isolation_level
-                .map_or(Ok(IsolationLevel::ReadUncommitted), |isolation| {
-                    IsolationLevel::try_from(isolation)
-                })?;
+        let Ok(isolation_level) =
+            isolation_level.map_or(Ok(self.default_isolation_level), IsolationLevel::try_from)
+        else {
+            return Ok(Body::FetchResponse {
+                throttle_time_ms: Some(0),
+                error_code: Some(ErrorCode::InvalidRequest.into()),
+                session_id: Some(0),
+                node_endpoints: Some([].into()),
+                responses: Some(topics.map_or(vec![], |topics| {
+                    topics
+                        .iter()
+                        .map(|fetch| self.error_topic_response(fetch, ErrorCode::InvalidRequest))
+                        .collect()
+                })),
+            })
+            .inspect(|r| debug!(?r));
+        };
 
+        let rotated = topics.and_then(|topics| self.rotate(topics));
+        let topics = rotated.as_deref().or(topics);
+
+        let responses = Some(if let Some(topics) = topics {
             let max_wait_ms = u64::try_from(max_wait_ms).map(Duration::from_millis)?;
 
             let min_bytes = u32::try_from(min_bytes)?;
@@ -361,3 +672,90 @@ impl ByteSize for FetchableTopicResponse {
         self.partitions.byte_size()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn broker(node_id: i32, rack: Option<&str>) -> MetadataResponseBroker {
+        MetadataResponseBroker {
+            node_id,
+            host: "localhost".into(),
+            port: 9092,
+            rack: rack.map(ToOwned::to_owned),
+        }
+    }
+
+    fn partition(leader_id: i32, replica_nodes: &[i32]) -> MetadataResponsePartition {
+        MetadataResponsePartition {
+            error_code: ErrorCode::None.into(),
+            partition_index: 0,
+            leader_id,
+            leader_epoch: Some(-1),
+            replica_nodes: Some(replica_nodes.into()),
+            isr_nodes: Some(replica_nodes.into()),
+            offline_replicas: Some([].into()),
+        }
+    }
+
+    #[test]
+    fn leader_epoch() {
+        let leader_epoch = 3;
+
+        assert_eq!(
+            ErrorCode::FencedLeaderEpoch,
+            current_leader_epoch(Some(2), leader_epoch)
+        );
+        assert_eq!(ErrorCode::None, current_leader_epoch(Some(3), leader_epoch));
+        assert_eq!(
+            ErrorCode::UnknownLeaderEpoch,
+            current_leader_epoch(Some(4), leader_epoch)
+        );
+
+        // without an epoch the client is not checked
+        assert_eq!(
+            ErrorCode::None,
+            current_leader_epoch(Some(-1), leader_epoch)
+        );
+        assert_eq!(ErrorCode::None, current_leader_epoch(None, leader_epoch));
+    }
+
+    #[test]
+    fn leader_only() {
+        let brokers = [broker(111, Some("a"))];
+        let partition = partition(111, &[111]);
+
+        assert_eq!(
+            -1,
+            preferred_read_replica(Some("a"), &brokers, Some(&partition))
+        );
+    }
+
+    #[test]
+    fn same_rack_follower() {
+        let brokers = [
+            broker(111, Some("a")),
+            broker(222, Some("b")),
+            broker(333, Some("c")),
+        ];
+        let partition = partition(111, &[111, 222, 333]);
+
+        assert_eq!(
+            333,
+            preferred_read_replica(Some("c"), &brokers, Some(&partition))
+        );
+        assert_eq!(
+            -1,
+            preferred_read_replica(Some("a"), &brokers, Some(&partition))
+        );
+        assert_eq!(
+            -1,
+            preferred_read_replica(Some("d"), &brokers, Some(&partition))
+        );
+        assert_eq!(-1, preferred_read_replica(None, &brokers, Some(&partition)));
+        assert_eq!(
+            -1,
+            preferred_read_replica(Some(""), &brokers, Some(&partition))
+        );
+    }
+}
@@ -0,0 +1,406 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::Result;
+use futures::future::select_all;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tansu_storage::Topition;
+use tokio::{
+    sync::Notify,
+    time::{Instant, timeout},
+};
+use tracing::debug;
+
+const DEFAULT_IDLE: Duration = Duration::from_secs(300);
+
+// the generation of a partition is incremented on every produce,
+// before any waiting fetch is notified
+#[derive(Debug, Default)]
+struct Produced {
+    notify: Notify,
+    generation: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Partition {
+    produced: Arc<Produced>,
+    used: Instant,
+}
+
+impl Partition {
+    fn new() -> Self {
+        Self {
+            produced: Arc::new(Produced::default()),
+            used: Instant::now(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Partitions {
+    watched: BTreeMap<Topition, Partition>,
+    swept: Instant,
+}
+
+impl Default for Partitions {
+    fn default() -> Self {
+        Self {
+            watched: BTreeMap::new(),
+            swept: Instant::now(),
+        }
+    }
+}
+
+// a partition watched by a fetch, taken before its offsets are read so
+// that a produce after the read is not missed
+#[derive(Clone, Debug)]
+pub struct Watch {
+    produced: Arc<Produced>,
+    generation: u64,
+}
+
+impl Watch {
+    fn produced_since(&self) -> bool {
+        self.produced.generation.load(Ordering::SeqCst) != self.generation
+    }
+}
+
+// wakes a long polling fetch when a partition is produced to, partitions
+// that have been idle for a while are swept at most once per idle period
+// and recreated on next use
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    idle: Duration,
+    partitions: Arc<Mutex<Partitions>>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self {
+            idle: DEFAULT_IDLE,
+            partitions: Arc::new(Mutex::new(Partitions::default())),
+        }
+    }
+}
+
+impl Notifier {
+    pub fn idle(self, idle: Duration) -> Self {
+        Self { idle, ..self }
+    }
+
+    pub fn len(&self) -> Result<usize> {
+        self.partitions
+            .lock()
+            .map(|partitions| partitions.watched.len())
+            .map_err(Into::into)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    fn sweep(&self, partitions: &mut Partitions) {
+        if partitions.swept.elapsed() < self.idle {
+            return;
+        }
+
+        partitions.swept = Instant::now();
+
+        let before = partitions.watched.len();
+
+        partitions.watched.retain(|_, partition| {
+            partition.used.elapsed() < self.idle || Arc::strong_count(&partition.produced) > 1
+        });
+
+        if partitions.watched.len() < before {
+            debug!(evicted = before - partitions.watched.len(), idle = ?self.idle);
+        }
+    }
+
+    pub fn watch(&self, topition: &Topition) -> Result<Watch> {
+        let mut partitions = self.partitions.lock()?;
+        self.sweep(&mut partitions);
+
+        let partition = partitions
+            .watched
+            .entry(topition.to_owned())
+            .or_insert_with(Partition::new);
+        partition.used = Instant::now();
+
+        Ok(Watch {
+            produced: partition.produced.clone(),
+            generation: partition.produced.generation.load(Ordering::SeqCst),
+        })
+    }
+
+    // a partition without a waiting fetch is not registered
+    pub fn notify(&self, topition: &Topition) -> Result<()> {
+        let produced = {
+            let mut partitions = self.partitions.lock()?;
+            self.sweep(&mut partitions);
+
+            partitions.watched.get_mut(topition).map(|partition| {
+                partition.used = Instant::now();
+                partition.produced.clone()
+            })
+        };
+
+        if let Some(produced) = produced {
+            _ = produced.generation.fetch_add(1, Ordering::SeqCst);
+            produced.notify.notify_waiters();
+        }
+
+        Ok(())
+    }
+
+    // wait until any of the watched partitions are produced to, returning
+    // false when the duration elapsed without a notification
+    pub async fn wait<'a>(
+        &self,
+        watching: impl IntoIterator<Item = &'a Watch>,
+        duration: Duration,
+    ) -> bool {
+        let watching = watching.into_iter().collect::<Vec<_>>();
+
+        if watching.is_empty() {
+            tokio::time::sleep(duration).await;
+            return false;
+        }
+
+        let mut notified = watching
+            .iter()
+            .map(|watch| Box::pin(watch.produced.notify.notified()))
+            .collect::<Vec<_>>();
+
+        for notified in notified.iter_mut() {
+            notified.as_mut().enable();
+        }
+
+        // produced to after being watched, but before being enabled
+        if watching.iter().any(|watch| watch.produced_since()) {
+            return true;
+        }
+
+        timeout(duration, select_all(notified)).await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{fetch::FetchRequest, produce::ProduceRequest};
+    use bytes::Bytes;
+    use object_store::memory::InMemory;
+    use tansu_kafka_sans_io::{
+        Body, ErrorCode,
+        create_topics_request::CreatableTopic,
+        fetch_request::{FetchPartition, FetchTopic},
+        produce_request::{PartitionProduceData, TopicProduceData},
+        record::{Record, deflated, inflated},
+    };
+    use tansu_storage::{Storage, dynostore::DynoStore};
+
+    #[tokio::test]
+    async fn idle_partitions_are_evicted() -> Result<()> {
+        let idle = Duration::from_millis(50);
+        let notifier = Notifier::default().idle(idle);
+
+        let watching = (0..10_000)
+            .map(|partition| notifier.watch(&Topition::new("abc", partition)))
+            .collect::<Result<Vec<_>>>()?;
+
+        assert!(!notifier.wait(&watching, Duration::from_millis(5)).await);
+        assert_eq!(watching.len(), notifier.len()?);
+
+        // a partition is only evicted while no fetch is watching it
+        tokio::time::sleep(idle * 2).await;
+        notifier.notify(&Topition::new("pqr", 6))?;
+        assert_eq!(watching.len(), notifier.len()?);
+
+        drop(watching);
+        tokio::time::sleep(idle * 2).await;
+
+        let topition = Topition::new("pqr", 6);
+        notifier.notify(&topition)?;
+        assert!(notifier.is_empty()?);
+
+        // an evicted partition is recreated by the next fetch
+        let watch = notifier.watch(&topition)?;
+        assert_eq!(1, notifier.len()?);
+
+        let waiting = tokio::spawn({
+            let notifier = notifier.clone();
+            let watch = watch.clone();
+            async move { notifier.wait([&watch], Duration::from_secs(30)).await }
+        });
+
+        notifier.notify(&topition)?;
+        assert!(waiting.await.expect("waiting"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn produced_before_waiting() -> Result<()> {
+        let notifier = Notifier::default();
+        let topition = Topition::new("abc", 3);
+
+        // a produce between the watch and the wait is not lost
+        let watch = notifier.watch(&topition)?;
+        notifier.notify(&topition)?;
+
+        let start = Instant::now();
+        assert!(notifier.wait([&watch], Duration::from_secs(30)).await);
+        assert!(start.elapsed() < Duration::from_secs(15));
+
+        // a fresh watch has seen that produce
+        let watch = notifier.watch(&topition)?;
+        assert!(!notifier.wait([&watch], Duration::from_millis(5)).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetch_woken_by_produce() -> Result<()> {
+        let topic = "abc";
+        let mut storage = DynoStore::new("tansu", 111, InMemory::new());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let idle = Duration::from_millis(50);
+        let notifier = Notifier::default().idle(idle);
+
+        // a partition that was previously waited on has since been evicted
+        let topition = Topition::new(topic, 0);
+        let watch = notifier.watch(&topition)?;
+        assert!(!notifier.wait([&watch], Duration::from_millis(5)).await);
+        drop(watch);
+
+        tokio::time::sleep(idle * 2).await;
+        notifier.notify(&topition)?;
+        assert!(notifier.is_empty()?);
+
+        let max_wait = Duration::from_secs(30);
+
+        let fetching = tokio::spawn({
+            let mut fetch =
+                FetchRequest::with_storage(storage.clone()).notifier(Some(notifier.clone()));
+
+            async move {
+                let start = tokio::time::Instant::now();
+
+                fetch
+                    .response(
+                        max_wait.as_millis() as i32,
+                        1,
+                        Some(50 * 1024),
+                        Some(0),
+                        Some(&[FetchTopic {
+                            topic: Some(topic.into()),
+                            topic_id: None,
+                            partitions: Some(vec![FetchPartition {
+                                partition: 0,
+                                current_leader_epoch: Some(-1),
+                                fetch_offset: 0,
+                                last_fetched_epoch: Some(-1),
+                                log_start_offset: Some(-1),
+                                partition_max_bytes: 50 * 1024,
+                                replica_directory_id: None,
+                            }]),
+                        }]),
+                    )
+                    .await
+                    .map(|body| (body, start.elapsed()))
+            }
+        });
+
+        while notifier.is_empty()? {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        let batch = inflated::Batch::builder()
+            .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+            .build()
+            .and_then(deflated::Batch::try_from)?;
+
+        let produced = ProduceRequest::with_storage(storage)
+            .notifier(Some(notifier.clone()))
+            .response(
+                None,
+                1,
+                5_000,
+                Some(vec![TopicProduceData {
+                    name: topic.into(),
+                    partition_data: Some(vec![PartitionProduceData {
+                        index: 0,
+                        records: Some(deflated::Frame {
+                            batches: vec![batch],
+                        }),
+                    }]),
+                }]),
+            )
+            .await?;
+
+        assert_eq!(
+            Some(i16::from(ErrorCode::None)),
+            produced
+                .responses
+                .as_deref()
+                .and_then(|responses| responses[0].partition_responses.as_deref())
+                .map(|partitions| partitions[0].error_code)
+        );
+
+        let (body, elapsed) = fetching.await.expect("fetching")?;
+        assert!(elapsed < max_wait / 2, "{elapsed:?}");
+
+        let Body::FetchResponse {
+            responses: Some(responses),
+            ..
+        } = body
+        else {
+            panic!("{body:?}")
+        };
+
+        let partitions = responses[0].partitions.as_deref().unwrap_or_default();
+        assert_eq!(i16::from(ErrorCode::None), partitions[0].error_code);
+        assert_eq!(
+            Some(1),
+            partitions[0]
+                .records
+                .as_ref()
+                .map(|records| records.batches.len())
+        );
+
+        Ok(())
+    }
+}
@@ -13,13 +13,29 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use tansu_kafka_sans_io::{Body, ErrorCode, find_coordinator_response::Coordinator};
+use tansu_kafka_sans_io::{
+    Body, CoordinatorType, ErrorCode, find_coordinator_response::Coordinator,
+};
+use tracing::debug;
 use url::Url;
 
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct FindCoordinatorRequest;
 
 impl FindCoordinatorRequest {
+    fn error_code(key: &str, key_type: Option<i8>) -> ErrorCode {
+        match CoordinatorType::try_from(key_type.unwrap_or(0)) {
+            Ok(CoordinatorType::Group | CoordinatorType::Transaction) if !key.is_empty() => {
+                ErrorCode::None
+            }
+
+            _ => {
+                debug!(key, ?key_type);
+                ErrorCode::InvalidRequest
+            }
+        }
+    }
+
     pub fn response(
         &self,
         key: Option<&str>,
@@ -28,31 +44,164 @@ impl FindCoordinatorRequest {
         node_id: i32,
         listener: &Url,
     ) -> Body {
-        let _ = key;
-        let _ = key_type;
-
         let host = listener.host_str().unwrap_or("localhost");
         let port = i32::from(listener.port().unwrap_or(9092));
 
+        let coordinator = |key: &str| {
+            let error_code = Self::error_code(key, key_type);
+
+            if error_code == ErrorCode::None {
+                Coordinator {
+                    key: key.to_string(),
+                    node_id,
+                    host: host.into(),
+                    port,
+                    error_code: error_code.into(),
+                    error_message: None,
+                }
+            } else {
+                Coordinator {
+                    key: key.to_string(),
+                    node_id: -1,
+                    host: "".into(),
+                    port: -1,
+                    error_code: error_code.into(),
+                    error_message: Some(error_code.to_string()),
+                }
+            }
+        };
+
+        let Coordinator {
+            node_id,
+            host,
+            port,
+            error_code,
+            error_message,
+            ..
+        } = key.map_or(
+            Coordinator {
+                key: "".into(),
+                node_id,
+                host: host.into(),
+                port,
+                error_code: ErrorCode::None.into(),
+                error_message: None,
+            },
+            coordinator,
+        );
+
         Body::FindCoordinatorResponse {
             throttle_time_ms: Some(0),
-            error_code: Some(ErrorCode::None.into()),
-            error_message: Some("NONE".into()),
+            error_code: Some(error_code),
+            error_message: error_message.or(Some("NONE".into())),
             node_id: Some(node_id),
-            host: Some(host.into()),
+            host: Some(host),
             port: Some(port),
-            coordinators: coordinator_keys.map(|keys| {
-                keys.iter()
-                    .map(|key| Coordinator {
-                        key: key.to_string(),
-                        node_id,
-                        host: host.into(),
-                        port,
-                        error_code: ErrorCode::None.into(),
-                        error_message: None,
-                    })
-                    .collect()
-            }),
+            coordinators: coordinator_keys
+                .map(|keys| keys.iter().map(|key| coordinator(key)).collect()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE_ID: i32 = 111;
+
+    fn listener() -> Url {
+        Url::parse("tcp://example.com:9092/").unwrap()
+    }
+
+    #[test]
+    fn transaction_coordinator() {
+        let Body::FindCoordinatorResponse {
+            error_code,
+            node_id,
+            host,
+            port,
+            ..
+        } = FindCoordinatorRequest.response(
+            Some("txn-abc"),
+            Some(CoordinatorType::Transaction.into()),
+            None,
+            NODE_ID,
+            &listener(),
+        )
+        else {
+            panic!("find coordinator response")
+        };
+
+        assert_eq!(Some(ErrorCode::None.into()), error_code);
+        assert_eq!(Some(NODE_ID), node_id);
+        assert_eq!(Some("example.com".into()), host);
+        assert_eq!(Some(9092), port);
+    }
+
+    #[test]
+    fn batched_coordinator_keys() {
+        let keys = ["txn-abc".to_string(), "".to_string(), "txn-def".to_string()];
+
+        let Body::FindCoordinatorResponse {
+            coordinators: Some(coordinators),
+            ..
+        } = FindCoordinatorRequest.response(
+            None,
+            Some(CoordinatorType::Transaction.into()),
+            Some(&keys[..]),
+            NODE_ID,
+            &listener(),
+        )
+        else {
+            panic!("find coordinator response")
+        };
+
+        assert_eq!(
+            vec![
+                ("txn-abc", NODE_ID, ErrorCode::None),
+                ("", -1, ErrorCode::InvalidRequest),
+                ("txn-def", NODE_ID, ErrorCode::None)
+            ],
+            coordinators
+                .iter()
+                .map(|coordinator| (
+                    coordinator.key.as_str(),
+                    coordinator.node_id,
+                    ErrorCode::try_from(coordinator.error_code).unwrap()
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(
+            coordinators
+                .iter()
+                .filter(|coordinator| coordinator.node_id == NODE_ID)
+                .all(|coordinator| coordinator.host == "example.com" && coordinator.port == 9092)
+        );
+    }
+
+    #[test]
+    fn unsupported_key_type() {
+        let keys = ["abc".to_string()];
+
+        let Body::FindCoordinatorResponse {
+            coordinators: Some(coordinators),
+            ..
+        } = FindCoordinatorRequest.response(
+            None,
+            Some(CoordinatorType::Share.into()),
+            Some(&keys[..]),
+            NODE_ID,
+            &listener(),
+        )
+        else {
+            panic!("find coordinator response")
+        };
+
+        assert_eq!(1, coordinators.len());
+        assert_eq!(
+            i16::from(ErrorCode::InvalidRequest),
+            coordinators[0].error_code
+        );
+    }
+}
@@ -34,6 +34,7 @@ where
     pub async fn response(
         &mut self,
         client_id: Option<&str>,
+        client_host: Option<&str>,
         group_id: &str,
         session_timeout_ms: i32,
         rebalance_timeout_ms: Option<i32>,
@@ -46,6 +47,7 @@ where
         self.coordinator
             .join(
                 client_id,
+                client_host,
                 group_id,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -13,13 +13,49 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::Result;
+use std::{ops::Range, sync::Arc};
+
+use crate::{Error, Result};
+use tansu_kafka_sans_io::ErrorCode;
 use tansu_storage::{ProducerIdResponse, Storage};
+use tokio::sync::Mutex;
 use tracing::debug;
 
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Default)]
+pub struct ProducerIdBlock {
+    size: i32,
+    ids: Arc<Mutex<Range<i64>>>,
+}
+
+impl ProducerIdBlock {
+    pub fn new(size: i32) -> Self {
+        Self {
+            size,
+            ids: Arc::new(Mutex::new(0..0)),
+        }
+    }
+
+    async fn next_id<S>(&self, storage: &mut S) -> Result<i64>
+    where
+        S: Storage,
+    {
+        let mut ids = self.ids.lock().await;
+
+        if ids.is_empty() {
+            *ids = storage.allocate_producer_id_block(self.size).await?;
+            debug!(size = self.size, ids = ?*ids);
+        }
+
+        ids.next().ok_or(Error::Storage(tansu_storage::Error::Api(
+            ErrorCode::UnknownServerError,
+        )))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct InitProducerIdRequest<S> {
     storage: S,
+    producer_ids: Option<ProducerIdBlock>,
 }
 
 impl<S> InitProducerIdRequest<S>
@@ -27,7 +63,17 @@ where
     S: Storage,
 {
     pub fn with_storage(storage: S) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            producer_ids: None,
+        }
+    }
+
+    pub fn producer_ids(self, producer_ids: Option<ProducerIdBlock>) -> Self {
+        Self {
+            producer_ids,
+            ..self
+        }
     }
 
     pub async fn response(
@@ -44,6 +90,22 @@ where
             ?producer_epoch
         );
 
+        if let (None, Some(-1), Some(-1), Some(producer_ids)) = (
+            transaction_id,
+            producer_id,
+            producer_epoch,
+            self.producer_ids.as_ref(),
+        ) {
+            return producer_ids
+                .next_id(&mut self.storage)
+                .await
+                .map(|id| ProducerIdResponse {
+                    error: ErrorCode::None,
+                    id,
+                    epoch: 0,
+                });
+        }
+
         self.storage
             .init_producer(
                 transaction_id,
@@ -59,9 +121,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Error;
+    use crate::broker::faulty::Faulty;
     use object_store::memory::InMemory;
-    use tansu_kafka_sans_io::ErrorCode;
     use tansu_storage::dynostore::DynoStore;
     use tracing::subscriber::DefaultGuard;
 
@@ -147,4 +208,59 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn producer_id_block() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let object_store = Faulty::default();
+        let storage = DynoStore::new(cluster, node, object_store.clone());
+
+        let producer_ids = Some(ProducerIdBlock::new(100));
+
+        for expected in 1..=1_000 {
+            assert_eq!(
+                ProducerIdResponse {
+                    error: ErrorCode::None,
+                    id: expected,
+                    epoch: 0
+                },
+                InitProducerIdRequest::with_storage(storage.clone())
+                    .producer_ids(producer_ids.clone())
+                    .response(None, 0, Some(-1), Some(-1))
+                    .await?
+            );
+        }
+
+        assert!(object_store.puts() <= 10, "puts: {}", object_store.puts());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn empty_producer_id_block() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+
+        for size in [0, -1] {
+            assert!(matches!(
+                InitProducerIdRequest::with_storage(storage.clone())
+                    .producer_ids(Some(ProducerIdBlock::new(size)))
+                    .response(None, 0, Some(-1), Some(-1))
+                    .await,
+                Err(Error::Storage(tansu_storage::Error::Api(
+                    ErrorCode::InvalidRequest
+                )))
+            ));
+        }
+
+        Ok(())
+    }
 }
@@ -16,18 +16,47 @@
 use std::{collections::BTreeSet, ops::Deref};
 
 use tansu_kafka_sans_io::{
-    Body, IsolationLevel,
+    Body, ErrorCode, IsolationLevel,
     list_offsets_request::ListOffsetsTopic,
     list_offsets_response::{ListOffsetsPartitionResponse, ListOffsetsTopicResponse},
 };
-use tansu_storage::{ListOffsetRequest, Storage, Topition};
+use tansu_storage::{LEADER_EPOCH, ListOffsetRequest, ListOffsetResponse, Storage, Topition};
 use tracing::{debug, error};
 
 use crate::Result;
 
+// a list offsets refused in its entirety, reporting the error against each partition
+pub(crate) fn error_response(topics: Option<&[ListOffsetsTopic]>, error_code: ErrorCode) -> Body {
+    Body::ListOffsetsResponse {
+        throttle_time_ms: Some(0),
+        topics: topics.map(|topics| {
+            topics
+                .iter()
+                .map(|topic| ListOffsetsTopicResponse {
+                    name: topic.name.clone(),
+                    partitions: topic.partitions.as_ref().map(|partitions| {
+                        partitions
+                            .iter()
+                            .map(|partition| ListOffsetsPartitionResponse {
+                                partition_index: partition.partition_index,
+                                error_code: error_code.into(),
+                                old_style_offsets: None,
+                                timestamp: Some(-1),
+                                offset: Some(-1),
+                                leader_epoch: Some(-1),
+                            })
+                            .collect()
+                    }),
+                })
+                .collect()
+        }),
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct ListOffsetsRequest<S> {
     storage: S,
+    default_isolation_level: IsolationLevel,
 }
 
 impl<S> ListOffsetsRequest<S>
@@ -35,17 +64,89 @@ where
     S: Storage,
 {
     pub fn with_storage(storage: S) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            default_isolation_level: IsolationLevel::default(),
+        }
+    }
+
+    // the isolation level used when absent from the request
+    pub fn default_isolation_level(self, default_isolation_level: IsolationLevel) -> Self {
+        Self {
+            default_isolation_level,
+            ..self
+        }
+    }
+
+    async fn list_offsets(
+        &mut self,
+        isolation_level: IsolationLevel,
+        offsets: &[(Topition, ListOffsetRequest)],
+    ) -> Result<Vec<(Topition, ListOffsetResponse)>> {
+        let by_watermark = |offset: &ListOffsetRequest| {
+            *offset == ListOffsetRequest::Earliest
+                || (*offset == ListOffsetRequest::Latest
+                    && isolation_level == IsolationLevel::ReadUncommitted)
+        };
+
+        let listed = offsets
+            .iter()
+            .filter(|(_, offset)| !by_watermark(offset))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut listed = if listed.is_empty() {
+            vec![]
+        } else {
+            self.storage.list_offsets(isolation_level, &listed).await?
+        }
+        .into_iter();
+
+        let mut responses = vec![];
+
+        for (topition, offset) in offsets {
+            if by_watermark(offset) {
+                let response = match self.storage.watermarks(topition).await {
+                    Ok((low, high)) => ListOffsetResponse {
+                        offset: Some(if *offset == ListOffsetRequest::Earliest {
+                            low
+                        } else {
+                            high
+                        }),
+                        ..Default::default()
+                    },
+
+                    Err(tansu_storage::Error::Api(error_code)) => ListOffsetResponse {
+                        error_code,
+                        ..Default::default()
+                    },
+
+                    Err(otherwise) => return Err(otherwise.into()),
+                };
+
+                responses.push((topition.to_owned(), response));
+            } else if let Some(listed) = listed.next() {
+                responses.push(listed);
+            }
+        }
+
+        Ok(responses)
     }
 
     pub async fn response(
         &mut self,
         replica_id: i32,
-        isolation_level: IsolationLevel,
+        isolation_level: Option<i8>,
         topics: Option<&[ListOffsetsTopic]>,
     ) -> Result<Body> {
         debug!(?replica_id, ?isolation_level, ?topics);
 
+        let Ok(isolation_level) =
+            isolation_level.map_or(Ok(self.default_isolation_level), IsolationLevel::try_from)
+        else {
+            return Ok(error_response(topics, ErrorCode::InvalidRequest)).inspect(|r| debug!(?r));
+        };
+
         let throttle_time_ms = Some(0);
 
         let topics = if let Some(topics) = topics {
@@ -63,8 +164,7 @@ where
             }
 
             Some(
-                self.storage
-                    .list_offsets(isolation_level, offsets.deref())
+                self.list_offsets(isolation_level, offsets.deref())
                     .await
                     .inspect(|r| debug!(?r, ?offsets))
                     .inspect_err(|err| error!(?err, ?offsets))
@@ -92,7 +192,7 @@ where
                                                         .unwrap_or(Some(-1))
                                                         .or(Some(-1)),
                                                     offset: offset.offset().or(Some(0)),
-                                                    leader_epoch: Some(0),
+                                                    leader_epoch: Some(LEADER_EPOCH),
                                                 })
                                             } else {
                                                 None
@@ -13,15 +13,94 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
 use tansu_kafka_sans_io::{Body, metadata_request::MetadataRequestTopic};
-use tansu_storage::{Storage, TopicId};
-use tracing::error;
+use tansu_storage::{
+    MetadataResponse, Storage, TopicId,
+    clock::{Clock, SystemClock},
+};
+use tracing::{debug, error};
 
 use crate::Result;
 
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Default)]
+struct Snapshot {
+    generation: u64,
+    taken: Option<(SystemTime, Arc<MetadataResponse>)>,
+}
+
+#[derive(Clone, Debug)]
+enum Cached {
+    Fresh(Arc<MetadataResponse>),
+
+    // a snapshot taken now from storage may be cached under this generation
+    Miss { generation: u64 },
+}
+
+/// Cluster metadata held in memory for up to a TTL, shared by every
+/// connection, and invalidated when a topic is created or deleted, this
+/// broker registers, or leaders are elected on this broker. Disabled
+/// without a TTL
+#[derive(Clone, Debug, Default)]
+pub struct MetadataCache {
+    ttl: Option<Duration>,
+    snapshot: Arc<Mutex<Snapshot>>,
+}
+
+impl MetadataCache {
+    pub fn ttl(self, ttl: Option<Duration>) -> Self {
+        Self { ttl, ..self }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Snapshot> {
+        self.snapshot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn get(&self, now: SystemTime) -> Cached {
+        let snapshot = self.lock();
+
+        match (self.ttl, snapshot.taken.as_ref()) {
+            (Some(ttl), Some((taken, response)))
+                if now.duration_since(*taken).unwrap_or_default() < ttl =>
+            {
+                Cached::Fresh(response.clone())
+            }
+
+            _ => Cached::Miss {
+                generation: snapshot.generation,
+            },
+        }
+    }
+
+    // a snapshot from a generation since invalidated is not cached, as
+    // it may predate the change
+    fn put(&self, generation: u64, now: SystemTime, response: &Arc<MetadataResponse>) {
+        if self.ttl.is_some() {
+            let mut snapshot = self.lock();
+
+            if snapshot.generation == generation {
+                snapshot.taken = Some((now, response.clone()));
+            }
+        }
+    }
+
+    pub(crate) fn invalidate(&self) {
+        let mut snapshot = self.lock();
+        snapshot.generation += 1;
+        snapshot.taken = None;
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct MetadataRequest<S> {
     storage: S,
+    cache: MetadataCache,
+    clock: Arc<dyn Clock>,
 }
 
 impl<S> MetadataRequest<S>
@@ -29,7 +108,38 @@ where
     S: Storage,
 {
     pub fn with_storage(storage: S) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            cache: MetadataCache::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn cache(self, cache: MetadataCache) -> Self {
+        Self { cache, ..self }
+    }
+
+    pub fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    async fn snapshot(&mut self) -> Result<Arc<MetadataResponse>> {
+        let now = self.clock.now();
+
+        match self.cache.get(now) {
+            Cached::Fresh(snapshot) => Ok(snapshot),
+
+            Cached::Miss { generation } => {
+                debug!(generation);
+
+                self.storage
+                    .metadata_snapshot()
+                    .await
+                    .map(Arc::new)
+                    .inspect(|snapshot| self.cache.put(generation, now, snapshot))
+                    .map_err(Into::into)
+            }
+        }
     }
 
     pub async fn response(&mut self, topics: Option<Vec<MetadataRequestTopic>>) -> Result<Body> {
@@ -38,14 +148,14 @@ where
         let topics = topics.map(|topics| topics.iter().map(TopicId::from).collect::<Vec<_>>());
 
         let response = self
-            .storage
-            .metadata(topics.as_deref())
+            .snapshot()
             .await
+            .map(|snapshot| snapshot.select(topics.as_deref()))
             .inspect_err(|err| error!(?err))?;
         let brokers = Some(response.brokers().to_owned());
         let cluster_id = response.cluster().map(|s| s.into());
         let controller_id = response.controller();
-        let topics = Some(response.topics().to_owned());
+        let topics = Some(response.into_topics());
         let cluster_authorized_operations = None;
 
         Ok(Body::MetadataResponse {
@@ -58,3 +168,229 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, broker::faulty::Faulty};
+    use object_store::memory::InMemory;
+    use tansu_kafka_sans_io::{ErrorCode, create_topics_request::CreatableTopic};
+    use tansu_storage::dynostore::DynoStore;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn bounded_storage_queries() -> Result<()> {
+        const TOPICS: usize = 1_000;
+
+        let object_store = Faulty::default();
+
+        // each request is made through a new store, without any cached
+        // reads, so that every read reaches the counting object store
+        let storage = || DynoStore::new("tansu", 111, object_store.clone());
+
+        let mut created = storage();
+
+        let mut names = vec![];
+
+        for i in 0..TOPICS {
+            let name = format!("topic-{i:04}");
+
+            _ = created
+                .create_topic(
+                    CreatableTopic {
+                        name: name.clone(),
+                        num_partitions: 3,
+                        replication_factor: 0,
+                        assignments: Some([].into()),
+                        configs: Some([].into()),
+                    },
+                    false,
+                )
+                .await?;
+
+            names.push(name);
+        }
+
+        let requested = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| MetadataRequestTopic {
+                    topic_id: None,
+                    name: Some(name.into()),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the reads from storage are the same for one topic as for many
+        let reads = object_store.reads();
+
+        _ = MetadataRequest::with_storage(storage())
+            .response(Some(requested(&names[..1])))
+            .await?;
+
+        let single = object_store.reads() - reads;
+        assert!(single > 0);
+
+        let mut all = requested(&names);
+        all.push(MetadataRequestTopic {
+            topic_id: None,
+            name: Some("unknown".into()),
+        });
+
+        let reads = object_store.reads();
+
+        let Body::MetadataResponse {
+            topics: Some(topics),
+            ..
+        } = MetadataRequest::with_storage(storage())
+            .response(Some(all))
+            .await?
+        else {
+            return Err(Error::Custom(String::from("unexpected response")));
+        };
+
+        assert_eq!(single, object_store.reads() - reads);
+
+        assert_eq!(TOPICS + 1, topics.len());
+
+        for (topic, name) in topics.iter().zip(names.iter()) {
+            assert_eq!(i16::from(ErrorCode::None), topic.error_code);
+            assert_eq!(Some(name.as_str()), topic.name.as_deref());
+            assert_eq!(Some(3), topic.partitions.as_ref().map(Vec::len));
+        }
+
+        assert_eq!(
+            i16::from(ErrorCode::UnknownTopicOrPartition),
+            topics[TOPICS].error_code
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cached_until_expired_or_invalidated() -> Result<()> {
+        use crate::{
+            broker::Broker, config::Config, coordinator::group::administrator::Controller,
+        };
+        use std::net::SocketAddr;
+        use tansu_storage::{StorageContainer, clock::ManualClock};
+        use url::Url;
+
+        let cluster_id = "tansu";
+        let node_id = 111;
+        let ttl = Duration::from_secs(5);
+
+        let clock = ManualClock::default();
+
+        let mut storage =
+            StorageContainer::DynoStore(DynoStore::new(cluster_id, node_id, InMemory::new()));
+
+        let config = Config::builder()
+            .cluster_id(cluster_id)
+            .node_id(node_id)
+            .listener(Url::parse("tcp://localhost:9092/")?)
+            .advertised_listener(Url::parse("tcp://localhost:9092/")?)
+            .storage(Url::parse("memory://tansu/")?)
+            .build()?;
+
+        let broker = Broker::new(
+            &config,
+            storage.clone(),
+            Controller::with_storage(storage.clone())?,
+            Uuid::nil(),
+        )
+        .metadata_cache(MetadataCache::default().ttl(Some(ttl)))
+        .clock(Arc::new(clock.clone()));
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], 9092));
+
+        let topic = |name: &str| CreatableTopic {
+            name: name.into(),
+            num_partitions: 1,
+            replication_factor: 1,
+            assignments: Some([].into()),
+            configs: Some([].into()),
+        };
+
+        // each connection has its own clone of the broker, sharing the cache
+        let metadata = async |mut broker: Broker<_, _>| -> Result<Vec<String>> {
+            let Body::MetadataResponse {
+                topics: Some(topics),
+                ..
+            } = broker
+                .response_for(
+                    &peer,
+                    Some("test"),
+                    Body::MetadataRequest {
+                        topics: None,
+                        allow_auto_topic_creation: Some(false),
+                        include_cluster_authorized_operations: None,
+                        include_topic_authorized_operations: Some(false),
+                    },
+                    1,
+                )
+                .await?
+            else {
+                return Err(Error::Custom(String::from("unexpected response")));
+            };
+
+            Ok(topics.into_iter().filter_map(|topic| topic.name).collect())
+        };
+
+        _ = storage.create_topic(topic("abc"), false).await?;
+        assert_eq!(vec!["abc"], metadata(broker.clone()).await?);
+
+        // topics created elsewhere are not seen while the snapshot is cached
+        _ = storage.create_topic(topic("def"), false).await?;
+
+        for _ in 0..2 {
+            assert_eq!(vec!["abc"], metadata(broker.clone()).await?);
+        }
+
+        // created through this broker, invalidating the cache
+        _ = broker
+            .clone()
+            .response_for(
+                &peer,
+                Some("test"),
+                Body::CreateTopicsRequest {
+                    topics: Some(vec![topic("ghi")]),
+                    timeout_ms: 30_000,
+                    validate_only: Some(false),
+                },
+                2,
+            )
+            .await?;
+
+        assert_eq!(vec!["abc", "def", "ghi"], metadata(broker.clone()).await?);
+
+        // registering this broker also invalidates the cache
+        _ = storage.create_topic(topic("jkl"), false).await?;
+
+        assert_eq!(vec!["abc", "def", "ghi"], metadata(broker.clone()).await?);
+
+        broker.clone().register().await?;
+
+        assert_eq!(
+            vec!["abc", "def", "ghi", "jkl"],
+            metadata(broker.clone()).await?
+        );
+
+        // created elsewhere, only seen once the cached snapshot expires
+        _ = storage.create_topic(topic("mno"), false).await?;
+
+        assert_eq!(
+            vec!["abc", "def", "ghi", "jkl"],
+            metadata(broker.clone()).await?
+        );
+
+        clock.advance(ttl);
+
+        assert_eq!(
+            vec!["abc", "def", "ghi", "jkl", "mno"],
+            metadata(broker).await?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt::Debug;
+
+use tansu_kafka_sans_io::{
+    create_topics_request::CreatableTopic, incremental_alter_configs_request::AlterConfigsResource,
+};
+
+/// Rules applied to topic creation and configuration changes, rejecting
+/// a request with a policy violation when a rule fails
+pub trait Policy: Debug + Send + Sync {
+    fn validate_create_topic(&self, topic: &CreatableTopic) -> Result<(), String> {
+        let _ = topic;
+        Ok(())
+    }
+
+    fn validate_alter_config(&self, resource: &AlterConfigsResource) -> Result<(), String> {
+        let _ = resource;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NoPolicy;
+
+impl Policy for NoPolicy {}
@@ -13,18 +13,195 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{Error, Result};
+pub mod config;
+pub mod tee;
+pub mod transform;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use bytes::Bytes;
+
+use crate::{Error, Result, broker::fetch::notifier::Notifier};
+use config::{Lookup, TopicConfig, TopicConfigCache};
 use tansu_kafka_sans_io::{
-    ErrorCode,
+    BatchAttribute, Body, Compression, ConfigResource, ErrorCode, TimestampType,
+    describe_configs_response::DescribeConfigsResourceResult,
     produce_request::{PartitionProduceData, TopicProduceData},
     produce_response::{NodeEndpoint, PartitionProduceResponse, TopicProduceResponse},
+    record::{
+        Header, Record,
+        deflated::{self, CompressionLevel, Limit},
+        inflated,
+    },
+    to_timestamp,
 };
-use tansu_storage::{Storage, Topition};
+use tansu_storage::{
+    COMPRESSION_GZIP_LEVEL, COMPRESSION_LZ4_LEVEL, COMPRESSION_ZSTD_LEVEL, Storage, TopicId,
+    Topition,
+    clock::{Clock, SystemClock},
+    compression_level,
+};
+use tee::Tee;
+use tokio::time::Instant;
 use tracing::{debug, error, warn};
+use transform::Transforms;
+
+const COMPRESSION_TYPE: &str = "compression.type";
+
+// every topic configuration used by produce
+const TOPIC_CONFIGS: [&str; 7] = [
+    COMPRESSION_TYPE,
+    COMPRESSION_GZIP_LEVEL,
+    COMPRESSION_LZ4_LEVEL,
+    COMPRESSION_ZSTD_LEVEL,
+    MIN_INSYNC_REPLICAS,
+    MESSAGE_TIMESTAMP_DIFFERENCE_MAX_MS,
+    DEAD_LETTER_TOPIC,
+];
+const MIN_INSYNC_REPLICAS: &str = "min.insync.replicas";
+const MESSAGE_TIMESTAMP_DIFFERENCE_MAX_MS: &str = "message.timestamp.difference.max.ms";
+pub const DEAD_LETTER_TOPIC: &str = "errors.deadletterqueue.topic.name";
+
+// headers added to each record quarantined in a dead letter topic
+pub const REJECTION_REASON: &str = "rejection-reason";
+pub const REJECTION_TOPIC: &str = "rejection-topic";
+pub const REJECTION_PARTITION: &str = "rejection-partition";
+
+// a batch without timestamps, from a producer that predates them
+const NO_TIMESTAMP: i64 = -1;
+
+// acks=all, waiting for the in sync replicas to acknowledge the produce
+const ACKS_ALL: i16 = -1;
+
+// a produce refused in its entirety, reporting the error against each partition
+pub(crate) fn error_response(
+    topic_data: Option<&[TopicProduceData]>,
+    error_code: ErrorCode,
+) -> Body {
+    let refused = refused(topic_data, error_code);
+
+    Body::ProduceResponse {
+        responses: refused.responses,
+        throttle_time_ms: refused.throttle_time_ms,
+        node_endpoints: refused.node_endpoints,
+    }
+}
+
+fn refused(topic_data: Option<&[TopicProduceData]>, error_code: ErrorCode) -> ProduceResponse {
+    ProduceResponse {
+        responses: Some(
+            topic_data
+                .unwrap_or_default()
+                .iter()
+                .map(|topic| TopicProduceResponse {
+                    name: topic.name.clone(),
+                    partition_responses: Some(
+                        topic
+                            .partition_data
+                            .as_deref()
+                            .unwrap_or_default()
+                            .iter()
+                            .map(|partition| PartitionProduceResponse {
+                                index: partition.index,
+                                error_code: error_code.into(),
+                                base_offset: -1,
+                                log_append_time_ms: Some(-1),
+                                log_start_offset: Some(0),
+                                record_errors: Some([].into()),
+                                error_message: None,
+                                current_leader: None,
+                            })
+                            .collect(),
+                    ),
+                })
+                .collect(),
+        ),
+        throttle_time_ms: Some(0),
+        node_endpoints: None,
+    }
+}
+
+// an acks=all produce that is not acknowledged by the deadline of the
+// request fails with a timeout. The deadline is checked before and after
+// a storage call rather than cancelling it part way through an append
+fn expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+fn acknowledged<T>(
+    deadline: Option<Instant>,
+    outcome: Result<T, ErrorCode>,
+) -> Result<T, ErrorCode> {
+    outcome.and_then(|acknowledged| {
+        if expired(deadline) {
+            Err(ErrorCode::RequestTimedOut)
+        } else {
+            Ok(acknowledged)
+        }
+    })
+}
+
+// a batch refused by validation of its schema, size or timestamps, rather
+// than one that could not be decoded or stored
+fn rejected(error_code: ErrorCode) -> bool {
+    matches!(
+        error_code,
+        ErrorCode::InvalidRecord | ErrorCode::RecordListTooLarge | ErrorCode::InvalidTimestamp
+    )
+}
+
+// the codec that batches are stored with, or none when the producer's codec is kept
+// the parsed value of a topic configuration, absent when unset or invalid
+fn config_value<T>(name: &str, configs: &[DescribeConfigsResourceResult], key: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    configs
+        .iter()
+        .find(|config| config.name == key)
+        .and_then(|config| config.value.as_deref())
+        .and_then(|value| {
+            value
+                .parse()
+                .inspect_err(|err| warn!(name, key, value, ?err))
+                .ok()
+        })
+}
+
+fn compression_type(value: &str) -> Option<Compression> {
+    match value {
+        "uncompressed" => Some(Compression::None),
+        "gzip" => Some(Compression::Gzip),
+        "snappy" => Some(Compression::Snappy),
+        "lz4" => Some(Compression::Lz4),
+        "zstd" => Some(Compression::Zstd),
+        "producer" => None,
 
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        otherwise => {
+            warn!(otherwise, "unknown {COMPRESSION_TYPE}");
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct ProduceRequest<S> {
     storage: S,
+    record_limit: Limit,
+    compression_level: CompressionLevel,
+    tee: Option<Tee>,
+    notifier: Option<Notifier>,
+    transforms: Transforms,
+    clock: Arc<dyn Clock>,
+    transactional_acks_all: bool,
+    topic_config_cache: TopicConfigCache,
 }
 
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -39,7 +216,79 @@ where
     S: Storage,
 {
     pub fn with_storage(storage: S) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            record_limit: Limit::produce_defaults(),
+            compression_level: CompressionLevel::default(),
+            tee: None,
+            notifier: None,
+            transforms: Transforms::default(),
+            clock: Arc::new(SystemClock),
+            transactional_acks_all: true,
+            topic_config_cache: TopicConfigCache::default(),
+        }
+    }
+
+    pub fn record_limit(self, record_limit: Limit) -> Self {
+        Self {
+            record_limit,
+            ..self
+        }
+    }
+
+    pub fn compression_level(self, compression_level: CompressionLevel) -> Self {
+        Self {
+            compression_level,
+            ..self
+        }
+    }
+
+    pub fn tee(self, tee: Option<Tee>) -> Self {
+        Self { tee, ..self }
+    }
+
+    pub fn notifier(self, notifier: Option<Notifier>) -> Self {
+        Self { notifier, ..self }
+    }
+
+    pub fn transforms(self, transforms: Transforms) -> Self {
+        Self { transforms, ..self }
+    }
+
+    pub fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    // a transactional produce must wait for all in sync replicas, with
+    // any other acks refused when enforced
+    pub fn transactional_acks_all(self, transactional_acks_all: bool) -> Self {
+        Self {
+            transactional_acks_all,
+            ..self
+        }
+    }
+
+    pub fn topic_config_cache(self, topic_config_cache: TopicConfigCache) -> Self {
+        Self {
+            topic_config_cache,
+            ..self
+        }
+    }
+
+    fn notify(&self, topition: &Topition) {
+        if let Some(notifier) = self.notifier.as_ref() {
+            if let Err(error) = notifier.notify(topition) {
+                warn!(?topition, ?error);
+            }
+        }
+    }
+
+    async fn teed(&self, topition: &Topition, base_offset: i64, batch: &deflated::Batch) {
+        if let Some(tee) = self.tee.as_ref() {
+            if let Err(error) = tee.write(topition, base_offset, batch).await {
+                warn!(?topition, base_offset, path = %tee.path().display(), ?error);
+            }
+        }
     }
 
     fn error(&self, index: i32, error_code: ErrorCode) -> PartitionProduceResponse {
@@ -55,63 +304,519 @@ where
         }
     }
 
-    async fn partition(
+    // the configuration used by produce, described in a single lookup that
+    // is cached between requests
+    async fn topic_config(&mut self, name: &str) -> TopicConfig {
+        let now = self.clock.now();
+
+        let generation = match self.topic_config_cache.get(name, now) {
+            Lookup::Hit(config) => return config,
+            Lookup::Miss(generation) => generation,
+        };
+
+        let described = self
+            .storage
+            .describe_config(
+                name,
+                ConfigResource::Topic,
+                Some(&TOPIC_CONFIGS.map(String::from)),
+            )
+            .await
+            .inspect_err(|err| warn!(name, ?err));
+
+        match described {
+            Ok(result) => {
+                let config = TopicConfig::from(result.configs.unwrap_or_default());
+                self.topic_config_cache
+                    .put(generation, name, now, config.clone());
+                config
+            }
+
+            // not cached, so that the next produce tries again
+            Err(_) => TopicConfig::from([]),
+        }
+    }
+
+    fn compression(
+        &self,
+        name: &str,
+        configs: &[DescribeConfigsResourceResult],
+    ) -> Option<(Compression, CompressionLevel)> {
+        let compression = configs
+            .iter()
+            .find(|config| config.name == COMPRESSION_TYPE)
+            .and_then(|config| config.value.as_deref())
+            .and_then(compression_type)?;
+
+        let level = configs
+            .iter()
+            .filter_map(|config| {
+                config
+                    .value
+                    .as_deref()
+                    .map(|value| (config.name.as_str(), value))
+            })
+            .fold(
+                CompressionLevel::default(),
+                |level, (config, value)| match compression_level(level, config, value) {
+                    Some(Ok(updated)) => updated,
+
+                    Some(Err(error)) => {
+                        warn!(name, config, value, ?error);
+                        level
+                    }
+
+                    None => level,
+                },
+            )
+            .or(self.compression_level);
+
+        Some((compression, level))
+    }
+
+    // the replicas that must acknowledge an acks=all produce to this topic
+    fn min_insync_replicas(name: &str, configs: &[DescribeConfigsResourceResult]) -> i32 {
+        config_value(name, configs, MIN_INSYNC_REPLICAS).unwrap_or(1)
+    }
+
+    // the most that a record timestamp may differ from the broker time, unbounded when unset
+    fn timestamp_difference(name: &str, configs: &[DescribeConfigsResourceResult]) -> Option<u64> {
+        config_value(name, configs, MESSAGE_TIMESTAMP_DIFFERENCE_MAX_MS)
+    }
+
+    // the topic keeping a copy of each batch rejected by validation
+    fn dead_letter_topic(name: &str, configs: &[DescribeConfigsResourceResult]) -> Option<String> {
+        config_value::<String>(name, configs, DEAD_LETTER_TOPIC)
+            .filter(|dead_letter| !dead_letter.is_empty() && dead_letter != name)
+    }
+
+    // a rejected batch written to the dead letter topic, with each record
+    // describing why and from where. The producer identity is removed so
+    // that the quarantined batch is not subject to sequence checks
+    async fn dead_letter(
+        &mut self,
+        dead_letter: &str,
+        topition: &Topition,
+        error_code: ErrorCode,
+        batch: deflated::Batch,
+    ) -> Result<i64, ErrorCode> {
+        let mut inflated = inflated::Batch::try_from(batch).map_err(|error| {
+            warn!(?topition, ?error);
+            ErrorCode::CorruptMessage
+        })?;
+
+        let headers = [
+            (REJECTION_REASON, format!("{error_code:?}")),
+            (REJECTION_TOPIC, topition.topic().to_owned()),
+            (REJECTION_PARTITION, topition.partition().to_string()),
+        ]
+        .map(|(key, value)| Header {
+            key: Some(Bytes::from_static(key.as_bytes())),
+            value: Some(Bytes::from(value)),
+        });
+
+        for record in inflated.records.iter_mut() {
+            record.headers.extend(headers.iter().cloned());
+        }
+
+        let attributes = BatchAttribute::try_from(inflated.attributes)
+            .map(|attributes| attributes.transaction(false).into())
+            .unwrap_or(inflated.attributes);
+
+        let batch = inflated
+            .into_builder()
+            .attributes(attributes)
+            .producer_id(-1)
+            .producer_epoch(-1)
+            .base_sequence(-1)
+            .build()
+            .and_then(deflated::Batch::try_from)
+            .map_err(|error| {
+                warn!(?topition, ?error);
+                ErrorCode::CorruptMessage
+            })?;
+
+        // the partition of the dead letter topic matching the rejected
+        // partition, or its first when it has fewer partitions
+        let quarantine = Topition::new(dead_letter, topition.partition());
+
+        let quarantine = if self.partition_exists(&quarantine).await.is_ok() {
+            quarantine
+        } else {
+            Topition::new(dead_letter, 0)
+        };
+
+        let outcome = self.storage.produce(None, &quarantine, batch).await;
+
+        self.outcome(outcome)
+            .inspect(|offset| {
+                warn!(?topition, ?error_code, ?quarantine, offset);
+                self.notify(&quarantine);
+            })
+            .inspect_err(|quarantine_error| warn!(?topition, ?error_code, ?quarantine_error))
+    }
+
+    // the timestamp of each record must be within the bounds of its batch, and
+    // when created by the producer, within the allowed difference of the broker time
+    fn timestamps(
+        &self,
+        name: &str,
+        index: i32,
+        batch: &deflated::Batch,
+        records: Option<&[Record]>,
+        timestamp_difference: Option<u64>,
+    ) -> Result<(), ErrorCode> {
+        if batch.base_timestamp == NO_TIMESTAMP && batch.max_timestamp == NO_TIMESTAMP {
+            return Ok(());
+        }
+
+        if batch.base_timestamp > batch.max_timestamp {
+            warn!(name, index, batch.base_timestamp, batch.max_timestamp);
+            return Err(ErrorCode::InvalidTimestamp);
+        }
+
+        // records are only decoded when bounded by a limit or a timestamp difference
+        let Some(records) = records else {
+            return Ok(());
+        };
+
+        let create_time = BatchAttribute::try_from(batch.attributes)
+            .is_ok_and(|attributes| attributes.timestamp == TimestampType::CreateTime);
+
+        let now = to_timestamp(self.clock.now()).map_err(|error| {
+            error!(name, index, ?error);
+            ErrorCode::UnknownServerError
+        })?;
+
+        for record in records {
+            let timestamp = record.timestamp(batch.base_timestamp);
+
+            if timestamp < batch.base_timestamp || timestamp > batch.max_timestamp {
+                warn!(
+                    name,
+                    index,
+                    record.offset_delta,
+                    timestamp,
+                    batch.base_timestamp,
+                    batch.max_timestamp
+                );
+                return Err(ErrorCode::InvalidTimestamp);
+            }
+
+            if create_time
+                && timestamp_difference.is_some_and(|timestamp_difference| {
+                    timestamp.abs_diff(now) > timestamp_difference
+                })
+            {
+                warn!(
+                    name,
+                    index, record.offset_delta, timestamp, now, timestamp_difference
+                );
+                return Err(ErrorCode::InvalidTimestamp);
+            }
+        }
+
+        Ok(())
+    }
+
+    // the partitions (or error) of each topic of a produce, from a single
+    // metadata lookup, so that a produce to an unknown topic or partition
+    // fails before any configuration is described or batch is decoded
+    async fn known_partitions(
         &mut self,
-        transaction_id: Option<&str>,
+        topics: &[TopicProduceData],
+    ) -> Result<BTreeMap<String, Result<BTreeSet<i32>, ErrorCode>>, ErrorCode> {
+        let names = topics
+            .iter()
+            .map(|topic| TopicId::Name(topic.name.clone()))
+            .collect::<Vec<_>>();
+
+        let outcome = self.storage.metadata(Some(&names)).await;
+
+        self.outcome(outcome).map(|metadata| {
+            metadata
+                .topics()
+                .iter()
+                .filter_map(|topic| {
+                    topic.name.clone().map(|name| {
+                        let error_code = ErrorCode::try_from(topic.error_code)
+                            .unwrap_or(ErrorCode::UnknownServerError);
+
+                        (
+                            name,
+                            if error_code == ErrorCode::None {
+                                Ok(topic
+                                    .partitions
+                                    .as_deref()
+                                    .unwrap_or_default()
+                                    .iter()
+                                    .map(|partition| partition.partition_index)
+                                    .collect())
+                            } else {
+                                Err(error_code)
+                            },
+                        )
+                    })
+                })
+                .collect()
+        })
+    }
+
+    async fn partition_exists(&mut self, topition: &Topition) -> Result<(), ErrorCode> {
+        let outcome = self.storage.partition_exists(topition).await;
+
+        match self.outcome(outcome) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(ErrorCode::UnknownTopicOrPartition),
+            Err(error_code) => Err(error_code),
+        }
+    }
+
+    async fn insufficient_replicas(
+        &mut self,
+        topition: &Topition,
+        min_insync_replicas: Option<i32>,
+    ) -> bool {
+        let Some(min_insync_replicas) = min_insync_replicas else {
+            return false;
+        };
+
+        self.storage
+            .in_sync_replicas(topition)
+            .await
+            .inspect(|in_sync_replicas| debug!(?topition, in_sync_replicas, min_insync_replicas))
+            .inspect_err(|err| warn!(?topition, ?err))
+            .map_or(true, |in_sync_replicas| {
+                in_sync_replicas < min_insync_replicas
+            })
+    }
+
+    fn batch(
+        &self,
         name: &str,
+        compression: Option<(Compression, CompressionLevel)>,
+        timestamp_difference: Option<u64>,
         partition: PartitionProduceData,
-    ) -> PartitionProduceResponse {
+    ) -> Result<deflated::Batch, ErrorCode> {
         match partition.records {
             Some(mut records) if records.batches.len() == 1 => {
-                let batch = records.batches.remove(0);
+                let mut batch = records.batches.remove(0);
 
-                let tp = Topition::new(name, partition.index);
+                let data = BatchAttribute::try_from(batch.attributes)
+                    .is_ok_and(|attributes| !attributes.control);
 
-                match self
-                    .storage
-                    .produce(transaction_id, &tp, batch)
-                    .await
-                    .map_err(Into::into)
-                    .inspect_err(|err| match err {
-                        storage_api @ Error::Storage(tansu_storage::Error::Api(_)) => {
-                            warn!(?storage_api)
+                // decoded at most once, and only when there is a bound to check
+                let records = if !self.record_limit.is_unlimited()
+                    || (data && timestamp_difference.is_some())
+                {
+                    match batch.records(self.record_limit) {
+                        Ok(records) => Some(records),
+
+                        Err(
+                            error @ (tansu_kafka_sans_io::Error::InflatedSizeExceeded(_)
+                            | tansu_kafka_sans_io::Error::RecordCountExceeded(_)),
+                        ) => {
+                            warn!(name, partition.index, ?error);
+                            return Err(ErrorCode::RecordListTooLarge);
                         }
-                        otherwise => error!(?otherwise),
-                    }) {
-                    Ok(base_offset) => PartitionProduceResponse {
-                        index: partition.index,
-                        error_code: ErrorCode::None.into(),
-                        base_offset,
-                        log_append_time_ms: Some(-1),
-                        log_start_offset: Some(0),
-                        record_errors: Some([].into()),
-                        error_message: None,
-                        current_leader: None,
-                    },
 
-                    Err(Error::Storage(tansu_storage::Error::Api(error_code))) => {
-                        debug!(?self, ?error_code);
-                        self.error(partition.index, error_code)
+                        Err(error) => {
+                            warn!(name, partition.index, ?error);
+                            return Err(ErrorCode::CorruptMessage);
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                if data {
+                    self.timestamps(
+                        name,
+                        partition.index,
+                        &batch,
+                        records.as_deref(),
+                        timestamp_difference,
+                    )?;
+                    batch = self.transforms.batch(name, batch, self.record_limit)?;
+                }
+
+                if let Some((compression, level)) = compression.filter(|_| data) {
+                    match batch.recompress_with_level(compression, level) {
+                        Ok(recompressed) => batch = recompressed,
+
+                        Err(error) => {
+                            warn!(name, partition.index, ?error);
+                            return Err(ErrorCode::CorruptMessage);
+                        }
+                    }
+                }
+
+                Ok(batch)
+            }
+
+            _otherwise => Err(ErrorCode::UnknownServerError),
+        }
+    }
+
+    fn outcome<T>(&self, outcome: tansu_storage::Result<T>) -> Result<T, ErrorCode> {
+        outcome
+            .map_err(Into::into)
+            .inspect_err(|err| match err {
+                storage_api @ Error::Storage(tansu_storage::Error::Api(_)) => {
+                    warn!(?storage_api)
+                }
+                otherwise => error!(?otherwise),
+            })
+            .map_err(|error| {
+                let error_code = error
+                    .storage_error_code()
+                    .unwrap_or(ErrorCode::UnknownServerError);
+                debug!(?self, ?error_code);
+                error_code
+            })
+    }
+
+    fn produced(&self, index: i32, outcome: Result<i64, ErrorCode>) -> PartitionProduceResponse {
+        match outcome {
+            Ok(base_offset) => PartitionProduceResponse {
+                index,
+                error_code: ErrorCode::None.into(),
+                base_offset,
+                log_append_time_ms: Some(-1),
+                log_start_offset: Some(0),
+                record_errors: Some([].into()),
+                error_message: None,
+                current_leader: None,
+            },
+
+            Err(error_code) => self.error(index, error_code),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn partition(
+        &mut self,
+        name: &str,
+        compression: Option<(Compression, CompressionLevel)>,
+        timestamp_difference: Option<u64>,
+        min_insync_replicas: Option<i32>,
+        dead_letter: Option<&str>,
+        deadline: Option<Instant>,
+        partition: PartitionProduceData,
+    ) -> PartitionProduceResponse {
+        let index = partition.index;
+        let tp = Topition::new(name, index);
+
+        if self.insufficient_replicas(&tp, min_insync_replicas).await {
+            return self.error(index, ErrorCode::NotEnoughReplicas);
+        }
+
+        // the batch as produced, kept only when it may be quarantined
+        let original = dead_letter.and_then(|_| {
+            partition
+                .records
+                .as_ref()
+                .filter(|records| records.batches.len() == 1)
+                .map(|records| records.batches[0].clone())
+        });
+
+        let outcome = match self.batch(name, compression, timestamp_difference, partition) {
+            Ok(batch) => {
+                let teed = self.tee.as_ref().map(|_| batch.clone());
+
+                let outcome = if expired(deadline) {
+                    Err(ErrorCode::RequestTimedOut)
+                } else {
+                    let outcome = self.storage.produce(None, &tp, batch).await;
+
+                    match self.outcome(outcome) {
+                        Ok(_) if self.insufficient_replicas(&tp, min_insync_replicas).await => {
+                            Err(ErrorCode::NotEnoughReplicasAfterAppend)
+                        }
+
+                        otherwise => otherwise,
                     }
+                };
 
-                    Err(_) => self.error(partition.index, ErrorCode::UnknownServerError),
+                if let (Ok(base_offset), Some(batch)) = (outcome.as_ref(), teed) {
+                    self.teed(&tp, *base_offset, &batch).await;
                 }
+
+                if outcome.is_ok() {
+                    self.notify(&tp);
+                }
+
+                acknowledged(deadline, outcome)
             }
 
-            _otherwise => self.error(partition.index, ErrorCode::UnknownServerError),
+            Err(error_code) => Err(error_code),
+        };
+
+        // a rejected batch is still refused, so that the producer state
+        // (e.g., an idempotent sequence) is unchanged, with a copy kept on
+        // the side in the dead letter topic
+        if let (Err(error_code), Some(dead_letter), Some(original)) =
+            (outcome, dead_letter, original)
+        {
+            if rejected(error_code) {
+                _ = self
+                    .dead_letter(dead_letter, &tp, error_code, original)
+                    .await;
+            }
         }
+
+        self.produced(index, outcome)
     }
 
     async fn topic(
         &mut self,
-        transaction_id: Option<&str>,
+        acks: i16,
+        deadline: Option<Instant>,
+        known: Result<&BTreeSet<i32>, ErrorCode>,
         topic: TopicProduceData,
     ) -> TopicProduceResponse {
         let mut partitions = vec![];
 
         if let Some(partition_data) = topic.partition_data {
+            if let Err(error_code) = known {
+                return TopicProduceResponse {
+                    partition_responses: Some(
+                        partition_data
+                            .iter()
+                            .map(|partition| self.error(partition.index, error_code))
+                            .collect(),
+                    ),
+                    name: topic.name,
+                };
+            }
+
+            let config = self.topic_config(&topic.name).await;
+            let compression = self.compression(&topic.name, &config);
+            let timestamp_difference = Self::timestamp_difference(&topic.name, &config);
+            let dead_letter = Self::dead_letter_topic(&topic.name, &config);
+
+            let min_insync_replicas =
+                (acks == ACKS_ALL).then(|| Self::min_insync_replicas(&topic.name, &config));
+
             for partition in partition_data {
-                partitions.push(self.partition(transaction_id, &topic.name, partition).await)
+                if known.is_ok_and(|known| !known.contains(&partition.index)) {
+                    partitions
+                        .push(self.error(partition.index, ErrorCode::UnknownTopicOrPartition));
+                    continue;
+                }
+
+                partitions.push(
+                    self.partition(
+                        &topic.name,
+                        compression.clone(),
+                        timestamp_difference,
+                        min_insync_replicas,
+                        dead_letter.as_deref(),
+                        deadline,
+                        partition,
+                    )
+                    .await,
+                )
             }
         }
 
@@ -121,6 +826,124 @@ where
         }
     }
 
+    // the batches of a transactional produce are written together, with
+    // a failure in any partition failing every partition: an unknown topic
+    // or partition fails the whole produce in storage rather than being
+    // guarded here, with any batches already appended retracted. A
+    // rejected batch is always refused, never quarantined, as the
+    // transaction would otherwise commit without it
+    async fn transactional(
+        &mut self,
+        transaction_id: &str,
+        acks: i16,
+        deadline: Option<Instant>,
+        topics: Vec<TopicProduceData>,
+    ) -> Vec<TopicProduceResponse> {
+        let mut responses = Vec::with_capacity(topics.len());
+        let mut entries = vec![];
+        let mut pending = vec![];
+
+        for topic in topics {
+            let mut partitions = vec![];
+
+            if let Some(partition_data) = topic.partition_data {
+                let config = self.topic_config(&topic.name).await;
+                let compression = self.compression(&topic.name, &config);
+                let timestamp_difference = Self::timestamp_difference(&topic.name, &config);
+
+                let min_insync_replicas =
+                    (acks == ACKS_ALL).then(|| Self::min_insync_replicas(&topic.name, &config));
+
+                for partition in partition_data {
+                    let index = partition.index;
+
+                    if self
+                        .insufficient_replicas(
+                            &Topition::new(topic.name.as_str(), index),
+                            min_insync_replicas,
+                        )
+                        .await
+                    {
+                        partitions.push(self.error(index, ErrorCode::NotEnoughReplicas));
+                        continue;
+                    }
+
+                    match self.batch(
+                        &topic.name,
+                        compression.clone(),
+                        timestamp_difference,
+                        partition,
+                    ) {
+                        Ok(batch) => {
+                            pending.push((responses.len(), partitions.len()));
+                            entries.push((Topition::new(topic.name.as_str(), index), batch));
+                            partitions.push(self.error(index, ErrorCode::None));
+                        }
+
+                        Err(error_code) => partitions.push(self.error(index, error_code)),
+                    }
+                }
+            }
+
+            responses.push(TopicProduceResponse {
+                name: topic.name,
+                partition_responses: Some(partitions),
+            });
+        }
+
+        if entries.is_empty() {
+            return responses;
+        }
+
+        let teed = self.tee.as_ref().map(|_| entries.clone());
+        let topitions = entries
+            .iter()
+            .map(|(topition, _)| topition.to_owned())
+            .collect::<Vec<_>>();
+
+        let outcome = if expired(deadline) {
+            Err(ErrorCode::RequestTimedOut)
+        } else {
+            let outcome = self
+                .storage
+                .produce_many(Some(transaction_id), entries)
+                .await;
+            self.outcome(outcome)
+        };
+
+        if let (Ok(offsets), Some(entries)) = (outcome.as_ref(), teed) {
+            for ((topition, batch), base_offset) in entries.iter().zip(offsets) {
+                self.teed(topition, *base_offset, batch).await;
+            }
+        }
+
+        if outcome.is_ok() {
+            for topition in &topitions {
+                self.notify(topition);
+            }
+        }
+
+        let outcome = acknowledged(deadline, outcome);
+
+        for (position, (topic, partition)) in pending.into_iter().enumerate() {
+            if let Some(response) = responses[topic]
+                .partition_responses
+                .as_mut()
+                .and_then(|partitions| partitions.get_mut(partition))
+            {
+                *response = self.produced(
+                    response.index,
+                    outcome
+                        .as_ref()
+                        .map(|offsets| offsets[position])
+                        .map_err(ToOwned::to_owned),
+                );
+            }
+        }
+
+        responses
+    }
+
     pub async fn response(
         &mut self,
         transaction_id: Option<String>,
@@ -128,16 +951,54 @@ where
         timeout_ms: i32,
         topic_data: Option<Vec<TopicProduceData>>,
     ) -> Result<ProduceResponse> {
-        debug!(?self, ?transaction_id, ?acks, timeout_ms, ?topic_data);
+        debug!(?self, ?transaction_id, ?acks, timeout_ms);
+
+        if self.transactional_acks_all && transaction_id.is_some() && acks != ACKS_ALL {
+            warn!(?transaction_id, acks);
+            return Ok(refused(
+                topic_data.as_deref(),
+                ErrorCode::InvalidRequiredAcks,
+            ));
+        }
 
         let mut responses =
             Vec::with_capacity(topic_data.as_ref().map_or(0, |topic_data| topic_data.len()));
 
+        // measured once for the whole request
+        let deadline = if acks == ACKS_ALL {
+            u64::try_from(timeout_ms)
+                .ok()
+                .map(|timeout_ms| Instant::now() + Duration::from_millis(timeout_ms))
+        } else {
+            None
+        };
+
         if let Some(topics) = topic_data {
-            for topic in topics {
-                debug!(?topic);
+            if let Some(transaction_id) = transaction_id.as_deref() {
+                responses = self
+                    .transactional(transaction_id, acks, deadline, topics)
+                    .await;
+            } else {
+                let known = self.known_partitions(&topics).await;
 
-                responses.push(self.topic(transaction_id.as_deref(), topic).await)
+                for topic in topics {
+                    debug!(?topic);
+
+                    let partitions =
+                        known
+                            .as_ref()
+                            .map_err(|error_code| *error_code)
+                            .and_then(|known| {
+                                known.get(&topic.name).map_or(
+                                    Err(ErrorCode::UnknownTopicOrPartition),
+                                    |partitions| {
+                                        partitions.as_ref().map_err(|error_code| *error_code)
+                                    },
+                                )
+                            });
+
+                    responses.push(self.topic(acks, deadline, partitions, topic).await)
+                }
             }
         }
 
@@ -152,11 +1013,14 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Error, broker::init_producer_id::InitProducerIdRequest};
+    use crate::{
+        Error,
+        broker::{faulty::Faulty, init_producer_id::InitProducerIdRequest},
+    };
     use bytes::Bytes;
     use object_store::memory::InMemory;
     use tansu_kafka_sans_io::{
-        ErrorCode,
+        BatchAttribute, Compression, ErrorCode,
         record::{
             Record,
             deflated::{self, Frame},
@@ -209,64 +1073,579 @@ mod tests {
                     }),
                 };
 
-                Some(vec![TopicProduceData {
-                    name: topic.into(),
-                    partition_data: Some(vec![partition_data]),
-                }])
-            })
-            .map_err(Into::into)
+                Some(vec![TopicProduceData {
+                    name: topic.into(),
+                    partition_data: Some(vec![partition_data]),
+                }])
+            })
+            .map_err(Into::into)
+    }
+
+    async fn create_topic(storage: &mut DynoStore, topic: &str) -> Result<()> {
+        use tansu_kafka_sans_io::create_topics_request::CreatableTopic;
+
+        storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await
+            .map(|_| ())
+            .map_err(Into::into)
+    }
+
+    #[tokio::test]
+    async fn unknown_topic_or_partition() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let topic = "pqr";
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+        create_topic(&mut storage, topic).await?;
+
+        let transactional_id = None;
+        let acks = 0;
+        let timeout_ms = 0;
+
+        let unknown = |name: &str, index| TopicProduceResponse {
+            name: name.into(),
+            partition_responses: Some(vec![PartitionProduceResponse {
+                index,
+                error_code: ErrorCode::UnknownTopicOrPartition.into(),
+                base_offset: -1,
+                log_append_time_ms: Some(-1),
+                log_start_offset: Some(0),
+                record_errors: Some(vec![]),
+                error_message: None,
+                current_leader: None,
+            }]),
+        };
+
+        // a typo in the topic name
+        assert_eq!(
+            ProduceResponse {
+                responses: Some(vec![unknown("pqrs", 0)]),
+                throttle_time_ms: Some(0),
+                node_endpoints: None
+            },
+            ProduceRequest::with_storage(storage.clone())
+                .response(
+                    transactional_id.clone(),
+                    acks,
+                    timeout_ms,
+                    topic_data(
+                        "pqrs",
+                        0,
+                        inflated::Batch::builder()
+                            .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+                    )?
+                )
+                .await?
+        );
+
+        // a partition beyond those of the topic
+        assert_eq!(
+            ProduceResponse {
+                responses: Some(vec![unknown(topic, 1)]),
+                throttle_time_ms: Some(0),
+                node_endpoints: None
+            },
+            ProduceRequest::with_storage(storage)
+                .response(
+                    transactional_id,
+                    acks,
+                    timeout_ms,
+                    topic_data(
+                        topic,
+                        1,
+                        inflated::Batch::builder()
+                            .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+                    )?
+                )
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn non_txn_idempotent_unknown_producer_id() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let topic = "pqr";
+        let index = 0;
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+        create_topic(&mut storage, topic).await?;
+
+        let transactional_id = None;
+        let acks = 0;
+        let timeout_ms = 0;
+
+        assert_eq!(
+            ProduceResponse {
+                responses: Some(vec![TopicProduceResponse {
+                    name: topic.into(),
+                    partition_responses: Some(vec![PartitionProduceResponse {
+                        index,
+                        error_code: ErrorCode::UnknownProducerId.into(),
+                        base_offset: -1,
+                        log_append_time_ms: Some(-1),
+                        log_start_offset: Some(0),
+                        record_errors: Some(vec![]),
+                        error_message: None,
+                        current_leader: None,
+                    }],),
+                }]),
+                throttle_time_ms: Some(0),
+                node_endpoints: None
+            },
+            ProduceRequest::with_storage(storage)
+                .response(
+                    transactional_id,
+                    acks,
+                    timeout_ms,
+                    topic_data(
+                        topic,
+                        index,
+                        inflated::Batch::builder()
+                            .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+                            .producer_id(54345)
+                    )?
+                )
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_list_too_large() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let topic = "pqr";
+        let index = 0;
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+        create_topic(&mut storage, topic).await?;
+
+        let transactional_id = None;
+        let acks = 0;
+        let timeout_ms = 0;
+
+        assert_eq!(
+            ProduceResponse {
+                responses: Some(vec![TopicProduceResponse {
+                    name: topic.into(),
+                    partition_responses: Some(vec![PartitionProduceResponse {
+                        index,
+                        error_code: ErrorCode::RecordListTooLarge.into(),
+                        base_offset: -1,
+                        log_append_time_ms: Some(-1),
+                        log_start_offset: Some(0),
+                        record_errors: Some(vec![]),
+                        error_message: None,
+                        current_leader: None,
+                    }],),
+                }]),
+                throttle_time_ms: Some(0),
+                node_endpoints: None
+            },
+            ProduceRequest::with_storage(storage)
+                .record_limit(Limit::default().max_inflated_bytes(Some(65_536)))
+                .response(
+                    transactional_id,
+                    acks,
+                    timeout_ms,
+                    topic_data(
+                        topic,
+                        index,
+                        inflated::Batch::builder()
+                            .attributes(
+                                BatchAttribute::default()
+                                    .compression(Compression::Zstd)
+                                    .into()
+                            )
+                            .record(Record::builder().value(vec![0u8; 1_048_576].into()))
+                    )?
+                )
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn not_enough_replicas() -> Result<()> {
+        use tansu_kafka_sans_io::create_topics_request::{CreatableTopic, CreatableTopicConfig};
+
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let topic = "pqr";
+        let index = 0;
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some(vec![CreatableTopicConfig {
+                        name: MIN_INSYNC_REPLICAS.into(),
+                        value: Some("2".into()),
+                    }]),
+                },
+                false,
+            )
+            .await?;
+
+        let mut request = ProduceRequest::with_storage(storage);
+
+        let produce = |acks| {
+            let mut request = request.clone();
+
+            async move {
+                request
+                    .response(
+                        None,
+                        acks,
+                        0,
+                        topic_data(
+                            topic,
+                            index,
+                            inflated::Batch::builder().record(
+                                Record::builder().value(Bytes::from_static(b"lorem").into()),
+                            ),
+                        )?,
+                    )
+                    .await
+            }
+        };
+
+        let error_codes = |response: ProduceResponse| {
+            response
+                .responses
+                .unwrap_or_default()
+                .into_iter()
+                .flat_map(|topic| topic.partition_responses.unwrap_or_default())
+                .map(|partition| ErrorCode::try_from(partition.error_code))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        // a single replica is available
+        assert_eq!(
+            vec![ErrorCode::NotEnoughReplicas],
+            error_codes(produce(ACKS_ALL).await?)?
+        );
+
+        // only the leader needs to acknowledge
+        assert_eq!(vec![ErrorCode::None], error_codes(produce(1).await?)?);
+
+        // nothing was appended by the acks=all produce
+        assert_eq!(
+            (0, 1),
+            request
+                .storage
+                .watermarks(&Topition::new(topic, index))
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_timestamp() -> Result<()> {
+        use std::time::SystemTime;
+        use tansu_kafka_sans_io::create_topics_request::{CreatableTopic, CreatableTopicConfig};
+        use tansu_storage::clock::ManualClock;
+
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let topic = "pqr";
+        let index = 0;
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some(vec![CreatableTopicConfig {
+                        name: MESSAGE_TIMESTAMP_DIFFERENCE_MAX_MS.into(),
+                        value: Some("3600000".into()),
+                    }]),
+                },
+                false,
+            )
+            .await?;
+
+        let clock = ManualClock::default();
+        let now = to_timestamp(clock.now())?;
+
+        let request = ProduceRequest::with_storage(storage).clock(Arc::new(clock));
+
+        let produce = |base_timestamp: i64, max_timestamp: i64, timestamp_delta: i64| {
+            let mut request = request.clone();
+
+            async move {
+                request
+                    .response(
+                        None,
+                        1,
+                        0,
+                        topic_data(
+                            topic,
+                            index,
+                            inflated::Batch::builder()
+                                .base_timestamp(base_timestamp)
+                                .max_timestamp(max_timestamp)
+                                .record(
+                                    Record::builder()
+                                        .timestamp_delta(timestamp_delta)
+                                        .value(Bytes::from_static(b"lorem").into()),
+                                ),
+                        )?,
+                    )
+                    .await
+                    .and_then(|response| {
+                        response
+                            .responses
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flat_map(|topic| topic.partition_responses.unwrap_or_default())
+                            .map(|partition| ErrorCode::try_from(partition.error_code))
+                            .collect::<Result<Vec<_>, _>>()
+                            .map_err(Into::into)
+                    })
+            }
+        };
+
+        let an_hour = 3_600_000;
+
+        assert_eq!(vec![ErrorCode::None], produce(now, now, 0).await?);
+
+        assert_eq!(
+            vec![ErrorCode::None],
+            produce(now - an_hour, now - an_hour, 0).await?
+        );
+
+        // a wildly future timestamp
+        let future = to_timestamp(SystemTime::now() + Duration::from_secs(365 * 86_400))?;
+        assert_eq!(
+            vec![ErrorCode::InvalidTimestamp],
+            produce(future, future, 0).await?
+        );
+
+        assert_eq!(
+            vec![ErrorCode::InvalidTimestamp],
+            produce(now - an_hour - 1, now - an_hour - 1, 0).await?
+        );
+
+        // a record beyond the maximum timestamp of its batch
+        assert_eq!(
+            vec![ErrorCode::InvalidTimestamp],
+            produce(now, now, 1).await?
+        );
+
+        // a record before the base timestamp of its batch
+        assert_eq!(
+            vec![ErrorCode::InvalidTimestamp],
+            produce(now, now, -1).await?
+        );
+
+        // only the valid batches were appended
+        assert_eq!(
+            (0, 2),
+            request
+                .storage
+                .clone()
+                .watermarks(&Topition::new(topic, index))
+                .await?
+        );
+
+        Ok(())
     }
 
     #[tokio::test]
-    async fn non_txn_idempotent_unknown_producer_id() -> Result<()> {
+    async fn dead_letter() -> Result<()> {
+        use std::time::SystemTime;
+        use tansu_kafka_sans_io::{
+            IsolationLevel,
+            create_topics_request::{CreatableTopic, CreatableTopicConfig},
+        };
+
         let _guard = init_tracing()?;
 
         let cluster = "abc";
         let node = 12321;
 
         let topic = "pqr";
+        let dead_letter = "pqr-dlq";
         let index = 0;
 
-        let storage = DynoStore::new(cluster, node, InMemory::new());
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
 
-        let transactional_id = None;
-        let acks = 0;
-        let timeout_ms = 0;
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some(vec![
+                        CreatableTopicConfig {
+                            name: MESSAGE_TIMESTAMP_DIFFERENCE_MAX_MS.into(),
+                            value: Some("3600000".into()),
+                        },
+                        CreatableTopicConfig {
+                            name: DEAD_LETTER_TOPIC.into(),
+                            value: Some(dead_letter.into()),
+                        },
+                    ]),
+                },
+                false,
+            )
+            .await?;
+
+        create_topic(&mut storage, dead_letter).await?;
+
+        let mut request = ProduceRequest::with_storage(storage);
+
+        // a wildly future timestamp
+        let future = to_timestamp(SystemTime::now() + Duration::from_secs(365 * 86_400))?;
+
+        let response = request
+            .response(
+                None,
+                1,
+                0,
+                topic_data(
+                    topic,
+                    index,
+                    inflated::Batch::builder()
+                        .base_timestamp(future)
+                        .max_timestamp(future)
+                        .record(
+                            Record::builder()
+                                .key(Bytes::from_static(b"lorem").into())
+                                .value(Bytes::from_static(b"ipsum").into()),
+                        ),
+                )?,
+            )
+            .await?;
+
+        let partition = response
+            .responses
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|topic| topic.partition_responses.unwrap_or_default())
+            .next()
+            .ok_or(Error::Message(String::from("no partition response")))?;
 
+        // refused, with a copy kept in the dead letter topic
+        assert_eq!(i16::from(ErrorCode::InvalidTimestamp), partition.error_code);
+        assert_eq!(-1, partition.base_offset);
+
+        // the main topic is unchanged
         assert_eq!(
-            ProduceResponse {
-                responses: Some(vec![TopicProduceResponse {
-                    name: topic.into(),
-                    partition_responses: Some(vec![PartitionProduceResponse {
-                        index,
-                        error_code: ErrorCode::UnknownProducerId.into(),
-                        base_offset: -1,
-                        log_append_time_ms: Some(-1),
-                        log_start_offset: Some(0),
-                        record_errors: Some(vec![]),
-                        error_message: None,
-                        current_leader: None,
-                    }],),
-                }]),
-                throttle_time_ms: Some(0),
-                node_endpoints: None
-            },
-            ProduceRequest::with_storage(storage)
-                .response(
-                    transactional_id,
-                    acks,
-                    timeout_ms,
-                    topic_data(
-                        topic,
-                        index,
-                        inflated::Batch::builder()
-                            .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
-                            .producer_id(54345)
-                    )?
-                )
+            (0, 0),
+            request
+                .storage
+                .watermarks(&Topition::new(topic, index))
                 .await?
         );
 
+        let quarantined = Topition::new(dead_letter, 0);
+        assert_eq!((0, 1), request.storage.watermarks(&quarantined).await?);
+
+        let batches = request
+            .storage
+            .fetch(
+                &quarantined,
+                0,
+                0,
+                1_024 * 1_024,
+                IsolationLevel::ReadUncommitted,
+            )
+            .await?;
+        assert_eq!(1, batches.len());
+
+        let batch = inflated::Batch::try_from(&batches[0])?;
+        assert_eq!(-1, batch.producer_id);
+        assert_eq!(1, batch.records.len());
+
+        let record = &batch.records[0];
+        assert_eq!(Some(Bytes::from_static(b"lorem")), record.key);
+        assert_eq!(Some(Bytes::from_static(b"ipsum")), record.value);
+
+        let header = |key: &str| {
+            record
+                .headers
+                .iter()
+                .find(|header| header.key.as_deref() == Some(key.as_bytes()))
+                .and_then(|header| header.value.clone())
+        };
+
+        assert_eq!(
+            Some(Bytes::from_static(b"InvalidTimestamp")),
+            header(REJECTION_REASON)
+        );
+        assert_eq!(Some(Bytes::from_static(b"pqr")), header(REJECTION_TOPIC));
+        assert_eq!(Some(Bytes::from_static(b"0")), header(REJECTION_PARTITION));
+
+        // a valid batch is appended to the main topic as before
+        let response = request
+            .response(
+                None,
+                1,
+                0,
+                topic_data(
+                    topic,
+                    index,
+                    inflated::Batch::builder()
+                        .record(Record::builder().value(Bytes::from_static(b"dolor").into())),
+                )?,
+            )
+            .await?;
+
+        assert_eq!(
+            Some(0),
+            response
+                .responses
+                .unwrap_or_default()
+                .into_iter()
+                .flat_map(|topic| topic.partition_responses.unwrap_or_default())
+                .map(|partition| partition.base_offset)
+                .next()
+        );
+
         Ok(())
     }
 
@@ -279,7 +1658,8 @@ mod tests {
         let topic = "pqr";
         let index = 0;
 
-        let storage = DynoStore::new(cluster, node, InMemory::new());
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+        create_topic(&mut storage, topic).await?;
 
         let producer = InitProducerIdRequest::with_storage(storage.clone())
             .response(None, 0, Some(-1), Some(-1))
@@ -423,7 +1803,8 @@ mod tests {
         let topic = "pqr";
         let index = 0;
 
-        let storage = DynoStore::new(cluster, node, InMemory::new());
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+        create_topic(&mut storage, topic).await?;
 
         let producer = InitProducerIdRequest::with_storage(storage.clone())
             .response(None, 0, Some(-1), Some(-1))
@@ -523,7 +1904,8 @@ mod tests {
         let topic = "pqr";
         let index = 0;
 
-        let storage = DynoStore::new(cluster, node, InMemory::new());
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+        create_topic(&mut storage, topic).await?;
 
         let producer = InitProducerIdRequest::with_storage(storage.clone())
             .response(None, 0, Some(-1), Some(-1))
@@ -614,4 +1996,376 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn storage_unavailable() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let topic = "pqr";
+        let index = 0;
+
+        let storage = DynoStore::new(cluster, node, Faulty::default().unavailable());
+
+        let transactional_id = None;
+        let acks = 0;
+        let timeout_ms = 0;
+
+        assert_eq!(
+            ProduceResponse {
+                responses: Some(vec![TopicProduceResponse {
+                    name: topic.into(),
+                    partition_responses: Some(vec![PartitionProduceResponse {
+                        index,
+                        error_code: ErrorCode::KafkaStorageError.into(),
+                        base_offset: -1,
+                        log_append_time_ms: Some(-1),
+                        log_start_offset: Some(0),
+                        record_errors: Some(vec![]),
+                        error_message: None,
+                        current_leader: None,
+                    }],),
+                }]),
+                throttle_time_ms: Some(0),
+                node_endpoints: None
+            },
+            ProduceRequest::with_storage(storage)
+                .response(
+                    transactional_id,
+                    acks,
+                    timeout_ms,
+                    topic_data(
+                        topic,
+                        index,
+                        inflated::Batch::builder().record(
+                            Record::builder()
+                                .value(Bytes::from_static(b"Lorem ipsum dolor sit amet").into())
+                        )
+                    )?
+                )
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn acks_all_timed_out() -> Result<()> {
+        use tansu_kafka_sans_io::create_topics_request::CreatableTopic;
+
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        let topic = "pqr";
+
+        let object_store = Faulty::default().put_delay(Duration::from_millis(300));
+        let mut storage = DynoStore::new(cluster, node, object_store.clone());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 2,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let partition_data = |index| {
+            inflated::Batch::builder()
+                .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+                .build()
+                .and_then(deflated::Batch::try_from)
+                .map(|batch| PartitionProduceData {
+                    index,
+                    records: Some(Frame {
+                        batches: vec![batch],
+                    }),
+                })
+        };
+
+        let timeout_ms = 250;
+        let puts = object_store.puts();
+
+        let response = ProduceRequest::with_storage(storage.clone())
+            .response(
+                None,
+                ACKS_ALL,
+                timeout_ms,
+                Some(vec![TopicProduceData {
+                    name: topic.into(),
+                    partition_data: Some(vec![partition_data(0)?, partition_data(1)?]),
+                }]),
+            )
+            .await?;
+
+        let timed_out = |index| PartitionProduceResponse {
+            index,
+            error_code: ErrorCode::RequestTimedOut.into(),
+            base_offset: -1,
+            log_append_time_ms: Some(-1),
+            log_start_offset: Some(0),
+            record_errors: Some(vec![]),
+            error_message: None,
+            current_leader: None,
+        };
+
+        assert_eq!(
+            ProduceResponse {
+                responses: Some(vec![TopicProduceResponse {
+                    name: topic.into(),
+                    partition_responses: Some(vec![timed_out(0), timed_out(1)]),
+                }]),
+                throttle_time_ms: Some(0),
+                node_endpoints: None
+            },
+            response
+        );
+
+        // the append to the first partition ran to completion, while the
+        // second partition was not attempted after the deadline
+        assert!(object_store.puts() > puts);
+
+        assert_eq!(
+            1,
+            storage
+                .offset_stage(&Topition::new(topic, 0))
+                .await?
+                .high_watermark()
+        );
+
+        assert_eq!(
+            0,
+            storage
+                .offset_stage(&Topition::new(topic, 1))
+                .await?
+                .high_watermark()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn transactional_acks() -> Result<()> {
+        use tansu_kafka_sans_io::add_partitions_to_txn_request::AddPartitionsToTxnTopic;
+        use tansu_storage::TxnAddPartitionsRequest;
+
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+        let topic = "pqr";
+        let index = 0;
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+        create_topic(&mut storage, topic).await?;
+
+        let transaction_id = "xyz";
+
+        let producer = InitProducerIdRequest::with_storage(storage.clone())
+            .response(Some(transaction_id), 10_000, Some(-1), Some(-1))
+            .await?;
+
+        _ = storage
+            .txn_add_partitions(TxnAddPartitionsRequest::VersionZeroToThree {
+                transaction_id: transaction_id.into(),
+                producer_id: producer.id,
+                producer_epoch: producer.epoch,
+                topics: [AddPartitionsToTxnTopic {
+                    name: topic.into(),
+                    partitions: Some([index].into()),
+                }]
+                .into(),
+            })
+            .await?;
+
+        let batch = || {
+            topic_data(
+                topic,
+                index,
+                inflated::Batch::builder()
+                    .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+                    .attributes(BatchAttribute::default().transaction(true).into())
+                    .producer_id(producer.id)
+                    .producer_epoch(producer.epoch),
+            )
+        };
+
+        let response = |error_code: ErrorCode, base_offset| ProduceResponse {
+            responses: Some(vec![TopicProduceResponse {
+                name: topic.into(),
+                partition_responses: Some(vec![PartitionProduceResponse {
+                    index,
+                    error_code: error_code.into(),
+                    base_offset,
+                    log_append_time_ms: Some(-1),
+                    log_start_offset: Some(0),
+                    record_errors: Some(vec![]),
+                    error_message: None,
+                    current_leader: None,
+                }]),
+            }]),
+            throttle_time_ms: Some(0),
+            node_endpoints: None,
+        };
+
+        let mut request = ProduceRequest::with_storage(storage.clone());
+
+        assert_eq!(
+            response(ErrorCode::InvalidRequiredAcks, -1),
+            request
+                .response(Some(transaction_id.into()), 1, 5_000, batch()?)
+                .await?
+        );
+
+        assert_eq!(
+            response(ErrorCode::None, 0),
+            request
+                .response(Some(transaction_id.into()), ACKS_ALL, 5_000, batch()?)
+                .await?
+        );
+
+        // without enforcement, any acks are accepted
+        assert_eq!(
+            response(ErrorCode::None, 1),
+            ProduceRequest::with_storage(storage)
+                .transactional_acks_all(false)
+                .response(
+                    Some(transaction_id.into()),
+                    1,
+                    5_000,
+                    topic_data(
+                        topic,
+                        index,
+                        inflated::Batch::builder()
+                            .record(Record::builder().value(Bytes::from_static(b"ipsum").into()))
+                            .attributes(BatchAttribute::default().transaction(true).into())
+                            .base_sequence(1)
+                            .producer_id(producer.id)
+                            .producer_epoch(producer.epoch),
+                    )?
+                )
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tee() -> Result<()> {
+        use base64::prelude::*;
+        use serde_json::{Value, json};
+        use tansu_kafka_sans_io::record::header;
+
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+        let topic = "pqr";
+        let index = 0;
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+        create_topic(&mut storage, topic).await?;
+
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("produced.jsonl");
+
+        let response = ProduceRequest::with_storage(storage)
+            .tee(Some(Tee::open(&path)?))
+            .response(
+                None,
+                0,
+                0,
+                topic_data(
+                    topic,
+                    index,
+                    inflated::Batch::builder()
+                        .base_timestamp(1_000)
+                        .record(
+                            Record::builder()
+                                .key(Bytes::from_static(b"k0").into())
+                                .value(Bytes::from_static(b"v0").into())
+                                .header(
+                                    header::Header::builder()
+                                        .key(b"h".into())
+                                        .value(b"x".into()),
+                                ),
+                        )
+                        .record(
+                            Record::builder()
+                                .offset_delta(1)
+                                .timestamp_delta(1)
+                                .value(Bytes::from_static(b"v1").into()),
+                        )
+                        .record(
+                            Record::builder()
+                                .offset_delta(2)
+                                .timestamp_delta(2)
+                                .key(Bytes::from_static(b"k2").into()),
+                        )
+                        .last_offset_delta(2),
+                )?,
+            )
+            .await?;
+
+        assert_eq!(
+            Some(ErrorCode::None.into()),
+            response
+                .responses
+                .as_deref()
+                .and_then(|responses| responses.first())
+                .and_then(|response| response.partition_responses.as_deref())
+                .and_then(|partitions| partitions.first())
+                .map(|partition| partition.error_code)
+        );
+
+        let lines = std::fs::read_to_string(&path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+
+        let encoded = |octets: &[u8]| Value::String(BASE64_STANDARD.encode(octets));
+
+        assert_eq!(
+            vec![
+                json!({
+                    "topic": topic,
+                    "partition": index,
+                    "offset": 0,
+                    "timestamp": 1_000,
+                    "key": encoded(b"k0"),
+                    "value": encoded(b"v0"),
+                    "headers": [{"key": encoded(b"h"), "value": encoded(b"x")}],
+                }),
+                json!({
+                    "topic": topic,
+                    "partition": index,
+                    "offset": 1,
+                    "timestamp": 1_001,
+                    "key": null,
+                    "value": encoded(b"v1"),
+                    "headers": [],
+                }),
+                json!({
+                    "topic": topic,
+                    "partition": index,
+                    "offset": 2,
+                    "timestamp": 1_002,
+                    "key": encoded(b"k2"),
+                    "value": null,
+                    "headers": [],
+                }),
+            ],
+            lines
+        );
+
+        Ok(())
+    }
 }
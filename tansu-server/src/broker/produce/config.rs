@@ -0,0 +1,163 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+use tansu_kafka_sans_io::describe_configs_response::DescribeConfigsResourceResult;
+
+// how long the configuration of a topic is used before being described again
+pub const DEFAULT_TOPIC_CONFIG_TTL: Duration = Duration::from_secs(30);
+
+pub(crate) type TopicConfig = Arc<[DescribeConfigsResourceResult]>;
+
+#[derive(Debug, Default)]
+struct Entries {
+    generation: u64,
+    topics: BTreeMap<String, (SystemTime, TopicConfig)>,
+}
+
+// a cached configuration, or the generation that a configuration
+// described now from storage may be cached under
+#[derive(Clone, Debug)]
+pub(crate) enum Lookup {
+    Hit(TopicConfig),
+    Miss(u64),
+}
+
+/// The configuration of each topic that is used by produce, shared by
+/// every connection for up to a TTL. Invalidated when a topic is altered,
+/// created or deleted on this broker. Disabled without a TTL
+#[derive(Clone, Debug)]
+pub struct TopicConfigCache {
+    ttl: Option<Duration>,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl Default for TopicConfigCache {
+    fn default() -> Self {
+        Self {
+            ttl: Some(DEFAULT_TOPIC_CONFIG_TTL),
+            entries: Arc::new(Mutex::new(Entries::default())),
+        }
+    }
+}
+
+impl TopicConfigCache {
+    pub fn ttl(self, ttl: Option<Duration>) -> Self {
+        Self { ttl, ..self }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn get(&self, name: &str, now: SystemTime) -> Lookup {
+        let entries = self.lock();
+
+        match (self.ttl, entries.topics.get(name)) {
+            (Some(ttl), Some((described, config)))
+                if now.duration_since(*described).unwrap_or_default() < ttl =>
+            {
+                Lookup::Hit(config.clone())
+            }
+
+            _ => Lookup::Miss(entries.generation),
+        }
+    }
+
+    // a configuration described before an invalidation is not cached, as
+    // it may predate the change
+    pub(crate) fn put(&self, generation: u64, name: &str, now: SystemTime, config: TopicConfig) {
+        if self.ttl.is_some() {
+            let mut entries = self.lock();
+
+            if entries.generation == generation {
+                _ = entries.topics.insert(name.to_owned(), (now, config));
+            }
+        }
+    }
+
+    pub(crate) fn invalidate(&self, name: &str) {
+        let mut entries = self.lock();
+        entries.generation += 1;
+        _ = entries.topics.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(value: &str) -> TopicConfig {
+        Arc::from(vec![DescribeConfigsResourceResult {
+            name: "compression.type".into(),
+            value: Some(value.into()),
+            read_only: false,
+            is_default: Some(false),
+            config_source: Some(1),
+            is_sensitive: false,
+            synonyms: Some([].into()),
+            config_type: Some(2),
+            documentation: None,
+        }])
+    }
+
+    #[test]
+    fn cached_until_expired_or_invalidated() {
+        let cache = TopicConfigCache::default().ttl(Some(Duration::from_secs(5)));
+        let now = SystemTime::now();
+
+        let Lookup::Miss(generation) = cache.get("pqr", now) else {
+            panic!("cold cache")
+        };
+
+        cache.put(generation, "pqr", now, config("gzip"));
+
+        assert!(
+            matches!(cache.get("pqr", now), Lookup::Hit(config) if config[0].value.as_deref() == Some("gzip"))
+        );
+        assert!(matches!(cache.get("abc", now), Lookup::Miss(_)));
+        assert!(matches!(
+            cache.get("pqr", now + Duration::from_secs(5)),
+            Lookup::Miss(_)
+        ));
+
+        cache.invalidate("pqr");
+        assert!(matches!(cache.get("pqr", now), Lookup::Miss(_)));
+
+        // described before the invalidation
+        cache.put(generation, "pqr", now, config("zstd"));
+        assert!(matches!(cache.get("pqr", now), Lookup::Miss(_)));
+    }
+
+    #[test]
+    fn disabled_without_ttl() {
+        let cache = TopicConfigCache::default().ttl(None);
+        let now = SystemTime::now();
+
+        let Lookup::Miss(generation) = cache.get("pqr", now) else {
+            panic!("cold cache")
+        };
+
+        cache.put(generation, "pqr", now, config("gzip"));
+        assert!(matches!(cache.get("pqr", now), Lookup::Miss(_)));
+    }
+}
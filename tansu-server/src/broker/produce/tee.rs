@@ -0,0 +1,107 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::Result;
+use base64::prelude::*;
+use bytes::Bytes;
+use serde_json::{Value, json};
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tansu_kafka_sans_io::{
+    BatchAttribute,
+    record::{Record, deflated},
+};
+use tansu_storage::Topition;
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
+
+// writes each produced record as a line of JSON, for local development
+#[derive(Clone, Debug)]
+pub struct Tee {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+fn encode(octets: Option<&Bytes>) -> Value {
+    octets.map_or(Value::Null, |octets| {
+        Value::String(BASE64_STANDARD.encode(octets))
+    })
+}
+
+impl Tee {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map(|file| Self {
+                path,
+                file: Arc::new(Mutex::new(File::from_std(file))),
+            })
+            .map_err(Into::into)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn write(
+        &self,
+        topition: &Topition,
+        base_offset: i64,
+        batch: &deflated::Batch,
+    ) -> Result<()> {
+        if BatchAttribute::try_from(batch.attributes).is_ok_and(|attributes| attributes.control) {
+            return Ok(());
+        }
+
+        let mut lines = vec![];
+
+        for record in Vec::<Record>::try_from(batch)? {
+            serde_json::to_writer(
+                &mut lines,
+                &json!({
+                    "topic": topition.topic(),
+                    "partition": topition.partition(),
+                    "offset": record.offset(base_offset),
+                    "timestamp": record.timestamp(batch.base_timestamp),
+                    "key": encode(record.key.as_ref()),
+                    "value": encode(record.value.as_ref()),
+                    "headers": record
+                        .headers
+                        .iter()
+                        .map(|header| {
+                            json!({
+                                "key": encode(header.key.as_ref()),
+                                "value": encode(header.value.as_ref()),
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                }),
+            )?;
+
+            lines.push(b'\n');
+        }
+
+        // a batch is written in one go, keeping its lines together
+        let mut file = self.file.lock().await;
+        file.write_all(&lines).await?;
+        file.flush().await.map_err(Into::into)
+    }
+}
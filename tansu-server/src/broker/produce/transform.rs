@@ -0,0 +1,220 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+
+use bytes::Bytes;
+use regex::bytes::{NoExpand, Regex};
+use tansu_kafka_sans_io::{
+    ErrorCode,
+    record::{
+        deflated::{self, Limit},
+        inflated,
+    },
+};
+use tracing::warn;
+
+/// Applied to the value of each produced record, returning the value
+/// that is stored. A failing transform rejects the record
+pub trait Transform: Debug + Send + Sync {
+    fn transform(&self, value: Bytes) -> Result<Bytes, String> {
+        Ok(value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Identity;
+
+impl Transform for Identity {}
+
+/// Replaces each match of a pattern in a value with a mask, e.g., to
+/// redact PII before it is stored
+#[derive(Clone, Debug)]
+pub struct Redact {
+    pattern: Regex,
+    mask: Bytes,
+}
+
+impl Redact {
+    pub fn new(pattern: Regex) -> Self {
+        Self {
+            pattern,
+            mask: Bytes::from_static(b"***"),
+        }
+    }
+
+    pub fn mask(self, mask: Bytes) -> Self {
+        Self { mask, ..self }
+    }
+}
+
+impl Transform for Redact {
+    fn transform(&self, value: Bytes) -> Result<Bytes, String> {
+        Ok(Bytes::from(
+            self.pattern
+                .replace_all(&value[..], NoExpand(&self.mask[..]))
+                .into_owned(),
+        ))
+    }
+}
+
+/// The transform used for each topic, topics without a transform
+/// store their values unchanged
+#[derive(Clone, Debug, Default)]
+pub struct Transforms(BTreeMap<String, Arc<dyn Transform>>);
+
+impl Transforms {
+    pub fn topic(self, name: &str, transform: Arc<dyn Transform>) -> Self {
+        let mut transforms = self.0;
+        _ = transforms.insert(name.to_owned(), transform);
+        Self(transforms)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Transform>> {
+        self.0.get(name)
+    }
+
+    // the batch with each record value transformed, null values are left as
+    // they are. the records are inflated within the same limit as produce
+    pub(crate) fn batch(
+        &self,
+        name: &str,
+        batch: deflated::Batch,
+        limit: Limit,
+    ) -> Result<deflated::Batch, ErrorCode> {
+        let Some(transform) = self.get(name) else {
+            return Ok(batch);
+        };
+
+        let records = batch.records(limit).map_err(|error| {
+            warn!(name, ?error);
+
+            match error {
+                tansu_kafka_sans_io::Error::InflatedSizeExceeded(_)
+                | tansu_kafka_sans_io::Error::RecordCountExceeded(_) => {
+                    ErrorCode::RecordListTooLarge
+                }
+
+                _otherwise => ErrorCode::CorruptMessage,
+            }
+        })?;
+
+        let mut inflated = inflated::Batch {
+            base_offset: batch.base_offset,
+            batch_length: batch.batch_length,
+            partition_leader_epoch: batch.partition_leader_epoch,
+            magic: batch.magic,
+            crc: batch.crc,
+            attributes: batch.attributes,
+            last_offset_delta: batch.last_offset_delta,
+            base_timestamp: batch.base_timestamp,
+            max_timestamp: batch.max_timestamp,
+            producer_id: batch.producer_id,
+            producer_epoch: batch.producer_epoch,
+            base_sequence: batch.base_sequence,
+            records,
+        };
+
+        for record in inflated.records.iter_mut() {
+            if let Some(value) = record.value.take() {
+                record.value = transform.transform(value).map(Some).map_err(|reason| {
+                    warn!(name, record.offset_delta, reason);
+                    ErrorCode::InvalidRecord
+                })?;
+            }
+        }
+
+        inflated
+            .into_builder()
+            .build()
+            .and_then(deflated::Batch::try_from)
+            .map_err(|error| {
+                warn!(name, ?error);
+                ErrorCode::CorruptMessage
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tansu_kafka_sans_io::record::Record;
+
+    fn batch(values: &[&'static [u8]]) -> deflated::Batch {
+        values
+            .iter()
+            .zip(0..)
+            .fold(
+                inflated::Batch::builder(),
+                |builder, (value, offset_delta)| {
+                    builder.last_offset_delta(offset_delta).record(
+                        Record::builder()
+                            .offset_delta(offset_delta)
+                            .value(Bytes::from_static(value).into()),
+                    )
+                },
+            )
+            .build()
+            .and_then(deflated::Batch::try_from)
+            .expect("batch")
+    }
+
+    fn values(batch: deflated::Batch) -> Vec<Option<Bytes>> {
+        batch
+            .records(Limit::default())
+            .expect("records")
+            .into_iter()
+            .map(|record| record.value)
+            .collect()
+    }
+
+    #[test]
+    fn redact() {
+        let transforms = Transforms::default().topic(
+            "pqr",
+            Arc::new(Redact::new(Regex::new(r"\d{3}-\d{4}").expect("pattern"))),
+        );
+
+        let redacted = transforms
+            .batch(
+                "pqr",
+                batch(&[b"call 555-1234 now", b"no number"]),
+                Limit::produce_defaults(),
+            )
+            .expect("redacted");
+
+        assert_eq!(
+            vec![
+                Some(Bytes::from_static(b"call *** now")),
+                Some(Bytes::from_static(b"no number"))
+            ],
+            values(redacted)
+        );
+    }
+
+    #[test]
+    fn within_record_limit() {
+        let transforms = Transforms::default().topic("pqr", Arc::new(Identity));
+
+        assert_eq!(
+            Err(ErrorCode::RecordListTooLarge),
+            transforms.batch(
+                "pqr",
+                batch(&[b"a", b"b", b"c"]),
+                Limit::default().max_record_count(Some(2)),
+            )
+        );
+    }
+}
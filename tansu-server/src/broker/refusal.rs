@@ -0,0 +1,171 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use tansu_kafka_sans_io::{
+    Body, ErrorCode, LIST_OFFSETS_API_KEY, METADATA_API_KEY, OFFSET_COMMIT_API_KEY,
+    PRODUCE_API_KEY,
+    list_offsets_request::ListOffsetsTopic,
+    metadata_request::MetadataRequestTopic,
+    metadata_response::MetadataResponseTopic,
+    offset_commit_response::{OffsetCommitResponsePartition, OffsetCommitResponseTopic},
+    produce_request::{PartitionProduceData, TopicProduceData},
+};
+
+use tansu_storage::NULL_TOPIC_ID;
+
+use crate::{
+    Result,
+    broker::{list_offsets, produce},
+};
+
+/// The topics and partitions of a request, retained so that an error can be
+/// reported against each of them once the request has been consumed. APIs
+/// without a top level error code would otherwise answer with no topics at all.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) enum Refusal {
+    Metadata(Option<Vec<MetadataRequestTopic>>),
+    Produce(Vec<TopicProduceData>),
+    ListOffsets(Option<Vec<ListOffsetsTopic>>),
+    OffsetCommit(Vec<(String, Vec<i32>)>),
+    #[default]
+    Other,
+}
+
+impl From<&Body> for Refusal {
+    fn from(body: &Body) -> Self {
+        match body {
+            Body::MetadataRequest { topics, .. } => Self::Metadata(topics.clone()),
+
+            // only the partitions are kept, without their records
+            Body::ProduceRequest { topic_data, .. } => Self::Produce(
+                topic_data
+                    .as_deref()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|topic| TopicProduceData {
+                        name: topic.name.clone(),
+                        partition_data: topic.partition_data.as_ref().map(|partitions| {
+                            partitions
+                                .iter()
+                                .map(|partition| PartitionProduceData {
+                                    index: partition.index,
+                                    records: None,
+                                })
+                                .collect()
+                        }),
+                    })
+                    .collect(),
+            ),
+
+            Body::ListOffsetsRequest { topics, .. } => Self::ListOffsets(topics.clone()),
+
+            Body::OffsetCommitRequest { topics, .. } => Self::OffsetCommit(
+                topics
+                    .as_deref()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|topic| {
+                        (
+                            topic.name.clone(),
+                            topic
+                                .partitions
+                                .as_deref()
+                                .unwrap_or_default()
+                                .iter()
+                                .map(|partition| partition.partition_index)
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+
+            _otherwise => Self::Other,
+        }
+    }
+}
+
+impl Refusal {
+    // whether an API reports errors only against its topics or partitions,
+    // so that a request that cannot be decoded has nothing to report them on
+    pub(crate) fn is_partitioned(api_key: i16) -> bool {
+        matches!(
+            api_key,
+            PRODUCE_API_KEY | LIST_OFFSETS_API_KEY | METADATA_API_KEY | OFFSET_COMMIT_API_KEY
+        )
+    }
+
+    pub(crate) fn error_response(
+        &self,
+        api_key: i16,
+        api_version: i16,
+        error_code: ErrorCode,
+    ) -> Result<Body> {
+        match self {
+            Self::Metadata(topics) => Ok(Body::MetadataResponse {
+                throttle_time_ms: Some(0),
+                brokers: Some([].into()),
+                cluster_id: None,
+                controller_id: Some(-1),
+                topics: Some(
+                    topics
+                        .as_deref()
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|topic| MetadataResponseTopic {
+                            error_code: error_code.into(),
+                            name: topic.name.clone(),
+                            topic_id: Some(topic.topic_id.unwrap_or(NULL_TOPIC_ID)),
+                            is_internal: Some(false),
+                            partitions: Some([].into()),
+                            topic_authorized_operations: Some(-2147483648),
+                        })
+                        .collect(),
+                ),
+                cluster_authorized_operations: Some(-2147483648),
+            }),
+
+            Self::Produce(topic_data) => Ok(produce::error_response(Some(topic_data), error_code)),
+
+            Self::ListOffsets(topics) => {
+                Ok(list_offsets::error_response(topics.as_deref(), error_code))
+            }
+
+            Self::OffsetCommit(topics) => Ok(Body::OffsetCommitResponse {
+                throttle_time_ms: Some(0),
+                topics: Some(
+                    topics
+                        .iter()
+                        .map(|(name, partitions)| OffsetCommitResponseTopic {
+                            name: name.clone(),
+                            partitions: Some(
+                                partitions
+                                    .iter()
+                                    .map(|partition_index| OffsetCommitResponsePartition {
+                                        partition_index: *partition_index,
+                                        error_code: error_code.into(),
+                                    })
+                                    .collect(),
+                            ),
+                        })
+                        .collect(),
+                ),
+            }),
+
+            Self::Other => {
+                Body::error_response(api_key, api_version, error_code).map_err(Into::into)
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Broker, DEFAULT_MAX_REQUEST_SIZE, read_frame};
+use crate::{Error, Result, coordinator::group::Coordinator};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::Path,
+};
+use tansu_kafka_sans_io::{Frame, Header};
+use tansu_storage::Storage;
+use tokio::{
+    fs::File,
+    io::{AsyncRead, BufReader},
+};
+use tracing::debug;
+
+// a request from a capture, together with the response from the broker
+#[derive(Clone, Debug, Deserialize, PartialEq, PartialOrd, Serialize)]
+pub struct Replayed {
+    pub request: Frame,
+    pub response: Frame,
+}
+
+// replays a capture of length prefixed requests through a broker,
+// reproducing the behaviour of a misbehaving client
+#[derive(Clone, Debug)]
+pub struct Replay<G, S> {
+    broker: Broker<G, S>,
+    peer: SocketAddr,
+}
+
+impl<G, S> Replay<G, S>
+where
+    G: Coordinator,
+    S: Storage + Clone + 'static,
+{
+    pub fn with_broker(broker: Broker<G, S>) -> Self {
+        Self {
+            broker,
+            peer: SocketAddr::from((IpAddr::V4(Ipv4Addr::LOCALHOST), 0)),
+        }
+    }
+
+    pub fn peer(self, peer: SocketAddr) -> Self {
+        Self { peer, ..self }
+    }
+
+    pub async fn file(&mut self, path: impl AsRef<Path>) -> Result<Vec<Replayed>> {
+        let mut reader = File::open(path).await.map(BufReader::new)?;
+        self.frames(&mut reader).await
+    }
+
+    pub async fn frames<R>(&mut self, reader: &mut R) -> Result<Vec<Replayed>>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut replayed = vec![];
+
+        while let Some(frame) = read_frame(reader, DEFAULT_MAX_REQUEST_SIZE).await? {
+            replayed.push(self.request(&frame).await?);
+        }
+
+        Ok(replayed)
+    }
+
+    pub async fn request(&mut self, frame: &Bytes) -> Result<Replayed> {
+        let request = Frame::request_from_bytes(frame)?;
+
+        let Header::Request {
+            api_key,
+            api_version,
+            ..
+        } = request.header
+        else {
+            return Err(Error::Message(format!("not a request: {request:?}")));
+        };
+
+        let response = self
+            .broker
+            .process_request(&self.peer, frame)
+            .await
+            .and_then(|response| {
+                Frame::response_from_bytes(&response, api_key, api_version).map_err(Into::into)
+            })?;
+
+        debug!(?request, ?response);
+
+        Ok(Replayed { request, response })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, coordinator::group::administrator::Controller};
+    use object_store::memory::InMemory;
+    use tansu_kafka_sans_io::{
+        Body, ErrorCode,
+        create_topics_request::CreatableTopic,
+        fetch_request::{FetchPartition, FetchTopic},
+        produce_request::{PartitionProduceData, TopicProduceData},
+        record::{Record, deflated, inflated},
+    };
+    use tansu_storage::{StorageContainer, dynostore::DynoStore};
+    use url::Url;
+    use uuid::Uuid;
+
+    const TOPIC: &str = "abc";
+
+    async fn replay() -> Result<Replay<Controller<StorageContainer>, StorageContainer>> {
+        let cluster_id = "tansu";
+        let node_id = 111;
+
+        let mut storage =
+            StorageContainer::DynoStore(DynoStore::new(cluster_id, node_id, InMemory::new()));
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: TOPIC.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let config = Config::builder()
+            .cluster_id(cluster_id)
+            .node_id(node_id)
+            .listener(Url::parse("tcp://localhost:9092/")?)
+            .advertised_listener(Url::parse("tcp://localhost:9092/")?)
+            .storage(Url::parse("memory://tansu/")?)
+            .build()?;
+
+        Ok(Replay::with_broker(Broker::new(
+            &config,
+            storage.clone(),
+            Controller::with_storage(storage)?,
+            Uuid::nil(),
+        )))
+    }
+
+    fn produce(correlation_id: i32, value: &'static [u8]) -> Result<Vec<u8>> {
+        let batch = inflated::Batch::builder()
+            .record(Record::builder().value(Bytes::from_static(value).into()))
+            .build()
+            .and_then(deflated::Batch::try_from)?;
+
+        Frame::request(
+            Header::Request {
+                api_key: 0,
+                api_version: 9,
+                correlation_id,
+                client_id: Some("capture".into()),
+            },
+            Body::ProduceRequest {
+                transactional_id: None,
+                acks: 1,
+                timeout_ms: 5_000,
+                topic_data: Some(vec![TopicProduceData {
+                    name: TOPIC.into(),
+                    partition_data: Some(vec![PartitionProduceData {
+                        index: 0,
+                        records: Some(deflated::Frame {
+                            batches: vec![batch],
+                        }),
+                    }]),
+                }]),
+            },
+        )
+        .map_err(Into::into)
+    }
+
+    fn fetch(correlation_id: i32) -> Result<Vec<u8>> {
+        Frame::request(
+            Header::Request {
+                api_key: 1,
+                api_version: 12,
+                correlation_id,
+                client_id: Some("capture".into()),
+            },
+            Body::FetchRequest {
+                cluster_id: None,
+                replica_id: Some(-1),
+                replica_state: None,
+                max_wait_ms: 100,
+                min_bytes: 1,
+                max_bytes: Some(50 * 1024),
+                isolation_level: Some(0),
+                session_id: Some(0),
+                session_epoch: Some(-1),
+                topics: Some(vec![FetchTopic {
+                    topic: Some(TOPIC.into()),
+                    topic_id: None,
+                    partitions: Some(vec![FetchPartition {
+                        partition: 0,
+                        current_leader_epoch: Some(-1),
+                        fetch_offset: 0,
+                        last_fetched_epoch: Some(-1),
+                        log_start_offset: Some(-1),
+                        partition_max_bytes: 50 * 1024,
+                        replica_directory_id: None,
+                    }]),
+                }]),
+                forgotten_topics_data: Some([].into()),
+                rack_id: Some("".into()),
+            },
+        )
+        .map_err(Into::into)
+    }
+
+    #[tokio::test]
+    async fn produce_then_fetch() -> Result<()> {
+        let value = b"Lorem ipsum dolor sit amet";
+
+        let capture = [produce(6, value)?, fetch(7)?].concat();
+
+        let replayed = replay().await?.frames(&mut &capture[..]).await?;
+        assert_eq!(2, replayed.len());
+
+        assert!(matches!(
+            replayed[0].request,
+            Frame {
+                header: Header::Request {
+                    api_key: 0,
+                    correlation_id: 6,
+                    ..
+                },
+                body: Body::ProduceRequest { .. },
+                ..
+            }
+        ));
+
+        let Frame {
+            header: Header::Response { correlation_id: 6 },
+            body:
+                Body::ProduceResponse {
+                    responses: Some(ref responses),
+                    ..
+                },
+            ..
+        } = replayed[0].response
+        else {
+            panic!("{:?}", replayed[0].response)
+        };
+
+        let partitions = responses[0]
+            .partition_responses
+            .as_deref()
+            .unwrap_or_default();
+        assert_eq!(i16::from(ErrorCode::None), partitions[0].error_code);
+        assert_eq!(0, partitions[0].base_offset);
+
+        assert!(matches!(
+            replayed[1].request,
+            Frame {
+                header: Header::Request {
+                    api_key: 1,
+                    correlation_id: 7,
+                    ..
+                },
+                body: Body::FetchRequest { .. },
+                ..
+            }
+        ));
+
+        let Frame {
+            header: Header::Response { correlation_id: 7 },
+            body:
+                Body::FetchResponse {
+                    responses: Some(ref responses),
+                    ..
+                },
+            ..
+        } = replayed[1].response
+        else {
+            panic!("{:?}", replayed[1].response)
+        };
+
+        let records = responses[0]
+            .partitions
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|partition| partition.records.as_ref())
+            .flat_map(|frame| frame.batches.iter())
+            .map(|batch| inflated::Batch::try_from(batch).map_err(Into::into))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flat_map(|batch| batch.records)
+            .map(|record| record.value)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![Some(Bytes::from_static(value))], records);
+
+        Ok(())
+    }
+}
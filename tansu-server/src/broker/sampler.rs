@@ -0,0 +1,125 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Chooses the requests that have their full request and response bodies
+/// logged at debug, keeping debugging feasible under load. A request that
+/// fails is always logged in full. Every request is sampled by default
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    rate: f64,
+    per_second: Option<u32>,
+    requests: Arc<AtomicU64>,
+    window: Arc<Mutex<(Instant, u32)>>,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            per_second: None,
+            requests: Arc::new(AtomicU64::new(0)),
+            window: Arc::new(Mutex::new((Instant::now(), 0))),
+        }
+    }
+}
+
+impl Sampler {
+    // the fraction of requests that are sampled, between 0 and 1
+    pub fn rate(self, rate: f64) -> Self {
+        Self { rate, ..self }
+    }
+
+    // at most the first per_second sampled requests in each second
+    pub fn per_second(self, per_second: Option<u32>) -> Self {
+        Self { per_second, ..self }
+    }
+
+    // requests are sampled evenly rather than at random, so that exactly
+    // rate of every n requests are sampled
+    fn by_rate(&self) -> bool {
+        if self.rate >= 1.0 {
+            return true;
+        }
+
+        let n = self.requests.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.rate).floor() > (n * self.rate).floor()
+    }
+
+    fn by_window(&self) -> bool {
+        let Some(per_second) = self.per_second else {
+            return true;
+        };
+
+        self.window
+            .lock()
+            .map(|mut window| {
+                if window.0.elapsed() >= WINDOW {
+                    *window = (Instant::now(), 0);
+                }
+
+                if window.1 < per_second {
+                    window.1 += 1;
+                    true
+                } else {
+                    false
+                }
+            })
+            .unwrap_or(true)
+    }
+
+    pub fn sample(&self) -> bool {
+        self.by_rate() && self.by_window()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn everything_by_default() {
+        let sampler = Sampler::default();
+        assert!((0..100).all(|_| sampler.sample()));
+    }
+
+    #[test]
+    fn rate() {
+        let sampler = Sampler::default().rate(0.25);
+        assert_eq!(25, (0..100).filter(|_| sampler.sample()).count());
+
+        let sampler = Sampler::default().rate(0.0);
+        assert_eq!(0, (0..100).filter(|_| sampler.sample()).count());
+    }
+
+    #[test]
+    fn per_second() {
+        let sampler = Sampler::default().per_second(Some(3));
+        assert_eq!(3, (0..100).filter(|_| sampler.sample()).count());
+
+        // shared between clones, as each connection has its own broker
+        let cloned = sampler.clone();
+        assert!(!cloned.sample());
+    }
+}
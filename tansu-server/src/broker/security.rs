@@ -0,0 +1,187 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{Error, Result};
+use std::{fmt, str::FromStr};
+use tansu_kafka_sans_io::Body;
+use url::Url;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum SecurityProtocol {
+    #[default]
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl,
+}
+
+impl SecurityProtocol {
+    pub fn requires_authentication(&self) -> bool {
+        matches!(self, Self::SaslPlaintext | Self::SaslSsl)
+    }
+
+    pub fn requires_encryption(&self) -> bool {
+        matches!(self, Self::Ssl | Self::SaslSsl)
+    }
+
+    // whether the request is permitted on an unauthenticated connection
+    // accepted by a listener with this protocol, only the requests used
+    // to authenticate are permitted before a client has authenticated
+    pub fn permits(&self, body: &Body) -> bool {
+        !self.requires_authentication()
+            || matches!(
+                body,
+                Body::ApiVersionsRequest { .. }
+                    | Body::SaslHandshakeRequest { .. }
+                    | Body::SaslAuthenticateRequest { .. }
+            )
+    }
+}
+
+impl FromStr for SecurityProtocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "PLAINTEXT" => Ok(Self::Plaintext),
+            "SSL" => Ok(Self::Ssl),
+            "SASL_PLAINTEXT" => Ok(Self::SaslPlaintext),
+            "SASL_SSL" => Ok(Self::SaslSsl),
+            _otherwise => Err(Error::UnknownSecurityProtocol(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for SecurityProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Plaintext => "PLAINTEXT",
+            Self::Ssl => "SSL",
+            Self::SaslPlaintext => "SASL_PLAINTEXT",
+            Self::SaslSsl => "SASL_SSL",
+        })
+    }
+}
+
+// the requests that brokers make of each other, refused on any other
+// listener when a listener is dedicated to inter-broker traffic
+pub fn inter_broker(body: &Body) -> bool {
+    matches!(
+        body,
+        Body::WriteTxnMarkersRequest { .. }
+            | Body::InitializeShareGroupStateRequest { .. }
+            | Body::ReadShareGroupStateRequest { .. }
+            | Body::ReadShareGroupStateSummaryRequest { .. }
+            | Body::WriteShareGroupStateRequest { .. }
+            | Body::DeleteShareGroupStateRequest { .. }
+    )
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Listener {
+    name: Option<String>,
+    url: Url,
+    security_protocol: SecurityProtocol,
+}
+
+impl Listener {
+    pub fn new(url: Url, security_protocol: SecurityProtocol) -> Self {
+        Self {
+            name: None,
+            url,
+            security_protocol,
+        }
+    }
+
+    pub fn named(self, name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn security_protocol(&self) -> SecurityProtocol {
+        self.security_protocol
+    }
+}
+
+// protocol=url, e.g., SASL_PLAINTEXT=tcp://0.0.0.0:9093, with a plaintext
+// listener when the protocol is omitted. A listener is named with
+// name:protocol=url, e.g., INTERNAL:PLAINTEXT=tcp://0.0.0.0:9094
+impl FromStr for Listener {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((listener, url)) = s.split_once('=') {
+            if let Some((name, security_protocol)) = listener.split_once(':') {
+                Ok(Self::new(Url::parse(url)?, security_protocol.parse()?).named(name))
+            } else {
+                Ok(Self::new(Url::parse(url)?, listener.parse()?))
+            }
+        } else {
+            Url::parse(s)
+                .map(|url| Self::new(url, SecurityProtocol::default()))
+                .map_err(Into::into)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listener_from_str() -> Result<()> {
+        assert_eq!(
+            Listener::new(
+                Url::parse("tcp://0.0.0.0:9093")?,
+                SecurityProtocol::SaslPlaintext
+            ),
+            "SASL_PLAINTEXT=tcp://0.0.0.0:9093".parse()?
+        );
+
+        assert_eq!(
+            Listener::new(
+                Url::parse("tcp://0.0.0.0:9092")?,
+                SecurityProtocol::Plaintext
+            ),
+            "tcp://0.0.0.0:9092".parse()?
+        );
+
+        assert_eq!(
+            Listener::new(
+                Url::parse("tcp://0.0.0.0:9094")?,
+                SecurityProtocol::Plaintext
+            )
+            .named("INTERNAL"),
+            "INTERNAL:PLAINTEXT=tcp://0.0.0.0:9094".parse()?
+        );
+
+        assert!(matches!(
+            "SASL_MTLS=tcp://0.0.0.0:9093".parse::<Listener>(),
+            Err(Error::UnknownSecurityProtocol(protocol)) if protocol == "SASL_MTLS"
+        ));
+
+        Ok(())
+    }
+}
@@ -13,28 +13,335 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::METER;
+use opentelemetry::{KeyValue, metrics::Gauge};
+use opentelemetry_proto::tonic::{
+    collector::metrics::v1::ExportMetricsServiceRequest,
+    metrics::v1::{metric::Data, number_data_point::Value},
+};
+use prost::Message;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
 use tansu_kafka_sans_io::{Body, ErrorCode};
+use tracing::{debug, warn};
 use uuid::Uuid;
 
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct GetTelemetrySubscriptionsRequest;
+const COMPRESSION_NONE: i8 = 0;
+
+#[derive(Clone, Debug)]
+pub struct Telemetry {
+    requested_metrics: Vec<String>,
+    push_interval_ms: i32,
+    telemetry_max_bytes: i32,
+    client_metrics: Gauge<f64>,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self {
+            requested_metrics: vec![],
+            push_interval_ms: 5_000,
+            telemetry_max_bytes: 1_024 * 1_024,
+            client_metrics: METER
+                .f64_gauge("tansu_client_metrics")
+                .with_description("Client metrics pushed with PushTelemetry")
+                .build(),
+        }
+    }
+}
+
+impl Telemetry {
+    pub fn requested_metrics(self, requested_metrics: Vec<String>) -> Self {
+        Self {
+            requested_metrics,
+            ..self
+        }
+    }
+
+    pub fn push_interval_ms(self, push_interval_ms: i32) -> Self {
+        Self {
+            push_interval_ms,
+            ..self
+        }
+    }
+
+    pub fn telemetry_max_bytes(self, telemetry_max_bytes: i32) -> Self {
+        Self {
+            telemetry_max_bytes,
+            ..self
+        }
+    }
+
+    // changes whenever the subscription does, so that clients
+    // pushing against a stale subscription are told to refresh
+    fn subscription_id(&self) -> i32 {
+        let mut hasher = DefaultHasher::new();
+        self.requested_metrics.hash(&mut hasher);
+        self.push_interval_ms.hash(&mut hasher);
+        hasher.finish() as i32
+    }
+
+    fn is_requested(&self, name: &str) -> bool {
+        self.requested_metrics
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GetTelemetrySubscriptionsRequest {
+    telemetry: Telemetry,
+}
 
 impl GetTelemetrySubscriptionsRequest {
-    pub fn response(&self, client_instance_id: [u8; 16]) -> Body {
-        let _ = client_instance_id;
+    pub fn with_telemetry(telemetry: Telemetry) -> Self {
+        Self { telemetry }
+    }
 
-        let client_instance_id = *Uuid::new_v4().as_bytes();
+    pub fn response(&self, client_instance_id: [u8; 16]) -> Body {
+        let client_instance_id = if Uuid::from_bytes(client_instance_id).is_nil() {
+            *Uuid::new_v4().as_bytes()
+        } else {
+            client_instance_id
+        };
 
         Body::GetTelemetrySubscriptionsResponse {
             throttle_time_ms: 0,
             error_code: ErrorCode::None.into(),
             client_instance_id,
-            subscription_id: 0,
-            accepted_compression_types: Some([0].into()),
-            push_interval_ms: 5_000,
-            telemetry_max_bytes: 1_024,
+            subscription_id: self.telemetry.subscription_id(),
+            accepted_compression_types: Some([COMPRESSION_NONE].into()),
+            push_interval_ms: self.telemetry.push_interval_ms,
+            telemetry_max_bytes: self.telemetry.telemetry_max_bytes,
             delta_temporality: false,
-            requested_metrics: Some([].into()),
+            requested_metrics: Some(self.telemetry.requested_metrics.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PushTelemetryRequest {
+    telemetry: Telemetry,
+}
+
+impl PushTelemetryRequest {
+    pub fn with_telemetry(telemetry: Telemetry) -> Self {
+        Self { telemetry }
+    }
+
+    fn push(
+        &self,
+        client_instance_id: [u8; 16],
+        subscription_id: i32,
+        compression_type: i8,
+        metrics: &[u8],
+    ) -> ErrorCode {
+        if Uuid::from_bytes(client_instance_id).is_nil()
+            || subscription_id != self.telemetry.subscription_id()
+        {
+            return ErrorCode::UnknownSubscriptionId;
+        }
+
+        if compression_type != COMPRESSION_NONE {
+            return ErrorCode::UnsupportedCompressionType;
+        }
+
+        if metrics.len() > usize::try_from(self.telemetry.telemetry_max_bytes).unwrap_or_default() {
+            return ErrorCode::TelemetryTooLarge;
+        }
+
+        let Ok(request) = ExportMetricsServiceRequest::decode(metrics).inspect_err(
+            |err| warn!(?err, client_instance_id = ?Uuid::from_bytes(client_instance_id)),
+        ) else {
+            return ErrorCode::InvalidRequest;
+        };
+
+        for metric in request
+            .resource_metrics
+            .into_iter()
+            .flat_map(|resource| resource.scope_metrics)
+            .flat_map(|scope| scope.metrics)
+            .filter(|metric| self.telemetry.is_requested(&metric.name))
+        {
+            let data_points = match metric.data {
+                Some(Data::Gauge(gauge)) => gauge.data_points,
+                Some(Data::Sum(sum)) => sum.data_points,
+                otherwise => {
+                    debug!(name = metric.name, ?otherwise);
+                    continue;
+                }
+            };
+
+            // each client instance would be a new series, metrics are
+            // attributed by name alone
+            let attributes = [KeyValue::new("name", metric.name.clone())];
+
+            for value in data_points
+                .into_iter()
+                .filter_map(|data_point| data_point.value)
+            {
+                let value = match value {
+                    Value::AsDouble(value) => value,
+                    Value::AsInt(value) => value as f64,
+                };
+
+                self.telemetry.client_metrics.record(value, &attributes);
+            }
         }
+
+        ErrorCode::None
+    }
+
+    pub fn response(
+        &self,
+        client_instance_id: [u8; 16],
+        subscription_id: i32,
+        terminating: bool,
+        compression_type: i8,
+        metrics: &[u8],
+    ) -> Body {
+        let error_code = self.push(
+            client_instance_id,
+            subscription_id,
+            compression_type,
+            metrics,
+        );
+
+        debug!(?error_code, terminating);
+
+        Body::PushTelemetryResponse {
+            throttle_time_ms: 0,
+            error_code: error_code.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_proto::tonic::metrics::v1::{
+        Gauge, Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics,
+    };
+
+    fn payload(name: &str) -> Vec<u8> {
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![Metric {
+                        name: name.into(),
+                        data: Some(Data::Gauge(Gauge {
+                            data_points: vec![NumberDataPoint {
+                                value: Some(Value::AsInt(6)),
+                                ..Default::default()
+                            }],
+                        })),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+        .encode_to_vec()
+    }
+
+    #[test]
+    fn subscribe_and_push() {
+        let telemetry = Telemetry::default()
+            .requested_metrics(vec!["org.apache.kafka.producer.".into()])
+            .push_interval_ms(30_000);
+
+        let Body::GetTelemetrySubscriptionsResponse {
+            error_code,
+            client_instance_id,
+            subscription_id,
+            push_interval_ms,
+            requested_metrics,
+            ..
+        } = GetTelemetrySubscriptionsRequest::with_telemetry(telemetry.clone())
+            .response(*Uuid::nil().as_bytes())
+        else {
+            panic!("unexpected response")
+        };
+
+        assert_eq!(i16::from(ErrorCode::None), error_code);
+        assert!(!Uuid::from_bytes(client_instance_id).is_nil());
+        assert_eq!(30_000, push_interval_ms);
+        assert_eq!(
+            Some(vec!["org.apache.kafka.producer.".into()]),
+            requested_metrics
+        );
+
+        let push = PushTelemetryRequest::with_telemetry(telemetry.clone());
+
+        assert_eq!(
+            Body::PushTelemetryResponse {
+                throttle_time_ms: 0,
+                error_code: ErrorCode::None.into(),
+            },
+            push.response(
+                client_instance_id,
+                subscription_id,
+                false,
+                COMPRESSION_NONE,
+                &payload("org.apache.kafka.producer.record.queue.time.max"),
+            )
+        );
+
+        let Body::GetTelemetrySubscriptionsResponse {
+            client_instance_id: renewed,
+            subscription_id: next,
+            push_interval_ms,
+            ..
+        } = GetTelemetrySubscriptionsRequest::with_telemetry(telemetry)
+            .response(client_instance_id)
+        else {
+            panic!("unexpected response")
+        };
+
+        assert_eq!(client_instance_id, renewed);
+        assert_eq!(subscription_id, next);
+        assert_eq!(30_000, push_interval_ms);
+    }
+
+    #[test]
+    fn push_rejected() {
+        let telemetry = Telemetry::default().telemetry_max_bytes(8);
+        let client_instance_id = *Uuid::new_v4().as_bytes();
+        let subscription_id = telemetry.subscription_id();
+        let push = PushTelemetryRequest::with_telemetry(telemetry);
+
+        let error_code = |subscription_id, compression_type, metrics: &[u8]| {
+            let Body::PushTelemetryResponse { error_code, .. } = push.response(
+                client_instance_id,
+                subscription_id,
+                false,
+                compression_type,
+                metrics,
+            ) else {
+                panic!("unexpected response")
+            };
+
+            ErrorCode::try_from(error_code).unwrap()
+        };
+
+        assert_eq!(
+            ErrorCode::UnknownSubscriptionId,
+            error_code(subscription_id.wrapping_add(1), COMPRESSION_NONE, &[])
+        );
+        assert_eq!(
+            ErrorCode::UnsupportedCompressionType,
+            error_code(subscription_id, 4, &[])
+        );
+        assert_eq!(
+            ErrorCode::TelemetryTooLarge,
+            error_code(subscription_id, COMPRESSION_NONE, &payload("abc"))
+        );
+        assert_eq!(
+            ErrorCode::None,
+            error_code(subscription_id, COMPRESSION_NONE, &[])
+        );
     }
 }
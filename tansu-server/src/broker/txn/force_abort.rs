@@ -0,0 +1,45 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use tansu_kafka_sans_io::ErrorCode;
+use tansu_storage::Storage;
+use tracing::debug;
+
+use crate::Result;
+
+// administrative abort of a hung transaction, fencing the current
+// producer epoch, a no-op when the transaction is already complete
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ForceAbort<S> {
+    storage: S,
+}
+
+impl<S> ForceAbort<S>
+where
+    S: Storage,
+{
+    pub fn with_storage(storage: S) -> Self {
+        Self { storage }
+    }
+
+    pub async fn response(&mut self, transaction_id: &str) -> Result<ErrorCode> {
+        debug!(?transaction_id);
+
+        self.storage
+            .txn_force_abort(transaction_id)
+            .await
+            .map_err(Into::into)
+    }
+}
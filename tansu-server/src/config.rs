@@ -0,0 +1,547 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{marker::PhantomData, net::IpAddr};
+
+use tracing::debug;
+use url::{Host, Url};
+
+use crate::{
+    Error, NODE_ID, Result,
+    broker::security::Listener,
+    coordinator::group::administrator::{MAX_SESSION_TIMEOUT_MS, MIN_SESSION_TIMEOUT_MS},
+};
+
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum Invalid {
+    #[error("cluster id is empty")]
+    EmptyClusterId,
+
+    #[error("node id must not be negative: {0}")]
+    NegativeNodeId(i32),
+
+    #[error("{name}: unsupported scheme: {url}")]
+    UnsupportedScheme { name: &'static str, url: Url },
+
+    #[error("{name}: missing host: {url}")]
+    MissingHost { name: &'static str, url: Url },
+
+    #[error("{name}: missing port: {url}")]
+    MissingPort { name: &'static str, url: Url },
+
+    #[error("{name}: unspecified address: {url}")]
+    UnspecifiedAddress { name: &'static str, url: Url },
+
+    #[error("inter broker listener: no such listener: {0}")]
+    UnknownInterBrokerListener(String),
+
+    #[error("group session timeout: minimum {min}ms exceeds maximum {max}ms")]
+    SessionTimeoutBounds { min: i32, max: i32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    cluster_id: String,
+    node_id: i32,
+    listener: Url,
+    advertised_listener: Url,
+    storage: Url,
+    schema_registry: Option<Url>,
+    prometheus_listener: Option<Url>,
+    additional_listeners: Vec<Listener>,
+    inter_broker_listener_name: Option<String>,
+    group_min_session_timeout_ms: i32,
+    group_max_session_timeout_ms: i32,
+}
+
+impl Config {
+    pub fn builder()
+    -> Builder<PhantomData<String>, PhantomData<Url>, PhantomData<Url>, PhantomData<Url>> {
+        Builder {
+            cluster_id: PhantomData,
+            node_id: NODE_ID,
+            listener: PhantomData,
+            advertised_listener: PhantomData,
+            storage: PhantomData,
+            schema_registry: None,
+            prometheus_listener: None,
+            additional_listeners: vec![],
+            inter_broker_listener_name: None,
+            group_min_session_timeout_ms: MIN_SESSION_TIMEOUT_MS,
+            group_max_session_timeout_ms: MAX_SESSION_TIMEOUT_MS,
+        }
+    }
+
+    pub fn cluster_id(&self) -> &str {
+        &self.cluster_id
+    }
+
+    pub fn node_id(&self) -> i32 {
+        self.node_id
+    }
+
+    pub fn listener(&self) -> &Url {
+        &self.listener
+    }
+
+    pub fn advertised_listener(&self) -> &Url {
+        &self.advertised_listener
+    }
+
+    pub fn storage(&self) -> &Url {
+        &self.storage
+    }
+
+    pub fn schema_registry(&self) -> Option<&Url> {
+        self.schema_registry.as_ref()
+    }
+
+    pub fn prometheus_listener(&self) -> Option<&Url> {
+        self.prometheus_listener.as_ref()
+    }
+
+    pub fn additional_listeners(&self) -> &[Listener] {
+        &self.additional_listeners
+    }
+
+    pub fn inter_broker_listener_name(&self) -> Option<&str> {
+        self.inter_broker_listener_name.as_deref()
+    }
+
+    pub fn group_min_session_timeout_ms(&self) -> i32 {
+        self.group_min_session_timeout_ms
+    }
+
+    pub fn group_max_session_timeout_ms(&self) -> i32 {
+        self.group_max_session_timeout_ms
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Builder<C, L, A, S> {
+    cluster_id: C,
+    node_id: i32,
+    listener: L,
+    advertised_listener: A,
+    storage: S,
+    schema_registry: Option<Url>,
+    prometheus_listener: Option<Url>,
+    additional_listeners: Vec<Listener>,
+    inter_broker_listener_name: Option<String>,
+    group_min_session_timeout_ms: i32,
+    group_max_session_timeout_ms: i32,
+}
+
+impl<C, L, A, S> Builder<C, L, A, S> {
+    pub fn cluster_id(self, cluster_id: impl Into<String>) -> Builder<String, L, A, S> {
+        Builder {
+            cluster_id: cluster_id.into(),
+            node_id: self.node_id,
+            listener: self.listener,
+            advertised_listener: self.advertised_listener,
+            storage: self.storage,
+            schema_registry: self.schema_registry,
+            prometheus_listener: self.prometheus_listener,
+            additional_listeners: self.additional_listeners,
+            inter_broker_listener_name: self.inter_broker_listener_name,
+            group_min_session_timeout_ms: self.group_min_session_timeout_ms,
+            group_max_session_timeout_ms: self.group_max_session_timeout_ms,
+        }
+    }
+
+    pub fn node_id(self, node_id: i32) -> Self {
+        Self { node_id, ..self }
+    }
+
+    pub fn listener(self, listener: Url) -> Builder<C, Url, A, S> {
+        Builder {
+            cluster_id: self.cluster_id,
+            node_id: self.node_id,
+            listener,
+            advertised_listener: self.advertised_listener,
+            storage: self.storage,
+            schema_registry: self.schema_registry,
+            prometheus_listener: self.prometheus_listener,
+            additional_listeners: self.additional_listeners,
+            inter_broker_listener_name: self.inter_broker_listener_name,
+            group_min_session_timeout_ms: self.group_min_session_timeout_ms,
+            group_max_session_timeout_ms: self.group_max_session_timeout_ms,
+        }
+    }
+
+    pub fn advertised_listener(self, advertised_listener: Url) -> Builder<C, L, Url, S> {
+        Builder {
+            cluster_id: self.cluster_id,
+            node_id: self.node_id,
+            listener: self.listener,
+            advertised_listener,
+            storage: self.storage,
+            schema_registry: self.schema_registry,
+            prometheus_listener: self.prometheus_listener,
+            additional_listeners: self.additional_listeners,
+            inter_broker_listener_name: self.inter_broker_listener_name,
+            group_min_session_timeout_ms: self.group_min_session_timeout_ms,
+            group_max_session_timeout_ms: self.group_max_session_timeout_ms,
+        }
+    }
+
+    pub fn storage(self, storage: Url) -> Builder<C, L, A, Url> {
+        Builder {
+            cluster_id: self.cluster_id,
+            node_id: self.node_id,
+            listener: self.listener,
+            advertised_listener: self.advertised_listener,
+            storage,
+            schema_registry: self.schema_registry,
+            prometheus_listener: self.prometheus_listener,
+            additional_listeners: self.additional_listeners,
+            inter_broker_listener_name: self.inter_broker_listener_name,
+            group_min_session_timeout_ms: self.group_min_session_timeout_ms,
+            group_max_session_timeout_ms: self.group_max_session_timeout_ms,
+        }
+    }
+
+    pub fn schema_registry(self, schema_registry: Option<Url>) -> Self {
+        Self {
+            schema_registry,
+            ..self
+        }
+    }
+
+    pub fn prometheus_listener(self, prometheus_listener: Option<Url>) -> Self {
+        Self {
+            prometheus_listener,
+            ..self
+        }
+    }
+
+    pub fn additional_listeners(self, additional_listeners: Vec<Listener>) -> Self {
+        Self {
+            additional_listeners,
+            ..self
+        }
+    }
+
+    pub fn inter_broker_listener_name(self, inter_broker_listener_name: Option<String>) -> Self {
+        Self {
+            inter_broker_listener_name,
+            ..self
+        }
+    }
+
+    pub fn group_min_session_timeout_ms(self, group_min_session_timeout_ms: i32) -> Self {
+        Self {
+            group_min_session_timeout_ms,
+            ..self
+        }
+    }
+
+    pub fn group_max_session_timeout_ms(self, group_max_session_timeout_ms: i32) -> Self {
+        Self {
+            group_max_session_timeout_ms,
+            ..self
+        }
+    }
+}
+
+impl Builder<String, Url, Url, Url> {
+    pub fn build(self) -> Result<Config> {
+        let mut invalid = vec![];
+
+        if self.cluster_id.trim().is_empty() {
+            invalid.push(Invalid::EmptyClusterId);
+        }
+
+        if self.node_id < 0 {
+            invalid.push(Invalid::NegativeNodeId(self.node_id));
+        }
+
+        listener("listener", &self.listener, &mut invalid);
+
+        listener(
+            "advertised listener",
+            &self.advertised_listener,
+            &mut invalid,
+        );
+
+        if is_unspecified(&self.advertised_listener) {
+            invalid.push(Invalid::UnspecifiedAddress {
+                name: "advertised listener",
+                url: self.advertised_listener.clone(),
+            });
+        }
+
+        if self.advertised_listener.port().is_none() {
+            invalid.push(Invalid::MissingPort {
+                name: "advertised listener",
+                url: self.advertised_listener.clone(),
+            });
+        }
+
+        scheme(
+            "storage",
+            &self.storage,
+            &["postgres", "postgresql", "s3", "memory"],
+            &mut invalid,
+        );
+
+        if let Some(ref schema_registry) = self.schema_registry {
+            scheme(
+                "schema registry",
+                schema_registry,
+                &["s3", "file", "memory"],
+                &mut invalid,
+            );
+        }
+
+        if let Some(ref prometheus_listener) = self.prometheus_listener {
+            listener("prometheus listener", prometheus_listener, &mut invalid);
+        }
+
+        // inter-broker requests would be refused on every listener without a match
+        if let Some(ref name) = self.inter_broker_listener_name {
+            if !self
+                .additional_listeners
+                .iter()
+                .any(|listener| listener.name() == Some(name))
+            {
+                invalid.push(Invalid::UnknownInterBrokerListener(name.clone()));
+            }
+        }
+
+        // every join would be rejected when no session timeout is within bounds
+        if self.group_min_session_timeout_ms > self.group_max_session_timeout_ms {
+            invalid.push(Invalid::SessionTimeoutBounds {
+                min: self.group_min_session_timeout_ms,
+                max: self.group_max_session_timeout_ms,
+            });
+        }
+
+        debug!(?invalid);
+
+        if invalid.is_empty() {
+            Ok(Config {
+                cluster_id: self.cluster_id,
+                node_id: self.node_id,
+                listener: self.listener,
+                advertised_listener: self.advertised_listener,
+                storage: self.storage,
+                schema_registry: self.schema_registry,
+                prometheus_listener: self.prometheus_listener,
+                additional_listeners: self.additional_listeners,
+                inter_broker_listener_name: self.inter_broker_listener_name,
+                group_min_session_timeout_ms: self.group_min_session_timeout_ms,
+                group_max_session_timeout_ms: self.group_max_session_timeout_ms,
+            })
+        } else {
+            Err(Error::InvalidConfig(invalid))
+        }
+    }
+}
+
+fn scheme(name: &'static str, url: &Url, supported: &[&str], invalid: &mut Vec<Invalid>) {
+    if !supported.contains(&url.scheme()) {
+        invalid.push(Invalid::UnsupportedScheme {
+            name,
+            url: url.clone(),
+        });
+    }
+}
+
+fn listener(name: &'static str, url: &Url, invalid: &mut Vec<Invalid>) {
+    scheme(name, url, &["tcp"], invalid);
+
+    if url.host().is_none() {
+        invalid.push(Invalid::MissingHost {
+            name,
+            url: url.clone(),
+        });
+    }
+}
+
+fn is_unspecified(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Ipv4(address)) => address.is_unspecified(),
+        Some(Host::Ipv6(address)) => address.is_unspecified(),
+
+        // ipv4 addresses are opaque domains within a tcp url
+        Some(Host::Domain(domain)) => domain
+            .parse::<IpAddr>()
+            .is_ok_and(|address| address.is_unspecified()),
+
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> Result<Builder<String, Url, Url, Url>> {
+        Ok(Config::builder()
+            .cluster_id("tansu")
+            .listener(Url::parse("tcp://[::]:9092")?)
+            .advertised_listener(Url::parse("tcp://localhost:9092")?)
+            .storage(Url::parse("memory://tansu/")?))
+    }
+
+    fn invalid(result: Result<Config>) -> Vec<Invalid> {
+        match result {
+            Err(Error::InvalidConfig(invalid)) => invalid,
+            otherwise => panic!("{otherwise:?}"),
+        }
+    }
+
+    #[test]
+    fn valid() -> Result<()> {
+        let config = builder()?
+            .schema_registry(Some(Url::parse("file://schema")?))
+            .prometheus_listener(Some(Url::parse("tcp://[::]:9000")?))
+            .build()?;
+
+        assert_eq!("tansu", config.cluster_id());
+        assert_eq!(NODE_ID, config.node_id());
+        assert_eq!(Some(9092), config.advertised_listener().port());
+
+        Ok(())
+    }
+
+    #[test]
+    fn bad_listener() -> Result<()> {
+        let listener = Url::parse("udp:9092")?;
+
+        assert_eq!(
+            vec![
+                Invalid::UnsupportedScheme {
+                    name: "listener",
+                    url: listener.clone(),
+                },
+                Invalid::MissingHost {
+                    name: "listener",
+                    url: listener.clone(),
+                },
+            ],
+            invalid(builder()?.listener(listener).build())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_advertised_port() -> Result<()> {
+        let advertised_listener = Url::parse("tcp://localhost")?;
+
+        assert_eq!(
+            vec![Invalid::MissingPort {
+                name: "advertised listener",
+                url: advertised_listener.clone(),
+            }],
+            invalid(builder()?.advertised_listener(advertised_listener).build())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_inter_broker_listener() -> Result<()> {
+        let internal = "INTERNAL:PLAINTEXT=tcp://localhost:9094".parse::<Listener>()?;
+
+        assert!(
+            builder()?
+                .additional_listeners(vec![internal.clone()])
+                .inter_broker_listener_name(Some("INTERNAL".into()))
+                .build()
+                .is_ok()
+        );
+
+        assert_eq!(
+            vec![Invalid::UnknownInterBrokerListener("EXTERNAL".into())],
+            invalid(
+                builder()?
+                    .additional_listeners(vec![internal])
+                    .inter_broker_listener_name(Some("EXTERNAL".into()))
+                    .build()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn reversed_session_timeout_bounds() -> Result<()> {
+        assert!(
+            builder()?
+                .group_min_session_timeout_ms(6_000)
+                .group_max_session_timeout_ms(6_000)
+                .build()
+                .is_ok()
+        );
+
+        assert_eq!(
+            vec![Invalid::SessionTimeoutBounds {
+                min: 30_000,
+                max: 6_000
+            }],
+            invalid(
+                builder()?
+                    .group_min_session_timeout_ms(30_000)
+                    .group_max_session_timeout_ms(6_000)
+                    .build()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn every_problem() -> Result<()> {
+        let advertised_listener = Url::parse("tcp://0.0.0.0:9092")?;
+        let storage = Url::parse("mysql://localhost")?;
+
+        let error = builder()?
+            .cluster_id("")
+            .node_id(-1)
+            .advertised_listener(advertised_listener.clone())
+            .storage(storage.clone())
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            "invalid configuration: cluster id is empty, \
+             node id must not be negative: -1, \
+             advertised listener: unspecified address: tcp://0.0.0.0:9092, \
+             storage: unsupported scheme: mysql://localhost",
+            error.to_string()
+        );
+
+        assert_eq!(
+            vec![
+                Invalid::EmptyClusterId,
+                Invalid::NegativeNodeId(-1),
+                Invalid::UnspecifiedAddress {
+                    name: "advertised listener",
+                    url: advertised_listener,
+                },
+                Invalid::UnsupportedScheme {
+                    name: "storage",
+                    url: storage,
+                },
+            ],
+            invalid(Err(error))
+        );
+
+        Ok(())
+    }
+}
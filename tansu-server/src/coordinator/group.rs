@@ -18,7 +18,7 @@ pub mod consumer;
 
 use crate::Result;
 use async_trait::async_trait;
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tansu_kafka_sans_io::{
     Body,
     join_group_request::JoinGroupRequestProtocol,
@@ -27,6 +27,7 @@ use tansu_kafka_sans_io::{
     offset_fetch_request::{OffsetFetchRequestGroup, OffsetFetchRequestTopic},
     sync_group_request::SyncGroupRequestAssignment,
 };
+use tansu_storage::clock::Clock;
 
 #[derive(Debug)]
 pub struct OffsetCommit<'a> {
@@ -38,12 +39,25 @@ pub struct OffsetCommit<'a> {
     pub topics: Option<&'a [OffsetCommitRequestTopic]>,
 }
 
+// a heartbeat from a member of a consumer protocol (KIP-848) group
+#[derive(Debug)]
+pub struct ConsumerGroupHeartbeat<'a> {
+    pub group_id: &'a str,
+    pub member_id: &'a str,
+    pub member_epoch: i32,
+    pub subscribed_topic_names: Option<&'a [String]>,
+}
+
 #[async_trait]
 pub trait Coordinator: Clone + Debug + Send + Sync + 'static {
+    // the clock used for session timeouts and group timestamps
+    fn clock(self, clock: Arc<dyn Clock>) -> Self;
+
     #[allow(clippy::too_many_arguments)]
     async fn join(
         &mut self,
         client_id: Option<&str>,
+        client_host: Option<&str>,
         group_id: &str,
         session_timeout_ms: i32,
         rebalance_timeout_ms: Option<i32>,
@@ -83,6 +97,11 @@ pub trait Coordinator: Clone + Debug + Send + Sync + 'static {
 
     async fn offset_commit(&mut self, detail: OffsetCommit<'_>) -> Result<Body>;
 
+    async fn consumer_group_heartbeat(
+        &mut self,
+        detail: ConsumerGroupHeartbeat<'_>,
+    ) -> Result<Body>;
+
     async fn offset_fetch(
         &mut self,
         group_id: Option<&str>,
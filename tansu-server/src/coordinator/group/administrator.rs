@@ -19,7 +19,7 @@ use std::{
     hash::{Hash, Hasher},
     marker::PhantomData,
     ops::Deref,
-    sync::LazyLock,
+    sync::{Arc, LazyLock},
     time::SystemTime,
 };
 
@@ -27,7 +27,7 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use opentelemetry::{KeyValue, metrics::Counter};
 use tansu_kafka_sans_io::{
-    Body, ErrorCode,
+    Body, ErrorCode, consumer_group_heartbeat_response,
     join_group_request::JoinGroupRequestProtocol,
     join_group_response::JoinGroupResponseMember,
     leave_group_request::MemberIdentity,
@@ -41,8 +41,9 @@ use tansu_kafka_sans_io::{
     sync_group_request::SyncGroupRequestAssignment,
 };
 use tansu_storage::{
-    GroupDetail, GroupMember, GroupState, OffsetCommitRequest, Storage, Topition, UpdateError,
-    Version,
+    GroupDetail, GroupMember, GroupState, GroupType, OffsetCommitRequest, Storage, TopicId,
+    Topition, UpdateError, Version,
+    clock::{Clock, SystemClock},
 };
 use tokio::time::{Duration, sleep};
 use tracing::{debug, error, info};
@@ -50,10 +51,21 @@ use uuid::Uuid;
 
 use crate::{Error, METER, Result};
 
-use super::{Coordinator, OffsetCommit};
+use super::{ConsumerGroupHeartbeat, Coordinator, OffsetCommit};
 
 const PAUSE_MS: u128 = 3_000;
 
+pub const MIN_SESSION_TIMEOUT_MS: i32 = 6_000;
+pub const MAX_SESSION_TIMEOUT_MS: i32 = 1_800_000;
+
+// the broker defaults of group.consumer.session.timeout.ms and
+// group.consumer.heartbeat.interval.ms for consumer protocol groups
+const CONSUMER_SESSION_TIMEOUT_MS: i32 = 45_000;
+const CONSUMER_HEARTBEAT_INTERVAL_MS: i32 = 5_000;
+
+const JOIN_GROUP_MEMBER_EPOCH: i32 = 0;
+const LEAVE_GROUP_MEMBER_EPOCH: i32 = -1;
+
 static COORDINATOR_REQUESTS: LazyLock<Counter<u64>> = LazyLock::new(|| {
     METER
         .u64_counter("tansu_group_coordinator_requests")
@@ -75,6 +87,7 @@ pub trait Group: Debug + Send {
         self,
         now: SystemTime,
         client_id: Option<&str>,
+        client_host: Option<&str>,
         group_id: &str,
         session_timeout_ms: i32,
         rebalance_timeout_ms: Option<i32>,
@@ -198,6 +211,7 @@ where
                 state,
                 skip_assignment,
                 inception,
+                group_type,
                 ..
             }) => GroupDetail {
                 session_timeout_ms: *session_timeout_ms,
@@ -210,6 +224,11 @@ where
                             GroupMember {
                                 join_response: member.join_response.clone(),
                                 last_contact: member.last_contact,
+                                client_id: member.client_id.clone(),
+                                client_host: member.client_host.clone(),
+                                awaiting_sync: member.awaiting_sync,
+                                member_epoch: member.member_epoch,
+                                subscribed_topic_names: member.subscribed_topic_names.clone(),
                             },
                         )
                     })
@@ -222,6 +241,7 @@ where
                     protocol_name: state.protocol_name.clone(),
                     leader: state.leader.clone(),
                 },
+                group_type: *group_type,
             },
             Wrapper::Formed(Inner {
                 session_timeout_ms,
@@ -231,6 +251,7 @@ where
                 state,
                 skip_assignment,
                 inception,
+                group_type,
                 ..
             }) => GroupDetail {
                 session_timeout_ms: *session_timeout_ms,
@@ -243,6 +264,11 @@ where
                             GroupMember {
                                 join_response: member.join_response.clone(),
                                 last_contact: member.last_contact,
+                                client_id: member.client_id.clone(),
+                                client_host: member.client_host.clone(),
+                                awaiting_sync: member.awaiting_sync,
+                                member_epoch: member.member_epoch,
+                                subscribed_topic_names: member.subscribed_topic_names.clone(),
                             },
                         )
                     })
@@ -256,6 +282,7 @@ where
                     leader: state.leader.clone(),
                     assignments: state.assignments.clone(),
                 },
+                group_type: *group_type,
             },
         }
     }
@@ -294,6 +321,11 @@ where
                                 Member {
                                     join_response: member.join_response.clone(),
                                     last_contact: member.last_contact,
+                                    client_id: member.client_id.clone(),
+                                    client_host: member.client_host.clone(),
+                                    awaiting_sync: member.awaiting_sync,
+                                    member_epoch: member.member_epoch,
+                                    subscribed_topic_names: member.subscribed_topic_names.clone(),
                                 },
                             )
                         })
@@ -307,6 +339,7 @@ where
                     storage,
                     skip_assignment: gd.skip_assignment,
                     inception: gd.inception,
+                    group_type: gd.group_type,
                 })
             }
             GroupState::Formed {
@@ -326,6 +359,11 @@ where
                             Member {
                                 join_response: member.join_response.clone(),
                                 last_contact: member.last_contact,
+                                client_id: member.client_id.clone(),
+                                client_host: member.client_host.clone(),
+                                awaiting_sync: member.awaiting_sync,
+                                member_epoch: member.member_epoch,
+                                subscribed_topic_names: member.subscribed_topic_names.clone(),
                             },
                         )
                     })
@@ -340,6 +378,7 @@ where
                 storage,
                 skip_assignment: gd.skip_assignment,
                 inception: gd.inception,
+                group_type: gd.group_type,
             }),
         }
     }
@@ -393,6 +432,13 @@ where
         }
     }
 
+    pub fn group_type(&self) -> GroupType {
+        match self {
+            Self::Forming(inner) => inner.group_type,
+            Self::Formed(inner) => inner.group_type,
+        }
+    }
+
     pub fn skip_assignment(&self) -> Option<&bool> {
         match self {
             Self::Forming(inner) => inner.skip_assignment.as_ref(),
@@ -433,9 +479,14 @@ where
     fn missed_heartbeat(self, group_id: &str, now: SystemTime) -> Self {
         debug!(?group_id, ?now);
 
+        // evicting expired members starts a new generation, so that
+        // the remaining members rejoin and the group rebalances
         match self {
             Self::Forming(mut inner) => {
-                _ = inner.missed_heartbeat(group_id, now);
+                if inner.missed_heartbeat(group_id, now) {
+                    inner.generation_id += 1;
+                }
+
                 Self::Forming(inner)
             }
             Self::Formed(mut inner) => {
@@ -446,7 +497,7 @@ where
                         session_timeout_ms: inner.session_timeout_ms,
                         rebalance_timeout_ms: inner.rebalance_timeout_ms,
                         members: inner.members,
-                        generation_id: inner.generation_id,
+                        generation_id: inner.generation_id + 1,
                         state: Forming {
                             protocol_type: Some(inner.state.protocol_type),
                             protocol_name: Some(inner.state.protocol_name),
@@ -455,6 +506,7 @@ where
                         storage: inner.storage,
                         skip_assignment: inner.skip_assignment,
                         inception: inner.inception,
+                        group_type: inner.group_type,
                     })
                 } else {
                     Self::Formed(inner)
@@ -481,6 +533,7 @@ where
         self,
         now: SystemTime,
         client_id: Option<&str>,
+        client_host: Option<&str>,
         group_id: &str,
         session_timeout_ms: i32,
         rebalance_timeout_ms: Option<i32>,
@@ -496,6 +549,7 @@ where
                     .join(
                         now,
                         client_id,
+                        client_host,
                         group_id,
                         session_timeout_ms,
                         rebalance_timeout_ms,
@@ -514,6 +568,7 @@ where
                     .join(
                         now,
                         client_id,
+                        client_host,
                         group_id,
                         session_timeout_ms,
                         rebalance_timeout_ms,
@@ -674,6 +729,9 @@ where
 pub struct Controller<O> {
     storage: O,
     wrappers: BTreeMap<String, (Wrapper<O>, Option<Version>)>,
+    min_session_timeout_ms: i32,
+    max_session_timeout_ms: i32,
+    clock: Arc<dyn Clock>,
 }
 
 impl<O> Controller<O>
@@ -684,6 +742,102 @@ where
         Ok(Self {
             storage,
             wrappers: BTreeMap::new(),
+            min_session_timeout_ms: MIN_SESSION_TIMEOUT_MS,
+            max_session_timeout_ms: MAX_SESSION_TIMEOUT_MS,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    pub fn min_session_timeout_ms(self, min_session_timeout_ms: i32) -> Self {
+        Self {
+            min_session_timeout_ms,
+            ..self
+        }
+    }
+
+    pub fn max_session_timeout_ms(self, max_session_timeout_ms: i32) -> Self {
+        Self {
+            max_session_timeout_ms,
+            ..self
+        }
+    }
+
+    // the partitions of each subscribed topic are spread round robin over
+    // the members subscribed to that topic, in member id order
+    async fn assignment(
+        &mut self,
+        group: &GroupDetail,
+        member_id: &str,
+    ) -> Result<consumer_group_heartbeat_response::Assignment> {
+        let Some(subscribed) = group
+            .members
+            .get(member_id)
+            .and_then(|member| member.subscribed_topic_names.as_deref())
+            .filter(|subscribed| !subscribed.is_empty())
+        else {
+            return Ok(consumer_group_heartbeat_response::Assignment {
+                topic_partitions: Some([].into()),
+            });
+        };
+
+        let topics = subscribed
+            .iter()
+            .cloned()
+            .map(TopicId::Name)
+            .collect::<Vec<_>>();
+
+        let metadata = self.storage.metadata(Some(&topics)).await?;
+
+        let mut topic_partitions = vec![];
+
+        for topic in metadata.topics() {
+            let (Some(name), Some(topic_id), Some(partitions)) = (
+                topic.name.as_deref(),
+                topic.topic_id,
+                topic.partitions.as_deref(),
+            ) else {
+                continue;
+            };
+
+            if topic.error_code != i16::from(ErrorCode::None) {
+                continue;
+            }
+
+            let subscribers = group
+                .members
+                .iter()
+                .filter(|(_, member)| {
+                    member
+                        .subscribed_topic_names
+                        .as_deref()
+                        .is_some_and(|subscribed| subscribed.iter().any(|topic| topic == name))
+                })
+                .map(|(id, _)| id.as_str())
+                .collect::<Vec<_>>();
+
+            let mut indexes = partitions
+                .iter()
+                .map(|partition| partition.partition_index)
+                .collect::<Vec<_>>();
+            indexes.sort();
+
+            let assigned = indexes
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| subscribers[i % subscribers.len()] == member_id)
+                .map(|(_, partition)| partition)
+                .collect::<Vec<_>>();
+
+            if !assigned.is_empty() {
+                topic_partitions.push(consumer_group_heartbeat_response::TopicPartitions {
+                    topic_id,
+                    partitions: Some(assigned),
+                });
+            }
+        }
+
+        Ok(consumer_group_heartbeat_response::Assignment {
+            topic_partitions: Some(topic_partitions),
         })
     }
 }
@@ -693,9 +847,18 @@ impl<O> Coordinator for Controller<O>
 where
     O: Storage,
 {
+    fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            storage: self.storage.clock(clock.clone()),
+            clock,
+            ..self
+        }
+    }
+
     async fn join(
         &mut self,
         client_id: Option<&str>,
+        client_host: Option<&str>,
         group_id: &str,
         session_timeout_ms: i32,
         rebalance_timeout_ms: Option<i32>,
@@ -707,6 +870,7 @@ where
     ) -> Result<Body> {
         debug!(
             ?client_id,
+            ?client_host,
             ?group_id,
             ?session_timeout_ms,
             ?rebalance_timeout_ms,
@@ -719,6 +883,29 @@ where
 
         COORDINATOR_REQUESTS.add(1, &[KeyValue::new("method", "join")]);
 
+        if !(self.min_session_timeout_ms..=self.max_session_timeout_ms)
+            .contains(&session_timeout_ms)
+        {
+            debug!(
+                session_timeout_ms,
+                self.min_session_timeout_ms,
+                self.max_session_timeout_ms,
+                join_outcome = ?ErrorCode::InvalidSessionTimeout
+            );
+
+            return Ok(Body::JoinGroupResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::InvalidSessionTimeout.into(),
+                generation_id: -1,
+                protocol_type: Some(protocol_type.into()),
+                protocol_name: Some("".into()),
+                leader: "".into(),
+                skip_assignment: Some(false),
+                member_id: member_id.into(),
+                members: Some([].into()),
+            });
+        }
+
         let started_at = SystemTime::now();
 
         let mut iteration = 0;
@@ -726,7 +913,7 @@ where
         loop {
             COORDINATOR_REQUESTS.add(1, &[KeyValue::new("method", "join_loop")]);
 
-            let now = SystemTime::now();
+            let now = self.clock.now();
 
             let (mut original, version) = self.wrappers.remove(group_id).unwrap_or_else(|| {
                 debug!(?iteration, ?group_id);
@@ -739,12 +926,32 @@ where
                     state: Forming::default(),
                     skip_assignment: Some(false),
                     storage: self.storage.clone(),
-                    inception: SystemTime::now(),
+                    inception: now,
+                    group_type: GroupType::Classic,
                 };
 
                 (Wrapper::Forming(inner), None)
             });
 
+            // a consumer protocol group only has members that heartbeat
+            if original.group_type() == GroupType::Consumer {
+                _ = self
+                    .wrappers
+                    .insert(group_id.to_owned(), (original, version));
+
+                return Ok(Body::JoinGroupResponse {
+                    throttle_time_ms: Some(0),
+                    error_code: ErrorCode::InconsistentGroupProtocol.into(),
+                    generation_id: -1,
+                    protocol_type: Some(protocol_type.into()),
+                    protocol_name: Some("".into()),
+                    leader: "".into(),
+                    skip_assignment: Some(false),
+                    member_id: member_id.into(),
+                    members: Some([].into()),
+                });
+            }
+
             if group_instance_id.is_none() {
                 original = original.missed_heartbeat(group_id, now);
             }
@@ -765,6 +972,7 @@ where
                 .join(
                     now,
                     client_id,
+                    client_host,
                     group_id,
                     session_timeout_ms,
                     rebalance_timeout_ms,
@@ -881,12 +1089,12 @@ where
         loop {
             COORDINATOR_REQUESTS.add(1, &[KeyValue::new("method", "sync_loop")]);
 
-            let now = SystemTime::now();
+            let now = self.clock.now();
 
-            let (mut original, version) = self
-                .wrappers
-                .remove(group_id)
-                .unwrap_or((Wrapper::Forming(Inner::new(self.storage.clone())), None));
+            let (mut original, version) = self.wrappers.remove(group_id).unwrap_or((
+                Wrapper::Forming(Inner::new(self.storage.clone(), self.clock.now())),
+                None,
+            ));
 
             debug!(?group_id, ?original, ?version, ?iteration);
 
@@ -996,14 +1204,14 @@ where
         loop {
             COORDINATOR_REQUESTS.add(1, &[KeyValue::new("method", "leave_loop")]);
 
-            let (wrapper, version) = self
-                .wrappers
-                .remove(group_id)
-                .unwrap_or((Wrapper::Forming(Inner::new(self.storage.clone())), None));
+            let (wrapper, version) = self.wrappers.remove(group_id).unwrap_or((
+                Wrapper::Forming(Inner::new(self.storage.clone(), self.clock.now())),
+                None,
+            ));
 
             debug!(?group_id, ?wrapper, ?version, ?iteration);
 
-            let now = SystemTime::now();
+            let now = self.clock.now();
             let wrapper = wrapper.missed_heartbeat(group_id, now);
 
             let (wrapper, body) = wrapper.leave(now, group_id, member_id, members).await;
@@ -1069,14 +1277,14 @@ where
         loop {
             COORDINATOR_REQUESTS.add(1, &[KeyValue::new("method", "offset_commit_loop")]);
 
-            let (wrapper, version) = self
-                .wrappers
-                .remove(group_id)
-                .unwrap_or((Wrapper::Forming(Inner::new(self.storage.clone())), None));
+            let (wrapper, version) = self.wrappers.remove(group_id).unwrap_or((
+                Wrapper::Forming(Inner::new(self.storage.clone(), self.clock.now())),
+                None,
+            ));
 
             debug!(?group_id, ?wrapper, ?version, ?iteration);
 
-            let now = SystemTime::now();
+            let now = self.clock.now();
 
             let (wrapper, body) = wrapper.offset_commit(now, &offset_commit).await;
             debug!(group_id, ?wrapper, ?version, iteration,);
@@ -1132,6 +1340,130 @@ where
         }
     }
 
+    async fn consumer_group_heartbeat(
+        &mut self,
+        heartbeat: ConsumerGroupHeartbeat<'_>,
+    ) -> Result<Body> {
+        debug!(?heartbeat);
+        COORDINATOR_REQUESTS.add(1, &[KeyValue::new("method", "consumer_group_heartbeat")]);
+
+        if heartbeat.group_id.is_empty() {
+            return Ok(consumer_group_heartbeat_error(
+                heartbeat.member_id,
+                ErrorCode::InvalidRequest,
+            ));
+        }
+
+        let group_id = heartbeat.group_id;
+        let mut iteration = 0;
+
+        loop {
+            COORDINATOR_REQUESTS.add(
+                1,
+                &[KeyValue::new("method", "consumer_group_heartbeat_loop")],
+            );
+
+            let now = self.clock.now();
+
+            let (current, version) = self.wrappers.remove(group_id).map_or_else(
+                || (consumer_group(now), None),
+                |(wrapper, version)| (GroupDetail::from(&wrapper), version),
+            );
+
+            debug!(?group_id, ?current, ?version, ?iteration);
+
+            let mut updated = current.clone();
+
+            let (member_id, member_epoch) = match member_heartbeat(&mut updated, now, &heartbeat) {
+                Ok(member) => member,
+
+                Err(error_code) => {
+                    _ = self.wrappers.insert(
+                        group_id.to_owned(),
+                        (
+                            Wrapper::with_storage_group_detail(self.storage.clone(), current),
+                            version,
+                        ),
+                    );
+
+                    return Ok(consumer_group_heartbeat_error(
+                        heartbeat.member_id,
+                        error_code,
+                    ));
+                }
+            };
+
+            match self
+                .storage
+                .update_group(group_id, updated.clone(), version)
+                .await
+            {
+                Ok(version) => {
+                    debug!(?group_id, ?version);
+
+                    let assignment = if member_epoch == LEAVE_GROUP_MEMBER_EPOCH {
+                        None
+                    } else {
+                        self.assignment(&updated, &member_id).await.map(Some)?
+                    };
+
+                    _ = self.wrappers.insert(
+                        group_id.to_owned(),
+                        (
+                            Wrapper::with_storage_group_detail(self.storage.clone(), updated),
+                            Some(version),
+                        ),
+                    );
+
+                    return Ok(Body::ConsumerGroupHeartbeatResponse {
+                        throttle_time_ms: 0,
+                        error_code: ErrorCode::None.into(),
+                        error_message: None,
+                        member_id: Some(member_id),
+                        member_epoch,
+                        heartbeat_interval_ms: CONSUMER_HEARTBEAT_INTERVAL_MS,
+                        assignment,
+                    });
+                }
+
+                Err(UpdateError::Outdated { current, version }) => {
+                    debug!(?group_id, ?current, ?version);
+                    COORDINATOR_REQUESTS.add(
+                        1,
+                        &[KeyValue::new("method", "consumer_group_heartbeat_outdated")],
+                    );
+
+                    _ = self.wrappers.insert(
+                        group_id.to_owned(),
+                        (
+                            Wrapper::with_storage_group_detail(self.storage.clone(), current),
+                            Some(version),
+                        ),
+                    );
+
+                    iteration += 1;
+                    continue;
+                }
+
+                Err(UpdateError::Error(error)) => return Err(error.into()),
+
+                Err(UpdateError::ObjectStore(error)) => return Err(error.into()),
+
+                Err(UpdateError::SerdeJson(error)) => return Err(error.into()),
+
+                Err(UpdateError::TokioPostgres(error)) => return Err(error.into()),
+
+                Err(UpdateError::MissingEtag) => {
+                    return Err(Error::Message(String::from("missing e-tag")));
+                }
+
+                Err(UpdateError::Uuid(uuid)) => {
+                    return Err(Error::Message(format!("uuid: {uuid}")));
+                }
+            }
+        }
+    }
+
     async fn offset_fetch(
         &mut self,
         group_id: Option<&str>,
@@ -1142,9 +1474,9 @@ where
         debug!(?group_id, ?topics, ?groups, ?require_stable);
         COORDINATOR_REQUESTS.add(1, &[KeyValue::new("method", "offset_fetch")]);
 
-        let wrapper = Wrapper::Forming(Inner::new(self.storage.clone()));
+        let wrapper = Wrapper::Forming(Inner::new(self.storage.clone(), self.clock.now()));
 
-        let now = SystemTime::now();
+        let now = self.clock.now();
         let (_wrapper, body) = wrapper
             .offset_fetch(now, group_id, topics, groups, require_stable)
             .await;
@@ -1166,23 +1498,25 @@ where
         loop {
             COORDINATOR_REQUESTS.add(1, &[KeyValue::new("method", "heartbeat_loop")]);
 
-            let (wrapper, version) = self
-                .wrappers
-                .remove(group_id)
-                .unwrap_or((Wrapper::Forming(Inner::new(self.storage.clone())), None));
+            let (mut wrapper, version) = self.wrappers.remove(group_id).unwrap_or((
+                Wrapper::Forming(Inner::new(self.storage.clone(), self.clock.now())),
+                None,
+            ));
 
             debug!(?group_id, ?wrapper, ?version, ?iteration);
 
-            let now = SystemTime::now();
-
-            let (mut wrapper, body) = wrapper
-                .heartbeat(now, group_id, generation_id, member_id, group_instance_id)
-                .await;
+            let now = self.clock.now();
 
+            // expire members before the heartbeat, so that an evicted member
+            // is told it is unknown
             if group_instance_id.is_none() {
                 wrapper = wrapper.missed_heartbeat(group_id, now);
             }
 
+            let (wrapper, body) = wrapper
+                .heartbeat(now, group_id, generation_id, member_id, group_instance_id)
+                .await;
+
             debug!(group_id, ?wrapper, ?version, iteration,);
 
             match self
@@ -1261,6 +1595,7 @@ pub struct Inner<O, S> {
     storage: O,
     skip_assignment: Option<bool>,
     inception: SystemTime,
+    group_type: GroupType,
 }
 
 impl<O, S> PartialEq for Inner<O, S>
@@ -1275,6 +1610,7 @@ where
             && self.state == other.state
             && self.skip_assignment == other.skip_assignment
             && self.inception == other.inception
+            && self.group_type == other.group_type
     }
 }
 
@@ -1290,6 +1626,7 @@ where
         self.state.hash(state);
         self.skip_assignment.hash(state);
         self.inception.hash(state);
+        self.group_type.hash(state);
     }
 }
 
@@ -1297,7 +1634,7 @@ impl<O> Inner<O, PhantomData<Forming>>
 where
     O: Storage,
 {
-    pub fn new(storage: O) -> Inner<O, Forming> {
+    pub fn new(storage: O, inception: SystemTime) -> Inner<O, Forming> {
         Inner {
             session_timeout_ms: Default::default(),
             rebalance_timeout_ms: Default::default(),
@@ -1306,7 +1643,8 @@ where
             state: Forming::default(),
             skip_assignment: Some(false),
             storage,
-            inception: SystemTime::now(),
+            inception,
+            group_type: GroupType::default(),
         }
     }
 }
@@ -1354,6 +1692,30 @@ where
                 })
         });
 
+        let rebalance_timeout_ms = self.rebalance_timeout_ms;
+
+        self.members.retain(|member_id, member| {
+            if member.missed_sync(now, rebalance_timeout_ms) {
+                info!(
+                    "missed sync for {member_id} for {group_id} in generation: {}",
+                    self.generation_id
+                );
+
+                if self
+                    .state
+                    .leader
+                    .as_ref()
+                    .is_some_and(|leader| leader == member_id)
+                {
+                    _ = self.state.leader.take();
+                }
+
+                false
+            } else {
+                true
+            }
+        });
+
         original > self.members.len()
     }
 }
@@ -1395,6 +1757,21 @@ where
                 })
         });
 
+        let rebalance_timeout_ms = self.rebalance_timeout_ms;
+
+        self.members.retain(|member_id, member| {
+            if member.missed_sync(now, rebalance_timeout_ms) {
+                info!(
+                    "missed sync for {member_id} for {group_id} in generation: {}",
+                    self.generation_id
+                );
+
+                false
+            } else {
+                true
+            }
+        });
+
         original > self.members.len()
     }
 }
@@ -1404,6 +1781,90 @@ where
     O: Storage,
     S: Debug,
 {
+    // members leave by member id, or when static by their instance id. a static
+    // member is fenced when its instance id belongs to a different member id
+    fn remove_members(
+        &mut self,
+        member_id: Option<&str>,
+        members: Option<&[MemberIdentity]>,
+    ) -> Vec<MemberResponse> {
+        if let Some(member_id) = member_id {
+            debug!(member_id);
+
+            return vec![MemberResponse {
+                member_id: member_id.to_owned(),
+                group_instance_id: None,
+                error_code: if self.members.remove(member_id).is_some() {
+                    ErrorCode::None.into()
+                } else {
+                    ErrorCode::UnknownMemberId.into()
+                },
+            }];
+        }
+
+        members
+            .unwrap_or_default()
+            .iter()
+            .map(|member| MemberResponse {
+                member_id: member.member_id.clone(),
+                group_instance_id: member.group_instance_id.clone(),
+                error_code: self.remove_member(member).into(),
+            })
+            .collect()
+    }
+
+    fn remove_member(&mut self, member: &MemberIdentity) -> ErrorCode {
+        let Some(group_instance_id) = member.group_instance_id.as_deref() else {
+            return if self.members.remove(&member.member_id).is_some() {
+                ErrorCode::None
+            } else {
+                ErrorCode::UnknownMemberId
+            };
+        };
+
+        let Some(id) = self
+            .members
+            .iter()
+            .find(|(_, existing)| {
+                existing.join_response.group_instance_id.as_deref() == Some(group_instance_id)
+            })
+            .map(|(id, _)| id.to_owned())
+        else {
+            debug!(group_instance_id, member.member_id);
+            return ErrorCode::UnknownMemberId;
+        };
+
+        if !member.member_id.is_empty() && member.member_id != id {
+            debug!(group_instance_id, member.member_id, id);
+            return ErrorCode::FencedInstanceId;
+        }
+
+        _ = self.members.remove(&id);
+        ErrorCode::None
+    }
+
+    // with require stable, the partitions with a transactional offset commit
+    // pending, answered with an unstable offset commit that the client
+    // retries, as the commit may yet be rolled back
+    async fn unstable(
+        &mut self,
+        group_id: &str,
+        offsets: &BTreeMap<Topition, i64>,
+        require_stable: Option<bool>,
+    ) -> Result<BTreeSet<Topition>> {
+        if !require_stable.unwrap_or_default() {
+            return Ok(BTreeSet::new());
+        }
+
+        let topitions = offsets.keys().cloned().collect::<Vec<_>>();
+
+        self.storage
+            .pending_offset_commits(group_id, &topitions)
+            .await
+            .inspect(|unstable| debug!(group_id, ?unstable))
+            .map_err(Into::into)
+    }
+
     async fn fetch_offset(
         &mut self,
         group_id: Option<&str>,
@@ -1413,7 +1874,7 @@ where
     ) -> Result<Body> {
         debug!(?group_id, ?topics, ?groups, ?require_stable);
 
-        let topics = if let Some(topics) = topics {
+        let offsets = if let Some(topics) = topics {
             let topics: Vec<Topition> = topics
                 .iter()
                 .flat_map(|topic| {
@@ -1435,51 +1896,74 @@ where
             self.storage
                 .offset_fetch(group_id, topics.deref(), require_stable)
                 .await
-                .map(|offsets| {
-                    offsets
-                        .iter()
-                        .fold(BTreeSet::new(), |mut topics, (topition, _)| {
-                            _ = topics.insert(topition.topic());
-                            topics
-                        })
-                        .iter()
-                        .map(|topic_name| OffsetFetchResponseTopic {
-                            name: (*topic_name).into(),
-                            partitions: Some(
-                                offsets
-                                    .iter()
-                                    .filter_map(|(topition, offset)| {
-                                        if topition.topic() == *topic_name {
-                                            Some(OffsetFetchResponsePartition {
-                                                partition_index: topition.partition(),
-                                                committed_offset: *offset,
-                                                committed_leader_epoch: None,
-                                                metadata: None,
-                                                error_code: ErrorCode::None.into(),
-                                            })
-                                        } else {
-                                            None
-                                        }
-                                    })
-                                    .collect(),
-                            ),
-                        })
-                        .collect()
-                })
+                .map(Some)?
+        } else if let Some(group_id) = group_id {
+            // null topics fetches every partition with a committed offset
+            self.storage
+                .committed_offset_topitions(group_id)
+                .await
                 .map(Some)?
         } else {
             None
         };
 
-        let groups = if let Some(groups) = groups {
-            let mut responses = vec![];
-
-            for group in groups {
-                debug!(?group);
+        let unstable = if let (Some(group_id), Some(offsets)) = (group_id, offsets.as_ref()) {
+            self.unstable(group_id, offsets, require_stable).await?
+        } else {
+            BTreeSet::new()
+        };
 
-                let response = if let Some(topics) = group.topics.as_ref().map(|topics| {
+        let topics = offsets.map(|offsets| {
+            offsets
+                .iter()
+                .fold(BTreeSet::new(), |mut topics, (topition, _)| {
+                    _ = topics.insert(topition.topic());
                     topics
-                        .iter()
+                })
+                .iter()
+                .map(|topic_name| OffsetFetchResponseTopic {
+                    name: (*topic_name).into(),
+                    partitions: Some(
+                        offsets
+                            .iter()
+                            .filter_map(|(topition, offset)| {
+                                if topition.topic() == *topic_name {
+                                    Some(if unstable.contains(topition) {
+                                        OffsetFetchResponsePartition {
+                                            partition_index: topition.partition(),
+                                            committed_offset: -1,
+                                            committed_leader_epoch: None,
+                                            metadata: None,
+                                            error_code: ErrorCode::UnstableOffsetCommit.into(),
+                                        }
+                                    } else {
+                                        OffsetFetchResponsePartition {
+                                            partition_index: topition.partition(),
+                                            committed_offset: *offset,
+                                            committed_leader_epoch: None,
+                                            metadata: None,
+                                            error_code: ErrorCode::None.into(),
+                                        }
+                                    })
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect(),
+                    ),
+                })
+                .collect()
+        });
+
+        let groups = if let Some(groups) = groups {
+            let mut responses = vec![];
+
+            for group in groups {
+                debug!(?group);
+
+                let offsets = if let Some(topics) = group.topics.as_ref().map(|topics| {
+                    topics
+                        .iter()
                         .flat_map(|topic| {
                             topic
                                 .partition_indexes
@@ -1511,8 +1995,13 @@ where
                         .await
                         .inspect(|offsets| debug!(?offsets))
                         .inspect_err(|err| error!(?err, ?group))
-                }
-                .map(|offsets| OffsetFetchResponseGroup {
+                }?;
+
+                let unstable = self
+                    .unstable(&group.group_id, &offsets, require_stable)
+                    .await?;
+
+                let response = OffsetFetchResponseGroup {
                     group_id: group.group_id.clone(),
                     topics: Some(
                         offsets
@@ -1529,12 +2018,23 @@ where
                                         .iter()
                                         .filter_map(|(topition, offset)| {
                                             if topition.topic() == *topic_name {
-                                                Some(OffsetFetchResponsePartitions {
-                                                    partition_index: topition.partition(),
-                                                    committed_offset: *offset,
-                                                    committed_leader_epoch: -1,
-                                                    metadata: None,
-                                                    error_code: ErrorCode::None.into(),
+                                                Some(if unstable.contains(topition) {
+                                                    OffsetFetchResponsePartitions {
+                                                        partition_index: topition.partition(),
+                                                        committed_offset: -1,
+                                                        committed_leader_epoch: -1,
+                                                        metadata: None,
+                                                        error_code: ErrorCode::UnstableOffsetCommit
+                                                            .into(),
+                                                    }
+                                                } else {
+                                                    OffsetFetchResponsePartitions {
+                                                        partition_index: topition.partition(),
+                                                        committed_offset: *offset,
+                                                        committed_leader_epoch: -1,
+                                                        metadata: None,
+                                                        error_code: ErrorCode::None.into(),
+                                                    }
                                                 })
                                             } else {
                                                 None
@@ -1546,7 +2046,7 @@ where
                             .collect(),
                     ),
                     error_code: ErrorCode::None.into(),
-                })?;
+                };
 
                 responses.push(response);
             }
@@ -1650,12 +2150,222 @@ where
             })
         }
     }
+
+    // a commit from outside of the group, without a generation or member id,
+    // is only accepted by an empty group. a static member is fenced when its
+    // instance id belongs to a different member id
+    fn offset_commit_member_error(&self, detail: &OffsetCommit<'_>) -> Option<ErrorCode> {
+        let generation_id = detail.generation_id_or_member_epoch.unwrap_or(-1);
+        let member_id = detail.member_id.unwrap_or_default();
+
+        if generation_id < 0 && member_id.is_empty() && detail.group_instance_id.is_none() {
+            return (!self.members.is_empty()).then_some(ErrorCode::UnknownMemberId);
+        }
+
+        if detail.group_instance_id.is_some_and(|group_instance_id| {
+            self.members.iter().any(|(id, member)| {
+                member.join_response.group_instance_id.as_deref() == Some(group_instance_id)
+                    && id != member_id
+            })
+        }) {
+            return Some(ErrorCode::FencedInstanceId);
+        }
+
+        (!self.members.contains_key(member_id)).then_some(ErrorCode::UnknownMemberId)
+    }
+
+    // a consumer protocol group member commits with its member epoch
+    // rather than the generation, any other epoch is stale
+    fn offset_commit_member_epoch_error(&self, detail: &OffsetCommit<'_>) -> Option<ErrorCode> {
+        detail
+            .member_id
+            .and_then(|member_id| self.members.get(member_id))
+            .filter(|member| member.member_epoch != detail.generation_id_or_member_epoch)
+            .and(Some(ErrorCode::StaleMemberEpoch))
+    }
+}
+
+// an empty consumer protocol group, with the group epoch kept as the
+// generation
+fn consumer_group(now: SystemTime) -> GroupDetail {
+    GroupDetail {
+        session_timeout_ms: CONSUMER_SESSION_TIMEOUT_MS,
+        rebalance_timeout_ms: None,
+        members: BTreeMap::new(),
+        generation_id: 0,
+        skip_assignment: None,
+        inception: now,
+        state: GroupState::Formed {
+            protocol_type: "consumer".into(),
+            protocol_name: "uniform".into(),
+            leader: "".into(),
+            assignments: BTreeMap::new(),
+        },
+        group_type: GroupType::Consumer,
+    }
+}
+
+// applies a heartbeat to a consumer protocol group, returning the member id
+// and epoch. A member joins with epoch 0 and leaves with epoch -1, while
+// any change in membership or subscription bumps the group epoch. There
+// is no revocation, a member moves to the group epoch on its next heartbeat
+fn member_heartbeat(
+    group: &mut GroupDetail,
+    now: SystemTime,
+    heartbeat: &ConsumerGroupHeartbeat<'_>,
+) -> Result<(String, i32), ErrorCode> {
+    // the type of a group is fixed when it is created
+    if group.group_type != GroupType::Consumer {
+        return Err(ErrorCode::GroupIdNotFound);
+    }
+
+    let session_timeout = Duration::from_millis(group.session_timeout_ms as u64);
+    let before = group.members.len();
+
+    group.members.retain(|_, member| {
+        member.last_contact.is_some_and(|last_contact| {
+            now.duration_since(last_contact).unwrap_or_default() < session_timeout
+        })
+    });
+
+    if group.members.len() < before {
+        group.generation_id += 1;
+    }
+
+    match heartbeat.member_epoch {
+        JOIN_GROUP_MEMBER_EPOCH => {
+            let Some(subscribed_topic_names) = heartbeat.subscribed_topic_names else {
+                return Err(ErrorCode::InvalidRequest);
+            };
+
+            let member_id = if heartbeat.member_id.is_empty() {
+                Uuid::new_v4().to_string()
+            } else {
+                heartbeat.member_id.to_owned()
+            };
+
+            group.generation_id += 1;
+
+            _ = group.members.insert(
+                member_id.clone(),
+                GroupMember {
+                    join_response: JoinGroupResponseMember {
+                        member_id: member_id.clone(),
+                        group_instance_id: None,
+                        metadata: Bytes::new(),
+                    },
+                    last_contact: Some(now),
+                    member_epoch: Some(group.generation_id),
+                    subscribed_topic_names: Some(subscribed_topic_names.to_vec()),
+                    ..Default::default()
+                },
+            );
+
+            Ok((member_id, group.generation_id))
+        }
+
+        LEAVE_GROUP_MEMBER_EPOCH => {
+            if group.members.remove(heartbeat.member_id).is_some() {
+                group.generation_id += 1;
+            }
+
+            Ok((heartbeat.member_id.to_owned(), LEAVE_GROUP_MEMBER_EPOCH))
+        }
+
+        member_epoch if member_epoch < 0 => Err(ErrorCode::InvalidRequest),
+
+        member_epoch => {
+            let generation_id = group.generation_id;
+
+            let Some(member) = group.members.get_mut(heartbeat.member_id) else {
+                return Err(ErrorCode::UnknownMemberId);
+            };
+
+            if member.member_epoch != Some(member_epoch) {
+                return Err(ErrorCode::FencedMemberEpoch);
+            }
+
+            member.last_contact = Some(now);
+
+            let resubscribed = heartbeat
+                .subscribed_topic_names
+                .filter(|subscribed| member.subscribed_topic_names.as_deref() != Some(*subscribed))
+                .map(ToOwned::to_owned);
+
+            if resubscribed.is_some() {
+                member.subscribed_topic_names = resubscribed;
+                member.member_epoch = Some(generation_id + 1);
+                group.generation_id += 1;
+            } else {
+                member.member_epoch = Some(generation_id);
+            }
+
+            Ok((heartbeat.member_id.to_owned(), group.generation_id))
+        }
+    }
+}
+
+fn consumer_group_heartbeat_error(member_id: &str, error_code: ErrorCode) -> Body {
+    Body::ConsumerGroupHeartbeatResponse {
+        throttle_time_ms: 0,
+        error_code: error_code.into(),
+        error_message: Some(error_code.to_string()),
+        member_id: Some(member_id.into()),
+        member_epoch: -1,
+        heartbeat_interval_ms: CONSUMER_HEARTBEAT_INTERVAL_MS,
+        assignment: None,
+    }
+}
+
+fn offset_commit_error(detail: &OffsetCommit<'_>, error_code: ErrorCode) -> Body {
+    Body::OffsetCommitResponse {
+        throttle_time_ms: Some(0),
+        topics: detail.topics.map(|topics| {
+            topics
+                .as_ref()
+                .iter()
+                .map(|topic| OffsetCommitResponseTopic {
+                    name: topic.name.clone(),
+                    partitions: topic.partitions.as_ref().map(|partitions| {
+                        partitions
+                            .iter()
+                            .map(|partition| OffsetCommitResponsePartition {
+                                partition_index: partition.partition_index,
+                                error_code: error_code.into(),
+                            })
+                            .collect()
+                    }),
+                })
+                .collect()
+        }),
+    }
 }
 
 #[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Member {
     join_response: JoinGroupResponseMember,
     last_contact: Option<SystemTime>,
+    client_id: Option<String>,
+    client_host: Option<String>,
+    awaiting_sync: Option<SystemTime>,
+    member_epoch: Option<i32>,
+    subscribed_topic_names: Option<Vec<String>>,
+}
+
+impl Member {
+    // joined, but not synced within the rebalance timeout
+    fn missed_sync(&self, now: SystemTime, rebalance_timeout_ms: Option<i32>) -> bool {
+        self.awaiting_sync.zip(rebalance_timeout_ms).is_some_and(
+            |(awaiting_sync, rebalance_timeout_ms)| {
+                u128::try_from(rebalance_timeout_ms).is_ok_and(|rebalance_timeout_ms| {
+                    now.duration_since(awaiting_sync)
+                        .unwrap_or_default()
+                        .as_millis()
+                        > rebalance_timeout_ms
+                })
+            },
+        )
+    }
 }
 
 #[async_trait::async_trait]
@@ -1674,6 +2384,7 @@ where
         mut self,
         now: SystemTime,
         client_id: Option<&str>,
+        client_host: Option<&str>,
         group_id: &str,
         session_timeout_ms: i32,
         rebalance_timeout_ms: Option<i32>,
@@ -1685,6 +2396,7 @@ where
     ) -> (Self::JoinState, Body) {
         debug!(
             client_id,
+            client_host,
             group_id,
             session_timeout_ms,
             rebalance_timeout_ms,
@@ -1779,6 +2491,11 @@ where
                         metadata: protocol.metadata.clone(),
                     },
                     last_contact: Some(now),
+                    client_id: client_id.map(ToOwned::to_owned),
+                    client_host: client_host.map(ToOwned::to_owned),
+                    awaiting_sync: Some(now),
+                    member_epoch: None,
+                    subscribed_topic_names: None,
                 },
             );
 
@@ -1804,6 +2521,8 @@ where
         debug!(?member_id, ?self.members);
 
         if let Some(member) = self.members.get_mut(&member_id) {
+            _ = member.awaiting_sync.replace(now);
+
             if member.join_response.metadata == protocol.metadata {
                 debug!(
                     member_metadata = "existing",
@@ -1852,6 +2571,11 @@ where
                         metadata: protocol.metadata.clone(),
                     },
                     last_contact: Some(now),
+                    client_id: client_id.map(ToOwned::to_owned),
+                    client_host: client_host.map(ToOwned::to_owned),
+                    awaiting_sync: Some(now),
+                    member_epoch: None,
+                    subscribed_topic_names: None,
                 },
             );
         }
@@ -2020,6 +2744,11 @@ where
 
         debug!(sync_outcome = ?ErrorCode::None, sync_assignment = assignments.contains_key(member_id));
 
+        _ = self
+            .members
+            .entry(member_id.to_owned())
+            .and_modify(|member| _ = member.awaiting_sync.take());
+
         let state = Inner {
             session_timeout_ms: self.session_timeout_ms,
             rebalance_timeout_ms: self.rebalance_timeout_ms,
@@ -2035,6 +2764,7 @@ where
             storage: self.storage,
             skip_assignment: self.skip_assignment,
             inception: self.inception,
+            group_type: self.group_type,
         };
 
         (state.into(), body)
@@ -2117,38 +2847,7 @@ where
         let _ = now;
         debug!(?group_id, member_id, ?members);
 
-        let members = if let Some(member_id) = member_id {
-            debug!(member_id);
-
-            vec![MemberResponse {
-                member_id: member_id.to_owned(),
-                group_instance_id: None,
-                error_code: {
-                    if self.members.remove(member_id).is_some() {
-                        ErrorCode::None.into()
-                    } else {
-                        ErrorCode::UnknownMemberId.into()
-                    }
-                },
-            }]
-        } else {
-            members.map_or(vec![], |members| {
-                members
-                    .iter()
-                    .map(|member| MemberResponse {
-                        member_id: member.member_id.clone(),
-                        group_instance_id: member.group_instance_id.clone(),
-                        error_code: {
-                            if self.members.remove(&member.member_id).is_some() {
-                                ErrorCode::None.into()
-                            } else {
-                                ErrorCode::UnknownMemberId.into()
-                            }
-                        },
-                    })
-                    .collect::<Vec<MemberResponse>>()
-            })
-        };
+        let members = self.remove_members(member_id, members);
 
         if members.iter().any(|member| {
             let error_code = i16::from(ErrorCode::None);
@@ -2177,34 +2876,29 @@ where
         let _ = now;
         debug!(?detail);
 
+        let error_code = self.offset_commit_member_error(detail).or_else(|| {
+            if self.group_type == GroupType::Consumer {
+                self.offset_commit_member_epoch_error(detail)
+            } else {
+                detail
+                    .generation_id_or_member_epoch
+                    .filter(|generation_id| *generation_id != self.generation_id)
+                    .and(Some(ErrorCode::IllegalGeneration))
+            }
+        });
+
+        if let Some(error_code) = error_code {
+            debug!(?error_code, generation_id = self.generation_id);
+            let body = offset_commit_error(detail, error_code);
+            return (self, body);
+        }
+
         match self.commit_offset(detail).await {
             Ok(body) => (self, body),
             Err(reason) => {
                 debug!(?reason);
-                (
-                    self,
-                    Body::OffsetCommitResponse {
-                        throttle_time_ms: Some(0),
-                        topics: detail.topics.map(|topics| {
-                            topics
-                                .as_ref()
-                                .iter()
-                                .map(|topic| OffsetCommitResponseTopic {
-                                    name: topic.name.clone(),
-                                    partitions: topic.partitions.as_ref().map(|partitions| {
-                                        partitions
-                                            .iter()
-                                            .map(|partition| OffsetCommitResponsePartition {
-                                                partition_index: partition.partition_index,
-                                                error_code: ErrorCode::UnknownMemberId.into(),
-                                            })
-                                            .collect()
-                                    }),
-                                })
-                                .collect()
-                        }),
-                    },
-                )
+                let body = offset_commit_error(detail, ErrorCode::UnknownMemberId);
+                (self, body)
             }
         }
     }
@@ -2248,6 +2942,7 @@ where
         mut self,
         now: SystemTime,
         client_id: Option<&str>,
+        client_host: Option<&str>,
         group_id: &str,
         session_timeout_ms: i32,
         rebalance_timeout_ms: Option<i32>,
@@ -2259,6 +2954,7 @@ where
     ) -> (Self::JoinState, Body) {
         debug!(
             client_id,
+            client_host,
             group_id,
             session_timeout_ms,
             rebalance_timeout_ms,
@@ -2337,6 +3033,11 @@ where
                         metadata: protocol.metadata.clone(),
                     },
                     last_contact: Some(now),
+                    client_id: client_id.map(ToOwned::to_owned),
+                    client_host: client_host.map(ToOwned::to_owned),
+                    awaiting_sync: Some(now),
+                    member_epoch: None,
+                    subscribed_topic_names: None,
                 },
             );
 
@@ -2355,6 +3056,7 @@ where
                     storage: self.storage,
                     skip_assignment: self.skip_assignment,
                     inception: self.inception,
+                    group_type: self.group_type,
                 }
                 .into(),
                 body,
@@ -2377,6 +3079,11 @@ where
 
         debug!(?member_id, ?self.members);
 
+        _ = self
+            .members
+            .entry(member_id.clone())
+            .and_modify(|member| _ = member.awaiting_sync.replace(now));
+
         match self.members.get_mut(&member_id) {
             Some(Member {
                 join_response: JoinGroupResponseMember { metadata, .. },
@@ -2453,6 +3160,7 @@ where
                     storage: self.storage,
                     skip_assignment: self.skip_assignment,
                     inception: self.inception,
+                    group_type: self.group_type,
                 }
                 .into();
 
@@ -2504,6 +3212,11 @@ where
                             metadata: protocol.metadata.clone(),
                         },
                         last_contact: Some(now),
+                        client_id: client_id.map(ToOwned::to_owned),
+                        client_host: client_host.map(ToOwned::to_owned),
+                        awaiting_sync: Some(now),
+                        member_epoch: None,
+                        subscribed_topic_names: None,
                     },
                 );
 
@@ -2521,6 +3234,7 @@ where
                     storage: self.storage,
                     skip_assignment: self.skip_assignment,
                     inception: self.inception,
+                    group_type: self.group_type,
                 }
                 .into();
 
@@ -2635,6 +3349,11 @@ where
 
         debug!(sync_outcome = ?ErrorCode::None, sync_assignment = self.state.assignments.contains_key(member_id));
 
+        _ = self
+            .members
+            .entry(member_id.to_owned())
+            .and_modify(|member| _ = member.awaiting_sync.take());
+
         (self, body)
     }
 
@@ -2701,36 +3420,7 @@ where
         let _ = now;
         let _ = group_id;
 
-        let members = if let Some(member_id) = member_id {
-            vec![MemberResponse {
-                member_id: member_id.to_owned(),
-                group_instance_id: None,
-                error_code: {
-                    if self.members.remove(member_id).is_some() {
-                        ErrorCode::None.into()
-                    } else {
-                        ErrorCode::UnknownMemberId.into()
-                    }
-                },
-            }]
-        } else {
-            members.map_or(vec![], |members| {
-                members
-                    .iter()
-                    .map(|member| MemberResponse {
-                        member_id: member.member_id.clone(),
-                        group_instance_id: member.group_instance_id.clone(),
-                        error_code: {
-                            if self.members.remove(&member.member_id).is_some() {
-                                ErrorCode::None.into()
-                            } else {
-                                ErrorCode::UnknownMemberId.into()
-                            }
-                        },
-                    })
-                    .collect::<Vec<MemberResponse>>()
-            })
-        };
+        let members = self.remove_members(member_id, members);
 
         let state: Wrapper<O> = if members
             .iter()
@@ -2756,6 +3446,7 @@ where
                 storage: self.storage,
                 skip_assignment: self.skip_assignment,
                 inception: self.inception,
+                group_type: self.group_type,
             }
             .into()
         } else {
@@ -2779,35 +3470,29 @@ where
         detail: &OffsetCommit<'_>,
     ) -> (Self::OffsetCommitState, Body) {
         let _ = now;
+        debug!(?detail);
+
+        let error_code = self.offset_commit_member_error(detail).or_else(|| {
+            if self.group_type == GroupType::Consumer {
+                self.offset_commit_member_epoch_error(detail)
+            } else {
+                (detail.generation_id_or_member_epoch.unwrap_or(-1) != self.generation_id)
+                    .then_some(ErrorCode::IllegalGeneration)
+            }
+        });
+
+        if let Some(error_code) = error_code {
+            debug!(?error_code, generation_id = self.generation_id);
+            let body = offset_commit_error(detail, error_code);
+            return (self, body);
+        }
 
         match self.commit_offset(detail).await {
             Ok(body) => (self, body),
             Err(reason) => {
                 debug!(?reason);
-                (
-                    self,
-                    Body::OffsetCommitResponse {
-                        throttle_time_ms: Some(0),
-                        topics: detail.topics.map(|topics| {
-                            topics
-                                .as_ref()
-                                .iter()
-                                .map(|topic| OffsetCommitResponseTopic {
-                                    name: topic.name.clone(),
-                                    partitions: topic.partitions.as_ref().map(|partitions| {
-                                        partitions
-                                            .iter()
-                                            .map(|partition| OffsetCommitResponsePartition {
-                                                partition_index: partition.partition_index,
-                                                error_code: ErrorCode::UnknownMemberId.into(),
-                                            })
-                                            .collect()
-                                    }),
-                                })
-                                .collect()
-                        }),
-                    },
-                )
+                let body = offset_commit_error(detail, ErrorCode::UnknownMemberId);
+                (self, body)
             }
         }
     }
@@ -2840,10 +3525,11 @@ mod tests {
     use super::*;
     use object_store::memory::InMemory;
     use pretty_assertions::assert_eq;
-    use tansu_kafka_sans_io::offset_commit_request::{
-        OffsetCommitRequestPartition, OffsetCommitRequestTopic,
+    use tansu_kafka_sans_io::{
+        create_topics_request::CreatableTopic,
+        offset_commit_request::{OffsetCommitRequestPartition, OffsetCommitRequestTopic},
     };
-    use tansu_storage::dynostore::DynoStore;
+    use tansu_storage::{clock::ManualClock, dynostore::DynoStore};
     use tracing::subscriber::DefaultGuard;
 
     #[cfg(miri)]
@@ -2918,6 +3604,7 @@ mod tests {
         let first_member_id = match s
             .join(
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -2950,6 +3637,7 @@ mod tests {
                 let join_response = s
                     .join(
                         Some(CLIENT_ID),
+                        None,
                         GROUP_ID,
                         session_timeout_ms,
                         rebalance_timeout_ms,
@@ -3041,6 +3729,7 @@ mod tests {
         let second_member_id = match s
             .join(
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -3072,6 +3761,7 @@ mod tests {
                 let join_response = s
                     .join(
                         Some(CLIENT_ID),
+                        None,
                         GROUP_ID,
                         session_timeout_ms,
                         rebalance_timeout_ms,
@@ -3122,7 +3812,7 @@ mod tests {
                             (0..=2)
                                 .map(|partition_index| OffsetCommitResponsePartition {
                                     partition_index,
-                                    error_code: ErrorCode::UnknownTopicOrPartition.into(),
+                                    error_code: ErrorCode::IllegalGeneration.into(),
                                 })
                                 .collect(),
                         ),
@@ -3172,6 +3862,7 @@ mod tests {
             match s
                 .join(
                     Some(CLIENT_ID),
+                    None,
                     GROUP_ID,
                     session_timeout_ms,
                     rebalance_timeout_ms,
@@ -3237,6 +3928,7 @@ mod tests {
             match s
                 .join(
                     Some(CLIENT_ID),
+                    None,
                     GROUP_ID,
                     session_timeout_ms,
                     rebalance_timeout_ms,
@@ -3414,6 +4106,7 @@ mod tests {
                 },
                 s.join(
                     Some(CLIENT_ID),
+                    None,
                     GROUP_ID,
                     session_timeout_ms,
                     rebalance_timeout_ms,
@@ -3497,6 +4190,7 @@ mod tests {
         let first_member_id = match s
             .join(
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -3544,6 +4238,7 @@ mod tests {
                     },
                     s.join(
                         Some(CLIENT_ID),
+                        None,
                         GROUP_ID,
                         session_timeout_ms,
                         rebalance_timeout_ms,
@@ -3579,6 +4274,7 @@ mod tests {
         let second_member_id = match s
             .join(
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -3619,6 +4315,7 @@ mod tests {
                     },
                     s.join(
                         Some(CLIENT_ID),
+                        None,
                         GROUP_ID,
                         session_timeout_ms,
                         rebalance_timeout_ms,
@@ -3640,6 +4337,7 @@ mod tests {
         match s
             .join(
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -3697,6 +4395,7 @@ mod tests {
             },
             s.join(
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -3768,6 +4467,7 @@ mod tests {
             .join(
                 now,
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -3860,6 +4560,7 @@ mod tests {
             .join(
                 now,
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -3974,6 +4675,7 @@ mod tests {
             .join(
                 now,
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -4011,6 +4713,7 @@ mod tests {
             .join(
                 now,
                 Some(CLIENT_ID),
+                None,
                 GROUP_ID,
                 session_timeout_ms,
                 rebalance_timeout_ms,
@@ -4139,4 +4842,748 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn offset_commit_member_epoch() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        const GROUP_ID: &str = "test-consumer-group";
+        const TOPIC: &str = "test";
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: TOPIC.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let mut s = Controller::with_storage(storage)?;
+
+        let subscribed = [TOPIC.to_owned()];
+
+        let heartbeat = async |s: &mut Controller<DynoStore>,
+                               member_id: &str,
+                               member_epoch: i32|
+               -> Result<(String, i32)> {
+            let Body::ConsumerGroupHeartbeatResponse {
+                error_code,
+                member_id,
+                member_epoch,
+                ..
+            } = s
+                .consumer_group_heartbeat(ConsumerGroupHeartbeat {
+                    group_id: GROUP_ID,
+                    member_id,
+                    member_epoch,
+                    subscribed_topic_names: Some(&subscribed),
+                })
+                .await?
+            else {
+                panic!("expecting a consumer group heartbeat response")
+            };
+
+            assert_eq!(i16::from(ErrorCode::None), error_code);
+            Ok((member_id.unwrap_or_default(), member_epoch))
+        };
+
+        let topics = [OffsetCommitRequestTopic {
+            name: TOPIC.into(),
+            partitions: Some(vec![OffsetCommitRequestPartition {
+                partition_index: 0,
+                committed_offset: 6,
+                committed_leader_epoch: Some(0),
+                commit_timestamp: None,
+                committed_metadata: Some("".into()),
+            }]),
+        }];
+
+        let (member_id, member_epoch) = heartbeat(&mut s, "", 0).await?;
+        assert_eq!(1, member_epoch);
+
+        // another member joining moves the group epoch on, but not the
+        // epoch of this member until its next heartbeat
+        let (_, group_epoch) = heartbeat(&mut s, "", 0).await?;
+        assert_eq!(2, group_epoch);
+
+        for (generation_id_or_member_epoch, expected) in [
+            (member_epoch - 1, ErrorCode::StaleMemberEpoch),
+            (group_epoch, ErrorCode::StaleMemberEpoch),
+            (member_epoch, ErrorCode::None),
+        ] {
+            let detail = OffsetCommit {
+                group_id: GROUP_ID,
+                generation_id_or_member_epoch: Some(generation_id_or_member_epoch),
+                member_id: Some(member_id.as_str()),
+                group_instance_id: None,
+                retention_time_ms: None,
+                topics: Some(&topics),
+            };
+
+            assert_eq!(
+                offset_commit_error(&detail, expected),
+                s.offset_commit(detail).await?,
+                "{generation_id_or_member_epoch}"
+            );
+        }
+
+        assert_eq!(
+            (member_id.clone(), group_epoch),
+            heartbeat(&mut s, &member_id, member_epoch).await?
+        );
+
+        for (generation_id_or_member_epoch, expected) in [
+            (member_epoch, ErrorCode::StaleMemberEpoch),
+            (group_epoch, ErrorCode::None),
+        ] {
+            let detail = OffsetCommit {
+                group_id: GROUP_ID,
+                generation_id_or_member_epoch: Some(generation_id_or_member_epoch),
+                member_id: Some(member_id.as_str()),
+                group_instance_id: None,
+                retention_time_ms: None,
+                topics: Some(&topics),
+            };
+
+            assert_eq!(
+                offset_commit_error(&detail, expected),
+                s.offset_commit(detail).await?,
+                "{generation_id_or_member_epoch}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn consumer_group_heartbeat() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        const GROUP_ID: &str = "test-consumer-group";
+        const TOPIC: &str = "test";
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: TOPIC.into(),
+                    num_partitions: 4,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let mut s = Controller::with_storage(storage)?;
+
+        let subscribed = [TOPIC.to_owned()];
+
+        let heartbeat = async |s: &mut Controller<DynoStore>,
+                               member_id: &str,
+                               member_epoch: i32,
+                               subscribed_topic_names: Option<&[String]>|
+               -> Result<(i16, String, i32, Vec<i32>)> {
+            let Body::ConsumerGroupHeartbeatResponse {
+                error_code,
+                member_id,
+                member_epoch,
+                assignment,
+                ..
+            } = s
+                .consumer_group_heartbeat(ConsumerGroupHeartbeat {
+                    group_id: GROUP_ID,
+                    member_id,
+                    member_epoch,
+                    subscribed_topic_names,
+                })
+                .await?
+            else {
+                panic!("expecting a consumer group heartbeat response")
+            };
+
+            let partitions = assignment
+                .and_then(|assignment| assignment.topic_partitions)
+                .unwrap_or_default()
+                .into_iter()
+                .flat_map(|topic| topic.partitions.unwrap_or_default())
+                .collect();
+
+            Ok((
+                error_code,
+                member_id.unwrap_or_default(),
+                member_epoch,
+                partitions,
+            ))
+        };
+
+        // the first member joins, creating the group and owning every partition
+        let (error_code, a, epoch, partitions) =
+            heartbeat(&mut s, "", 0, Some(&subscribed)).await?;
+        assert_eq!(i16::from(ErrorCode::None), error_code);
+        assert!(!a.is_empty());
+        assert_eq!(1, epoch);
+        assert_eq!(vec![0, 1, 2, 3], partitions);
+
+        // a second member joins, bumping the group epoch
+        let (error_code, b, epoch, partitions) =
+            heartbeat(&mut s, "", 0, Some(&subscribed)).await?;
+        assert_eq!(i16::from(ErrorCode::None), error_code);
+        assert_ne!(a, b);
+        assert_eq!(2, epoch);
+        assert_eq!(2, partitions.len());
+
+        // the first member catches up with the group epoch
+        let (error_code, _, epoch, partitions) = heartbeat(&mut s, &a, 1, None).await?;
+        assert_eq!(i16::from(ErrorCode::None), error_code);
+        assert_eq!(2, epoch);
+        assert_eq!(2, partitions.len());
+
+        // a stale member epoch is fenced
+        let (error_code, ..) = heartbeat(&mut s, &a, 1, None).await?;
+        assert_eq!(i16::from(ErrorCode::FencedMemberEpoch), error_code);
+
+        // a classic member cannot join a consumer protocol group
+        let Body::JoinGroupResponse { error_code, .. } = s
+            .join(
+                None,
+                None,
+                GROUP_ID,
+                45_000,
+                None,
+                "",
+                None,
+                "consumer",
+                Some(&[JoinGroupRequestProtocol {
+                    name: "range".into(),
+                    metadata: Bytes::new(),
+                }]),
+                None,
+            )
+            .await?
+        else {
+            panic!("expecting a join group response")
+        };
+        assert_eq!(i16::from(ErrorCode::InconsistentGroupProtocol), error_code);
+
+        // after the first member leaves, the second owns every partition
+        let (error_code, _, epoch, _) = heartbeat(&mut s, &a, -1, None).await?;
+        assert_eq!(i16::from(ErrorCode::None), error_code);
+        assert_eq!(-1, epoch);
+
+        let (error_code, _, _, partitions) = heartbeat(&mut s, &b, 2, None).await?;
+        assert_eq!(i16::from(ErrorCode::None), error_code);
+        assert_eq!(vec![0, 1, 2, 3], partitions);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn offset_commit_generation() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let session_timeout_ms = 45_000;
+        let rebalance_timeout_ms = Some(300_000);
+        let group_instance_id = Some("instance-1");
+        let reason = None;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        const CLIENT_ID: &str = "console-consumer";
+        const GROUP_ID: &str = "test-consumer-group";
+        const TOPIC: &str = "test";
+        const RANGE: &str = "range";
+
+        const PROTOCOL_TYPE: &str = "consumer";
+
+        let mut storage = DynoStore::new(cluster, node, InMemory::new());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: TOPIC.into(),
+                    num_partitions: 1,
+                    replication_factor: 1,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let mut s = Controller::with_storage(storage)?;
+
+        let join = async |s: &mut Controller<DynoStore>,
+                          member_id: &str,
+                          group_instance_id: Option<&str>,
+                          metadata: &'static [u8]|
+               -> Result<(i32, String)> {
+            let protocols = [JoinGroupRequestProtocol {
+                name: RANGE.into(),
+                metadata: Bytes::from_static(metadata),
+            }];
+
+            let Body::JoinGroupResponse {
+                error_code,
+                generation_id,
+                member_id,
+                ..
+            } = s
+                .join(
+                    Some(CLIENT_ID),
+                    None,
+                    GROUP_ID,
+                    session_timeout_ms,
+                    rebalance_timeout_ms,
+                    member_id,
+                    group_instance_id,
+                    PROTOCOL_TYPE,
+                    Some(&protocols[..]),
+                    reason,
+                )
+                .await?
+            else {
+                panic!("join group response")
+            };
+
+            assert!(
+                [ErrorCode::None, ErrorCode::MemberIdRequired]
+                    .contains(&ErrorCode::try_from(error_code)?)
+            );
+
+            Ok((generation_id, member_id))
+        };
+
+        let sync = async |s: &mut Controller<DynoStore>,
+                          generation_id: i32,
+                          member_id: &str,
+                          group_instance_id: Option<&str>,
+                          assignments: &[SyncGroupRequestAssignment]|
+               -> Result<()> {
+            let Body::SyncGroupResponse { error_code, .. } = s
+                .sync(
+                    GROUP_ID,
+                    generation_id,
+                    member_id,
+                    group_instance_id,
+                    Some(PROTOCOL_TYPE),
+                    Some(RANGE),
+                    Some(assignments),
+                )
+                .await?
+            else {
+                panic!("sync group response")
+            };
+
+            assert_eq!(i16::from(ErrorCode::None), error_code);
+            Ok(())
+        };
+
+        // a static member forms the group
+        let (first_generation_id, static_member_id) =
+            join(&mut s, "", group_instance_id, b"static_meta").await?;
+
+        sync(
+            &mut s,
+            first_generation_id,
+            &static_member_id,
+            group_instance_id,
+            &[SyncGroupRequestAssignment {
+                member_id: static_member_id.clone(),
+                assignment: Bytes::from_static(b"static_assignment"),
+            }],
+        )
+        .await?;
+
+        // a dynamic member joining moves the group to a later generation
+        let (_, dynamic_member_id) = join(&mut s, "", None, b"dynamic_meta").await?;
+        let (generation_id, _) = join(&mut s, &dynamic_member_id, None, b"dynamic_meta").await?;
+        assert!(generation_id > first_generation_id);
+
+        assert_eq!(
+            (generation_id, static_member_id.clone()),
+            join(&mut s, &static_member_id, group_instance_id, b"static_meta").await?
+        );
+
+        // while forming, a commit must be made in the current generation
+        let topics = [OffsetCommitRequestTopic {
+            name: TOPIC.into(),
+            partitions: Some(vec![OffsetCommitRequestPartition {
+                partition_index: 0,
+                committed_offset: 6,
+                committed_leader_epoch: Some(0),
+                commit_timestamp: None,
+                committed_metadata: Some("".into()),
+            }]),
+        }];
+
+        let forming = || OffsetCommit {
+            group_id: GROUP_ID,
+            generation_id_or_member_epoch: Some(first_generation_id),
+            member_id: Some(static_member_id.as_str()),
+            group_instance_id,
+            retention_time_ms: None,
+            topics: Some(&topics),
+        };
+
+        assert_eq!(
+            offset_commit_error(&forming(), ErrorCode::IllegalGeneration),
+            s.offset_commit(forming()).await?
+        );
+
+        sync(
+            &mut s,
+            generation_id,
+            &static_member_id,
+            group_instance_id,
+            &[
+                SyncGroupRequestAssignment {
+                    member_id: static_member_id.clone(),
+                    assignment: Bytes::from_static(b"static_assignment"),
+                },
+                SyncGroupRequestAssignment {
+                    member_id: dynamic_member_id.clone(),
+                    assignment: Bytes::from_static(b"dynamic_assignment"),
+                },
+            ],
+        )
+        .await?;
+
+        sync(&mut s, generation_id, &dynamic_member_id, None, &[]).await?;
+
+        let member_id = static_member_id;
+
+        for (generation_id, member_id, group_instance_id, expected) in [
+            (
+                generation_id - 1,
+                member_id.as_str(),
+                group_instance_id,
+                ErrorCode::IllegalGeneration,
+            ),
+            (generation_id, "unknown", None, ErrorCode::UnknownMemberId),
+            (
+                generation_id,
+                "unknown",
+                group_instance_id,
+                ErrorCode::FencedInstanceId,
+            ),
+            (-1, "", None, ErrorCode::UnknownMemberId),
+            (
+                generation_id,
+                member_id.as_str(),
+                group_instance_id,
+                ErrorCode::None,
+            ),
+        ] {
+            assert_eq!(
+                offset_commit_error(
+                    &OffsetCommit {
+                        group_id: GROUP_ID,
+                        generation_id_or_member_epoch: Some(generation_id),
+                        member_id: Some(member_id),
+                        group_instance_id,
+                        retention_time_ms: None,
+                        topics: Some(&topics),
+                    },
+                    expected
+                ),
+                s.offset_commit(OffsetCommit {
+                    group_id: GROUP_ID,
+                    generation_id_or_member_epoch: Some(generation_id),
+                    member_id: Some(member_id),
+                    group_instance_id,
+                    retention_time_ms: None,
+                    topics: Some(&topics),
+                })
+                .await?,
+                "{generation_id} {member_id} {group_instance_id:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn session_timeout_with_clock() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let session_timeout_ms = 45_000;
+        let rebalance_timeout_ms = Some(300_000);
+
+        const CLIENT_ID: &str = "console-consumer";
+        const GROUP_ID: &str = "test-consumer-group";
+        const RANGE: &str = "range";
+        const PROTOCOL_TYPE: &str = "consumer";
+
+        let clock = ManualClock::default();
+
+        let mut s = Controller::with_storage(DynoStore::new("abc", 12321, InMemory::new()))?
+            .clock(Arc::new(clock.clone()));
+
+        let protocols = [JoinGroupRequestProtocol {
+            name: RANGE.into(),
+            metadata: Bytes::from_static(b"range_meta"),
+        }];
+
+        let mut member_id = String::new();
+
+        let generation_id = loop {
+            let Body::JoinGroupResponse {
+                error_code,
+                generation_id,
+                member_id: assigned,
+                ..
+            } = s
+                .join(
+                    Some(CLIENT_ID),
+                    None,
+                    GROUP_ID,
+                    session_timeout_ms,
+                    rebalance_timeout_ms,
+                    &member_id,
+                    None,
+                    PROTOCOL_TYPE,
+                    Some(&protocols[..]),
+                    None,
+                )
+                .await?
+            else {
+                panic!("join group response")
+            };
+
+            member_id = assigned;
+
+            if error_code == i16::from(ErrorCode::None) {
+                break generation_id;
+            }
+
+            assert_eq!(i16::from(ErrorCode::MemberIdRequired), error_code);
+        };
+
+        let Body::SyncGroupResponse { error_code, .. } = s
+            .sync(
+                GROUP_ID,
+                generation_id,
+                &member_id,
+                None,
+                Some(PROTOCOL_TYPE),
+                Some(RANGE),
+                Some(
+                    &[SyncGroupRequestAssignment {
+                        member_id: member_id.clone(),
+                        assignment: Bytes::from_static(b"assignment"),
+                    }][..],
+                ),
+            )
+            .await?
+        else {
+            panic!("sync group response")
+        };
+
+        assert_eq!(i16::from(ErrorCode::None), error_code);
+
+        // within the session timeout the member remains in the group
+        clock.advance(Duration::from_millis(session_timeout_ms as u64));
+
+        assert_eq!(
+            Body::HeartbeatResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::None.into(),
+            },
+            s.heartbeat(GROUP_ID, generation_id, &member_id, None)
+                .await?
+        );
+
+        // no heartbeat for longer than the session timeout expires the member
+        clock.advance(Duration::from_millis(session_timeout_ms as u64 + 1));
+
+        assert_eq!(
+            Body::HeartbeatResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::UnknownMemberId.into(),
+            },
+            s.heartbeat(GROUP_ID, generation_id, &member_id, None)
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn static_member_leaves_by_instance_id() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = "abc";
+        let node = 12321;
+
+        const GROUP_ID: &str = "test-consumer-group";
+        const PROTOCOL_TYPE: &str = "consumer";
+        const RANGE: &str = "range";
+
+        let now = SystemTime::now();
+
+        let member = |member_id: &str, group_instance_id: &str| {
+            (
+                member_id.to_owned(),
+                GroupMember {
+                    join_response: JoinGroupResponseMember {
+                        member_id: member_id.into(),
+                        group_instance_id: Some(group_instance_id.into()),
+                        metadata: Bytes::from(format!("{group_instance_id}_range_meta")),
+                    },
+                    last_contact: Some(now),
+                    client_id: None,
+                    client_host: None,
+                    awaiting_sync: None,
+                    member_epoch: None,
+                    subscribed_topic_names: None,
+                },
+            )
+        };
+
+        let first = member("first-member", "first");
+        let second = member("second-member", "second");
+
+        let storage = DynoStore::new(cluster, node, InMemory::new());
+        let s = Wrapper::with_storage_group_detail(
+            storage,
+            GroupDetail {
+                members: [first.clone(), second.clone()].into(),
+                generation_id: 3,
+                state: GroupState::Formed {
+                    protocol_type: PROTOCOL_TYPE.into(),
+                    protocol_name: RANGE.into(),
+                    leader: first.0.clone(),
+                    assignments: [
+                        (first.0.clone(), Bytes::from_static(b"first_assignment")),
+                        (second.0.clone(), Bytes::from_static(b"second_assignment")),
+                    ]
+                    .into(),
+                },
+                ..Default::default()
+            },
+        );
+
+        // a member id that does not belong to the instance is fenced
+        let (s, body) = s
+            .leave(
+                now,
+                GROUP_ID,
+                None,
+                Some(&[MemberIdentity {
+                    member_id: "imposter".into(),
+                    group_instance_id: Some("second".into()),
+                    reason: None,
+                }]),
+            )
+            .await;
+
+        assert_eq!(
+            Body::LeaveGroupResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::None.into(),
+                members: Some(vec![MemberResponse {
+                    member_id: "imposter".into(),
+                    group_instance_id: Some("second".into()),
+                    error_code: ErrorCode::FencedInstanceId.into(),
+                }]),
+            },
+            body
+        );
+
+        assert_eq!(3, s.generation_id());
+        assert_eq!(
+            Some(Bytes::from_static(b"second_assignment")),
+            s.assignments()
+                .and_then(|assignments| assignments.get(second.0.as_str()).cloned())
+        );
+
+        // the first member leaves by its instance id alone, in the batched form
+        let (s, body) = s
+            .leave(
+                now,
+                GROUP_ID,
+                None,
+                Some(&[
+                    MemberIdentity {
+                        member_id: "".into(),
+                        group_instance_id: Some("first".into()),
+                        reason: Some("the consumer is being closed".into()),
+                    },
+                    MemberIdentity {
+                        member_id: "".into(),
+                        group_instance_id: Some("unknown".into()),
+                        reason: None,
+                    },
+                ]),
+            )
+            .await;
+
+        assert_eq!(
+            Body::LeaveGroupResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::None.into(),
+                members: Some(vec![
+                    MemberResponse {
+                        member_id: "".into(),
+                        group_instance_id: Some("first".into()),
+                        error_code: ErrorCode::None.into(),
+                    },
+                    MemberResponse {
+                        member_id: "".into(),
+                        group_instance_id: Some("unknown".into()),
+                        error_code: ErrorCode::UnknownMemberId.into(),
+                    },
+                ]),
+            },
+            body
+        );
+
+        // the remaining member is retained, rebalancing in the next generation
+        assert_eq!(vec![second.1.join_response.clone()], s.members());
+        assert_eq!(4, s.generation_id());
+        assert_eq!(None, s.leader());
+
+        let (s, body) = s
+            .heartbeat(now, GROUP_ID, 3, first.0.as_str(), Some("first"))
+            .await;
+
+        assert_eq!(
+            Body::HeartbeatResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::UnknownMemberId.into(),
+            },
+            body
+        );
+
+        let (_, body) = s
+            .heartbeat(now, GROUP_ID, 3, second.0.as_str(), Some("second"))
+            .await;
+
+        assert_eq!(
+            Body::HeartbeatResponse {
+                throttle_time_ms: Some(0),
+                error_code: ErrorCode::RebalanceInProgress.into(),
+            },
+            body
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,297 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    io::Cursor,
+};
+
+use base64::prelude::*;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize, Serializer};
+use tansu_kafka_sans_io::{
+    BatchAttribute, Decoder,
+    record::{Record, deflated},
+};
+
+use crate::Result;
+
+fn encode<S>(octets: &Option<Bytes>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match octets {
+        Some(octets) => serializer.serialize_some(&BASE64_STANDARD.encode(octets)),
+        None => serializer.serialize_none(),
+    }
+}
+
+struct Lossy<'a>(&'a Option<Bytes>);
+
+impl Display for Lossy<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(octets) => write!(f, "{:?}", String::from_utf8_lossy(octets)),
+            None => f.write_str("null"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct DumpedHeader {
+    #[serde(serialize_with = "encode")]
+    pub key: Option<Bytes>,
+    #[serde(serialize_with = "encode")]
+    pub value: Option<Bytes>,
+}
+
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct DumpedRecord {
+    pub offset: i64,
+    pub timestamp: i64,
+    #[serde(serialize_with = "encode")]
+    pub key: Option<Bytes>,
+    #[serde(serialize_with = "encode")]
+    pub value: Option<Bytes>,
+    pub headers: Vec<DumpedHeader>,
+}
+
+impl DumpedRecord {
+    fn new(batch: &deflated::Batch, record: Record) -> Self {
+        Self {
+            offset: record.offset(batch.base_offset),
+            timestamp: record.timestamp(batch.base_timestamp),
+            key: record.key,
+            value: record.value,
+            headers: record
+                .headers
+                .into_iter()
+                .map(|header| DumpedHeader {
+                    key: header.key,
+                    value: header.value,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Display for DumpedRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "offset: {}, timestamp: {}, key: {}, value: {}, headers: [",
+            self.offset,
+            self.timestamp,
+            Lossy(&self.key),
+            Lossy(&self.value)
+        )?;
+
+        for (index, header) in self.headers.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+
+            write!(f, "{}: {}", Lossy(&header.key), Lossy(&header.value))?;
+        }
+
+        f.write_str("]")
+    }
+}
+
+/// A stored batch decoded with its header fields and records, for
+/// forensic inspection of what a producer sent
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct DumpedBatch {
+    pub base_offset: i64,
+    pub batch_length: i32,
+    pub partition_leader_epoch: i32,
+    pub magic: i8,
+    pub crc: u32,
+    pub crc_verified: bool,
+    pub attributes: BatchAttribute,
+    pub last_offset_delta: i32,
+    pub base_timestamp: i64,
+    pub max_timestamp: i64,
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+    pub base_sequence: i32,
+    pub record_count: u32,
+    pub records: Vec<DumpedRecord>,
+}
+
+impl TryFrom<&deflated::Batch> for DumpedBatch {
+    type Error = crate::Error;
+
+    fn try_from(batch: &deflated::Batch) -> Result<Self, Self::Error> {
+        Ok(Self {
+            base_offset: batch.base_offset,
+            batch_length: batch.batch_length,
+            partition_leader_epoch: batch.partition_leader_epoch,
+            magic: batch.magic,
+            crc: batch.crc,
+            crc_verified: batch.verify_crc(),
+            attributes: BatchAttribute::try_from(batch.attributes)?,
+            last_offset_delta: batch.last_offset_delta,
+            base_timestamp: batch.base_timestamp,
+            max_timestamp: batch.max_timestamp,
+            producer_id: batch.producer_id,
+            producer_epoch: batch.producer_epoch,
+            base_sequence: batch.base_sequence,
+            record_count: batch.record_count,
+            records: batch
+                .records(deflated::Limit::default())?
+                .into_iter()
+                .map(|record| DumpedRecord::new(batch, record))
+                .collect(),
+        })
+    }
+}
+
+impl Display for DumpedBatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "base_offset: {}, batch_length: {}, partition_leader_epoch: {}, magic: {}, \
+             crc: {:#010x}, crc_verified: {}, attributes: {:?}, last_offset_delta: {}, \
+             base_timestamp: {}, max_timestamp: {}, producer_id: {}, producer_epoch: {}, \
+             base_sequence: {}, record_count: {}",
+            self.base_offset,
+            self.batch_length,
+            self.partition_leader_epoch,
+            self.magic,
+            self.crc,
+            self.crc_verified,
+            self.attributes,
+            self.last_offset_delta,
+            self.base_timestamp,
+            self.max_timestamp,
+            self.producer_id,
+            self.producer_epoch,
+            self.base_sequence,
+            self.record_count,
+        )?;
+
+        for record in &self.records {
+            writeln!(f, "  {record}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// decodes raw batch bytes, as held by storage, that may contain several
+// batches one after another
+pub fn batches(encoded: Bytes) -> Result<Vec<DumpedBatch>> {
+    let length = encoded.len() as u64;
+    let mut c = Cursor::new(encoded);
+
+    let mut batches = vec![];
+
+    while c.position() < length {
+        let mut decoder = Decoder::new(&mut c);
+        let batch = deflated::Batch::deserialize(&mut decoder)?;
+        batches.push(DumpedBatch::try_from(&batch)?);
+    }
+
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tansu_kafka_sans_io::{
+        Compression, Encoder,
+        record::{Header, inflated},
+    };
+
+    fn encoded(base_offset: i64, compression: Compression) -> Result<Vec<u8>> {
+        let batch: deflated::Batch = inflated::Batch::builder()
+            .base_offset(base_offset)
+            .attributes(BatchAttribute::default().compression(compression).into())
+            .record(
+                Record::builder()
+                    .key(Bytes::from_static(b"abc").into())
+                    .value(Bytes::from_static(b"def").into())
+                    .header(
+                        Header::builder()
+                            .key(Bytes::from_static(b"trace").into())
+                            .value(Bytes::from_static(b"pqr").into()),
+                    ),
+            )
+            .record(
+                Record::builder()
+                    .offset_delta(1)
+                    .timestamp_delta(5)
+                    .value(Bytes::from_static(b"ghi").into()),
+            )
+            .base_timestamp(1_707_058_170_165)
+            .max_timestamp(1_707_058_170_170)
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        let mut encoded = vec![];
+        let mut encoder = Encoder::new(&mut encoded);
+        batch.serialize(&mut encoder)?;
+
+        Ok(encoded)
+    }
+
+    #[test]
+    fn dump() -> Result<()> {
+        let mut encoded = encoded(32, Compression::None)?;
+        encoded.extend(self::encoded(34, Compression::Gzip)?);
+
+        let batches = batches(Bytes::from(encoded))?;
+        assert_eq!(2, batches.len());
+
+        assert!(batches.iter().all(|batch| batch.crc_verified));
+        assert_eq!(Compression::Gzip, batches[1].attributes.compression);
+
+        assert_eq!(
+            vec![32, 33, 34, 35],
+            batches
+                .iter()
+                .flat_map(|batch| batch.records.iter().map(|record| record.offset))
+                .collect::<Vec<_>>()
+        );
+
+        let text = batches[0].to_string();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(3, lines.len());
+        assert!(lines[0].starts_with("base_offset: 32, "), "{}", lines[0]);
+        assert_eq!(
+            r#"  offset: 32, timestamp: 1707058170165, key: "abc", value: "def", headers: ["trace": "pqr"]"#,
+            lines[1]
+        );
+        assert_eq!(
+            r#"  offset: 33, timestamp: 1707058170170, key: null, value: "ghi", headers: []"#,
+            lines[2]
+        );
+
+        assert_eq!(
+            json!({
+                "offset": 35,
+                "timestamp": 1_707_058_170_170_i64,
+                "key": null,
+                "value": BASE64_STANDARD.encode(b"ghi"),
+                "headers": [],
+            }),
+            serde_json::to_value(&batches[1].records[1])?
+        );
+
+        Ok(())
+    }
+}
@@ -29,13 +29,14 @@ use jsonschema::ValidationError;
 use opentelemetry::{InstrumentationScope, global, metrics::Meter, trace::TraceError};
 use opentelemetry_semantic_conventions::SCHEMA_URL;
 use regex::{Regex, Replacer};
-use tansu_kafka_sans_io::ErrorCode;
+use tansu_kafka_sans_io::{Body, ErrorCode};
 use thiserror::Error;
 use tracing_subscriber::filter::ParseError;
-use url::Url;
 
 pub mod broker;
+pub mod config;
 pub mod coordinator;
+pub mod dump;
 pub mod otel;
 
 pub const NODE_ID: i32 = 111;
@@ -58,7 +59,9 @@ pub enum Error {
     EmptyJoinGroupRequestProtocol,
     ExpectedJoinGroupRequestProtocol(&'static str),
     Hyper(#[from] hyper::http::Error),
+    InvalidConfig(Vec<config::Invalid>),
     Io(Arc<io::Error>),
+    Join(#[from] tokio::task::JoinError),
     Json(#[from] serde_json::Error),
     KafkaProtocol(#[from] tansu_kafka_sans_io::Error),
     Message(String),
@@ -77,7 +80,9 @@ pub enum Error {
     Regex(#[from] regex::Error),
     TokioPostgres(#[from] tokio_postgres::error::Error),
     TryFromInt(#[from] TryFromIntError),
-    UnsupportedStorageUrl(Url),
+    UnknownSecurityProtocol(String),
+    UnsupportedRequest(Box<Body>),
+    UnsupportedSecurityProtocol(broker::security::SecurityProtocol),
     UnsupportedTracingFormat(String),
     Url(#[from] url::ParseError),
     Utf8(#[from] Utf8Error),
@@ -109,10 +114,42 @@ impl From<TraceError> for Error {
     }
 }
 
+impl Error {
+    pub fn storage_error_code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Storage(tansu_storage::Error::Api(error_code)) => Some(*error_code),
+
+            Self::Storage(_) | Self::ObjectStore(_) | Self::Pool(_) | Self::TokioPostgres(_) => {
+                Some(ErrorCode::KafkaStorageError)
+            }
+
+            _otherwise => None,
+        }
+    }
+
+    // the error code answering a request that failed with this error, with
+    // none closing the connection instead
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::UnsupportedRequest(_) => Some(ErrorCode::UnsupportedVersion),
+            otherwise => otherwise.storage_error_code(),
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Message(msg) => write!(f, "{}", msg),
+            Self::InvalidConfig(invalid) => write!(
+                f,
+                "invalid configuration: {}",
+                invalid
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
             error => write!(f, "{:?}", error),
         }
     }
@@ -13,22 +13,57 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use clap::Parser;
-use object_store::{
-    aws::{AmazonS3Builder, S3ConditionalPut},
-    memory::InMemory,
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::PathBuf,
+    result,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use clap::{ArgAction, Parser};
+use tansu_kafka_sans_io::{
+    Compression, ErrorCode, IsolationLevel, RootMessageMeta,
+    record::deflated::{
+        CompressionLevel, DEFAULT_MAX_INFLATED_BYTES, DEFAULT_MAX_RECORD_COUNT, Limit,
+    },
 };
 use tansu_schema_registry::Registry;
 use tansu_server::{
-    EnvVarExp, Error, NODE_ID, Result, TracingFormat, broker::Broker,
-    coordinator::group::administrator::Controller, otel,
+    EnvVarExp, NODE_ID, Result, TracingFormat,
+    broker::{
+        Broker, DEFAULT_MAX_REQUEST_SIZE, DEFAULT_STREAM_BUFFER_SIZE, SocketOptions,
+        chaos::Chaos,
+        connection_limit::ConnectionLimit,
+        drain::Drain,
+        elect_leaders::LeaderRebalance,
+        fetch::notifier::Notifier,
+        init_producer_id::ProducerIdBlock,
+        metadata::MetadataCache,
+        produce::config::{DEFAULT_TOPIC_CONFIG_TTL, TopicConfigCache},
+        produce::{
+            tee::Tee,
+            transform::{Redact, Transforms},
+        },
+        sampler::Sampler,
+        security::Listener,
+        telemetry::Telemetry,
+    },
+    config::Config,
+    coordinator::group::administrator::Controller,
+    otel,
 };
-use tansu_storage::{StorageContainer, dynostore::DynoStore, pg::Postgres};
+use tansu_storage::{ConnectionPool, SequenceWindow, StorageContainer, TopicLimit, TxnLimit};
 use tokio::task::JoinSet;
-use tracing::debug;
+use tracing::{debug, error};
 use url::Url;
 use uuid::Uuid;
 
+#[cfg(unix)]
+use tokio::signal::unix::SignalKind;
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Cli {
@@ -64,6 +99,241 @@ struct Cli {
 
     #[arg(long, env = "TRACING_FORMAT", default_value = "text")]
     tracing_format: TracingFormat,
+
+    #[arg(long, env = "MAX_INFLATED_BATCH_BYTES", default_value_t = DEFAULT_MAX_INFLATED_BYTES)]
+    max_inflated_batch_bytes: u64,
+
+    #[arg(long, env = "MAX_BATCH_RECORD_COUNT", default_value_t = DEFAULT_MAX_RECORD_COUNT)]
+    max_batch_record_count: u32,
+
+    #[arg(long, env = "COMPRESSION_GZIP_LEVEL", allow_negative_numbers = true)]
+    compression_gzip_level: Option<i32>,
+
+    #[arg(long, env = "COMPRESSION_LZ4_LEVEL", allow_negative_numbers = true)]
+    compression_lz4_level: Option<i32>,
+
+    #[arg(long, env = "COMPRESSION_ZSTD_LEVEL", allow_negative_numbers = true)]
+    compression_zstd_level: Option<i32>,
+
+    #[arg(long, env = "DEFAULT_ISOLATION_LEVEL", default_value = "read_uncommitted", value_parser = isolation_level)]
+    default_isolation_level: IsolationLevel,
+
+    #[arg(long, env = "METRIC_TOPICS", value_delimiter = ',')]
+    metric_topics: Option<Vec<String>>,
+
+    #[arg(long, env = "TELEMETRY_METRICS", value_delimiter = ',')]
+    telemetry_metrics: Option<Vec<String>>,
+
+    #[arg(long, env = "TELEMETRY_PUSH_INTERVAL_MS", default_value = "5000")]
+    telemetry_push_interval_ms: i32,
+
+    #[arg(long, env = "GROUP_MIN_SESSION_TIMEOUT_MS", default_value = "6000")]
+    group_min_session_timeout_ms: i32,
+
+    #[arg(long, env = "GROUP_MAX_SESSION_TIMEOUT_MS", default_value = "1800000")]
+    group_max_session_timeout_ms: i32,
+
+    // a number of partitions served by a fetch
+    #[arg(long, env = "MAX_PARTITIONS_PER_FETCH", value_parser = positive::<usize>)]
+    max_partitions_per_fetch: Option<usize>,
+
+    #[arg(long, env = "TRANSACTIONAL_ACKS_ALL", default_value = "true", action = ArgAction::Set)]
+    transactional_acks_all: bool,
+
+    #[arg(long, env = "TCP_NODELAY", default_value = "true", action = ArgAction::Set)]
+    tcp_nodelay: bool,
+
+    #[arg(long, env = "TCP_KEEPALIVE_MS")]
+    tcp_keepalive_ms: Option<u64>,
+
+    #[arg(long, env = "TCP_SEND_BUFFER_BYTES")]
+    tcp_send_buffer_bytes: Option<usize>,
+
+    #[arg(long, env = "TCP_RECV_BUFFER_BYTES")]
+    tcp_recv_buffer_bytes: Option<usize>,
+
+    #[arg(long, env = "MAX_CONNECTIONS")]
+    max_connections: Option<usize>,
+
+    #[arg(long, env = "MAX_CONNECTIONS_PER_IP")]
+    max_connections_per_ip: Option<usize>,
+
+    #[arg(long, env = "READ_BUFFER_BYTES", default_value_t = DEFAULT_STREAM_BUFFER_SIZE)]
+    read_buffer_bytes: usize,
+
+    #[arg(long, env = "WRITE_BUFFER_BYTES", default_value_t = DEFAULT_STREAM_BUFFER_SIZE)]
+    write_buffer_bytes: usize,
+
+    #[arg(long, env = "MAX_REQUEST_BYTES", default_value_t = DEFAULT_MAX_REQUEST_SIZE)]
+    max_request_bytes: usize,
+
+    #[arg(long, env = "REQUEST_TIMEOUT_MS")]
+    request_timeout_ms: Option<u64>,
+
+    #[arg(long, env = "API_REQUEST_TIMEOUT_MS", value_delimiter = ',', value_parser = api_request_timeout)]
+    api_request_timeout_ms: Option<Vec<(i16, Duration)>>,
+
+    // a number of producer ids reserved from storage at a time
+    #[arg(long, env = "PRODUCER_ID_BLOCK_SIZE", value_parser = positive::<i32>)]
+    producer_id_block_size: Option<i32>,
+
+    #[arg(long, env = "METADATA_CACHE_TTL_MS")]
+    metadata_cache_ttl_ms: Option<u64>,
+
+    #[arg(long, env = "TOPIC_CONFIG_CACHE_TTL_MS", default_value_t = DEFAULT_TOPIC_CONFIG_TTL.as_millis() as u64)]
+    topic_config_cache_ttl_ms: u64,
+
+    #[arg(long, env = "MAX_TOPICS")]
+    max_topics: Option<i64>,
+
+    #[arg(long, env = "MAX_PARTITIONS")]
+    max_partitions: Option<i64>,
+
+    #[arg(long, env = "PRODUCER_SEQUENCE_WINDOW", default_value = "5")]
+    producer_sequence_window: usize,
+
+    #[arg(long, env = "MAX_OPEN_TRANSACTIONS")]
+    max_open_transactions: Option<usize>,
+
+    #[arg(long, env = "MAX_TRANSACTION_PARTITIONS")]
+    max_transaction_partitions: Option<usize>,
+
+    // the most connections open to storage at a time
+    #[arg(long, env = "STORAGE_POOL_MAX_SIZE", default_value = "16", value_parser = positive::<usize>)]
+    storage_pool_max_size: usize,
+
+    #[arg(long, env = "STORAGE_POOL_ACQUIRE_TIMEOUT_MS")]
+    storage_pool_acquire_timeout_ms: Option<u64>,
+
+    #[arg(long, env = "STORAGE_POOL_IDLE_TIMEOUT_MS")]
+    storage_pool_idle_timeout_ms: Option<u64>,
+
+    #[arg(long, env = "PRODUCE_TEE")]
+    produce_tee: Option<PathBuf>,
+
+    #[arg(long, env = "REDACT_VALUE", value_parser = redact)]
+    redact_value: Option<Vec<(String, Redact)>>,
+
+    #[arg(long, env = "FETCH_NOTIFIER_IDLE_MS", default_value = "300000")]
+    fetch_notifier_idle_ms: u64,
+
+    // the period of the leader imbalance check
+    #[arg(long, env = "LEADER_IMBALANCE_CHECK_INTERVAL_MS", value_parser = positive::<u64>)]
+    leader_imbalance_check_interval_ms: Option<u64>,
+
+    #[arg(long, env = "ADDITIONAL_LISTENERS", value_delimiter = ',')]
+    additional_listeners: Option<Vec<Listener>>,
+
+    #[arg(long, env = "INTER_BROKER_LISTENER_NAME")]
+    inter_broker_listener_name: Option<String>,
+
+    #[arg(long, env = "CHAOS_APIS", value_delimiter = ',', value_parser = api_key)]
+    chaos_apis: Option<Vec<i16>>,
+
+    #[arg(long, env = "CHAOS_LATENCY_MS")]
+    chaos_latency_ms: Option<u64>,
+
+    #[arg(long, env = "CHAOS_ERROR_RATE", default_value = "0", value_parser = rate)]
+    chaos_error_rate: f64,
+
+    #[arg(long, env = "CHAOS_ERROR_CODES", value_delimiter = ',', value_parser = error_code)]
+    chaos_error_codes: Option<Vec<ErrorCode>>,
+
+    #[arg(long, env = "CHAOS_DISCONNECT_RATE", default_value = "0", value_parser = rate)]
+    chaos_disconnect_rate: f64,
+
+    #[arg(long, env = "REQUEST_LOG_SAMPLE_RATE", default_value = "1", value_parser = rate)]
+    request_log_sample_rate: f64,
+
+    #[arg(long, env = "REQUEST_LOG_PER_SECOND")]
+    request_log_per_second: Option<u32>,
+}
+
+// an api named without its "Request" suffix, e.g., Fetch
+fn api_key(api: &str) -> result::Result<i16, String> {
+    RootMessageMeta::messages()
+        .requests()
+        .iter()
+        .find(|(_, meta)| meta.name.strip_suffix("Request") == Some(api))
+        .map(|(api_key, _)| *api_key)
+        .ok_or_else(|| format!("unknown api: {api}"))
+}
+
+// a number of at least one, e.g., a size, a count or a period in milliseconds
+fn positive<T>(value: &str) -> result::Result<T, String>
+where
+    T: Default + FromStr + PartialOrd,
+    T::Err: fmt::Display,
+{
+    value
+        .parse::<T>()
+        .map_err(|error| format!("{error}: {value}"))
+        .and_then(|positive| {
+            if positive > T::default() {
+                Ok(positive)
+            } else {
+                Err(format!("expecting at least 1, got: {value}"))
+            }
+        })
+}
+
+// a fraction of requests, between 0 and 1
+fn rate(value: &str) -> result::Result<f64, String> {
+    value
+        .parse::<f64>()
+        .map_err(|error| format!("{error}: {value}"))
+        .and_then(|rate| {
+            if (0.0..=1.0).contains(&rate) {
+                Ok(rate)
+            } else {
+                Err(format!("expecting a rate between 0 and 1, got: {value}"))
+            }
+        })
+}
+
+// a kafka error code by number, e.g., 7 for RequestTimedOut
+fn error_code(value: &str) -> result::Result<ErrorCode, String> {
+    value
+        .parse::<i16>()
+        .map_err(|error| format!("{error}: {value}"))
+        .and_then(|code| ErrorCode::try_from(code).map_err(|error| format!("{error}: {value}")))
+}
+
+// as the isolation.level consumer configuration, e.g., read_committed
+fn isolation_level(value: &str) -> result::Result<IsolationLevel, String> {
+    match value {
+        "read_uncommitted" => Ok(IsolationLevel::ReadUncommitted),
+        "read_committed" => Ok(IsolationLevel::ReadCommitted),
+        _otherwise => Err(format!(
+            "expecting read_uncommitted or read_committed, got: {value}"
+        )),
+    }
+}
+
+// api=ms, with the api named without its "Request" suffix, e.g., Fetch=30000
+fn api_request_timeout(value: &str) -> result::Result<(i16, Duration), String> {
+    let (api, ms) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expecting api=ms, got: {value}"))?;
+
+    let timeout = ms
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|error| format!("{error}: {value}"))?;
+
+    api_key(api).map(|api_key| (api_key, timeout))
+}
+
+// topic=pattern, with each match of the pattern in a produced value
+// masked before it is stored, e.g., pqr=\d{3}-\d{4}
+fn redact(value: &str) -> result::Result<(String, Redact), String> {
+    let (topic, pattern) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expecting topic=pattern, got: {value}"))?;
+
+    regex::bytes::Regex::new(pattern)
+        .map(|pattern| (topic.to_owned(), Redact::new(pattern)))
+        .map_err(|error| format!("{error}: {value}"))
 }
 
 #[tokio::main]
@@ -73,78 +343,188 @@ async fn main() -> Result<()> {
     let instance_id = Uuid::now_v7();
     let _guard = otel::init(args.tracing_format)?;
 
-    let cluster_id = args.kafka_cluster_id;
-    let prometheus_listener_url = args.prometheus_listener_url.into_inner();
-    let storage_engine = args.storage_engine.into_inner();
-    let advertised_listener = args.kafka_advertised_listener_url.into_inner();
-    let listener = args.kafka_listener_url.into_inner();
-    debug!(%cluster_id, %prometheus_listener_url, %storage_engine, %advertised_listener, %listener);
+    let config = Config::builder()
+        .cluster_id(args.kafka_cluster_id)
+        .node_id(NODE_ID)
+        .listener(args.kafka_listener_url.into_inner())
+        .advertised_listener(args.kafka_advertised_listener_url.into_inner())
+        .storage(args.storage_engine.into_inner())
+        .schema_registry(args.schema_registry.map(EnvVarExp::into_inner))
+        .prometheus_listener(Some(args.prometheus_listener_url.into_inner()))
+        .additional_listeners(args.additional_listeners.unwrap_or_default())
+        .inter_broker_listener_name(args.inter_broker_listener_name)
+        .group_min_session_timeout_ms(args.group_min_session_timeout_ms)
+        .group_max_session_timeout_ms(args.group_max_session_timeout_ms)
+        .build()
+        .inspect_err(|error| error!(%error))?;
+    debug!(?config);
 
     let mut set = JoinSet::new();
 
-    _ = set.spawn(async move {
-        if let Err(e) = otel::prom::init(prometheus_listener_url).await {
-            panic!("Errors on initializing prometheus listener. error: {}", e);
-        }
-    });
+    if let Some(prometheus_listener_url) = config.prometheus_listener().cloned() {
+        _ = set.spawn(otel::prom::init(prometheus_listener_url));
+    }
 
-    let schemas = args.schema_registry.map_or(Ok(None), |schema| {
-        Registry::try_from(schema.into_inner()).map(Some)
+    let schemas = config.schema_registry().map_or(Ok(None), |schema| {
+        Registry::try_from(schema.clone()).map(Some)
     })?;
 
-    let storage = match storage_engine.scheme() {
-        "postgres" | "postgresql" => Postgres::builder(storage_engine.to_string().as_str())
-            .map(|builder| builder.cluster(cluster_id.as_str()))
-            .map(|builder| builder.node(NODE_ID))
-            .map(|builder| builder.advertised_listener(advertised_listener.clone()))
-            .map(|builder| builder.schemas(schemas))
-            .map(|builder| builder.build())
-            .map(StorageContainer::Postgres)
-            .map_err(Into::into),
-
-        "s3" => {
-            let bucket_name = storage_engine.host_str().unwrap_or("tansu");
-
-            AmazonS3Builder::from_env()
-                .with_bucket_name(bucket_name)
-                .with_conditional_put(S3ConditionalPut::ETagMatch)
-                .build()
-                .map(|object_store| {
-                    DynoStore::new(cluster_id.as_str(), NODE_ID, object_store)
-                        .advertised_listener(advertised_listener.clone())
-                        .schemas(schemas)
-                })
-                .map(StorageContainer::DynoStore)
-                .map_err(Into::into)
-        }
+    let storage = StorageContainer::builder()
+        .cluster_id(config.cluster_id())
+        .node(config.node_id())
+        .advertised_listener(config.advertised_listener().clone())
+        .storage(config.storage().clone())
+        .schemas(schemas)
+        .sequence_window(SequenceWindow::new(args.producer_sequence_window))
+        .txn_limit(
+            TxnLimit::default()
+                .max_open(args.max_open_transactions)
+                .max_partitions(args.max_transaction_partitions),
+        )
+        .connection_pool(
+            ConnectionPool::default()
+                .max_size(args.storage_pool_max_size)
+                .acquire_timeout(
+                    args.storage_pool_acquire_timeout_ms
+                        .map(Duration::from_millis),
+                )
+                .idle_timeout(args.storage_pool_idle_timeout_ms.map(Duration::from_millis)),
+        )
+        .build()?;
 
-        "memory" => Ok(StorageContainer::DynoStore(
-            DynoStore::new(cluster_id.as_str(), NODE_ID, InMemory::new())
-                .advertised_listener(advertised_listener.clone()),
-        )),
+    let compression_level = [
+        (Compression::Gzip, args.compression_gzip_level),
+        (Compression::Lz4, args.compression_lz4_level),
+        (Compression::Zstd, args.compression_zstd_level),
+    ]
+    .into_iter()
+    .try_fold(
+        CompressionLevel::default(),
+        |compression_level, (compression, level)| {
+            level.map_or(Ok(compression_level), |level| {
+                compression_level.level(compression, level)
+            })
+        },
+    )?;
 
-        _unsupported => Err(Error::UnsupportedStorageUrl(storage_engine)),
-    }?;
+    if let Some(interval) = args.leader_imbalance_check_interval_ms {
+        let rebalance = LeaderRebalance::new(storage.clone(), Duration::from_millis(interval));
 
-    {
-        let groups = Controller::with_storage(storage.clone())?;
-
-        let mut broker = Broker::new(
-            NODE_ID,
-            &cluster_id,
-            listener,
-            advertised_listener,
-            storage,
-            groups,
-            instance_id,
-        );
-
-        _ = set.spawn(async move {
-            broker.serve().await.unwrap();
-        });
+        _ = set.spawn(async move { rebalance.run().await });
     }
 
-    _ = set.join_next().await;
+    {
+        let groups = Controller::with_storage(storage.clone())?
+            .min_session_timeout_ms(config.group_min_session_timeout_ms())
+            .max_session_timeout_ms(config.group_max_session_timeout_ms());
 
-    Ok(())
+        let drain = Drain::default();
+
+        let mut broker = Broker::new(&config, storage, groups, instance_id)
+            .record_limit(
+                Limit::default()
+                    .max_inflated_bytes(Some(args.max_inflated_batch_bytes))
+                    .max_record_count(Some(args.max_batch_record_count)),
+            )
+            .compression_level(compression_level)
+            .default_isolation_level(args.default_isolation_level)
+            .transactional_acks_all(args.transactional_acks_all)
+            .max_partitions_per_fetch(args.max_partitions_per_fetch)
+            .inter_broker_listener_name(config.inter_broker_listener_name().map(ToOwned::to_owned))
+            .tee(args.produce_tee.map(Tee::open).transpose()?)
+            .transforms(
+                args.redact_value
+                    .unwrap_or_default()
+                    .into_iter()
+                    .fold(Transforms::default(), |transforms, (topic, redact)| {
+                        transforms.topic(&topic, Arc::new(redact))
+                    }),
+            )
+            .notifier(Notifier::default().idle(Duration::from_millis(args.fetch_notifier_idle_ms)))
+            .metric_topics(
+                args.metric_topics
+                    .map(|topics| topics.into_iter().collect::<BTreeSet<_>>()),
+            )
+            .telemetry(
+                Telemetry::default()
+                    .requested_metrics(args.telemetry_metrics.unwrap_or_default())
+                    .push_interval_ms(args.telemetry_push_interval_ms),
+            )
+            .socket_options(
+                SocketOptions::default()
+                    .nodelay(args.tcp_nodelay)
+                    .keepalive(args.tcp_keepalive_ms.map(Duration::from_millis))
+                    .send_buffer_size(args.tcp_send_buffer_bytes)
+                    .recv_buffer_size(args.tcp_recv_buffer_bytes)
+                    .read_buffer_size(args.read_buffer_bytes)
+                    .write_buffer_size(args.write_buffer_bytes)
+                    .max_request_size(args.max_request_bytes),
+            )
+            .connection_limit(
+                ConnectionLimit::default()
+                    .max_connections(args.max_connections)
+                    .max_connections_per_ip(args.max_connections_per_ip),
+            )
+            .request_timeout(args.request_timeout_ms.map(Duration::from_millis))
+            .api_request_timeouts(
+                args.api_request_timeout_ms
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect::<BTreeMap<_, _>>(),
+            )
+            .producer_ids(args.producer_id_block_size.map(ProducerIdBlock::new))
+            .metadata_cache(
+                MetadataCache::default().ttl(args.metadata_cache_ttl_ms.map(Duration::from_millis)),
+            )
+            .topic_config_cache(
+                TopicConfigCache::default().ttl(
+                    Some(args.topic_config_cache_ttl_ms)
+                        .filter(|ttl_ms| *ttl_ms > 0)
+                        .map(Duration::from_millis),
+                ),
+            )
+            .topic_limit(
+                TopicLimit::default()
+                    .max_topics(args.max_topics)
+                    .max_partitions(args.max_partitions),
+            )
+            .chaos(
+                Chaos::default()
+                    .apis(args.chaos_apis.unwrap_or_default().into_iter().collect())
+                    .latency(args.chaos_latency_ms.map(Duration::from_millis))
+                    .error_rate(args.chaos_error_rate)
+                    .error_codes(
+                        args.chaos_error_codes
+                            .unwrap_or(vec![ErrorCode::RequestTimedOut]),
+                    )
+                    .disconnect_rate(args.chaos_disconnect_rate),
+            )
+            .sampler(
+                Sampler::default()
+                    .rate(args.request_log_sample_rate)
+                    .per_second(args.request_log_per_second),
+            )
+            .drain(drain.clone());
+
+        // SIGUSR1 toggles draining ahead of a rolling restart
+        #[cfg(unix)]
+        {
+            _ = set.spawn(async move { drain.toggle_on(SignalKind::user_defined1()).await });
+        }
+
+        for listener in config.additional_listeners().iter().cloned() {
+            let broker = broker.clone().listener(listener);
+
+            _ = set.spawn(async move { broker.listen().await });
+        }
+
+        _ = set.spawn(async move { broker.serve().await });
+    }
+
+    // the first task to finish stops the broker, returning any error
+    match set.join_next().await {
+        Some(Ok(outcome)) => outcome.inspect_err(|error| error!(?error)),
+        Some(Err(error)) => Err(error.into()),
+        None => Ok(()),
+    }
 }
@@ -14,19 +14,29 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{Result, TracingFormat};
+use futures::future::BoxFuture;
 use opentelemetry::KeyValue;
-use opentelemetry_otlp::SpanExporter;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
 use opentelemetry_sdk::{
     Resource,
-    trace::{RandomIdGenerator, Sampler, SdkTracerProvider},
+    error::OTelSdkResult,
+    trace::{self, RandomIdGenerator, Sampler, SdkTracerProvider, SpanData},
 };
 use opentelemetry_semantic_conventions::{
     SCHEMA_URL,
     resource::{SERVICE_NAME, SERVICE_VERSION},
 };
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
 use tracing_subscriber::{
     EnvFilter, fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt,
 };
+use url::Url;
+
+const EXPORT_WARNING_INTERVAL: Duration = Duration::from_secs(60);
 
 fn resource() -> Resource {
     Resource::builder()
@@ -40,21 +50,117 @@ fn resource() -> Resource {
         .build()
 }
 
-fn init_tracer_provider() -> Result<SdkTracerProvider> {
-    SpanExporter::builder()
-        .with_tonic()
-        .build()
-        .map_err(Into::into)
-        .map(|exporter| {
-            SdkTracerProvider::builder()
-                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
-                    1.0,
-                ))))
-                .with_resource(resource())
-                .with_batch_exporter(exporter)
-                .with_id_generator(RandomIdGenerator::default())
-                .build()
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Suppressed {
+    warned_at: Option<Instant>,
+    failures: u64,
+}
+
+impl Suppressed {
+    // returns the number of failures suppressed since the last warning,
+    // when another warning is due
+    fn failure(&mut self, now: Instant, interval: Duration) -> Option<u64> {
+        if self
+            .warned_at
+            .is_none_or(|warned_at| now.duration_since(warned_at) >= interval)
+        {
+            self.warned_at = Some(now);
+            Some(std::mem::take(&mut self.failures))
+        } else {
+            self.failures += 1;
+            None
+        }
+    }
+}
+
+// an exporter that never fails, so that an unreachable collector doesn't
+// disturb request handling, with failures logged at most once per interval
+#[derive(Debug)]
+struct RateLimited<E> {
+    exporter: E,
+    interval: Duration,
+    suppressed: Arc<Mutex<Suppressed>>,
+}
+
+impl<E> RateLimited<E> {
+    fn new(exporter: E, interval: Duration) -> Self {
+        Self {
+            exporter,
+            interval,
+            suppressed: Arc::new(Mutex::new(Suppressed::default())),
+        }
+    }
+}
+
+impl<E> trace::SpanExporter for RateLimited<E>
+where
+    E: trace::SpanExporter,
+{
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, OTelSdkResult> {
+        let spans = batch.len();
+        let export = self.exporter.export(batch);
+        let interval = self.interval;
+        let suppressed = self.suppressed.clone();
+
+        Box::pin(async move {
+            if let Err(error) = export.await {
+                if let Some(suppressed) = suppressed
+                    .lock()
+                    .map(|mut suppressed| suppressed.failure(Instant::now(), interval))
+                    .unwrap_or_default()
+                {
+                    warn!(%error, spans, suppressed, "unable to export spans");
+                }
+            }
+
+            Ok(())
         })
+    }
+
+    fn shutdown(&mut self) -> OTelSdkResult {
+        self.exporter.shutdown()
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.exporter.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.exporter.set_resource(resource)
+    }
+}
+
+fn span_exporter(endpoint: Option<&Url>) -> Result<SpanExporter> {
+    let builder = SpanExporter::builder().with_tonic();
+
+    // the tonic channel connects lazily, reconnecting on each export
+    // until the collector is reachable
+    if let Some(endpoint) = endpoint {
+        builder.with_endpoint(endpoint.as_str()).build()
+    } else {
+        builder.build()
+    }
+    .map_err(Into::into)
+}
+
+fn init_tracer_provider(endpoint: Option<&Url>) -> SdkTracerProvider {
+    let builder = SdkTracerProvider::builder()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            1.0,
+        ))))
+        .with_resource(resource())
+        .with_id_generator(RandomIdGenerator::default());
+
+    match span_exporter(endpoint) {
+        Ok(exporter) => builder
+            .with_batch_exporter(RateLimited::new(exporter, EXPORT_WARNING_INTERVAL))
+            .build(),
+
+        Err(error) => {
+            warn!(%error, ?endpoint, "spans will not be exported");
+            builder.build()
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -71,8 +177,6 @@ impl Drop for Guard {
 }
 
 pub fn init_tracing_subscriber(tracing_format: TracingFormat) -> Result<Guard> {
-    let provider = init_tracer_provider()?;
-
     // let tracer = provider.tracer(format!("{}-otel-subscriber", env!("CARGO_PKG_NAME")));
 
     match tracing_format {
@@ -95,5 +199,86 @@ pub fn init_tracing_subscriber(tracing_format: TracingFormat) -> Result<Guard> {
             .init(),
     }
 
-    Ok(Guard { tracer: provider })
+    Ok(Guard {
+        tracer: init_tracer_provider(None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{TraceContextExt, Tracer, TracerProvider};
+    use tokio::net::TcpListener;
+
+    async fn unreachable() -> Result<Url> {
+        // nothing is listening once the listener is dropped
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        drop(listener);
+
+        Url::parse(&format!("http://{address}")).map_err(Into::into)
+    }
+
+    #[test]
+    fn suppressed_failures() {
+        let interval = Duration::from_secs(60);
+        let now = Instant::now();
+
+        let mut suppressed = Suppressed::default();
+        assert_eq!(Some(0), suppressed.failure(now, interval));
+        assert_eq!(
+            None,
+            suppressed.failure(now + Duration::from_secs(1), interval)
+        );
+        assert_eq!(
+            None,
+            suppressed.failure(now + Duration::from_secs(2), interval)
+        );
+        assert_eq!(Some(2), suppressed.failure(now + interval, interval));
+        assert_eq!(None, suppressed.failure(now + interval, interval));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unreachable_collector() -> Result<()> {
+        let endpoint = unreachable().await?;
+
+        let provider = init_tracer_provider(Some(&endpoint));
+
+        provider.tracer("test").in_span("request", |cx| {
+            assert!(cx.span().span_context().is_sampled())
+        });
+
+        assert!(provider.force_flush().is_ok());
+        assert!(provider.shutdown().is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn export_failures_are_rate_limited() -> Result<()> {
+        let endpoint = unreachable().await?;
+
+        let exporter = RateLimited::new(span_exporter(Some(&endpoint))?, Duration::from_secs(60));
+        let suppressed = exporter.suppressed.clone();
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .build();
+
+        let tracer = provider.tracer("test");
+
+        tracer.in_span("first", |_| {});
+        assert!(provider.force_flush().is_ok());
+
+        tracer.in_span("second", |_| {});
+        assert!(provider.force_flush().is_ok());
+
+        let suppressed = *suppressed.lock()?;
+        assert!(suppressed.warned_at.is_some());
+        assert_eq!(1, suppressed.failures);
+
+        assert!(provider.shutdown().is_ok());
+
+        Ok(())
+    }
 }
@@ -13,11 +13,22 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::collections::BTreeSet;
+
 use common::{StorageType, alphanumeric_string, init_tracing, register_broker};
 use rand::{prelude::*, rng};
-use tansu_kafka_sans_io::{ErrorCode, create_topics_request::CreatableTopic};
-use tansu_server::Result;
-use tansu_storage::{OffsetCommitRequest, Storage, StorageContainer, Topition};
+use tansu_kafka_sans_io::{
+    Body, ErrorCode,
+    create_topics_request::CreatableTopic,
+    list_groups_response::ListedGroup,
+    offset_fetch_request::OffsetFetchRequestGroup,
+    offset_fetch_response::{OffsetFetchResponseGroup, OffsetFetchResponseTopic},
+};
+use tansu_server::{
+    Result,
+    coordinator::group::{ConsumerGroupHeartbeat, Coordinator, administrator::Controller},
+};
+use tansu_storage::{GroupDetail, OffsetCommitRequest, Storage, StorageContainer, Topition};
 use tracing::debug;
 use url::Url;
 use uuid::Uuid;
@@ -84,7 +95,7 @@ pub async fn offset_commit(
     assert!(co_tps.contains_key(&topition));
     assert_eq!(Some(&offset), co_tps.get(&topition));
 
-    let groups = sc.list_groups(None).await?;
+    let groups = sc.list_groups(None, None).await?;
     assert_eq!(1, groups.len());
     assert_eq!(group_id, groups[0].group_id);
 
@@ -287,7 +298,7 @@ pub async fn offset_commit_unknown_topition(
     assert!(offset_fetch.contains_key(&topition));
     assert_eq!(Some(&-1), offset_fetch.get(&topition));
 
-    let groups = sc.list_groups(None).await?;
+    let groups = sc.list_groups(None, None).await?;
     assert_eq!(0, groups.len());
 
     Ok(())
@@ -316,12 +327,132 @@ pub async fn offset_fetch_unknown_topition(
     assert!(offset_fetch.contains_key(&topition));
     assert_eq!(Some(&-1), offset_fetch.get(&topition));
 
-    let groups = sc.list_groups(None).await?;
+    let groups = sc.list_groups(None, None).await?;
     assert_eq!(0, groups.len());
 
     Ok(())
 }
 
+pub async fn offset_fetch_all_topics(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let group_id: String = alphanumeric_string(15);
+
+    let mut offsets = vec![];
+
+    for num_partitions in [2, 3] {
+        let topic_name: String = alphanumeric_string(15);
+
+        _ = sc
+            .create_topic(
+                CreatableTopic {
+                    name: topic_name.clone(),
+                    num_partitions,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        for partition_index in 0..num_partitions {
+            offsets.push((
+                Topition::new(topic_name.clone(), partition_index),
+                OffsetCommitRequest::default().offset(rng().random_range(0..i64::MAX)),
+            ));
+        }
+    }
+
+    let commit = sc.offset_commit(&group_id, None, &offsets).await?;
+    assert!(
+        commit
+            .iter()
+            .all(|(_, error_code)| *error_code == ErrorCode::None)
+    );
+
+    let expected = sc
+        .offset_fetch(
+            Some(&group_id),
+            &offsets
+                .iter()
+                .map(|(topition, _)| topition.clone())
+                .collect::<Vec<_>>(),
+            None,
+        )
+        .await?;
+    assert_eq!(offsets.len(), expected.len());
+
+    let mut controller = Controller::with_storage(sc.clone())?;
+
+    let Body::OffsetFetchResponse {
+        topics: Some(topics),
+        ..
+    } = controller
+        .offset_fetch(Some(&group_id), None, None, Some(false))
+        .await?
+    else {
+        panic!("offset fetch response")
+    };
+
+    assert_eq!(
+        expected,
+        topics
+            .iter()
+            .flat_map(|OffsetFetchResponseTopic { name, partitions }| {
+                partitions.iter().flatten().map(|partition| {
+                    (
+                        Topition::new(name.clone(), partition.partition_index),
+                        partition.committed_offset,
+                    )
+                })
+            })
+            .collect()
+    );
+
+    let Body::OffsetFetchResponse {
+        groups: Some(groups),
+        ..
+    } = controller
+        .offset_fetch(
+            None,
+            None,
+            Some(&[OffsetFetchRequestGroup {
+                group_id: group_id.clone(),
+                member_id: None,
+                member_epoch: Some(-1),
+                topics: None,
+            }]),
+            Some(false),
+        )
+        .await?
+    else {
+        panic!("offset fetch response")
+    };
+
+    assert_eq!(
+        expected,
+        groups
+            .iter()
+            .flat_map(|OffsetFetchResponseGroup { topics, .. }| topics.iter().flatten())
+            .flat_map(|topic| {
+                topic.partitions.iter().flatten().map(|partition| {
+                    (
+                        Topition::new(topic.name.clone(), partition.partition_index),
+                        partition.committed_offset,
+                    )
+                })
+            })
+            .collect()
+    );
+
+    Ok(())
+}
+
 pub async fn list_groups_none(
     cluster_id: Uuid,
     broker_id: i32,
@@ -329,12 +460,79 @@ pub async fn list_groups_none(
 ) -> Result<()> {
     register_broker(&cluster_id, broker_id, &mut sc).await?;
 
-    let groups = sc.list_groups(None).await?;
+    let groups = sc.list_groups(None, None).await?;
     assert_eq!(0, groups.len());
 
     Ok(())
 }
 
+pub async fn list_groups_by_type(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let classic: String = alphanumeric_string(15);
+    let consumer: String = alphanumeric_string(15);
+
+    // a group that only has committed offsets is classic
+    assert!(
+        sc.update_group(&classic, GroupDetail::default(), None)
+            .await
+            .is_ok()
+    );
+
+    // a consumer protocol group is created by the first member heartbeat
+    let mut controller = Controller::with_storage(sc.clone())?;
+
+    let Body::ConsumerGroupHeartbeatResponse {
+        error_code,
+        member_epoch,
+        ..
+    } = controller
+        .consumer_group_heartbeat(ConsumerGroupHeartbeat {
+            group_id: consumer.as_str(),
+            member_id: "",
+            member_epoch: 0,
+            subscribed_topic_names: Some(&[]),
+        })
+        .await?
+    else {
+        panic!("expecting a consumer group heartbeat response")
+    };
+
+    assert_eq!(i16::from(ErrorCode::None), error_code);
+    assert_eq!(1, member_epoch);
+
+    let listed = |groups: Vec<ListedGroup>| {
+        groups
+            .into_iter()
+            .map(|group| (group.group_id, group.group_type))
+            .collect::<BTreeSet<_>>()
+    };
+
+    assert_eq!(
+        BTreeSet::from([(consumer.clone(), Some("consumer".into()))]),
+        listed(sc.list_groups(None, Some(&["consumer".into()][..])).await?)
+    );
+
+    assert_eq!(
+        BTreeSet::from([(classic.clone(), Some("classic".into()))]),
+        listed(sc.list_groups(None, Some(&["Classic".into()][..])).await?)
+    );
+
+    assert_eq!(
+        BTreeSet::from([
+            (classic.clone(), Some("classic".into())),
+            (consumer.clone(), Some("consumer".into()))
+        ]),
+        listed(sc.list_groups(None, None).await?)
+    );
+
+    Ok(())
+}
+
 mod pg {
     use super::*;
 
@@ -442,6 +640,21 @@ mod pg {
         .await
     }
 
+    #[tokio::test]
+    async fn offset_fetch_all_topics() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::offset_fetch_all_topics(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn list_groups_none() -> Result<()> {
         let _guard = init_tracing()?;
@@ -456,6 +669,21 @@ mod pg {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn list_groups_by_type() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::list_groups_by_type(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
 
 mod in_memory {
@@ -520,7 +748,6 @@ mod in_memory {
         .await
     }
 
-    #[ignore]
     #[tokio::test]
     async fn delete_unknown_consumer_group() -> Result<()> {
         let _guard = init_tracing()?;
@@ -566,6 +793,21 @@ mod in_memory {
         .await
     }
 
+    #[tokio::test]
+    async fn offset_fetch_all_topics() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::offset_fetch_all_topics(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn list_groups_none() -> Result<()> {
         let _guard = init_tracing()?;
@@ -580,4 +822,19 @@ mod in_memory {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn list_groups_by_type() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::list_groups_by_type(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
@@ -15,16 +15,21 @@
 
 use bytes::Bytes;
 use common::{
-    CLIENT_ID, COOPERATIVE_STICKY, HeartbeatResponse, PROTOCOL_TYPE, RANGE, StorageType,
-    alphanumeric_string, heartbeat, join, join_group, register_broker, sync_group,
+    CLIENT_HOST, CLIENT_ID, COOPERATIVE_STICKY, HeartbeatResponse, JoinResponse, PROTOCOL_TYPE,
+    RANGE, StorageType, alphanumeric_string, heartbeat, join, join_group, register_broker,
+    sync_group,
 };
 use rand::{prelude::*, rng};
+use std::{sync::Arc, time::Duration};
 use tansu_kafka_sans_io::{
-    ErrorCode, join_group_request::JoinGroupRequestProtocol,
-    sync_group_request::SyncGroupRequestAssignment,
+    ErrorCode, describe_groups_response::DescribedGroup,
+    join_group_request::JoinGroupRequestProtocol, sync_group_request::SyncGroupRequestAssignment,
 };
-use tansu_server::{Result, coordinator::group::administrator::Controller};
-use tansu_storage::StorageContainer;
+use tansu_server::{
+    Result,
+    coordinator::group::{Coordinator, administrator::Controller},
+};
+use tansu_storage::{Storage, StorageContainer, clock::ManualClock};
 use tracing::debug;
 use url::Url;
 use uuid::Uuid;
@@ -406,94 +411,846 @@ pub async fn lifecycle(cluster_id: Uuid, broker_id: i32, mut sc: StorageContaine
     Ok(())
 }
 
-mod pg {
-    use super::*;
+pub async fn describe_members(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
 
-    fn storage_container(cluster: impl Into<String>, node: i32) -> Result<StorageContainer> {
-        Url::parse("tcp://127.0.0.1/")
-            .map_err(Into::into)
-            .and_then(|advertised_listener| {
-                common::storage_container(
-                    StorageType::Postgres,
-                    cluster,
-                    node,
-                    advertised_listener,
-                    None,
-                )
-            })
-    }
+    let mut controller = Controller::with_storage(sc.clone())?;
 
-    #[tokio::test]
-    async fn reject_empty_member_id_on_join() -> Result<()> {
-        let _guard = common::init_tracing()?;
+    let session_timeout_ms = 45_000;
+    let rebalance_timeout_ms = Some(300_000);
+    let group_instance_id = None;
 
-        let cluster_id = Uuid::now_v7();
-        let broker_id = rng().random_range(0..i32::MAX);
+    let group_id: String = alphanumeric_string(15);
+    debug!(?group_id);
 
-        super::reject_empty_member_id_on_join(
-            cluster_id,
-            broker_id,
-            storage_container(cluster_id, broker_id)?,
+    let range_meta = common::random_bytes(15);
+
+    let member = join(
+        &mut controller,
+        group_id.as_str(),
+        None,
+        None,
+        Some(
+            [
+                JoinGroupRequestProtocol {
+                    name: RANGE.into(),
+                    metadata: range_meta.clone(),
+                },
+                JoinGroupRequestProtocol {
+                    name: COOPERATIVE_STICKY.into(),
+                    metadata: common::random_bytes(15),
+                },
+            ]
+            .into(),
+        ),
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+    debug!(?member);
+
+    let assignment = common::random_bytes(15);
+
+    let sync_response = sync_group(
+        &mut controller,
+        group_id.as_str(),
+        member.generation(),
+        member.id(),
+        group_instance_id,
+        PROTOCOL_TYPE,
+        RANGE,
+        &[SyncGroupRequestAssignment {
+            member_id: member.id().into(),
+            assignment: assignment.clone(),
+        }],
+    )
+    .await?;
+    assert_eq!(ErrorCode::None, sync_response.error_code);
+
+    let described = sc
+        .describe_groups(Some(&[group_id.clone()]), false)
+        .await?
+        .iter()
+        .map(DescribedGroup::from)
+        .collect::<Vec<_>>();
+
+    assert_eq!(1, described.len());
+    assert_eq!(i16::from(ErrorCode::None), described[0].error_code);
+    assert_eq!(group_id, described[0].group_id);
+    assert_eq!("Stable", described[0].group_state);
+    assert_eq!(PROTOCOL_TYPE, described[0].protocol_type);
+    assert_eq!(RANGE, described[0].protocol_data);
+
+    let members = described[0].members.as_deref().unwrap_or_default();
+    assert_eq!(1, members.len());
+    assert_eq!(member.id(), members[0].member_id);
+    assert_eq!(None, members[0].group_instance_id);
+    assert_eq!(CLIENT_ID, members[0].client_id);
+    assert_eq!(CLIENT_HOST, members[0].client_host);
+    assert_eq!(range_meta, members[0].member_metadata);
+    assert_eq!(assignment, members[0].member_assignment);
+
+    Ok(())
+}
+
+pub async fn session_timeout_expiry(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let clock = ManualClock::default();
+    let mut controller = Controller::with_storage(sc.clone())?.clock(Arc::new(clock.clone()));
+
+    let session_timeout_ms = 6_000;
+    let rebalance_timeout_ms = Some(300_000);
+    let group_instance_id = None;
+
+    let group_id: String = alphanumeric_string(15);
+    debug!(?group_id);
+
+    // 1st member forms the group
+    //
+    let first_member = join(
+        &mut controller,
+        group_id.as_str(),
+        None,
+        None,
+        None,
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+
+    let sync_response = sync_group(
+        &mut controller,
+        group_id.as_str(),
+        first_member.generation(),
+        first_member.id(),
+        group_instance_id,
+        PROTOCOL_TYPE,
+        RANGE,
+        &[SyncGroupRequestAssignment {
+            member_id: first_member.id().into(),
+            assignment: common::random_bytes(15),
+        }],
+    )
+    .await?;
+    assert_eq!(ErrorCode::None, sync_response.error_code);
+
+    // 2nd member joins, rebalancing the group
+    //
+    let second_member = join(
+        &mut controller,
+        group_id.as_str(),
+        None,
+        None,
+        None,
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+
+    assert_eq!(
+        HeartbeatResponse {
+            error_code: ErrorCode::RebalanceInProgress,
+        },
+        heartbeat(
+            &mut controller,
+            group_id.as_str(),
+            first_member.generation(),
+            first_member.id(),
+            group_instance_id
         )
-        .await
+        .await?
+    );
+
+    let first_member = join(
+        &mut controller,
+        group_id.as_str(),
+        Some(first_member.id()),
+        None,
+        Some(first_member.protocols().into()),
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+    assert!(first_member.is_leader());
+    assert_eq!(first_member.generation(), second_member.generation());
+
+    let assignments = [
+        SyncGroupRequestAssignment {
+            member_id: first_member.id().into(),
+            assignment: common::random_bytes(15),
+        },
+        SyncGroupRequestAssignment {
+            member_id: second_member.id().into(),
+            assignment: common::random_bytes(15),
+        },
+    ];
+
+    for member in [&first_member, &second_member] {
+        let sync_response = sync_group(
+            &mut controller,
+            group_id.as_str(),
+            member.generation(),
+            member.id(),
+            group_instance_id,
+            PROTOCOL_TYPE,
+            RANGE,
+            &assignments,
+        )
+        .await?;
+        assert_eq!(ErrorCode::None, sync_response.error_code);
     }
 
-    #[tokio::test]
-    async fn lifecycle() -> Result<()> {
-        let _guard = common::init_tracing()?;
+    // 2nd member stops heartbeating, while the 1st continues until
+    // the 2nd is evicted and the group rebalances
+    //
+    let mut rebalancing = false;
 
-        let cluster_id = Uuid::now_v7();
-        let broker_id = rng().random_range(0..i32::MAX);
+    for _ in 0..(2 * session_timeout_ms / 1_000) {
+        clock.advance(Duration::from_secs(1));
 
-        super::lifecycle(
-            cluster_id,
-            broker_id,
-            storage_container(cluster_id, broker_id)?,
+        match heartbeat(
+            &mut controller,
+            group_id.as_str(),
+            first_member.generation(),
+            first_member.id(),
+            group_instance_id,
         )
-        .await
+        .await?
+        {
+            HeartbeatResponse {
+                error_code: ErrorCode::None,
+            } => continue,
+
+            HeartbeatResponse {
+                error_code: ErrorCode::RebalanceInProgress,
+            } => {
+                rebalancing = true;
+                break;
+            }
+
+            otherwise => panic!("{otherwise:?}"),
+        }
     }
+
+    assert!(rebalancing);
+
+    // the evicted member is no longer known
+    //
+    assert_eq!(
+        HeartbeatResponse {
+            error_code: ErrorCode::UnknownMemberId,
+        },
+        heartbeat(
+            &mut controller,
+            group_id.as_str(),
+            second_member.generation(),
+            second_member.id(),
+            group_instance_id
+        )
+        .await?
+    );
+
+    // 1st member rejoins into the next generation
+    //
+    let rejoined = join(
+        &mut controller,
+        group_id.as_str(),
+        Some(first_member.id()),
+        None,
+        Some(first_member.protocols().into()),
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+    assert!(rejoined.is_leader());
+    assert_eq!(first_member.generation() + 1, rejoined.generation());
+
+    let assignment = common::random_bytes(15);
+
+    let sync_response = sync_group(
+        &mut controller,
+        group_id.as_str(),
+        rejoined.generation(),
+        rejoined.id(),
+        group_instance_id,
+        PROTOCOL_TYPE,
+        RANGE,
+        &[SyncGroupRequestAssignment {
+            member_id: rejoined.id().into(),
+            assignment: assignment.clone(),
+        }],
+    )
+    .await?;
+    assert_eq!(ErrorCode::None, sync_response.error_code);
+    assert_eq!(assignment, sync_response.assignment);
+
+    Ok(())
 }
 
-mod in_memory {
-    use super::*;
+pub async fn session_timeout_bounds(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
 
-    fn storage_container(cluster: impl Into<String>, node: i32) -> Result<StorageContainer> {
-        Url::parse("tcp://127.0.0.1/")
-            .map_err(Into::into)
-            .and_then(|advertised_listener| {
-                common::storage_container(
-                    StorageType::InMemory,
-                    cluster,
-                    node,
-                    advertised_listener,
-                    None,
-                )
-            })
-    }
+    let mut controller = Controller::with_storage(sc.clone())?
+        .min_session_timeout_ms(6_000)
+        .max_session_timeout_ms(1_800_000);
 
-    #[tokio::test]
-    async fn reject_empty_member_id_on_join() -> Result<()> {
-        let _guard = common::init_tracing()?;
+    let rebalance_timeout_ms = Some(300_000);
 
-        let cluster_id = Uuid::now_v7();
-        let broker_id = rng().random_range(0..i32::MAX);
+    let group_id: String = alphanumeric_string(15);
+    debug!(?group_id);
 
-        super::reject_empty_member_id_on_join(
-            cluster_id,
-            broker_id,
-            storage_container(cluster_id, broker_id)?,
-        )
-        .await
-    }
+    let protocols = [JoinGroupRequestProtocol {
+        name: RANGE.into(),
+        metadata: common::random_bytes(15),
+    }];
 
-    #[tokio::test]
-    async fn lifecycle() -> Result<()> {
-        let _guard = common::init_tracing()?;
+    for session_timeout_ms in [5_999, 1_800_001] {
+        let rejected = join_group(
+            &mut controller,
+            Some(CLIENT_ID),
+            group_id.as_str(),
+            session_timeout_ms,
+            rebalance_timeout_ms,
+            "",
+            None,
+            PROTOCOL_TYPE,
+            Some(&protocols[..]),
+            None,
+        )
+        .await?;
 
-        let cluster_id = Uuid::now_v7();
-        let broker_id = rng().random_range(0..i32::MAX);
+        assert_eq!(ErrorCode::InvalidSessionTimeout, rejected.error_code);
+        assert_eq!(-1, rejected.generation_id);
+        assert!(rejected.member_id.is_empty());
+    }
 
-        super::lifecycle(
+    let member = join(
+        &mut controller,
+        group_id.as_str(),
+        None,
+        None,
+        Some(protocols.into()),
+        6_000,
+        rebalance_timeout_ms,
+    )
+    .await?;
+    assert!(member.is_leader());
+
+    Ok(())
+}
+
+pub async fn rebalance_timeout(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let clock = ManualClock::default();
+    let mut controller = Controller::with_storage(sc.clone())?.clock(Arc::new(clock.clone()));
+
+    let session_timeout_ms = 30_000;
+    let rebalance_timeout_ms = Some(5_000);
+    let group_instance_id = None;
+
+    let group_id: String = alphanumeric_string(15);
+    debug!(?group_id);
+
+    // 1st member forms the group
+    //
+    let first_member = join(
+        &mut controller,
+        group_id.as_str(),
+        None,
+        None,
+        None,
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+
+    let sync_response = sync_group(
+        &mut controller,
+        group_id.as_str(),
+        first_member.generation(),
+        first_member.id(),
+        group_instance_id,
+        PROTOCOL_TYPE,
+        RANGE,
+        &[SyncGroupRequestAssignment {
+            member_id: first_member.id().into(),
+            assignment: common::random_bytes(15),
+        }],
+    )
+    .await?;
+    assert_eq!(ErrorCode::None, sync_response.error_code);
+
+    // 2nd member joins, rebalancing the group
+    //
+    let second_member = join(
+        &mut controller,
+        group_id.as_str(),
+        None,
+        None,
+        None,
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+
+    let first_member = join(
+        &mut controller,
+        group_id.as_str(),
+        Some(first_member.id()),
+        None,
+        Some(first_member.protocols().into()),
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+    assert!(first_member.is_leader());
+    assert_eq!(first_member.generation(), second_member.generation());
+
+    // only the leader syncs, the 2nd member never does
+    //
+    let sync_response = sync_group(
+        &mut controller,
+        group_id.as_str(),
+        first_member.generation(),
+        first_member.id(),
+        group_instance_id,
+        PROTOCOL_TYPE,
+        RANGE,
+        &[
+            SyncGroupRequestAssignment {
+                member_id: first_member.id().into(),
+                assignment: common::random_bytes(15),
+            },
+            SyncGroupRequestAssignment {
+                member_id: second_member.id().into(),
+                assignment: common::random_bytes(15),
+            },
+        ],
+    )
+    .await?;
+    assert_eq!(ErrorCode::None, sync_response.error_code);
+
+    // the 2nd member is evicted once the rebalance timeout has
+    // elapsed, well within its session timeout
+    //
+    let mut rebalancing = false;
+
+    for _ in 0..(session_timeout_ms / 1_000 / 2) {
+        clock.advance(Duration::from_secs(1));
+
+        match heartbeat(
+            &mut controller,
+            group_id.as_str(),
+            first_member.generation(),
+            first_member.id(),
+            group_instance_id,
+        )
+        .await?
+        {
+            HeartbeatResponse {
+                error_code: ErrorCode::None,
+            } => continue,
+
+            HeartbeatResponse {
+                error_code: ErrorCode::RebalanceInProgress,
+            } => {
+                rebalancing = true;
+                break;
+            }
+
+            otherwise => panic!("{otherwise:?}"),
+        }
+    }
+
+    assert!(rebalancing);
+
+    // 1st member rejoins alone into the next generation
+    //
+    let rejoined = join(
+        &mut controller,
+        group_id.as_str(),
+        Some(first_member.id()),
+        None,
+        Some(first_member.protocols().into()),
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+    assert_eq!(first_member.generation() + 1, rejoined.generation());
+
+    let JoinResponse::Leader { ref members, .. } = rejoined else {
+        panic!("{rejoined:?}")
+    };
+    assert_eq!(
+        vec![first_member.id()],
+        members
+            .iter()
+            .map(|member| member.member_id.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    let assignment = common::random_bytes(15);
+
+    let sync_response = sync_group(
+        &mut controller,
+        group_id.as_str(),
+        rejoined.generation(),
+        rejoined.id(),
+        group_instance_id,
+        PROTOCOL_TYPE,
+        RANGE,
+        &[SyncGroupRequestAssignment {
+            member_id: rejoined.id().into(),
+            assignment: assignment.clone(),
+        }],
+    )
+    .await?;
+    assert_eq!(ErrorCode::None, sync_response.error_code);
+    assert_eq!(assignment, sync_response.assignment);
+
+    Ok(())
+}
+
+pub async fn delete_non_empty_group(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let mut controller = Controller::with_storage(sc.clone())?;
+
+    let session_timeout_ms = 45_000;
+    let rebalance_timeout_ms = Some(300_000);
+    let group_instance_id = None;
+
+    let group_id: String = alphanumeric_string(15);
+    debug!(?group_id);
+
+    let member = join(
+        &mut controller,
+        group_id.as_str(),
+        None,
+        None,
+        None,
+        session_timeout_ms,
+        rebalance_timeout_ms,
+    )
+    .await?;
+    debug!(?member);
+
+    let sync_response = sync_group(
+        &mut controller,
+        group_id.as_str(),
+        member.generation(),
+        member.id(),
+        group_instance_id,
+        PROTOCOL_TYPE,
+        RANGE,
+        &[SyncGroupRequestAssignment {
+            member_id: member.id().into(),
+            assignment: common::random_bytes(15),
+        }],
+    )
+    .await?;
+    assert_eq!(ErrorCode::None, sync_response.error_code);
+
+    // the group has an active member
+    //
+    let deleted = sc.delete_groups(Some(&[group_id.clone()])).await?;
+    assert_eq!(1, deleted.len());
+    assert_eq!(group_id, deleted[0].group_id);
+    assert_eq!(
+        ErrorCode::NonEmptyGroup,
+        ErrorCode::try_from(deleted[0].error_code)?
+    );
+
+    let leave_response = common::leave(
+        &mut controller,
+        group_id.as_str(),
+        member.id(),
+        group_instance_id,
+    )
+    .await?;
+    assert_eq!(ErrorCode::None, leave_response.error_code);
+
+    // the group is now empty
+    //
+    let deleted = sc.delete_groups(Some(&[group_id.clone()])).await?;
+    assert_eq!(1, deleted.len());
+    assert_eq!(group_id, deleted[0].group_id);
+    assert_eq!(ErrorCode::None, ErrorCode::try_from(deleted[0].error_code)?);
+
+    let unknown: String = alphanumeric_string(15);
+
+    let deleted = sc.delete_groups(Some(&[unknown.clone()])).await?;
+    assert_eq!(1, deleted.len());
+    assert_eq!(unknown, deleted[0].group_id);
+    assert_eq!(
+        ErrorCode::GroupIdNotFound,
+        ErrorCode::try_from(deleted[0].error_code)?
+    );
+
+    Ok(())
+}
+
+mod pg {
+    use super::*;
+
+    fn storage_container(cluster: impl Into<String>, node: i32) -> Result<StorageContainer> {
+        Url::parse("tcp://127.0.0.1/")
+            .map_err(Into::into)
+            .and_then(|advertised_listener| {
+                common::storage_container(
+                    StorageType::Postgres,
+                    cluster,
+                    node,
+                    advertised_listener,
+                    None,
+                )
+            })
+    }
+
+    #[tokio::test]
+    async fn reject_empty_member_id_on_join() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::reject_empty_member_id_on_join(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn lifecycle() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::lifecycle(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn describe_members() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::describe_members(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn session_timeout_expiry() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::session_timeout_expiry(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn session_timeout_bounds() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::session_timeout_bounds(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn rebalance_timeout() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::rebalance_timeout(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn delete_non_empty_group() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::delete_non_empty_group(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+}
+
+mod in_memory {
+    use super::*;
+
+    fn storage_container(cluster: impl Into<String>, node: i32) -> Result<StorageContainer> {
+        Url::parse("tcp://127.0.0.1/")
+            .map_err(Into::into)
+            .and_then(|advertised_listener| {
+                common::storage_container(
+                    StorageType::InMemory,
+                    cluster,
+                    node,
+                    advertised_listener,
+                    None,
+                )
+            })
+    }
+
+    #[tokio::test]
+    async fn reject_empty_member_id_on_join() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::reject_empty_member_id_on_join(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn lifecycle() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::lifecycle(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn describe_members() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::describe_members(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    // the pause of a follower joining the group elapses in paused time
+    #[tokio::test(start_paused = true)]
+    async fn session_timeout_expiry() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::session_timeout_expiry(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn session_timeout_bounds() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::session_timeout_bounds(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn rebalance_timeout() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::rebalance_timeout(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn delete_non_empty_group() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::delete_non_empty_group(
             cluster_id,
             broker_id,
             storage_container(cluster_id, broker_id)?,
@@ -181,6 +181,7 @@ pub(crate) async fn join_group(
     controller
         .join(
             client_id,
+            Some(CLIENT_HOST),
             group_id,
             session_timeout_ms,
             rebalance_timeout_ms,
@@ -421,6 +422,7 @@ impl JoinResponse {
 }
 
 pub(crate) const CLIENT_ID: &str = "console-consumer";
+pub(crate) const CLIENT_HOST: &str = "/127.0.0.1";
 pub(crate) const RANGE: &str = "range";
 pub(crate) const COOPERATIVE_STICKY: &str = "cooperative-sticky";
 pub(crate) const PROTOCOL_TYPE: &str = "consumer";
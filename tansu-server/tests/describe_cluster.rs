@@ -54,11 +54,12 @@ pub async fn describe(
             throttle_time_ms: 0,
             error_code,
             error_message: None,
-            controller_id: -1,
+            controller_id,
             brokers,
             cluster_authorized_operations: -2_147_483_648,
             ..
         } if error_code == i16::from(ErrorCode::None)
+        && controller_id == broker_id
         && brokers == Some(vec![DescribeClusterBroker {
             broker_id,
             host,
@@ -70,6 +71,55 @@ pub async fn describe(
     Ok(())
 }
 
+pub async fn describe_brokers(
+    cluster_id: Uuid,
+    brokers: Vec<(i32, Url, StorageContainer)>,
+) -> Result<()> {
+    debug!(%cluster_id, ?brokers);
+
+    let mut expected = vec![];
+
+    for (broker_id, advertised_listener, mut sc) in brokers.iter().cloned() {
+        register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+        expected.push(DescribeClusterBroker {
+            broker_id,
+            host: advertised_listener.host_str().unwrap().to_string(),
+            port: advertised_listener.port().unwrap() as i32,
+            rack: None,
+        });
+    }
+
+    expected.sort_by_key(|broker| broker.broker_id);
+
+    for (_, _, sc) in brokers {
+        let mut dc = DescribeClusterRequest {
+            cluster_id: cluster_id.to_string(),
+            storage: sc,
+        };
+
+        let response = dc.response(false, Some(1)).await?;
+
+        let Body::DescribeClusterResponse {
+            error_code,
+            controller_id,
+            brokers: Some(mut brokers),
+            ..
+        } = response
+        else {
+            panic!("unexpected: {response:?}")
+        };
+
+        brokers.sort_by_key(|broker| broker.broker_id);
+
+        assert_eq!(i16::from(ErrorCode::None), error_code);
+        assert_eq!(expected, brokers);
+        assert_eq!(expected[0].broker_id, controller_id);
+    }
+
+    Ok(())
+}
+
 mod pg {
     use common::{StorageType, init_tracing};
     use rand::{prelude::*, rng};
@@ -106,11 +156,36 @@ mod pg {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn describe_brokers() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = Uuid::now_v7();
+
+        let mut brokers = vec![];
+
+        for port in [9092, 9093] {
+            let node = rng().random_range(0..i32::MAX);
+            let advertised_listener = Url::parse(&format!("tcp://example.com:{port}/"))?;
+
+            brokers.push((
+                node,
+                advertised_listener.clone(),
+                storage_container(cluster, node, advertised_listener)?,
+            ));
+        }
+
+        super::describe_brokers(cluster, brokers).await
+    }
 }
 
 mod in_memory {
     use common::{StorageType, init_tracing};
+    use object_store::memory::InMemory;
     use rand::{prelude::*, rng};
+    use std::{sync::Arc, time::Duration};
+    use tansu_storage::{BROKER_LIVENESS, Storage, clock::ManualClock, dynostore::DynoStore};
 
     use super::*;
 
@@ -144,4 +219,89 @@ mod in_memory {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn describe_brokers() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = Uuid::now_v7();
+
+        let dyno_store = DynoStore::new(&cluster.to_string(), 0, InMemory::new());
+
+        let mut brokers = vec![];
+
+        for port in [9092, 9093] {
+            let node = rng().random_range(0..i32::MAX);
+            let advertised_listener = Url::parse(&format!("tcp://example.com:{port}/"))?;
+
+            brokers.push((
+                node,
+                advertised_listener.clone(),
+                StorageContainer::DynoStore(
+                    dyno_store.clone().advertised_listener(advertised_listener),
+                ),
+            ));
+        }
+
+        super::describe_brokers(cluster, brokers).await
+    }
+
+    #[tokio::test]
+    async fn controller_is_live() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster = Uuid::now_v7();
+
+        let clock = ManualClock::default();
+        let dyno_store =
+            DynoStore::new(&cluster.to_string(), 0, InMemory::new()).clock(Arc::new(clock.clone()));
+
+        let mut expired = StorageContainer::DynoStore(
+            dyno_store
+                .clone()
+                .advertised_listener(Url::parse("tcp://example.com:9092/")?),
+        );
+        register_broker(&cluster, 1, &mut expired).await?;
+
+        clock.advance(BROKER_LIVENESS / 2);
+
+        let advertised_listener = Url::parse("tcp://example.com:9093/")?;
+        let mut live = StorageContainer::DynoStore(
+            dyno_store
+                .clone()
+                .advertised_listener(advertised_listener.clone()),
+        );
+        register_broker(&cluster, 2, &mut live).await?;
+
+        clock.advance(BROKER_LIVENESS / 2 + Duration::from_secs(1));
+
+        let response = DescribeClusterRequest {
+            cluster_id: cluster.to_string(),
+            storage: live,
+        }
+        .response(false, Some(1))
+        .await?;
+
+        let Body::DescribeClusterResponse {
+            controller_id,
+            brokers,
+            ..
+        } = response
+        else {
+            panic!("unexpected: {response:?}")
+        };
+
+        assert_eq!(2, controller_id);
+        assert_eq!(
+            Some(vec![DescribeClusterBroker {
+                broker_id: 2,
+                host: "example.com".into(),
+                port: 9093,
+                rack: None,
+            }]),
+            brokers
+        );
+
+        Ok(())
+    }
 }
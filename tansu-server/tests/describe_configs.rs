@@ -16,7 +16,7 @@
 use common::{alphanumeric_string, register_broker};
 use rand::{prelude::*, rng};
 use tansu_kafka_sans_io::{
-    ConfigResource, ConfigSource, ErrorCode, OpType,
+    ConfigResource, ConfigSource, ConfigType, ErrorCode, OpType,
     create_topics_request::{CreatableTopic, CreatableTopicConfig},
     describe_configs_request::DescribeConfigsResource,
     describe_configs_response::{DescribeConfigsResourceResult, DescribeConfigsResult},
@@ -25,6 +25,7 @@ use tansu_kafka_sans_io::{
 use tansu_server::{Result, broker::describe_configs::DescribeConfigsRequest};
 use tansu_storage::{Storage, StorageContainer};
 use tracing::debug;
+use url::Url;
 use uuid::Uuid;
 pub mod common;
 
@@ -99,7 +100,7 @@ pub async fn single_topic(
                     config_source: Some(ConfigSource::DefaultConfig.into()),
                     is_sensitive: false,
                     synonyms: Some([].into()),
-                    config_type: Some(ConfigResource::Topic.into()),
+                    config_type: Some(ConfigType::List.into()),
                     documentation: Some("".into()),
                 }]
                 .into(),
@@ -213,7 +214,7 @@ pub async fn alter_single_topic(
                     config_source: Some(ConfigSource::DefaultConfig.into()),
                     is_sensitive: false,
                     synonyms: Some([].into()),
-                    config_type: Some(ConfigResource::Topic.into()),
+                    config_type: Some(ConfigType::List.into()),
                     documentation: Some("".into()),
                 }]
                 .into(),
@@ -262,7 +263,7 @@ pub async fn alter_single_topic(
                     config_source: Some(ConfigSource::DefaultConfig.into()),
                     is_sensitive: false,
                     synonyms: Some([].into()),
-                    config_type: Some(ConfigResource::Topic.into()),
+                    config_type: Some(ConfigType::List.into()),
                     documentation: Some("".into()),
                 }]
                 .into(),
@@ -310,9 +311,162 @@ pub async fn alter_single_topic(
     Ok(())
 }
 
+pub async fn broker(cluster_id: Uuid, broker_id: i32, mut sc: StorageContainer) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let listener = Url::parse("tcp://0.0.0.0:9092/")?;
+    let advertised_listener = Url::parse("tcp://localhost:9092/")?;
+
+    let mut request = DescribeConfigsRequest::with_storage(sc)
+        .node_id(broker_id)
+        .listener(listener.clone())
+        .advertised_listener(advertised_listener.clone());
+
+    let results = request
+        .response(
+            Some(&[DescribeConfigsResource {
+                resource_type: ConfigResource::Broker.into(),
+                resource_name: broker_id.to_string(),
+                configuration_keys: None,
+            }]),
+            Some(true),
+            Some(true),
+        )
+        .await
+        .inspect(|results| debug!(?results))?;
+
+    assert_eq!(1, results.len());
+    assert_eq!(i16::from(ErrorCode::None), results[0].error_code);
+    assert_eq!(broker_id.to_string(), results[0].resource_name);
+
+    let configs = results[0].configs.as_deref().unwrap_or_default();
+
+    let config = |name: &str| {
+        configs
+            .iter()
+            .find(|config| config.name == name)
+            .map(|config| {
+                (
+                    config.value.clone(),
+                    config.config_source.map(ConfigSource::from),
+                )
+            })
+    };
+
+    assert_eq!(
+        Some((
+            Some(broker_id.to_string()),
+            Some(ConfigSource::StaticBrokerConfig)
+        )),
+        config("node.id")
+    );
+
+    assert_eq!(
+        Some((
+            Some(listener.to_string()),
+            Some(ConfigSource::StaticBrokerConfig)
+        )),
+        config("listeners")
+    );
+
+    assert_eq!(
+        Some((
+            Some(advertised_listener.to_string()),
+            Some(ConfigSource::StaticBrokerConfig)
+        )),
+        config("advertised.listeners")
+    );
+
+    assert_eq!(
+        Some((Some("1".into()), Some(ConfigSource::DefaultConfig))),
+        config("num.partitions")
+    );
+
+    assert_eq!(
+        Some((Some("3".into()), Some(ConfigSource::DefaultConfig))),
+        config("default.replication.factor")
+    );
+
+    assert_eq!(
+        Some((Some("604800000".into()), Some(ConfigSource::DefaultConfig))),
+        config("log.retention.ms")
+    );
+
+    assert!(configs.iter().all(|config| {
+        config
+            .documentation
+            .as_deref()
+            .is_some_and(|doc| !doc.is_empty())
+    }));
+
+    // only the requested keys, for a broker logger
+    let results = request
+        .response(
+            Some(&[
+                DescribeConfigsResource {
+                    resource_type: ConfigResource::Broker.into(),
+                    resource_name: broker_id.to_string(),
+                    configuration_keys: Some(vec!["log.retention.bytes".into()]),
+                },
+                DescribeConfigsResource {
+                    resource_type: ConfigResource::BrokerLogger.into(),
+                    resource_name: broker_id.to_string(),
+                    configuration_keys: None,
+                },
+            ]),
+            Some(false),
+            Some(false),
+        )
+        .await
+        .inspect(|results| debug!(?results))?;
+
+    assert_eq!(2, results.len());
+
+    assert_eq!(
+        Some(vec![DescribeConfigsResourceResult {
+            name: "log.retention.bytes".into(),
+            value: Some("-1".into()),
+            read_only: true,
+            is_default: None,
+            config_source: Some(ConfigSource::DefaultConfig.into()),
+            is_sensitive: false,
+            synonyms: Some([].into()),
+            config_type: Some(ConfigType::Long.into()),
+            documentation: Some("".into()),
+        }]),
+        results[0].configs
+    );
+
+    assert_eq!(
+        Some(ConfigSource::DynamicBrokerLoggerConfig),
+        results[1]
+            .configs
+            .as_deref()
+            .and_then(|configs| configs.iter().find(|config| config.name == "root"))
+            .and_then(|config| config.config_source.map(ConfigSource::from))
+    );
+
+    // a different broker
+    let results = request
+        .response(
+            Some(&[DescribeConfigsResource {
+                resource_type: ConfigResource::Broker.into(),
+                resource_name: broker_id.wrapping_add(1).to_string(),
+                configuration_keys: None,
+            }]),
+            Some(false),
+            Some(false),
+        )
+        .await?;
+
+    assert_eq!(1, results.len());
+    assert_eq!(i16::from(ErrorCode::InvalidRequest), results[0].error_code);
+
+    Ok(())
+}
+
 mod pg {
     use common::{StorageType, init_tracing};
-    use url::Url;
 
     use super::*;
 
@@ -345,6 +499,21 @@ mod pg {
         .await
     }
 
+    #[tokio::test]
+    async fn broker() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::broker(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn single_topic() -> Result<()> {
         let _guard = init_tracing()?;
@@ -363,7 +532,6 @@ mod pg {
 
 mod in_memory {
     use common::{StorageType, init_tracing};
-    use url::Url;
 
     use super::*;
 
@@ -396,6 +564,21 @@ mod in_memory {
         .await
     }
 
+    #[tokio::test]
+    async fn broker() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::broker(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn single_topic() -> Result<()> {
         let _guard = init_tracing()?;
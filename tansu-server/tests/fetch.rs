@@ -19,14 +19,23 @@ use bytes::Bytes;
 use common::{FetchResponse, StorageType, alphanumeric_string, init_tracing, register_broker};
 use rand::{prelude::*, rng};
 use tansu_kafka_sans_io::{
-    ErrorCode, IsolationLevel,
+    BatchAttribute, Body, Compression, ControlBatch, EndTransactionMarker, ErrorCode,
+    IsolationLevel,
+    add_partitions_to_txn_request::AddPartitionsToTxnTopic,
     create_topics_request::CreatableTopic,
+    delete_records_request::{DeleteRecordsPartition, DeleteRecordsTopic},
     fetch_request::{FetchPartition, FetchTopic},
-    record::{Record, inflated},
+    metadata_request::MetadataRequestTopic,
+    produce_request::{PartitionProduceData, TopicProduceData},
+    record::{Record, deflated, inflated},
+};
+use tansu_server::{
+    Result,
+    broker::{fetch::FetchRequest, metadata::MetadataRequest, produce::ProduceRequest},
 };
-use tansu_server::{Result, broker::fetch::FetchRequest};
 use tansu_storage::{
-    ListOffsetRequest, ListOffsetResponse, NULL_TOPIC_ID, Storage, StorageContainer, Topition,
+    LEADER_EPOCH, ListOffsetRequest, ListOffsetResponse, NULL_TOPIC_ID, Storage, StorageContainer,
+    Topition, TxnAddPartitionsRequest,
 };
 use tracing::{debug, error};
 use url::Url;
@@ -298,94 +307,1137 @@ pub async fn simple_non_txn(
     Ok(())
 }
 
-mod pg {
-    use super::*;
+pub async fn txn_commit_marker(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
 
-    fn storage_container(cluster: impl Into<String>, node: i32) -> Result<StorageContainer> {
-        Url::parse("tcp://127.0.0.1/")
-            .map_err(Into::into)
-            .and_then(|advertised_listener| {
-                common::storage_container(
-                    StorageType::Postgres,
-                    cluster,
-                    node,
-                    advertised_listener,
-                    None,
-                )
-            })
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 6;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let partition_index = rng().random_range(0..num_partitions);
+    let topition = Topition::new(topic_name.clone(), partition_index);
+
+    let transaction_id = alphanumeric_string(10);
+    let producer = sc
+        .init_producer(Some(transaction_id.as_str()), 10_000, Some(-1), Some(-1))
+        .await?;
+
+    _ = sc
+        .txn_add_partitions(TxnAddPartitionsRequest::VersionZeroToThree {
+            transaction_id: transaction_id.clone(),
+            producer_id: producer.id,
+            producer_epoch: producer.epoch,
+            topics: [AddPartitionsToTxnTopic {
+                name: topic_name.clone(),
+                partitions: Some([partition_index].into()),
+            }]
+            .into(),
+        })
+        .await?;
+
+    let mut values = vec![];
+
+    for base_sequence in 0..3 {
+        let value = Bytes::copy_from_slice(alphanumeric_string(15).as_bytes());
+
+        let batch = inflated::Batch::builder()
+            .record(Record::builder().value(value.clone().into()))
+            .attributes(BatchAttribute::default().transaction(true).into())
+            .producer_id(producer.id)
+            .producer_epoch(producer.epoch)
+            .base_sequence(base_sequence)
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        _ = sc
+            .produce(Some(transaction_id.as_str()), &topition, batch)
+            .await?;
+
+        values.push(value);
     }
 
-    #[tokio::test]
-    async fn empty_topic() -> Result<()> {
-        let _guard = init_tracing()?;
+    assert_eq!(
+        ErrorCode::None,
+        sc.txn_end(transaction_id.as_str(), producer.id, producer.epoch, true)
+            .await?
+    );
 
-        let cluster_id = Uuid::now_v7();
-        let broker_id = rng().random_range(0..i32::MAX);
+    let topics = [FetchTopic {
+        topic: Some(topition.topic().to_string()),
+        topic_id: Some(NULL_TOPIC_ID),
+        partitions: Some(vec![FetchPartition {
+            partition: topition.partition(),
+            current_leader_epoch: Some(-1),
+            fetch_offset: 0,
+            last_fetched_epoch: Some(-1),
+            log_start_offset: Some(-1),
+            partition_max_bytes: 50 * 1024,
+            replica_directory_id: None,
+        }]),
+    }];
 
-        super::simple_non_txn(
-            cluster_id,
-            broker_id,
-            storage_container(cluster_id, broker_id)?,
+    let fetch: FetchResponse = FetchRequest::with_storage(sc.clone())
+        .response(
+            500,
+            1,
+            Some(50 * 1024),
+            Some((&IsolationLevel::ReadCommitted).into()),
+            Some(&topics[..]),
         )
         .await
-    }
+        .and_then(TryInto::try_into)?;
 
-    #[tokio::test]
-    async fn simple_non_txn() -> Result<()> {
-        let _guard = init_tracing()?;
+    assert_eq!(ErrorCode::None, fetch.error_code());
 
-        let cluster_id = Uuid::now_v7();
-        let broker_id = rng().random_range(0..i32::MAX);
+    let batches = fetch
+        .responses()
+        .iter()
+        .flat_map(|response| response.partitions.as_deref().unwrap_or_default())
+        .flat_map(|partition| partition.records.iter())
+        .flat_map(|frame| frame.batches.iter().cloned())
+        .map(inflated::Batch::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
 
-        super::simple_non_txn(
-            cluster_id,
-            broker_id,
-            storage_container(cluster_id, broker_id)?,
+    // the commit marker follows the records of the transaction
+    let marker = batches.last().expect("commit marker");
+    let attributes = BatchAttribute::try_from(marker.attributes)?;
+    assert!(attributes.control);
+    assert!(attributes.transaction);
+    assert_eq!(producer.id, marker.producer_id);
+    assert_eq!(1, marker.records.len());
+
+    let control_batch = marker.records[0]
+        .key()
+        .map(ControlBatch::try_from)
+        .transpose()?
+        .expect("control batch key");
+    assert!(control_batch.is_commit());
+
+    assert!(
+        marker.records[0]
+            .value()
+            .map(EndTransactionMarker::try_from)
+            .transpose()?
+            .is_some()
+    );
+
+    // consumers skip control batches, only seeing the produced values
+    assert_eq!(
+        values,
+        batches
+            .iter()
+            .filter(|batch| {
+                BatchAttribute::try_from(batch.attributes)
+                    .is_ok_and(|attributes| !attributes.control)
+            })
+            .flat_map(|batch| batch.records.iter())
+            .flat_map(|record| record.value())
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+pub async fn stored_batches_unchanged(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
         )
+        .await?;
+
+    let mut produced = vec![];
+
+    for compression in [Compression::Zstd, Compression::None] {
+        let batch: deflated::Batch = (0..3)
+            .fold(
+                inflated::Batch::builder()
+                    .attributes(BatchAttribute::default().compression(compression).into())
+                    .last_offset_delta(2),
+                |builder, offset_delta| {
+                    builder.record(
+                        Record::builder()
+                            .offset_delta(offset_delta)
+                            .value(Bytes::from(alphanumeric_string(150)).into()),
+                    )
+                },
+            )
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        let response = ProduceRequest::with_storage(sc.clone())
+            .response(
+                None,
+                1,
+                5_000,
+                Some(vec![TopicProduceData {
+                    name: topic_name.clone(),
+                    partition_data: Some(vec![PartitionProduceData {
+                        index: 0,
+                        records: Some(deflated::Frame {
+                            batches: vec![batch.clone()],
+                        }),
+                    }]),
+                }]),
+            )
+            .await?;
+
+        assert_eq!(
+            Some(vec![i16::from(ErrorCode::None)]),
+            response.responses.map(|responses| responses
+                .iter()
+                .flat_map(|topic| topic.partition_responses.iter().flatten())
+                .map(|partition| partition.error_code)
+                .collect::<Vec<_>>())
+        );
+
+        produced.push(batch);
+    }
+
+    let topics = [FetchTopic {
+        topic: Some(topic_name.clone()),
+        topic_id: Some(NULL_TOPIC_ID),
+        partitions: Some(vec![FetchPartition {
+            partition: 0,
+            current_leader_epoch: Some(-1),
+            fetch_offset: 0,
+            last_fetched_epoch: Some(-1),
+            log_start_offset: Some(-1),
+            partition_max_bytes: 50 * 1024,
+            replica_directory_id: None,
+        }]),
+    }];
+
+    let fetch: FetchResponse = FetchRequest::with_storage(sc.clone())
+        .response(500, 1, Some(50 * 1024), None, Some(&topics[..]))
         .await
+        .and_then(TryInto::try_into)?;
+
+    assert_eq!(ErrorCode::None, fetch.error_code());
+
+    let fetched = fetch
+        .responses()
+        .iter()
+        .flat_map(|response| response.partitions.as_deref().unwrap_or_default())
+        .flat_map(|partition| partition.records.iter())
+        .flat_map(|frame| frame.batches.iter().cloned())
+        .collect::<Vec<_>>();
+
+    assert_eq!(produced.len(), fetched.len());
+
+    // the record data is served byte for byte as it was produced, only the
+    // offset assigned on produce differing
+    for (offset, (produced, fetched)) in produced.iter().zip(fetched.iter()).enumerate() {
+        assert_eq!(i64::try_from(offset * 3)?, fetched.base_offset);
+        assert_eq!(produced.attributes, fetched.attributes);
+        assert_eq!(produced.record_count, fetched.record_count);
+        assert_eq!(produced.record_data, fetched.record_data);
+        assert_eq!(produced.crc, fetched.crc);
+        assert!(fetched.verify_crc());
     }
+
+    assert!(
+        BatchAttribute::try_from(fetched[0].attributes)
+            .is_ok_and(|attributes| attributes.compression == Compression::Zstd)
+    );
+
+    Ok(())
 }
 
-mod in_memory {
-    use super::*;
+pub async fn preferred_read_replica(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
 
-    fn storage_container(cluster: impl Into<String>, node: i32) -> Result<StorageContainer> {
-        Url::parse("tcp://127.0.0.1/")
-            .map_err(Into::into)
-            .and_then(|advertised_listener| {
-                common::storage_container(
-                    StorageType::InMemory,
-                    cluster,
-                    node,
-                    advertised_listener,
-                    None,
-                )
-            })
-    }
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
 
-    #[tokio::test]
-    async fn empty_topic() -> Result<()> {
-        let _guard = init_tracing()?;
+    let num_partitions = 3;
 
-        let cluster_id = Uuid::now_v7();
-        let broker_id = rng().random_range(0..i32::MAX);
+    let topic_id = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+    debug!(?topic_id);
 
-        super::simple_non_txn(
-            cluster_id,
-            broker_id,
-            storage_container(cluster_id, broker_id)?,
+    let topics = [FetchTopic {
+        topic: Some(topic_name.clone()),
+        topic_id: Some(NULL_TOPIC_ID),
+        partitions: Some(
+            (0..num_partitions)
+                .map(|partition| FetchPartition {
+                    partition,
+                    current_leader_epoch: Some(-1),
+                    fetch_offset: 0,
+                    last_fetched_epoch: Some(-1),
+                    log_start_offset: Some(-1),
+                    partition_max_bytes: 50 * 1024,
+                    replica_directory_id: None,
+                })
+                .collect(),
+        ),
+    }];
+
+    // a single broker is the leader for every partition, there is no
+    // follower in the consumer's rack to read from
+    //
+    let fetch: FetchResponse = FetchRequest::with_storage(sc.clone())
+        .rack_id(Some("eu-west-2a".into()))
+        .response(
+            500,
+            1,
+            Some(50 * 1024),
+            Some((&IsolationLevel::ReadUncommitted).into()),
+            Some(&topics[..]),
         )
         .await
+        .and_then(TryInto::try_into)?;
+
+    assert_eq!(ErrorCode::None, fetch.error_code());
+
+    let partitions = fetch
+        .responses()
+        .iter()
+        .flat_map(|response| response.partitions.as_deref().unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    assert_eq!(num_partitions as usize, partitions.len());
+
+    for partition in partitions {
+        assert_eq!(Some(-1), partition.preferred_read_replica);
     }
 
-    #[tokio::test]
-    async fn simple_non_txn() -> Result<()> {
-        let _guard = init_tracing()?;
+    Ok(())
+}
 
-        let cluster_id = Uuid::now_v7();
-        let broker_id = rng().random_range(0..i32::MAX);
+pub async fn by_topic_id(cluster_id: Uuid, broker_id: i32, mut sc: StorageContainer) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
 
-        super::simple_non_txn(
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 6;
+
+    let topic_id = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+    debug!(?topic_id);
+
+    let partition_index = rng().random_range(0..num_partitions);
+    let topition = Topition::new(topic_name.clone(), partition_index);
+
+    let record_count = 3;
+
+    for n in 0..record_count {
+        let value = Bytes::copy_from_slice(alphanumeric_string(15).as_bytes());
+
+        let batch = inflated::Batch::builder()
+            .record(Record::builder().value(value.into()))
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        assert_eq!(n, sc.produce(None, &topition, batch).await?);
+    }
+
+    let metadata = |topic: MetadataRequestTopic| {
+        let mut metadata = MetadataRequest::with_storage(sc.clone());
+
+        async move {
+            let Body::MetadataResponse {
+                topics: Some(topics),
+                ..
+            } = metadata.response(Some(vec![topic])).await?
+            else {
+                panic!("metadata response")
+            };
+
+            assert_eq!(1, topics.len());
+            Ok::<_, tansu_server::Error>(topics[0].clone())
+        }
+    };
+
+    // the id of the topic is in the metadata response for its name
+    let topic = metadata(MetadataRequestTopic {
+        topic_id: None,
+        name: Some(topic_name.clone()),
+    })
+    .await?;
+    assert_eq!(i16::from(ErrorCode::None), topic.error_code);
+    assert_eq!(Some(topic_id.into_bytes()), topic.topic_id);
+
+    let fetch = |topic_id: [u8; 16], partition: i32| {
+        let mut fetch = FetchRequest::with_storage(sc.clone());
+
+        async move {
+            fetch
+                .response(
+                    500,
+                    1,
+                    Some(50 * 1024),
+                    Some((&IsolationLevel::ReadUncommitted).into()),
+                    Some(&[FetchTopic {
+                        topic: None,
+                        topic_id: Some(topic_id),
+                        partitions: Some(vec![FetchPartition {
+                            partition,
+                            current_leader_epoch: Some(-1),
+                            fetch_offset: 0,
+                            last_fetched_epoch: Some(-1),
+                            log_start_offset: Some(-1),
+                            partition_max_bytes: 50 * 1024,
+                            replica_directory_id: None,
+                        }]),
+                    }]),
+                )
+                .await
+                .and_then(TryInto::<FetchResponse>::try_into)
+        }
+    };
+
+    let fetched = fetch(topic.topic_id.unwrap_or(NULL_TOPIC_ID), partition_index).await?;
+    assert_eq!(ErrorCode::None, fetched.error_code());
+    assert_eq!(1, fetched.responses().len());
+    assert_eq!(Some(topic_id.into_bytes()), fetched.responses()[0].topic_id);
+
+    let partitions = fetched.responses()[0].partitions.as_deref().unwrap_or(&[]);
+    assert_eq!(1, partitions.len());
+    assert_eq!(i16::from(ErrorCode::None), partitions[0].error_code);
+    assert_eq!(
+        record_count,
+        partitions[0].records.as_ref().map_or(0, |records| records
+            .batches
+            .iter()
+            .map(|batch| batch.record_count as i64)
+            .sum::<i64>())
+    );
+
+    let unknown = Uuid::now_v7().into_bytes();
+
+    let topic = metadata(MetadataRequestTopic {
+        topic_id: Some(unknown),
+        name: None,
+    })
+    .await?;
+    assert_eq!(i16::from(ErrorCode::UnknownTopicId), topic.error_code);
+    assert_eq!(Some(unknown), topic.topic_id);
+
+    let fetched = fetch(unknown, partition_index).await?;
+    assert_eq!(Some(unknown), fetched.responses()[0].topic_id);
+
+    let partitions = fetched.responses()[0].partitions.as_deref().unwrap_or(&[]);
+    assert_eq!(
+        i16::from(ErrorCode::UnknownTopicId),
+        partitions[0].error_code
+    );
+    assert!(partitions[0].records.is_none());
+
+    // a partition beyond those of a known topic
+    let fetched = fetch(topic_id.into_bytes(), num_partitions).await?;
+    assert_eq!(Some(topic_id.into_bytes()), fetched.responses()[0].topic_id);
+
+    let partitions = fetched.responses()[0].partitions.as_deref().unwrap_or(&[]);
+    assert_eq!(1, partitions.len());
+    assert_eq!(
+        i16::from(ErrorCode::UnknownTopicOrPartition),
+        partitions[0].error_code
+    );
+    assert!(partitions[0].records.is_none());
+
+    Ok(())
+}
+
+pub async fn current_leader_epoch(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let topition = Topition::new(topic_name.clone(), 0);
+
+    let batch = inflated::Batch::builder()
+        .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+        .build()
+        .and_then(TryInto::try_into)?;
+
+    assert_eq!(0, sc.produce(None, &topition, batch).await?);
+
+    let fetch = |current_leader_epoch: i32| {
+        let mut fetch = FetchRequest::with_storage(sc.clone());
+        let topic_name = topic_name.clone();
+
+        async move {
+            fetch
+                .response(
+                    500,
+                    1,
+                    Some(50 * 1024),
+                    Some((&IsolationLevel::ReadUncommitted).into()),
+                    Some(&[FetchTopic {
+                        topic: Some(topic_name),
+                        topic_id: None,
+                        partitions: Some(vec![FetchPartition {
+                            partition: 0,
+                            current_leader_epoch: Some(current_leader_epoch),
+                            fetch_offset: 0,
+                            last_fetched_epoch: Some(-1),
+                            log_start_offset: Some(-1),
+                            partition_max_bytes: 50 * 1024,
+                            replica_directory_id: None,
+                        }]),
+                    }]),
+                )
+                .await
+                .and_then(TryInto::<FetchResponse>::try_into)
+        }
+    };
+
+    for (current_leader_epoch, error_code, record_count) in [
+        (LEADER_EPOCH, ErrorCode::None, 1),
+        (-1, ErrorCode::None, 1),
+        (LEADER_EPOCH + 1, ErrorCode::UnknownLeaderEpoch, 0),
+    ] {
+        let fetched = fetch(current_leader_epoch).await?;
+        assert_eq!(1, fetched.responses().len());
+
+        let partitions = fetched.responses()[0].partitions.as_deref().unwrap_or(&[]);
+        assert_eq!(1, partitions.len());
+        assert_eq!(
+            i16::from(error_code),
+            partitions[0].error_code,
+            "{current_leader_epoch}"
+        );
+        assert_eq!(
+            record_count,
+            partitions[0].records.as_ref().map_or(0, |records| records
+                .batches
+                .iter()
+                .map(|batch| batch.record_count)
+                .sum::<u32>())
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn partition_offsets(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let topition = Topition::new(topic_name.clone(), 0);
+
+    for offset in 0..5 {
+        let batch = inflated::Batch::builder()
+            .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        assert_eq!(offset, sc.produce(None, &topition, batch).await?);
+    }
+
+    // the high watermark, last stable and log start offsets of a fetch
+    let fetch = {
+        let sc = sc.clone();
+        let topic_name = topic_name.clone();
+
+        move |fetch_offset: i64| {
+            let mut fetch = FetchRequest::with_storage(sc.clone());
+            let topic_name = topic_name.clone();
+
+            async move {
+                fetch
+                    .response(
+                        500,
+                        1,
+                        Some(50 * 1024),
+                        Some((&IsolationLevel::ReadUncommitted).into()),
+                        Some(&[FetchTopic {
+                            topic: Some(topic_name),
+                            topic_id: None,
+                            partitions: Some(vec![FetchPartition {
+                                partition: 0,
+                                current_leader_epoch: None,
+                                fetch_offset,
+                                last_fetched_epoch: Some(-1),
+                                log_start_offset: Some(-1),
+                                partition_max_bytes: 50 * 1024,
+                                replica_directory_id: None,
+                            }]),
+                        }]),
+                    )
+                    .await
+                    .and_then(TryInto::<FetchResponse>::try_into)
+                    .map(|fetched| {
+                        let partition = &fetched.responses()[0]
+                            .partitions
+                            .as_deref()
+                            .unwrap_or_default()[0];
+
+                        (
+                            ErrorCode::try_from(partition.error_code).unwrap(),
+                            partition.high_watermark,
+                            partition.last_stable_offset,
+                            partition.log_start_offset,
+                        )
+                    })
+            }
+        }
+    };
+
+    assert_eq!((ErrorCode::None, 5, Some(5), Some(0)), fetch(0).await?);
+
+    // an open transaction holds back the last stable offset
+    let transaction_id = alphanumeric_string(10);
+    let producer = sc
+        .init_producer(Some(transaction_id.as_str()), 10_000, Some(-1), Some(-1))
+        .await?;
+
+    _ = sc
+        .txn_add_partitions(TxnAddPartitionsRequest::VersionZeroToThree {
+            transaction_id: transaction_id.clone(),
+            producer_id: producer.id,
+            producer_epoch: producer.epoch,
+            topics: [AddPartitionsToTxnTopic {
+                name: topic_name.clone(),
+                partitions: Some([0].into()),
+            }]
+            .into(),
+        })
+        .await?;
+
+    let batch = inflated::Batch::builder()
+        .record(Record::builder().value(Bytes::from_static(b"ipsum").into()))
+        .attributes(BatchAttribute::default().transaction(true).into())
+        .producer_id(producer.id)
+        .producer_epoch(producer.epoch)
+        .build()
+        .and_then(TryInto::try_into)?;
+
+    assert_eq!(
+        5,
+        sc.produce(Some(transaction_id.as_str()), &topition, batch)
+            .await?
+    );
+
+    assert_eq!((ErrorCode::None, 6, Some(5), Some(0)), fetch(0).await?);
+
+    // deleting records advances the log start offset
+    _ = sc
+        .delete_records(&[DeleteRecordsTopic {
+            name: topic_name.clone(),
+            partitions: Some(vec![DeleteRecordsPartition {
+                partition_index: 0,
+                offset: 3,
+            }]),
+        }])
+        .await?;
+
+    assert_eq!((ErrorCode::None, 6, Some(5), Some(3)), fetch(3).await?);
+
+    assert_eq!(
+        (ErrorCode::OffsetOutOfRange, 6, Some(5), Some(3)),
+        fetch(0).await?
+    );
+
+    Ok(())
+}
+
+pub async fn max_partitions(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 5;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    for partition in 0..num_partitions {
+        let batch = inflated::Batch::builder()
+            .record(Record::builder().value(Bytes::from_static(b"lorem").into()))
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        _ = sc
+            .produce(None, &Topition::new(topic_name.clone(), partition), batch)
+            .await?;
+    }
+
+    let topics = [FetchTopic {
+        topic: Some(topic_name.clone()),
+        topic_id: None,
+        partitions: Some(
+            (0..num_partitions)
+                .map(|partition| FetchPartition {
+                    partition,
+                    current_leader_epoch: None,
+                    fetch_offset: 0,
+                    last_fetched_epoch: Some(-1),
+                    log_start_offset: Some(-1),
+                    partition_max_bytes: 50 * 1024,
+                    replica_directory_id: None,
+                })
+                .collect(),
+        ),
+    }];
+
+    // the same partitions requested in a different order
+    let reordered = [FetchTopic {
+        partitions: topics[0].partitions.clone().map(|mut partitions| {
+            partitions.reverse();
+            partitions
+        }),
+        ..topics[0].clone()
+    }];
+
+    let max_partitions = 3;
+
+    let mut fetch = FetchRequest::with_storage(sc.clone()).max_partitions(Some(max_partitions));
+
+    let mut served = vec![];
+
+    for topics in [&topics, &topics, &reordered] {
+        let fetched = fetch
+            .response(
+                500,
+                1,
+                Some(50 * 1024),
+                Some((&IsolationLevel::ReadUncommitted).into()),
+                Some(&topics[..]),
+            )
+            .await
+            .and_then(TryInto::<FetchResponse>::try_into)?;
+
+        assert_eq!(1, fetched.responses().len());
+
+        let partitions = fetched.responses()[0]
+            .partitions
+            .as_deref()
+            .unwrap_or_default();
+
+        assert_eq!(max_partitions, partitions.len());
+
+        for partition in partitions {
+            assert_eq!(ErrorCode::None, ErrorCode::try_from(partition.error_code)?);
+            assert!(partition.records.is_some());
+        }
+
+        served.push(
+            partitions
+                .iter()
+                .map(|partition| partition.partition_index)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    // the second fetch continues from where the first left off, wrapping
+    // around, with every partition served in the order requested. The
+    // third continues by partition, even though the request is reordered
+    assert_eq!(vec![vec![0, 1, 2], vec![0, 3, 4], vec![3, 2, 1]], served);
+
+    Ok(())
+}
+
+mod pg {
+    use super::*;
+
+    fn storage_container(cluster: impl Into<String>, node: i32) -> Result<StorageContainer> {
+        Url::parse("tcp://127.0.0.1/")
+            .map_err(Into::into)
+            .and_then(|advertised_listener| {
+                common::storage_container(
+                    StorageType::Postgres,
+                    cluster,
+                    node,
+                    advertised_listener,
+                    None,
+                )
+            })
+    }
+
+    #[tokio::test]
+    async fn empty_topic() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::simple_non_txn(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn simple_non_txn() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::simple_non_txn(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn preferred_read_replica() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::preferred_read_replica(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn current_leader_epoch() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::current_leader_epoch(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn partition_offsets() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::partition_offsets(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn max_partitions() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::max_partitions(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn by_topic_id() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::by_topic_id(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn txn_commit_marker() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::txn_commit_marker(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn stored_batches_unchanged() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::stored_batches_unchanged(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+}
+
+mod in_memory {
+    use super::*;
+
+    fn storage_container(cluster: impl Into<String>, node: i32) -> Result<StorageContainer> {
+        Url::parse("tcp://127.0.0.1/")
+            .map_err(Into::into)
+            .and_then(|advertised_listener| {
+                common::storage_container(
+                    StorageType::InMemory,
+                    cluster,
+                    node,
+                    advertised_listener,
+                    None,
+                )
+            })
+    }
+
+    #[tokio::test]
+    async fn empty_topic() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::simple_non_txn(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn simple_non_txn() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::simple_non_txn(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn preferred_read_replica() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::preferred_read_replica(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn current_leader_epoch() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::current_leader_epoch(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn partition_offsets() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::partition_offsets(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn max_partitions() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::max_partitions(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn by_topic_id() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::by_topic_id(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn txn_commit_marker() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::txn_commit_marker(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn stored_batches_unchanged() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::stored_batches_unchanged(
             cluster_id,
             broker_id,
             storage_container(cluster_id, broker_id)?,
@@ -30,7 +30,7 @@ use tansu_kafka_sans_io::{
 use tansu_server::{
     Result,
     broker::{
-        init_producer_id::InitProducerIdRequest,
+        init_producer_id::{InitProducerIdRequest, ProducerIdBlock},
         produce::{ProduceRequest, ProduceResponse},
     },
 };
@@ -504,6 +504,215 @@ async fn non_txn_idempotent_sequence_out_of_order(
     Ok(())
 }
 
+async fn non_txn_idempotent_sequence_window(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic = alphanumeric_string(10);
+    let index = 0;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic.clone(),
+                num_partitions: 3,
+                replication_factor: 1,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let producer = InitProducerIdRequest::with_storage(sc.clone())
+        .response(None, 0, Some(-1), Some(-1))
+        .await?;
+
+    let mut request = ProduceRequest::with_storage(sc.clone());
+
+    let acks = 0;
+    let timeout_ms = 0;
+
+    let produce_response = |error_code: ErrorCode, base_offset| ProduceResponse {
+        responses: Some(vec![TopicProduceResponse {
+            name: topic.clone(),
+            partition_responses: Some(vec![PartitionProduceResponse {
+                index,
+                error_code: error_code.into(),
+                base_offset,
+                log_append_time_ms: Some(-1),
+                log_start_offset: Some(0),
+                record_errors: Some(vec![]),
+                error_message: None,
+                current_leader: None,
+            }]),
+        }]),
+        throttle_time_ms: Some(0),
+        node_endpoints: None,
+    };
+
+    for base_sequence in 0..10 {
+        assert_eq!(
+            produce_response(ErrorCode::None, i64::from(base_sequence)),
+            request
+                .response(
+                    None,
+                    acks,
+                    timeout_ms,
+                    topic_data(
+                        topic.as_str(),
+                        index,
+                        inflated::Batch::builder()
+                            .record(
+                                Record::builder().value(
+                                    Bytes::from_static(b"Lorem ipsum dolor sit amet").into()
+                                )
+                            )
+                            .base_sequence(base_sequence)
+                            .producer_id(producer.id)
+                    )?
+                )
+                .await?
+        );
+    }
+
+    // within the default window of the 5 most recent batches
+    assert_eq!(
+        produce_response(ErrorCode::DuplicateSequenceNumber, -1),
+        request
+            .response(
+                None,
+                acks,
+                timeout_ms,
+                topic_data(
+                    topic.as_str(),
+                    index,
+                    inflated::Batch::builder()
+                        .record(
+                            Record::builder()
+                                .value(Bytes::from_static(b"Lorem ipsum dolor sit amet").into())
+                        )
+                        .base_sequence(7)
+                        .producer_id(producer.id)
+                )?
+            )
+            .await?
+    );
+
+    // outside of the window
+    assert_eq!(
+        produce_response(ErrorCode::OutOfOrderSequenceNumber, -1),
+        request
+            .response(
+                None,
+                acks,
+                timeout_ms,
+                topic_data(
+                    topic.as_str(),
+                    index,
+                    inflated::Batch::builder()
+                        .record(
+                            Record::builder()
+                                .value(Bytes::from_static(b"Lorem ipsum dolor sit amet").into())
+                        )
+                        .base_sequence(3)
+                        .producer_id(producer.id)
+                )?
+            )
+            .await?
+    );
+
+    Ok(())
+}
+
+async fn non_txn_idempotent_producer_id_block(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic = alphanumeric_string(10);
+    let index = 0;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic.clone(),
+                num_partitions: 3,
+                replication_factor: 1,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let producer_ids = Some(ProducerIdBlock::new(10));
+
+    let first = InitProducerIdRequest::with_storage(sc.clone())
+        .producer_ids(producer_ids.clone())
+        .response(None, 0, Some(-1), Some(-1))
+        .await?;
+
+    let second = InitProducerIdRequest::with_storage(sc.clone())
+        .producer_ids(producer_ids)
+        .response(None, 0, Some(-1), Some(-1))
+        .await?;
+
+    assert_eq!(ErrorCode::None, first.error);
+    assert_eq!(ErrorCode::None, second.error);
+    assert_eq!(first.id + 1, second.id);
+    assert_eq!(0, second.epoch);
+
+    let transactional_id = None;
+    let acks = 0;
+    let timeout_ms = 0;
+
+    assert_eq!(
+        ProduceResponse {
+            responses: Some(vec![TopicProduceResponse {
+                name: topic.clone(),
+                partition_responses: Some(vec![PartitionProduceResponse {
+                    index,
+                    error_code: ErrorCode::None.into(),
+                    base_offset: 0,
+                    log_append_time_ms: Some(-1),
+                    log_start_offset: Some(0),
+                    record_errors: Some(vec![]),
+                    error_message: None,
+                    current_leader: None,
+                }],),
+            }]),
+            throttle_time_ms: Some(0),
+            node_endpoints: None
+        },
+        ProduceRequest::with_storage(sc)
+            .response(
+                transactional_id,
+                acks,
+                timeout_ms,
+                topic_data(
+                    topic.as_str(),
+                    index,
+                    inflated::Batch::builder()
+                        .record(
+                            Record::builder()
+                                .value(Bytes::from_static(b"Lorem ipsum dolor sit amet").into())
+                        )
+                        .producer_id(second.id)
+                        .producer_epoch(second.epoch)
+                )?
+            )
+            .await?
+    );
+
+    Ok(())
+}
+
 mod pg {
     use super::*;
 
@@ -566,6 +775,21 @@ mod pg {
         .await
     }
 
+    #[tokio::test]
+    async fn non_txn_idempotent_sequence_window() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::non_txn_idempotent_sequence_window(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn non_txn_idempotent_sequence_out_of_order() -> Result<()> {
         let _guard = common::init_tracing()?;
@@ -579,6 +803,21 @@ mod pg {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn non_txn_idempotent_producer_id_block() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::non_txn_idempotent_producer_id_block(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
 
 mod in_memory {
@@ -643,6 +882,21 @@ mod in_memory {
         .await
     }
 
+    #[tokio::test]
+    async fn non_txn_idempotent_sequence_window() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::non_txn_idempotent_sequence_window(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn non_txn_idempotent_sequence_out_of_order() -> Result<()> {
         let _guard = common::init_tracing()?;
@@ -657,4 +911,19 @@ mod in_memory {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn non_txn_idempotent_producer_id_block() -> Result<()> {
+        let _guard = common::init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::non_txn_idempotent_producer_id_block(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
@@ -17,12 +17,21 @@ use bytes::Bytes;
 use common::{StorageType, alphanumeric_string, init_tracing, register_broker};
 use rand::{prelude::*, rng};
 use tansu_kafka_sans_io::{
-    IsolationLevel,
+    BatchAttribute, Body, ErrorCode, IsolationLevel,
+    add_partitions_to_txn_request::AddPartitionsToTxnTopic,
     create_topics_request::CreatableTopic,
+    delete_records_request::{DeleteRecordsPartition, DeleteRecordsTopic},
+    delete_records_response::{DeleteRecordsPartitionResult, DeleteRecordsTopicResult},
+    list_offsets_request::{ListOffsetsPartition, ListOffsetsTopic},
     record::{Record, inflated},
 };
-use tansu_server::Result;
-use tansu_storage::{ListOffsetRequest, Storage, StorageContainer, Topition};
+use tansu_server::{
+    Error, Result,
+    broker::{delete_records::DeleteRecordsRequest, list_offsets::ListOffsetsRequest},
+};
+use tansu_storage::{
+    ListOffsetRequest, Storage, StorageContainer, Topition, TxnAddPartitionsRequest,
+};
 use tracing::debug;
 use url::Url;
 use uuid::Uuid;
@@ -133,6 +142,442 @@ pub async fn single_record(
     Ok(())
 }
 
+pub async fn watermarks(cluster_id: Uuid, broker_id: i32, mut sc: StorageContainer) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 6;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let partition_index = rng().random_range(0..num_partitions);
+    let topition = Topition::new(topic_name.clone(), partition_index);
+
+    assert_eq!((0, 0), sc.watermarks(&topition).await?);
+
+    let records = 5;
+
+    for n in 0..records {
+        let value = Bytes::copy_from_slice(alphanumeric_string(15).as_bytes());
+
+        let batch = inflated::Batch::builder()
+            .record(Record::builder().value(value.into()))
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        assert_eq!(n, sc.produce(None, &topition, batch).await?);
+    }
+
+    assert_eq!((0, records), sc.watermarks(&topition).await?);
+
+    let deleted = sc
+        .delete_records(&[DeleteRecordsTopic {
+            name: topic_name.clone(),
+            partitions: Some(vec![DeleteRecordsPartition {
+                partition_index,
+                offset: 3,
+            }]),
+        }])
+        .await?;
+
+    assert_eq!(1, deleted.len());
+    let partitions = deleted[0].partitions.as_deref().unwrap_or_default();
+    assert_eq!(1, partitions.len());
+    assert_eq!(i16::from(ErrorCode::None), partitions[0].error_code);
+    assert_eq!(3, partitions[0].low_watermark);
+
+    assert_eq!((3, records), sc.watermarks(&topition).await?);
+
+    let offsets = [
+        (topition.clone(), ListOffsetRequest::Earliest),
+        (topition.clone(), ListOffsetRequest::Latest),
+    ];
+
+    let responses = sc
+        .list_offsets(IsolationLevel::ReadUncommitted, &offsets[..])
+        .await?;
+
+    assert_eq!(Some(3), responses[0].1.offset);
+    assert_eq!(Some(records), responses[1].1.offset);
+
+    let deleted = sc
+        .delete_records(&[DeleteRecordsTopic {
+            name: topic_name,
+            partitions: Some(vec![DeleteRecordsPartition {
+                partition_index,
+                offset: records + 1,
+            }]),
+        }])
+        .await?;
+
+    let partitions = deleted[0].partitions.as_deref().unwrap_or_default();
+    assert_eq!(
+        i16::from(ErrorCode::OffsetOutOfRange),
+        partitions[0].error_code
+    );
+    assert_eq!((3, records), sc.watermarks(&topition).await?);
+
+    Ok(())
+}
+
+pub async fn delete_records_per_partition(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 3;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let records = 5;
+
+    for partition_index in [0, 1] {
+        let topition = Topition::new(topic_name.clone(), partition_index);
+
+        for n in 0..records {
+            let value = Bytes::copy_from_slice(alphanumeric_string(15).as_bytes());
+
+            let batch = inflated::Batch::builder()
+                .record(Record::builder().value(value.into()))
+                .build()
+                .and_then(TryInto::try_into)?;
+
+            assert_eq!(n, sc.produce(None, &topition, batch).await?);
+        }
+    }
+
+    let Body::DeleteRecordsResponse { topics, .. } = DeleteRecordsRequest::with_storage(sc.clone())
+        .request(&[DeleteRecordsTopic {
+            name: topic_name.clone(),
+            partitions: Some(vec![
+                DeleteRecordsPartition {
+                    partition_index: 0,
+                    offset: 3,
+                },
+                DeleteRecordsPartition {
+                    partition_index: 1,
+                    offset: records + 1,
+                },
+                DeleteRecordsPartition {
+                    partition_index: num_partitions,
+                    offset: 3,
+                },
+            ]),
+        }])
+        .await?
+    else {
+        panic!("delete records response")
+    };
+
+    assert_eq!(
+        Some(vec![DeleteRecordsTopicResult {
+            name: topic_name.clone(),
+            partitions: Some(vec![
+                DeleteRecordsPartitionResult {
+                    partition_index: 0,
+                    low_watermark: 3,
+                    error_code: ErrorCode::None.into(),
+                },
+                DeleteRecordsPartitionResult {
+                    partition_index: 1,
+                    low_watermark: 0,
+                    error_code: ErrorCode::OffsetOutOfRange.into(),
+                },
+                DeleteRecordsPartitionResult {
+                    partition_index: num_partitions,
+                    low_watermark: -1,
+                    error_code: ErrorCode::UnknownTopicOrPartition.into(),
+                },
+            ]),
+        }]),
+        topics
+    );
+
+    // only the valid partition has its records deleted
+    assert_eq!(
+        (3, records),
+        sc.watermarks(&Topition::new(topic_name.clone(), 0)).await?
+    );
+    assert_eq!(
+        (0, records),
+        sc.watermarks(&Topition::new(topic_name, 1)).await?
+    );
+
+    Ok(())
+}
+
+pub async fn read_committed(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let topition = Topition::new(topic_name.clone(), 0);
+
+    let batch = inflated::Batch::builder()
+        .record(Record::builder().value(Bytes::from_static(b"abc").into()))
+        .build()
+        .and_then(TryInto::try_into)?;
+
+    assert_eq!(0, sc.produce(None, &topition, batch).await?);
+
+    let transaction_id: String = alphanumeric_string(10);
+
+    let producer = sc
+        .init_producer(Some(transaction_id.as_str()), 10_000, Some(-1), Some(-1))
+        .await?;
+
+    _ = sc
+        .txn_add_partitions(TxnAddPartitionsRequest::VersionZeroToThree {
+            transaction_id: transaction_id.clone(),
+            producer_id: producer.id,
+            producer_epoch: producer.epoch,
+            topics: [AddPartitionsToTxnTopic {
+                name: topic_name.clone(),
+                partitions: Some([0].into()),
+            }]
+            .into(),
+        })
+        .await?;
+
+    let batch = inflated::Batch::builder()
+        .record(Record::builder().value(Bytes::from_static(b"pqr").into()))
+        .attributes(BatchAttribute::default().transaction(true).into())
+        .producer_id(producer.id)
+        .producer_epoch(producer.epoch)
+        .base_sequence(0)
+        .build()
+        .and_then(TryInto::try_into)?;
+
+    assert_eq!(
+        1,
+        sc.produce(Some(transaction_id.as_str()), &topition, batch)
+            .await?
+    );
+
+    let storage = sc.clone();
+
+    let latest = |isolation_level| {
+        let mut list_offsets = ListOffsetsRequest::with_storage(storage.clone());
+        let topic_name = topic_name.clone();
+
+        async move {
+            let body = list_offsets
+                .response(
+                    -1,
+                    Some(i8::from(&isolation_level)),
+                    Some(&[ListOffsetsTopic {
+                        name: topic_name,
+                        partitions: Some(vec![ListOffsetsPartition {
+                            partition_index: 0,
+                            current_leader_epoch: None,
+                            timestamp: ListOffsetRequest::Latest.try_into()?,
+                            max_num_offsets: None,
+                        }]),
+                    }]),
+                )
+                .await?;
+
+            let Body::ListOffsetsResponse {
+                topics: Some(topics),
+                ..
+            } = body
+            else {
+                panic!("{body:?}")
+            };
+
+            Ok::<_, Error>(
+                topics
+                    .into_iter()
+                    .flat_map(|topic| topic.partitions.unwrap_or_default())
+                    .map(|partition| partition.offset)
+                    .collect::<Vec<_>>(),
+            )
+        }
+    };
+
+    // the last stable offset is the start of the open transaction
+    //
+    assert_eq!(vec![Some(1)], latest(IsolationLevel::ReadCommitted).await?);
+    assert_eq!(
+        vec![Some(2)],
+        latest(IsolationLevel::ReadUncommitted).await?
+    );
+
+    assert_eq!(
+        ErrorCode::None,
+        sc.txn_end(transaction_id.as_str(), producer.id, producer.epoch, true)
+            .await?
+    );
+
+    // after the commit marker, both are the high watermark
+    //
+    assert_eq!(vec![Some(3)], latest(IsolationLevel::ReadCommitted).await?);
+    assert_eq!(
+        vec![Some(3)],
+        latest(IsolationLevel::ReadUncommitted).await?
+    );
+
+    Ok(())
+}
+
+pub async fn tiered(cluster_id: Uuid, broker_id: i32, mut sc: StorageContainer) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 6;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let partition_index = rng().random_range(0..num_partitions);
+    let topition = Topition::new(topic_name.clone(), partition_index);
+
+    let records = 5;
+
+    for n in 0..records {
+        let value = Bytes::copy_from_slice(alphanumeric_string(15).as_bytes());
+
+        let batch = inflated::Batch::builder()
+            .record(Record::builder().value(value.into()))
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        assert_eq!(n, sc.produce(None, &topition, batch).await?);
+    }
+
+    let offsets = [
+        (topition.clone(), ListOffsetRequest::EarliestLocal),
+        (topition.clone(), ListOffsetRequest::LatestTiered),
+    ];
+
+    // nothing has been tiered yet
+    //
+    let responses = sc
+        .list_offsets(IsolationLevel::ReadUncommitted, &offsets[..])
+        .await?;
+    assert_eq!(Some(0), responses[0].1.offset);
+    assert_eq!(Some(-1), responses[1].1.offset);
+
+    assert_eq!(
+        ErrorCode::OffsetOutOfRange,
+        sc.tier_records(&topition, records + 1).await?
+    );
+
+    assert_eq!(ErrorCode::None, sc.tier_records(&topition, 3).await?);
+
+    let responses = sc
+        .list_offsets(IsolationLevel::ReadUncommitted, &offsets[..])
+        .await?;
+    assert_eq!(Some(3), responses[0].1.offset);
+    assert_eq!(Some(2), responses[1].1.offset);
+
+    // tiering never moves backwards
+    //
+    assert_eq!(ErrorCode::None, sc.tier_records(&topition, 1).await?);
+
+    let body = ListOffsetsRequest::with_storage(sc.clone())
+        .response(
+            -1,
+            Some(i8::from(&IsolationLevel::ReadUncommitted)),
+            Some(&[ListOffsetsTopic {
+                name: topic_name,
+                partitions: Some(vec![
+                    ListOffsetsPartition {
+                        partition_index,
+                        current_leader_epoch: None,
+                        timestamp: ListOffsetRequest::EarliestLocal.try_into()?,
+                        max_num_offsets: None,
+                    },
+                    ListOffsetsPartition {
+                        partition_index,
+                        current_leader_epoch: None,
+                        timestamp: ListOffsetRequest::Earliest.try_into()?,
+                        max_num_offsets: None,
+                    },
+                ]),
+            }]),
+        )
+        .await?;
+
+    let Body::ListOffsetsResponse {
+        topics: Some(topics),
+        ..
+    } = body
+    else {
+        panic!("{body:?}")
+    };
+
+    assert_eq!(
+        vec![Some(3), Some(0)],
+        topics
+            .into_iter()
+            .flat_map(|topic| topic.partitions.unwrap_or_default())
+            .map(|partition| partition.offset)
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
 mod pg {
     use super::*;
 
@@ -165,6 +610,36 @@ mod pg {
         .await
     }
 
+    #[tokio::test]
+    async fn watermarks() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::watermarks(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn delete_records_per_partition() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::delete_records_per_partition(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn single_record() -> Result<()> {
         let _guard = init_tracing()?;
@@ -179,6 +654,36 @@ mod pg {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn read_committed() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::read_committed(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn tiered() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::tiered(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
 
 mod in_memory {
@@ -213,6 +718,158 @@ mod in_memory {
         .await
     }
 
+    #[tokio::test]
+    async fn watermarks() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::watermarks(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn delete_records_per_partition() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::delete_records_per_partition(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn delete_records_removes_batches() -> Result<()> {
+        use futures::TryStreamExt;
+        use object_store::{ObjectStore, memory::InMemory, path::Path};
+        use std::sync::Arc;
+        use tansu_storage::dynostore::DynoStore;
+
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+
+        let mut sc = StorageContainer::DynoStore(DynoStore::new(
+            cluster_id.to_string().as_str(),
+            broker_id,
+            object_store.clone(),
+        ));
+
+        register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+        let topic_name: String = alphanumeric_string(15);
+        debug!(?topic_name);
+
+        _ = sc
+            .create_topic(
+                CreatableTopic {
+                    name: topic_name.clone(),
+                    num_partitions: 1,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        let topition = Topition::new(topic_name.clone(), 0);
+
+        let records_per_batch = 3;
+        let batches = 3;
+
+        for n in 0..batches {
+            let batch = (0..records_per_batch)
+                .try_fold(inflated::Batch::builder(), |builder, delta| {
+                    Ok::<_, Error>(
+                        builder.record(
+                            Record::builder()
+                                .offset_delta(delta)
+                                .value(Bytes::from(alphanumeric_string(15)).into()),
+                        ),
+                    )
+                })?
+                .last_offset_delta(records_per_batch - 1)
+                .build()
+                .and_then(TryInto::try_into)?;
+
+            assert_eq!(
+                i64::from(n * records_per_batch),
+                sc.produce(None, &topition, batch).await?
+            );
+        }
+
+        let prefix = Path::from(format!(
+            "clusters/{cluster_id}/topics/{topic_name}/partitions/{:0>10}/records/",
+            0
+        ));
+
+        let batch_objects = async || {
+            object_store
+                .list(Some(&prefix))
+                .map_ok(|meta| meta.location.filename().map(ToOwned::to_owned))
+                .try_collect::<Vec<_>>()
+                .await
+                .map(|mut filenames| {
+                    filenames.sort();
+                    filenames
+                })
+        };
+
+        assert_eq!(3, batch_objects().await?.len());
+
+        // deleting into the middle of the second batch keeps it, only
+        // the first batch lies entirely below the log start
+        //
+        let deleted = sc
+            .delete_records(&[DeleteRecordsTopic {
+                name: topic_name.clone(),
+                partitions: Some(vec![DeleteRecordsPartition {
+                    partition_index: 0,
+                    offset: 4,
+                }]),
+            }])
+            .await?;
+
+        let partitions = deleted[0].partitions.as_deref().unwrap_or_default();
+        assert_eq!(i16::from(ErrorCode::None), partitions[0].error_code);
+
+        assert_eq!(
+            vec![
+                Some(format!("{:0>20}.batch", 3)),
+                Some(format!("{:0>20}.batch", 6))
+            ],
+            batch_objects().await?
+        );
+
+        _ = sc
+            .delete_records(&[DeleteRecordsTopic {
+                name: topic_name,
+                partitions: Some(vec![DeleteRecordsPartition {
+                    partition_index: 0,
+                    offset: -1,
+                }]),
+            }])
+            .await?;
+
+        assert!(batch_objects().await?.is_empty());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn single_record() -> Result<()> {
         let _guard = init_tracing()?;
@@ -227,4 +884,34 @@ mod in_memory {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn read_committed() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::read_committed(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn tiered() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::tiered(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
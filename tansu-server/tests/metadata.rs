@@ -531,7 +531,7 @@ pub async fn topics_some_not_matching_by_id(
 
     assert_eq!(1, metadata.topics().len());
     assert_eq!(
-        i16::from(ErrorCode::UnknownTopicOrPartition),
+        i16::from(ErrorCode::UnknownTopicId),
         metadata.topics()[0].error_code
     );
     assert_eq!(None, metadata.topics()[0].name);
@@ -13,22 +13,32 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, io::Cursor, sync::Arc};
 
+use base64::prelude::*;
 use bytes::Bytes;
 use common::{StorageType, alphanumeric_string, init_tracing, register_broker};
+use futures::TryStreamExt;
+use object_store::{ObjectStore, memory::InMemory, path::Path};
 use rand::{prelude::*, rng};
+use serde::Serialize;
 use tansu_kafka_sans_io::{
-    BatchAttribute, ControlBatch, EndTransactionMarker, ErrorCode, IsolationLevel,
+    BatchAttribute, Compression, ControlBatch, Encoder, EndTransactionMarker, ErrorCode,
+    IsolationLevel,
     add_partitions_to_txn_request::AddPartitionsToTxnTopic,
     add_partitions_to_txn_response::{
         AddPartitionsToTxnPartitionResult, AddPartitionsToTxnTopicResult,
     },
-    create_topics_request::CreatableTopic,
-    record::{Record, inflated},
+    create_topics_request::{CreatableTopic, CreatableTopicConfig},
+    produce_request::{PartitionProduceData, TopicProduceData},
+    record::{Header, Record, deflated, inflated},
 };
-use tansu_server::Result;
-use tansu_storage::{Storage, StorageContainer, Topition, TxnAddPartitionsRequest};
+use tansu_server::{Result, broker::produce::ProduceRequest};
+use tansu_storage::{
+    LEADER_EPOCH, Storage, StorageContainer, Topition, TxnAddPartitionsRequest, ZSTD_DICTIONARY,
+    dynostore::DynoStore,
+};
+use tokio::task::JoinSet;
 use tracing::{debug, error};
 use url::Url;
 use uuid::Uuid;
@@ -156,6 +166,422 @@ pub async fn simple_non_txn(
     Ok(())
 }
 
+pub async fn partition_leader_epoch(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 1;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let topition = Topition::new(topic_name, 0);
+
+    let producer = sc.init_producer(None, 10_000, Some(-1), Some(-1)).await?;
+
+    let batch = inflated::Batch::builder()
+        .partition_leader_epoch(32_123)
+        .record(Record::builder().value(Bytes::from_static(b"abc").into()))
+        .producer_id(producer.id)
+        .producer_epoch(producer.epoch)
+        .build()
+        .and_then(TryInto::try_into)?;
+
+    let offset = sc.produce(None, &topition, batch).await?;
+
+    let batches = sc
+        .fetch(
+            &topition,
+            offset,
+            1,
+            50 * 1024,
+            IsolationLevel::ReadUncommitted,
+        )
+        .await?;
+
+    assert!(!batches.is_empty());
+
+    for batch in batches {
+        assert_eq!(LEADER_EPOCH, batch.partition_leader_epoch);
+        assert!(batch.verify_crc());
+    }
+
+    Ok(())
+}
+
+pub async fn concurrent_produce(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let topition = Topition::new(topic_name, 0);
+
+    let producers = 16;
+    let batches = 8;
+
+    let mut set = JoinSet::new();
+
+    for _ in 0..producers {
+        let mut sc = sc.clone();
+        let topition = topition.clone();
+
+        _ = set.spawn(async move {
+            let producer = sc.init_producer(None, 10_000, Some(-1), Some(-1)).await?;
+
+            let mut produced = vec![];
+            let mut base_sequence = 0;
+
+            for batch in 0..batches {
+                // batches with 1, 2 or 3 records
+                let records = 1 + batch % 3;
+
+                let batch = (0..records)
+                    .fold(inflated::Batch::builder(), |builder, _| {
+                        builder.record(Record::builder().value(
+                            Bytes::copy_from_slice(alphanumeric_string(15).as_bytes()).into(),
+                        ))
+                    })
+                    .last_offset_delta(records - 1)
+                    .producer_id(producer.id)
+                    .producer_epoch(producer.epoch)
+                    .base_sequence(base_sequence)
+                    .build()
+                    .and_then(TryInto::try_into)?;
+
+                let offset = sc.produce(None, &topition, batch).await?;
+                produced.push((offset, i64::from(records)));
+                base_sequence += records;
+            }
+
+            Ok::<_, tansu_server::Error>(produced)
+        });
+    }
+
+    let mut produced = BTreeMap::new();
+
+    while let Some(outcome) = set.join_next().await {
+        for (offset, records) in outcome.expect("join")? {
+            assert_eq!(
+                None,
+                produced.insert(offset, records),
+                "duplicate offset: {offset}"
+            );
+        }
+    }
+
+    assert_eq!(producers * batches, produced.len() as i32);
+
+    // offsets are contiguous without gaps: each batch starting where the
+    // previous one ended
+    let high = produced.iter().try_fold(0, |expected, (offset, records)| {
+        if *offset == expected {
+            Ok(offset + records)
+        } else {
+            Err(format!("expected: {expected}, offset: {offset}"))
+        }
+    });
+
+    assert_eq!(Ok(sc.watermarks(&topition).await?.1), high);
+
+    Ok(())
+}
+
+pub async fn compression_type(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some(
+                    [CreatableTopicConfig {
+                        name: "compression.type".into(),
+                        value: Some("zstd".into()),
+                    }]
+                    .into(),
+                ),
+            },
+            false,
+        )
+        .await?;
+
+    let batch: deflated::Batch = inflated::Batch::builder()
+        .record(Record::builder().value(Bytes::from_static(b"abc").into()))
+        .record(
+            Record::builder()
+                .offset_delta(1)
+                .value(Bytes::from_static(b"pqr").into()),
+        )
+        .last_offset_delta(1)
+        .build()
+        .and_then(TryInto::try_into)?;
+
+    assert_eq!(Compression::None, Compression::try_from(batch.attributes)?);
+
+    let response = ProduceRequest::with_storage(sc.clone())
+        .response(
+            None,
+            -1,
+            5_000,
+            Some(vec![TopicProduceData {
+                name: topic_name.clone(),
+                partition_data: Some(vec![PartitionProduceData {
+                    index: 0,
+                    records: Some(deflated::Frame {
+                        batches: vec![batch],
+                    }),
+                }]),
+            }]),
+        )
+        .await?;
+
+    let partition = response
+        .responses
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|topic| topic.partition_responses.unwrap_or_default())
+        .next()
+        .expect("partition response");
+
+    assert_eq!(i16::from(ErrorCode::None), partition.error_code);
+
+    let batches = sc
+        .fetch(
+            &Topition::new(topic_name, 0),
+            partition.base_offset,
+            1,
+            50 * 1024,
+            IsolationLevel::ReadUncommitted,
+        )
+        .await?;
+
+    assert_eq!(1, batches.len());
+    assert_eq!(
+        Compression::Zstd,
+        Compression::try_from(batches[0].attributes)?
+    );
+    assert!(batches[0].verify_crc());
+
+    let records = inflated::Batch::try_from(&batches[0])?.records;
+    assert_eq!(2, records.len());
+    assert_eq!(Some(Bytes::from_static(b"abc")), records[0].value);
+    assert_eq!(Some(Bytes::from_static(b"pqr")), records[1].value);
+
+    Ok(())
+}
+
+pub async fn produce_many(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 2;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let transaction_id: String = alphanumeric_string(10);
+    debug!(?transaction_id);
+
+    // the 3rd partition does not exist, failing the whole produce
+    //
+    let mut partition_data = vec![];
+
+    for index in 0..=num_partitions {
+        partition_data.push(PartitionProduceData {
+            index,
+            records: Some(deflated::Frame {
+                batches: vec![
+                    inflated::Batch::builder()
+                        .record(Record::builder().value(Bytes::from_static(b"abc").into()))
+                        .build()
+                        .and_then(TryInto::try_into)?,
+                ],
+            }),
+        });
+    }
+
+    let response = ProduceRequest::with_storage(sc.clone())
+        .response(
+            Some(transaction_id),
+            -1,
+            5_000,
+            Some(vec![TopicProduceData {
+                name: topic_name.clone(),
+                partition_data: Some(partition_data),
+            }]),
+        )
+        .await?;
+
+    let partitions = response
+        .responses
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|topic| topic.partition_responses.unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    assert_eq!(3, partitions.len());
+
+    for partition in partitions {
+        assert_eq!(
+            i16::from(ErrorCode::UnknownTopicOrPartition),
+            partition.error_code
+        );
+        assert_eq!(-1, partition.base_offset);
+    }
+
+    // none of the batches are visible
+    //
+    for partition in 0..num_partitions {
+        let topition = Topition::new(topic_name.clone(), partition);
+
+        assert_eq!(
+            0,
+            sc.fetch(&topition, 0, 1, 50 * 1024, IsolationLevel::ReadUncommitted)
+                .await?
+                .iter()
+                .map(|batch| batch.record_count)
+                .sum::<u32>()
+        );
+
+        assert_eq!(0, sc.offset_stage(&topition).await?.high_watermark());
+    }
+
+    Ok(())
+}
+
+pub async fn produce_many_retracted(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    let num_partitions = 3;
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let producer = sc.init_producer(None, 0, Some(-1), Some(-1)).await?;
+    debug!(?producer);
+
+    let batch = |base_sequence: i32| {
+        inflated::Batch::builder()
+            .producer_id(producer.id)
+            .producer_epoch(producer.epoch)
+            .base_sequence(base_sequence)
+            .record(Record::builder().value(Bytes::from_static(b"abc").into()))
+            .build()
+            .and_then(deflated::Batch::try_from)
+    };
+
+    // the last partition is out of sequence, after the earlier partitions
+    // have been appended
+    //
+    let entries = (0..num_partitions)
+        .map(|partition| {
+            batch(if partition == num_partitions - 1 {
+                1
+            } else {
+                0
+            })
+            .map(|batch| (Topition::new(topic_name.clone(), partition), batch))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert!(sc.produce_many(None, entries).await.is_err());
+
+    for partition in 0..num_partitions {
+        let topition = Topition::new(topic_name.clone(), partition);
+        assert_eq!(0, sc.offset_stage(&topition).await?.high_watermark());
+    }
+
+    // the sequences of the earlier partitions are unchanged, so that the
+    // producer may retry them
+    //
+    let entries = (0..num_partitions)
+        .map(|partition| {
+            batch(0).map(|batch| (Topition::new(topic_name.clone(), partition), batch))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(vec![0, 0, 0], sc.produce_many(None, entries).await?);
+
+    Ok(())
+}
+
 pub async fn with_txn(cluster_id: Uuid, broker_id: i32, mut sc: StorageContainer) -> Result<()> {
     register_broker(&cluster_id, broker_id, &mut sc).await?;
 
@@ -610,6 +1036,328 @@ pub async fn with_multiple_txn(
     Ok(())
 }
 
+pub async fn multiple_batches(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let topition = Topition::new(topic_name, 0);
+
+    let encode = |batch: &deflated::Batch| -> Result<Vec<u8>> {
+        let mut encoded = Cursor::new(vec![]);
+        let mut encoder = Encoder::new(&mut encoded);
+        batch.serialize(&mut encoder)?;
+        Ok(encoded.into_inner())
+    };
+
+    let mut expected = vec![];
+
+    for n in 0..3 {
+        let batch: deflated::Batch = inflated::Batch::builder()
+            .attributes(
+                BatchAttribute::default()
+                    .compression(Compression::Gzip)
+                    .into(),
+            )
+            .record(Record::builder().value(Bytes::from(format!("{n}-abc")).into()))
+            .record(
+                Record::builder()
+                    .offset_delta(1)
+                    .value(Bytes::from(format!("{n}-pqr")).into()),
+            )
+            .last_offset_delta(1)
+            .build()
+            .and_then(TryInto::try_into)?;
+
+        let base_offset = sc.produce(None, &topition, batch.clone()).await?;
+
+        expected.append(&mut encode(&deflated::Batch {
+            base_offset,
+            partition_leader_epoch: LEADER_EPOCH,
+            ..batch
+        })?);
+    }
+
+    let batches = sc
+        .fetch(&topition, 0, 1, 50 * 1024, IsolationLevel::ReadUncommitted)
+        .await?;
+
+    assert_eq!(3, batches.len());
+
+    let mut fetched = vec![];
+
+    for batch in &batches {
+        assert_eq!(Compression::Gzip, Compression::try_from(batch.attributes)?);
+        assert!(batch.verify_crc());
+
+        fetched.append(&mut encode(batch)?);
+    }
+
+    assert_eq!(expected, fetched);
+
+    let batches = sc
+        .fetch(&topition, 2, 1, 50 * 1024, IsolationLevel::ReadUncommitted)
+        .await?;
+
+    assert_eq!(
+        vec![2, 4],
+        batches
+            .iter()
+            .map(|batch| batch.base_offset)
+            .collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+pub async fn zstd_dictionary(
+    cluster_id: Uuid,
+    broker_id: i32,
+    object_store: Arc<dyn ObjectStore>,
+) -> Result<()> {
+    let mut sc = StorageContainer::DynoStore(DynoStore::new(
+        cluster_id.to_string().as_str(),
+        broker_id,
+        object_store.clone(),
+    ));
+
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let value = |n: usize| {
+        Bytes::from(format!(
+            r#"{{"sensor":"temperature","unit":"celsius","site":"north","reading":{n}}}"#
+        ))
+    };
+
+    let dictionary = zstd::dict::from_samples(&(0..1_000).map(value).collect::<Vec<_>>(), 4_096)?;
+
+    let mut stored = vec![];
+
+    for dictionary in [None, Some(BASE64_STANDARD.encode(&dictionary))] {
+        let topic_name: String = alphanumeric_string(15);
+        debug!(?topic_name);
+
+        let mut configs = vec![CreatableTopicConfig {
+            name: "compression.type".into(),
+            value: Some("zstd".into()),
+        }];
+
+        if let Some(dictionary) = dictionary {
+            configs.push(CreatableTopicConfig {
+                name: ZSTD_DICTIONARY.into(),
+                value: Some(dictionary),
+            });
+        }
+
+        _ = sc
+            .create_topic(
+                CreatableTopic {
+                    name: topic_name.clone(),
+                    num_partitions: 1,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some(configs),
+                },
+                false,
+            )
+            .await?;
+
+        let topition = Topition::new(topic_name.clone(), 0);
+
+        for n in 1_000..1_100 {
+            let batch = inflated::Batch::builder()
+                .attributes(
+                    BatchAttribute::default()
+                        .compression(Compression::Zstd)
+                        .into(),
+                )
+                .record(Record::builder().value(value(n).into()))
+                .build()
+                .and_then(TryInto::try_into)?;
+
+            _ = sc.produce(None, &topition, batch).await?;
+        }
+
+        let batches = sc
+            .fetch(
+                &topition,
+                0,
+                1,
+                1024 * 1024,
+                IsolationLevel::ReadUncommitted,
+            )
+            .await?;
+
+        assert_eq!(100, batches.len());
+
+        for (n, batch) in (1_000..).zip(batches) {
+            assert_eq!(Compression::Zstd, Compression::try_from(batch.attributes)?);
+            assert_eq!(None, batch.dictionary_id());
+            assert!(batch.verify_crc());
+
+            let records = inflated::Batch::try_from(batch)?.records;
+            assert_eq!(1, records.len());
+            assert_eq!(Some(value(n)), records[0].value);
+        }
+
+        let prefix = Path::from(format!(
+            "clusters/{cluster_id}/topics/{topic_name}/partitions/"
+        ));
+
+        stored.push(
+            object_store
+                .list(Some(&prefix))
+                .try_fold(0, |size, meta| async move { Ok(size + meta.size) })
+                .await?,
+        );
+    }
+
+    debug!(?stored);
+    assert!(stored[1] < stored[0]);
+
+    Ok(())
+}
+
+pub async fn record_headers(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    // out of key order, with a repeated key, an empty and a null value
+    let headers = [
+        Header::builder().key(b"trace".into()).value(b"abc".into()),
+        Header::builder()
+            .key(b"content-type".into())
+            .value(b"json".into()),
+        Header::builder().key(b"trace".into()).value(b"pqr".into()),
+        Header::builder().key(b"empty".into()).value(vec![]),
+        Header::builder().key(b"null".into()),
+    ];
+
+    let batch: deflated::Batch = inflated::Batch::builder()
+        .record(
+            headers
+                .iter()
+                .cloned()
+                .fold(Record::builder(), |record, header| record.header(header))
+                .value(Bytes::from_static(b"lorem").into()),
+        )
+        .record(
+            Record::builder()
+                .offset_delta(1)
+                .header(Header::builder().key(b"only".into()).value(b"one".into()))
+                .value(Bytes::from_static(b"ipsum").into()),
+        )
+        .last_offset_delta(1)
+        .build()
+        .and_then(TryInto::try_into)?;
+
+    let response = ProduceRequest::with_storage(sc.clone())
+        .response(
+            None,
+            1,
+            5_000,
+            Some(vec![TopicProduceData {
+                name: topic_name.clone(),
+                partition_data: Some(vec![PartitionProduceData {
+                    index: 0,
+                    records: Some(deflated::Frame {
+                        batches: vec![batch],
+                    }),
+                }]),
+            }]),
+        )
+        .await?;
+
+    let partition = response
+        .responses
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|topic| topic.partition_responses.unwrap_or_default())
+        .next()
+        .expect("partition response");
+
+    assert_eq!(i16::from(ErrorCode::None), partition.error_code);
+
+    let batches = sc
+        .fetch(
+            &Topition::new(topic_name, 0),
+            partition.base_offset,
+            1,
+            50 * 1024,
+            IsolationLevel::ReadUncommitted,
+        )
+        .await?;
+
+    let records = batches
+        .iter()
+        .map(inflated::Batch::try_from)
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flat_map(|batch| batch.records)
+        .collect::<Vec<_>>();
+
+    assert_eq!(2, records.len());
+
+    assert_eq!(Some(Bytes::from_static(b"lorem")), records[0].value);
+    assert_eq!(
+        headers
+            .into_iter()
+            .map(|header| header.build())
+            .collect::<Vec<_>>(),
+        records[0].headers
+    );
+
+    assert_eq!(Some(Bytes::from_static(b"ipsum")), records[1].value);
+    assert_eq!(
+        vec![
+            Header::builder()
+                .key(b"only".into())
+                .value(b"one".into())
+                .build()
+        ],
+        records[1].headers
+    );
+
+    Ok(())
+}
+
 mod pg {
     use super::*;
 
@@ -642,6 +1390,81 @@ mod pg {
         .await
     }
 
+    #[tokio::test]
+    async fn partition_leader_epoch() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::partition_leader_epoch(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn concurrent_produce() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::concurrent_produce(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn compression_type() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::compression_type(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn produce_many() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::produce_many(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn produce_many_retracted() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::produce_many_retracted(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn with_txn() -> Result<()> {
         let _guard = init_tracing()?;
@@ -671,6 +1494,21 @@ mod pg {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn record_headers() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::record_headers(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
 
 mod in_memory {
@@ -705,6 +1543,81 @@ mod in_memory {
         .await
     }
 
+    #[tokio::test]
+    async fn partition_leader_epoch() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::partition_leader_epoch(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn concurrent_produce() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::concurrent_produce(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn compression_type() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::compression_type(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn produce_many() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::produce_many(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn produce_many_retracted() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::produce_many_retracted(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn with_txn() -> Result<()> {
         let _guard = init_tracing()?;
@@ -734,4 +1647,49 @@ mod in_memory {
         )
         .await
     }
+
+    // stored batches are stitched together as produced, pg rebuilds
+    // batches from individual records and so is not byte identical
+    //
+    #[tokio::test]
+    async fn multiple_batches() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::multiple_batches(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
+
+    // dictionaries only apply to batches stored as objects
+    //
+    #[tokio::test]
+    async fn zstd_dictionary() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::zstd_dictionary(cluster_id, broker_id, Arc::new(InMemory::new())).await
+    }
+
+    #[tokio::test]
+    async fn record_headers() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::record_headers(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
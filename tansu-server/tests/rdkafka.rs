@@ -0,0 +1,381 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{net::TcpListener, time::Duration};
+
+use bytes::Bytes;
+use common::{StorageType, alphanumeric_string, init_tracing};
+use rand::{prelude::*, rng};
+use rdkafka::{
+    ClientConfig, Message, Offset, TopicPartitionList,
+    consumer::{BaseConsumer, CommitMode, Consumer},
+    error::KafkaError,
+    message::{Header as KafkaHeader, Headers, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+};
+use tansu_kafka_sans_io::{
+    BatchAttribute, Compression, ErrorCode,
+    create_topics_request::CreatableTopic,
+    produce_request::{PartitionProduceData, TopicProduceData},
+    record::{Header, Record, deflated, inflated},
+    to_timestamp,
+};
+use tansu_server::{
+    Error, Result,
+    broker::{Broker, produce::ProduceRequest},
+    config::Config,
+    coordinator::group::administrator::Controller,
+};
+use tansu_storage::{Storage, StorageContainer};
+use tracing::debug;
+use url::Url;
+use uuid::Uuid;
+
+pub mod common;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Produced {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    headers: Vec<(String, Option<Vec<u8>>)>,
+    timestamp: i64,
+}
+
+fn kafka(error: KafkaError) -> Error {
+    Error::Message(error.to_string())
+}
+
+// a listener on an unused port, that is also advertised in the metadata
+pub fn listener() -> Result<Url> {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map_err(Into::into)
+        .and_then(|addr| Url::parse(&format!("tcp://{addr}/")).map_err(Into::into))
+}
+
+fn bootstrap(listener: &Url) -> String {
+    format!(
+        "{}:{}",
+        listener.host_str().unwrap_or("127.0.0.1"),
+        listener.port().unwrap_or(9092)
+    )
+}
+
+// batches produced through the broker, consumed by librdkafka checking the
+// CRC of each batch, including those produced by librdkafka itself
+pub async fn round_trip(
+    cluster_id: Uuid,
+    broker_id: i32,
+    listener: Url,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    let config = Config::builder()
+        .cluster_id(cluster_id)
+        .node_id(broker_id)
+        .listener(listener.clone())
+        .advertised_listener(listener.clone())
+        .storage(Url::parse("memory://tansu/")?)
+        .build()?;
+
+    let mut broker = Broker::new(
+        &config,
+        sc.clone(),
+        Controller::with_storage(sc.clone())?,
+        Uuid::now_v7(),
+    );
+
+    let server = tokio::spawn(async move { broker.serve().await });
+
+    let topic_name: String = alphanumeric_string(15);
+    debug!(?topic_name);
+
+    _ = sc
+        .create_topic(
+            CreatableTopic {
+                name: topic_name.clone(),
+                num_partitions: 1,
+                replication_factor: 0,
+                assignments: Some([].into()),
+                configs: Some([].into()),
+            },
+            false,
+        )
+        .await?;
+
+    let now = to_timestamp(std::time::SystemTime::now())?;
+
+    let mut produced = vec![];
+
+    for (index, compression) in [
+        Compression::None,
+        Compression::Gzip,
+        Compression::Snappy,
+        Compression::Lz4,
+        Compression::Zstd,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let records = (0..3)
+            .map(|offset_delta| Produced {
+                key: format!("key-{index}-{offset_delta}").into_bytes(),
+                value: format!("{compression:?}-{offset_delta}")
+                    .repeat(50)
+                    .into_bytes(),
+                headers: vec![
+                    ("trace".into(), Some(format!("{index}").into_bytes())),
+                    ("null".into(), None),
+                ],
+                timestamp: now + offset_delta,
+            })
+            .collect::<Vec<_>>();
+
+        let batch = records
+            .iter()
+            .zip(0..)
+            .fold(
+                inflated::Batch::builder()
+                    .attributes(BatchAttribute::default().compression(compression).into())
+                    .base_timestamp(now)
+                    .max_timestamp(now + 2)
+                    .last_offset_delta(2),
+                |builder, (record, offset_delta)| {
+                    builder.record(
+                        record
+                            .headers
+                            .iter()
+                            .fold(Record::builder(), |builder, (key, value)| {
+                                builder.header(value.iter().fold(
+                                    Header::builder().key(key.as_bytes().to_vec()),
+                                    |header, value| header.value(value.clone()),
+                                ))
+                            })
+                            .offset_delta(offset_delta)
+                            .timestamp_delta(i64::from(offset_delta))
+                            .key(Bytes::from(record.key.clone()).into())
+                            .value(Bytes::from(record.value.clone()).into()),
+                    )
+                },
+            )
+            .build()
+            .and_then(deflated::Batch::try_from)?;
+
+        let response = ProduceRequest::with_storage(sc.clone())
+            .response(
+                None,
+                1,
+                5_000,
+                Some(vec![TopicProduceData {
+                    name: topic_name.clone(),
+                    partition_data: Some(vec![PartitionProduceData {
+                        index: 0,
+                        records: Some(deflated::Frame {
+                            batches: vec![batch],
+                        }),
+                    }]),
+                }]),
+            )
+            .await?;
+
+        assert_eq!(
+            Some(vec![i16::from(ErrorCode::None)]),
+            response.responses.map(|responses| responses
+                .iter()
+                .flat_map(|topic| topic.partition_responses.iter().flatten())
+                .map(|partition| partition.error_code)
+                .collect::<Vec<_>>())
+        );
+
+        produced.extend(records);
+    }
+
+    // produced by librdkafka, with a codec chosen by the client
+    let producer: FutureProducer = ClientConfig::new()
+        .set("bootstrap.servers", bootstrap(&listener))
+        .set("compression.codec", "lz4")
+        .set("enable.idempotence", "false")
+        .set("message.timeout.ms", "30000")
+        .create()
+        .map_err(kafka)?;
+
+    for offset_delta in 0..3 {
+        let record = Produced {
+            key: format!("rdkafka-{offset_delta}").into_bytes(),
+            value: format!("librdkafka-{offset_delta}").repeat(50).into_bytes(),
+            headers: vec![("client".into(), Some(b"rdkafka".to_vec()))],
+            timestamp: now + offset_delta,
+        };
+
+        _ = producer
+            .send(
+                FutureRecord::to(&topic_name)
+                    .partition(0)
+                    .key(&record.key)
+                    .payload(&record.value)
+                    .timestamp(record.timestamp)
+                    .headers(OwnedHeaders::new().insert(KafkaHeader {
+                        key: "client",
+                        value: Some(b"rdkafka"),
+                    })),
+                TIMEOUT,
+            )
+            .await
+            .map_err(|(error, _)| kafka(error))?;
+
+        produced.push(record);
+    }
+
+    let bootstrap = bootstrap(&listener);
+    let group_id = alphanumeric_string(15);
+    let expected = produced.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let consumer: BaseConsumer = ClientConfig::new()
+            .set("bootstrap.servers", bootstrap)
+            .set("group.id", group_id)
+            .set("enable.auto.commit", "false")
+            .set("check.crcs", "true")
+            .create()
+            .map_err(kafka)?;
+
+        let mut assignment = TopicPartitionList::new();
+        assignment
+            .add_partition_offset(&topic_name, 0, Offset::Beginning)
+            .map_err(kafka)?;
+        consumer.assign(&assignment).map_err(kafka)?;
+
+        let mut consumed = vec![];
+
+        while consumed.len() < expected.len() {
+            let message = consumer
+                .poll(TIMEOUT)
+                .ok_or(Error::Message(String::from("no message")))?
+                .map_err(kafka)?;
+
+            assert_eq!(i64::try_from(consumed.len())?, message.offset());
+
+            consumed.push(Produced {
+                key: message.key().map(ToOwned::to_owned).unwrap_or_default(),
+                value: message.payload().map(ToOwned::to_owned).unwrap_or_default(),
+                headers: message
+                    .headers()
+                    .map(|headers| {
+                        headers
+                            .iter()
+                            .map(|header| {
+                                (header.key.to_owned(), header.value.map(ToOwned::to_owned))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                timestamp: message.timestamp().to_millis().unwrap_or(-1),
+            });
+        }
+
+        assert_eq!(expected, consumed);
+
+        // the consumed position is committed and fetched back by the client
+        let mut position = TopicPartitionList::new();
+        position
+            .add_partition_offset(
+                &topic_name,
+                0,
+                Offset::Offset(i64::try_from(consumed.len())?),
+            )
+            .map_err(kafka)?;
+        consumer
+            .commit(&position, CommitMode::Sync)
+            .map_err(kafka)?;
+
+        let committed = consumer
+            .committed_offsets(assignment, TIMEOUT)
+            .map_err(kafka)?;
+
+        assert_eq!(
+            Some(Offset::Offset(i64::try_from(consumed.len())?)),
+            committed
+                .find_partition(&topic_name, 0)
+                .map(|partition| partition.offset())
+        );
+
+        Ok::<(), Error>(())
+    })
+    .await
+    .expect("consumer")?;
+
+    server.abort();
+
+    Ok(())
+}
+
+mod pg {
+    use super::*;
+
+    fn storage_container(
+        cluster: impl Into<String>,
+        node: i32,
+        listener: Url,
+    ) -> Result<StorageContainer> {
+        common::storage_container(StorageType::Postgres, cluster, node, listener, None)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn round_trip() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+        let listener = super::listener()?;
+
+        super::round_trip(
+            cluster_id,
+            broker_id,
+            listener.clone(),
+            storage_container(cluster_id, broker_id, listener)?,
+        )
+        .await
+    }
+}
+
+mod in_memory {
+    use super::*;
+
+    fn storage_container(
+        cluster: impl Into<String>,
+        node: i32,
+        listener: Url,
+    ) -> Result<StorageContainer> {
+        common::storage_container(StorageType::InMemory, cluster, node, listener, None)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn round_trip() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+        let listener = super::listener()?;
+
+        super::round_trip(
+            cluster_id,
+            broker_id,
+            listener.clone(),
+            storage_container(cluster_id, broker_id, listener)?,
+        )
+        .await
+    }
+}
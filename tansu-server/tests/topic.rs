@@ -286,6 +286,48 @@ pub async fn create_with_config_delete(
     Ok(())
 }
 
+pub async fn partition_count(
+    cluster_id: Uuid,
+    broker_id: i32,
+    mut sc: StorageContainer,
+) -> Result<()> {
+    register_broker(&cluster_id, broker_id, &mut sc).await?;
+
+    assert_eq!((0, 0), sc.partition_count().await?);
+
+    let mut topic_ids = vec![];
+
+    for num_partitions in [3, 5] {
+        let topic_name: String = alphanumeric_string(15);
+        debug!(?topic_name);
+
+        topic_ids.push(
+            sc.create_topic(
+                CreatableTopic {
+                    name: topic_name,
+                    num_partitions,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?,
+        );
+    }
+
+    assert_eq!((2, 8), sc.partition_count().await?);
+
+    assert_eq!(
+        ErrorCode::None,
+        sc.delete_topic(&TopicId::from(topic_ids[0])).await?
+    );
+
+    assert_eq!((1, 5), sc.partition_count().await?);
+
+    Ok(())
+}
+
 mod pg {
     use common::{StorageType, init_tracing};
     use rand::{prelude::*, rng};
@@ -381,6 +423,21 @@ mod pg {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn partition_count() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::partition_count(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
 
 mod in_memory {
@@ -478,4 +535,19 @@ mod in_memory {
         )
         .await
     }
+
+    #[tokio::test]
+    async fn partition_count() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let cluster_id = Uuid::now_v7();
+        let broker_id = rng().random_range(0..i32::MAX);
+
+        super::partition_count(
+            cluster_id,
+            broker_id,
+            storage_container(cluster_id, broker_id)?,
+        )
+        .await
+    }
 }
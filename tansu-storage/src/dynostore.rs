@@ -17,6 +17,8 @@ use std::{
     collections::{BTreeMap, BTreeSet, btree_map::Entry},
     fmt::{Debug, Display},
     io::Cursor,
+    iter,
+    ops::Range,
     str::FromStr,
     sync::{Arc, Mutex},
     time::{Duration, SystemTime},
@@ -25,8 +27,8 @@ use std::{
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures::{
-    StreamExt,
-    stream::{BoxStream, TryStreamExt},
+    StreamExt, future,
+    stream::{self, BoxStream, TryStreamExt},
 };
 use metadata::Cache;
 use object_store::{
@@ -42,15 +44,15 @@ use opticon::OptiCon;
 use rand::{prelude::*, rng};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use tansu_kafka_sans_io::{
-    BatchAttribute, ConfigResource, ConfigSource, ConfigType, ControlBatch, Decoder, Encoder,
-    EndTransactionMarker, ErrorCode, IsolationLevel, OpType,
+    BatchAttribute, Compression, ConfigResource, ConfigSource, ConfigType, ControlBatch, Decoder,
+    Encoder, EndTransactionMarker, ErrorCode, IsolationLevel, OpType,
     add_partitions_to_txn_response::{
         AddPartitionsToTxnPartitionResult, AddPartitionsToTxnTopicResult,
     },
     create_topics_request::{CreatableTopic, CreatableTopicConfig},
     delete_groups_response::DeletableGroupResult,
     delete_records_request::DeleteRecordsTopic,
-    delete_records_response::DeleteRecordsTopicResult,
+    delete_records_response::{DeleteRecordsPartitionResult, DeleteRecordsTopicResult},
     describe_cluster_response::DescribeClusterBroker,
     describe_configs_response::{DescribeConfigsResourceResult, DescribeConfigsResult},
     describe_topic_partitions_response::{
@@ -72,10 +74,14 @@ mod metadata;
 mod opticon;
 
 use crate::{
-    BrokerRegistrationRequest, Error, GroupDetail, ListOffsetRequest, ListOffsetResponse, METER,
-    MetadataResponse, NULL_TOPIC_ID, NamedGroupDetail, OffsetCommitRequest, OffsetStage,
-    ProducerIdResponse, Result, Storage, TopicId, Topition, TxnAddPartitionsRequest,
-    TxnAddPartitionsResponse, TxnOffsetCommitRequest, TxnState, UpdateError, Version,
+    BROKER_LIVENESS, BrokerRegistrationRequest, Error, GroupDetail, GroupType, LEADER_EPOCH,
+    ListOffsetRequest, ListOffsetResponse, METER, MetadataResponse, NULL_TOPIC_ID,
+    NamedGroupDetail, OffsetCommitRequest, OffsetStage, ProducerIdResponse, Result, SequenceWindow,
+    Storage, TopicId, TopicLimit, Topition, TxnAddPartitionsRequest, TxnAddPartitionsResponse,
+    TxnLimit, TxnOffsetCommitRequest, TxnState, UpdateError, Version, ZSTD_DICTIONARY,
+    ZstdDictionaries,
+    clock::{Clock, SystemClock},
+    reinitialized, txn_add_partitions_outcome, txn_verify_partitions,
 };
 
 const APPLICATION_JSON: &str = "application/json";
@@ -86,12 +92,59 @@ pub struct DynoStore {
     node: i32,
     advertised_listener: Url,
     schemas: Option<Registry>,
+    sequence_window: SequenceWindow,
+    txn_limit: TxnLimit,
+    clock: Arc<dyn Clock>,
     watermarks: Arc<Mutex<BTreeMap<Topition, OptiCon<Watermark>>>>,
+    appends: Appends,
+    dictionaries: Arc<Mutex<BTreeMap<Topic, (String, ZstdDictionaries)>>>,
     meta: OptiCon<Meta>,
 
+    // the metadata cache is kept so that it can be given the clock
+    cache: Cache<Arc<DynObjectStore>>,
     object_store: Arc<DynObjectStore>,
 }
 
+type Appends = Arc<Mutex<BTreeMap<Topition, Arc<tokio::sync::Mutex<()>>>>>;
+
+// a reference to the append lock of a partition, the entry of the
+// partition is removed from the map when its last reference is dropped
+#[derive(Debug)]
+struct Append {
+    appends: Appends,
+    topition: Topition,
+
+    // only taken when the reference is dropped
+    lock: Option<Arc<tokio::sync::Mutex<()>>>,
+}
+
+impl Append {
+    async fn lock(&self) -> Option<tokio::sync::MutexGuard<'_, ()>> {
+        match self.lock {
+            Some(ref lock) => Some(lock.lock().await),
+            None => None,
+        }
+    }
+}
+
+impl Drop for Append {
+    fn drop(&mut self) {
+        // the reference is released while the map is locked, so that no
+        // other reference is taken or released between the release and
+        // the check of those that remain
+        if let Ok(mut locked) = self.appends.lock() {
+            _ = self.lock.take();
+
+            if locked
+                .get(&self.topition)
+                .is_some_and(|append| Arc::strong_count(append) == 1)
+            {
+                _ = locked.remove(&self.topition);
+            }
+        }
+    }
+}
+
 type Group = String;
 type Offset = i64;
 type Partition = i32;
@@ -102,6 +155,8 @@ type Topic = String;
 
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 struct Meta {
+    #[serde(default)]
+    brokers: BTreeMap<i32, BrokerDetail>,
     producers: BTreeMap<ProducerId, ProducerDetail>,
     topics: BTreeMap<Topic, TopicMetadata>,
     transactions: BTreeMap<String, Txn>,
@@ -114,6 +169,68 @@ impl OptiCon<Meta> {
 }
 
 impl Meta {
+    // complete a prepared transaction, collecting the offsets to commit
+    fn complete(&mut self, txn_id: &TxnId, offsets_to_commit: &mut TxnOffsetsToCommit) {
+        debug!(?txn_id);
+
+        let Some(txn_detail) = self
+            .transactions
+            .get_mut(txn_id.transaction.as_str())
+            .and_then(|txn| txn.epochs.get_mut(&txn_id.producer_epoch))
+        else {
+            return;
+        };
+
+        debug!(?txn_detail);
+
+        match txn_detail.state {
+            None | Some(TxnState::PrepareCommit) => {
+                _ = txn_detail.state.replace(TxnState::Committed);
+            }
+
+            Some(TxnState::PrepareAbort) => {
+                _ = txn_detail.state.replace(TxnState::Aborted);
+            }
+
+            otherwise => {
+                warn!(
+                    transaction = txn_id.transaction,
+                    producer = txn_id.producer_id,
+                    epoch = txn_id.producer_epoch,
+                    ?otherwise,
+                );
+
+                return;
+            }
+        }
+
+        if txn_id.state == TxnState::PrepareCommit {
+            for (group, topics) in txn_detail.offsets.iter() {
+                for (topic, partitions) in topics.iter() {
+                    for (partition, committed_offset) in partitions {
+                        _ = offsets_to_commit
+                            .entry(group.to_owned())
+                            .or_default()
+                            .entry(topic.to_owned())
+                            .or_default()
+                            .insert(*partition, committed_offset.to_owned());
+                    }
+                }
+            }
+        }
+
+        txn_detail.produces.clear();
+        txn_detail.offsets.clear();
+        _ = txn_detail.started_at.take();
+    }
+
+    fn partitions(&self) -> i64 {
+        self.topics
+            .values()
+            .map(|metadata| i64::from(metadata.topic.num_partitions.max(0)))
+            .sum()
+    }
+
     fn produced(
         &self,
         transaction_id: &str,
@@ -148,6 +265,35 @@ impl Meta {
         Ok(produced)
     }
 
+    fn enrolled(
+        &self,
+        transaction_id: &str,
+        producer_id: ProducerId,
+        producer_epoch: ProducerEpoch,
+    ) -> Result<BTreeSet<Topition>> {
+        let Some(txn) = self.transactions.get(transaction_id) else {
+            return Err(Error::Api(ErrorCode::TransactionalIdNotFound));
+        };
+
+        if txn.producer != producer_id {
+            return Err(Error::Api(ErrorCode::UnknownProducerId));
+        }
+
+        let Some(txn_detail) = txn.epochs.get(&producer_epoch) else {
+            return Err(Error::Api(ErrorCode::ProducerFenced));
+        };
+
+        Ok(txn_detail
+            .produces
+            .iter()
+            .flat_map(|(topic, partitions)| {
+                partitions
+                    .keys()
+                    .map(|partition| Topition::new(topic.to_owned(), *partition))
+            })
+            .collect())
+    }
+
     fn overlapping_transactions(
         &self,
         transaction_id: &str,
@@ -245,9 +391,23 @@ impl Meta {
     }
 }
 
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+struct BrokerDetail {
+    host: String,
+    port: i32,
+    rack: Option<String>,
+    incarnation_id: Uuid,
+    #[serde(default)]
+    last_updated: Option<SystemTime>,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 struct ProducerDetail {
     sequences: BTreeMap<ProducerEpoch, BTreeMap<String, BTreeMap<i32, Sequence>>>,
+
+    // base sequences of the recent batches, for the current epoch only
+    #[serde(default)]
+    recent: BTreeMap<ProducerEpoch, BTreeMap<String, BTreeMap<i32, Vec<Sequence>>>>,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
@@ -307,6 +467,8 @@ struct TxnCommitOffset {
     metadata: Option<String>,
 }
 
+type TxnOffsetsToCommit = BTreeMap<Group, BTreeMap<Topic, BTreeMap<Partition, TxnCommitOffset>>>;
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 struct TopicMetadata {
     id: Uuid,
@@ -317,6 +479,7 @@ struct TopicMetadata {
 struct Watermark {
     low: Option<i64>,
     high: Option<i64>,
+    tiered: Option<i64>,
 }
 
 impl OptiCon<Watermark> {
@@ -354,22 +517,94 @@ fn json_content_type() -> Attributes {
     attributes
 }
 
+// a batch appended by a produce many, with the sequence of an idempotent
+// producer, retracted when a later batch fails
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Appended {
+    offset: i64,
+    last_offset_delta: i32,
+    sequence: Option<(i64, i16, i32)>,
+}
+
 impl DynoStore {
     pub fn new(cluster: &str, node: i32, object_store: impl ObjectStore) -> Self {
+        let cache = Cache::new(
+            Arc::new(Metron::new(object_store, cluster)) as Arc<DynObjectStore>,
+            Duration::from_millis(5_000),
+        );
+
         Self {
             cluster: cluster.into(),
             node,
             advertised_listener: Url::parse("tcp://127.0.0.1/").unwrap(),
             schemas: None,
+            sequence_window: SequenceWindow::default(),
+            txn_limit: TxnLimit::default(),
+            clock: Arc::new(SystemClock),
             watermarks: Arc::new(Mutex::new(BTreeMap::new())),
+            appends: Arc::new(Mutex::new(BTreeMap::new())),
+            dictionaries: Arc::new(Mutex::new(BTreeMap::new())),
             meta: OptiCon::<Meta>::new(cluster),
-            object_store: Arc::new(Cache::new(
-                Metron::new(object_store, cluster),
-                Duration::from_millis(5_000),
-            )),
+            object_store: Arc::new(cache.clone()),
+            cache,
         }
     }
 
+    // batches are keyed by their base offset, with a batch lying entirely
+    // below the log start when the next batch (or the high watermark) does
+    async fn delete_batches_below(
+        &self,
+        topition: &Topition,
+        log_start: i64,
+        high_watermark: i64,
+    ) -> Result<()> {
+        let prefix = Path::from(format!(
+            "clusters/{}/topics/{}/partitions/{:0>10}/records/",
+            self.cluster, topition.topic, topition.partition
+        ));
+
+        let base_offsets = self
+            .object_store
+            .list(Some(&prefix))
+            .try_filter_map(|meta| async move {
+                Ok(meta
+                    .location
+                    .filename()
+                    .and_then(|filename| filename.strip_suffix(".batch"))
+                    .and_then(|base_offset| i64::from_str(base_offset).ok()))
+            })
+            .try_collect::<BTreeSet<_>>()
+            .await?;
+
+        let locations = base_offsets
+            .iter()
+            .zip(
+                base_offsets
+                    .iter()
+                    .skip(1)
+                    .copied()
+                    .chain(iter::once(high_watermark)),
+            )
+            .filter(|(_, end)| *end <= log_start)
+            .map(|(base_offset, _)| {
+                Ok(Path::from(format!(
+                    "clusters/{}/topics/{}/partitions/{:0>10}/records/{:0>20}.batch",
+                    self.cluster, topition.topic, topition.partition, base_offset,
+                )))
+            })
+            .collect::<Vec<_>>();
+
+        debug!(?topition, log_start, deleted = locations.len());
+
+        _ = self
+            .object_store
+            .delete_stream(stream::iter(locations).boxed())
+            .try_collect::<Vec<Path>>()
+            .await?;
+
+        Ok(())
+    }
+
     pub fn advertised_listener(self, advertised_listener: Url) -> Self {
         Self {
             advertised_listener,
@@ -381,6 +616,76 @@ impl DynoStore {
         Self { schemas, ..self }
     }
 
+    pub fn sequence_window(self, sequence_window: SequenceWindow) -> Self {
+        Self {
+            sequence_window,
+            ..self
+        }
+    }
+
+    pub fn txn_limit(self, txn_limit: TxnLimit) -> Self {
+        Self { txn_limit, ..self }
+    }
+
+    async fn commit_txn_offsets(&mut self, offsets_to_commit: TxnOffsetsToCommit) -> Result<()> {
+        debug!(?offsets_to_commit);
+
+        for (group, topics) in offsets_to_commit.iter() {
+            let mut offsets = vec![];
+
+            for (topic, partitions) in topics.iter() {
+                for (partition, txn_co) in partitions {
+                    let tp = Topition::new(topic.to_owned(), *partition);
+                    let ocr = OffsetCommitRequest {
+                        offset: txn_co.committed_offset,
+                        leader_epoch: txn_co.leader_epoch,
+                        timestamp: None,
+                        metadata: txn_co.metadata.clone(),
+                    };
+
+                    offsets.push((tp, ocr));
+                }
+            }
+
+            _ = self.offset_commit(group, None, &offsets[..]).await?;
+        }
+
+        Ok(())
+    }
+
+    // dictionaries are decoded once, and again only when the configuration changes
+    async fn zstd_dictionaries(&self, topic: &str) -> Result<ZstdDictionaries> {
+        self.meta
+            .with(&self.object_store, |meta| {
+                let Some(encoded) = meta
+                    .topics
+                    .get(topic)
+                    .and_then(|metadata| metadata.topic.configs.as_deref())
+                    .and_then(|configs| {
+                        configs.iter().find(|config| config.name == ZSTD_DICTIONARY)
+                    })
+                    .and_then(|config| config.value.as_deref())
+                else {
+                    return Ok(ZstdDictionaries::default());
+                };
+
+                let mut decoded = self.dictionaries.lock()?;
+
+                if let Some((_, dictionaries)) = decoded
+                    .get(topic)
+                    .filter(|(configured, _)| configured == encoded)
+                {
+                    return Ok(dictionaries.clone());
+                }
+
+                ZstdDictionaries::from_str(encoded).inspect(|dictionaries| {
+                    _ = decoded
+                        .insert(topic.to_owned(), (encoded.to_owned(), dictionaries.clone()));
+                })
+            })
+            .await
+    }
+
     async fn topic_metadata(&self, topic: &TopicId) -> Result<Option<TopicMetadata>> {
         debug!(?topic);
 
@@ -400,6 +705,27 @@ impl DynoStore {
             .await
     }
 
+    // the lock held while appending to a partition
+    fn append(&self, topition: &Topition) -> Result<Append> {
+        self.appends
+            .lock()
+            .map(|mut locked| Append {
+                appends: self.appends.clone(),
+                topition: topition.to_owned(),
+                lock: Some(locked.entry(topition.to_owned()).or_default().to_owned()),
+            })
+            .map_err(Into::into)
+    }
+
+    fn consumer_group_marker(&self, group_id: &str) -> Path {
+        Path::from(format!(
+            "clusters/{}/groups/types/{}/{}",
+            self.cluster,
+            GroupType::Consumer,
+            group_id
+        ))
+    }
+
     fn encode(&self, deflated: deflated::Batch) -> Result<PutPayload> {
         let mut encoded = Cursor::new(vec![]);
         let mut encoder = Encoder::new(&mut encoded);
@@ -415,6 +741,55 @@ impl DynoStore {
         deflated::Batch::deserialize(&mut decoder).map_err(Into::into)
     }
 
+    // the partitions with an offset committed by a group
+    async fn offset_topitions(&self, group_id: &str) -> Result<Vec<Topition>> {
+        let mut topitions = vec![];
+
+        let location = Path::from(format!(
+            "clusters/{}/groups/consumers/{}/offsets/",
+            self.cluster, group_id,
+        ));
+
+        let mut list_stream = self.object_store.list(Some(&location));
+
+        while let Some(meta) = list_stream
+            .next()
+            .await
+            .inspect(|meta| debug!(?meta))
+            .transpose()
+            .inspect_err(|error| error!(?error))
+            .map_err(|_| Error::Api(ErrorCode::UnknownServerError))?
+        {
+            debug!(?meta);
+            let Some(topic): Option<String> = meta
+                .location
+                .parts()
+                .nth(6)
+                .inspect(|topic| debug!(?topic))
+                .map(|topic| topic.as_ref().into())
+            else {
+                continue;
+            };
+
+            let Some(partition) = meta
+                .location
+                .parts()
+                .nth(8)
+                .inspect(|partition| debug!(?partition))
+                .map(|partition| i32::from_str(&partition.as_ref()[0..10]))
+                .transpose()?
+            else {
+                continue;
+            };
+
+            debug!(topic, partition);
+
+            topitions.push(Topition::new(topic, partition));
+        }
+
+        Ok(topitions)
+    }
+
     async fn get<P>(&self, location: &Path) -> Result<(P, Version)>
     where
         P: DeserializeOwned,
@@ -477,52 +852,342 @@ impl DynoStore {
         }
     }
 
-    fn txn_offset_commit_response_error(
-        offsets: &TxnOffsetCommitRequest,
-        error_code: ErrorCode,
-    ) -> Result<Vec<TxnOffsetCommitResponseTopic>> {
-        let mut responses = vec![];
+    // checks the sequence of an idempotent batch, assigns its offset and
+    // writes it, while the append lock of the partition is held
+    async fn append_batch(
+        &mut self,
+        transaction_id: Option<&str>,
+        topition: &Topition,
+        deflated: deflated::Batch,
+    ) -> Result<i64> {
+        if deflated.is_idempotent() {
+            let sequence_window = self.sequence_window;
 
-        for topic in &offsets.topics {
-            let mut partition_responses = vec![];
+            self.meta
+                .with_mut(&self.object_store, |meta| {
+                    let Some(pd) = meta.producers.get_mut(&deflated.producer_id) else {
+                        debug!(producer_id = deflated.producer_id, ?meta.producers);
+                        return Err(Error::Api(ErrorCode::UnknownProducerId));
+                    };
 
-            if let Some(partitions) = topic.partitions.as_deref() {
-                for partition in partitions {
-                    partition_responses.push(TxnOffsetCommitResponsePartition {
-                        partition_index: partition.partition_index,
-                        error_code: error_code.into(),
-                    });
-                }
-            }
+                    let Some(mut current) = pd.sequences.last_entry() else {
+                        debug!(last_entry = ?pd.sequences.last_entry());
+                        return Err(Error::Api(ErrorCode::UnknownServerError));
+                    };
 
-            responses.push(TxnOffsetCommitResponseTopic {
-                name: topic.name.to_string(),
-                partitions: Some(partition_responses),
-            });
+                    if current.key() != &deflated.producer_epoch {
+                        debug!(current = ?current.key(), producer_epoch = deflated.producer_epoch);
+                        return Err(Error::Api(ErrorCode::ProducerFenced));
+                    }
+
+                    let epoch = *current.key();
+                    let sequences = current.get_mut();
+                    debug!(?sequences);
+
+                    pd.recent.retain(|recent, _| *recent == epoch);
+
+                    let recent = pd
+                        .recent
+                        .entry(epoch)
+                        .or_default()
+                        .entry(topition.topic.clone())
+                        .or_default()
+                        .entry(topition.partition)
+                        .or_default();
+
+                    match sequences
+                        .entry(topition.topic.clone())
+                        .or_default()
+                        .entry(topition.partition)
+                        .or_default()
+                    {
+                        sequence if *sequence < deflated.base_sequence => {
+                            debug!(?sequence, base_sequence = deflated.base_sequence);
+
+                            Err(Error::Api(ErrorCode::OutOfOrderSequenceNumber))
+                        }
+
+                        sequence if *sequence > deflated.base_sequence => {
+                            debug!(?sequence, ?recent, base_sequence = deflated.base_sequence);
+
+                            Err(sequence_window.retry(recent, deflated.base_sequence))
+                        }
+
+                        sequence => {
+                            debug!(?sequence, delta = deflated.last_offset_delta + 1);
+
+                            *sequence += deflated.last_offset_delta + 1;
+                            sequence_window.accepted(recent, deflated.base_sequence);
+                            Ok(())
+                        }
+                    }
+                })
+                .await
+                .inspect(|outcome| debug!(transaction_id, ?topition, ?outcome))
+                .inspect_err(|err| error!(?err, transaction_id, ?topition))?;
         }
 
-        Ok(responses)
-    }
-}
+        if let Some(ref schemas) = self.schemas {
+            let inflated = inflated::Batch::try_from(&deflated)?;
 
-#[async_trait]
-impl Storage for DynoStore {
-    async fn register_broker(
-        &mut self,
-        broker_registration: BrokerRegistrationRequest,
-    ) -> Result<()> {
-        debug!(?broker_registration);
-        Ok(())
-    }
+            schemas.validate(topition.topic(), &inflated).await?;
+        }
 
-    async fn incremental_alter_resource(
-        &mut self,
-        resource: AlterConfigsResource,
-    ) -> Result<AlterConfigsResourceResponse> {
-        let _ = resource;
+        let dictionaries = self.zstd_dictionaries(topition.topic()).await?;
 
-        match ConfigResource::from(resource.resource_type) {
-            ConfigResource::Group => Ok(AlterConfigsResourceResponse {
+        let deflated = match dictionaries.compressor() {
+            Some(dictionary)
+                if BatchAttribute::try_from(deflated.attributes).is_ok_and(|attributes| {
+                    attributes.compression == Compression::Zstd && !attributes.control
+                }) =>
+            {
+                deflated.compress_with_dictionary(dictionary)?
+            }
+
+            _otherwise => deflated,
+        };
+
+        let watermark = self.watermarks.lock().map(|mut locked| {
+            locked
+                .entry(topition.to_owned())
+                .or_insert(OptiCon::<Watermark>::new(self.cluster.as_str(), topition))
+                .to_owned()
+        })?;
+
+        let offset = watermark
+            .with_mut(&self.object_store, |watermark| {
+                debug!(?watermark);
+
+                let offset = watermark.high.unwrap_or_default();
+                watermark.high = watermark
+                    .high
+                    .map_or(Some(deflated.last_offset_delta as i64 + 1i64), |high| {
+                        Some(high + deflated.last_offset_delta as i64 + 1i64)
+                    });
+
+                debug!(?watermark);
+
+                Ok(offset)
+            })
+            .await
+            .inspect(|offset| debug!(offset, transaction_id, ?topition))
+            .inspect_err(|err| error!(?err, transaction_id, ?topition))?;
+
+        let attributes = BatchAttribute::try_from(deflated.attributes)?;
+
+        if let Some(transaction_id) = transaction_id {
+            if attributes.transaction {
+                self.meta
+                    .with_mut(&self.object_store, |meta| {
+                        if let Some(transaction) = meta.transactions.get_mut(transaction_id) {
+                            debug!(?transaction);
+
+                            if let Some(txn_detail) =
+                                transaction.epochs.get_mut(&deflated.producer_epoch)
+                            {
+                                debug!(?txn_detail);
+
+                                let offset_end = offset + deflated.last_offset_delta as i64;
+
+                                _ = txn_detail
+                                    .produces
+                                    .entry(topition.topic.clone())
+                                    .or_default()
+                                    .entry(topition.partition)
+                                    .and_modify(|entry| {
+                                        let range = entry.get_or_insert(TxnProduceOffset {
+                                            offset_start: offset,
+                                            offset_end,
+                                        });
+
+                                        if offset_end > range.offset_end {
+                                            range.offset_end = offset_end;
+                                        }
+                                    })
+                                    .or_insert(Some(TxnProduceOffset {
+                                        offset_start: offset,
+                                        offset_end,
+                                    }));
+                            }
+                        }
+
+                        Ok(())
+                    })
+                    .await
+                    .inspect(|outcome| debug!(?outcome, transaction_id, ?topition))
+                    .inspect_err(|err| error!(?err, transaction_id, ?topition))?;
+            }
+        }
+
+        let location = Path::from(format!(
+            "clusters/{}/topics/{}/partitions/{:0>10}/records/{:0>20}.batch",
+            self.cluster, topition.topic, topition.partition, offset,
+        ));
+
+        let payload = self.encode(deflated::Batch {
+            partition_leader_epoch: LEADER_EPOCH,
+            ..deflated
+        })?;
+
+        _ = self
+            .object_store
+            .put_opts(
+                &location,
+                payload,
+                PutOptions {
+                    mode: PutMode::Create,
+                    tags: TagSet::default(),
+                    attributes: Attributes::new(),
+                },
+            )
+            .await
+            .inspect(|outcome| debug!(?outcome, transaction_id, ?topition))
+            .inspect_err(|error| error!(?error, transaction_id, ?topition))?;
+
+        Ok(offset)
+    }
+
+    // removes a batch appended by a produce many that failed part way,
+    // moving back the watermark and producer sequence of the partition
+    // unless they have since moved on, while the append lock is held
+    async fn retract(&self, topition: &Topition, appended: &Appended) -> Result<()> {
+        debug!(?topition, ?appended);
+
+        let location = Path::from(format!(
+            "clusters/{}/topics/{}/partitions/{:0>10}/records/{:0>20}.batch",
+            self.cluster, topition.topic, topition.partition, appended.offset,
+        ));
+
+        self.object_store.delete(&location).await?;
+
+        let watermark = self.watermarks.lock().map(|mut locked| {
+            locked
+                .entry(topition.to_owned())
+                .or_insert(OptiCon::<Watermark>::new(self.cluster.as_str(), topition))
+                .to_owned()
+        })?;
+
+        watermark
+            .with_mut(&self.object_store, |watermark| {
+                if watermark.high == Some(appended.offset + appended.last_offset_delta as i64 + 1) {
+                    watermark.high = Some(appended.offset);
+                }
+
+                Ok(())
+            })
+            .await?;
+
+        let Some((producer_id, producer_epoch, base_sequence)) = appended.sequence else {
+            return Ok(());
+        };
+
+        self.meta
+            .with_mut(&self.object_store, |meta| {
+                let Some(pd) = meta.producers.get_mut(&producer_id) else {
+                    return Ok(());
+                };
+
+                if let Some(sequence) = pd
+                    .sequences
+                    .get_mut(&producer_epoch)
+                    .and_then(|topics| topics.get_mut(topition.topic()))
+                    .and_then(|partitions| partitions.get_mut(&topition.partition()))
+                    .filter(|sequence| **sequence == base_sequence + appended.last_offset_delta + 1)
+                {
+                    *sequence = base_sequence;
+                }
+
+                if let Some(recent) = pd
+                    .recent
+                    .get_mut(&producer_epoch)
+                    .and_then(|topics| topics.get_mut(topition.topic()))
+                    .and_then(|partitions| partitions.get_mut(&topition.partition()))
+                {
+                    recent.retain(|recent| *recent != base_sequence);
+                }
+
+                Ok(())
+            })
+            .await
+    }
+
+    fn txn_offset_commit_response_error(
+        offsets: &TxnOffsetCommitRequest,
+        error_code: ErrorCode,
+    ) -> Result<Vec<TxnOffsetCommitResponseTopic>> {
+        let mut responses = vec![];
+
+        for topic in &offsets.topics {
+            let mut partition_responses = vec![];
+
+            if let Some(partitions) = topic.partitions.as_deref() {
+                for partition in partitions {
+                    partition_responses.push(TxnOffsetCommitResponsePartition {
+                        partition_index: partition.partition_index,
+                        error_code: error_code.into(),
+                    });
+                }
+            }
+
+            responses.push(TxnOffsetCommitResponseTopic {
+                name: topic.name.to_string(),
+                partitions: Some(partition_responses),
+            });
+        }
+
+        Ok(responses)
+    }
+}
+
+#[async_trait]
+impl Storage for DynoStore {
+    fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        let cache = self.cache.clock(clock.clone());
+
+        Self {
+            object_store: Arc::new(cache.clone()),
+            cache,
+            clock,
+            ..self
+        }
+    }
+
+    async fn register_broker(
+        &mut self,
+        broker_registration: BrokerRegistrationRequest,
+    ) -> Result<()> {
+        debug!(?broker_registration);
+
+        let detail = BrokerDetail {
+            host: self
+                .advertised_listener
+                .host_str()
+                .unwrap_or("0.0.0.0")
+                .into(),
+            port: self.advertised_listener.port().unwrap_or(9092).into(),
+            rack: broker_registration.rack,
+            incarnation_id: broker_registration.incarnation_id,
+            last_updated: Some(self.clock.now()),
+        };
+
+        self.meta
+            .with_mut(&self.object_store, |meta| {
+                _ = meta
+                    .brokers
+                    .insert(broker_registration.broker_id, detail.clone());
+                Ok(())
+            })
+            .await
+    }
+
+    async fn incremental_alter_resource(
+        &mut self,
+        resource: AlterConfigsResource,
+    ) -> Result<AlterConfigsResourceResponse> {
+        let _ = resource;
+
+        match ConfigResource::from(resource.resource_type) {
+            ConfigResource::Group => Ok(AlterConfigsResourceResponse {
                 error_code: ErrorCode::None.into(),
                 error_message: Some("".into()),
                 resource_type: resource.resource_type,
@@ -571,7 +1236,17 @@ impl Storage for DynoStore {
     }
 
     async fn create_topic(&mut self, topic: CreatableTopic, validate_only: bool) -> Result<Uuid> {
-        debug!(?topic, ?validate_only);
+        self.create_topic_within(topic, validate_only, TopicLimit::default())
+            .await
+    }
+
+    async fn create_topic_within(
+        &mut self,
+        topic: CreatableTopic,
+        validate_only: bool,
+        limit: TopicLimit,
+    ) -> Result<Uuid> {
+        debug!(?topic, ?validate_only, ?limit);
 
         match self
             .meta
@@ -580,6 +1255,14 @@ impl Storage for DynoStore {
                     return Err(Error::Api(ErrorCode::TopicAlreadyExists));
                 }
 
+                if let Some(violation) = limit.violation(
+                    meta.topics.len() as i64,
+                    meta.partitions(),
+                    topic.num_partitions,
+                ) {
+                    return Err(Error::TopicLimit(violation));
+                }
+
                 let id = Uuid::now_v7();
                 let td = TopicMetadata {
                     id,
@@ -624,7 +1307,92 @@ impl Storage for DynoStore {
         topics: &[DeleteRecordsTopic],
     ) -> Result<Vec<DeleteRecordsTopicResult>> {
         debug!(?topics);
-        todo!()
+
+        let mut responses = vec![];
+
+        for topic in topics {
+            let metadata = self
+                .topic_metadata(&TopicId::Name(topic.name.clone()))
+                .await?;
+
+            let mut partition_responses = vec![];
+
+            for partition in topic.partitions.as_deref().unwrap_or_default() {
+                if metadata.as_ref().is_none_or(|metadata| {
+                    partition.partition_index < 0
+                        || partition.partition_index >= metadata.topic.num_partitions
+                }) {
+                    partition_responses.push(DeleteRecordsPartitionResult {
+                        partition_index: partition.partition_index,
+                        low_watermark: -1,
+                        error_code: ErrorCode::UnknownTopicOrPartition.into(),
+                    });
+                    continue;
+                }
+
+                let topition = Topition::new(topic.name.as_str(), partition.partition_index);
+
+                let watermark = self.watermarks.lock().map(|mut locked| {
+                    locked
+                        .entry(topition.to_owned())
+                        .or_insert(OptiCon::<Watermark>::new(self.cluster.as_str(), &topition))
+                        .to_owned()
+                })?;
+
+                let (partition_response, high) = watermark
+                    .with_mut(&self.object_store, |watermark| {
+                        debug!(?watermark);
+
+                        let low = watermark.low.unwrap_or(0);
+                        let high = watermark.high.unwrap_or(0);
+
+                        let offset = if partition.offset == -1 {
+                            high
+                        } else {
+                            partition.offset
+                        };
+
+                        if offset < 0 || offset > high {
+                            return Ok((
+                                DeleteRecordsPartitionResult {
+                                    partition_index: partition.partition_index,
+                                    low_watermark: low,
+                                    error_code: ErrorCode::OffsetOutOfRange.into(),
+                                },
+                                high,
+                            ));
+                        }
+
+                        watermark.low = Some(offset.max(low));
+
+                        Ok((
+                            DeleteRecordsPartitionResult {
+                                partition_index: partition.partition_index,
+                                low_watermark: offset.max(low),
+                                error_code: ErrorCode::None.into(),
+                            },
+                            high,
+                        ))
+                    })
+                    .await
+                    .inspect(|response| debug!(?topition, ?response))
+                    .inspect_err(|err| error!(?err, ?topition))?;
+
+                if partition_response.error_code == i16::from(ErrorCode::None) {
+                    self.delete_batches_below(&topition, partition_response.low_watermark, high)
+                        .await?;
+                }
+
+                partition_responses.push(partition_response);
+            }
+
+            responses.push(DeleteRecordsTopicResult {
+                name: topic.name.clone(),
+                partitions: Some(partition_responses),
+            });
+        }
+
+        Ok(responses)
     }
 
     async fn delete_topic(&mut self, topic: &TopicId) -> Result<ErrorCode> {
@@ -638,6 +1406,11 @@ impl Storage for DynoStore {
                 })
                 .await?;
 
+            _ = self
+                .dictionaries
+                .lock()
+                .map(|mut decoded| decoded.remove(metadata.topic.name.as_str()))?;
+
             let prefix = Path::from(format!(
                 "clusters/{}/topics/{}/",
                 self.cluster, metadata.topic.name,
@@ -698,21 +1471,39 @@ impl Storage for DynoStore {
     async fn brokers(&mut self) -> Result<Vec<DescribeClusterBroker>> {
         debug!(cluster = self.cluster);
 
-        let broker_id = self.node;
-        let host = self
-            .advertised_listener
-            .host_str()
-            .unwrap_or("0.0.0.0")
-            .into();
-        let port = self.advertised_listener.port().unwrap_or(9092).into();
-        let rack = None;
+        let now = self.clock.now();
+
+        self.meta
+            .with(&self.object_store, |meta| {
+                Ok(meta
+                    .brokers
+                    .iter()
+                    .filter(|(_, detail)| {
+                        detail.last_updated.is_some_and(|last_updated| {
+                            now.duration_since(last_updated)
+                                .is_ok_and(|elapsed| elapsed <= BROKER_LIVENESS)
+                                || last_updated > now
+                        })
+                    })
+                    .map(|(broker_id, detail)| DescribeClusterBroker {
+                        broker_id: *broker_id,
+                        host: detail.host.clone(),
+                        port: detail.port,
+                        rack: detail.rack.clone(),
+                    })
+                    .collect())
+            })
+            .await
+    }
+
+    async fn partition_count(&mut self) -> Result<(i64, i64)> {
+        debug!(cluster = self.cluster);
 
-        Ok(vec![DescribeClusterBroker {
-            broker_id,
-            host,
-            port,
-            rack,
-        }])
+        self.meta
+            .with(&self.object_store, |meta| {
+                Ok((meta.topics.len() as i64, meta.partitions()))
+            })
+            .await
     }
 
     async fn produce(
@@ -723,159 +1514,94 @@ impl Storage for DynoStore {
     ) -> Result<i64> {
         debug!(?transaction_id, ?topition, ?deflated);
 
-        if deflated.is_idempotent() {
-            self.meta
-                .with_mut(&self.object_store, |meta| {
-                    let Some(pd) = meta.producers.get_mut(&deflated.producer_id) else {
-                        debug!(producer_id = deflated.producer_id, ?meta.producers);
-                        return Err(Error::Api(ErrorCode::UnknownProducerId));
-                    };
-
-                    let Some(mut current) = pd.sequences.last_entry() else {
-                        debug!(last_entry = ?pd.sequences.last_entry());
-                        return Err(Error::Api(ErrorCode::UnknownServerError));
-                    };
-
-                    if current.key() != &deflated.producer_epoch {
-                        debug!(current = ?current.key(), producer_epoch = deflated.producer_epoch);
-                        return Err(Error::Api(ErrorCode::ProducerFenced));
-                    }
-
-                    let sequences = current.get_mut();
-                    debug!(?sequences);
-
-                    match sequences
-                        .entry(topition.topic.clone())
-                        .or_default()
-                        .entry(topition.partition)
-                        .or_default()
-                    {
-                        sequence if *sequence < deflated.base_sequence => {
-                            debug!(?sequence, base_sequence = deflated.base_sequence);
-
-                            Err(Error::Api(ErrorCode::OutOfOrderSequenceNumber))
-                        }
-
-                        sequence if *sequence > deflated.base_sequence => {
-                            debug!(?sequence, base_sequence = deflated.base_sequence);
-
-                            Err(Error::Api(ErrorCode::DuplicateSequenceNumber))
-                        }
+        // appends to a partition are serialized, so that a sequence is
+        // checked, an offset assigned and the batch written before the
+        // next append to the partition begins. Appends to different
+        // partitions proceed in parallel
+        let append = self.append(topition)?;
+        let _append = append.lock().await;
 
-                        sequence => {
-                            debug!(?sequence, delta = deflated.last_offset_delta + 1);
+        self.append_batch(transaction_id, topition, deflated).await
+    }
 
-                            *sequence += deflated.last_offset_delta + 1;
-                            Ok(())
-                        }
-                    }
+    async fn produce_many(
+        &mut self,
+        transaction_id: Option<&str>,
+        entries: Vec<(Topition, deflated::Batch)>,
+    ) -> Result<Vec<i64>> {
+        debug!(?transaction_id, ?entries);
+
+        // without a storage transaction: check every topition before
+        // appending, holding the append lock of each partition (taken in
+        // order) until every batch is appended. This is per partition
+        // rather than atomic: a failure part way retracts the batches
+        // already appended, which may be briefly visible beforehand
+        self.meta
+            .with(&self.object_store, |meta| {
+                entries.iter().try_for_each(|(topition, _)| {
+                    meta.topics
+                        .get(topition.topic())
+                        .filter(|metadata| {
+                            (0..metadata.topic.num_partitions).contains(&topition.partition())
+                        })
+                        .and(Some(()))
+                        .ok_or(Error::Api(ErrorCode::UnknownTopicOrPartition))
                 })
-                .await
-                .inspect(|outcome| debug!(transaction_id, ?topition, ?outcome))
-                .inspect_err(|err| error!(?err, transaction_id, ?topition))?;
-        }
-
-        if let Some(ref schemas) = self.schemas {
-            let inflated = inflated::Batch::try_from(&deflated)?;
-
-            schemas.validate(topition.topic(), &inflated).await?;
-        }
-
-        let watermark = self.watermarks.lock().map(|mut locked| {
-            locked
-                .entry(topition.to_owned())
-                .or_insert(OptiCon::<Watermark>::new(self.cluster.as_str(), topition))
-                .to_owned()
-        })?;
-
-        let offset = watermark
-            .with_mut(&self.object_store, |watermark| {
-                debug!(?watermark);
-
-                let offset = watermark.high.unwrap_or_default();
-                watermark.high = watermark
-                    .high
-                    .map_or(Some(deflated.last_offset_delta as i64 + 1i64), |high| {
-                        Some(high + deflated.last_offset_delta as i64 + 1i64)
-                    });
-
-                debug!(?watermark);
-
-                Ok(offset)
             })
             .await
-            .inspect(|offset| debug!(offset, transaction_id, ?topition))
-            .inspect_err(|err| error!(?err, transaction_id, ?topition))?;
+            .inspect_err(|err| error!(?err, transaction_id))?;
 
-        let attributes = BatchAttribute::try_from(deflated.attributes)?;
+        let appends = entries
+            .iter()
+            .map(|(topition, _)| topition)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|topition| self.append(topition))
+            .collect::<Result<Vec<_>>>()?;
 
-        if let Some(transaction_id) = transaction_id {
-            if attributes.transaction {
-                self.meta
-                    .with_mut(&self.object_store, |meta| {
-                        if let Some(transaction) = meta.transactions.get_mut(transaction_id) {
-                            debug!(?transaction);
+        let mut locked = Vec::with_capacity(appends.len());
 
-                            if let Some(txn_detail) =
-                                transaction.epochs.get_mut(&deflated.producer_epoch)
-                            {
-                                debug!(?txn_detail);
+        for append in &appends {
+            locked.push(append.lock().await);
+        }
 
-                                let offset_end = offset + deflated.last_offset_delta as i64;
+        let mut appended: Vec<(Topition, Appended)> = Vec::with_capacity(entries.len());
 
-                                _ = txn_detail
-                                    .produces
-                                    .entry(topition.topic.clone())
-                                    .or_default()
-                                    .entry(topition.partition)
-                                    .and_modify(|entry| {
-                                        let range = entry.get_or_insert(TxnProduceOffset {
-                                            offset_start: offset,
-                                            offset_end,
-                                        });
+        for (topition, deflated) in entries {
+            let sequence = deflated.is_idempotent().then_some((
+                deflated.producer_id,
+                deflated.producer_epoch,
+                deflated.base_sequence,
+            ));
+            let last_offset_delta = deflated.last_offset_delta;
 
-                                        if offset_end > range.offset_end {
-                                            range.offset_end = offset_end;
-                                        }
-                                    })
-                                    .or_insert(Some(TxnProduceOffset {
-                                        offset_start: offset,
-                                        offset_end,
-                                    }));
-                            }
-                        }
+            match self.append_batch(transaction_id, &topition, deflated).await {
+                Ok(offset) => appended.push((
+                    topition,
+                    Appended {
+                        offset,
+                        last_offset_delta,
+                        sequence,
+                    },
+                )),
+
+                Err(error) => {
+                    for (topition, appended) in appended.iter().rev() {
+                        _ = self
+                            .retract(topition, appended)
+                            .await
+                            .inspect_err(|error| error!(?error, transaction_id, ?topition));
+                    }
 
-                        Ok(())
-                    })
-                    .await
-                    .inspect(|outcome| debug!(?outcome, transaction_id, ?topition))
-                    .inspect_err(|err| error!(?err, transaction_id, ?topition))?;
+                    return Err(error);
+                }
             }
         }
 
-        let location = Path::from(format!(
-            "clusters/{}/topics/{}/partitions/{:0>10}/records/{:0>20}.batch",
-            self.cluster, topition.topic, topition.partition, offset,
-        ));
-
-        let payload = self.encode(deflated)?;
-
-        _ = self
-            .object_store
-            .put_opts(
-                &location,
-                payload,
-                PutOptions {
-                    mode: PutMode::Create,
-                    tags: TagSet::default(),
-                    attributes: Attributes::new(),
-                },
-            )
-            .await
-            .inspect(|outcome| debug!(?outcome, transaction_id, ?topition))
-            .inspect_err(|error| error!(?error, transaction_id, ?topition))?;
-
-        Ok(offset)
+        Ok(appended
+            .into_iter()
+            .map(|(_, appended)| appended.offset)
+            .collect())
     }
 
     async fn fetch(
@@ -911,7 +1637,15 @@ impl Storage for DynoStore {
                 self.cluster, topition.topic, topition.partition
             ));
 
-            let mut list_stream = self.object_store.list(Some(&location));
+            // batches are keyed by their zero padded base offset, skip
+            // listing any that precede the requested offset
+            //
+            let start = Path::from(format!(
+                "clusters/{}/topics/{}/partitions/{:0>10}/records/{:0>20}",
+                self.cluster, topition.topic, topition.partition, offset
+            ));
+
+            let mut list_stream = self.object_store.list_with_offset(Some(&location), &start);
 
             while let Some(meta) = list_stream
                 .next()
@@ -935,6 +1669,7 @@ impl Storage for DynoStore {
         }
 
         let mut batches = vec![];
+        let mut dictionaries = None;
 
         let mut bytes = max_bytes as usize;
 
@@ -966,6 +1701,25 @@ impl Storage for DynoStore {
                 .map_err(|_| Error::Api(ErrorCode::UnknownServerError))
                 .and_then(|encoded| self.decode(encoded))?;
             batch.base_offset = offset;
+
+            // clients only know the codec, not the dictionary
+            if let Some(id) = batch.dictionary_id() {
+                if dictionaries.is_none() {
+                    dictionaries = Some(self.zstd_dictionaries(topition.topic()).await?);
+                }
+
+                batch = dictionaries
+                    .as_ref()
+                    .and_then(|dictionaries| dictionaries.get(id))
+                    .ok_or(Error::UnknownZstdDictionary(id))
+                    .and_then(|dictionary| {
+                        batch
+                            .decompress_with_dictionary(dictionary)
+                            .map_err(Into::into)
+                    })
+                    .inspect_err(|error| error!(?error, ?topition, offset))?;
+            }
+
             batches.push(batch);
         }
 
@@ -1039,6 +1793,74 @@ impl Storage for DynoStore {
             .await
     }
 
+    async fn tier_records(&mut self, topition: &Topition, offset: i64) -> Result<ErrorCode> {
+        debug!(?topition, offset);
+
+        let watermark = self.watermarks.lock().map(|mut locked| {
+            locked
+                .entry(topition.to_owned())
+                .or_insert(OptiCon::<Watermark>::new(self.cluster.as_str(), topition))
+                .to_owned()
+        })?;
+
+        watermark
+            .with_mut(&self.object_store, |watermark| {
+                if offset < 0 || offset > watermark.high.unwrap_or(0) {
+                    return Ok(ErrorCode::OffsetOutOfRange);
+                }
+
+                watermark.tiered =
+                    Some(watermark.tiered.map_or(offset, |tiered| tiered.max(offset)));
+                Ok(ErrorCode::None)
+            })
+            .await
+    }
+
+    async fn topic_exists(&mut self, topic: &TopicId) -> Result<bool> {
+        debug!(?topic);
+
+        self.topic_metadata(topic)
+            .await
+            .map(|metadata| metadata.is_some())
+    }
+
+    async fn partition_exists(&mut self, topition: &Topition) -> Result<bool> {
+        debug!(?topition);
+
+        self.topic_metadata(&TopicId::Name(topition.topic().into()))
+            .await
+            .map(|metadata| {
+                metadata.is_some_and(|metadata| {
+                    (0..metadata.topic.num_partitions).contains(&topition.partition())
+                })
+            })
+    }
+
+    async fn in_sync_replicas(&mut self, topition: &Topition) -> Result<i32> {
+        debug!(?topition);
+
+        // the object store holds the only copy of a batch
+        Ok(1)
+    }
+
+    async fn watermarks(&mut self, topition: &Topition) -> Result<(i64, i64)> {
+        debug!(?topition);
+
+        let watermark = self.watermarks.lock().map(|mut locked| {
+            locked
+                .entry(topition.to_owned())
+                .or_insert(OptiCon::<Watermark>::new(self.cluster.as_str(), topition))
+                .to_owned()
+        })?;
+
+        watermark
+            .with(&self.object_store, |watermark| {
+                debug!(?watermark);
+                Ok((watermark.low.unwrap_or(0), watermark.high.unwrap_or(0)))
+            })
+            .await
+    }
+
     async fn list_offsets(
         &mut self,
         isolation_level: IsolationLevel,
@@ -1141,6 +1963,27 @@ impl Storage for DynoStore {
                                 .await?
                         }
                     }
+                    ListOffsetRequest::EarliestLocal | ListOffsetRequest::LatestTiered => {
+                        let watermark = self.watermarks.lock().map(|mut locked| {
+                            locked
+                                .entry(topition.to_owned())
+                                .or_insert(OptiCon::<Watermark>::new(
+                                    self.cluster.as_str(),
+                                    topition,
+                                ))
+                                .to_owned()
+                        })?;
+
+                        watermark
+                            .with(&self.object_store, |watermark| {
+                                Ok(ListOffsetResponse {
+                                    offset: offset_request
+                                        .tiered(watermark.low.unwrap_or(0), watermark.tiered),
+                                    ..Default::default()
+                                })
+                            })
+                            .await?
+                    }
                     ListOffsetRequest::Timestamp(..) => todo!(),
                 },
             ));
@@ -1170,6 +2013,12 @@ impl Storage for DynoStore {
                     self.cluster, group_id, topition.topic, topition.partition,
                 ));
 
+                // recording when the offset was committed, unless given
+                let offset_commit = OffsetCommitRequest {
+                    timestamp: offset_commit.timestamp.or(Some(self.clock.now())),
+                    ..offset_commit.clone()
+                };
+
                 let payload = serde_json::to_vec(&offset_commit)
                     .map(Bytes::from)
                     .map(PutPayload::from)?;
@@ -1203,54 +2052,67 @@ impl Storage for DynoStore {
     ) -> Result<BTreeMap<Topition, i64>> {
         debug!(group_id);
 
-        let mut topitions = vec![];
+        let topitions = self.offset_topitions(group_id).await?;
 
-        {
-            let location = Path::from(format!(
-                "clusters/{}/groups/consumers/{}/offsets/",
-                self.cluster, group_id,
-            ));
-
-            let mut list_stream = self.object_store.list(Some(&location));
-
-            while let Some(meta) = list_stream
-                .next()
-                .await
-                .inspect(|meta| debug!(?meta))
-                .transpose()
-                .inspect_err(|error| error!(?error))
-                .map_err(|_| Error::Api(ErrorCode::UnknownServerError))?
-            {
-                debug!(?meta);
-                let Some(topic): Option<String> = meta
-                    .location
-                    .parts()
-                    .nth(6)
-                    .inspect(|topic| debug!(?topic))
-                    .map(|topic| topic.as_ref().into())
-                else {
-                    continue;
-                };
+        self.offset_fetch(Some(group_id), topitions.as_ref(), Some(false))
+            .await
+    }
 
-                let Some(partition) = meta
-                    .location
-                    .parts()
-                    .nth(8)
-                    .inspect(|partition| debug!(?partition))
-                    .map(|partition| i32::from_str(&partition.as_ref()[0..10]))
-                    .transpose()?
-                else {
-                    continue;
-                };
+    async fn committed_offsets(
+        &mut self,
+        group_id: &str,
+    ) -> Result<BTreeMap<Topition, OffsetCommitRequest>> {
+        debug!(group_id);
+
+        let mut offsets = BTreeMap::new();
+
+        for topition in self.offset_topitions(group_id).await? {
+            let location = Path::from(format!(
+                "clusters/{}/groups/consumers/{}/offsets/{}/partitions/{:0>10}.json",
+                self.cluster, group_id, topition.topic, topition.partition,
+            ));
+
+            match self.get::<OffsetCommitRequest>(&location).await {
+                Ok((offset, _)) => {
+                    _ = offsets.insert(topition, offset);
+                }
 
-                debug!(topic, partition);
+                Err(Error::ObjectStore(object_store::Error::NotFound { .. })) => (),
 
-                topitions.push(Topition::new(topic, partition));
+                Err(error) => {
+                    error!(?error, group_id, ?topition);
+                    return Err(error);
+                }
             }
         }
 
-        self.offset_fetch(Some(group_id), topitions.as_ref(), Some(false))
+        Ok(offsets)
+    }
+
+    async fn pending_offset_commits(
+        &mut self,
+        group_id: &str,
+        topics: &[Topition],
+    ) -> Result<BTreeSet<Topition>> {
+        debug!(group_id, ?topics);
+
+        self.meta
+            .with(&self.object_store, |meta| {
+                Ok(topics
+                    .iter()
+                    .filter(|topition| {
+                        meta.transactions
+                            .values()
+                            .flat_map(|transaction| transaction.epochs.values())
+                            .filter_map(|txn_detail| txn_detail.offsets.get(group_id))
+                            .filter_map(|offsets| offsets.get(topition.topic()))
+                            .any(|partitions| partitions.contains_key(&topition.partition()))
+                    })
+                    .cloned()
+                    .collect())
+            })
             .await
+            .inspect(|pending| debug!(group_id, ?pending))
     }
 
     async fn offset_fetch(
@@ -1381,7 +2243,11 @@ impl Storage for DynoStore {
                         }
 
                         Ok(None) => MetadataResponseTopic {
-                            error_code: ErrorCode::UnknownTopicOrPartition.into(),
+                            error_code: match topic {
+                                TopicId::Name(_) => ErrorCode::UnknownTopicOrPartition,
+                                TopicId::Id(_) => ErrorCode::UnknownTopicId,
+                            }
+                            .into(),
                             name: match topic {
                                 TopicId::Name(name) => Some(name.into()),
                                 TopicId::Id(_) => None,
@@ -1395,8 +2261,12 @@ impl Storage for DynoStore {
                             topic_authorized_operations: Some(-2147483648),
                         },
 
-                        Err(_) => MetadataResponseTopic {
-                            error_code: ErrorCode::UnknownServerError.into(),
+                        Err(error) => MetadataResponseTopic {
+                            error_code: match error {
+                                Error::Api(error_code) => error_code,
+                                _otherwise => ErrorCode::KafkaStorageError,
+                            }
+                            .into(),
                             name: match topic {
                                 TopicId::Name(name) => Some(name.into()),
                                 TopicId::Id(_) => Some("".into()),
@@ -1496,6 +2366,10 @@ impl Storage for DynoStore {
         })
     }
 
+    async fn metadata_snapshot(&mut self) -> Result<MetadataResponse> {
+        self.metadata(None).await
+    }
+
     async fn describe_config(
         &mut self,
         name: &str,
@@ -1533,8 +2407,19 @@ impl Storage for DynoStore {
                     })
                 }
 
-                Ok(None) => todo!(),
-                Err(_) => todo!(),
+                Ok(None) => {
+                    let error_code = ErrorCode::UnknownTopicOrPartition;
+
+                    Ok(DescribeConfigsResult {
+                        error_code: error_code.into(),
+                        error_message: Some(error_code.to_string()),
+                        resource_type: i8::from(resource),
+                        resource_name: name.into(),
+                        configs: Some([].into()),
+                    })
+                }
+
+                Err(error) => Err(error),
             },
 
             _ => todo!(),
@@ -1624,8 +2509,12 @@ impl Storage for DynoStore {
         Ok(responses)
     }
 
-    async fn list_groups(&mut self, states_filter: Option<&[String]>) -> Result<Vec<ListedGroup>> {
-        debug!(?states_filter);
+    async fn list_groups(
+        &mut self,
+        states_filter: Option<&[String]>,
+        types_filter: Option<&[String]>,
+    ) -> Result<Vec<ListedGroup>> {
+        debug!(?states_filter, ?types_filter);
 
         let location = Path::from(format!("clusters/{}/groups/consumers/", self.cluster,));
         let list_result = self
@@ -1635,17 +2524,58 @@ impl Storage for DynoStore {
             .inspect(|list_result| debug!(?list_result))
             .inspect_err(|error| error!(?error, cluster = self.cluster))?;
 
+        // groups with committed offsets have a prefix, while groups
+        // with members have detail
+        //
+        let group_ids = list_result
+            .common_prefixes
+            .iter()
+            .filter_map(|prefix| prefix.parts().last())
+            .map(|group_id| group_id.as_ref().to_owned())
+            .chain(list_result.objects.iter().filter_map(|object| {
+                object
+                    .location
+                    .filename()
+                    .and_then(|filename| filename.strip_suffix(".json"))
+                    .map(ToOwned::to_owned)
+            }))
+            .collect::<BTreeSet<_>>();
+
+        let location = Path::from(format!(
+            "clusters/{}/groups/types/{}/",
+            self.cluster,
+            GroupType::Consumer
+        ));
+
+        let consumers = self
+            .object_store
+            .list(Some(&location))
+            .try_filter_map(|object| {
+                future::ready(Ok(object.location.filename().map(ToOwned::to_owned)))
+            })
+            .try_collect::<BTreeSet<_>>()
+            .await
+            .inspect_err(|error| error!(?error, cluster = self.cluster))?;
+
         let mut listed_groups = vec![];
 
-        for prefix in list_result.common_prefixes {
-            if let Some(group_id) = prefix.parts().last() {
-                listed_groups.push(ListedGroup {
-                    group_id: group_id.as_ref().into(),
-                    protocol_type: "consumer".into(),
-                    group_state: Some("Unknown".into()),
-                    group_type: None,
-                });
+        for group_id in group_ids {
+            let group_type = if consumers.contains(&group_id) {
+                GroupType::Consumer
+            } else {
+                GroupType::Classic
+            };
+
+            if !group_type.is_listed(types_filter) {
+                continue;
             }
+
+            listed_groups.push(ListedGroup {
+                group_id,
+                protocol_type: "consumer".into(),
+                group_state: Some("Unknown".into()),
+                group_type: Some(group_type.to_string()),
+            });
         }
 
         Ok(listed_groups)
@@ -1666,16 +2596,71 @@ impl Storage for DynoStore {
                     self.cluster, group_id,
                 ));
 
-                let had_group_state = self
-                    .object_store
-                    .delete(&location)
-                    .await
-                    .inspect(|outcome| debug!(group_id, ?outcome))
-                    .inspect_err(|err| error!(group_id, ?err))
-                    .is_ok();
+                // object stores have no conditional delete: rewrite the empty
+                // detail against the version that was read, so that a member
+                // joining in the meantime fails the precondition, then delete
+                let deleted = loop {
+                    let (detail, version) = match self.get::<GroupDetail>(&location).await {
+                        Ok(found) => found,
+                        Err(_) => break Some(false),
+                    };
+
+                    if !detail.members.is_empty() {
+                        break None;
+                    }
+
+                    match self
+                        .put(&location, detail, json_content_type(), Some(version.into()))
+                        .await
+                    {
+                        Ok(_) => (),
+
+                        Err(UpdateError::Outdated { current, .. })
+                            if current.members.is_empty() =>
+                        {
+                            continue;
+                        }
+
+                        Err(UpdateError::Outdated { .. }) => break None,
+
+                        Err(error) => {
+                            error!(group_id, ?error);
+                            break Some(false);
+                        }
+                    }
+
+                    break Some(
+                        self.object_store
+                            .delete(&location)
+                            .await
+                            .inspect(|outcome| debug!(group_id, ?outcome))
+                            .inspect_err(|err| error!(group_id, ?err))
+                            .is_ok(),
+                    );
+                };
+
+                let Some(had_group_state) = deleted else {
+                    results.push(DeletableGroupResult {
+                        group_id: group_id.into(),
+                        error_code: ErrorCode::NonEmptyGroup.into(),
+                    });
+
+                    continue;
+                };
 
                 debug!(group_id, had_group_state);
 
+                if had_group_state {
+                    match self
+                        .object_store
+                        .delete(&self.consumer_group_marker(group_id))
+                        .await
+                    {
+                        Ok(()) | Err(object_store::Error::NotFound { .. }) => (),
+                        Err(error) => error!(group_id, ?error),
+                    }
+                }
+
                 let prefix = Path::from(format!(
                     "clusters/{}/groups/consumers/{}",
                     self.cluster, group_id,
@@ -1737,7 +2722,10 @@ impl Storage for DynoStore {
                     Err(Error::ObjectStore(object_store::Error::NotFound { .. })) => {
                         results.push(NamedGroupDetail::found(
                             group_id.into(),
-                            GroupDetail::default(),
+                            GroupDetail {
+                                inception: self.clock.now(),
+                                ..Default::default()
+                            },
                         ));
                     }
 
@@ -1767,14 +2755,29 @@ impl Storage for DynoStore {
             self.cluster, group_id,
         ));
 
-        self.put(
-            &location,
-            detail,
-            json_content_type(),
-            version.map(Into::into),
-        )
-        .await
-        .map(Into::into)
+        // the type of a group is fixed when it is created, with a marker
+        // for consumer protocol groups so that they can be listed by type
+        // without reading the detail of every group
+        let consumer = version.is_none() && detail.group_type == GroupType::Consumer;
+
+        let put_result = self
+            .put(
+                &location,
+                detail,
+                json_content_type(),
+                version.map(Into::into),
+            )
+            .await?;
+
+        if consumer {
+            _ = self
+                .object_store
+                .put(&self.consumer_group_marker(group_id), PutPayload::new())
+                .await
+                .inspect_err(|error| error!(group_id, ?error))?;
+        }
+
+        Ok(put_result.into())
     }
 
     async fn init_producer(
@@ -1800,88 +2803,116 @@ impl Storage for DynoStore {
             },
         }
 
+        // starts a new epoch for the transactional producer, checked and
+        // bumped within the same conditional update of the meta
+        fn bump(
+            meta: &mut Meta,
+            transaction_id: &str,
+            transaction_timeout_ms: i32,
+        ) -> Result<InitProducer> {
+            match meta.transactions.entry(transaction_id.to_string()) {
+                Entry::Vacant(vacant) => {
+                    let id = meta
+                        .producers
+                        .last_key_value()
+                        .map_or(1.into(), |(k, _v)| k + 1);
+
+                    let mut pd = ProducerDetail::default();
+                    assert_eq!(None, pd.sequences.insert(0, BTreeMap::new()));
+                    assert_eq!(None, meta.producers.insert(id, pd));
+
+                    let mut epochs = BTreeMap::new();
+                    assert_eq!(
+                        None,
+                        epochs.insert(
+                            0,
+                            TxnDetail {
+                                transaction_timeout_ms,
+                                ..Default::default()
+                            },
+                        )
+                    );
+
+                    _ = vacant.insert(Txn {
+                        producer: id,
+                        epochs,
+                    });
+
+                    Ok(InitProducer::Completed(ProducerIdResponse {
+                        id,
+                        epoch: 0,
+                        error: ErrorCode::None,
+                    }))
+                }
+
+                Entry::Occupied(mut occupied) => {
+                    if let Some((current_epoch, txn_detail)) =
+                        occupied.get().epochs.last_key_value()
+                    {
+                        if txn_detail.state == Some(TxnState::Begin) {
+                            Ok(InitProducer::NeedToRollback {
+                                producer_id: occupied.get().producer,
+                                producer_epoch: *current_epoch,
+                            })
+                        } else {
+                            let id = occupied.get().producer;
+                            let epoch = current_epoch + 1;
+
+                            _ = meta.producers.entry(id).and_modify(|pd| {
+                                assert_eq!(None, pd.sequences.insert(epoch, BTreeMap::new()));
+                            });
+
+                            assert_eq!(
+                                None,
+                                occupied.get_mut().epochs.insert(
+                                    epoch,
+                                    TxnDetail {
+                                        transaction_timeout_ms,
+                                        ..Default::default()
+                                    }
+                                )
+                            );
+
+                            Ok(InitProducer::Completed(ProducerIdResponse {
+                                id,
+                                epoch,
+                                error: ErrorCode::None,
+                            }))
+                        }
+                    } else {
+                        todo!()
+                    }
+                }
+            }
+        }
+
         if let Some(transaction_id) = transaction_id {
             match self
                 .meta
                 .with_mut(&self.object_store, |meta| {
                     debug!(?meta);
                     match (producer_id, producer_epoch) {
-                        (Some(-1), Some(-1)) => {
-                            match meta.transactions.entry(transaction_id.to_string()) {
-                                Entry::Vacant(vacant) => {
-                                    let id = meta
-                                        .producers
-                                        .last_key_value()
-                                        .map_or(1.into(), |(k, _v)| k + 1);
-
-                                    let mut pd = ProducerDetail::default();
-                                    assert_eq!(None, pd.sequences.insert(0, BTreeMap::new()));
-                                    assert_eq!(None, meta.producers.insert(id, pd));
-
-                                    let mut epochs = BTreeMap::new();
-                                    assert_eq!(
-                                        None,
-                                        epochs.insert(
-                                            0,
-                                            TxnDetail {
-                                                transaction_timeout_ms,
-                                                ..Default::default()
-                                            },
-                                        )
-                                    );
-
-                                    _ = vacant.insert(Txn {
-                                        producer: id,
-                                        epochs,
-                                    });
+                        (Some(-1), Some(-1)) => bump(meta, transaction_id, transaction_timeout_ms),
 
-                                    Ok(InitProducer::Completed(ProducerIdResponse {
-                                        id,
-                                        epoch: 0,
-                                        error: ErrorCode::None,
-                                    }))
+                        (Some(id), Some(epoch)) if id >= 0 && epoch >= 0 => {
+                            let current = meta.transactions.get(transaction_id).and_then(|txn| {
+                                txn.epochs
+                                    .last_key_value()
+                                    .map(|(current_epoch, _)| (txn.producer, *current_epoch))
+                            });
+
+                            match reinitialized(current, id, epoch) {
+                                ErrorCode::None => {
+                                    bump(meta, transaction_id, transaction_timeout_ms)
                                 }
 
-                                Entry::Occupied(mut occupied) => {
-                                    if let Some((current_epoch, txn_detail)) =
-                                        occupied.get().epochs.last_key_value()
-                                    {
-                                        if txn_detail.state == Some(TxnState::Begin) {
-                                            Ok(InitProducer::NeedToRollback {
-                                                producer_id: occupied.get().producer,
-                                                producer_epoch: *current_epoch,
-                                            })
-                                        } else {
-                                            let id = occupied.get().producer;
-                                            let epoch = current_epoch + 1;
-
-                                            _ = meta.producers.entry(id).and_modify(|pd| {
-                                                assert_eq!(
-                                                    None,
-                                                    pd.sequences.insert(epoch, BTreeMap::new())
-                                                );
-                                            });
-
-                                            assert_eq!(
-                                                None,
-                                                occupied.get_mut().epochs.insert(
-                                                    epoch,
-                                                    TxnDetail {
-                                                        transaction_timeout_ms,
-                                                        ..Default::default()
-                                                    }
-                                                )
-                                            );
-
-                                            Ok(InitProducer::Completed(ProducerIdResponse {
-                                                id,
-                                                epoch,
-                                                error: ErrorCode::None,
-                                            }))
-                                        }
-                                    } else {
-                                        todo!()
-                                    }
+                                error => {
+                                    debug!(?current, id, epoch, ?error);
+                                    Ok(InitProducer::Completed(ProducerIdResponse {
+                                        id: -1,
+                                        epoch: -1,
+                                        error,
+                                    }))
                                 }
                             }
                         }
@@ -1899,6 +2930,7 @@ impl Storage for DynoStore {
                 .await?
             {
                 InitProducer::Completed(completed) => Ok(completed),
+
                 InitProducer::NeedToRollback {
                     producer_id: rollback_producer_id,
                     producer_epoch: rollback_producer_epoch,
@@ -1970,6 +3002,30 @@ impl Storage for DynoStore {
         }
     }
 
+    async fn allocate_producer_id_block(&mut self, size: i32) -> Result<Range<i64>> {
+        debug!(size);
+
+        if size < 1 {
+            return Err(Error::Api(ErrorCode::InvalidRequest));
+        }
+
+        self.meta
+            .with_mut(&self.object_store, |meta| {
+                let start = meta.producers.last_key_value().map_or(1, |(k, _v)| k + 1);
+
+                let block = start..start + i64::from(size);
+
+                for producer in block.clone() {
+                    let mut pd = ProducerDetail::default();
+                    assert_eq!(None, pd.sequences.insert(0, BTreeMap::new()));
+                    assert_eq!(None, meta.producers.insert(producer, pd));
+                }
+
+                Ok(block)
+            })
+            .await
+    }
+
     async fn txn_add_offsets(
         &mut self,
         transaction_id: &str,
@@ -1995,8 +3051,24 @@ impl Storage for DynoStore {
                 producer_epoch,
                 ref topics,
             } => {
+                let txn_limit = self.txn_limit;
+                let clock = self.clock.clone();
+
                 self.meta
                     .with_mut(&self.object_store, |meta| {
+                        let others = meta
+                            .transactions
+                            .iter()
+                            .filter(|(name, transaction)| {
+                                *name != &transaction_id
+                                    && transaction
+                                        .epochs
+                                        .last_key_value()
+                                        .and_then(|(_, txn_detail)| txn_detail.state)
+                                        .is_some_and(|state| state.is_open())
+                            })
+                            .count();
+
                         let Some(transaction) = meta.transactions.get_mut(&transaction_id) else {
                             let mut results = vec![];
 
@@ -2086,24 +3158,58 @@ impl Storage for DynoStore {
                             return Ok(TxnAddPartitionsResponse::VersionZeroToThree(results));
                         }
 
+                        if let Some(error_code) = txn_limit.open(others) {
+                            let mut results = vec![];
+
+                            for topic in topics {
+                                let mut results_by_partition = vec![];
+
+                                for partition_index in topic.partitions.as_deref().unwrap_or(&[]) {
+                                    results_by_partition.push(AddPartitionsToTxnPartitionResult {
+                                        partition_index: *partition_index,
+                                        partition_error_code: error_code.into(),
+                                    });
+                                }
+
+                                results.push(AddPartitionsToTxnTopicResult {
+                                    name: topic.name.clone(),
+                                    results_by_partition: Some(results_by_partition),
+                                })
+                            }
+
+                            return Ok(TxnAddPartitionsResponse::VersionZeroToThree(results));
+                        }
+
                         let txn_detail = current_epoch.get_mut();
 
+                        let mut enrolled = txn_detail
+                            .produces
+                            .values()
+                            .map(BTreeMap::len)
+                            .sum::<usize>();
+
                         let mut results = vec![];
 
                         for topic in topics {
                             let mut results_by_partition = vec![];
 
                             for partition_index in topic.partitions.as_deref().unwrap_or(&[]) {
-                                _ = txn_detail
-                                    .produces
-                                    .entry(topic.name.clone())
-                                    .or_default()
-                                    .entry(*partition_index)
-                                    .or_default();
+                                let partitions =
+                                    txn_detail.produces.entry(topic.name.clone()).or_default();
+
+                                let error_code = if partitions.contains_key(partition_index) {
+                                    ErrorCode::None
+                                } else if let Some(error_code) = txn_limit.enrol(enrolled) {
+                                    error_code
+                                } else {
+                                    _ = partitions.insert(*partition_index, None);
+                                    enrolled += 1;
+                                    ErrorCode::None
+                                };
 
                                 results_by_partition.push(AddPartitionsToTxnPartitionResult {
                                     partition_index: *partition_index,
-                                    partition_error_code: i16::from(ErrorCode::None),
+                                    partition_error_code: i16::from(error_code),
                                 });
                             }
 
@@ -2113,7 +3219,7 @@ impl Storage for DynoStore {
                             })
                         }
 
-                        txn_detail.started_at = Some(SystemTime::now());
+                        txn_detail.started_at = Some(clock.now());
                         txn_detail.state = Some(TxnState::Begin);
 
                         Ok(TxnAddPartitionsResponse::VersionZeroToThree(results))
@@ -2121,8 +3227,44 @@ impl Storage for DynoStore {
                     .await
             }
 
-            TxnAddPartitionsRequest::VersionFourPlus { .. } => {
-                todo!()
+            TxnAddPartitionsRequest::VersionFourPlus { transactions } => {
+                let mut results = vec![];
+
+                for transaction in transactions {
+                    let topics = transaction.topics.unwrap_or_default();
+
+                    let outcome = if transaction.verify_only {
+                        let enrolled = self
+                            .meta
+                            .with(&self.object_store, |meta| {
+                                meta.enrolled(
+                                    transaction.transactional_id.as_str(),
+                                    transaction.producer_id,
+                                    transaction.producer_epoch,
+                                )
+                            })
+                            .await;
+
+                        txn_verify_partitions(&topics, enrolled)
+                    } else {
+                        self.txn_add_partitions(TxnAddPartitionsRequest::VersionZeroToThree {
+                            transaction_id: transaction.transactional_id.clone(),
+                            producer_id: transaction.producer_id,
+                            producer_epoch: transaction.producer_epoch,
+                            topics: topics.clone(),
+                        })
+                        .await
+                        .map(|response| response.zero_to_three().to_vec())
+                    };
+
+                    results.push(txn_add_partitions_outcome(
+                        transaction.transactional_id.as_str(),
+                        &topics,
+                        outcome,
+                    ));
+                }
+
+                Ok(TxnAddPartitionsResponse::VersionFourPlus(results))
             }
         }
     }
@@ -2345,10 +3487,7 @@ impl Storage for DynoStore {
                     meta.overlapping_transactions(transaction_id, producer_id, producer_epoch)?;
                 debug!(?overlaps);
 
-                let mut offsets_to_commit: BTreeMap<
-                    Group,
-                    BTreeMap<Topic, BTreeMap<Partition, TxnCommitOffset>>,
-                > = BTreeMap::new();
+                let mut offsets_to_commit = TxnOffsetsToCommit::new();
 
                 if overlaps.iter().all(|txn_id| txn_id.state.is_prepared()) {
                     let txn_ids = {
@@ -2367,56 +3506,7 @@ impl Storage for DynoStore {
                     };
 
                     for txn_id in txn_ids {
-                        debug!(?txn_id);
-
-                        if let Some(txn) = meta.transactions.get_mut(txn_id.transaction.as_str()) {
-                            if let Some(txn_detail) = txn.epochs.get_mut(&txn_id.producer_epoch) {
-                                debug!(?txn_detail);
-
-                                match txn_detail.state {
-                                    None | Some(TxnState::PrepareCommit) => {
-                                        _ = txn_detail.state.replace(TxnState::Committed);
-                                    }
-
-                                    Some(TxnState::PrepareAbort) => {
-                                        _ = txn_detail.state.replace(TxnState::Aborted);
-                                    }
-
-                                    otherwise => {
-                                        warn!(
-                                            transaction = txn_id.transaction,
-                                            producer = txn_id.producer_id,
-                                            epoch = txn_id.producer_epoch,
-                                            ?otherwise,
-                                        );
-
-                                        continue;
-                                    }
-                                }
-
-                                if txn_id.state == TxnState::PrepareCommit {
-                                    for (group, topics) in txn_detail.offsets.iter() {
-                                        for (topic, partitions) in topics.iter() {
-                                            for (partition, committed_offset) in partitions {
-                                                _ = offsets_to_commit
-                                                    .entry(group.to_owned())
-                                                    .or_default()
-                                                    .entry(topic.to_owned())
-                                                    .or_default()
-                                                    .insert(
-                                                        *partition,
-                                                        committed_offset.to_owned(),
-                                                    );
-                                            }
-                                        }
-                                    }
-                                }
-
-                                txn_detail.produces.clear();
-                                txn_detail.offsets.clear();
-                                _ = txn_detail.started_at.take();
-                            }
-                        }
+                        meta.complete(&txn_id, &mut offsets_to_commit);
                     }
                 }
 
@@ -2426,29 +3516,84 @@ impl Storage for DynoStore {
             .inspect(|outcome| debug!(?outcome))
             .inspect_err(|err| error!(?err))?;
 
-        debug!(?offsets_to_commit);
+        self.commit_txn_offsets(offsets_to_commit)
+            .await
+            .map(|()| ErrorCode::None)
+    }
 
-        for (group, topics) in offsets_to_commit.iter() {
-            let mut offsets = vec![];
+    async fn txn_force_abort(&mut self, transaction_id: &str) -> Result<ErrorCode> {
+        debug!(transaction_id);
 
-            for (topic, partitions) in topics.iter() {
-                for (partition, txn_co) in partitions {
-                    let tp = Topition::new(topic.to_owned(), *partition);
-                    let ocr = OffsetCommitRequest {
-                        offset: txn_co.committed_offset,
-                        leader_epoch: txn_co.leader_epoch,
-                        timestamp: None,
-                        metadata: txn_co.metadata.clone(),
-                    };
+        let Some((producer_id, producer_epoch, state, transaction_timeout_ms)) =
+            self.meta
+                .with(&self.object_store, |meta| {
+                    Ok(meta
+                        .transactions
+                        .get(transaction_id)
+                        .and_then(|transaction| {
+                            transaction.epochs.last_key_value().map(
+                                |(producer_epoch, txn_detail)| {
+                                    (
+                                        transaction.producer,
+                                        *producer_epoch,
+                                        txn_detail.state,
+                                        txn_detail.transaction_timeout_ms,
+                                    )
+                                },
+                            )
+                        }))
+                })
+                .await?
+        else {
+            return Ok(ErrorCode::TransactionalIdNotFound);
+        };
 
-                    offsets.push((tp, ocr));
-                }
+        debug!(
+            transaction_id,
+            producer_id,
+            producer_epoch,
+            ?state,
+            transaction_timeout_ms
+        );
+
+        match state {
+            // rollback of the current epoch happens as part of init producer
+            Some(TxnState::Begin) => (),
+
+            // the outcome is decided with its markers written, waiting only
+            // on overlapping transactions, so it is completed as prepared
+            Some(state @ (TxnState::PrepareCommit | TxnState::PrepareAbort)) => {
+                let txn_id = TxnId {
+                    transaction: transaction_id.into(),
+                    producer_id,
+                    producer_epoch,
+                    state,
+                };
+
+                let offsets_to_commit = self
+                    .meta
+                    .with_mut(&self.object_store, |meta| {
+                        let mut offsets_to_commit = TxnOffsetsToCommit::new();
+                        meta.complete(&txn_id, &mut offsets_to_commit);
+                        Ok(offsets_to_commit)
+                    })
+                    .await?;
+
+                self.commit_txn_offsets(offsets_to_commit).await?;
             }
 
-            _ = self.offset_commit(group, None, &offsets[..]).await?;
+            _otherwise => return Ok(ErrorCode::None),
         }
 
-        Ok(ErrorCode::None)
+        // fencing the current producer epoch
+        self.init_producer(
+            Some(transaction_id),
+            transaction_timeout_ms,
+            Some(-1),
+            Some(-1),
+        )
+        .await
+        .map(|producer| producer.error)
     }
 }
 
@@ -2652,6 +3797,16 @@ where
         self.object_store.list(prefix)
     }
 
+    fn list_with_offset(
+        &self,
+        prefix: Option<&Path>,
+        offset: &Path,
+    ) -> BoxStream<'_, Result<ObjectMeta, object_store::Error>> {
+        debug!(?prefix, %offset);
+
+        self.object_store.list_with_offset(prefix, offset)
+    }
+
     async fn list_with_delimiter(
         &self,
         prefix: Option<&Path>,
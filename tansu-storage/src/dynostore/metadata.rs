@@ -30,7 +30,11 @@ use object_store::{
 use opentelemetry::{KeyValue, metrics::Counter};
 use tracing::debug;
 
-use crate::{Error, dynostore::object_store_error_name};
+use crate::{
+    Error,
+    clock::{Clock, SystemClock},
+    dynostore::object_store_error_name,
+};
 
 use super::METER;
 
@@ -40,8 +44,8 @@ struct CacheEntry {
     tagged_at: SystemTime,
 }
 
-impl From<&PutResult> for CacheEntry {
-    fn from(put_result: &PutResult) -> Self {
+impl CacheEntry {
+    fn put(put_result: &PutResult, now: SystemTime) -> Self {
         debug!(?put_result);
 
         let e_tag = put_result.e_tag.clone();
@@ -49,13 +53,11 @@ impl From<&PutResult> for CacheEntry {
 
         Self {
             version: UpdateVersion { e_tag, version },
-            tagged_at: SystemTime::now(),
+            tagged_at: now,
         }
     }
-}
 
-impl From<&GetResult> for CacheEntry {
-    fn from(get_result: &GetResult) -> Self {
+    fn get(get_result: &GetResult, now: SystemTime) -> Self {
         debug!(?get_result);
 
         let e_tag = get_result.meta.e_tag.clone();
@@ -63,22 +65,27 @@ impl From<&GetResult> for CacheEntry {
 
         Self {
             version: UpdateVersion { e_tag, version },
-            tagged_at: SystemTime::now(),
+            tagged_at: now,
         }
     }
-}
 
-impl CacheEntry {
-    fn hit(&mut self) {
-        self.tagged_at = SystemTime::now();
+    fn hit(&mut self, now: SystemTime) {
+        self.tagged_at = now;
+    }
+
+    fn is_expired(&self, now: SystemTime, retention: Duration) -> bool {
+        !now.duration_since(self.tagged_at)
+            .is_ok_and(|elapsed| elapsed.as_millis() < retention.as_millis())
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Cache<O> {
     entries: Arc<Mutex<HashMap<Path, CacheEntry>>>,
+    swept_at: Arc<Mutex<Option<SystemTime>>>,
     object_store: O,
     retention: Duration,
+    clock: Arc<dyn Clock>,
 }
 
 impl<O> Display for Cache<O> {
@@ -94,14 +101,21 @@ where
 {
     pub fn new(object_store: O, retention: Duration) -> Self {
         let entries = Arc::new(Mutex::new(HashMap::new()));
+        let swept_at = Arc::new(Mutex::new(None));
 
         Self {
             entries,
+            swept_at,
             object_store,
             retention,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    pub fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
     fn into_inner(self) -> O {
         self.object_store
     }
@@ -115,13 +129,27 @@ where
         guard: &mut MutexGuard<'_, HashMap<Path, CacheEntry>>,
         attributes: &[KeyValue],
     ) {
-        let now = SystemTime::now();
+        let now = self.clock.now();
+
+        // sweep at most once per retention period, rather than scanning
+        // every entry on each request
+        let Ok(mut swept_at) = self.swept_at.lock() else {
+            return;
+        };
+
+        if swept_at.is_some_and(|swept_at| {
+            now.duration_since(swept_at)
+                .is_ok_and(|elapsed| elapsed < self.retention)
+        }) {
+            return;
+        }
+
+        *swept_at = Some(now);
 
         let original = guard.deref().len();
-        guard.deref_mut().retain(|_location, entry| {
-            now.duration_since(entry.tagged_at)
-                .is_ok_and(|elapsed| elapsed.as_millis() < self.retention.as_millis())
-        });
+        guard
+            .deref_mut()
+            .retain(|_location, entry| !entry.is_expired(now, self.retention));
 
         let mut a = vec![KeyValue::new("outcome", "evict")];
         a.extend_from_slice(attributes);
@@ -186,7 +214,7 @@ where
                 if let Ok(mut guard) = self.entries.lock() {
                     self.evict(&mut guard, &[method.clone()]);
 
-                    let replacement = CacheEntry::from(put_result);
+                    let replacement = CacheEntry::put(put_result, self.clock.now());
 
                     let outcome = match guard
                         .deref_mut()
@@ -266,7 +294,13 @@ where
         if let Ok(mut guard) = self.entries.lock() {
             self.evict(&mut guard, &[method.clone()]);
 
-            if let Some(entry) = guard.deref_mut().get_mut(location) {
+            let now = self.clock.now();
+
+            if let Some(entry) = guard
+                .deref_mut()
+                .get_mut(location)
+                .filter(|entry| !entry.is_expired(now, self.retention))
+            {
                 debug!(%location, ?entry);
 
                 if let Some(ref cached_e_tag) = entry.version.e_tag {
@@ -276,7 +310,7 @@ where
                         debug!(%location, cached_e_tag, presented);
 
                         if cached_e_tag == presented {
-                            entry.hit();
+                            entry.hit(now);
 
                             let outcome = "hit";
 
@@ -323,7 +357,7 @@ where
                 if let Ok(mut guard) = self.entries.lock() {
                     debug!(%location, e_tag, version);
 
-                    let replacement = CacheEntry::from(get_result);
+                    let replacement = CacheEntry::get(get_result, self.clock.now());
 
                     let outcome = match guard
                         .deref_mut()
@@ -398,6 +432,16 @@ where
         self.object_store.list(prefix)
     }
 
+    fn list_with_offset(
+        &self,
+        prefix: Option<&Path>,
+        offset: &Path,
+    ) -> BoxStream<'_, Result<ObjectMeta, object_store::Error>> {
+        debug!(?prefix, %offset);
+        REQUESTS.add(1, &[KeyValue::new("method", "list_with_offset")]);
+        self.object_store.list_with_offset(prefix, offset)
+    }
+
     async fn list_with_delimiter(
         &self,
         prefix: Option<&Path>,
@@ -458,11 +502,10 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::Result;
+    use crate::{Result, clock::ManualClock};
     use bytes::Bytes;
     use object_store::memory::InMemory;
     use serde::{Deserialize, Serialize};
-    use tokio::time::sleep;
     use tracing::subscriber::DefaultGuard;
     use tracing_subscriber::EnvFilter;
 
@@ -665,7 +708,8 @@ mod tests {
             .await?;
 
         let duration = Duration::from_millis(100);
-        let cache = Cache::new(object_store, duration);
+        let clock = ManualClock::default();
+        let cache = Cache::new(object_store, duration).clock(Arc::new(clock.clone()));
 
         assert_eq!(0, cache.inner().get_opts()?);
 
@@ -683,7 +727,7 @@ mod tests {
         ));
         assert_eq!(1, cache.inner().get_opts()?);
 
-        sleep(duration).await;
+        clock.advance(duration);
 
         assert!(matches!(
             cache.get_opts(&path, options).await,
@@ -694,6 +738,43 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn put_sweep() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let duration = Duration::from_millis(100);
+        let clock = ManualClock::default();
+        let cache = Cache::new(InMemory::new(), duration).clock(Arc::new(clock.clone()));
+
+        for id in 0..10 {
+            _ = cache
+                .put(
+                    &Path::from(format!("/abc/{id}.json")),
+                    serde_json::to_vec(&X(id))
+                        .map(Bytes::from)
+                        .map(PutPayload::from)?,
+                )
+                .await?;
+        }
+
+        assert_eq!(10, cache.entries.lock()?.len());
+
+        clock.advance(duration);
+
+        _ = cache
+            .put(
+                &Path::from("/abc/10.json"),
+                serde_json::to_vec(&X(10))
+                    .map(Bytes::from)
+                    .map(PutPayload::from)?,
+            )
+            .await?;
+
+        assert_eq!(1, cache.entries.lock()?.len());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn put_get() -> Result<()> {
         let _guard = init_tracing()?;
@@ -0,0 +1,616 @@
+// Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Group metadata and committed offsets in the key/value layout used by
+//! the `__consumer_offsets` topic, so that coordinator state can be exported
+//! from one instance (or backend) and imported into another.
+
+use std::{collections::BTreeMap, time::SystemTime};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tansu_kafka_sans_io::{
+    ErrorCode, join_group_response::JoinGroupResponseMember, to_system_time, to_timestamp,
+};
+use tracing::debug;
+
+use crate::{
+    Error, GroupDetail, GroupDetailResponse, GroupMember, GroupState, OffsetCommitRequest, Result,
+    Storage, Topition, UpdateError,
+};
+
+const OFFSET_COMMIT_KEY_VERSION: i16 = 1;
+const OFFSET_COMMIT_VALUE_VERSION: i16 = 3;
+const GROUP_METADATA_KEY_VERSION: i16 = 2;
+const GROUP_METADATA_VALUE_VERSION: i16 = 3;
+
+/// A key/value record of exported group state
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct GroupStateRecord {
+    pub key: Bytes,
+    pub value: Bytes,
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum GroupRecord {
+    OffsetCommit {
+        group_id: String,
+        topition: Topition,
+        offset: OffsetCommitRequest,
+    },
+
+    GroupMetadata {
+        group_id: String,
+        detail: GroupDetail,
+        current_state_timestamp: SystemTime,
+    },
+}
+
+fn put_string(buf: &mut BytesMut, value: &str) -> Result<()> {
+    buf.put_i16(i16::try_from(value.len())?);
+    buf.put_slice(value.as_bytes());
+    Ok(())
+}
+
+fn put_nullable_string(buf: &mut BytesMut, value: Option<&str>) -> Result<()> {
+    if let Some(value) = value {
+        put_string(buf, value)
+    } else {
+        buf.put_i16(-1);
+        Ok(())
+    }
+}
+
+fn put_bytes(buf: &mut BytesMut, value: &[u8]) -> Result<()> {
+    buf.put_i32(i32::try_from(value.len())?);
+    buf.put_slice(value);
+    Ok(())
+}
+
+// reads from a key or value, failing rather than panicking when truncated
+struct Reader(Bytes);
+
+impl Reader {
+    fn remaining(&mut self, n: usize) -> Result<()> {
+        if self.0.remaining() < n {
+            Err(Error::Message(format!(
+                "group state truncated, {} of {n} bytes remaining",
+                self.0.remaining()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn i16(&mut self) -> Result<i16> {
+        self.remaining(size_of::<i16>()).map(|()| self.0.get_i16())
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        self.remaining(size_of::<i32>()).map(|()| self.0.get_i32())
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        self.remaining(size_of::<i64>()).map(|()| self.0.get_i64())
+    }
+
+    fn nullable_string(&mut self) -> Result<Option<String>> {
+        let length = self.i16()?;
+
+        if length < 0 {
+            return Ok(None);
+        }
+
+        let length = usize::try_from(length)?;
+        self.remaining(length)?;
+
+        String::from_utf8(self.0.split_to(length).to_vec())
+            .map(Some)
+            .map_err(|error| Error::Message(error.to_string()))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.nullable_string()?
+            .ok_or(Error::Message(String::from("unexpected null string")))
+    }
+
+    fn bytes(&mut self) -> Result<Bytes> {
+        let length = usize::try_from(self.i32()?.max(0))?;
+        self.remaining(length)?;
+        Ok(self.0.split_to(length))
+    }
+
+    fn version(&mut self, expected: i16) -> Result<()> {
+        let version = self.i16()?;
+
+        if version == expected {
+            Ok(())
+        } else {
+            Err(Error::Message(format!(
+                "unsupported group state version: {version}, expected: {expected}"
+            )))
+        }
+    }
+}
+
+impl GroupRecord {
+    pub fn group_id(&self) -> &str {
+        match self {
+            Self::OffsetCommit { group_id, .. } | Self::GroupMetadata { group_id, .. } => group_id,
+        }
+    }
+
+    pub fn encode(&self) -> Result<GroupStateRecord> {
+        let mut key = BytesMut::new();
+        let mut value = BytesMut::new();
+
+        match self {
+            Self::OffsetCommit {
+                group_id,
+                topition,
+                offset,
+            } => {
+                key.put_i16(OFFSET_COMMIT_KEY_VERSION);
+                put_string(&mut key, group_id)?;
+                put_string(&mut key, topition.topic())?;
+                key.put_i32(topition.partition());
+
+                value.put_i16(OFFSET_COMMIT_VALUE_VERSION);
+                value.put_i64(offset.offset);
+                value.put_i32(offset.leader_epoch.unwrap_or(-1));
+                put_string(&mut value, offset.metadata.as_deref().unwrap_or_default())?;
+                value.put_i64(offset.timestamp.map_or(Ok(-1), to_timestamp)?);
+            }
+
+            Self::GroupMetadata {
+                group_id,
+                detail,
+                current_state_timestamp,
+            } => {
+                key.put_i16(GROUP_METADATA_KEY_VERSION);
+                put_string(&mut key, group_id)?;
+
+                let assignments = match detail.state {
+                    GroupState::Formed {
+                        ref assignments, ..
+                    } => Some(assignments),
+                    GroupState::Forming { .. } => None,
+                };
+
+                value.put_i16(GROUP_METADATA_VALUE_VERSION);
+                put_string(
+                    &mut value,
+                    detail.state.protocol_type().as_deref().unwrap_or_default(),
+                )?;
+                value.put_i32(detail.generation_id);
+                put_nullable_string(&mut value, detail.state.protocol_name().as_deref())?;
+                put_nullable_string(&mut value, detail.state.leader().as_deref())?;
+                value.put_i64(to_timestamp(*current_state_timestamp)?);
+
+                value.put_i32(i32::try_from(detail.members.len())?);
+
+                for (member_id, member) in &detail.members {
+                    put_string(&mut value, member_id)?;
+                    put_nullable_string(
+                        &mut value,
+                        member.join_response.group_instance_id.as_deref(),
+                    )?;
+                    put_string(&mut value, member.client_id.as_deref().unwrap_or_default())?;
+                    put_string(
+                        &mut value,
+                        member.client_host.as_deref().unwrap_or_default(),
+                    )?;
+                    value.put_i32(detail.rebalance_timeout_ms.unwrap_or(-1));
+                    value.put_i32(detail.session_timeout_ms);
+                    put_bytes(&mut value, &member.join_response.metadata)?;
+                    put_bytes(
+                        &mut value,
+                        assignments
+                            .and_then(|assignments| assignments.get(member_id))
+                            .map_or(&[][..], |assignment| &assignment[..]),
+                    )?;
+                }
+            }
+        }
+
+        Ok(GroupStateRecord {
+            key: key.freeze(),
+            value: value.freeze(),
+        })
+    }
+
+    pub fn decode(record: &GroupStateRecord) -> Result<Self> {
+        let mut key = Reader(record.key.clone());
+        let mut value = Reader(record.value.clone());
+
+        match key.i16()? {
+            OFFSET_COMMIT_KEY_VERSION => {
+                let group_id = key.string()?;
+                let topic = key.string()?;
+                let partition = key.i32()?;
+
+                value.version(OFFSET_COMMIT_VALUE_VERSION)?;
+                let offset = value.i64()?;
+                let leader_epoch = Some(value.i32()?).filter(|leader_epoch| *leader_epoch >= 0);
+                let metadata = value.string()?;
+                let timestamp = Some(value.i64()?)
+                    .filter(|timestamp| *timestamp >= 0)
+                    .map(to_system_time)
+                    .transpose()?;
+
+                Ok(Self::OffsetCommit {
+                    group_id,
+                    topition: Topition::new(topic, partition),
+                    offset: OffsetCommitRequest {
+                        offset,
+                        leader_epoch,
+                        timestamp,
+                        metadata: Some(metadata),
+                    },
+                })
+            }
+
+            GROUP_METADATA_KEY_VERSION => {
+                let group_id = key.string()?;
+
+                value.version(GROUP_METADATA_VALUE_VERSION)?;
+                let protocol_type = value.string()?;
+                let generation_id = value.i32()?;
+                let protocol_name = value.nullable_string()?;
+                let leader = value.nullable_string()?;
+                let current_state_timestamp = to_system_time(value.i64()?)?;
+
+                let mut detail = GroupDetail {
+                    generation_id,
+                    inception: current_state_timestamp,
+                    ..Default::default()
+                };
+
+                let mut assignments = BTreeMap::new();
+
+                for _ in 0..value.i32()?.max(0) {
+                    let member_id = value.string()?;
+                    let group_instance_id = value.nullable_string()?;
+                    let client_id = value.string()?;
+                    let client_host = value.string()?;
+                    let rebalance_timeout_ms = value.i32()?;
+                    let session_timeout_ms = value.i32()?;
+                    let metadata = value.bytes()?;
+                    let assignment = value.bytes()?;
+
+                    detail.rebalance_timeout_ms =
+                        Some(rebalance_timeout_ms).filter(|timeout| *timeout >= 0);
+                    detail.session_timeout_ms = session_timeout_ms;
+
+                    _ = assignments.insert(member_id.clone(), assignment);
+
+                    // contact is from the export, a member that does not
+                    // heartbeat within its session timeout is expired
+                    _ = detail.members.insert(
+                        member_id.clone(),
+                        GroupMember {
+                            join_response: JoinGroupResponseMember {
+                                member_id,
+                                group_instance_id,
+                                metadata,
+                            },
+                            last_contact: Some(current_state_timestamp),
+                            client_id: Some(client_id),
+                            client_host: Some(client_host),
+                            ..Default::default()
+                        },
+                    );
+                }
+
+                detail.state = match (protocol_name, leader) {
+                    (Some(protocol_name), Some(leader))
+                        if !detail.members.is_empty()
+                            && assignments
+                                .values()
+                                .all(|assignment| !assignment.is_empty()) =>
+                    {
+                        GroupState::Formed {
+                            protocol_type,
+                            protocol_name,
+                            leader,
+                            assignments,
+                        }
+                    }
+
+                    (protocol_name, leader) => GroupState::Forming {
+                        protocol_type: Some(protocol_type).filter(|name| !name.is_empty()),
+                        protocol_name,
+                        leader,
+                    },
+                };
+
+                Ok(Self::GroupMetadata {
+                    group_id,
+                    detail,
+                    current_state_timestamp,
+                })
+            }
+
+            version => Err(Error::Message(format!(
+                "unsupported group state key version: {version}"
+            ))),
+        }
+    }
+}
+
+pub(crate) async fn export<S>(storage: &mut S, group_id: &str) -> Result<Vec<GroupStateRecord>>
+where
+    S: Storage,
+{
+    let mut records = vec![];
+
+    for named in storage
+        .describe_groups(Some(&[group_id.to_owned()]), false)
+        .await?
+    {
+        match named.response {
+            // a group that has never formed only has committed offsets
+            GroupDetailResponse::Found(detail)
+                if detail.members.is_empty() && detail.generation_id < 0 => {}
+
+            GroupDetailResponse::Found(detail) => {
+                let current_state_timestamp = detail.inception;
+
+                records.push(
+                    GroupRecord::GroupMetadata {
+                        group_id: group_id.to_owned(),
+                        detail,
+                        current_state_timestamp,
+                    }
+                    .encode()?,
+                )
+            }
+
+            GroupDetailResponse::ErrorCode(error_code) => return Err(Error::Api(error_code)),
+        }
+    }
+
+    for (topition, offset) in storage.committed_offsets(group_id).await? {
+        records.push(
+            GroupRecord::OffsetCommit {
+                group_id: group_id.to_owned(),
+                topition,
+                offset,
+            }
+            .encode()?,
+        );
+    }
+
+    debug!(group_id, records = records.len());
+    Ok(records)
+}
+
+pub(crate) async fn import<S>(storage: &mut S, records: &[GroupStateRecord]) -> Result<()>
+where
+    S: Storage,
+{
+    for record in records {
+        match GroupRecord::decode(record)? {
+            GroupRecord::OffsetCommit {
+                group_id,
+                topition,
+                offset,
+            } => {
+                let offsets = [(topition, offset)];
+
+                for (topition, error_code) in
+                    storage.offset_commit(&group_id, None, &offsets).await?
+                {
+                    if error_code != ErrorCode::None {
+                        debug!(group_id, ?topition, ?error_code);
+                        return Err(Error::Api(error_code));
+                    }
+                }
+            }
+
+            GroupRecord::GroupMetadata {
+                group_id, detail, ..
+            } => {
+                // an existing group is replaced by the imported group
+                let mut version = None;
+
+                loop {
+                    match storage
+                        .update_group(&group_id, detail.clone(), version)
+                        .await
+                    {
+                        Ok(_) => break,
+
+                        Err(UpdateError::Outdated {
+                            version: current, ..
+                        }) => version = Some(current),
+
+                        Err(UpdateError::Error(error)) => return Err(error),
+
+                        Err(otherwise) => {
+                            return Err(Error::Message(format!("{otherwise:?}")));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynostore::DynoStore;
+    use object_store::memory::InMemory;
+    use std::time::Duration;
+    use tansu_kafka_sans_io::create_topics_request::CreatableTopic;
+
+    async fn storage(cluster: &str, topic: &str) -> Result<DynoStore> {
+        let mut storage = DynoStore::new(cluster, 111, InMemory::new());
+
+        _ = storage
+            .create_topic(
+                CreatableTopic {
+                    name: topic.into(),
+                    num_partitions: 3,
+                    replication_factor: 0,
+                    assignments: Some([].into()),
+                    configs: Some([].into()),
+                },
+                false,
+            )
+            .await?;
+
+        Ok(storage)
+    }
+
+    #[tokio::test]
+    async fn round_trip() -> Result<()> {
+        let group_id = "test-consumer-group";
+        let topic = "pqr";
+
+        let mut exporter = storage("abc", topic).await?;
+
+        let member = GroupMember {
+            join_response: JoinGroupResponseMember {
+                member_id: "console-consumer-a".into(),
+                group_instance_id: Some("instance-a".into()),
+                metadata: Bytes::from_static(b"range_meta"),
+            },
+            last_contact: Some(SystemTime::now()),
+            client_id: Some("console-consumer".into()),
+            client_host: Some("/127.0.0.1".into()),
+            ..Default::default()
+        };
+
+        let detail = GroupDetail {
+            session_timeout_ms: 30_000,
+            rebalance_timeout_ms: Some(300_000),
+            members: [("console-consumer-a".into(), member)].into(),
+            generation_id: 3,
+            state: GroupState::Formed {
+                protocol_type: "consumer".into(),
+                protocol_name: "range".into(),
+                leader: "console-consumer-a".into(),
+                assignments: [(
+                    "console-consumer-a".into(),
+                    Bytes::from_static(b"assignment"),
+                )]
+                .into(),
+            },
+            ..Default::default()
+        };
+
+        _ = exporter
+            .update_group(group_id, detail.clone(), None)
+            .await
+            .map_err(|error| Error::Message(format!("{error:?}")))?;
+
+        let offsets = [
+            (
+                Topition::new(topic, 0),
+                OffsetCommitRequest {
+                    offset: 12321,
+                    leader_epoch: Some(7),
+                    timestamp: Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000)),
+                    metadata: Some("checkpoint-a".into()),
+                },
+            ),
+            (
+                Topition::new(topic, 2),
+                OffsetCommitRequest {
+                    offset: 32123,
+                    leader_epoch: None,
+                    timestamp: Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_500)),
+                    metadata: Some("".into()),
+                },
+            ),
+        ];
+
+        _ = exporter.offset_commit(group_id, None, &offsets).await?;
+
+        let records = exporter.export_group_state(group_id).await?;
+        assert_eq!(3, records.len());
+
+        let mut importer = storage("def", topic).await?;
+        importer.import_group_state(&records).await?;
+
+        assert_eq!(
+            exporter.committed_offsets(group_id).await?,
+            importer.committed_offsets(group_id).await?
+        );
+
+        let imported = importer
+            .describe_groups(Some(&[group_id.into()]), false)
+            .await?;
+        assert_eq!(1, imported.len());
+
+        let GroupDetailResponse::Found(ref imported) = imported[0].response else {
+            panic!("group not imported: {imported:?}")
+        };
+
+        assert_eq!(detail.generation_id, imported.generation_id);
+        assert_eq!(detail.session_timeout_ms, imported.session_timeout_ms);
+        assert_eq!(detail.rebalance_timeout_ms, imported.rebalance_timeout_ms);
+        assert_eq!(detail.state, imported.state);
+        assert_eq!(
+            detail
+                .members
+                .iter()
+                .map(|(member_id, member)| (
+                    member_id,
+                    &member.join_response,
+                    &member.client_id,
+                    &member.client_host
+                ))
+                .collect::<Vec<_>>(),
+            imported
+                .members
+                .iter()
+                .map(|(member_id, member)| (
+                    member_id,
+                    &member.join_response,
+                    &member.client_id,
+                    &member.client_host
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        // importing again replaces the existing group
+        importer.import_group_state(&records).await?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_version() {
+        assert!(
+            GroupRecord::decode(&GroupStateRecord {
+                key: Bytes::from_static(&[0, 9]),
+                value: Bytes::new(),
+            })
+            .is_err()
+        );
+
+        assert!(
+            GroupRecord::decode(&GroupStateRecord {
+                key: Bytes::from_static(&[0, 2, 0, 1]),
+                value: Bytes::new(),
+            })
+            .is_err()
+        );
+    }
+}
@@ -14,9 +14,16 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use async_trait::async_trait;
+use base64::prelude::*;
 use bytes::Bytes;
+use clock::Clock;
 use dynostore::DynoStore;
 use glob::{GlobError, PatternError};
+use group_state::GroupStateRecord;
+use object_store::{
+    aws::{AmazonS3Builder, S3ConditionalPut},
+    memory::InMemory,
+};
 use opentelemetry::{
     InstrumentationScope, KeyValue, global,
     metrics::{Counter, Meter},
@@ -27,22 +34,26 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
     array::TryFromSliceError,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     ffi::OsString,
     fmt::{self, Debug, Display, Formatter},
     fs::DirEntry,
     io,
+    marker::PhantomData,
     num::{ParseIntError, TryFromIntError},
+    ops::Range,
     path::PathBuf,
     result,
     str::FromStr,
-    sync::{LazyLock, PoisonError},
+    sync::{Arc, LazyLock, PoisonError},
     time::{Duration, SystemTime, SystemTimeError},
 };
 use tansu_kafka_sans_io::{
-    Body, ConfigResource, ErrorCode, IsolationLevel,
+    Body, Compression, ConfigResource, ErrorCode, IsolationLevel,
     add_partitions_to_txn_request::{AddPartitionsToTxnTopic, AddPartitionsToTxnTransaction},
-    add_partitions_to_txn_response::{AddPartitionsToTxnResult, AddPartitionsToTxnTopicResult},
+    add_partitions_to_txn_response::{
+        AddPartitionsToTxnPartitionResult, AddPartitionsToTxnResult, AddPartitionsToTxnTopicResult,
+    },
     consumer_group_describe_response,
     create_topics_request::CreatableTopic,
     delete_groups_response::DeletableGroupResult,
@@ -62,16 +73,20 @@ use tansu_kafka_sans_io::{
     metadata_request::MetadataRequestTopic,
     metadata_response::{MetadataResponseBroker, MetadataResponseTopic},
     offset_commit_request::OffsetCommitRequestPartition,
-    record::deflated,
+    record::deflated::{self, CompressionLevel},
     to_system_time, to_timestamp,
     txn_offset_commit_request::TxnOffsetCommitRequestTopic,
     txn_offset_commit_response::TxnOffsetCommitResponseTopic,
 };
-use tracing::{Instrument, debug, debug_span};
+use tansu_schema_registry::Registry;
+use tracing::{Instrument, debug, debug_span, error};
 use tracing_subscriber::filter::ParseError;
+use url::Url;
 use uuid::Uuid;
 
+pub mod clock;
 pub mod dynostore;
+pub mod group_state;
 pub mod index;
 pub mod os;
 pub mod pg;
@@ -79,6 +94,12 @@ pub mod segment;
 
 pub const NULL_TOPIC_ID: [u8; 16] = [0; 16];
 
+pub const LEADER_EPOCH: i32 = 0;
+
+// a broker is live while its registration has been refreshed within this
+// period, with only live brokers described or eligible to be controller
+pub const BROKER_LIVENESS: Duration = Duration::from_secs(30);
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("api")]
@@ -168,6 +189,9 @@ pub enum Error {
     #[error("postgres")]
     TokioPostgres(#[from] tokio_postgres::error::Error),
 
+    #[error("topic limit: {0}")]
+    TopicLimit(String),
+
     #[error("try from int: {0}")]
     TryFromInt(#[from] TryFromIntError),
 
@@ -177,11 +201,26 @@ pub enum Error {
     #[error("body: {0:?}")]
     UnexpectedBody(Body),
 
+    #[error("unsupported storage url: {0}")]
+    UnsupportedStorageUrl(Url),
+
     #[error("url: {0}")]
     Url(#[from] url::ParseError),
 
     #[error("state: {0}")]
     UnknownTxnState(String),
+
+    #[error("cleanup policy: {0}")]
+    UnknownCleanupPolicy(String),
+
+    #[error("zstd dictionary: {0}")]
+    InvalidZstdDictionary(String),
+
+    #[error("compaction: {0}")]
+    InvalidCompaction(String),
+
+    #[error("unknown zstd dictionary: {0}")]
+    UnknownZstdDictionary(u32),
 }
 
 impl<T> From<PoisonError<T>> for Error {
@@ -322,6 +361,22 @@ pub enum ListOffsetRequest {
     Earliest,
     Latest,
     Timestamp(SystemTime),
+    EarliestLocal,
+    LatestTiered,
+}
+
+impl ListOffsetRequest {
+    // offsets before tiered have been moved to object storage, with the
+    // remainder being local
+    pub(crate) fn tiered(&self, low: i64, tiered: Option<i64>) -> Option<i64> {
+        let tiered = tiered.filter(|tiered| *tiered > low);
+
+        match self {
+            Self::EarliestLocal => Some(tiered.unwrap_or(low)),
+            Self::LatestTiered => Some(tiered.map_or(-1, |tiered| tiered - 1)),
+            _otherwise => None,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -364,6 +419,8 @@ impl TryFrom<ListOffsetRequest> for i64 {
         match value {
             ListOffsetRequest::Earliest => Ok(-2),
             ListOffsetRequest::Latest => Ok(-1),
+            ListOffsetRequest::EarliestLocal => Ok(-4),
+            ListOffsetRequest::LatestTiered => Ok(-5),
             ListOffsetRequest::Timestamp(timestamp) => to_timestamp(timestamp).map_err(Into::into),
         }
     }
@@ -376,6 +433,8 @@ impl TryFrom<i64> for ListOffsetRequest {
         match value {
             -2 => Ok(ListOffsetRequest::Earliest),
             -1 => Ok(ListOffsetRequest::Latest),
+            -4 => Ok(ListOffsetRequest::EarliestLocal),
+            -5 => Ok(ListOffsetRequest::LatestTiered),
             timestamp => to_system_time(timestamp)
                 .map(ListOffsetRequest::Timestamp)
                 .map_err(Into::into),
@@ -383,6 +442,125 @@ impl TryFrom<i64> for ListOffsetRequest {
     }
 }
 
+// the number of recent batches from an idempotent producer that are
+// remembered for each partition, a retry of one of these batches is a
+// duplicate, while a retry of an older batch is out of order
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SequenceWindow(usize);
+
+impl Default for SequenceWindow {
+    fn default() -> Self {
+        Self(5)
+    }
+}
+
+impl SequenceWindow {
+    pub fn new(batches: usize) -> Self {
+        Self(batches)
+    }
+
+    pub fn batches(&self) -> usize {
+        self.0
+    }
+
+    // the error for a batch with an earlier base sequence than expected
+    pub(crate) fn retry(&self, recent: &[i32], base_sequence: i32) -> Error {
+        if recent.contains(&base_sequence) {
+            Error::Api(ErrorCode::DuplicateSequenceNumber)
+        } else {
+            Error::Api(ErrorCode::OutOfOrderSequenceNumber)
+        }
+    }
+
+    // remember an accepted batch, forgetting those outside the window
+    pub(crate) fn accepted(&self, recent: &mut Vec<i32>, base_sequence: i32) {
+        recent.push(base_sequence);
+
+        if recent.len() > self.0 {
+            _ = recent.drain(..recent.len() - self.0);
+        }
+    }
+}
+
+// caps on the transactions open in the cluster and on the partitions
+// enrolled in any one transaction, unlimited when absent
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TxnLimit {
+    max_open: Option<usize>,
+    max_partitions: Option<usize>,
+}
+
+impl TxnLimit {
+    pub fn max_open(self, max_open: Option<usize>) -> Self {
+        Self { max_open, ..self }
+    }
+
+    pub fn max_partitions(self, max_partitions: Option<usize>) -> Self {
+        Self {
+            max_partitions,
+            ..self
+        }
+    }
+
+    pub(crate) fn is_unlimited(&self) -> bool {
+        self.max_open.is_none() && self.max_partitions.is_none()
+    }
+
+    // the error for a transaction enrolling partitions while other
+    // transactions are open
+    pub(crate) fn open(&self, others: usize) -> Option<ErrorCode> {
+        self.max_open
+            .filter(|max_open| others >= *max_open)
+            .map(|_| ErrorCode::ConcurrentTransactions)
+    }
+
+    // the error for enrolling another partition into a transaction
+    pub(crate) fn enrol(&self, enrolled: usize) -> Option<ErrorCode> {
+        self.max_partitions
+            .filter(|max_partitions| enrolled >= *max_partitions)
+            .map(|_| ErrorCode::InvalidTxnState)
+    }
+}
+
+// the connection pool of a storage engine backed by a database: waiting
+// indefinitely for a connection and keeping idle connections when absent
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ConnectionPool {
+    max_size: usize,
+    acquire_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+}
+
+impl Default for ConnectionPool {
+    fn default() -> Self {
+        Self {
+            max_size: 16,
+            acquire_timeout: None,
+            idle_timeout: None,
+        }
+    }
+}
+
+impl ConnectionPool {
+    pub fn max_size(self, max_size: usize) -> Self {
+        Self { max_size, ..self }
+    }
+
+    pub fn acquire_timeout(self, acquire_timeout: Option<Duration>) -> Self {
+        Self {
+            acquire_timeout,
+            ..self
+        }
+    }
+
+    pub fn idle_timeout(self, idle_timeout: Option<Duration>) -> Self {
+        Self {
+            idle_timeout,
+            ..self
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct OffsetCommitRequest {
     offset: i64,
@@ -545,6 +723,69 @@ impl MetadataResponse {
     pub fn topics(&self) -> &[MetadataResponseTopic] {
         self.topics.as_ref()
     }
+
+    pub fn into_topics(self) -> Vec<MetadataResponseTopic> {
+        self.topics
+    }
+
+    /// The requested topics from this (snapshot) response, all topics when
+    /// none are requested, with an error for those that are unknown
+    pub fn select(&self, topics: Option<&[TopicId]>) -> Self {
+        let topics = match topics {
+            Some(topics) if !topics.is_empty() => {
+                // indexed once by name and id, rather than searched per topic
+                let by_name = self
+                    .topics
+                    .iter()
+                    .filter_map(|topic| topic.name.as_deref().map(|name| (name, topic)))
+                    .collect::<BTreeMap<_, _>>();
+
+                let by_id = self
+                    .topics
+                    .iter()
+                    .filter_map(|topic| topic.topic_id.map(|id| (id, topic)))
+                    .collect::<BTreeMap<_, _>>();
+
+                topics
+                    .iter()
+                    .map(|topic| {
+                        match topic {
+                            TopicId::Name(name) => by_name.get(name.as_str()),
+                            TopicId::Id(id) => by_id.get(&id.into_bytes()),
+                        }
+                        .map(|found| (*found).clone())
+                        .unwrap_or_else(|| MetadataResponseTopic {
+                            error_code: match topic {
+                                TopicId::Name(_) => ErrorCode::UnknownTopicOrPartition,
+                                TopicId::Id(_) => ErrorCode::UnknownTopicId,
+                            }
+                            .into(),
+                            name: match topic {
+                                TopicId::Name(name) => Some(name.into()),
+                                TopicId::Id(_) => None,
+                            },
+                            topic_id: Some(match topic {
+                                TopicId::Name(_) => NULL_TOPIC_ID,
+                                TopicId::Id(id) => id.into_bytes(),
+                            }),
+                            is_internal: Some(false),
+                            partitions: Some([].into()),
+                            topic_authorized_operations: Some(-2147483648),
+                        })
+                    })
+                    .collect()
+            }
+
+            _ => self.topics.clone(),
+        };
+
+        Self {
+            cluster: self.cluster.clone(),
+            controller: self.controller,
+            brokers: self.brokers.clone(),
+            topics,
+        }
+    }
 }
 
 #[derive(
@@ -574,6 +815,16 @@ impl OffsetStage {
 pub struct GroupMember {
     pub join_response: JoinGroupResponseMember,
     pub last_contact: Option<SystemTime>,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub client_host: Option<String>,
+    #[serde(default)]
+    pub awaiting_sync: Option<SystemTime>,
+    #[serde(default)]
+    pub member_epoch: Option<i32>,
+    #[serde(default)]
+    pub subscribed_topic_names: Option<Vec<String>>,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
@@ -659,6 +910,37 @@ impl Display for ConsumerGroupState {
     }
 }
 
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum GroupType {
+    #[default]
+    Classic,
+    Consumer,
+}
+
+impl Display for GroupType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Classic => f.write_str("classic"),
+            Self::Consumer => f.write_str("consumer"),
+        }
+    }
+}
+
+impl GroupType {
+    // whether this type is in the types filter of a list groups request,
+    // ignoring case, with an absent or empty filter matching any type
+    pub fn is_listed(&self, types_filter: Option<&[String]>) -> bool {
+        types_filter.is_none_or(|types_filter| {
+            types_filter.is_empty()
+                || types_filter
+                    .iter()
+                    .any(|group_type| group_type.eq_ignore_ascii_case(&self.to_string()))
+        })
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct GroupDetail {
     pub session_timeout_ms: i32,
@@ -668,6 +950,8 @@ pub struct GroupDetail {
     pub skip_assignment: Option<bool>,
     pub inception: SystemTime,
     pub state: GroupState,
+    #[serde(default)]
+    pub group_type: GroupType,
 }
 
 impl Default for GroupDetail {
@@ -678,8 +962,10 @@ impl Default for GroupDetail {
             members: BTreeMap::new(),
             generation_id: -1,
             skip_assignment: Some(false),
-            inception: SystemTime::now(),
+            // set by the coordinator from its clock
+            inception: SystemTime::UNIX_EPOCH,
             state: GroupState::default(),
+            group_type: GroupType::default(),
         }
     }
 }
@@ -812,17 +1098,24 @@ impl From<&NamedGroupDetail> for describe_groups_response::DescribedGroup {
             } => {
                 let group_state = ConsumerGroupState::from(group_detail).to_string();
 
+                let assignments = group_detail.state.assignments();
+
                 let members = group_detail
                     .members
-                    .keys()
-                    .map(|member_id| describe_groups_response::DescribedGroupMember {
-                        member_id: member_id.into(),
-                        group_instance_id: None,
-                        client_id: "".into(),
-                        client_host: "".into(),
-                        member_metadata: Bytes::new(),
-                        member_assignment: Bytes::new(),
-                    })
+                    .iter()
+                    .map(
+                        |(member_id, member)| describe_groups_response::DescribedGroupMember {
+                            member_id: member_id.into(),
+                            group_instance_id: member.join_response.group_instance_id.clone(),
+                            client_id: member.client_id.clone().unwrap_or_default(),
+                            client_host: member.client_host.clone().unwrap_or_default(),
+                            member_metadata: member.join_response.metadata.clone(),
+                            member_assignment: assignments
+                                .get(member_id)
+                                .cloned()
+                                .unwrap_or_default(),
+                        },
+                    )
                     .collect::<Vec<_>>();
 
                 Self {
@@ -830,7 +1123,7 @@ impl From<&NamedGroupDetail> for describe_groups_response::DescribedGroup {
                     group_id: name.clone(),
                     group_state,
                     protocol_type: group_detail.state.protocol_type().unwrap_or_default(),
-                    protocol_data: "".into(),
+                    protocol_data: group_detail.state.protocol_name().unwrap_or_default(),
                     members: Some(members),
                     authorized_operations: Some(-1),
                 }
@@ -888,6 +1181,21 @@ impl Default for ProducerIdResponse {
     }
 }
 
+// a transactional producer re-initializing with its known id and epoch is
+// bumped as if it were new, fencing the previous instance. The id and
+// epoch must match the current producer of the transaction
+pub(crate) fn reinitialized(
+    current: Option<(i64, i16)>,
+    producer_id: i64,
+    producer_epoch: i16,
+) -> ErrorCode {
+    match current {
+        Some((id, epoch)) if id == producer_id && epoch == producer_epoch => ErrorCode::None,
+        Some((id, _)) if id == producer_id => ErrorCode::ProducerFenced,
+        _otherwise => ErrorCode::InvalidProducerIdMapping,
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum TxnAddPartitionsRequest {
     VersionZeroToThree {
@@ -955,6 +1263,79 @@ impl TxnAddPartitionsResponse {
     }
 }
 
+// a verify only AddPartitionsToTxn checks that each partition is already
+// part of the transaction, without adding it
+pub(crate) fn txn_verify_partitions(
+    topics: &[AddPartitionsToTxnTopic],
+    enrolled: Result<BTreeSet<Topition>>,
+) -> Result<Vec<AddPartitionsToTxnTopicResult>> {
+    match enrolled {
+        Ok(enrolled) => Ok(txn_partition_results(topics, |topition| {
+            if enrolled.contains(topition) {
+                ErrorCode::None
+            } else {
+                ErrorCode::InvalidTxnState
+            }
+        })),
+
+        Err(Error::Api(error_code)) => Ok(txn_partition_results(topics, |_| error_code)),
+
+        Err(otherwise) => Err(otherwise),
+    }
+}
+
+// one transaction in a batched AddPartitionsToTxn failing is reported
+// against its own partitions, leaving the other transactions unaffected
+pub(crate) fn txn_add_partitions_outcome(
+    transactional_id: &str,
+    topics: &[AddPartitionsToTxnTopic],
+    outcome: Result<Vec<AddPartitionsToTxnTopicResult>>,
+) -> AddPartitionsToTxnResult {
+    let topic_results = match outcome {
+        Ok(topic_results) => topic_results,
+
+        Err(Error::Api(error_code)) => txn_partition_results(topics, |_| error_code),
+
+        Err(err) => {
+            error!(?err, transactional_id);
+            txn_partition_results(topics, |_| ErrorCode::UnknownServerError)
+        }
+    };
+
+    AddPartitionsToTxnResult {
+        transactional_id: transactional_id.to_owned(),
+        topic_results: Some(topic_results),
+    }
+}
+
+fn txn_partition_results(
+    topics: &[AddPartitionsToTxnTopic],
+    error_code: impl Fn(&Topition) -> ErrorCode,
+) -> Vec<AddPartitionsToTxnTopicResult> {
+    topics
+        .iter()
+        .map(|topic| AddPartitionsToTxnTopicResult {
+            name: topic.name.clone(),
+            results_by_partition: Some(
+                topic
+                    .partitions
+                    .as_deref()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|partition_index| AddPartitionsToTxnPartitionResult {
+                        partition_index: *partition_index,
+                        partition_error_code: error_code(&Topition::new(
+                            topic.name.clone(),
+                            *partition_index,
+                        ))
+                        .into(),
+                    })
+                    .collect(),
+            ),
+        })
+        .collect()
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct TxnOffsetCommitRequest {
     pub transaction_id: String,
@@ -983,6 +1364,13 @@ impl TxnState {
             _otherwise => false,
         }
     }
+
+    pub fn is_open(&self) -> bool {
+        match self {
+            Self::Begin | Self::PrepareAbort | Self::PrepareCommit => true,
+            _otherwise => false,
+        }
+    }
 }
 
 impl FromStr for TxnState {
@@ -1020,6 +1408,201 @@ impl From<TxnState> for String {
     }
 }
 
+// cleanup.policy is a comma separated list, with compact, delete or both
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CleanupPolicy {
+    compact: bool,
+    delete: bool,
+}
+
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        Self {
+            compact: false,
+            delete: true,
+        }
+    }
+}
+
+impl CleanupPolicy {
+    pub fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    pub fn is_delete(&self) -> bool {
+        self.delete
+    }
+}
+
+impl FromStr for CleanupPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',').map(str::trim).try_fold(
+            Self {
+                compact: false,
+                delete: false,
+            },
+            |policy, name| match name {
+                "compact" => Ok(Self {
+                    compact: true,
+                    ..policy
+                }),
+                "delete" => Ok(Self {
+                    delete: true,
+                    ..policy
+                }),
+                _ => Err(Error::UnknownCleanupPolicy(s.to_owned())),
+            },
+        )
+    }
+}
+
+pub const MIN_CLEANABLE_DIRTY_RATIO: &str = "min.cleanable.dirty.ratio";
+pub const MAX_COMPACTION_LAG_MS: &str = "max.compaction.lag.ms";
+
+// validates a compaction configuration of a topic, min.cleanable.dirty.ratio
+// or max.compaction.lag.ms, returning none for any other configuration.
+// Neither storage engine has a log cleaner to act on them yet
+pub fn compaction(name: &str, value: &str) -> Option<Result<()>> {
+    let invalid = || Error::InvalidCompaction(format!("{name}: {value}"));
+
+    match name {
+        MIN_CLEANABLE_DIRTY_RATIO => Some(
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|ratio| (0.0..=1.0).contains(ratio))
+                .and(Some(()))
+                .ok_or_else(invalid),
+        ),
+
+        // the default of i64::MAX leaves compaction to the dirty ratio
+        MAX_COMPACTION_LAG_MS => Some(
+            value
+                .parse::<i64>()
+                .ok()
+                .filter(|ms| *ms > 0)
+                .and(Some(()))
+                .ok_or_else(invalid),
+        ),
+
+        _otherwise => None,
+    }
+}
+
+pub const ZSTD_DICTIONARY: &str = "compression.zstd.dictionary";
+
+// compression.zstd.dictionary is a comma separated list of base64 encoded
+// zstd dictionaries, with the first compressing stored batches and the
+// remainder kept to decompress batches stored with an earlier dictionary
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ZstdDictionaries(Vec<(u32, Bytes)>);
+
+impl ZstdDictionaries {
+    pub fn compressor(&self) -> Option<&[u8]> {
+        self.0.first().map(|(_, dictionary)| &dictionary[..])
+    }
+
+    pub fn get(&self, id: u32) -> Option<&[u8]> {
+        self.0
+            .iter()
+            .find(|(dictionary_id, _)| *dictionary_id == id)
+            .map(|(_, dictionary)| &dictionary[..])
+    }
+}
+
+impl FromStr for ZstdDictionaries {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|encoded| !encoded.is_empty())
+            .enumerate()
+            .map(|(index, encoded)| {
+                let dictionary = BASE64_STANDARD
+                    .decode(encoded)
+                    .map_err(|error| Error::InvalidZstdDictionary(format!("{index}: {error}")))?;
+
+                // a raw content dictionary has no id to identify it when fetched
+                zstd::zstd_safe::get_dict_id_from_dict(&dictionary[..])
+                    .map(|id| (id.get(), Bytes::from(dictionary)))
+                    .ok_or_else(|| Error::InvalidZstdDictionary(format!("{index}: missing id")))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Self)
+    }
+}
+
+pub const COMPRESSION_GZIP_LEVEL: &str = "compression.gzip.level";
+pub const COMPRESSION_LZ4_LEVEL: &str = "compression.lz4.level";
+pub const COMPRESSION_ZSTD_LEVEL: &str = "compression.zstd.level";
+
+// applies a compression level configuration, e.g., compression.zstd.level,
+// returning none for any other configuration
+pub fn compression_level(
+    level: CompressionLevel,
+    name: &str,
+    value: &str,
+) -> Option<Result<CompressionLevel>> {
+    let compression = match name {
+        COMPRESSION_GZIP_LEVEL => Compression::Gzip,
+        COMPRESSION_LZ4_LEVEL => Compression::Lz4,
+        COMPRESSION_ZSTD_LEVEL => Compression::Zstd,
+        _otherwise => return None,
+    };
+
+    Some(
+        value
+            .parse()
+            .map_err(Into::into)
+            .and_then(|value| level.level(compression, value).map_err(Into::into)),
+    )
+}
+
+/// Limits on the number of topics and partitions in a cluster, checked
+/// by storage in the same operation that creates a topic.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TopicLimit {
+    max_topics: Option<i64>,
+    max_partitions: Option<i64>,
+}
+
+impl TopicLimit {
+    pub fn max_topics(self, max_topics: Option<i64>) -> Self {
+        Self { max_topics, ..self }
+    }
+
+    pub fn max_partitions(self, max_partitions: Option<i64>) -> Self {
+        Self {
+            max_partitions,
+            ..self
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_topics.is_none() && self.max_partitions.is_none()
+    }
+
+    /// The reason that a topic of `num_partitions` cannot be created in a
+    /// cluster already holding `topics` with `partitions`, if any.
+    pub fn violation(&self, topics: i64, partitions: i64, num_partitions: i32) -> Option<String> {
+        self.max_topics
+            .filter(|max_topics| topics >= *max_topics)
+            .map(|max_topics| format!("topic limit of {max_topics} reached"))
+            .or_else(|| {
+                self.max_partitions
+                    .filter(|max_partitions| {
+                        partitions + i64::from(num_partitions.max(0)) > *max_partitions
+                    })
+                    .map(|max_partitions| {
+                        format!("partition limit of {max_partitions} exceeded, {partitions} in use")
+                    })
+            })
+    }
+}
+
 #[async_trait]
 pub trait StorageProvider {
     async fn provide_storage(&mut self) -> impl Storage;
@@ -1027,6 +1610,9 @@ pub trait StorageProvider {
 
 #[async_trait]
 pub trait Storage: Clone + Debug + Send + Sync + 'static {
+    // the clock used for timestamps and liveness, replaced in tests
+    fn clock(self, clock: Arc<dyn Clock>) -> Self;
+
     async fn register_broker(
         &mut self,
         broker_registration: BrokerRegistrationRequest,
@@ -1034,6 +1620,15 @@ pub trait Storage: Clone + Debug + Send + Sync + 'static {
 
     async fn create_topic(&mut self, topic: CreatableTopic, validate_only: bool) -> Result<Uuid>;
 
+    /// Create a topic unless it would exceed the limit, failing with
+    /// [`Error::TopicLimit`], without a concurrent create in between.
+    async fn create_topic_within(
+        &mut self,
+        topic: CreatableTopic,
+        validate_only: bool,
+        limit: TopicLimit,
+    ) -> Result<Uuid>;
+
     async fn incremental_alter_resource(
         &mut self,
         resource: AlterConfigsResource,
@@ -1048,6 +1643,12 @@ pub trait Storage: Clone + Debug + Send + Sync + 'static {
 
     async fn brokers(&mut self) -> Result<Vec<DescribeClusterBroker>>;
 
+    async fn partition_count(&mut self) -> Result<(i64, i64)>;
+
+    async fn topic_exists(&mut self, topic: &TopicId) -> Result<bool>;
+
+    async fn partition_exists(&mut self, topition: &Topition) -> Result<bool>;
+
     async fn produce(
         &mut self,
         transaction_id: Option<&str>,
@@ -1055,6 +1656,12 @@ pub trait Storage: Clone + Debug + Send + Sync + 'static {
         batch: deflated::Batch,
     ) -> Result<i64>;
 
+    async fn produce_many(
+        &mut self,
+        transaction_id: Option<&str>,
+        entries: Vec<(Topition, deflated::Batch)>,
+    ) -> Result<Vec<i64>>;
+
     async fn fetch(
         &mut self,
         topition: &'_ Topition,
@@ -1066,6 +1673,12 @@ pub trait Storage: Clone + Debug + Send + Sync + 'static {
 
     async fn offset_stage(&mut self, topition: &Topition) -> Result<OffsetStage>;
 
+    async fn watermarks(&mut self, topition: &Topition) -> Result<(i64, i64)>;
+
+    async fn in_sync_replicas(&mut self, topition: &Topition) -> Result<i32>;
+
+    async fn tier_records(&mut self, topition: &Topition, offset: i64) -> Result<ErrorCode>;
+
     async fn list_offsets(
         &mut self,
         isolation_level: IsolationLevel,
@@ -1091,8 +1704,27 @@ pub trait Storage: Clone + Debug + Send + Sync + 'static {
         group_id: &str,
     ) -> Result<BTreeMap<Topition, i64>>;
 
+    // the offsets committed by a group as stored, with their leader
+    // epoch, metadata and commit timestamp
+    async fn committed_offsets(
+        &mut self,
+        group_id: &str,
+    ) -> Result<BTreeMap<Topition, OffsetCommitRequest>>;
+
+    // the partitions with an offset committed by a transaction that has
+    // yet to end, which may still be rolled back
+    async fn pending_offset_commits(
+        &mut self,
+        group_id: &str,
+        topics: &[Topition],
+    ) -> Result<BTreeSet<Topition>>;
+
     async fn metadata(&mut self, topics: Option<&[TopicId]>) -> Result<MetadataResponse>;
 
+    // every topic, partition and broker in a single storage query, from
+    // which any metadata request can be answered with select
+    async fn metadata_snapshot(&mut self) -> Result<MetadataResponse>;
+
     async fn describe_config(
         &mut self,
         name: &str,
@@ -1100,7 +1732,11 @@ pub trait Storage: Clone + Debug + Send + Sync + 'static {
         keys: Option<&[String]>,
     ) -> Result<DescribeConfigsResult>;
 
-    async fn list_groups(&mut self, states_filter: Option<&[String]>) -> Result<Vec<ListedGroup>>;
+    async fn list_groups(
+        &mut self,
+        states_filter: Option<&[String]>,
+        types_filter: Option<&[String]>,
+    ) -> Result<Vec<ListedGroup>>;
 
     async fn delete_groups(
         &mut self,
@@ -1135,6 +1771,8 @@ pub trait Storage: Clone + Debug + Send + Sync + 'static {
         producer_epoch: Option<i16>,
     ) -> Result<ProducerIdResponse>;
 
+    async fn allocate_producer_id_block(&mut self, size: i32) -> Result<Range<i64>>;
+
     async fn txn_add_offsets(
         &mut self,
         transaction_id: &str,
@@ -1160,6 +1798,16 @@ pub trait Storage: Clone + Debug + Send + Sync + 'static {
         producer_epoch: i16,
         committed: bool,
     ) -> Result<ErrorCode>;
+
+    async fn txn_force_abort(&mut self, transaction_id: &str) -> Result<ErrorCode>;
+
+    async fn export_group_state(&mut self, group_id: &str) -> Result<Vec<GroupStateRecord>> {
+        group_state::export(self, group_id).await
+    }
+
+    async fn import_group_state(&mut self, records: &[GroupStateRecord]) -> Result<()> {
+        group_state::import(self, records).await
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -1179,6 +1827,145 @@ pub enum StorageContainer {
     DynoStore(DynoStore),
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct Builder<C, N, L, S> {
+    cluster_id: C,
+    node: N,
+    advertised_listener: L,
+    storage: S,
+    schemas: Option<Registry>,
+    sequence_window: SequenceWindow,
+    txn_limit: TxnLimit,
+    connection_pool: ConnectionPool,
+}
+
+impl<C, N, L, S> Builder<C, N, L, S> {
+    pub fn cluster_id(self, cluster_id: impl Into<String>) -> Builder<String, N, L, S> {
+        Builder {
+            cluster_id: cluster_id.into(),
+            node: self.node,
+            advertised_listener: self.advertised_listener,
+            storage: self.storage,
+            schemas: self.schemas,
+            sequence_window: self.sequence_window,
+            txn_limit: self.txn_limit,
+            connection_pool: self.connection_pool,
+        }
+    }
+
+    pub fn node(self, node: i32) -> Builder<C, i32, L, S> {
+        Builder {
+            cluster_id: self.cluster_id,
+            node,
+            advertised_listener: self.advertised_listener,
+            storage: self.storage,
+            schemas: self.schemas,
+            sequence_window: self.sequence_window,
+            txn_limit: self.txn_limit,
+            connection_pool: self.connection_pool,
+        }
+    }
+
+    pub fn advertised_listener(self, advertised_listener: Url) -> Builder<C, N, Url, S> {
+        Builder {
+            cluster_id: self.cluster_id,
+            node: self.node,
+            advertised_listener,
+            storage: self.storage,
+            schemas: self.schemas,
+            sequence_window: self.sequence_window,
+            txn_limit: self.txn_limit,
+            connection_pool: self.connection_pool,
+        }
+    }
+
+    pub fn storage(self, storage: Url) -> Builder<C, N, L, Url> {
+        Builder {
+            cluster_id: self.cluster_id,
+            node: self.node,
+            advertised_listener: self.advertised_listener,
+            storage,
+            schemas: self.schemas,
+            sequence_window: self.sequence_window,
+            txn_limit: self.txn_limit,
+            connection_pool: self.connection_pool,
+        }
+    }
+
+    pub fn schemas(self, schemas: Option<Registry>) -> Self {
+        Self { schemas, ..self }
+    }
+
+    pub fn sequence_window(self, sequence_window: SequenceWindow) -> Self {
+        Self {
+            sequence_window,
+            ..self
+        }
+    }
+    pub fn txn_limit(self, txn_limit: TxnLimit) -> Self {
+        Self { txn_limit, ..self }
+    }
+
+    pub fn connection_pool(self, connection_pool: ConnectionPool) -> Self {
+        Self {
+            connection_pool,
+            ..self
+        }
+    }
+}
+
+impl Builder<String, i32, Url, Url> {
+    pub fn build(self) -> Result<StorageContainer> {
+        match self.storage.scheme() {
+            "postgres" | "postgresql" => Postgres::builder(self.storage.to_string().as_str())
+                .map(|builder| builder.cluster(self.cluster_id.as_str()))
+                .map(|builder| builder.node(self.node))
+                .map(|builder| builder.advertised_listener(self.advertised_listener.clone()))
+                .map(|builder| builder.schemas(self.schemas))
+                .map(|builder| builder.sequence_window(self.sequence_window))
+                .map(|builder| builder.txn_limit(self.txn_limit))
+                .map(|builder| builder.connection_pool(self.connection_pool))
+                .map(|builder| builder.build())
+                .map(StorageContainer::Postgres),
+
+            "s3" => {
+                let bucket_name = self.storage.host_str().unwrap_or("tansu");
+
+                AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket_name)
+                    .with_conditional_put(S3ConditionalPut::ETagMatch)
+                    .build()
+                    .map(|object_store| {
+                        DynoStore::new(self.cluster_id.as_str(), self.node, object_store)
+                            .advertised_listener(self.advertised_listener.clone())
+                            .schemas(self.schemas)
+                            .sequence_window(self.sequence_window)
+                            .txn_limit(self.txn_limit)
+                    })
+                    .map(StorageContainer::DynoStore)
+                    .map_err(Into::into)
+            }
+
+            "memory" => Ok(StorageContainer::DynoStore(
+                DynoStore::new(self.cluster_id.as_str(), self.node, InMemory::new())
+                    .advertised_listener(self.advertised_listener)
+                    .schemas(self.schemas)
+                    .sequence_window(self.sequence_window)
+                    .txn_limit(self.txn_limit),
+            )),
+
+            _unsupported => Err(Error::UnsupportedStorageUrl(self.storage)),
+        }
+    }
+}
+
+impl StorageContainer {
+    pub fn builder()
+    -> Builder<PhantomData<String>, PhantomData<i32>, PhantomData<Url>, PhantomData<Url>> {
+        Builder::default()
+    }
+}
+
 pub(crate) static METER: LazyLock<Meter> = LazyLock::new(|| {
     global::meter_with_scope(
         InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
@@ -1204,6 +1991,13 @@ static STORAGE_CONTAINER_ERRORS: LazyLock<Counter<u64>> = LazyLock::new(|| {
 
 #[async_trait]
 impl Storage for StorageContainer {
+    fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        match self {
+            Self::Postgres(pg) => Self::Postgres(pg.clock(clock)),
+            Self::DynoStore(dyn_store) => Self::DynoStore(dyn_store.clock(clock)),
+        }
+    }
+
     async fn register_broker(
         &mut self,
         broker_registration: BrokerRegistrationRequest,
@@ -1261,6 +2055,34 @@ impl Storage for StorageContainer {
         })
     }
 
+    async fn create_topic_within(
+        &mut self,
+        topic: CreatableTopic,
+        validate_only: bool,
+        limit: TopicLimit,
+    ) -> Result<Uuid> {
+        let attributes = [KeyValue::new("method", "create_topic_within")];
+        let span = debug_span!("create_topic_within", ?topic, validate_only, ?limit);
+
+        async move {
+            match self {
+                Self::Postgres(pg) => pg.create_topic_within(topic, validate_only, limit),
+                Self::DynoStore(dyn_store) => {
+                    dyn_store.create_topic_within(topic, validate_only, limit)
+                }
+            }
+            .await
+        }
+        .instrument(span)
+        .await
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
     async fn delete_records(
         &mut self,
         topics: &[DeleteRecordsTopic],
@@ -1309,6 +2131,51 @@ impl Storage for StorageContainer {
         })
     }
 
+    async fn partition_count(&mut self) -> Result<(i64, i64)> {
+        let attributes = [KeyValue::new("method", "partition_count")];
+
+        match self {
+            Self::Postgres(pg) => pg.partition_count().await,
+            Self::DynoStore(dyn_store) => dyn_store.partition_count().await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
+    async fn topic_exists(&mut self, topic: &TopicId) -> Result<bool> {
+        let attributes = [KeyValue::new("method", "topic_exists")];
+
+        match self {
+            Self::Postgres(pg) => pg.topic_exists(topic).await,
+            Self::DynoStore(dyn_store) => dyn_store.topic_exists(topic).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
+    async fn partition_exists(&mut self, topition: &Topition) -> Result<bool> {
+        let attributes = [KeyValue::new("method", "partition_exists")];
+
+        match self {
+            Self::Postgres(pg) => pg.partition_exists(topition).await,
+            Self::DynoStore(dyn_store) => dyn_store.partition_exists(topition).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
     async fn produce(
         &mut self,
         transaction_id: Option<&str>,
@@ -1329,6 +2196,25 @@ impl Storage for StorageContainer {
         })
     }
 
+    async fn produce_many(
+        &mut self,
+        transaction_id: Option<&str>,
+        entries: Vec<(Topition, deflated::Batch)>,
+    ) -> Result<Vec<i64>> {
+        let attributes = [KeyValue::new("method", "produce_many")];
+
+        match self {
+            Self::Postgres(pg) => pg.produce_many(transaction_id, entries).await,
+            Self::DynoStore(dyn_store) => dyn_store.produce_many(transaction_id, entries).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
     async fn fetch(
         &mut self,
         topition: &'_ Topition,
@@ -1373,6 +2259,51 @@ impl Storage for StorageContainer {
         })
     }
 
+    async fn watermarks(&mut self, topition: &Topition) -> Result<(i64, i64)> {
+        let attributes = [KeyValue::new("method", "watermarks")];
+
+        match self {
+            Self::Postgres(pg) => pg.watermarks(topition).await,
+            Self::DynoStore(dyn_store) => dyn_store.watermarks(topition).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
+    async fn tier_records(&mut self, topition: &Topition, offset: i64) -> Result<ErrorCode> {
+        let attributes = [KeyValue::new("method", "tier_records")];
+
+        match self {
+            Self::Postgres(pg) => pg.tier_records(topition, offset).await,
+            Self::DynoStore(dyn_store) => dyn_store.tier_records(topition, offset).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
+    async fn in_sync_replicas(&mut self, topition: &Topition) -> Result<i32> {
+        let attributes = [KeyValue::new("method", "in_sync_replicas")];
+
+        match self {
+            Self::Postgres(pg) => pg.in_sync_replicas(topition).await,
+            Self::DynoStore(dyn_store) => dyn_store.in_sync_replicas(topition).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
     async fn list_offsets(
         &mut self,
         isolation_level: IsolationLevel,
@@ -1434,6 +2365,43 @@ impl Storage for StorageContainer {
         })
     }
 
+    async fn committed_offsets(
+        &mut self,
+        group_id: &str,
+    ) -> Result<BTreeMap<Topition, OffsetCommitRequest>> {
+        let attributes = [KeyValue::new("method", "committed_offsets")];
+
+        match self {
+            Self::Postgres(inner) => inner.committed_offsets(group_id).await,
+            Self::DynoStore(inner) => inner.committed_offsets(group_id).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
+    async fn pending_offset_commits(
+        &mut self,
+        group_id: &str,
+        topics: &[Topition],
+    ) -> Result<BTreeSet<Topition>> {
+        let attributes = [KeyValue::new("method", "pending_offset_commits")];
+
+        match self {
+            Self::Postgres(inner) => inner.pending_offset_commits(group_id, topics).await,
+            Self::DynoStore(inner) => inner.pending_offset_commits(group_id, topics).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
     async fn offset_fetch(
         &mut self,
         group_id: Option<&str>,
@@ -1473,6 +2441,21 @@ impl Storage for StorageContainer {
         })
     }
 
+    async fn metadata_snapshot(&mut self) -> Result<MetadataResponse> {
+        let attributes = [KeyValue::new("method", "metadata_snapshot")];
+
+        match self {
+            Self::Postgres(pg) => pg.metadata_snapshot().await,
+            Self::DynoStore(dyn_store) => dyn_store.metadata_snapshot().await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
     async fn describe_config(
         &mut self,
         name: &str,
@@ -1520,12 +2503,16 @@ impl Storage for StorageContainer {
         })
     }
 
-    async fn list_groups(&mut self, states_filter: Option<&[String]>) -> Result<Vec<ListedGroup>> {
+    async fn list_groups(
+        &mut self,
+        states_filter: Option<&[String]>,
+        types_filter: Option<&[String]>,
+    ) -> Result<Vec<ListedGroup>> {
         let attributes = [KeyValue::new("method", "list_groups")];
 
         match self {
-            Self::Postgres(pg) => pg.list_groups(states_filter).await,
-            Self::DynoStore(dyn_store) => dyn_store.list_groups(states_filter).await,
+            Self::Postgres(pg) => pg.list_groups(states_filter, types_filter).await,
+            Self::DynoStore(dyn_store) => dyn_store.list_groups(states_filter, types_filter).await,
         }
         .inspect(|_| {
             STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
@@ -1645,6 +2632,23 @@ impl Storage for StorageContainer {
         })
     }
 
+    async fn allocate_producer_id_block(&mut self, size: i32) -> Result<Range<i64>> {
+        debug!(size);
+
+        let attributes = [KeyValue::new("method", "allocate_producer_id_block")];
+
+        match self {
+            Self::Postgres(pg) => pg.allocate_producer_id_block(size).await,
+            Self::DynoStore(dyn_store) => dyn_store.allocate_producer_id_block(size).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
+
     async fn txn_add_offsets(
         &mut self,
         transaction_id: &str,
@@ -1736,6 +2740,21 @@ impl Storage for StorageContainer {
             STORAGE_CONTAINER_ERRORS.add(1, &attributes);
         })
     }
+
+    async fn txn_force_abort(&mut self, transaction_id: &str) -> Result<ErrorCode> {
+        let attributes = [KeyValue::new("method", "txn_force_abort")];
+
+        match self {
+            Self::Postgres(pg) => pg.txn_force_abort(transaction_id).await,
+            Self::DynoStore(dyn_store) => dyn_store.txn_force_abort(transaction_id).await,
+        }
+        .inspect(|_| {
+            STORAGE_CONTAINER_REQUESTS.add(1, &attributes);
+        })
+        .inspect_err(|_| {
+            STORAGE_CONTAINER_ERRORS.add(1, &attributes);
+        })
+    }
 }
 
 #[cfg(test)]
@@ -1757,4 +2776,110 @@ mod tests {
         assert_eq!(i32::MAX, topition.partition());
         Ok(())
     }
+
+    #[test]
+    fn cleanup_policy_from_str() -> Result<()> {
+        let policy = CleanupPolicy::from_str("delete")?;
+        assert!(!policy.is_compact());
+        assert!(policy.is_delete());
+        assert_eq!(CleanupPolicy::default(), policy);
+
+        let policy = CleanupPolicy::from_str("compact")?;
+        assert!(policy.is_compact());
+        assert!(!policy.is_delete());
+
+        for combined in ["compact,delete", "delete,compact", "compact, delete"] {
+            let policy = CleanupPolicy::from_str(combined)?;
+            assert!(policy.is_compact());
+            assert!(policy.is_delete());
+        }
+
+        for invalid in ["", "compact,", "compact,remove"] {
+            assert!(matches!(
+                CleanupPolicy::from_str(invalid),
+                Err(Error::UnknownCleanupPolicy(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn compaction_from_config() {
+        assert_eq!(
+            None,
+            compaction("retention.ms", "1").map(|validated| validated.is_ok())
+        );
+
+        for (name, valid) in [
+            (MIN_CLEANABLE_DIRTY_RATIO, "0.5"),
+            (MIN_CLEANABLE_DIRTY_RATIO, "1"),
+            (MAX_COMPACTION_LAG_MS, "9223372036854775807"),
+        ] {
+            assert!(
+                compaction(name, valid).is_some_and(|validated| validated.is_ok()),
+                "{name}: {valid}"
+            );
+        }
+
+        for (name, invalid) in [
+            (MIN_CLEANABLE_DIRTY_RATIO, "1.5"),
+            (MIN_CLEANABLE_DIRTY_RATIO, "-0.1"),
+            (MIN_CLEANABLE_DIRTY_RATIO, "half"),
+            (MAX_COMPACTION_LAG_MS, "0"),
+            (MAX_COMPACTION_LAG_MS, "soon"),
+        ] {
+            assert!(
+                compaction(name, invalid).is_some_and(|validated| validated.is_err()),
+                "{name}: {invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn zstd_dictionaries_from_str() -> Result<()> {
+        let dictionary = |site: &str| -> Result<Vec<u8>> {
+            zstd::dict::from_samples(
+                &(0..1_000)
+                    .map(|n| format!(r#"{{"site":"{site}","reading":{n}}}"#))
+                    .collect::<Vec<_>>()[..],
+                2_048,
+            )
+            .map_err(Into::into)
+        };
+
+        let north = dictionary("north")?;
+        let south = dictionary("south")?;
+
+        let id = |dictionary: &[u8]| {
+            zstd::zstd_safe::get_dict_id_from_dict(dictionary)
+                .map(|id| id.get())
+                .unwrap()
+        };
+
+        let dictionaries = ZstdDictionaries::from_str(&format!(
+            "{}, {}",
+            BASE64_STANDARD.encode(&north),
+            BASE64_STANDARD.encode(&south)
+        ))?;
+
+        assert_eq!(Some(&north[..]), dictionaries.compressor());
+        assert_eq!(Some(&north[..]), dictionaries.get(id(&north)));
+        assert_eq!(Some(&south[..]), dictionaries.get(id(&south)));
+
+        assert_eq!(ZstdDictionaries::default(), ZstdDictionaries::from_str("")?);
+        assert_eq!(None, ZstdDictionaries::default().compressor());
+
+        for invalid in [
+            "not base64!",
+            BASE64_STANDARD.encode(b"raw content").as_str(),
+        ] {
+            assert!(matches!(
+                ZstdDictionaries::from_str(invalid),
+                Err(Error::InvalidZstdDictionary(_))
+            ));
+        }
+
+        Ok(())
+    }
 }
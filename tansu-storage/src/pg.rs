@@ -15,18 +15,20 @@
 
 use std::{
     cmp::Ordering,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     hash::{DefaultHasher, Hash, Hasher},
     marker::PhantomData,
+    ops::Range,
     str::FromStr,
-    sync::LazyLock,
+    sync::{Arc, LazyLock},
     time::{Duration, SystemTime},
 };
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use deadpool::managed;
 use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, RecyclingMethod};
-use opentelemetry::metrics::Histogram;
+use opentelemetry::metrics::{Gauge, Histogram};
 use opentelemetry::{KeyValue, metrics::Counter};
 use rand::{prelude::*, rng};
 use serde_json::Value;
@@ -54,16 +56,20 @@ use tansu_kafka_sans_io::{
     txn_offset_commit_response::{TxnOffsetCommitResponsePartition, TxnOffsetCommitResponseTopic},
 };
 use tansu_schema_registry::Registry;
+use tokio::time::{interval, timeout};
 use tokio_postgres::{Config, NoTls, Row, Transaction, error::SqlState, types::ToSql};
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 use url::Url;
 use uuid::Uuid;
 
 use crate::{
-    BrokerRegistrationRequest, Error, GroupDetail, ListOffsetRequest, ListOffsetResponse, METER,
-    MetadataResponse, NULL_TOPIC_ID, NamedGroupDetail, OffsetCommitRequest, OffsetStage,
-    ProducerIdResponse, Result, Storage, TopicId, Topition, TxnAddPartitionsRequest,
-    TxnAddPartitionsResponse, TxnOffsetCommitRequest, TxnState, UpdateError, Version,
+    BROKER_LIVENESS, BrokerRegistrationRequest, ConnectionPool, Error, GroupDetail, LEADER_EPOCH,
+    ListOffsetRequest, ListOffsetResponse, METER, MetadataResponse, NULL_TOPIC_ID,
+    NamedGroupDetail, OffsetCommitRequest, OffsetStage, ProducerIdResponse, Result, SequenceWindow,
+    Storage, TopicId, TopicLimit, Topition, TxnAddPartitionsRequest, TxnAddPartitionsResponse,
+    TxnLimit, TxnOffsetCommitRequest, TxnState, UpdateError, Version,
+    clock::{Clock, SystemClock},
+    reinitialized, txn_add_partitions_outcome, txn_verify_partitions,
 };
 
 macro_rules! include_sql {
@@ -79,6 +85,12 @@ pub struct Postgres {
     advertised_listener: Url,
     pool: Pool,
     schemas: Option<Registry>,
+    sequence_window: SequenceWindow,
+    txn_limit: TxnLimit,
+    connection_pool: ConnectionPool,
+    clock: Arc<dyn Clock>,
+    // idle connections are reaped while any clone is alive
+    _reaper: Option<Arc<()>>,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -88,6 +100,9 @@ pub struct Builder<C, N, L, P> {
     advertised_listener: L,
     pool: P,
     schemas: Option<Registry>,
+    sequence_window: SequenceWindow,
+    txn_limit: TxnLimit,
+    connection_pool: ConnectionPool,
 }
 
 impl<C, N, L, P> Builder<C, N, L, P> {
@@ -98,6 +113,9 @@ impl<C, N, L, P> Builder<C, N, L, P> {
             advertised_listener: self.advertised_listener,
             pool: self.pool,
             schemas: self.schemas,
+            sequence_window: self.sequence_window,
+            txn_limit: self.txn_limit,
+            connection_pool: self.connection_pool,
         }
     }
 }
@@ -110,6 +128,9 @@ impl<C, N, L, P> Builder<C, N, L, P> {
             advertised_listener: self.advertised_listener,
             pool: self.pool,
             schemas: self.schemas,
+            sequence_window: self.sequence_window,
+            txn_limit: self.txn_limit,
+            connection_pool: self.connection_pool,
         }
     }
 }
@@ -122,22 +143,55 @@ impl<C, N, L, P> Builder<C, N, L, P> {
             advertised_listener,
             pool: self.pool,
             schemas: self.schemas,
+            sequence_window: self.sequence_window,
+            txn_limit: self.txn_limit,
+            connection_pool: self.connection_pool,
         }
     }
 
     pub fn schemas(self, schemas: Option<Registry>) -> Builder<C, N, L, P> {
         Self { schemas, ..self }
     }
+
+    pub fn sequence_window(self, sequence_window: SequenceWindow) -> Builder<C, N, L, P> {
+        Self {
+            sequence_window,
+            ..self
+        }
+    }
+
+    pub fn txn_limit(self, txn_limit: TxnLimit) -> Builder<C, N, L, P> {
+        Self { txn_limit, ..self }
+    }
+
+    pub fn connection_pool(self, connection_pool: ConnectionPool) -> Builder<C, N, L, P> {
+        Self {
+            connection_pool,
+            ..self
+        }
+    }
 }
 
 impl Builder<String, i32, Url, Pool> {
     pub fn build(self) -> Postgres {
+        self.pool.resize(self.connection_pool.max_size);
+
+        let reaper = self
+            .connection_pool
+            .idle_timeout
+            .map(|idle_timeout| reap(self.pool.clone(), idle_timeout));
+
         Postgres {
             cluster: self.cluster,
             node: self.node,
             advertised_listener: self.advertised_listener,
             pool: self.pool,
             schemas: self.schemas,
+            sequence_window: self.sequence_window,
+            txn_limit: self.txn_limit,
+            connection_pool: self.connection_pool,
+            clock: Arc::new(SystemClock),
+            _reaper: reaper,
         }
     }
 }
@@ -159,8 +213,10 @@ where
         let mgr = Manager::from_config(pg_config, NoTls, mgr_config);
         let advertised_listener = Url::parse("tcp://127.0.0.1/")?;
 
+        let connection_pool = ConnectionPool::default();
+
         Pool::builder(mgr)
-            .max_size(16)
+            .max_size(connection_pool.max_size)
             .build()
             .map(|pool| Self {
                 pool,
@@ -168,6 +224,9 @@ where
                 advertised_listener,
                 cluster: C::default(),
                 schemas: None,
+                sequence_window: SequenceWindow::default(),
+                txn_limit: TxnLimit::default(),
+                connection_pool,
             })
             .map_err(Into::into)
     }
@@ -214,23 +273,25 @@ impl Postgres {
     }
 
     async fn connection(&self) -> Result<Object> {
-        self.pool.get().await.map_err(Into::into)
+        acquire(&self.pool, &self.connection_pool).await
     }
 
     fn idempotent_sequence_check(
+        &self,
         producer_epoch: &i16,
         sequence: &i32,
+        recent: &[i32],
         deflated: &deflated::Batch,
     ) -> Result<i32> {
-        debug!(?producer_epoch, ?sequence, ?deflated);
+        debug!(?producer_epoch, ?sequence, ?recent, ?deflated);
 
         match producer_epoch.cmp(&deflated.producer_epoch) {
             Ordering::Equal => match sequence.cmp(&deflated.base_sequence) {
                 Ordering::Equal => Ok(deflated.last_offset_delta + 1),
 
                 Ordering::Greater => {
-                    debug!(?sequence, ?deflated.base_sequence);
-                    Err(Error::Api(ErrorCode::DuplicateSequenceNumber))
+                    debug!(?sequence, ?recent, ?deflated.base_sequence);
+                    Err(self.sequence_window.retry(recent, deflated.base_sequence))
                 }
 
                 Ordering::Less => {
@@ -292,6 +353,9 @@ impl Postgres {
                 })?;
 
             let sequence = row.try_get::<_, i32>(0).inspect_err(|err| error!(?err))?;
+            let mut recent = row
+                .try_get::<_, Vec<i32>>(1)
+                .inspect_err(|err| error!(?err))?;
 
             debug!(
                 self.cluster,
@@ -302,10 +366,14 @@ impl Postgres {
                 sequence,
             );
 
-            let increment = Self::idempotent_sequence_check(&current_epoch, &sequence, deflated)?;
+            let increment =
+                self.idempotent_sequence_check(&current_epoch, &sequence, &recent, deflated)?;
 
             debug!(increment);
 
+            self.sequence_window
+                .accepted(&mut recent, deflated.base_sequence);
+
             assert_eq!(
                 1,
                 self.tx_prepare_execute(
@@ -318,6 +386,7 @@ impl Postgres {
                         &deflated.producer_id,
                         &deflated.producer_epoch,
                         &increment,
+                        &recent,
                     ],
                     "idempotent_message_check",
                 )
@@ -795,7 +864,7 @@ impl Postgres {
                     .await
                     .inspect_err(|err| {
                         error!(?err, ?topic, ?partition, ?offset, ?key, ?value);
-                    });
+                    })?;
             }
         }
 
@@ -845,6 +914,166 @@ impl Postgres {
         Ok(high.unwrap_or_default())
     }
 
+    async fn txn_enrolled(
+        &mut self,
+        transaction_id: &str,
+        producer_id: i64,
+        producer_epoch: i16,
+    ) -> Result<BTreeSet<Topition>> {
+        debug!(cluster = ?self.cluster, transaction_id, producer_id, producer_epoch);
+
+        let c = self.connection().await.inspect_err(|err| error!(?err))?;
+
+        // an unknown transaction is an error, rather than one without partitions
+        let Some(row) = self
+            .prepare_query_opt(
+                &c,
+                include_sql!("pg/txn_select_enrolled_epoch.sql").as_str(),
+                &[&self.cluster, &transaction_id, &producer_epoch],
+                "txn_enrolled",
+            )
+            .await
+            .inspect_err(|err| error!(?err, cluster = self.cluster, transaction_id))?
+        else {
+            return Err(Error::Api(ErrorCode::TransactionalIdNotFound));
+        };
+
+        if row.try_get::<_, i64>(0)? != producer_id {
+            return Err(Error::Api(ErrorCode::UnknownProducerId));
+        }
+
+        if !row.try_get::<_, bool>(1)? {
+            return Err(Error::Api(ErrorCode::ProducerFenced));
+        }
+
+        let mut enrolled = BTreeSet::new();
+
+        for row in self
+            .prepare_query(
+                &c,
+                include_sql!("pg/txn_topition_select_by_txn.sql").as_str(),
+                &[
+                    &self.cluster,
+                    &transaction_id,
+                    &producer_id,
+                    &producer_epoch,
+                ],
+                "txn_enrolled",
+            )
+            .await
+            .inspect_err(|err| error!(?err, cluster = self.cluster, transaction_id))?
+        {
+            let topic = row.try_get::<_, String>(0)?;
+            let partition = row.try_get::<_, i32>(1)?;
+            _ = enrolled.insert(Topition::new(topic, partition));
+        }
+
+        Ok(enrolled)
+    }
+
+    // complete a prepared transaction, committing any offsets
+    async fn complete_in_tx(&self, txn: &Txn, tx: &Transaction<'_>) -> Result<()> {
+        debug!(?txn);
+
+        _ = self
+            .tx_prepare_execute(
+                tx,
+                include_sql!("pg/txn_produce_offset_delete_by_txn.sql").as_str(),
+                &[
+                    &self.cluster,
+                    &txn.name,
+                    &txn.producer_id,
+                    &txn.producer_epoch,
+                ],
+                "complete_in_tx",
+            )
+            .await?;
+
+        _ = self
+            .tx_prepare_execute(
+                tx,
+                include_sql!("pg/txn_topition_delete_by_txn.sql").as_str(),
+                &[
+                    &self.cluster,
+                    &txn.name,
+                    &txn.producer_id,
+                    &txn.producer_epoch,
+                ],
+                "complete_in_tx",
+            )
+            .await?;
+
+        if txn.status == TxnState::PrepareCommit {
+            _ = self
+                .tx_prepare_execute(
+                    tx,
+                    include_sql!("pg/consumer_offset_insert_from_txn.sql").as_str(),
+                    &[
+                        &self.cluster,
+                        &txn.name,
+                        &txn.producer_id,
+                        &txn.producer_epoch,
+                    ],
+                    "complete_in_tx",
+                )
+                .await?;
+        }
+
+        _ = self
+            .tx_prepare_execute(
+                tx,
+                include_sql!("pg/txn_offset_commit_tp_delete_by_txn.sql").as_str(),
+                &[
+                    &self.cluster,
+                    &txn.name,
+                    &txn.producer_id,
+                    &txn.producer_epoch,
+                ],
+                "complete_in_tx",
+            )
+            .await?;
+
+        _ = self
+            .tx_prepare_execute(
+                tx,
+                include_sql!("pg/txn_offset_commit_delete_by_txn.sql").as_str(),
+                &[
+                    &self.cluster,
+                    &txn.name,
+                    &txn.producer_id,
+                    &txn.producer_epoch,
+                ],
+                "complete_in_tx",
+            )
+            .await?;
+
+        let outcome = if txn.status == TxnState::PrepareCommit {
+            String::from(TxnState::Committed)
+        } else if txn.status == TxnState::PrepareAbort {
+            String::from(TxnState::Aborted)
+        } else {
+            String::from(txn.status)
+        };
+
+        _ = self
+            .tx_prepare_execute(
+                tx,
+                include_sql!("pg/txn_status_update.sql").as_str(),
+                &[
+                    &self.cluster,
+                    &txn.name,
+                    &txn.producer_id,
+                    &txn.producer_epoch,
+                    &outcome,
+                    &self.clock.now(),
+                ],
+                "complete_in_tx",
+            )
+            .await?;
+
+        Ok(())
+    }
+
     async fn end_in_tx(
         &mut self,
         transaction_id: &str,
@@ -976,102 +1205,7 @@ impl Postgres {
             debug!(?txns);
 
             for txn in txns {
-                debug!(?txn);
-
-                _ = self
-                    .tx_prepare_execute(
-                        tx,
-                        include_sql!("pg/txn_produce_offset_delete_by_txn.sql").as_str(),
-                        &[
-                            &self.cluster,
-                            &txn.name,
-                            &txn.producer_id,
-                            &txn.producer_epoch,
-                        ],
-                        "end_in_tx",
-                    )
-                    .await?;
-
-                _ = self
-                    .tx_prepare_execute(
-                        tx,
-                        include_sql!("pg/txn_topition_delete_by_txn.sql").as_str(),
-                        &[
-                            &self.cluster,
-                            &txn.name,
-                            &txn.producer_id,
-                            &txn.producer_epoch,
-                        ],
-                        "end_in_tx",
-                    )
-                    .await?;
-
-                if txn.status == TxnState::PrepareCommit {
-                    _ = self
-                        .tx_prepare_execute(
-                            tx,
-                            include_sql!("pg/consumer_offset_insert_from_txn.sql").as_str(),
-                            &[
-                                &self.cluster,
-                                &txn.name,
-                                &txn.producer_id,
-                                &txn.producer_epoch,
-                            ],
-                            "end_in_tx",
-                        )
-                        .await?;
-                }
-
-                _ = self
-                    .tx_prepare_execute(
-                        tx,
-                        include_sql!("pg/txn_offset_commit_tp_delete_by_txn.sql").as_str(),
-                        &[
-                            &self.cluster,
-                            &txn.name,
-                            &txn.producer_id,
-                            &txn.producer_epoch,
-                        ],
-                        "end_in_tx",
-                    )
-                    .await?;
-
-                _ = self
-                    .tx_prepare_execute(
-                        tx,
-                        include_sql!("pg/txn_offset_commit_delete_by_txn.sql").as_str(),
-                        &[
-                            &self.cluster,
-                            &txn.name,
-                            &txn.producer_id,
-                            &txn.producer_epoch,
-                        ],
-                        "end_in_tx",
-                    )
-                    .await?;
-
-                let outcome = if txn.status == TxnState::PrepareCommit {
-                    String::from(TxnState::Committed)
-                } else if txn.status == TxnState::PrepareAbort {
-                    String::from(TxnState::Aborted)
-                } else {
-                    String::from(txn.status)
-                };
-
-                _ = self
-                    .tx_prepare_execute(
-                        tx,
-                        include_sql!("pg/txn_status_update.sql").as_str(),
-                        &[
-                            &self.cluster,
-                            &txn.name,
-                            &txn.producer_id,
-                            &txn.producer_epoch,
-                            &outcome,
-                        ],
-                        "end_in_tx",
-                    )
-                    .await?;
+                self.complete_in_tx(&txn, tx).await?;
             }
         } else {
             debug!(?overlaps);
@@ -1091,6 +1225,7 @@ impl Postgres {
                     &producer_id,
                     &producer_epoch,
                     &outcome,
+                    &self.clock.now(),
                 ],
                 "end_in_tx",
             )
@@ -1107,8 +1242,37 @@ impl Postgres {
     }
 }
 
+// an offset and timestamp row, or offset zero when there are no records
+fn list_offset_response(
+    row: Option<Row>,
+) -> Result<ListOffsetResponse, tokio_postgres::error::Error> {
+    debug!(?row);
+
+    row.map_or(
+        Ok(ListOffsetResponse {
+            timestamp: None,
+            offset: Some(0),
+            ..Default::default()
+        }),
+        |row| {
+            row.try_get::<_, i64>(0).and_then(|offset| {
+                row.try_get::<_, SystemTime>(1)
+                    .map(|timestamp| ListOffsetResponse {
+                        timestamp: Some(timestamp),
+                        offset: Some(offset),
+                        ..Default::default()
+                    })
+            })
+        },
+    )
+}
+
 #[async_trait]
 impl Storage for Postgres {
+    fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
     async fn register_broker(
         &mut self,
         broker_registration: BrokerRegistrationRequest,
@@ -1133,35 +1297,113 @@ impl Storage for Postgres {
             .await
             .inspect(|n| debug!(cluster = self.cluster, n))?;
 
+        let host = self.advertised_listener.host_str().unwrap_or("0.0.0.0");
+        let port = i32::from(self.advertised_listener.port().unwrap_or(9092));
+
+        _ = self
+            .prepare_execute(
+                &c,
+                include_sql!("pg/broker_insert.sql").as_str(),
+                &[
+                    &broker_registration.cluster_id,
+                    &broker_registration.broker_id,
+                    &host,
+                    &port,
+                    &broker_registration.rack,
+                    &broker_registration.incarnation_id,
+                    &self.clock.now(),
+                ],
+                "register_broker",
+            )
+            .await
+            .inspect(|n| debug!(cluster = self.cluster, n))?;
+
         Ok(())
     }
 
     async fn brokers(&mut self) -> Result<Vec<DescribeClusterBroker>> {
         debug!(cluster = self.cluster);
 
-        let broker_id = self.node;
-        let host = self
-            .advertised_listener
-            .host_str()
-            .unwrap_or("0.0.0.0")
-            .into();
-        let port = self.advertised_listener.port().unwrap_or(9092).into();
-        let rack = None;
+        let c = self.connection().await?;
+
+        self.prepare_query(
+            &c,
+            include_sql!("pg/broker_metadata_select.sql").as_str(),
+            &[&self.cluster, &(self.clock.now() - BROKER_LIVENESS)],
+            "brokers",
+        )
+        .await
+        .map(|rows| {
+            rows.iter()
+                .map(|row| DescribeClusterBroker {
+                    broker_id: row.get(0),
+                    host: row.get(1),
+                    port: row.get(2),
+                    rack: row.get(3),
+                })
+                .collect()
+        })
+        .map_err(Into::into)
+    }
+
+    async fn partition_count(&mut self) -> Result<(i64, i64)> {
+        debug!(cluster = self.cluster);
+
+        let c = self.connection().await?;
 
-        Ok(vec![DescribeClusterBroker {
-            broker_id,
-            host,
-            port,
-            rack,
-        }])
+        self.prepare_query_one(
+            &c,
+            include_sql!("pg/topic_partition_count.sql").as_str(),
+            &[&self.cluster],
+            "partition_count",
+        )
+        .await
+        .map(|row| (row.get(0), row.get(1)))
+        .map_err(Into::into)
     }
 
     async fn create_topic(&mut self, topic: CreatableTopic, validate_only: bool) -> Result<Uuid> {
-        debug!(cluster = self.cluster, ?topic, validate_only);
+        self.create_topic_within(topic, validate_only, TopicLimit::default())
+            .await
+    }
+
+    async fn create_topic_within(
+        &mut self,
+        topic: CreatableTopic,
+        validate_only: bool,
+        limit: TopicLimit,
+    ) -> Result<Uuid> {
+        debug!(cluster = self.cluster, ?topic, validate_only, ?limit);
 
         let mut c = self.connection().await?;
         let tx = c.transaction().await?;
 
+        if !limit.is_unlimited() {
+            // serialize topic creation in the cluster while it is counted
+            _ = self
+                .tx_prepare_query_one(
+                    &tx,
+                    include_sql!("pg/cluster_lock.sql").as_str(),
+                    &[&self.cluster],
+                    "create_topic",
+                )
+                .await?;
+
+            let (topics, partitions) = self
+                .tx_prepare_query_one(
+                    &tx,
+                    include_sql!("pg/topic_partition_count.sql").as_str(),
+                    &[&self.cluster],
+                    "create_topic",
+                )
+                .await
+                .map(|row| (row.get::<_, i64>(0), row.get::<_, i64>(1)))?;
+
+            if let Some(violation) = limit.violation(topics, partitions, topic.num_partitions) {
+                return Err(Error::TopicLimit(violation));
+            }
+        }
+
         let topic_uuid = self
             .tx_prepare_query_one(
                 &tx,
@@ -1250,120 +1492,99 @@ impl Storage for Postgres {
     ) -> Result<Vec<DeleteRecordsTopicResult>> {
         debug!(cluster = self.cluster, ?topics);
 
-        let c = self.connection().await?;
-
-        let delete_records = c
-            .prepare(concat!(
-                "delete from record",
-                " using topic, cluster",
-                " where",
-                " cluster.name=$1",
-                " and topic.name = $2",
-                " and record.partition = $3",
-                " and record.id >= $4",
-                " and topic.cluster = cluster.id",
-                " and record.topic = topic.id",
-            ))
-            .await
-            .inspect_err(|err| error!(?err, ?topics))?;
+        let mut c = self.connection().await?;
+        let tx = c.transaction().await?;
 
         let mut responses = vec![];
 
         for topic in topics {
             let mut partition_responses = vec![];
 
-            if let Some(ref partitions) = topic.partitions {
-                for partition in partitions {
-                    _ = c
-                        .execute(
-                            &delete_records,
-                            &[
-                                &self.cluster,
-                                &topic.name,
-                                &partition.partition_index,
-                                &partition.offset,
-                            ],
-                        )
-                        .await
-                        .inspect_err(|err| {
-                            let cluster = self.cluster.as_str();
-                            let topic = topic.name.as_str();
-                            let partition_index = partition.partition_index;
-                            let offset = partition.offset;
+            for partition in topic.partitions.as_deref().unwrap_or_default() {
+                let topition = Topition::new(topic.name.as_str(), partition.partition_index);
 
-                            error!(?err, ?cluster, ?topic, ?partition_index, ?offset)
-                        })?;
+                let (low, high) = match self.watermark_select_for_update(&topition, &tx).await {
+                    Ok((low, high)) => (low.unwrap_or_default(), high.unwrap_or_default()),
 
-                    let prepared = c
-                        .prepare(concat!(
-                            "select",
-                            " id as offset",
-                            " from",
-                            " record",
-                            " join (",
-                            " select",
-                            " coalesce(min(record.id), (select last_value from record_id_seq)) as offset",
-                            " from record, topic, cluster",
-                            " where",
-                            " topic.cluster = cluster.id",
-                            " and cluster.name = $1",
-                            " and topic.name = $2",
-                            " and record.partition = $3",
-                            " and record.topic = topic.id) as minimum",
-                            " on record.id = minimum.offset",
-                        ))
-                        .await
-                        .inspect_err(|err| {
-                            let cluster = self.cluster.as_str();
-                            let topic = topic.name.as_str();
-                            let partition_index = partition.partition_index;
-                            let offset = partition.offset;
+                    Err(Error::Api(error_code)) => {
+                        partition_responses.push(DeleteRecordsPartitionResult {
+                            partition_index: partition.partition_index,
+                            low_watermark: -1,
+                            error_code: error_code.into(),
+                        });
+                        continue;
+                    }
 
-                            error!(?err, ?cluster, ?topic, ?partition_index, ?offset)
-                        })?;
+                    Err(otherwise) => return Err(otherwise),
+                };
 
-                    let partition_result = c
-                        .query_opt(
-                            &prepared,
-                            &[&self.cluster, &topic.name, &partition.partition_index],
-                        )
-                        .await
-                        .inspect_err(|err| {
-                            let cluster = self.cluster.as_str();
-                            let topic = topic.name.as_str();
-                            let partition_index = partition.partition_index;
-                            let offset = partition.offset;
+                let offset = if partition.offset == -1 {
+                    high
+                } else {
+                    partition.offset
+                };
 
-                            error!(?err, ?cluster, ?topic, ?partition_index, ?offset)
-                        })
-                        .map_or(
-                            Ok(DeleteRecordsPartitionResult {
-                                partition_index: partition.partition_index,
-                                low_watermark: 0,
-                                error_code: ErrorCode::UnknownServerError.into(),
-                            }),
-                            |row| {
-                                row.map_or(
-                                    Ok(DeleteRecordsPartitionResult {
-                                        partition_index: partition.partition_index,
-                                        low_watermark: 0,
-                                        error_code: ErrorCode::UnknownServerError.into(),
-                                    }),
-                                    |row| {
-                                        row.try_get::<_, i64>(0).map(|low_watermark| {
-                                            DeleteRecordsPartitionResult {
-                                                partition_index: partition.partition_index,
-                                                low_watermark,
-                                                error_code: ErrorCode::None.into(),
-                                            }
-                                        })
-                                    },
-                                )
-                            },
-                        )?;
+                debug!(?topition, low, high, offset);
 
-                    partition_responses.push(partition_result);
+                if offset < 0 || offset > high {
+                    partition_responses.push(DeleteRecordsPartitionResult {
+                        partition_index: partition.partition_index,
+                        low_watermark: low,
+                        error_code: ErrorCode::OffsetOutOfRange.into(),
+                    });
+                    continue;
                 }
+
+                if offset > low {
+                    for (sql, nickname) in [
+                        (
+                            include_sql!("pg/header_delete_before_offset.sql"),
+                            "header_delete_before_offset",
+                        ),
+                        (
+                            include_sql!("pg/record_delete_before_offset.sql"),
+                            "record_delete_before_offset",
+                        ),
+                    ] {
+                        _ = self
+                            .tx_prepare_execute(
+                                &tx,
+                                sql.as_str(),
+                                &[
+                                    &self.cluster,
+                                    &topition.topic(),
+                                    &topition.partition(),
+                                    &offset,
+                                ],
+                                nickname,
+                            )
+                            .await
+                            .inspect(|n| debug!(?topition, offset, n))
+                            .inspect_err(|err| error!(?err, ?topition, offset))?;
+                    }
+
+                    _ = self
+                        .tx_prepare_execute(
+                            &tx,
+                            include_sql!("pg/watermark_update.sql").as_str(),
+                            &[
+                                &self.cluster,
+                                &topition.topic(),
+                                &topition.partition(),
+                                &offset,
+                                &high,
+                            ],
+                            "delete_records",
+                        )
+                        .await
+                        .inspect_err(|err| error!(?err, ?topition, offset))?;
+                }
+
+                partition_responses.push(DeleteRecordsPartitionResult {
+                    partition_index: partition.partition_index,
+                    low_watermark: offset.max(low),
+                    error_code: ErrorCode::None.into(),
+                });
             }
 
             responses.push(DeleteRecordsTopicResult {
@@ -1371,6 +1592,9 @@ impl Storage for Postgres {
                 partitions: Some(partition_responses),
             });
         }
+
+        tx.commit().await.inspect_err(|err| error!(?err))?;
+
         Ok(responses)
     }
 
@@ -1598,6 +1822,31 @@ impl Storage for Postgres {
         Ok(high)
     }
 
+    async fn produce_many(
+        &mut self,
+        transaction_id: Option<&str>,
+        entries: Vec<(Topition, deflated::Batch)>,
+    ) -> Result<Vec<i64>> {
+        debug!(cluster = self.cluster, transaction_id, ?entries);
+
+        let mut c = self.connection().await?;
+
+        let tx = c.transaction().await?;
+
+        let mut highs = Vec::with_capacity(entries.len());
+
+        for (topition, deflated) in entries {
+            highs.push(
+                self.produce_in_tx(transaction_id, &topition, deflated, &tx)
+                    .await?,
+            );
+        }
+
+        tx.commit().await?;
+
+        Ok(highs)
+    }
+
     async fn fetch(
         &mut self,
         topition: &Topition,
@@ -1647,6 +1896,7 @@ impl Storage for Postgres {
 
         if let Some(first) = records.first() {
             let mut batch_builder = inflated::Batch::builder()
+                .partition_leader_epoch(LEADER_EPOCH)
                 .base_offset(
                     first
                         .try_get::<_, i64>(0)
@@ -1701,6 +1951,7 @@ impl Storage for Postgres {
                     batches.push(batch_builder.build().and_then(TryInto::try_into)?);
 
                     batch_builder = inflated::Batch::builder()
+                        .partition_leader_epoch(LEADER_EPOCH)
                         .base_offset(
                             record
                                 .try_get::<_, i64>(0)
@@ -1833,7 +2084,7 @@ impl Storage for Postgres {
             .unwrap_or_default();
 
         let last_stable = row
-            .try_get::<_, Option<i64>>(1)
+            .try_get::<_, Option<i64>>(2)
             .inspect_err(|err| error!(?topition, ?err))?
             .unwrap_or(high_watermark);
 
@@ -1846,6 +2097,126 @@ impl Storage for Postgres {
         })
     }
 
+    async fn tier_records(&mut self, topition: &Topition, offset: i64) -> Result<ErrorCode> {
+        debug!(cluster = self.cluster, ?topition, offset);
+
+        let mut c = self.connection().await?;
+        let tx = c.transaction().await?;
+
+        let high = match self.watermark_select_for_update(topition, &tx).await {
+            Ok((_, high)) => high.unwrap_or_default(),
+            Err(Error::Api(error_code)) => return Ok(error_code),
+            Err(otherwise) => return Err(otherwise),
+        };
+
+        if offset < 0 || offset > high {
+            return Ok(ErrorCode::OffsetOutOfRange);
+        }
+
+        _ = self
+            .tx_prepare_execute(
+                &tx,
+                include_sql!("pg/watermark_update_tiered.sql").as_str(),
+                &[
+                    &self.cluster,
+                    &topition.topic(),
+                    &topition.partition(),
+                    &offset,
+                    &self.clock.now(),
+                ],
+                "tier_records",
+            )
+            .await
+            .inspect_err(|err| error!(?err, ?topition, offset))?;
+
+        tx.commit().await?;
+
+        Ok(ErrorCode::None)
+    }
+
+    async fn topic_exists(&mut self, topic: &TopicId) -> Result<bool> {
+        debug!(cluster = self.cluster, ?topic);
+        let c = self.connection().await?;
+
+        match topic {
+            TopicId::Id(id) => {
+                self.prepare_query_opt(
+                    &c,
+                    include_sql!("pg/topic_select_uuid.sql").as_str(),
+                    &[&self.cluster, id],
+                    "topic_exists",
+                )
+                .await
+            }
+
+            TopicId::Name(name) => {
+                self.prepare_query_opt(
+                    &c,
+                    include_sql!("pg/topic_select_name.sql").as_str(),
+                    &[&self.cluster, name],
+                    "topic_exists",
+                )
+                .await
+            }
+        }
+        .map(|row| row.is_some())
+        .inspect_err(|err| error!(?topic, ?err))
+        .map_err(Into::into)
+    }
+
+    async fn partition_exists(&mut self, topition: &Topition) -> Result<bool> {
+        debug!(cluster = self.cluster, ?topition);
+        let c = self.connection().await?;
+
+        self.prepare_query_opt(
+            &c,
+            include_sql!("pg/topition_select.sql").as_str(),
+            &[&self.cluster, &topition.topic(), &topition.partition()],
+            "partition_exists",
+        )
+        .await
+        .map(|row| row.is_some())
+        .inspect_err(|err| error!(?topition, ?err))
+        .map_err(Into::into)
+    }
+
+    async fn in_sync_replicas(&mut self, topition: &Topition) -> Result<i32> {
+        debug!(cluster = self.cluster, ?topition);
+
+        // records are not replicated beyond the database
+        Ok(1)
+    }
+
+    async fn watermarks(&mut self, topition: &Topition) -> Result<(i64, i64)> {
+        debug!(cluster = self.cluster, ?topition);
+        let c = self.connection().await?;
+
+        let row = self
+            .prepare_query_opt(
+                &c,
+                include_sql!("pg/watermark_select.sql").as_str(),
+                &[&self.cluster, &topition.topic(), &topition.partition()],
+                "watermarks",
+            )
+            .await
+            .inspect_err(|err| error!(?topition, ?err))?
+            .ok_or(Error::Api(ErrorCode::UnknownTopicOrPartition))?;
+
+        let low = row
+            .try_get::<_, Option<i64>>(0)
+            .inspect_err(|err| error!(?topition, ?err))?
+            .unwrap_or_default();
+
+        let high = row
+            .try_get::<_, Option<i64>>(1)
+            .inspect_err(|err| error!(?topition, ?err))?
+            .unwrap_or_default();
+
+        debug!(cluster = self.cluster, ?topition, low, high);
+
+        Ok((low, high))
+    }
+
     async fn offset_commit(
         &mut self,
         group: &str,
@@ -1900,7 +2271,7 @@ impl Storage for Postgres {
                             &group,
                             &offset.offset,
                             &offset.leader_epoch,
-                            &offset.timestamp,
+                            &offset.timestamp.unwrap_or(self.clock.now()),
                             &offset.metadata,
                         ],
                         "offset_commit",
@@ -1963,6 +2334,83 @@ impl Storage for Postgres {
         Ok(results)
     }
 
+    async fn committed_offsets(
+        &mut self,
+        group_id: &str,
+    ) -> Result<BTreeMap<Topition, OffsetCommitRequest>> {
+        debug!(group_id);
+
+        let mut results = BTreeMap::new();
+
+        let c = self.connection().await?;
+
+        for row in self
+            .prepare_query(
+                &c,
+                include_sql!("pg/consumer_offset_select_by_group.sql").as_str(),
+                &[&self.cluster, &group_id],
+                "committed_offsets",
+            )
+            .await
+            .inspect_err(|err| error!(?err))?
+        {
+            let topic = row.try_get::<_, String>(0)?;
+            let partition = row.try_get::<_, i32>(1)?;
+
+            let offset = OffsetCommitRequest {
+                offset: row.try_get::<_, i64>(2)?,
+                leader_epoch: row.try_get::<_, Option<i32>>(3)?,
+                timestamp: row.try_get::<_, Option<SystemTime>>(4)?,
+                metadata: row.try_get::<_, Option<String>>(5)?,
+            };
+
+            debug!(group_id, topic, partition, ?offset);
+
+            assert_eq!(
+                None,
+                results.insert(Topition::new(topic, partition), offset)
+            );
+        }
+
+        Ok(results)
+    }
+
+    async fn pending_offset_commits(
+        &mut self,
+        group_id: &str,
+        topics: &[Topition],
+    ) -> Result<BTreeSet<Topition>> {
+        debug!(cluster = self.cluster, group_id, ?topics);
+
+        let c = self.connection().await?;
+
+        // the pending commits of every requested partition in one query
+        let (names, partitions): (Vec<&str>, Vec<i32>) = topics
+            .iter()
+            .map(|topition| (topition.topic(), topition.partition()))
+            .unzip();
+
+        let mut pending = BTreeSet::new();
+
+        for row in self
+            .prepare_query(
+                &c,
+                include_sql!("pg/txn_offset_commit_tp_select.sql").as_str(),
+                &[&self.cluster, &group_id, &names, &partitions],
+                "pending_offset_commits",
+            )
+            .await
+            .inspect_err(|err| error!(?err, cluster = self.cluster, group_id))?
+        {
+            let topic = row.try_get::<_, String>(0)?;
+            let partition = row.try_get::<_, i32>(1)?;
+
+            _ = pending.insert(Topition::new(topic, partition));
+        }
+
+        Ok(pending)
+    }
+
     async fn offset_fetch(
         &mut self,
         group_id: Option<&str>,
@@ -2022,36 +2470,55 @@ impl Storage for Postgres {
         let mut responses = vec![];
 
         for (topition, offset_type) in offsets {
-            let query = match (offset_type, isolation_level) {
-                (ListOffsetRequest::Earliest, _) => include_sql!("pg/list_earliest_offset.sql"),
-                (ListOffsetRequest::Latest, IsolationLevel::ReadCommitted) => {
-                    include_sql!("pg/list_latest_offset_committed.sql")
-                }
-                (ListOffsetRequest::Latest, IsolationLevel::ReadUncommitted) => {
-                    include_sql!("pg/list_latest_offset_uncommitted.sql")
-                }
-                (ListOffsetRequest::Timestamp(_), _) => {
-                    include_sql!("pg/list_latest_offset_timestamp.sql")
+            debug!(cluster = self.cluster, ?topition, ?offset_type);
+
+            let list_offset = match offset_type {
+                ListOffsetRequest::EarliestLocal | ListOffsetRequest::LatestTiered => {
+                    let (low, tiered) = self
+                        .prepare_query_opt(
+                            &c,
+                            include_sql!("pg/watermark_select_tiered.sql").as_str(),
+                            &[&self.cluster, &topition.topic(), &topition.partition()],
+                            "list_offsets",
+                        )
+                        .await
+                        .inspect_err(|err| error!(?err, cluster = self.cluster, ?topition))?
+                        .map_or(Ok((None, None)), |row| {
+                            row.try_get::<_, Option<i64>>(0).and_then(|low| {
+                                row.try_get::<_, Option<i64>>(1).map(|tiered| (low, tiered))
+                            })
+                        })?;
+
+                    ListOffsetResponse {
+                        offset: offset_type.tiered(low.unwrap_or_default(), tiered),
+                        ..Default::default()
+                    }
                 }
-            };
 
-            debug!(?query);
+                ListOffsetRequest::Earliest | ListOffsetRequest::Latest => {
+                    let query = if *offset_type == ListOffsetRequest::Earliest {
+                        include_sql!("pg/list_earliest_offset.sql")
+                    } else if isolation_level == IsolationLevel::ReadCommitted {
+                        include_sql!("pg/list_latest_offset_committed.sql")
+                    } else {
+                        include_sql!("pg/list_latest_offset_uncommitted.sql")
+                    };
 
-            let list_offset = match offset_type {
-                ListOffsetRequest::Earliest | ListOffsetRequest::Latest => self
-                    .prepare_query_opt(
+                    self.prepare_query_opt(
                         &c,
                         query.as_str(),
                         &[&self.cluster, &topition.topic(), &topition.partition()],
                         "list_offsets",
                     )
                     .await
-                    .inspect_err(|err| error!(?err, cluster = self.cluster, ?topition)),
+                    .inspect_err(|err| error!(?err, cluster = self.cluster, ?topition))
+                    .and_then(list_offset_response)?
+                }
 
                 ListOffsetRequest::Timestamp(timestamp) => self
                     .prepare_query_opt(
                         &c,
-                        query.as_str(),
+                        include_sql!("pg/list_latest_offset_timestamp.sql").as_str(),
                         &[
                             &self.cluster.as_str(),
                             &topition.topic(),
@@ -2061,52 +2528,16 @@ impl Storage for Postgres {
                         "list_offsets",
                     )
                     .await
-                    .inspect_err(|err| error!(?err)),
-            }
-            .inspect_err(|err| {
-                error!(?err, cluster = self.cluster, ?topition);
-            })
-            .inspect(|result| debug!(?result))?
-            .map_or_else(
-                || {
-                    let timestamp = None;
-                    let offset = Some(0);
-                    debug!(
-                        cluster = self.cluster,
-                        ?topition,
-                        ?offset_type,
-                        offset,
-                        ?timestamp
-                    );
-
-                    Ok(ListOffsetResponse {
-                        timestamp,
-                        offset,
-                        ..Default::default()
-                    })
-                },
-                |row| {
-                    debug!(?row);
-
-                    row.try_get::<_, i64>(0).map(Some).and_then(|offset| {
-                        row.try_get::<_, SystemTime>(1).map(Some).map(|timestamp| {
-                            debug!(
-                                cluster = self.cluster,
-                                ?topition,
-                                ?offset_type,
-                                offset,
-                                ?timestamp
-                            );
+                    .inspect_err(|err| error!(?err, cluster = self.cluster, ?topition))
+                    .and_then(list_offset_response)?,
+            };
 
-                            ListOffsetResponse {
-                                timestamp,
-                                offset,
-                                ..Default::default()
-                            }
-                        })
-                    })
-                },
-            )?;
+            debug!(
+                cluster = self.cluster,
+                ?topition,
+                ?offset_type,
+                ?list_offset
+            );
 
             responses.push((topition.clone(), list_offset));
         }
@@ -2312,7 +2743,7 @@ impl Storage for Postgres {
                                 Err(reason) => {
                                     debug!(?reason);
                                     MetadataResponseTopic {
-                                        error_code: ErrorCode::UnknownTopicOrPartition.into(),
+                                        error_code: ErrorCode::UnknownTopicId.into(),
                                         name: None,
                                         topic_id: Some(id.into_bytes()),
                                         is_internal: Some(false),
@@ -2429,6 +2860,10 @@ impl Storage for Postgres {
         })
     }
 
+    async fn metadata_snapshot(&mut self) -> Result<MetadataResponse> {
+        self.metadata(None).await
+    }
+
     async fn describe_config(
         &mut self,
         name: &str,
@@ -2711,8 +3146,12 @@ impl Storage for Postgres {
         Ok(responses)
     }
 
-    async fn list_groups(&mut self, states_filter: Option<&[String]>) -> Result<Vec<ListedGroup>> {
-        debug!(?states_filter);
+    async fn list_groups(
+        &mut self,
+        states_filter: Option<&[String]>,
+        types_filter: Option<&[String]>,
+    ) -> Result<Vec<ListedGroup>> {
+        debug!(?states_filter, ?types_filter);
 
         let c = self.connection().await.inspect_err(|err| error!(?err))?;
 
@@ -2730,11 +3169,24 @@ impl Storage for Postgres {
         {
             let group_id = row.try_get::<_, String>(0)?;
 
+            // groups without detail only have committed offsets
+            //
+            let group_type = row
+                .try_get::<_, Option<Value>>(1)?
+                .map(serde_json::from_value::<GroupDetail>)
+                .transpose()?
+                .map(|detail| detail.group_type)
+                .unwrap_or_default();
+
+            if !group_type.is_listed(types_filter) {
+                continue;
+            }
+
             listed_groups.push(ListedGroup {
                 group_id,
                 protocol_type: "consumer".into(),
                 group_state: Some("unknown".into()),
-                group_type: None,
+                group_type: Some(group_type.to_string()),
             });
         }
 
@@ -2750,39 +3202,86 @@ impl Storage for Postgres {
         let mut results = vec![];
 
         if let Some(group_ids) = group_ids {
-            let c = self.connection().await?;
+            let mut c = self.connection().await?;
 
-            let consumer_offset = c
-                .prepare(include_sql!("pg/consumer_offset_delete_by_cg.sql").as_str())
-                .await
-                .inspect_err(|err| error!(?err))?;
+            for group_id in group_ids {
+                let tx = c.transaction().await?;
+
+                // lock the group so that the emptiness check and the delete
+                // happen as one, with the detail delete conditional on the
+                // e_tag that was read
+                let Some(row) = tx
+                    .query_opt(
+                        include_sql!("pg/consumer_group_select_for_delete.sql").as_str(),
+                        &[&self.cluster, &group_id],
+                    )
+                    .await
+                    .inspect_err(|err| error!(?err))?
+                else {
+                    results.push(DeletableGroupResult {
+                        group_id: group_id.into(),
+                        error_code: ErrorCode::GroupIdNotFound.into(),
+                    });
 
-            let group_detail = c
-                .prepare(include_sql!("pg/consumer_group_detail_delete_by_cg.sql").as_str())
-                .await
-                .inspect_err(|err| error!(?err))?;
+                    continue;
+                };
 
-            let group = c
-                .prepare(include_sql!("pg/consumer_group_delete.sql").as_str())
-                .await
-                .inspect_err(|err| error!(?err))?;
+                let e_tag = row
+                    .try_get::<_, Option<Uuid>>(0)
+                    .inspect_err(|err| error!(?err, group_id))?;
 
-            for group_id in group_ids {
-                _ = c
-                    .execute(&consumer_offset, &[&self.cluster, &group_id])
-                    .await
-                    .inspect_err(|err| error!(?err))?;
+                let detail = row
+                    .try_get::<_, Option<Value>>(1)
+                    .inspect_err(|err| error!(?err, group_id))?
+                    .map(serde_json::from_value::<GroupDetail>)
+                    .transpose()?;
+
+                if detail.is_some_and(|detail| !detail.members.is_empty()) {
+                    results.push(DeletableGroupResult {
+                        group_id: group_id.into(),
+                        error_code: ErrorCode::NonEmptyGroup.into(),
+                    });
+
+                    continue;
+                }
 
-                _ = c
-                    .execute(&group_detail, &[&self.cluster, &group_id])
+                if let Some(e_tag) = e_tag {
+                    let rows = tx
+                        .execute(
+                            include_sql!("pg/consumer_group_detail_delete_by_cg.sql").as_str(),
+                            &[&self.cluster, &group_id, &e_tag],
+                        )
+                        .await
+                        .inspect_err(|err| error!(?err))?;
+
+                    if rows == 0 {
+                        results.push(DeletableGroupResult {
+                            group_id: group_id.into(),
+                            error_code: ErrorCode::NonEmptyGroup.into(),
+                        });
+
+                        continue;
+                    }
+                }
+
+                _ = tx
+                    .execute(
+                        include_sql!("pg/consumer_offset_delete_by_cg.sql").as_str(),
+                        &[&self.cluster, &group_id],
+                    )
                     .await
                     .inspect_err(|err| error!(?err))?;
 
-                let rows = c
-                    .execute(&group, &[&self.cluster, &group_id])
+                let rows = tx
+                    .execute(
+                        include_sql!("pg/consumer_group_delete.sql").as_str(),
+                        &[&self.cluster, &group_id],
+                    )
                     .await
                     .inspect_err(|err| error!(?err))?;
 
+                tx.commit().await?;
+
                 results.push(DeletableGroupResult {
                     group_id: group_id.into(),
                     error_code: if rows == 0 {
@@ -2955,11 +3454,16 @@ impl Storage for Postgres {
         );
 
         match (producer_id, producer_epoch, transaction_id) {
-            (Some(-1), Some(-1), Some(transaction_id)) => {
+            // a new instance (-1, -1) or a re-initialization of the current
+            // epoch are both checked and bumped within the same transaction
+            (Some(producer_id), Some(producer_epoch), Some(transaction_id))
+                if (producer_id == -1 && producer_epoch == -1)
+                    || (producer_id >= 0 && producer_epoch >= 0) =>
+            {
                 let mut c = self.connection().await.inspect_err(|err| error!(?err))?;
                 let tx = c.transaction().await.inspect_err(|err| error!(?err))?;
 
-                if let Some(row) = self
+                let current = self
                     .tx_prepare_query_opt(
                         &tx,
                         include_sql!("pg/producer_epoch_for_current_txn.sql").as_str(),
@@ -2968,34 +3472,62 @@ impl Storage for Postgres {
                     )
                     .await
                     .inspect_err(|err| error!(?err))?
-                {
-                    let id: i64 = row.try_get(0).inspect_err(|err| error!(?err))?;
-                    let epoch: i16 = row.try_get(1).inspect_err(|err| error!(?err))?;
-                    let status = row
-                        .try_get::<_, Option<String>>(2)
-                        .inspect_err(|err| error!(?err))?
-                        .map_or(Ok(None), |status| {
-                            TxnState::from_str(status.as_str()).map(Some)
-                        })?;
+                    .map(|row| {
+                        let id: i64 = row.try_get(0).inspect_err(|err| error!(?err))?;
+                        let epoch: i16 = row.try_get(1).inspect_err(|err| error!(?err))?;
+                        let status = row
+                            .try_get::<_, Option<String>>(2)
+                            .inspect_err(|err| error!(?err))?
+                            .map_or(Ok(None), |status| {
+                                TxnState::from_str(status.as_str()).map(Some)
+                            })?;
+
+                        Ok::<_, Error>((id, epoch, status))
+                    })
+                    .transpose()?;
+
+                debug!(transaction_id, ?current);
 
-                    debug!(transaction_id, id, epoch, ?status);
+                if producer_id >= 0 {
+                    match reinitialized(
+                        current.map(|(id, epoch, _)| (id, epoch)),
+                        producer_id,
+                        producer_epoch,
+                    ) {
+                        ErrorCode::None => (),
 
-                    if let Some(TxnState::Begin) = status {
-                        let error = self
-                            .end_in_tx(transaction_id, id, epoch, false, &tx)
-                            .await?;
+                        error => {
+                            debug!(?current, producer_id, producer_epoch, ?error);
 
-                        if error != ErrorCode::None {
                             _ = tx
                                 .rollback()
                                 .await
-                                .inspect_err(|err| error!(?err, ?transaction_id, id, epoch));
+                                .inspect_err(|err| error!(?err, ?transaction_id));
 
-                            return Ok(ProducerIdResponse { error, id, epoch });
+                            return Ok(ProducerIdResponse {
+                                error,
+                                id: -1,
+                                epoch: -1,
+                            });
                         }
                     }
                 }
 
+                if let Some((id, epoch, Some(TxnState::Begin))) = current {
+                    let error = self
+                        .end_in_tx(transaction_id, id, epoch, false, &tx)
+                        .await?;
+
+                    if error != ErrorCode::None {
+                        _ = tx
+                            .rollback()
+                            .await
+                            .inspect_err(|err| error!(?err, ?transaction_id, id, epoch));
+
+                        return Ok(ProducerIdResponse { error, id, epoch });
+                    }
+                }
+
                 let (producer, epoch) = if let Some(row) = self
                     .tx_prepare_query_opt(
                         &tx,
@@ -3161,6 +3693,47 @@ impl Storage for Postgres {
         }
     }
 
+    async fn allocate_producer_id_block(&mut self, size: i32) -> Result<Range<i64>> {
+        debug!(cluster = self.cluster, size);
+
+        if size < 1 {
+            return Err(Error::Api(ErrorCode::InvalidRequest));
+        }
+
+        let mut c = self.connection().await.inspect_err(|err| error!(?err))?;
+        let tx = c.transaction().await.inspect_err(|err| error!(?err))?;
+
+        // serialize allocations so that the identities in a block are contiguous
+        _ = self
+            .tx_prepare_execute(
+                &tx,
+                include_sql!("pg/producer_lock.sql").as_str(),
+                &[],
+                "allocate_producer_id_block",
+            )
+            .await
+            .inspect_err(|err| error!(self.cluster, ?err))?;
+
+        let row = self
+            .tx_prepare_query_one(
+                &tx,
+                include_sql!("pg/producer_insert_block.sql").as_str(),
+                &[&self.cluster, &size],
+                "allocate_producer_id_block",
+            )
+            .await
+            .inspect_err(|err| error!(self.cluster, size, ?err))?;
+
+        let first: i64 = row.try_get(0)?;
+        let last: i64 = row.try_get(1)?;
+
+        tx.commit()
+            .await
+            .inspect_err(|err| error!(self.cluster, size, ?err))?;
+
+        Ok(first..last + 1)
+    }
+
     async fn txn_add_offsets(
         &mut self,
         transaction_id: &str,
@@ -3194,12 +3767,101 @@ impl Storage for Postgres {
                 let mut c = self.connection().await.inspect_err(|err| error!(?err))?;
                 let tx = c.transaction().await.inspect_err(|err| error!(?err))?;
 
+                if !self.txn_limit.is_unlimited() {
+                    // serialize enrolment in the cluster while the open
+                    // transactions and enrolled partitions are counted
+                    _ = self
+                        .tx_prepare_query_one(
+                            &tx,
+                            include_sql!("pg/cluster_lock.sql").as_str(),
+                            &[&self.cluster],
+                            "txn_add_partitions",
+                        )
+                        .await
+                        .inspect_err(|err| error!(?err, cluster = self.cluster))?;
+                }
+
+                let others = self
+                    .tx_prepare_query_one(
+                        &tx,
+                        include_sql!("pg/txn_select_open_count.sql").as_str(),
+                        &[&self.cluster, &transaction_id],
+                        "txn_add_partitions",
+                    )
+                    .await
+                    .and_then(|row| row.try_get::<_, i64>(0))
+                    .inspect_err(|err| error!(?err, cluster = self.cluster, transaction_id))?;
+
+                if let Some(error_code) = self.txn_limit.open(usize::try_from(others)?) {
+                    let mut results = vec![];
+
+                    for topic in topics {
+                        let mut results_by_partition = vec![];
+
+                        for partition_index in topic.partitions.unwrap_or(vec![]) {
+                            results_by_partition.push(AddPartitionsToTxnPartitionResult {
+                                partition_index,
+                                partition_error_code: i16::from(error_code),
+                            });
+                        }
+
+                        results.push(AddPartitionsToTxnTopicResult {
+                            name: topic.name,
+                            results_by_partition: Some(results_by_partition),
+                        })
+                    }
+
+                    return Ok(TxnAddPartitionsResponse::VersionZeroToThree(results));
+                }
+
+                let mut enrolled = BTreeSet::new();
+
+                for row in self
+                    .tx_prepare_query(
+                        &tx,
+                        include_sql!("pg/txn_topition_select_by_txn.sql").as_str(),
+                        &[
+                            &self.cluster,
+                            &transaction_id,
+                            &producer_id,
+                            &producer_epoch,
+                        ],
+                        "txn_add_partitions",
+                    )
+                    .await
+                    .inspect_err(|err| error!(?err, cluster = self.cluster, transaction_id))?
+                {
+                    let topic = row.try_get::<_, String>(0)?;
+                    let partition = row.try_get::<_, i32>(1)?;
+                    _ = enrolled.insert(Topition::new(topic, partition));
+                }
+
                 let mut results = vec![];
 
                 for topic in topics {
                     let mut results_by_partition = vec![];
 
                     for partition_index in topic.partitions.unwrap_or(vec![]) {
+                        let topition = Topition::new(topic.name.clone(), partition_index);
+
+                        if enrolled.contains(&topition) {
+                            results_by_partition.push(AddPartitionsToTxnPartitionResult {
+                                partition_index,
+                                partition_error_code: i16::from(ErrorCode::None),
+                            });
+
+                            continue;
+                        }
+
+                        if let Some(error_code) = self.txn_limit.enrol(enrolled.len()) {
+                            results_by_partition.push(AddPartitionsToTxnPartitionResult {
+                                partition_index,
+                                partition_error_code: i16::from(error_code),
+                            });
+
+                            continue;
+                        }
+
                         _ = self
                             .tx_prepare_execute(
                                 &tx,
@@ -3225,6 +3887,8 @@ impl Storage for Postgres {
                                 )
                             })?;
 
+                        _ = enrolled.insert(topition);
+
                         results_by_partition.push(AddPartitionsToTxnPartitionResult {
                             partition_index,
                             partition_error_code: i16::from(ErrorCode::None),
@@ -3246,6 +3910,7 @@ impl Storage for Postgres {
                             &transaction_id,
                             &producer_id,
                             &producer_epoch,
+                            &self.clock.now(),
                         ],
                         "txn_add_partitions",
                     )
@@ -3265,8 +3930,41 @@ impl Storage for Postgres {
                 Ok(TxnAddPartitionsResponse::VersionZeroToThree(results))
             }
 
-            TxnAddPartitionsRequest::VersionFourPlus { .. } => {
-                todo!()
+            TxnAddPartitionsRequest::VersionFourPlus { transactions } => {
+                let mut results = vec![];
+
+                for transaction in transactions {
+                    let topics = transaction.topics.unwrap_or_default();
+
+                    let outcome = if transaction.verify_only {
+                        let enrolled = self
+                            .txn_enrolled(
+                                transaction.transactional_id.as_str(),
+                                transaction.producer_id,
+                                transaction.producer_epoch,
+                            )
+                            .await;
+
+                        txn_verify_partitions(&topics, enrolled)
+                    } else {
+                        self.txn_add_partitions(TxnAddPartitionsRequest::VersionZeroToThree {
+                            transaction_id: transaction.transactional_id.clone(),
+                            producer_id: transaction.producer_id,
+                            producer_epoch: transaction.producer_epoch,
+                            topics: topics.clone(),
+                        })
+                        .await
+                        .map(|response| response.zero_to_three().to_vec())
+                    };
+
+                    results.push(txn_add_partitions_outcome(
+                        transaction.transactional_id.as_str(),
+                        &topics,
+                        outcome,
+                    ));
+                }
+
+                Ok(TxnAddPartitionsResponse::VersionFourPlus(results))
             }
         }
     }
@@ -3414,6 +4112,82 @@ impl Storage for Postgres {
 
         Ok(error_code)
     }
+
+    async fn txn_force_abort(&mut self, transaction_id: &str) -> Result<ErrorCode> {
+        debug!(cluster = ?self.cluster, transaction_id);
+
+        let mut c = self.connection().await.inspect_err(|err| error!(?err))?;
+        let tx = c.transaction().await?;
+
+        let Some(row) = self
+            .tx_prepare_query_opt(
+                &tx,
+                include_sql!("pg/producer_epoch_for_current_txn.sql").as_str(),
+                &[&self.cluster, &transaction_id],
+                "txn_force_abort",
+            )
+            .await
+            .inspect_err(|err| error!(?err))?
+        else {
+            return Ok(ErrorCode::TransactionalIdNotFound);
+        };
+
+        let producer_id: i64 = row.try_get(0).inspect_err(|err| error!(?err))?;
+        let producer_epoch: i16 = row.try_get(1).inspect_err(|err| error!(?err))?;
+
+        let status = row
+            .try_get::<_, Option<String>>(2)
+            .inspect_err(|err| error!(?err))?
+            .map_or(Ok(None), |status| {
+                TxnState::from_str(status.as_str()).map(Some)
+            })?;
+
+        let transaction_timeout_ms: i32 = row.try_get(3).inspect_err(|err| error!(?err))?;
+
+        debug!(
+            transaction_id,
+            producer_id,
+            producer_epoch,
+            ?status,
+            transaction_timeout_ms
+        );
+
+        match status {
+            // initializing the producer aborts the ongoing transaction
+            // writing abort markers, before bumping the epoch
+            Some(TxnState::Begin) => (),
+
+            // the outcome is decided with its markers written, waiting only
+            // on overlapping transactions, so it is completed as prepared
+            Some(status @ (TxnState::PrepareCommit | TxnState::PrepareAbort)) => {
+                self.complete_in_tx(
+                    &Txn {
+                        name: transaction_id.into(),
+                        producer_id,
+                        producer_epoch,
+                        status,
+                    },
+                    &tx,
+                )
+                .await?;
+            }
+
+            _otherwise => return Ok(ErrorCode::None),
+        }
+
+        tx.commit().await?;
+        drop(c);
+
+        // fencing the current producer epoch
+        self.init_producer(
+            Some(transaction_id),
+            transaction_timeout_ms,
+            Some(-1),
+            Some(-1),
+        )
+        .await
+        .map(|producer| producer.error)
+    }
 }
 
 fn default_hash<H>(h: &H) -> Uuid
@@ -3448,6 +4222,100 @@ fn remove_comments(commented: &str) -> String {
     })
 }
 
+// periodically closes connections that have been idle for too long,
+// until the returned reaper (and every clone of it) is dropped
+fn reap<M>(pool: managed::Pool<M>, idle_timeout: Duration) -> Arc<()>
+where
+    M: managed::Manager + 'static,
+{
+    let reaper = Arc::new(());
+    let alive = Arc::downgrade(&reaper);
+
+    _ = tokio::spawn(async move {
+        let mut interval = interval(idle_timeout);
+
+        loop {
+            _ = interval.tick().await;
+
+            if alive.strong_count() == 0 {
+                break;
+            }
+
+            let retained = pool.retain(|_, metrics| metrics.last_used() < idle_timeout);
+            debug!(
+                retained = retained.retained,
+                removed = retained.removed.len()
+            );
+        }
+    });
+
+    reaper
+}
+
+// acquire a connection from the pool, with a retriable error rather than
+// waiting indefinitely when the pool remains exhausted beyond the acquire
+// timeout
+async fn acquire<M>(
+    pool: &managed::Pool<M>,
+    connection_pool: &ConnectionPool,
+) -> Result<managed::Object<M>>
+where
+    M: managed::Manager<Error = tokio_postgres::Error>,
+{
+    let start = SystemTime::now();
+
+    let connection = if let Some(acquire_timeout) = connection_pool.acquire_timeout {
+        timeout(acquire_timeout, pool.get())
+            .await
+            .unwrap_or_else(|_elapsed| {
+                let status = pool.status();
+                warn!(?acquire_timeout, ?status);
+                Err(managed::PoolError::Timeout(managed::TimeoutType::Wait))
+            })
+    } else {
+        pool.get().await
+    };
+
+    let status = pool.status();
+
+    POOL_ACQUIRE_DURATION.record(
+        start
+            .elapsed()
+            .map_or(0, |duration| duration.as_millis() as u64),
+        &[KeyValue::new("acquired", connection.is_ok())],
+    );
+
+    POOL_IN_USE.record(status.size.saturating_sub(status.available) as u64, &[]);
+    POOL_WAITING.record(status.waiting as u64, &[]);
+
+    connection.map_err(|err| match err {
+        managed::PoolError::Timeout(_) => Error::Api(ErrorCode::RequestTimedOut),
+        otherwise => otherwise.into(),
+    })
+}
+
+static POOL_ACQUIRE_DURATION: LazyLock<Histogram<u64>> = LazyLock::new(|| {
+    METER
+        .u64_histogram("tansu_pg_pool_acquire_duration")
+        .with_unit("ms")
+        .with_description("The time waiting to acquire a connection from the pool in milliseconds")
+        .build()
+});
+
+static POOL_IN_USE: LazyLock<Gauge<u64>> = LazyLock::new(|| {
+    METER
+        .u64_gauge("tansu_pg_pool_in_use")
+        .with_description("The number of pooled connections in use")
+        .build()
+});
+
+static POOL_WAITING: LazyLock<Gauge<u64>> = LazyLock::new(|| {
+    METER
+        .u64_gauge("tansu_pg_pool_waiting")
+        .with_description("The number of requests waiting for a pooled connection")
+        .build()
+});
+
 static SQL_DURATION: LazyLock<Histogram<u64>> = LazyLock::new(|| {
     METER
         .u64_histogram("tansu_sql_duration")
@@ -3472,7 +4340,14 @@ static SQL_ERROR: LazyLock<Counter<u64>> = LazyLock::new(|| {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Error, Result};
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use crate::{ConnectionPool, Error, Result};
+    use deadpool::managed::{self, Metrics, RecycleResult};
+    use tansu_kafka_sans_io::ErrorCode;
     use tracing::subscriber::DefaultGuard;
     use tracing_subscriber::EnvFilter;
 
@@ -3520,4 +4395,84 @@ mod tests {
 
         Ok(())
     }
+
+    #[derive(Default)]
+    struct Unconnected {
+        created: AtomicUsize,
+    }
+
+    impl managed::Manager for Unconnected {
+        type Type = ();
+        type Error = tokio_postgres::Error;
+
+        async fn create(&self) -> Result<Self::Type, Self::Error> {
+            _ = self.created.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        async fn recycle(&self, _: &mut Self::Type, _: &Metrics) -> RecycleResult<Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn acquire_timeout() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let pool = managed::Pool::builder(Unconnected::default())
+            .max_size(1)
+            .build()?;
+
+        let connection_pool = ConnectionPool::default()
+            .max_size(1)
+            .acquire_timeout(Some(Duration::from_millis(50)));
+
+        let held = super::acquire(&pool, &connection_pool).await?;
+
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { super::acquire(&pool, &connection_pool).await.map(|_| ()) }
+        });
+
+        assert!(matches!(
+            super::acquire(&pool, &connection_pool).await,
+            Err(Error::Api(ErrorCode::RequestTimedOut))
+        ));
+
+        assert!(matches!(
+            waiting.await,
+            Ok(Err(Error::Api(ErrorCode::RequestTimedOut)))
+        ));
+
+        drop(held);
+        assert!(super::acquire(&pool, &connection_pool).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn idle_timeout() -> Result<()> {
+        let _guard = init_tracing()?;
+
+        let pool = managed::Pool::builder(Unconnected::default())
+            .max_size(1)
+            .build()?;
+
+        let connection_pool = ConnectionPool::default()
+            .max_size(1)
+            .idle_timeout(Some(Duration::from_millis(10)));
+
+        let _reaper = super::reap(pool.clone(), Duration::from_millis(10));
+
+        drop(super::acquire(&pool, &connection_pool).await?);
+        drop(super::acquire(&pool, &connection_pool).await?);
+        assert_eq!(1, pool.manager().created.load(Ordering::Relaxed));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        drop(super::acquire(&pool, &connection_pool).await?);
+        assert_eq!(2, pool.manager().created.load(Ordering::Relaxed));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,31 @@
+-- -*- mode: sql; sql-product: postgres; -*-
+-- Copyright ⓒ 2024 Peter Morgan <peter.james.morgan@gmail.com>
+--
+-- This program is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU Affero General Public License as
+-- published by the Free Software Foundation, either version 3 of the
+-- License, or (at your option) any later version.
+--
+-- This program is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY; without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU Affero General Public License for more details.
+--
+-- You should have received a copy of the GNU Affero General Public License
+-- along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+insert into broker
+(cluster, node, host, port, rack, incarnation, last_updated)
+select c.id, $2, $3, $4, $5, $6, $7
+from cluster c
+where c.name = $1
+
+on conflict (cluster, node)
+
+do update set
+
+host = excluded.host,
+port = excluded.port,
+rack = excluded.rack,
+incarnation = excluded.incarnation,
+last_updated = excluded.last_updated;
@@ -14,15 +14,16 @@
 -- You should have received a copy of the GNU Affero General Public License
 -- along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-select b.node, l.host, l.port, b.rack
+select b.node, b.host, b.port, b.rack
 
 from
 
 cluster c
 join broker b on b.cluster = c.id
-join listener l on l.broker = b.id
 
 where
 
 c.name = $1
-and l.name = 'broker';
+and b.last_updated > $2
+
+order by b.node;
@@ -18,4 +18,6 @@ delete from consumer_group_detail
 using cluster c, consumer_group cg
 where c.name = $1
 and cg.name = $2
-and cg.cluster = c.id;
+and cg.cluster = c.id
+and consumer_group_detail.consumer_group = cg.id
+and consumer_group_detail.e_tag = $3;
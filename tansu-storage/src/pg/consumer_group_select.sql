@@ -14,11 +14,12 @@
 -- You should have received a copy of the GNU Affero General Public License
 -- along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-select cg.name
+select cg.name, cgd.detail
 
 from
 
 cluster c
 join consumer_group cg on cg.cluster = c.id
+left join consumer_group_detail cgd on cgd.consumer_group = cg.id
 
 where c.name = $1;
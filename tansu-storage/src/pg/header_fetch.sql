@@ -23,4 +23,5 @@ and r.offset_id = $4
 and t.cluster = c.id
 and tp.topic = t.id
 and r.topition = tp.id
-and h.record = r.id;
+and h.record = r.id
+order by h.id;
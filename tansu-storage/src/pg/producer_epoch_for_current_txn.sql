@@ -20,7 +20,8 @@ select
 
 p.id as producer,
 pe.epoch as epoch,
-txn_d.status as status
+txn_d.status as status,
+txn_d.transaction_timeout_ms as transaction_timeout_ms
 
 from
 
@@ -37,4 +38,6 @@ and txn.name = $2
 
 order by p.id, pe.epoch desc
 
-limit 1;
+limit 1
+
+for update of txn;
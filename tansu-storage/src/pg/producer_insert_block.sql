@@ -0,0 +1,34 @@
+-- -*- mode: sql; sql-product: postgres; -*-
+-- Copyright ⓒ 2024 Peter Morgan <peter.james.morgan@gmail.com>
+--
+-- This program is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU Affero General Public License as
+-- published by the Free Software Foundation, either version 3 of the
+-- License, or (at your option) any later version.
+--
+-- This program is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY; without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU Affero General Public License for more details.
+--
+-- You should have received a copy of the GNU Affero General Public License
+-- along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+with
+
+p as (
+insert into producer (cluster)
+select c.id
+from cluster c, generate_series(1, $2)
+where c.name = $1
+returning id
+),
+
+pe as (
+insert into producer_epoch (producer, epoch)
+select p.id, 0
+from p
+returning producer
+)
+
+select min(pe.producer), max(pe.producer) from pe;
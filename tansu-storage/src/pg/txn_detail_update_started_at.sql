@@ -18,7 +18,7 @@ update txn_detail
 
 set
 
-started_at = current_timestamp,
+started_at = $5,
 status = 'BEGIN'
 
 from
@@ -0,0 +1,40 @@
+-- -*- mode: sql; sql-product: postgres; -*-
+-- Copyright ⓒ 2025 Peter Morgan <peter.james.morgan@gmail.com>
+--
+-- This program is free software: you can redistribute it and/or modify
+-- it under the terms of the GNU Affero General Public License as
+-- published by the Free Software Foundation, either version 3 of the
+-- License, or (at your option) any later version.
+--
+-- This program is distributed in the hope that it will be useful,
+-- but WITHOUT ANY WARRANTY; without even the implied warranty of
+-- MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+-- GNU Affero General Public License for more details.
+--
+-- You should have received a copy of the GNU Affero General Public License
+-- along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+-- prepare txn_select_open_count (text, text) as
+
+-- only the detail of the current producer epoch of each transaction
+-- is counted, an earlier epoch having been fenced
+
+select count(distinct txn.id)
+
+from
+
+cluster c
+join txn on txn.cluster = c.id
+join txn_detail txn_d on txn_d.transaction = txn.id
+join producer_epoch pe on pe.id = txn_d.producer_epoch
+
+where
+
+c.name = $1
+and txn.name <> $2
+and txn_d.status in ('BEGIN', 'PREPARE_COMMIT', 'PREPARE_ABORT')
+and pe.epoch = (
+    select max(current.epoch)
+    from producer_epoch current
+    where current.producer = pe.producer
+);
@@ -38,4 +38,9 @@ where
 c.name = $1
 and txn.name = $2
 and p.id = $3
-and pe.epoch = $4;
+and pe.epoch = $4
+
+order by
+
+t.name,
+tp.partition;
@@ -19,7 +19,7 @@ update txn_detail
 set
 
 status = $5,
-last_updated = current_timestamp
+last_updated = $6
 
 from cluster c, producer p, producer_epoch pe, txn
 